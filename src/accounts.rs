@@ -0,0 +1,164 @@
+//! Provider accounts per workspace and tenant.
+//!
+//! Web providers are signed into through a browser profile, so an account is
+//! a profile directory holding its cookies and sessions. Accounts are mapped
+//! to providers per workspace and per tenant, such as a work ChatGPT account
+//! for one repository and a personal one for another. A call uses the account
+//! mapped for its tenant, else for the server's workspace, else the default
+//! mapping, else the browser's own profile.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use embeddenator_webpuppet::{Config, Provider};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A provider account, signed into in its own browser profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderAccount {
+    /// Directory holding the account's browser profile and sessions.
+    pub profile_dir: PathBuf,
+}
+
+impl ProviderAccount {
+    /// Configuration of the browser context numbered `context`, signed
+    /// into this account. Each context has its own profile and sessions, so
+    /// contexts run side by side and are each signed in separately.
+    pub fn browser_config(&self, context: usize) -> Config {
+        let dir = |name: &str| match context {
+            0 => self.profile_dir.join(name),
+            n => self.profile_dir.join(format!("{}-{}", name, n)),
+        };
+        let mut config = Config::default();
+        config.browser.user_data_dir = Some(dir("browser"));
+        config.session.storage_dir = Some(dir("sessions"));
+        config
+    }
+}
+
+/// Account names keyed by provider name.
+pub type AccountMapping = HashMap<String, String>;
+
+/// Configured accounts and which provider uses which, where.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountPolicy {
+    /// Accounts keyed by name.
+    #[serde(default)]
+    pub accounts: HashMap<String, ProviderAccount>,
+    /// Accounts used when no workspace or tenant mapping applies.
+    #[serde(default)]
+    pub default: AccountMapping,
+    /// Accounts keyed by workspace ID.
+    #[serde(default)]
+    pub workspaces: HashMap<String, AccountMapping>,
+    /// Accounts keyed by tenant name.
+    #[serde(default)]
+    pub tenants: HashMap<String, AccountMapping>,
+}
+
+impl AccountPolicy {
+    /// Check that every mapping names a known provider and a configured
+    /// account.
+    pub fn validate(&self) -> Result<()> {
+        let mappings = std::iter::once(&self.default)
+            .chain(self.workspaces.values())
+            .chain(self.tenants.values());
+        for (provider, account) in mappings.flatten() {
+            if Provider::from_string(provider).is_none() {
+                return Err(Error::Config(format!(
+                    "unknown provider in account mapping: {}",
+                    provider
+                )));
+            }
+            if !self.accounts.contains_key(account) {
+                return Err(Error::Config(format!(
+                    "account mapping for {} names unknown account {}",
+                    provider, account
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Account `provider` uses for `tenant` in `workspace`, with its name.
+    pub fn account_for(
+        &self,
+        provider: Provider,
+        workspace: Option<&str>,
+        tenant: &str,
+    ) -> Option<(&str, &ProviderAccount)> {
+        fn mapped(mapping: &AccountMapping, provider: Provider) -> Option<&str> {
+            mapping
+                .iter()
+                .find(|(name, _)| Provider::from_string(name) == Some(provider))
+                .map(|(_, account)| account.as_str())
+        }
+        let name = self
+            .tenants
+            .get(tenant)
+            .and_then(|mapping| mapped(mapping, provider))
+            .or_else(|| {
+                let mapping = self.workspaces.get(workspace?)?;
+                mapped(mapping, provider)
+            })
+            .or_else(|| mapped(&self.default, provider))?;
+        self.accounts
+            .get_key_value(name)
+            .map(|(name, account)| (name.as_str(), account))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_for_workspace_and_tenant() {
+        let policy: AccountPolicy = serde_json::from_str(
+            r#"{
+                "accounts": {
+                    "work": { "profile_dir": "/profiles/work" },
+                    "personal": { "profile_dir": "/profiles/personal" }
+                },
+                "default": { "chatgpt": "personal" },
+                "workspaces": { "repo-a": { "openai": "work" } },
+                "tenants": { "ci": { "chatgpt": "personal" } }
+            }"#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+
+        let name = |workspace, tenant| {
+            policy
+                .account_for(Provider::ChatGpt, workspace, tenant)
+                .map(|(name, _)| name)
+        };
+        assert_eq!(name(Some("repo-a"), "default"), Some("work"));
+        assert_eq!(name(Some("repo-b"), "default"), Some("personal"));
+        assert_eq!(name(Some("repo-a"), "ci"), Some("personal"));
+        assert_eq!(
+            policy.account_for(Provider::Claude, Some("repo-a"), "default"),
+            None
+        );
+
+        let (_, work) = policy
+            .account_for(Provider::ChatGpt, Some("repo-a"), "default")
+            .unwrap();
+        let config = work.browser_config(0);
+        assert_eq!(
+            config.browser.user_data_dir,
+            Some(PathBuf::from("/profiles/work/browser"))
+        );
+        let config = work.browser_config(2);
+        assert_eq!(
+            config.session.storage_dir,
+            Some(PathBuf::from("/profiles/work/sessions-2"))
+        );
+
+        let mut broken = policy.clone();
+        broken.default.insert("claude".into(), "nobody".into());
+        assert!(matches!(broken.validate(), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,230 @@
+//! Aggregating findings from several workflow steps.
+//!
+//! An `aggregate` step collects the outputs of earlier steps, such as reviews
+//! of different files by different providers, and splits them into separate
+//! findings: list items, or paragraphs when there is no list. Near-identical
+//! findings are merged, keeping every step that reported them, and a provider
+//! then consolidates what is left into a single answer. An `assemble` step
+//! keeps the outputs as they are and joins them into one document instead.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::group_duplicates;
+
+/// Word overlap at which two findings count as the same. Lower than for
+/// whole answers, since findings are short and reworded between providers.
+pub const DUPLICATE_THRESHOLD: f64 = 0.6;
+
+/// Estimated tokens of an aggregation prompt and its answer, used to estimate
+/// an aggregate step's cost before it runs.
+pub const TOKENS_PER_AGGREGATION: u64 = 4_000;
+
+/// One finding and the steps that reported it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    /// Finding text, without its list marker.
+    pub text: String,
+    /// Steps (and providers) that reported it, in order.
+    pub sources: Vec<String>,
+}
+
+/// Findings collected from several outputs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Aggregation {
+    /// Distinct findings, in the order first reported.
+    pub findings: Vec<Finding>,
+    /// Findings dropped as duplicates of another.
+    pub duplicates: usize,
+}
+
+impl Aggregation {
+    /// Split each `(source, output)` into findings and merge duplicates.
+    pub fn collect<'a>(outputs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let all: Vec<(&str, String)> = outputs
+            .into_iter()
+            .flat_map(|(source, text)| {
+                split_findings(text)
+                    .into_iter()
+                    .map(move |finding| (source, finding))
+            })
+            .collect();
+        let texts: Vec<&str> = all.iter().map(|(_, text)| text.as_str()).collect();
+        let groups = group_duplicates(&texts, DUPLICATE_THRESHOLD);
+        let findings: Vec<Finding> = groups
+            .iter()
+            .map(|group| {
+                let mut sources: Vec<String> = Vec::new();
+                for &i in group {
+                    if !sources.iter().any(|s| s == all[i].0) {
+                        sources.push(all[i].0.to_string());
+                    }
+                }
+                Finding {
+                    text: all[group[0]].1.clone(),
+                    sources,
+                }
+            })
+            .collect();
+        Self {
+            duplicates: all.len() - findings.len(),
+            findings,
+        }
+    }
+
+    /// Build the prompt asking a provider to consolidate the findings.
+    pub fn prompt(&self, instructions: &str) -> String {
+        let findings = self
+            .findings
+            .iter()
+            .enumerate()
+            .map(|(i, f)| format!("{}. [{}] {}", i + 1, f.sources.join("; "), f.text))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "{}\n\n\
+             Below are findings collected from several reviewers, each with the \
+             sources that reported it. Merge findings that describe the same issue, \
+             drop any that are wrong or not actionable, and order the rest by \
+             importance. Keep file names and line references.\n\n{}",
+            instructions.trim(),
+            findings
+        )
+    }
+}
+
+/// Join outputs into one Markdown document titled `title`, with a table of
+/// contents linking their second-level headings.
+pub fn assemble(title: &str, outputs: &[&str]) -> String {
+    let mut contents = Vec::new();
+    let mut in_fence = false;
+    for line in outputs.iter().flat_map(|output| output.lines()) {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if let Some(heading) = line.strip_prefix("## ").filter(|_| !in_fence) {
+            contents.push(format!("- [{}](#{})", heading.trim(), anchor(heading)));
+        }
+    }
+    let mut out = format!("# {}\n\n", title.trim());
+    if !contents.is_empty() {
+        out.push_str(&format!("## Contents\n\n{}\n\n", contents.join("\n")));
+    }
+    let body: Vec<&str> = outputs.iter().map(|output| output.trim()).collect();
+    out.push_str(&body.join("\n\n"));
+    out
+}
+
+/// GitHub's link anchor for a heading.
+fn anchor(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Matches a top-level list item marker.
+fn bullet_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:[-*+]|\d+[.)])\s+").unwrap())
+}
+
+/// Check if a line is structure rather than content: a heading, a rule, or
+/// a bold label such as the `**provider**:` lines of a parallel step.
+fn is_structure(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('#')
+        || (line.len() >= 3 && line.chars().all(|c| c == '-' || c == '*' || c == '_'))
+        || (line.starts_with("**") && line.trim_end_matches(':').ends_with("**"))
+}
+
+/// Split an output into findings: one per top-level list item, with its
+/// nested lines, or one per paragraph outside lists. Code blocks stay with
+/// the finding they follow.
+pub fn split_findings(text: &str) -> Vec<String> {
+    let mut findings: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_fence = false;
+    let mut flush = |current: &mut String| {
+        let finding = current.trim();
+        if !finding.is_empty() {
+            findings.push(finding.to_string());
+        }
+        current.clear();
+    };
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if line.trim().is_empty() || is_structure(line) {
+                flush(&mut current);
+                continue;
+            }
+            if let Some(marker) = bullet_re().find(line) {
+                flush(&mut current);
+                current.push_str(&line[marker.end()..]);
+                current.push('\n');
+                continue;
+            }
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    flush(&mut current);
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_findings() {
+        let claude = "**claude**:\n## Issues\n- `parse` panics on empty input.\n- Missing docs on `Config`.\n  Add a summary line.\n\n---\n";
+        let gemini = "The parse function panics on empty input.\n\n1. The retry loop never sleeps.\n```rust\nloop {}\n\nretry();\n```\n";
+        assert_eq!(
+            split_findings(claude),
+            [
+                "`parse` panics on empty input.",
+                "Missing docs on `Config`.\n  Add a summary line."
+            ]
+        );
+        assert_eq!(split_findings(gemini).len(), 2);
+        assert!(split_findings(gemini)[1].ends_with("retry();\n```"));
+
+        let aggregation = Aggregation::collect([
+            ("Review a.rs (claude)", claude),
+            ("Review a.rs (gemini)", gemini),
+            ("Review b.rs (claude)", "- `parse` panics on empty input."),
+        ]);
+        assert_eq!(aggregation.findings.len(), 3);
+        assert_eq!(aggregation.duplicates, 2);
+        assert_eq!(
+            aggregation.findings[0].sources,
+            [
+                "Review a.rs (claude)",
+                "Review a.rs (gemini)",
+                "Review b.rs (claude)"
+            ]
+        );
+        let prompt = aggregation.prompt("Summarize the review.");
+        assert!(prompt.starts_with("Summarize the review."));
+        assert!(prompt.contains("3. [Review a.rs (gemini)] The retry loop never sleeps."));
+
+        let document = assemble(
+            "API Reference",
+            &["## src/lib.rs\n\nEntry point.\n```\n## not a heading\n```", "## Error Types\n"],
+        );
+        assert!(document.starts_with(
+            "# API Reference\n\n## Contents\n\n- [src/lib.rs](#srclibrs)\n- [Error Types](#error-types)\n\n## src/lib.rs"
+        ));
+        assert!(document.ends_with("```\n\n## Error Types"));
+    }
+}
@@ -0,0 +1,330 @@
+//! Cost anomaly detection.
+//!
+//! Estimated spend of every answered request is added to hourly buckets kept
+//! in storage. The current hour and day are compared with a rolling baseline,
+//! the mean spend of the hours and days before them, and an anomaly is
+//! reported once per window when spend climbs well past it, such as when a
+//! runaway loop keeps calling paid providers. Optionally, auto runs are
+//! paused until someone resumes them. Instances sharing a backend share the
+//! history, the alerts, and the pause.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::storage::Records;
+
+/// Key of the spend history in its collection.
+const HISTORY_KEY: &str = "history";
+
+/// Hours of history needed before hourly spend is judged.
+const MIN_HOURLY_HISTORY: i64 = 24;
+
+/// Days of history needed before daily spend is judged.
+const MIN_DAILY_HISTORY: i64 = 3;
+
+fn default_threshold() -> f64 {
+    3.0
+}
+
+fn default_min_spend_usd() -> f64 {
+    0.5
+}
+
+fn default_baseline_hours() -> i64 {
+    7 * 24
+}
+
+fn default_baseline_days() -> i64 {
+    14
+}
+
+/// When spend counts as anomalous, and what to do about it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyPolicy {
+    /// Spend above this multiple of the baseline is anomalous.
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+    /// Spend in a window below this, in USD, is never anomalous.
+    #[serde(default = "default_min_spend_usd")]
+    pub min_spend_usd: f64,
+    /// Hours the hourly baseline is averaged over.
+    #[serde(default = "default_baseline_hours")]
+    pub baseline_hours: i64,
+    /// Days the daily baseline is averaged over.
+    #[serde(default = "default_baseline_days")]
+    pub baseline_days: i64,
+    /// Pause auto runs, including background jobs, until resumed.
+    #[serde(default)]
+    pub pause_auto_runs: bool,
+}
+
+impl Default for AnomalyPolicy {
+    fn default() -> Self {
+        Self {
+            threshold: default_threshold(),
+            min_spend_usd: default_min_spend_usd(),
+            baseline_hours: default_baseline_hours(),
+            baseline_days: default_baseline_days(),
+            pause_auto_runs: false,
+        }
+    }
+}
+
+/// Length of a window spend is judged over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpendWindow {
+    /// A clock hour, UTC.
+    Hour,
+    /// A day, UTC.
+    Day,
+}
+
+impl SpendWindow {
+    /// Name of the window.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+
+    fn length(self) -> Duration {
+        match self {
+            Self::Hour => Duration::hours(1),
+            Self::Day => Duration::days(1),
+        }
+    }
+}
+
+/// Spend that climbed well past its baseline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpendAnomaly {
+    /// Window the spend was judged over.
+    pub window: SpendWindow,
+    /// Start of the window.
+    pub start: DateTime<Utc>,
+    /// Estimated spend in the window so far, in USD.
+    pub spent_usd: f64,
+    /// Mean spend of earlier windows, in USD.
+    pub baseline_usd: f64,
+}
+
+impl SpendAnomaly {
+    /// One-line description.
+    pub fn describe(&self) -> String {
+        format!(
+            "spent ${:.4} this {} against a baseline of ${:.4}",
+            self.spent_usd,
+            self.window.name(),
+            self.baseline_usd
+        )
+    }
+}
+
+/// Spend history and alert state.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SpendHistory {
+    /// Estimated spend per clock hour, keyed by the hour's start.
+    hours: BTreeMap<DateTime<Utc>, f64>,
+    /// Start of the first hour with recorded spend, kept after pruning.
+    since: Option<DateTime<Utc>>,
+    /// Windows already alerted on, by window name.
+    alerted: BTreeMap<String, DateTime<Utc>>,
+    /// The anomaly auto runs are paused for.
+    paused: Option<SpendAnomaly>,
+}
+
+impl SpendHistory {
+    fn spent(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        self.hours.range(from..to).map(|(_, usd)| usd).sum()
+    }
+
+    /// Spend in the window containing `now`, and the mean spend of up to
+    /// `count` whole windows before it, if there is enough history.
+    fn baseline(
+        &self,
+        window: SpendWindow,
+        count: i64,
+        min_history: i64,
+        now: DateTime<Utc>,
+    ) -> (DateTime<Utc>, f64, Option<f64>) {
+        let start = window_start(window, now);
+        let spent = self.spent(start, start + window.length());
+        let since = self.since.map(|since| window_start(window, since));
+        let available = since.map_or(0, |since| {
+            ((start - since).num_seconds() / window.length().num_seconds()).min(count)
+        });
+        let baseline = (available >= min_history.min(count).max(1)).then(|| {
+            self.spent(start - window.length() * available as i32, start) / available as f64
+        });
+        (start, spent, baseline)
+    }
+}
+
+fn window_start(window: SpendWindow, time: DateTime<Utc>) -> DateTime<Utc> {
+    time.duration_trunc(window.length()).unwrap_or(time)
+}
+
+/// Spend history shared through storage, checked against a policy.
+#[derive(Clone)]
+pub struct SpendMonitor {
+    records: Records,
+    policy: AnomalyPolicy,
+}
+
+impl SpendMonitor {
+    /// Keep spend history in `records`, judged by `policy`.
+    pub fn open(records: Records, policy: AnomalyPolicy) -> Self {
+        Self { records, policy }
+    }
+
+    /// The policy spend is judged by.
+    pub fn policy(&self) -> &AnomalyPolicy {
+        &self.policy
+    }
+
+    /// Windows judged, with the windows their baseline is averaged over and
+    /// the history they need.
+    fn windows(&self) -> [(SpendWindow, i64, i64); 2] {
+        [
+            (
+                SpendWindow::Hour,
+                self.policy.baseline_hours,
+                MIN_HOURLY_HISTORY,
+            ),
+            (
+                SpendWindow::Day,
+                self.policy.baseline_days,
+                MIN_DAILY_HISTORY,
+            ),
+        ]
+    }
+
+    /// Add `cost_usd` spent at `now`, returning an anomaly the spend caused
+    /// that was not reported before. Auto runs are paused on it if the
+    /// policy says so.
+    pub fn record(&self, cost_usd: f64, now: DateTime<Utc>) -> Result<Option<SpendAnomaly>> {
+        let policy = &self.policy;
+        let keep_from = now
+            - Duration::hours(policy.baseline_hours.max(policy.baseline_days * 24))
+            - Duration::days(1);
+        let mut anomaly = None;
+        self.records
+            .update(HISTORY_KEY, |history: Option<SpendHistory>| {
+                let mut history = history.unwrap_or_default();
+                let hour = window_start(SpendWindow::Hour, now);
+                *history.hours.entry(hour).or_default() += cost_usd;
+                history.since.get_or_insert(hour);
+                history.hours = history.hours.split_off(&keep_from);
+
+                anomaly = self
+                    .windows()
+                    .into_iter()
+                    .find_map(|(window, count, min_history)| {
+                        let (start, spent_usd, baseline_usd) =
+                            history.baseline(window, count, min_history, now);
+                        let baseline_usd = baseline_usd?;
+                        let anomalous = spent_usd >= policy.min_spend_usd
+                            && spent_usd > baseline_usd * policy.threshold;
+                        let alerted = history.alerted.get(window.name()) == Some(&start);
+                        (anomalous && !alerted).then_some(SpendAnomaly {
+                            window,
+                            start,
+                            spent_usd,
+                            baseline_usd,
+                        })
+                    });
+                if let Some(anomaly) = &anomaly {
+                    history
+                        .alerted
+                        .insert(anomaly.window.name().into(), anomaly.start);
+                    if policy.pause_auto_runs && history.paused.is_none() {
+                        history.paused = Some(anomaly.clone());
+                    }
+                }
+                history
+            })?;
+        Ok(anomaly)
+    }
+
+    /// The anomaly auto runs are paused for, if they are.
+    pub fn paused(&self) -> Result<Option<SpendAnomaly>> {
+        Ok(self
+            .records
+            .load::<SpendHistory>(HISTORY_KEY)?
+            .and_then(|history| history.paused))
+    }
+
+    /// Let auto runs continue after an anomaly paused them. Returns the
+    /// anomaly they were paused for, if they were.
+    pub fn resume(&self) -> Result<Option<SpendAnomaly>> {
+        let mut resumed = None;
+        self.records
+            .update(HISTORY_KEY, |history: Option<SpendHistory>| {
+                let mut history = history.unwrap_or_default();
+                resumed = history.paused.take();
+                history
+            })?;
+        Ok(resumed)
+    }
+
+    /// Spend in the current hour and day at `now`, with their baselines
+    /// (`None` until there is enough history).
+    pub fn report(&self, now: DateTime<Utc>) -> Result<Vec<(SpendWindow, f64, Option<f64>)>> {
+        let history = self
+            .records
+            .load::<SpendHistory>(HISTORY_KEY)?
+            .unwrap_or_default();
+        Ok(self
+            .windows()
+            .into_iter()
+            .map(|(window, count, min_history)| {
+                let (_, spent, baseline) = history.baseline(window, count, min_history, now);
+                (window, spent, baseline)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStorage, SPEND};
+    use chrono::TimeZone;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_spend_anomaly_against_baseline() {
+        let records = Records::new(Arc::new(MemoryStorage::new()), SPEND, None);
+        let policy = AnomalyPolicy {
+            pause_auto_runs: true,
+            ..AnomalyPolicy::default()
+        };
+        let monitor = SpendMonitor::open(records, policy);
+        let start = Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+
+        // Two days of steady spend, then a runaway hour
+        for hour in 0..48 {
+            let at = start + Duration::hours(hour) + Duration::minutes(10);
+            assert_eq!(monitor.record(0.2, at).unwrap(), None);
+        }
+        let now = start + Duration::hours(48) + Duration::minutes(5);
+        assert_eq!(monitor.record(0.5, now).unwrap(), None);
+        let anomaly = monitor.record(0.2, now).unwrap().unwrap();
+        assert_eq!(anomaly.window, SpendWindow::Hour);
+        assert!((anomaly.spent_usd - 0.7).abs() < 1e-9);
+        assert!((anomaly.baseline_usd - 0.2).abs() < 1e-9);
+        assert_eq!(monitor.record(1.0, now).unwrap(), None);
+        assert_eq!(monitor.paused().unwrap(), Some(anomaly.clone()));
+
+        let report = monitor.report(now).unwrap();
+        assert_eq!(report[1].0, SpendWindow::Day);
+        assert_eq!(report[1].2, None);
+        assert_eq!(monitor.resume().unwrap(), Some(anomaly));
+        assert_eq!(monitor.paused().unwrap(), None);
+    }
+}
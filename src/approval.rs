@@ -0,0 +1,263 @@
+//! Policy-driven approval of workflow steps.
+//!
+//! Each step is assessed for risk (estimated cost, file writes, data
+//! classification) before it runs. The first matching rule decides whether the
+//! step runs automatically or pauses the workflow for human approval.
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::aggregate::TOKENS_PER_AGGREGATION;
+use crate::cost::{estimate_tokens, CostModel};
+use crate::factcheck::{DEFAULT_MAX_CLAIMS, TOKENS_PER_CLAIM};
+use crate::patch::{DEFAULT_MAX_REFINEMENTS, TOKENS_PER_REFINEMENT};
+use crate::records::TOKENS_PER_EXTRACTION;
+use crate::research::DEFAULT_MAX_ROUNDS;
+use crate::security::DataClassification;
+use crate::workflow::{StepConfig, StepType, WorkflowStep};
+
+/// Outcome of evaluating a step against the approval policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// Run the step without asking.
+    #[default]
+    AutoApprove,
+    /// Pause the workflow until a human approves the step.
+    RequireApproval,
+}
+
+/// Risk profile of a workflow step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepRisk {
+    /// Type of the step.
+    pub step_type: StepType,
+    /// Estimated cost of the step's prompts in USD.
+    pub estimated_cost_usd: f64,
+    /// Whether the step writes files.
+    pub writes_files: bool,
+    /// Effective data classification.
+    pub classification: DataClassification,
+}
+
+impl StepRisk {
+    /// Assess the risk of a step.
+    pub fn assess(
+        step: &WorkflowStep,
+        classification: DataClassification,
+        costs: &CostModel,
+        policy: &ApprovalPolicy,
+    ) -> Self {
+        let estimated_cost_usd = match &step.config {
+            StepConfig::Prompt {
+                message,
+                provider,
+                context,
+            } => {
+                let tokens =
+                    estimate_tokens(message) + context.as_deref().map_or(0, estimate_tokens);
+                costs.estimate(provider.as_deref(), tokens)
+            }
+            StepConfig::ParallelPrompt { message, providers } => {
+                let tokens = estimate_tokens(message);
+                providers
+                    .iter()
+                    .map(|p| costs.estimate(Some(p), tokens))
+                    .sum()
+            }
+            StepConfig::Consensus {
+                message,
+                min_providers,
+                ..
+            } => costs.estimate(None, estimate_tokens(message)) * (*min_providers).max(3) as f64,
+            StepConfig::Research {
+                question,
+                providers,
+                max_rounds,
+                max_cost_usd,
+                ..
+            } => max_cost_usd.unwrap_or_else(|| {
+                let per_round = if providers.is_empty() {
+                    costs.estimate(None, estimate_tokens(question))
+                        * Provider::search_providers().len() as f64
+                } else {
+                    providers
+                        .iter()
+                        .map(|p| costs.estimate(Some(p), estimate_tokens(question)))
+                        .sum()
+                };
+                per_round * max_rounds.unwrap_or(DEFAULT_MAX_ROUNDS) as f64
+            }),
+            StepConfig::FactCheck {
+                provider,
+                max_claims,
+                ..
+            } => costs.estimate(
+                provider.as_deref(),
+                max_claims.unwrap_or(DEFAULT_MAX_CLAIMS) as u64 * TOKENS_PER_CLAIM,
+            ),
+            StepConfig::ValidatePatch {
+                provider,
+                max_refinements,
+                ..
+            } => costs.estimate(
+                provider.as_deref(),
+                max_refinements.unwrap_or(DEFAULT_MAX_REFINEMENTS) as u64 * TOKENS_PER_REFINEMENT,
+            ),
+            StepConfig::Map {
+                message,
+                items,
+                provider,
+                checker,
+                ..
+            } => items
+                .iter()
+                .map(|item| {
+                    let tokens = estimate_tokens(message) + estimate_tokens(&item.text);
+                    // A cross-check reads the prompt and the answer.
+                    costs.estimate(provider.as_deref(), tokens)
+                        + checker
+                            .as_deref()
+                            .map_or(0.0, |c| costs.estimate(Some(c), tokens * 2))
+                })
+                .sum(),
+            StepConfig::Aggregate {
+                message, provider, ..
+            } => costs.estimate(
+                provider.as_deref(),
+                estimate_tokens(message) + TOKENS_PER_AGGREGATION,
+            ),
+            StepConfig::Extract {
+                message, providers, ..
+            } => {
+                let tokens = estimate_tokens(message) + TOKENS_PER_EXTRACTION;
+                if providers.is_empty() {
+                    costs.estimate(None, tokens)
+                } else {
+                    providers.iter().map(|p| costs.estimate(Some(p), tokens)).sum()
+                }
+            }
+            _ => 0.0,
+        };
+
+        let writes_files = matches!(
+            &step.config,
+            StepConfig::Tool { tool_name, .. } if policy.write_tools.contains(tool_name)
+        );
+
+        Self {
+            step_type: step.step_type.clone(),
+            estimated_cost_usd,
+            writes_files,
+            classification,
+        }
+    }
+}
+
+/// A single approval rule. All set conditions must hold for the rule to match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRule {
+    /// Rule name, reported when the rule decides.
+    pub name: String,
+    /// Matches only steps estimated to cost at most this much.
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    /// Whether steps that write files can match.
+    #[serde(default)]
+    pub allow_file_writes: bool,
+    /// Matches only these step types (empty matches all).
+    #[serde(default)]
+    pub step_types: Vec<StepType>,
+    /// Matches only steps at or below this classification.
+    #[serde(default)]
+    pub max_classification: Option<DataClassification>,
+    /// Decision when the rule matches.
+    pub decision: ApprovalDecision,
+}
+
+impl ApprovalRule {
+    /// Check if the rule applies to a step.
+    pub fn matches(&self, risk: &StepRisk) -> bool {
+        self.max_cost_usd
+            .is_none_or(|max| risk.estimated_cost_usd <= max)
+            && (self.allow_file_writes || !risk.writes_files)
+            && (self.step_types.is_empty() || self.step_types.contains(&risk.step_type))
+            && self
+                .max_classification
+                .is_none_or(|max| risk.classification <= max)
+    }
+}
+
+/// Ordered approval rules with a fallback decision.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    /// Rules, evaluated in order; the first match decides.
+    #[serde(default)]
+    pub rules: Vec<ApprovalRule>,
+    /// Decision when no rule matches.
+    #[serde(default)]
+    pub default_decision: ApprovalDecision,
+    /// Tools treated as writing files when invoked from a tool step.
+    #[serde(default)]
+    pub write_tools: Vec<String>,
+}
+
+impl ApprovalPolicy {
+    /// Decide whether a step needs approval, with the reason for the decision.
+    pub fn evaluate(&self, risk: &StepRisk) -> (ApprovalDecision, String) {
+        match self.rules.iter().find(|r| r.matches(risk)) {
+            Some(rule) => (rule.decision, format!("rule '{}'", rule.name)),
+            None => (self.default_decision, "default decision".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ApprovalPolicy {
+        ApprovalPolicy {
+            rules: vec![ApprovalRule {
+                name: "cheap-readonly".into(),
+                max_cost_usd: Some(0.05),
+                allow_file_writes: false,
+                step_types: Vec::new(),
+                max_classification: None,
+                decision: ApprovalDecision::AutoApprove,
+            }],
+            default_decision: ApprovalDecision::RequireApproval,
+            write_tools: vec!["write_file".into()],
+        }
+    }
+
+    #[test]
+    fn test_cheap_step_auto_approved() {
+        let policy = policy();
+        let costs = CostModel::default();
+        let step = WorkflowStep::prompt("ask", "Hello");
+
+        let risk = StepRisk::assess(&step, DataClassification::Public, &costs, &policy);
+        assert_eq!(policy.evaluate(&risk).0, ApprovalDecision::AutoApprove);
+    }
+
+    #[test]
+    fn test_expensive_or_writing_step_requires_approval() {
+        let policy = policy();
+        let costs = CostModel::default().with_rate("chatgpt", 10.0);
+
+        let step = WorkflowStep::prompt("ask", "x".repeat(4000)).with_provider("chatgpt");
+        let risk = StepRisk::assess(&step, DataClassification::Public, &costs, &policy);
+        assert_eq!(policy.evaluate(&risk).0, ApprovalDecision::RequireApproval);
+
+        let mut step = WorkflowStep::prompt("write", "");
+        step.step_type = StepType::Tool;
+        step.config = StepConfig::Tool {
+            tool_name: "write_file".into(),
+            arguments: serde_json::json!({}),
+        };
+        let risk = StepRisk::assess(&step, DataClassification::Public, &costs, &policy);
+        assert!(risk.writes_files);
+        assert_eq!(policy.evaluate(&risk).0, ApprovalDecision::RequireApproval);
+    }
+}
@@ -0,0 +1,373 @@
+//! Tamper-evident audit log of provider dispatches.
+//!
+//! Each entry is a JSON line whose `hash` covers the entry contents and the
+//! previous entry's hash, so editing, removing, or reordering any entry breaks
+//! the chain. When a signing key is configured, each hash is additionally
+//! signed with HMAC-SHA256.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::security::DataClassification;
+
+/// Environment variable holding the audit signing key.
+pub const SIGNING_KEY_ENV_VAR: &str = "AGENT_MCP_AUDIT_KEY";
+
+/// Hash used as `prev_hash` for the first entry.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An auditable event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Event kind (e.g. `prompt_dispatch`).
+    pub kind: String,
+    /// Provider involved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Data classification of the payload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classification: Option<DataClassification>,
+    /// SHA-256 of the prompt text (the text itself is never logged).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_sha256: Option<String>,
+    /// Workflow the event belongs to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workflow_id: Option<String>,
+}
+
+impl AuditEvent {
+    /// Create an event recording a prompt sent to a provider.
+    pub fn prompt_dispatch(
+        provider: impl Into<String>,
+        classification: DataClassification,
+        prompt: &str,
+    ) -> Self {
+        Self {
+            kind: "prompt_dispatch".into(),
+            provider: Some(provider.into()),
+            classification: Some(classification),
+            prompt_sha256: Some(sha256_hex(prompt.as_bytes())),
+            workflow_id: None,
+        }
+    }
+}
+
+/// A single chained entry in the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Sequence number, starting at 1.
+    pub seq: u64,
+    /// When the entry was recorded.
+    pub timestamp: DateTime<Utc>,
+    /// The recorded event.
+    pub event: AuditEvent,
+    /// Hash of the previous entry.
+    pub prev_hash: String,
+    /// Hash of this entry.
+    pub hash: String,
+    /// HMAC-SHA256 signature of `hash` (if signing is enabled).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl AuditEntry {
+    /// Compute the chained hash for the entry contents.
+    fn compute_hash(&self) -> Result<String> {
+        let body = serde_json::to_string(&(&self.seq, &self.timestamp, &self.event))?;
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(body.as_bytes());
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// Append-only, hash-chained audit log backed by a JSONL file.
+pub struct AuditLog {
+    path: PathBuf,
+    signing_key: Option<Vec<u8>>,
+    state: Mutex<ChainState>,
+}
+
+struct ChainState {
+    file: File,
+    seq: u64,
+    last_hash: String,
+    last_signature: Option<String>,
+}
+
+impl AuditLog {
+    /// Open (or create) an audit log, resuming the existing chain.
+    ///
+    /// Fails if the last entry does not match its hash or does not link to
+    /// the entry before it, so new entries are never chained onto a tampered
+    /// tail.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (seq, last_hash, last_signature) = match read_entries(&path) {
+            Ok(entries) => match entries.last() {
+                Some(last) => {
+                    let prev_hash = match entries.len() {
+                        1 => GENESIS_HASH,
+                        n => entries[n - 2].hash.as_str(),
+                    };
+                    if last.compute_hash()? != last.hash || last.prev_hash != prev_hash {
+                        return Err(Error::Internal(format!(
+                            "audit log broken at entry {}: hash chain mismatch",
+                            last.seq
+                        )));
+                    }
+                    (last.seq, last.hash.clone(), last.signature.clone())
+                }
+                None => (0, GENESIS_HASH.into(), None),
+            },
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                (0, GENESIS_HASH.into(), None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            path,
+            signing_key: None,
+            state: Mutex::new(ChainState {
+                file,
+                seq,
+                last_hash,
+                last_signature,
+            }),
+        })
+    }
+
+    /// Sign entries with an HMAC key.
+    ///
+    /// Fails if the log already has entries and the last one is not signed
+    /// with `key`, since a forged tail can carry a recomputed hash.
+    pub fn with_signing_key(mut self, key: impl Into<Vec<u8>>) -> Result<Self> {
+        let key = key.into();
+        {
+            let state = self
+                .state
+                .get_mut()
+                .map_err(|_| Error::Internal("audit log lock poisoned".into()))?;
+            if state.seq > 0
+                && state.last_signature.as_deref() != Some(sign(&key, &state.last_hash).as_str())
+            {
+                return Err(Error::Internal(format!(
+                    "audit log broken at entry {}: invalid signature",
+                    state.seq
+                )));
+            }
+        }
+        self.signing_key = Some(key);
+        Ok(self)
+    }
+
+    /// Get the log file path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an event to the log.
+    pub fn record(&self, event: AuditEvent) -> Result<AuditEntry> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| Error::Internal("audit log lock poisoned".into()))?;
+
+        let mut entry = AuditEntry {
+            seq: state.seq + 1,
+            timestamp: Utc::now(),
+            event,
+            prev_hash: state.last_hash.clone(),
+            hash: String::new(),
+            signature: None,
+        };
+        entry.hash = entry.compute_hash()?;
+        entry.signature = self
+            .signing_key
+            .as_deref()
+            .map(|key| sign(key, &entry.hash));
+
+        writeln!(state.file, "{}", serde_json::to_string(&entry)?)?;
+        state.file.flush()?;
+
+        state.seq = entry.seq;
+        state.last_hash = entry.hash.clone();
+        state.last_signature = entry.signature.clone();
+        Ok(entry)
+    }
+
+    /// Verify the chain (and signatures, if a key is given) of a log file.
+    ///
+    /// Returns the number of verified entries.
+    pub fn verify(path: impl AsRef<Path>, signing_key: Option<&[u8]>) -> Result<u64> {
+        let entries = read_entries(path.as_ref())?;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        for (i, entry) in entries.iter().enumerate() {
+            let expected_seq = i as u64 + 1;
+            let broken = |reason: &str| {
+                Error::Internal(format!(
+                    "audit log broken at entry {}: {}",
+                    expected_seq, reason
+                ))
+            };
+
+            if entry.seq != expected_seq {
+                return Err(broken("unexpected sequence number"));
+            }
+            if entry.prev_hash != prev_hash {
+                return Err(broken("previous hash mismatch"));
+            }
+            if entry.compute_hash()? != entry.hash {
+                return Err(broken("content hash mismatch"));
+            }
+            if let Some(key) = signing_key {
+                if entry.signature.as_deref() != Some(sign(key, &entry.hash).as_str()) {
+                    return Err(broken("invalid signature"));
+                }
+            }
+
+            prev_hash = entry.hash.clone();
+        }
+
+        Ok(entries.len() as u64)
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("path", &self.path)
+            .field("signed", &self.signing_key.is_some())
+            .finish()
+    }
+}
+
+/// Read all entries from a log file.
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
+
+/// Hex-encoded SHA-256 digest.
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Hex-encoded HMAC-SHA256 signature.
+pub(crate) fn sign(key: &[u8], message: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "agent-mcp-audit-{}-{}.jsonl",
+            name,
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    #[test]
+    fn test_chain_verifies_and_resumes() {
+        let path = temp_path("chain");
+        let log = AuditLog::open(&path).unwrap().with_signing_key("secret").unwrap();
+        log.record(AuditEvent::prompt_dispatch(
+            "claude",
+            DataClassification::Public,
+            "hi",
+        ))
+        .unwrap();
+        drop(log);
+
+        // Reopening continues the chain
+        let log = AuditLog::open(&path).unwrap().with_signing_key("secret").unwrap();
+        let entry = log
+            .record(AuditEvent::prompt_dispatch(
+                "grok",
+                DataClassification::Internal,
+                "yo",
+            ))
+            .unwrap();
+        assert_eq!(entry.seq, 2);
+
+        assert_eq!(AuditLog::verify(&path, Some(b"secret")).unwrap(), 2);
+        assert!(AuditLog::verify(&path, Some(b"wrong")).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let path = temp_path("tamper");
+        let log = AuditLog::open(&path).unwrap();
+        log.record(AuditEvent::prompt_dispatch(
+            "claude",
+            DataClassification::Public,
+            "a",
+        ))
+        .unwrap();
+        log.record(AuditEvent::prompt_dispatch(
+            "gemini",
+            DataClassification::Public,
+            "b",
+        ))
+        .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.replace("gemini", "claude")).unwrap();
+
+        assert!(AuditLog::verify(&path, None).is_err());
+        // The tampered entry is the tail, so the chain is not resumed
+        assert!(AuditLog::open(&path).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_forged_signed_tail_not_resumed() {
+        let path = temp_path("forged");
+        let log = AuditLog::open(&path).unwrap().with_signing_key("secret").unwrap();
+        log.record(AuditEvent::prompt_dispatch(
+            "claude",
+            DataClassification::Public,
+            "a",
+        ))
+        .unwrap();
+        drop(log);
+
+        // Rewrite the tail with a consistent hash but no valid signature
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let mut entry: AuditEntry = serde_json::from_str(contents.trim()).unwrap();
+        entry.event.provider = Some("gemini".into());
+        entry.hash = entry.compute_hash().unwrap();
+        std::fs::write(&path, format!("{}\n", serde_json::to_string(&entry).unwrap())).unwrap();
+
+        let log = AuditLog::open(&path).unwrap();
+        assert!(log.with_signing_key("secret").is_err());
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,154 @@
+//! Plan-and-execute runs: goal in, synthesized answer out.
+//!
+//! A run plans the goal into a workflow, executes the steps one by one under
+//! the approval policy and an optional cost budget, then asks a provider to
+//! combine the step outputs into a final answer. The goal and the answer are
+//! kept in the workflow's context, so a run that pauses (or outlives the tool
+//! call that started it) can be inspected and resumed like any workflow.
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::cost::CostModel;
+use crate::plan::{WorkflowDef, DEFAULT_MAX_STEPS};
+use crate::workflow::{StepState, Workflow};
+
+/// Workflow context key holding the goal of an auto run.
+pub const GOAL_KEY: &str = "goal";
+/// Workflow context key holding the plan of an auto run.
+pub const PLAN_KEY: &str = "plan";
+/// Workflow context key holding the final synthesis of an auto run.
+pub const SYNTHESIS_KEY: &str = "synthesis";
+
+/// Limits and providers for an auto run.
+#[derive(Debug, Clone)]
+pub struct AutoOptions {
+    /// Maximum number of planned steps.
+    pub max_steps: usize,
+    /// Stop before a step that would take the run's estimated cost past this.
+    pub max_cost_usd: Option<f64>,
+    /// Provider that plans (defaults to the best available).
+    pub planner: Option<Provider>,
+    /// Provider that writes the synthesis (defaults to the best available).
+    pub synthesizer: Option<Provider>,
+}
+
+impl Default for AutoOptions {
+    fn default() -> Self {
+        Self {
+            max_steps: DEFAULT_MAX_STEPS,
+            max_cost_usd: None,
+            planner: None,
+            synthesizer: None,
+        }
+    }
+}
+
+/// How an auto run ended.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AutoOutcome {
+    /// Every step ran and the synthesis was written.
+    Completed,
+    /// Stopped at a step awaiting human approval or review.
+    AwaitingApproval {
+        /// Why the step is waiting.
+        reason: String,
+    },
+    /// Stopped before a step that would exceed the budget.
+    OverBudget {
+        /// Estimated cost of the step that was not run.
+        next_step_usd: f64,
+    },
+    /// A step failed.
+    Failed {
+        /// The error.
+        reason: String,
+    },
+    /// Stopped before a step because auto runs are paused, by maintenance
+    /// mode or after a cost anomaly.
+    Paused {
+        /// Why auto runs are paused.
+        reason: String,
+    },
+}
+
+/// Result of an auto run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoReport {
+    /// ID of the workflow the run executes.
+    pub workflow_id: String,
+    /// The planned workflow.
+    pub plan: WorkflowDef,
+    /// How the run ended.
+    pub outcome: AutoOutcome,
+    /// Final answer, once every step has run.
+    pub synthesis: Option<String>,
+    /// Number of completed steps.
+    pub steps_completed: usize,
+    /// Estimated cost of the run so far in USD.
+    pub spent_usd: f64,
+}
+
+/// Estimated cost of a workflow's completed steps in USD.
+pub fn spent_usd(workflow: &Workflow, costs: &CostModel) -> f64 {
+    workflow
+        .steps
+        .iter()
+        .filter_map(|s| s.result.as_ref())
+        .map(|r| costs.estimate_step(r))
+        .sum()
+}
+
+/// Build the prompt asking for the final answer from the step outputs.
+pub fn synthesis_prompt(goal: &str, workflow: &Workflow) -> String {
+    let outputs: Vec<String> = workflow
+        .steps
+        .iter()
+        .filter(|s| s.state == StepState::Completed)
+        .filter_map(|s| {
+            s.result
+                .as_ref()
+                .map(|r| format!("## {}\n\n{}", s.name, r.output))
+        })
+        .collect();
+    format!(
+        "The goal below was worked on in several steps. Using the step results, \
+         write the final deliverable for the goal. Resolve conflicts between steps, \
+         and do not describe the process.\n\nGoal:\n{}\n\nStep results:\n\n{}",
+        goal,
+        outputs.join("\n\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::metadata::RESPONSE_TOKENS_KEY;
+    use crate::workflow::{StepResult, WorkflowStep};
+
+    #[test]
+    fn test_synthesis_uses_completed_steps() {
+        let mut workflow = Workflow::new("Launch");
+        let mut done = WorkflowStep::prompt("Research", "Find facts").with_provider("chatgpt");
+        done.complete(StepResult {
+            output: "Fact one.".into(),
+            provider: Some("chatgpt".into()),
+            responses: None,
+            duration_ms: 10,
+            metadata: HashMap::from([(RESPONSE_TOKENS_KEY.to_string(), 2000.into())]),
+        });
+        workflow.add_step(done);
+        workflow.add_step(WorkflowStep::prompt("Draft", "Write it"));
+
+        let costs = CostModel::default().with_rate("chatgpt", 0.01);
+        assert!((spent_usd(&workflow, &costs) - 0.02).abs() < 1e-9);
+
+        let prompt = synthesis_prompt("Announce the CLI", &workflow);
+        assert!(prompt.contains("Announce the CLI"));
+        assert!(prompt.contains("## Research\n\nFact one."));
+        assert!(!prompt.contains("## Draft"));
+    }
+}
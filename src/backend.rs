@@ -0,0 +1,214 @@
+//! Provider backends.
+//!
+//! A backend is how prompts reach a provider: a browser driving its web app
+//! through webpuppet, its HTTP API, or a stand-in in tests. The orchestrator
+//! talks to each through [`ProviderBackend`]. Providers are reached through
+//! the browser unless a backend is registered for them, from configuration
+//! (`--api-providers`) or at runtime with
+//! `AgentOrchestrator::register_backend`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use embeddenator_webpuppet::{PromptRequest, PromptResponse, Provider, WebPuppet};
+use serde::Serialize;
+
+use crate::cost::CostModel;
+use crate::error::Result;
+use crate::metadata::prompt_tokens;
+
+/// What a backend supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BackendCapabilities {
+    /// Kind of backend, such as `browser` or `anthropic`.
+    pub kind: &'static str,
+    /// Drives a browser, with its startup, sign-in, and captchas.
+    pub browser: bool,
+    /// Takes a system prompt separately from the message.
+    pub system_prompt: bool,
+    /// Continues a truncated answer itself, given the answer so far.
+    pub continuation: bool,
+}
+
+/// A way of sending prompts to providers.
+#[async_trait::async_trait]
+pub trait ProviderBackend: Send + Sync {
+    /// Sign in to `provider`, if the backend needs to.
+    async fn authenticate(&self, provider: Provider) -> Result<()>;
+
+    /// Send a prompt to `provider` and wait for the whole answer.
+    async fn prompt(&self, provider: Provider, request: PromptRequest) -> Result<PromptResponse>;
+
+    /// What the backend supports.
+    fn capabilities(&self) -> BackendCapabilities;
+
+    /// Estimated cost in USD of sending `request` to `provider`, priced by
+    /// `costs`.
+    fn cost_estimate(&self, provider: Provider, request: &PromptRequest, costs: &CostModel) -> f64 {
+        estimate_cost(provider, request, costs)
+    }
+}
+
+/// Estimated cost in USD of sending `request` to `provider`, at the
+/// provider's rate.
+pub fn estimate_cost(provider: Provider, request: &PromptRequest, costs: &CostModel) -> f64 {
+    costs.estimate(Some(&provider.to_string()), prompt_tokens(request))
+}
+
+#[async_trait::async_trait]
+impl ProviderBackend for WebPuppet {
+    async fn authenticate(&self, provider: Provider) -> Result<()> {
+        WebPuppet::authenticate(self, provider).await?;
+        Ok(())
+    }
+
+    async fn prompt(&self, provider: Provider, request: PromptRequest) -> Result<PromptResponse> {
+        Ok(WebPuppet::prompt(self, provider, request).await?)
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            kind: "browser",
+            browser: true,
+            system_prompt: false,
+            continuation: false,
+        }
+    }
+}
+
+type Backends = HashMap<Provider, Arc<dyn ProviderBackend>>;
+
+/// Backends registered for providers, shared by every orchestrator handle.
+#[derive(Clone, Default)]
+pub struct BackendRegistry {
+    backends: Arc<RwLock<Backends>>,
+}
+
+impl BackendRegistry {
+    /// Answer `provider` through `backend`, replacing any backend registered
+    /// for it before, which is returned.
+    pub fn register(
+        &self,
+        provider: Provider,
+        backend: Arc<dyn ProviderBackend>,
+    ) -> Option<Arc<dyn ProviderBackend>> {
+        self.write().insert(provider, backend)
+    }
+
+    /// Go back to reaching `provider` through the browser, returning the
+    /// backend registered for it.
+    pub fn unregister(&self, provider: Provider) -> Option<Arc<dyn ProviderBackend>> {
+        self.write().remove(&provider)
+    }
+
+    /// The backend registered for `provider`, if any.
+    pub fn get(&self, provider: Provider) -> Option<Arc<dyn ProviderBackend>> {
+        self.read().get(&provider).cloned()
+    }
+
+    /// Providers whose backend needs no browser.
+    pub fn without_browser(&self) -> Vec<Provider> {
+        self.read()
+            .iter()
+            .filter(|(_, backend)| !backend.capabilities().browser)
+            .map(|(provider, _)| *provider)
+            .collect()
+    }
+
+    /// Capabilities of every registered backend, keyed by provider.
+    pub fn capabilities(&self) -> HashMap<Provider, BackendCapabilities> {
+        self.read()
+            .iter()
+            .map(|(provider, backend)| (*provider, backend.capabilities()))
+            .collect()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Backends> {
+        self.backends.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Backends> {
+        self.backends.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+    use crate::storage::MemoryStorage;
+    use chrono::Utc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct EchoBackend {
+        prompts: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl ProviderBackend for EchoBackend {
+        async fn authenticate(&self, _provider: Provider) -> Result<()> {
+            Ok(())
+        }
+
+        async fn prompt(
+            &self,
+            provider: Provider,
+            request: PromptRequest,
+        ) -> Result<PromptResponse> {
+            self.prompts.fetch_add(1, Ordering::SeqCst);
+            Ok(PromptResponse {
+                text: format!("echo: {}", request.message),
+                provider,
+                conversation_id: None,
+                timestamp: Utc::now(),
+                tokens_used: None,
+                metadata: HashMap::new(),
+            })
+        }
+
+        fn capabilities(&self) -> BackendCapabilities {
+            BackendCapabilities {
+                kind: "echo",
+                browser: false,
+                system_prompt: true,
+                continuation: false,
+            }
+        }
+
+        fn cost_estimate(&self, _: Provider, _: &PromptRequest, _: &CostModel) -> f64 {
+            0.25
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_backend_answers_provider() {
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            storage: Some(Arc::new(MemoryStorage::new())),
+            ..Default::default()
+        });
+        let echo = Arc::new(EchoBackend::default());
+        assert!(orchestrator
+            .register_backend(Provider::Claude, echo.clone())
+            .await
+            .is_none());
+        assert_eq!(orchestrator.backends()[&Provider::Claude].kind, "echo");
+
+        let response = orchestrator
+            .prompt_provider(Provider::Claude, "hello")
+            .await
+            .unwrap();
+        assert_eq!(response.text, "echo: hello");
+        assert_eq!(echo.prompts.load(Ordering::SeqCst), 1);
+
+        let request = PromptRequest::new("hello");
+        let costs = CostModel::default();
+        assert_eq!(echo.cost_estimate(Provider::Claude, &request, &costs), 0.25);
+
+        assert!(orchestrator
+            .unregister_backend(Provider::Claude)
+            .await
+            .is_some());
+        assert!(orchestrator.backends().is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+//! Load benchmarks through the orchestrator.
+//!
+//! A run sends a fixed number of prompts with a fixed number in flight and
+//! reports throughput, the end-to-end latency distribution, time spent queued
+//! before reaching a provider, and how often in-flight requests reached the
+//! orchestrator's `max_concurrent`. Comparing runs at different concurrency
+//! levels shows where adding requests stops adding throughput.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use embeddenator_webpuppet::Provider;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::QUEUE_MS_KEY;
+use crate::orchestrator::{AgentOrchestrator, PromptOptions};
+
+/// What load a benchmark drives.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Prompts to send in total.
+    pub requests: usize,
+    /// Prompts in flight at once.
+    pub concurrency: usize,
+    /// Prompt sent on every request.
+    pub prompt: String,
+    /// Send every prompt to this provider instead of routing.
+    pub provider: Option<Provider>,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            requests: 50,
+            concurrency: 5,
+            prompt: "Reply with OK.".into(),
+            provider: None,
+        }
+    }
+}
+
+/// Distribution of durations, in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    /// Mean.
+    pub mean_ms: f64,
+    /// Median.
+    pub p50_ms: u64,
+    /// 90th percentile.
+    pub p90_ms: u64,
+    /// 99th percentile.
+    pub p99_ms: u64,
+    /// Slowest.
+    pub max_ms: u64,
+}
+
+impl LatencyStats {
+    /// Summarize samples (all zero when there are none).
+    pub fn from_samples(samples: &[u64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        // Nearest-rank percentile.
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        Self {
+            mean_ms: sorted.iter().sum::<u64>() as f64 / sorted.len() as f64,
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p99_ms: percentile(99.0),
+            max_ms: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Results of a benchmark run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchReport {
+    /// When the run started.
+    pub started_at: DateTime<Utc>,
+    /// Prompts sent.
+    pub requests: usize,
+    /// Prompts in flight at once.
+    pub concurrency: usize,
+    /// The orchestrator's configured `max_concurrent`.
+    pub max_concurrent: usize,
+    /// Prompts answered.
+    pub succeeded: usize,
+    /// Prompts that failed, by error kind.
+    pub errors: BTreeMap<String, usize>,
+    /// Wall-clock duration of the run, in milliseconds.
+    pub duration_ms: u64,
+    /// Answered prompts per second.
+    pub throughput_rps: f64,
+    /// End-to-end latency of answered prompts.
+    pub latency: LatencyStats,
+    /// Time answered prompts spent before reaching a provider.
+    pub queue: LatencyStats,
+    /// Most prompts in flight at once.
+    pub peak_in_flight: usize,
+    /// Share of the run (0.0-1.0) with at least `max_concurrent` prompts in
+    /// flight.
+    pub saturation: f64,
+}
+
+impl BenchReport {
+    /// One-line summary for logs.
+    pub fn summary(&self) -> String {
+        format!(
+            "{}/{} ok, {:.2} req/s, p50 {}ms, p99 {}ms, queue p99 {}ms, saturated {:.0}% of the run",
+            self.succeeded,
+            self.requests,
+            self.throughput_rps,
+            self.latency.p50_ms,
+            self.latency.p99_ms,
+            self.queue.p99_ms,
+            self.saturation * 100.0
+        )
+    }
+}
+
+/// One prompt's outcome, with offsets from the start of the run.
+struct Sample {
+    started: Duration,
+    finished: Duration,
+    queue_ms: Option<u64>,
+    error: Option<&'static str>,
+}
+
+/// Drive load through an orchestrator.
+pub async fn run(orchestrator: &AgentOrchestrator, options: &BenchOptions) -> BenchReport {
+    let started_at = Utc::now();
+    let start = Instant::now();
+    let samples: Vec<Sample> = stream::iter(0..options.requests)
+        .map(|_| async {
+            let started = start.elapsed();
+            let result = match options.provider {
+                Some(provider) => {
+                    orchestrator
+                        .prompt_provider_with(provider, &options.prompt, PromptOptions::default())
+                        .await
+                }
+                None => {
+                    orchestrator
+                        .prompt_with(&options.prompt, PromptOptions::default())
+                        .await
+                }
+            };
+            Sample {
+                started,
+                finished: start.elapsed(),
+                queue_ms: result
+                    .as_ref()
+                    .ok()
+                    .and_then(|r| r.metadata.get(QUEUE_MS_KEY)?.parse().ok()),
+                error: result.err().map(|e| e.kind()),
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect()
+        .await;
+    let elapsed = start.elapsed();
+    report(
+        started_at,
+        options,
+        orchestrator.config().max_concurrent,
+        &samples,
+        elapsed,
+    )
+}
+
+fn report(
+    started_at: DateTime<Utc>,
+    options: &BenchOptions,
+    max_concurrent: usize,
+    samples: &[Sample],
+    elapsed: Duration,
+) -> BenchReport {
+    let mut errors = BTreeMap::new();
+    let mut latencies = Vec::new();
+    let mut queues = Vec::new();
+    for sample in samples {
+        match sample.error {
+            Some(kind) => *errors.entry(kind.to_string()).or_default() += 1,
+            None => {
+                latencies.push((sample.finished - sample.started).as_millis() as u64);
+                queues.extend(sample.queue_ms);
+            }
+        }
+    }
+
+    // Sweep start and finish events in time order, ending before starting
+    // at the same instant, to find how many prompts were in flight when.
+    let mut events: Vec<(Duration, i32)> = samples
+        .iter()
+        .flat_map(|s| [(s.started, 1), (s.finished, -1)])
+        .collect();
+    events.sort();
+    let (mut in_flight, mut peak, mut saturated) = (0i32, 0usize, Duration::ZERO);
+    let mut last = Duration::ZERO;
+    for (at, delta) in events {
+        if max_concurrent > 0 && in_flight as usize >= max_concurrent {
+            saturated += at - last;
+        }
+        in_flight += delta;
+        peak = peak.max(in_flight.max(0) as usize);
+        last = at;
+    }
+
+    let secs = elapsed.as_secs_f64();
+    BenchReport {
+        started_at,
+        requests: options.requests,
+        concurrency: options.concurrency,
+        max_concurrent,
+        succeeded: latencies.len(),
+        errors,
+        duration_ms: elapsed.as_millis() as u64,
+        throughput_rps: if secs > 0.0 {
+            latencies.len() as f64 / secs
+        } else {
+            0.0
+        },
+        latency: LatencyStats::from_samples(&latencies),
+        queue: LatencyStats::from_samples(&queues),
+        peak_in_flight: peak,
+        saturation: if secs > 0.0 {
+            saturated.as_secs_f64() / secs
+        } else {
+            0.0
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::OrchestratorConfig;
+    use crate::storage::MemoryStorage;
+    use crate::testkit::{MockProviders, MockReply};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_bench_with_mock_providers() {
+        let stats = LatencyStats::from_samples(&[40, 10, 30, 20]);
+        assert_eq!((stats.p50_ms, stats.p99_ms, stats.mean_ms), (20, 40, 25.0));
+
+        let mocks = MockProviders::new().with_latency(Duration::from_millis(20));
+        mocks.enqueue(Provider::Claude, MockReply::Fail("internal error".into()));
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(mocks),
+            storage: Some(Arc::new(MemoryStorage::new())),
+            max_concurrent: 2,
+            ..Default::default()
+        });
+        let options = BenchOptions {
+            requests: 8,
+            concurrency: 4,
+            provider: Some(Provider::Claude),
+            ..Default::default()
+        };
+        let report = run(&orchestrator, &options).await;
+        assert_eq!(report.succeeded, 7);
+        assert_eq!(report.errors["provider"], 1);
+        assert_eq!(report.peak_in_flight, 4);
+        assert!(report.saturation > 0.5, "{}", report.summary());
+        assert!(report.latency.p50_ms >= 20);
+    }
+}
@@ -0,0 +1,300 @@
+//! Canary rollout of routing priority changes.
+//!
+//! A candidate set of routing priorities is applied to a share of routed
+//! prompts while the rest keep the configured priorities. Failure rate and
+//! estimated cost per request are tracked for both groups, and the canary is
+//! rolled back automatically once it does measurably worse than the baseline.
+//! Progress is kept in storage, so a rolled-back canary stays rolled back
+//! across restarts and instances sharing a backend.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::sha256_hex;
+use crate::error::Result;
+use crate::storage::Records;
+
+fn default_percent() -> f64 {
+    10.0
+}
+
+fn default_min_requests() -> u64 {
+    20
+}
+
+fn default_max_failure_rate_increase() -> f64 {
+    0.05
+}
+
+fn default_max_cost_increase() -> f64 {
+    0.25
+}
+
+/// Candidate routing priorities and when to roll them back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryPolicy {
+    /// Routing priorities tried on canary traffic, keyed by provider name.
+    pub priorities: BTreeMap<String, u32>,
+    /// Share of routed prompts sent through the canary (0-100).
+    #[serde(default = "default_percent")]
+    pub percent: f64,
+    /// Requests each group needs before the canary is judged.
+    #[serde(default = "default_min_requests")]
+    pub min_requests: u64,
+    /// Roll back once the canary's failure rate exceeds the baseline's by
+    /// more than this (0.05 = five percentage points).
+    #[serde(default = "default_max_failure_rate_increase")]
+    pub max_failure_rate_increase: f64,
+    /// Roll back once the canary's mean cost per request exceeds the
+    /// baseline's by more than this fraction (0.25 = 25%).
+    #[serde(default = "default_max_cost_increase")]
+    pub max_cost_increase: f64,
+}
+
+impl CanaryPolicy {
+    /// Key identifying this policy's run in storage.
+    fn key(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        sha256_hex(json.as_bytes())[..16].to_string()
+    }
+}
+
+/// Which routing priorities a prompt was routed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryArm {
+    /// The configured priorities.
+    Baseline,
+    /// The canary's priorities.
+    Canary,
+}
+
+/// Outcomes of the prompts routed one way.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArmStats {
+    /// Prompts sent.
+    pub requests: u64,
+    /// Prompts that failed.
+    pub failures: u64,
+    /// Estimated spend of answered prompts, in USD.
+    pub cost_usd: f64,
+}
+
+impl ArmStats {
+    /// Share of prompts that failed (0.0-1.0).
+    pub fn failure_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.requests as f64
+        }
+    }
+
+    /// Mean estimated cost per answered prompt, in USD.
+    pub fn mean_cost_usd(&self) -> f64 {
+        let answered = self.requests - self.failures;
+        if answered == 0 {
+            0.0
+        } else {
+            self.cost_usd / answered as f64
+        }
+    }
+}
+
+/// Progress of a canary.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanaryRun {
+    /// When the canary started.
+    pub started_at: DateTime<Utc>,
+    /// Prompts routed with the configured priorities.
+    pub baseline: ArmStats,
+    /// Prompts routed with the canary's priorities.
+    pub canary: ArmStats,
+    /// Why the canary was rolled back, if it was.
+    #[serde(default)]
+    pub rolled_back: Option<String>,
+    /// When the canary was rolled back.
+    #[serde(default)]
+    pub rolled_back_at: Option<DateTime<Utc>>,
+}
+
+impl CanaryRun {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            baseline: ArmStats::default(),
+            canary: ArmStats::default(),
+            rolled_back: None,
+            rolled_back_at: None,
+        }
+    }
+}
+
+/// A canary in progress.
+#[derive(Clone)]
+pub struct CanaryRollout {
+    records: Records,
+    policy: CanaryPolicy,
+    key: String,
+    routed: Arc<AtomicU64>,
+}
+
+impl CanaryRollout {
+    /// Run `policy` as a canary, keeping its progress in `records`.
+    pub fn open(records: Records, policy: CanaryPolicy) -> Self {
+        Self {
+            records,
+            key: policy.key(),
+            policy,
+            routed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The canary's policy.
+    pub fn policy(&self) -> &CanaryPolicy {
+        &self.policy
+    }
+
+    /// Choose how the next prompt is routed. Once rolled back, every prompt
+    /// is routed with the baseline.
+    ///
+    /// Assignment is even rather than random: at 25 percent, every fourth
+    /// prompt goes through the canary.
+    pub fn assign(&self) -> CanaryArm {
+        if self.status().is_ok_and(|run| run.rolled_back.is_some()) {
+            return CanaryArm::Baseline;
+        }
+        let rate = (self.policy.percent / 100.0).clamp(0.0, 1.0);
+        let n = self.routed.fetch_add(1, Ordering::Relaxed) as f64;
+        if ((n + 1.0) * rate).floor() > (n * rate).floor() {
+            CanaryArm::Canary
+        } else {
+            CanaryArm::Baseline
+        }
+    }
+
+    /// Record a prompt's outcome. Returns the reason if this outcome made
+    /// the canary regress and roll back.
+    pub fn record(&self, arm: CanaryArm, succeeded: bool, cost_usd: f64) -> Result<Option<String>> {
+        let mut rollback = None;
+        self.records.update(&self.key, |run: Option<CanaryRun>| {
+            let mut run = run.unwrap_or_else(CanaryRun::new);
+            let stats = match arm {
+                CanaryArm::Baseline => &mut run.baseline,
+                CanaryArm::Canary => &mut run.canary,
+            };
+            stats.requests += 1;
+            if succeeded {
+                stats.cost_usd += cost_usd;
+            } else {
+                stats.failures += 1;
+            }
+            rollback = None;
+            if run.rolled_back.is_none() {
+                if let Some(reason) = self.regression(&run) {
+                    run.rolled_back = Some(reason.clone());
+                    run.rolled_back_at = Some(Utc::now());
+                    rollback = Some(reason);
+                }
+            }
+            run
+        })?;
+        Ok(rollback)
+    }
+
+    /// Progress so far.
+    pub fn status(&self) -> Result<CanaryRun> {
+        Ok(self.records.load(&self.key)?.unwrap_or_else(CanaryRun::new))
+    }
+
+    /// How the canary does worse than the baseline, once both have enough
+    /// requests to compare.
+    fn regression(&self, run: &CanaryRun) -> Option<String> {
+        let (baseline, canary) = (&run.baseline, &run.canary);
+        if baseline.requests < self.policy.min_requests
+            || canary.requests < self.policy.min_requests
+        {
+            return None;
+        }
+        if canary.failure_rate() > baseline.failure_rate() + self.policy.max_failure_rate_increase {
+            return Some(format!(
+                "failure rate {:.1}% vs {:.1}% on the baseline",
+                canary.failure_rate() * 100.0,
+                baseline.failure_rate() * 100.0
+            ));
+        }
+        let base_cost = baseline.mean_cost_usd();
+        if base_cost > 0.0
+            && canary.mean_cost_usd() > base_cost * (1.0 + self.policy.max_cost_increase)
+        {
+            return Some(format!(
+                "mean cost ${:.5} per request vs ${:.5} on the baseline",
+                canary.mean_cost_usd(),
+                base_cost
+            ));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn test_canary_rolls_back_on_regression() {
+        let policy: CanaryPolicy = serde_json::from_value(serde_json::json!({
+            "priorities": { "gemini": 120 },
+            "percent": 25,
+            "min_requests": 4
+        }))
+        .unwrap();
+        let storage = Arc::new(MemoryStorage::new());
+        let canary = CanaryRollout::open(Records::new(storage.clone(), "canary", None), policy);
+        let arms: Vec<CanaryArm> = (0..8).map(|_| canary.assign()).collect();
+        assert_eq!(arms.iter().filter(|a| **a == CanaryArm::Canary).count(), 2);
+
+        for _ in 0..4 {
+            assert_eq!(
+                canary.record(CanaryArm::Baseline, true, 0.01).unwrap(),
+                None
+            );
+        }
+        for succeeded in [true, true, true] {
+            assert_eq!(
+                canary.record(CanaryArm::Canary, succeeded, 0.011).unwrap(),
+                None
+            );
+        }
+        let reason = canary
+            .record(CanaryArm::Canary, false, 0.0)
+            .unwrap()
+            .unwrap();
+        assert!(reason.starts_with("failure rate 25.0%"), "{}", reason);
+        assert_eq!(canary.record(CanaryArm::Canary, false, 0.0).unwrap(), None);
+
+        let reopened = CanaryRollout::open(
+            Records::new(storage, "canary", None),
+            canary.policy().clone(),
+        );
+        assert!(reopened.status().unwrap().rolled_back.is_some());
+        assert!((0..8).all(|_| reopened.assign() == CanaryArm::Baseline));
+    }
+
+    #[test]
+    fn test_canary_priorities_override_routing() {
+        use crate::router::{ProviderRouter, TaskType};
+        use embeddenator_webpuppet::Provider;
+
+        let router = ProviderRouter::new();
+        let priorities = BTreeMap::from([("grok".to_string(), 200)]);
+        let best = router.select_best_with(TaskType::General, None, &priorities, |_| true);
+        assert_eq!(best.unwrap(), Provider::Grok);
+        assert_ne!(router.select_best(TaskType::General).unwrap(), Provider::Grok);
+    }
+}
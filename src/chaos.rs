@@ -0,0 +1,272 @@
+//! Fault injection for resilience testing.
+//!
+//! Built only with the `chaos` feature. A [`ChaosPolicy`] makes a share of
+//! provider calls fail, stall, or come back malformed, so retry, fallback,
+//! and budget policies can be exercised before a real outage does it. Faults
+//! are drawn from a seeded generator: a run with a fixed seed injects the
+//! same faults in the same order.
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use embeddenator_webpuppet::{Error as PuppetError, PromptResponse, Provider};
+use serde::{Deserialize, Serialize};
+
+/// A synthetic provider failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// The provider reports an internal error.
+    ProviderError,
+    /// The provider rate limits the call.
+    RateLimited,
+    /// The provider never answers.
+    Timeout,
+    /// The provider's login session expired.
+    SessionExpired,
+    /// The browser driving the provider crashes.
+    BrowserCrash,
+}
+
+impl Fault {
+    /// Every fault kind.
+    pub const ALL: [Fault; 5] = [
+        Fault::ProviderError,
+        Fault::RateLimited,
+        Fault::Timeout,
+        Fault::SessionExpired,
+        Fault::BrowserCrash,
+    ];
+
+    /// The error a provider call fails with.
+    pub fn error(self, provider: Provider) -> PuppetError {
+        match self {
+            Fault::ProviderError => PuppetError::ProviderError {
+                provider: provider.to_string(),
+                message: "injected fault: internal server error".into(),
+            },
+            Fault::RateLimited => PuppetError::RateLimitExceeded {
+                retry_after_secs: 5,
+            },
+            Fault::Timeout => PuppetError::Timeout(30_000),
+            Fault::SessionExpired => PuppetError::SessionExpired(provider.to_string()),
+            Fault::BrowserCrash => PuppetError::Browser("injected fault: browser crashed".into()),
+        }
+    }
+}
+
+/// A way of mangling a successful response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Malformation {
+    /// No text at all.
+    Empty,
+    /// The answer stops halfway, mid-word.
+    Truncated,
+    /// An error page scraped in place of the answer.
+    ErrorPage,
+    /// A code block that is opened and never closed.
+    UnclosedFence,
+}
+
+impl Malformation {
+    /// Every malformation kind.
+    pub const ALL: [Malformation; 4] = [
+        Malformation::Empty,
+        Malformation::Truncated,
+        Malformation::ErrorPage,
+        Malformation::UnclosedFence,
+    ];
+
+    /// Mangle a response text.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Malformation::Empty => String::new(),
+            Malformation::Truncated => {
+                let half = text.chars().count() / 2;
+                text.chars().take(half).collect()
+            }
+            Malformation::ErrorPage => {
+                "<html><head><title>502 Bad Gateway</title></head><body>502 Bad Gateway</body></html>"
+                    .into()
+            }
+            Malformation::UnclosedFence => format!("{}\n\n```rust\nfn main() {{", text),
+        }
+    }
+}
+
+/// Which provider calls are disrupted, and how.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosPolicy {
+    /// Share of calls (0.0-1.0) failing with an injected fault.
+    pub failure_rate: f64,
+    /// Faults drawn from when a call fails.
+    pub faults: Vec<Fault>,
+    /// Share of calls (0.0-1.0) delayed before reaching the provider.
+    pub latency_rate: f64,
+    /// Length of an injected delay, in milliseconds.
+    pub latency_ms: u64,
+    /// Share of successful calls (0.0-1.0) whose response is mangled.
+    pub malformed_rate: f64,
+    /// Malformations drawn from when a response is mangled.
+    pub malformations: Vec<Malformation>,
+    /// Only disrupt these providers (every provider if empty).
+    pub providers: Vec<String>,
+    /// Seed for reproducible runs (seeded from the clock if unset).
+    pub seed: Option<u64>,
+}
+
+impl Default for ChaosPolicy {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.0,
+            faults: Fault::ALL.to_vec(),
+            latency_rate: 0.0,
+            latency_ms: 5_000,
+            malformed_rate: 0.0,
+            malformations: Malformation::ALL.to_vec(),
+            providers: Vec::new(),
+            seed: None,
+        }
+    }
+}
+
+impl ChaosPolicy {
+    /// Check if calls to `provider` are disrupted.
+    pub fn targets(&self, provider: Provider) -> bool {
+        self.providers.is_empty()
+            || self
+                .providers
+                .iter()
+                .any(|p| Provider::from_string(p) == Some(provider))
+    }
+}
+
+/// Draws faults for provider calls according to a policy.
+#[derive(Debug)]
+pub struct FaultInjector {
+    policy: ChaosPolicy,
+    state: Mutex<u64>,
+}
+
+impl FaultInjector {
+    /// Create an injector for a policy.
+    pub fn new(policy: ChaosPolicy) -> Self {
+        let seed = policy.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        });
+        Self {
+            policy,
+            state: Mutex::new(seed),
+        }
+    }
+
+    /// The policy faults are drawn by.
+    pub fn policy(&self) -> &ChaosPolicy {
+        &self.policy
+    }
+
+    /// Delay to add before calling `provider`, if one is drawn.
+    pub fn latency(&self, provider: Provider) -> Option<Duration> {
+        self.draw(provider, self.policy.latency_rate)
+            .then(|| Duration::from_millis(self.policy.latency_ms))
+    }
+
+    /// Error to fail a call to `provider` with, if one is drawn.
+    pub fn failure(&self, provider: Provider) -> Option<PuppetError> {
+        if !self.draw(provider, self.policy.failure_rate) {
+            return None;
+        }
+        self.pick(&self.policy.faults).map(|f| f.error(provider))
+    }
+
+    /// Mangle a successful response, returning the malformation applied, if
+    /// one is drawn.
+    pub fn malform(&self, response: &mut PromptResponse) -> Option<Malformation> {
+        if !self.draw(response.provider, self.policy.malformed_rate) {
+            return None;
+        }
+        let malformation = self.pick(&self.policy.malformations)?;
+        response.text = malformation.apply(&response.text);
+        Some(malformation)
+    }
+
+    /// Whether an event with probability `rate` happens on a call to
+    /// `provider`.
+    fn draw(&self, provider: Provider, rate: f64) -> bool {
+        rate > 0.0 && self.policy.targets(provider) && self.next() < rate
+    }
+
+    fn pick<T: Copy>(&self, items: &[T]) -> Option<T> {
+        if items.is_empty() {
+            return None;
+        }
+        let index = (self.next() * items.len() as f64) as usize;
+        items.get(index.min(items.len() - 1)).copied()
+    }
+
+    /// Next value in [0, 1) from a splitmix64 sequence.
+    fn next(&self) -> f64 {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_injection() {
+        let policy = ChaosPolicy {
+            failure_rate: 0.5,
+            faults: vec![Fault::RateLimited],
+            malformed_rate: 1.0,
+            malformations: vec![Malformation::Truncated],
+            providers: vec!["claude".into()],
+            seed: Some(7),
+            ..Default::default()
+        };
+        let injector = FaultInjector::new(policy.clone());
+        assert!(injector.failure(Provider::Gemini).is_none());
+        assert!(injector.latency(Provider::Claude).is_none());
+
+        let failures: Vec<bool> = (0..200)
+            .map(|_| injector.failure(Provider::Claude).is_some())
+            .collect();
+        let failed = failures.iter().filter(|f| **f).count();
+        assert!((60..140).contains(&failed), "{} of 200 failed", failed);
+        assert!(
+            crate::error::Error::from(Fault::RateLimited.error(Provider::Claude)).is_retryable()
+        );
+
+        // The same seed replays the same faults.
+        let replay = FaultInjector::new(policy);
+        let replayed: Vec<bool> = (0..200)
+            .map(|_| replay.failure(Provider::Claude).is_some())
+            .collect();
+        assert_eq!(failures, replayed);
+
+        let mut response = PromptResponse {
+            text: "Ownership moves values".into(),
+            provider: Provider::Claude,
+            conversation_id: None,
+            timestamp: chrono::Utc::now(),
+            tokens_used: None,
+            metadata: Default::default(),
+        };
+        assert_eq!(
+            injector.malform(&mut response),
+            Some(Malformation::Truncated)
+        );
+        assert_eq!(response.text, "Ownership m");
+    }
+}
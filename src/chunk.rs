@@ -0,0 +1,307 @@
+//! Splitting long texts into chunks.
+//!
+//! Chunkers cut documents and source files into pieces small enough to send,
+//! index, or fit into a prompt budget on their own. Every chunk keeps the
+//! lines it came from. Markdown is cut at headings and code at top-level
+//! items, so a chunk holds one section or one function where possible;
+//! pieces still too large fall back to fixed-size chunks.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::cost::{estimate_tokens, CHARS_PER_TOKEN};
+
+fn default_max_tokens() -> u64 {
+    512
+}
+
+/// How a text is split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Chunker {
+    /// Runs of whole lines up to a token size, optionally overlapping.
+    FixedTokens {
+        /// Largest chunk, in estimated tokens.
+        #[serde(default = "default_max_tokens")]
+        max_tokens: u64,
+        /// Tokens of trailing lines repeated at the start of the next chunk.
+        #[serde(default)]
+        overlap_tokens: u64,
+    },
+    /// One chunk per Markdown section, cut at headings outside code fences.
+    Markdown {
+        /// Largest chunk, in estimated tokens.
+        #[serde(default = "default_max_tokens")]
+        max_tokens: u64,
+    },
+    /// One chunk per top-level item (function, type, impl, class), with its
+    /// doc comments and attributes.
+    Code {
+        /// Largest chunk, in estimated tokens.
+        #[serde(default = "default_max_tokens")]
+        max_tokens: u64,
+    },
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::FixedTokens {
+            max_tokens: default_max_tokens(),
+            overlap_tokens: 0,
+        }
+    }
+}
+
+/// A piece of a text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Chunk text.
+    pub text: String,
+    /// First line, counting from 1.
+    pub start_line: usize,
+    /// Last line, inclusive.
+    pub end_line: usize,
+    /// Heading or item the chunk belongs to, if known.
+    pub label: Option<String>,
+}
+
+impl Chunk {
+    /// Estimated tokens of the chunk.
+    pub fn tokens(&self) -> u64 {
+        estimate_tokens(&self.text)
+    }
+}
+
+impl Chunker {
+    /// Split `text` into chunks, in order. Blank texts have no chunks.
+    pub fn chunk(&self, text: &str) -> Vec<Chunk> {
+        let lines: Vec<&str> = text.lines().collect();
+        match *self {
+            Self::FixedTokens {
+                max_tokens,
+                overlap_tokens,
+            } => pack(&lines, 0, max_tokens, overlap_tokens, None),
+            Self::Markdown { max_tokens } => {
+                let mut in_fence = false;
+                let starts = section_starts(&lines, |line| {
+                    if line.trim_start().starts_with("```") {
+                        in_fence = !in_fence;
+                    }
+                    (!in_fence && line.starts_with('#'))
+                        .then(|| line.trim_start_matches('#').trim().to_string())
+                });
+                split_sections(&lines, &starts, max_tokens)
+            }
+            Self::Code { max_tokens } => {
+                let starts = item_starts(&lines);
+                split_sections(&lines, &starts, max_tokens)
+            }
+        }
+    }
+}
+
+/// Every top-level item of a source file, whole, labelled with its name.
+/// Lines before the first item form an unlabelled chunk.
+pub fn code_items(text: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    split_sections(&lines, &item_starts(&lines), u64::MAX)
+}
+
+/// Lines where a section starts, with its label, as reported by `start`.
+fn section_starts(
+    lines: &[&str],
+    mut start: impl FnMut(&str) -> Option<String>,
+) -> Vec<(usize, Option<String>)> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| start(line).map(|label| (i, Some(label))))
+        .collect()
+}
+
+/// Matches the start of a top-level item in common languages.
+fn item_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(?:pub(?:\([^)]*\))?\s+)?(?:export\s+)?(?:default\s+)?(?:async\s+)?(?:unsafe\s+)?(?:fn|struct|enum|trait|impl|mod|union|type|const|static|macro_rules!|def|class|function|func|interface)\b[\s<(]*([A-Za-z_][A-Za-z0-9_]*)?",
+        )
+        .unwrap()
+    })
+}
+
+/// Check if a line is a doc comment or attribute belonging to the item below.
+pub(crate) fn is_preamble(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("///")
+        || line.starts_with("//!")
+        || line.starts_with("#[")
+        || line.starts_with('@')
+        || line.starts_with("/**")
+        || line.starts_with("* ")
+        || line.starts_with("*/")
+}
+
+/// Lines where top-level items start, moved up over the doc comments and
+/// attributes directly above them.
+fn item_starts(lines: &[&str]) -> Vec<(usize, Option<String>)> {
+    let mut starts = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(caps) = item_re().captures(line) else {
+            continue;
+        };
+        let label = caps.get(1).map_or_else(
+            || line.trim().trim_end_matches('{').trim().to_string(),
+            |m| m.as_str().to_string(),
+        );
+        let mut start = i;
+        while start > 0 && is_preamble(lines[start - 1]) {
+            start -= 1;
+        }
+        starts.push((start, Some(label)));
+    }
+    starts
+}
+
+/// Cut `lines` at `starts`, splitting sections larger than `max_tokens`.
+fn split_sections(
+    lines: &[&str],
+    starts: &[(usize, Option<String>)],
+    max_tokens: u64,
+) -> Vec<Chunk> {
+    let mut bounds: Vec<(usize, Option<String>)> = Vec::new();
+    if starts.first().is_none_or(|(s, _)| *s > 0) {
+        bounds.push((0, None));
+    }
+    bounds.extend(starts.iter().cloned());
+    let mut chunks = Vec::new();
+    for (i, (start, label)) in bounds.iter().enumerate() {
+        let end = bounds.get(i + 1).map_or(lines.len(), |(next, _)| *next);
+        chunks.extend(pack(
+            &lines[*start..end],
+            *start,
+            max_tokens,
+            0,
+            label.clone(),
+        ));
+    }
+    chunks
+}
+
+/// Pack runs of whole lines into chunks of at most `max_tokens`, repeating
+/// up to `overlap_tokens` of trailing lines at the start of the next chunk.
+/// `offset` is the index of the first line in the whole text. Lines longer
+/// than a chunk are cut.
+fn pack(
+    lines: &[&str],
+    offset: usize,
+    max_tokens: u64,
+    overlap_tokens: u64,
+    label: Option<String>,
+) -> Vec<Chunk> {
+    let max_tokens = max_tokens.max(2);
+    let overlap_tokens = overlap_tokens.min(max_tokens / 2);
+    // Each piece costs its tokens plus one for the line break.
+    let pieces: Vec<(usize, String)> = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            split_long_line(line, max_tokens - 1)
+                .into_iter()
+                .map(move |piece| (i, piece))
+        })
+        .collect();
+    let cost = |piece: &(usize, String)| estimate_tokens(&piece.1) + 1;
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < pieces.len() {
+        let (mut end, mut used) = (start, 0);
+        while end < pieces.len() && (end == start || used + cost(&pieces[end]) <= max_tokens) {
+            used += cost(&pieces[end]);
+            end += 1;
+        }
+        let run = &pieces[start..end];
+        let text = run
+            .iter()
+            .map(|(_, piece)| piece.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                text,
+                start_line: offset + run[0].0 + 1,
+                end_line: offset + run[run.len() - 1].0 + 1,
+                label: label.clone(),
+            });
+        }
+        if end == pieces.len() {
+            break;
+        }
+        // Step back over trailing pieces for the overlap, always advancing.
+        let (mut next, mut carried) = (end, 0);
+        while next > start + 1 && carried + cost(&pieces[next - 1]) <= overlap_tokens {
+            next -= 1;
+            carried += cost(&pieces[next]);
+        }
+        start = next;
+    }
+    chunks
+}
+
+/// Cut a line longer than `max_tokens` into pieces that fit.
+fn split_long_line(line: &str, max_tokens: u64) -> Vec<String> {
+    if estimate_tokens(line) <= max_tokens {
+        return vec![line.to_string()];
+    }
+    let chars: Vec<char> = line.chars().collect();
+    chars
+        .chunks(max_tokens as usize * CHARS_PER_TOKEN)
+        .map(|c| c.iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunkers() {
+        let markdown =
+            "Intro line\n# Setup\nInstall it.\n```\n# not a heading\n```\n## Usage\nRun it.";
+        let sections = Chunker::Markdown { max_tokens: 100 }.chunk(markdown);
+        let labels: Vec<_> = sections.iter().map(|c| c.label.as_deref()).collect();
+        assert_eq!(labels, [None, Some("Setup"), Some("Usage")]);
+        assert_eq!((sections[1].start_line, sections[1].end_line), (2, 6));
+
+        let code = "use std::fmt;\n\n/// Adds.\n#[inline]\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\nimpl Foo {\n    fn bar() {}\n}\n";
+        let items = Chunker::Code { max_tokens: 100 }.chunk(code);
+        let labels: Vec<_> = items.iter().map(|c| c.label.as_deref()).collect();
+        assert_eq!(labels, [None, Some("add"), Some("Foo")]);
+        assert!(items[1].text.starts_with("/// Adds."));
+
+        let text = (1..=20)
+            .map(|i| format!("line number {:02}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let fixed = Chunker::FixedTokens {
+            max_tokens: 25,
+            overlap_tokens: 5,
+        }
+        .chunk(&text);
+        assert!(fixed.iter().all(|c| c.tokens() <= 25));
+        assert_eq!(fixed[0].start_line, 1);
+        assert_eq!(fixed.last().unwrap().end_line, 20);
+        assert!(fixed[1].start_line <= fixed[0].end_line);
+
+        assert!(Chunker::default().chunk("  \n").is_empty());
+        let long = Chunker::FixedTokens {
+            max_tokens: 10,
+            overlap_tokens: 0,
+        }
+        .chunk(&"x".repeat(100));
+        assert_eq!(long.len(), 3);
+    }
+}
@@ -0,0 +1,295 @@
+//! Citations parsed from provider responses.
+//!
+//! Search-backed providers (Perplexity, Gemini) cite sources as Markdown
+//! links, bare URLs, or numbered reference lists. Every response's citations
+//! are normalized into `metadata.sources`, and results combining several
+//! responses merge them into one deduplicated bibliography that records
+//! which providers cited each source.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use embeddenator_webpuppet::PromptResponse;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::workflow::ProviderResponse;
+
+/// Response metadata key holding the JSON list of citations.
+pub const SOURCES_KEY: &str = "sources";
+
+/// Query parameters that only track the referrer and never change the page.
+const TRACKING_PARAMS: &[&str] = &["utm_", "ref", "fbclid", "gclid"];
+
+/// A source cited in one response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    /// Normalized URL.
+    pub url: String,
+    /// Link or reference text, if the provider gave any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// A source cited by one or more providers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    /// Normalized URL.
+    pub url: String,
+    /// Link text, if a provider gave one.
+    pub title: Option<String>,
+    /// Providers that cited the source.
+    pub providers: Vec<String>,
+}
+
+/// Sources merged across responses, deduplicated by URL in first-cited
+/// order.
+#[derive(Debug, Clone, Default)]
+pub struct SourceList {
+    sources: Vec<Source>,
+    index: HashMap<String, usize>,
+}
+
+impl SourceList {
+    /// Add the citations of one provider's response.
+    pub fn add(&mut self, provider: &str, citations: &[Citation]) {
+        for citation in citations {
+            let i = *self.index.entry(citation.url.clone()).or_insert_with(|| {
+                self.sources.push(Source {
+                    url: citation.url.clone(),
+                    title: None,
+                    providers: Vec::new(),
+                });
+                self.sources.len() - 1
+            });
+            let source = &mut self.sources[i];
+            if source.title.is_none() {
+                source.title = citation.title.clone();
+            }
+            if !source.providers.iter().any(|p| p == provider) {
+                source.providers.push(provider.to_string());
+            }
+        }
+    }
+
+    /// Add the citations recorded in response metadata, as produced by
+    /// [`annotate`] and carried over into JSON metadata.
+    pub fn add_metadata(&mut self, provider: &str, metadata: &HashMap<String, serde_json::Value>) {
+        if let Some(sources) = metadata.get(SOURCES_KEY) {
+            let citations: Vec<Citation> =
+                serde_json::from_value(sources.clone()).unwrap_or_default();
+            self.add(provider, &citations);
+        }
+    }
+
+    /// Check if no sources were cited.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// The sources, in first-cited order.
+    pub fn into_vec(self) -> Vec<Source> {
+        self.sources
+    }
+}
+
+/// Merge the citations of several provider responses.
+pub fn merge(responses: &[ProviderResponse]) -> Vec<Source> {
+    let mut sources = SourceList::default();
+    for response in responses {
+        sources.add_metadata(&response.provider, &response.metadata);
+    }
+    sources.into_vec()
+}
+
+/// Citations recorded in a response's metadata by [`annotate`].
+pub fn from_response(response: &PromptResponse) -> Vec<Citation> {
+    response
+        .metadata
+        .get(SOURCES_KEY)
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// Record a response's citations under [`SOURCES_KEY`] in its metadata.
+pub fn annotate(response: &mut PromptResponse) {
+    let citations = extract_citations(&response.text);
+    if citations.is_empty() {
+        response.metadata.remove(SOURCES_KEY);
+    } else if let Ok(json) = serde_json::to_string(&citations) {
+        response.metadata.insert(SOURCES_KEY.into(), json);
+    }
+}
+
+/// Render sources as a numbered Markdown bibliography, or an empty string if
+/// there are none.
+pub fn bibliography(sources: &[Source]) -> String {
+    if sources.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("## Sources\n");
+    for (i, source) in sources.iter().enumerate() {
+        let title = source.title.as_deref().unwrap_or(&source.url);
+        out.push_str(&format!(
+            "\n{}. [{}]({}) ({})",
+            i + 1,
+            title,
+            source.url,
+            source.providers.join(", ")
+        ));
+    }
+    out
+}
+
+/// Citations in a response, deduplicated, in order of appearance.
+///
+/// Recognizes Markdown links, reference definitions (`[1]: url`), numbered
+/// reference lists (`1. Title - url`, `[2] url`), and bare URLs. Reference
+/// text is kept as the title; a URL or bare number used as link text is not.
+pub fn extract_citations(text: &str) -> Vec<Citation> {
+    static MARKDOWN_LINK: OnceLock<Regex> = OnceLock::new();
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    static BARE_URL: OnceLock<Regex> = OnceLock::new();
+    let markdown = MARKDOWN_LINK
+        .get_or_init(|| Regex::new(r"\[([^\]]+)\]\((https?://[^\s)]+)\)").expect("valid regex"));
+    let reference = REFERENCE.get_or_init(|| {
+        Regex::new(
+            r"(?m)^[ \t]*\[?\d+[\].):]+[ \t]+(.*?)[ \t]*[-–—:]?[ \t]*<?(https?://[^\s>]+)>?[ \t]*$",
+        )
+        .expect("valid regex")
+    });
+    let bare =
+        BARE_URL.get_or_init(|| Regex::new(r#"https?://[^\s)\]>"'<]+"#).expect("valid regex"));
+
+    let mut found: Vec<(usize, String, Option<String>)> = Vec::new();
+    let mut covered: Vec<(usize, usize)> = Vec::new();
+    for caps in markdown.captures_iter(text) {
+        let whole = caps.get(0).expect("match");
+        covered.push((whole.start(), whole.end()));
+        found.push((whole.start(), normalize_url(&caps[2]), title(&caps[1])));
+    }
+    for caps in reference.captures_iter(text) {
+        let whole = caps.get(0).expect("match");
+        if covered
+            .iter()
+            .any(|(s, e)| whole.start() < *e && whole.end() > *s)
+        {
+            continue;
+        }
+        covered.push((whole.start(), whole.end()));
+        found.push((whole.start(), normalize_url(&caps[2]), title(&caps[1])));
+    }
+    for m in bare.find_iter(text) {
+        if !covered
+            .iter()
+            .any(|(s, e)| m.start() >= *s && m.end() <= *e)
+        {
+            found.push((m.start(), normalize_url(m.as_str()), None));
+        }
+    }
+    found.sort_by_key(|(start, ..)| *start);
+
+    let mut citations: Vec<Citation> = Vec::new();
+    for (_, url, title) in found {
+        match citations.iter_mut().find(|c| c.url == url) {
+            Some(existing) => {
+                if existing.title.is_none() {
+                    existing.title = title;
+                }
+            }
+            None => citations.push(Citation { url, title }),
+        }
+    }
+    citations
+}
+
+/// Link text worth keeping as a title.
+fn title(text: &str) -> Option<String> {
+    let text = text.trim().trim_matches(|c| c == '"' || c == '*');
+    let useless = text.is_empty()
+        || text.starts_with("http")
+        || text
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '[' || c == ']');
+    (!useless).then(|| text.to_string())
+}
+
+/// Normalize a URL so the same page cited differently is counted once:
+/// trailing punctuation, the fragment, tracking parameters, and a trailing
+/// slash are dropped, and the scheme and host are lowercased.
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    let url = url.split('#').next().unwrap_or(url);
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (url, None),
+    };
+    let path = path.trim_end_matches('/');
+    let (scheme, rest) = path.split_once("://").unwrap_or(("", path));
+    let host_end = rest.find('/').unwrap_or(rest.len());
+
+    let mut normalized = if scheme.is_empty() {
+        rest.to_string()
+    } else {
+        format!(
+            "{}://{}{}",
+            scheme.to_lowercase(),
+            rest[..host_end].to_lowercase(),
+            &rest[host_end..]
+        )
+    };
+    let params: Vec<&str> = query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .filter(|p| {
+            let name = p.split('=').next().unwrap_or(p);
+            !p.is_empty()
+                && !TRACKING_PARAMS
+                    .iter()
+                    .any(|t| name == *t || (t.ends_with('_') && name.starts_with(t)))
+        })
+        .collect();
+    if !params.is_empty() {
+        normalized.push('?');
+        normalized.push_str(&params.join("&"));
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_citations_extract_and_merge() {
+        let perplexity = "Axum is popular [Axum docs](https://Docs.rs/axum/#routing) [1].\n\n\
+            Sources:\n\
+            1. Tokio blog - https://tokio.rs/blog/2021-07-announcing-axum?utm_source=pplx\n\
+            [2] https://github.com/tokio-rs/axum\n";
+        let citations = extract_citations(perplexity);
+        assert_eq!(citations.len(), 3);
+        assert_eq!(citations[0].url, "https://docs.rs/axum");
+        assert_eq!(citations[0].title.as_deref(), Some("Axum docs"));
+        assert_eq!(
+            citations[1].url,
+            "https://tokio.rs/blog/2021-07-announcing-axum"
+        );
+        assert_eq!(citations[1].title.as_deref(), Some("Tokio blog"));
+        assert_eq!(citations[2].title, None);
+
+        let gemini =
+            extract_citations("See https://docs.rs/axum/ and [1](https://blog.rust-lang.org).");
+        let mut sources = SourceList::default();
+        sources.add("perplexity", &citations);
+        sources.add("gemini", &gemini);
+        let sources = sources.into_vec();
+        assert_eq!(sources.len(), 4);
+        assert_eq!(sources[0].providers, vec!["perplexity", "gemini"]);
+        assert!(bibliography(&sources)
+            .contains("1. [Axum docs](https://docs.rs/axum) (perplexity, gemini)"));
+        assert_eq!(
+            normalize_url("https://a.io/x?id=2&ref=hn"),
+            "https://a.io/x?id=2"
+        );
+    }
+}
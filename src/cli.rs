@@ -0,0 +1,232 @@
+//! Command-line output formats and shell completions.
+//!
+//! Subcommands that report results print text for people by default and a
+//! single JSON document with `--output json` for scripts. Completion scripts
+//! are generated from the command-line definition itself, so they list the
+//! same subcommands and options as `--help`.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use clap::Command;
+
+use crate::error::{Error, Result};
+
+/// How a subcommand prints its result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// One pretty-printed JSON document.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(Error::InvalidParams(format!(
+                "unknown output format: {} (expected text or json)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Shells completion scripts can be generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// Bash.
+    Bash,
+    /// Zsh, through its bash completion compatibility.
+    Zsh,
+    /// Fish.
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(Error::InvalidParams(format!(
+                "unknown shell: {} (expected bash, zsh, or fish)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Completion script for `command` in `shell`.
+///
+/// Subcommands and long options are completed; option values fall back to
+/// the shell's file name completion.
+pub fn completion_script(command: &Command, shell: Shell) -> String {
+    let mut command = command.clone();
+    command.build();
+    match shell {
+        Shell::Bash => bash_script(&command),
+        Shell::Zsh => format!(
+            "#compdef {}\n\nautoload -U +X bashcompinit && bashcompinit\n\n{}",
+            command.get_name(),
+            bash_script(&command)
+        ),
+        Shell::Fish => fish_script(&command),
+    }
+}
+
+/// Long options of a command, with their help text and whether they take a
+/// value.
+fn options(command: &Command) -> Vec<(&str, String, bool)> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .filter_map(|arg| {
+            let help = arg
+                .get_help()
+                .map(|help| help.to_string())
+                .unwrap_or_default();
+            let help = help.lines().next().unwrap_or_default().to_string();
+            Some((arg.get_long()?, help, arg.get_action().takes_values()))
+        })
+        .collect()
+}
+
+/// Visible subcommands, except the generated `help`.
+fn subcommands(command: &Command) -> Vec<&Command> {
+    command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+        .collect()
+}
+
+fn bash_script(command: &Command) -> String {
+    let name = command.get_name();
+    let function = format!("_{}", name.replace('-', "_"));
+    let words = |command: &Command| {
+        options(command)
+            .iter()
+            .map(|(long, _, _)| format!("--{}", long))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let subcommands = subcommands(command);
+    let names: Vec<&str> = subcommands.iter().map(|sub| sub.get_name()).collect();
+
+    let cases: String = subcommands
+        .iter()
+        .map(|sub| format!("        {}) words=\"{}\" ;;\n", sub.get_name(), words(sub)))
+        .collect();
+    format!(
+        r#"{function}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" command="" word
+    for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+        case "$word" in
+            {commands}) command="$word"; break ;;
+        esac
+    done
+    local words
+    case "$command" in
+{cases}        *) words="{options} {subcommands}" ;;
+    esac
+    COMPREPLY=($(compgen -W "$words" -- "$cur"))
+}}
+complete -o default -F {function} {name}
+"#,
+        commands = names.join("|"),
+        options = words(command),
+        subcommands = names.join(" "),
+    )
+}
+
+fn fish_script(command: &Command) -> String {
+    let name = command.get_name();
+    let mut out = String::new();
+    let mut option_lines = |condition: &str, command: &Command| {
+        for (long, help, takes_value) in options(command) {
+            let value = if takes_value { " -r" } else { "" };
+            writeln!(
+                out,
+                "complete -c {} -n '{}' -l {}{} -d '{}'",
+                name,
+                condition,
+                long,
+                value,
+                fish_quote(&help)
+            )
+            .unwrap();
+        }
+    };
+    option_lines("__fish_use_subcommand", command);
+    let subcommands = subcommands(command);
+    for sub in &subcommands {
+        option_lines(
+            &format!("__fish_seen_subcommand_from {}", sub.get_name()),
+            sub,
+        );
+    }
+    for sub in &subcommands {
+        let about = sub.get_about().map(|a| a.to_string()).unwrap_or_default();
+        writeln!(
+            out,
+            "complete -c {} -n '__fish_use_subcommand' -f -a {} -d '{}'",
+            name,
+            sub.get_name(),
+            fish_quote(about.lines().next().unwrap_or_default())
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Escape text for a single-quoted fish string.
+fn fish_quote(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    #[test]
+    fn test_completion_scripts() {
+        let command = Command::new("agent-mcp")
+            .arg(
+                Arg::new("visible")
+                    .long("visible")
+                    .help("Show the browser")
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(
+                Command::new("run")
+                    .about("Run a workflow's steps")
+                    .arg(Arg::new("input").long("input").help("An input")),
+            );
+
+        let bash = completion_script(&command, Shell::Bash);
+        assert!(bash.starts_with("_agent_mcp() {\n"));
+        assert!(bash.contains("            run) command=\"$word\"; break ;;\n"));
+        assert!(bash.contains("        run) words=\"--input --help\" ;;\n"));
+        assert!(bash.contains("        *) words=\"--visible --help run\" ;;\n"));
+        assert!(bash.ends_with("complete -o default -F _agent_mcp agent-mcp\n"));
+        assert!(completion_script(&command, Shell::Zsh).starts_with("#compdef agent-mcp\n"));
+
+        let fish = completion_script(&command, Shell::Fish);
+        assert!(fish.contains(
+            "complete -c agent-mcp -n '__fish_seen_subcommand_from run' -l input -r -d 'An input'\n"
+        ));
+        assert!(fish.contains("-f -a run -d 'Run a workflow\\'s steps'\n"));
+
+        assert_eq!("JSON".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert!("yaml".parse::<OutputFormat>().is_err());
+        assert!("tcsh".parse::<Shell>().is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Detecting empty and truncated provider responses.
+//!
+//! Scraping an answer from a web app sometimes goes wrong: the page is read
+//! before the answer starts, or while it is still being written, or an error
+//! page is read in its place. These are not errors reported by the provider,
+//! so they are told apart here. An empty answer fails with
+//! `incomplete_response` and is sent again; an answer that stops partway is
+//! continued, and if it still stops short it is returned marked
+//! `incomplete`.
+//!
+//! API backends continue an answer from where it stopped. A browser provider
+//! is asked to continue in its conversation, or else sent the prompt again
+//! with the answer so far. Either way the parts are stitched together,
+//! dropping text repeated at the seam.
+
+use embeddenator_webpuppet::{PromptRequest, PromptResponse};
+
+use crate::error::{Error, Result};
+use crate::metadata::{FINISH_REASON_KEY, RESPONSE_TOKENS_KEY};
+
+/// Response metadata key: why an answer returned anyway is incomplete.
+pub const INCOMPLETE_KEY: &str = "incomplete";
+
+/// Response metadata key: how many continuations the answer was joined from.
+pub const CONTINUATIONS_KEY: &str = "continuations";
+
+/// Keys recorded only on responses that were incomplete.
+pub const METADATA_KEYS: &[&str] = &[INCOMPLETE_KEY, CONTINUATIONS_KEY];
+
+/// Request metadata key holding the partial answer to continue.
+pub const CONTINUE_FROM_KEY: &str = "continue_from";
+
+/// Asks a provider to pick up where its answer stopped.
+pub const CONTINUE_PROMPT: &str =
+    "Continue exactly where your previous answer stopped. Do not repeat anything.";
+
+/// Finish reasons meaning the answer hit the response token limit.
+const LENGTH_FINISH_REASONS: &[&str] = &["length", "max_tokens"];
+
+/// Openings of an HTML error page scraped in place of an answer.
+const ERROR_PAGE_MARKERS: &[&str] = &["<html", "<!doctype html"];
+
+/// Shortest and longest repeated text, in characters, dropped when joining
+/// a continuation. Shorter overlaps are likely coincidence.
+const MIN_OVERLAP_CHARS: usize = 8;
+const MAX_OVERLAP_CHARS: usize = 200;
+
+/// Shortest cut-off word completed by a continuation's first word.
+const MIN_WORD_FRAGMENT_CHARS: usize = 3;
+
+/// Why a response is incomplete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Incomplete {
+    /// No answer, or an error page in place of one.
+    Empty,
+    /// The answer stops partway, for the given reason.
+    Truncated(&'static str),
+}
+
+/// Check whether a response is obviously empty or truncated.
+pub fn check(response: &PromptResponse) -> Option<Incomplete> {
+    let text = response.text.trim();
+    let opening = text.chars().take(16).collect::<String>().to_lowercase();
+    if text.is_empty() || ERROR_PAGE_MARKERS.iter().any(|m| opening.starts_with(m)) {
+        return Some(Incomplete::Empty);
+    }
+    let finish_reason = response.metadata.get(FINISH_REASON_KEY);
+    if finish_reason.is_some_and(|r| LENGTH_FINISH_REASONS.contains(&r.as_str())) {
+        return Some(Incomplete::Truncated("hit the response length limit"));
+    }
+    let fences = text
+        .lines()
+        .filter(|line| line.trim_start().starts_with("```"))
+        .count();
+    if fences % 2 == 1 {
+        return Some(Incomplete::Truncated("ends inside a code block"));
+    }
+    if text.ends_with([',', ';', ':']) {
+        return Some(Incomplete::Truncated("ends mid-sentence"));
+    }
+    None
+}
+
+/// Fail with `IncompleteResponse` if the response has no answer.
+pub fn ensure_answered(response: &PromptResponse) -> Result<()> {
+    match check(response) {
+        Some(Incomplete::Empty) => Err(Error::IncompleteResponse {
+            provider: response.provider.to_string(),
+            reason: "no answer in the response".into(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Request continuing `partial`, the truncated answer to `request`: in the
+/// provider's conversation if it has one, else the same request carrying
+/// the answer so far under [`CONTINUE_FROM_KEY`].
+pub fn continuation(request: &PromptRequest, partial: &PromptResponse) -> PromptRequest {
+    match &partial.conversation_id {
+        Some(id) => PromptRequest::new(CONTINUE_PROMPT).with_conversation(id.clone()),
+        None => {
+            let mut request = request.clone();
+            request
+                .metadata
+                .insert(CONTINUE_FROM_KEY.into(), partial.text.clone());
+            request
+        }
+    }
+}
+
+/// A continuation request spelled out for a backend that cannot continue
+/// an answer itself: the prompt again, followed by the answer so far.
+/// Other requests are returned unchanged.
+pub fn restate(mut request: PromptRequest) -> PromptRequest {
+    if let Some(partial) = request.metadata.remove(CONTINUE_FROM_KEY) {
+        request.message = format!(
+            "{}\n\n---\n\nYour answer so far:\n\n{}\n\n---\n\n{}",
+            request.message, partial, CONTINUE_PROMPT
+        );
+    }
+    request
+}
+
+/// Append a continuation to a partial answer. Text the provider repeated
+/// from the end of the answer is dropped, as is a word cut off at the end
+/// that the continuation starts over.
+pub fn append(partial: &mut PromptResponse, continuation: PromptResponse) {
+    let overlap = continuation
+        .text
+        .char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .take(MAX_OVERLAP_CHARS)
+        .skip(MIN_OVERLAP_CHARS - 1)
+        .filter(|&end| partial.text.ends_with(&continuation.text[..end]))
+        .last()
+        .unwrap_or(0);
+    if overlap == 0 {
+        let fragment = trailing_word(&partial.text);
+        let first_word = continuation
+            .text
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        if fragment.chars().count() >= MIN_WORD_FRAGMENT_CHARS
+            && first_word.len() > fragment.len()
+            && first_word.starts_with(fragment)
+        {
+            let cut = partial.text.len() - fragment.len();
+            partial.text.truncate(cut);
+        }
+    }
+    partial.text.push_str(&continuation.text[overlap..]);
+    partial.tokens_used = match (partial.tokens_used, continuation.tokens_used) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    };
+    let tokens = |r: &PromptResponse| r.metadata.get(RESPONSE_TOKENS_KEY)?.parse::<u64>().ok();
+    if let (Some(a), Some(b)) = (tokens(partial), tokens(&continuation)) {
+        partial
+            .metadata
+            .insert(RESPONSE_TOKENS_KEY.into(), (a + b).to_string());
+    }
+    match continuation.metadata.get(FINISH_REASON_KEY) {
+        Some(reason) => partial
+            .metadata
+            .insert(FINISH_REASON_KEY.into(), reason.clone()),
+        None => partial.metadata.remove(FINISH_REASON_KEY),
+    };
+    if continuation.conversation_id.is_some() {
+        partial.conversation_id = continuation.conversation_id;
+    }
+}
+
+/// The word `text` ends in, if it ends mid-word.
+fn trailing_word(text: &str) -> &str {
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric())
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use embeddenator_webpuppet::Provider;
+    use std::collections::HashMap;
+
+    fn response(text: &str) -> PromptResponse {
+        PromptResponse {
+            text: text.into(),
+            provider: Provider::Claude,
+            conversation_id: None,
+            timestamp: Utc::now(),
+            tokens_used: Some(10),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_detects_and_continues_incomplete_answers() {
+        assert_eq!(check(&response("Paris")), None);
+        assert_eq!(check(&response("  \n")), Some(Incomplete::Empty));
+        assert_eq!(
+            check(&response("<html><title>502 Bad Gateway</title></html>")),
+            Some(Incomplete::Empty)
+        );
+        assert_eq!(
+            ensure_answered(&response("")).unwrap_err().kind(),
+            "incomplete_response"
+        );
+        assert!(matches!(
+            check(&response("Here it is:\n\n```rust\nfn main() {")),
+            Some(Incomplete::Truncated(_))
+        ));
+        assert_eq!(check(&response("```sh\nls\n```")), None);
+        let mut limited = response("The steps are");
+        limited
+            .metadata
+            .insert(FINISH_REASON_KEY.into(), "length".into());
+        assert!(matches!(check(&limited), Some(Incomplete::Truncated(_))));
+
+        let request = PromptRequest::new("List the steps").with_context("Be brief");
+        let next = continuation(&request, &limited);
+        assert_eq!(next.message, "List the steps");
+        assert_eq!(next.metadata[CONTINUE_FROM_KEY], "The steps are");
+        let restated = restate(next);
+        assert!(restated.message.starts_with("List the steps"));
+        assert!(restated.message.contains("The steps are"));
+        assert!(restated.metadata.is_empty());
+        assert_eq!(restated.context.as_deref(), Some("Be brief"));
+
+        let mut more = response("steps are: first, then done.");
+        more.metadata
+            .insert(FINISH_REASON_KEY.into(), "stop".into());
+        append(&mut limited, more);
+        assert_eq!(limited.text, "The steps are: first, then done.");
+        assert_eq!(limited.tokens_used, Some(20));
+        assert_eq!(check(&limited), None);
+
+        // A cut-off word started over by the continuation
+        let mut cut = response("Then configure the serv");
+        append(&mut cut, response("server and restart it."));
+        assert_eq!(cut.text, "Then configure the server and restart it.");
+        let mut cut = response("Then configure the serv");
+        append(&mut cut, response("er and restart it."));
+        assert_eq!(cut.text, "Then configure the server and restart it.");
+
+        let mut chat = response("First,");
+        chat.conversation_id = Some("c1".into());
+        let next = continuation(&request, &chat);
+        assert_eq!(next.conversation_id.as_deref(), Some("c1"));
+        assert_eq!(next.message, CONTINUE_PROMPT);
+    }
+}
@@ -0,0 +1,404 @@
+//! Composite prompts assembled within a token budget.
+//!
+//! A prompt may combine system instructions, retrieved documents, and earlier
+//! conversation turns with the user's message. Each section has a priority
+//! and a truncation strategy; sections are given tokens in priority order, so
+//! when everything does not fit, the least important sections are cut or
+//! dropped first. The message itself is never cut. The result reports what
+//! was kept, truncated, and dropped. Sections with a chunker are cut between
+//! whole chunks rather than mid-text.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Chunker;
+use crate::cost::{estimate_tokens, CHARS_PER_TOKEN};
+use crate::error::{Error, Result};
+
+/// Budget used when none is configured or requested.
+pub const DEFAULT_MAX_TOKENS: u64 = 32_000;
+
+/// Sections cut below this many tokens are dropped instead.
+const MIN_SECTION_TOKENS: u64 = 32;
+
+/// Appended to a section cut at its end.
+const TRUNCATED_END: &str = "\n[... truncated]";
+/// Prepended to a section cut at its start.
+const TRUNCATED_START: &str = "[truncated ...]\n";
+
+/// What a context section holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionKind {
+    /// System context or instructions.
+    System,
+    /// A retrieved document or excerpt.
+    Retrieved,
+    /// Earlier conversation turns.
+    History,
+}
+
+impl SectionKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::Retrieved => "retrieved",
+            Self::History => "history",
+        }
+    }
+}
+
+/// How a section is shortened when it does not fit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Truncation {
+    /// Keep the beginning and cut the end.
+    #[default]
+    KeepStart,
+    /// Keep the end and cut the beginning (recent history).
+    KeepEnd,
+    /// Keep the section whole or not at all.
+    Drop,
+}
+
+/// Priority and truncation strategy of a kind of section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionRule {
+    /// Higher priorities are given tokens first.
+    pub priority: u32,
+    /// How sections of this kind are shortened.
+    #[serde(default)]
+    pub truncation: Truncation,
+}
+
+/// Token budget for composite prompts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextBudget {
+    /// Tokens available to the sections and message together (the target
+    /// provider's context window, or [`DEFAULT_MAX_TOKENS`], if unset).
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    /// Rules per kind of section, overriding the defaults.
+    #[serde(default)]
+    pub rules: HashMap<SectionKind, SectionRule>,
+}
+
+impl ContextBudget {
+    /// Rule applying to a kind of section: system instructions first and cut
+    /// at the end, then retrieved documents, then history cut from the start.
+    pub fn rule(&self, kind: SectionKind) -> SectionRule {
+        self.rules.get(&kind).copied().unwrap_or(match kind {
+            SectionKind::System => SectionRule {
+                priority: 100,
+                truncation: Truncation::KeepStart,
+            },
+            SectionKind::Retrieved => SectionRule {
+                priority: 50,
+                truncation: Truncation::KeepStart,
+            },
+            SectionKind::History => SectionRule {
+                priority: 25,
+                truncation: Truncation::KeepEnd,
+            },
+        })
+    }
+
+    /// Fit `sections` and `message` into `max_tokens`. Sections keep their
+    /// order in the composed context.
+    pub fn compose(
+        &self,
+        sections: &[ContextSection],
+        message: &str,
+        max_tokens: u64,
+    ) -> Result<ComposedPrompt> {
+        let message_tokens = estimate_tokens(message);
+        if message_tokens > max_tokens {
+            return Err(Error::InvalidParams(format!(
+                "message alone is about {} tokens, over the {}-token context budget",
+                message_tokens, max_tokens
+            )));
+        }
+
+        let mut order: Vec<usize> = (0..sections.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(sections[i].rule(self).priority));
+        let mut remaining = max_tokens - message_tokens;
+        let mut kept: Vec<Option<String>> = vec![None; sections.len()];
+        let mut reports: Vec<Option<SectionReport>> = vec![None; sections.len()];
+        for i in order {
+            let section = &sections[i];
+            let rule = section.rule(self);
+            let requested = estimate_tokens(&section.text);
+            let (text, outcome) = if requested <= remaining {
+                (Some(section.text.clone()), SectionOutcome::Kept)
+            } else if rule.truncation == Truncation::Drop || remaining < MIN_SECTION_TOKENS {
+                (None, SectionOutcome::Dropped)
+            } else {
+                let text = match &section.chunker {
+                    Some(chunker) => {
+                        truncate_chunks(&section.text, remaining, rule.truncation, chunker)
+                    }
+                    None => Some(truncate(&section.text, remaining, rule.truncation)),
+                };
+                let outcome = match text {
+                    Some(_) => SectionOutcome::Truncated,
+                    None => SectionOutcome::Dropped,
+                };
+                (text, outcome)
+            };
+            let kept_tokens = text.as_deref().map_or(0, estimate_tokens);
+            remaining = remaining.saturating_sub(kept_tokens);
+            kept[i] = text;
+            reports[i] = Some(SectionReport {
+                name: section.label(),
+                kind: section.kind,
+                requested_tokens: requested,
+                kept_tokens,
+                outcome,
+            });
+        }
+
+        let context = kept.into_iter().flatten().collect::<Vec<_>>().join("\n\n");
+        Ok(ComposedPrompt {
+            context: Some(context).filter(|c| !c.is_empty()),
+            message: message.to_string(),
+            max_tokens,
+            used_tokens: max_tokens - remaining,
+            sections: reports.into_iter().flatten().collect(),
+        })
+    }
+}
+
+/// Shorten a text to about `tokens` tokens, marking the cut.
+fn truncate(text: &str, tokens: u64, truncation: Truncation) -> String {
+    let marker = match truncation {
+        Truncation::KeepEnd => TRUNCATED_START,
+        _ => TRUNCATED_END,
+    };
+    let chars = (tokens as usize * CHARS_PER_TOKEN).saturating_sub(marker.chars().count());
+    let count = text.chars().count();
+    match truncation {
+        Truncation::KeepEnd => {
+            let tail: String = text.chars().skip(count.saturating_sub(chars)).collect();
+            format!("{}{}", marker, tail)
+        }
+        _ => {
+            let head: String = text.chars().take(chars).collect();
+            format!("{}{}", head, marker)
+        }
+    }
+}
+
+/// Shorten a text to whole chunks totalling about `tokens` tokens, marking
+/// the cut. `None` if not even one chunk fits.
+fn truncate_chunks(
+    text: &str,
+    tokens: u64,
+    truncation: Truncation,
+    chunker: &Chunker,
+) -> Option<String> {
+    let (marker, mut chunks): (_, Vec<_>) = match truncation {
+        Truncation::KeepEnd => (
+            TRUNCATED_START,
+            chunker.chunk(text).into_iter().rev().collect(),
+        ),
+        _ => (TRUNCATED_END, chunker.chunk(text)),
+    };
+    let mut remaining = tokens.checked_sub(estimate_tokens(marker))?;
+    let mut fitting = 0;
+    for chunk in &chunks {
+        // One more token for the line break joining chunks.
+        let cost = chunk.tokens() + 1;
+        if cost > remaining {
+            break;
+        }
+        remaining -= cost;
+        fitting += 1;
+    }
+    if fitting == 0 {
+        return None;
+    }
+    chunks.truncate(fitting);
+    let texts = chunks.into_iter().map(|chunk| chunk.text);
+    Some(match truncation {
+        Truncation::KeepEnd => {
+            let mut texts: Vec<String> = texts.collect();
+            texts.reverse();
+            format!("{}{}", marker, texts.join("\n"))
+        }
+        _ => format!("{}{}", texts.collect::<Vec<_>>().join("\n"), marker),
+    })
+}
+
+/// A section of context to fit into a prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSection {
+    /// What the section holds.
+    pub kind: SectionKind,
+    /// Label used in reports (the kind if unset).
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Section text.
+    pub text: String,
+    /// Priority overriding the kind's.
+    #[serde(default)]
+    pub priority: Option<u32>,
+    /// Truncation strategy overriding the kind's.
+    #[serde(default)]
+    pub truncation: Option<Truncation>,
+    /// Cut the section between whole chunks when truncating it.
+    #[serde(default)]
+    pub chunker: Option<Chunker>,
+}
+
+impl ContextSection {
+    /// A section of `kind` holding `text`.
+    pub fn new(kind: SectionKind, text: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: None,
+            text: text.into(),
+            priority: None,
+            truncation: None,
+            chunker: None,
+        }
+    }
+
+    /// Label used in reports.
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.kind.name().into())
+    }
+
+    fn rule(&self, budget: &ContextBudget) -> SectionRule {
+        let rule = budget.rule(self.kind);
+        SectionRule {
+            priority: self.priority.unwrap_or(rule.priority),
+            truncation: self.truncation.unwrap_or(rule.truncation),
+        }
+    }
+}
+
+/// What happened to a section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionOutcome {
+    /// Included whole.
+    Kept,
+    /// Included in part.
+    Truncated,
+    /// Left out.
+    Dropped,
+}
+
+/// How one section fared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SectionReport {
+    /// Section label.
+    pub name: String,
+    /// What the section holds.
+    pub kind: SectionKind,
+    /// Estimated tokens of the whole section.
+    pub requested_tokens: u64,
+    /// Estimated tokens included.
+    pub kept_tokens: u64,
+    /// What happened to the section.
+    pub outcome: SectionOutcome,
+}
+
+/// A prompt fitted into a token budget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComposedPrompt {
+    /// System context built from the kept sections.
+    pub context: Option<String>,
+    /// The user's message, unchanged.
+    pub message: String,
+    /// Token budget.
+    pub max_tokens: u64,
+    /// Estimated tokens used by the message and kept sections.
+    pub used_tokens: u64,
+    /// Every section, in input order.
+    pub sections: Vec<SectionReport>,
+}
+
+impl ComposedPrompt {
+    /// One-line account of the budget and any cut sections.
+    pub fn summary(&self) -> String {
+        let names = |outcome| {
+            self.sections
+                .iter()
+                .filter(|s| s.outcome == outcome)
+                .map(|s| format!("`{}` ({} tokens)", s.name, s.requested_tokens))
+                .collect::<Vec<_>>()
+        };
+        let mut summary = format!(
+            "Context budget: ~{} of {} tokens",
+            self.used_tokens, self.max_tokens
+        );
+        let truncated = names(SectionOutcome::Truncated);
+        if !truncated.is_empty() {
+            summary.push_str(&format!("; truncated {}", truncated.join(", ")));
+        }
+        let dropped = names(SectionOutcome::Dropped);
+        if !dropped.is_empty() {
+            summary.push_str(&format!("; dropped {}", dropped.join(", ")));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_within_budget() {
+        let budget = ContextBudget::default();
+        let sections = [
+            ContextSection::new(SectionKind::History, "old turn ".repeat(200)),
+            ContextSection::new(SectionKind::System, "Answer in English."),
+            ContextSection {
+                name: Some("design-doc".into()),
+                truncation: Some(Truncation::Drop),
+                ..ContextSection::new(SectionKind::Retrieved, "d".repeat(2000))
+            },
+        ];
+
+        let roomy = budget.compose(&sections, "Summarize.", 10_000).unwrap();
+        assert!(roomy
+            .sections
+            .iter()
+            .all(|s| s.outcome == SectionOutcome::Kept));
+        assert!(roomy.context.unwrap().starts_with("old turn"));
+
+        let tight = budget.compose(&sections, "Summarize.", 400).unwrap();
+        let outcomes: Vec<_> = tight.sections.iter().map(|s| s.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                SectionOutcome::Truncated,
+                SectionOutcome::Kept,
+                SectionOutcome::Dropped
+            ]
+        );
+        assert!(tight.used_tokens <= 400);
+        let context = tight.context.as_deref().unwrap();
+        assert!(context.starts_with(TRUNCATED_START));
+        assert!(context.ends_with("Answer in English."));
+        assert!(tight
+            .summary()
+            .contains("dropped `design-doc` (500 tokens)"));
+
+        let doc = format!("# One\n{}\n# Two\n{}", "a".repeat(400), "b".repeat(400));
+        let chunked = ContextSection {
+            chunker: Some(Chunker::Markdown { max_tokens: 512 }),
+            ..ContextSection::new(SectionKind::Retrieved, doc)
+        };
+        let cut = budget.compose(&[chunked], "Summarize.", 150).unwrap();
+        assert_eq!(cut.sections[0].outcome, SectionOutcome::Truncated);
+        let context = cut.context.unwrap();
+        assert!(context.ends_with(&format!("a{}", TRUNCATED_END)));
+        assert!(!context.contains('b'));
+
+        assert!(budget.compose(&[], &"x".repeat(100), 10).is_err());
+    }
+}
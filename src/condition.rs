@@ -0,0 +1,536 @@
+//! Conditions of conditional workflow steps.
+//!
+//! A condition is a small expression over the workflow context and the
+//! results of earlier steps, such as
+//! `steps.review.output contains "approved" and steps.draft.metadata.confidence >= 0.8`.
+//!
+//! - `steps.<step>.<field>` reads an earlier step, by ID or name; quote a
+//!   name with spaces as `steps["first draft"].output`. Fields are `output`,
+//!   `provider`, `state`, `duration_ms`, and `metadata.<key>`.
+//! - `context.<key>` reads the workflow context.
+//! - Paths descend into JSON objects by key and into arrays by index; a
+//!   missing value is `null`.
+//! - Values compare with `==`, `!=`, `<`, `<=`, `>`, `>=`, and `contains`,
+//!   and combine with `and`, `or`, `not`, and parentheses. Text that reads
+//!   as a number compares as one.
+//! - A value on its own holds unless it is `null`, `false`, zero, or empty.
+
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use crate::error::{Error, Result};
+use crate::workflow::Workflow;
+
+/// A parsed condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    expr: Expr,
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid =
+            |reason: String| Error::InvalidParams(format!("invalid condition '{}': {}", s, reason));
+        let tokens = tokenize(s).map_err(invalid)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or().map_err(invalid)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(invalid(format!("unexpected {}", token.describe())));
+        }
+        Ok(Self { expr })
+    }
+}
+
+impl Condition {
+    /// Whether the condition holds for the workflow's current step. Fails
+    /// if it reads a step that is not an earlier step.
+    pub fn evaluate(&self, workflow: &Workflow) -> Result<bool> {
+        Ok(truthy(&self.value(workflow)?))
+    }
+
+    /// Value of the expression for the workflow's current step, such as the
+    /// output a path reads.
+    pub fn value(&self, workflow: &Workflow) -> Result<Value> {
+        self.expr.value(workflow)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Path(Root, Vec<String>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Comparison, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Root {
+    Steps,
+    Context,
+}
+
+impl Expr {
+    fn value(&self, workflow: &Workflow) -> Result<Value> {
+        Ok(match self {
+            Self::Literal(value) => value.clone(),
+            Self::Path(root, segments) => lookup(workflow, *root, segments)?,
+            Self::Not(expr) => Value::Bool(!truthy(&expr.value(workflow)?)),
+            Self::And(a, b) => {
+                Value::Bool(truthy(&a.value(workflow)?) && truthy(&b.value(workflow)?))
+            }
+            Self::Or(a, b) => {
+                Value::Bool(truthy(&a.value(workflow)?) || truthy(&b.value(workflow)?))
+            }
+            Self::Compare(a, comparison, b) => Value::Bool(compare(
+                &a.value(workflow)?,
+                *comparison,
+                &b.value(workflow)?,
+            )),
+        })
+    }
+}
+
+/// The value at a path.
+fn lookup(workflow: &Workflow, root: Root, segments: &[String]) -> Result<Value> {
+    let (base, rest) = match root {
+        Root::Context => (json!(workflow.context), segments),
+        Root::Steps => {
+            let (id, rest) = segments
+                .split_first()
+                .ok_or_else(|| Error::Workflow("condition reads `steps` without a step".into()))?;
+            let step = workflow
+                .earlier_steps()
+                .into_iter()
+                .find(|s| &s.id == id || &s.name == id)
+                .ok_or_else(|| {
+                    Error::Workflow(format!("condition reads no earlier step '{}'", id))
+                })?;
+            let result = step.result.as_ref();
+            let base = json!({
+                "id": step.id,
+                "name": step.name,
+                "state": step.state.name(),
+                "output": result.map(|r| r.output.as_str()),
+                "provider": result.and_then(|r| r.provider.as_deref()),
+                "duration_ms": result.map(|r| r.duration_ms),
+                "metadata": result.map(|r| &r.metadata),
+            });
+            (base, rest)
+        }
+    };
+    let mut value = &base;
+    for segment in rest {
+        value = match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        }
+        .unwrap_or(&Value::Null);
+    }
+    Ok(value.clone())
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.trim().is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+/// A value as a number, reading numeric text as one.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn compare(a: &Value, comparison: Comparison, b: &Value) -> bool {
+    use std::cmp::Ordering;
+
+    let ordering = match (number(a), number(b), a, b) {
+        (Some(x), Some(y), _, _) => x.partial_cmp(&y),
+        (_, _, Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    };
+    match comparison {
+        Comparison::Eq => ordering.map_or(a == b, |o| o == Ordering::Equal),
+        Comparison::Ne => ordering.map_or(a != b, |o| o != Ordering::Equal),
+        Comparison::Lt => ordering == Some(Ordering::Less),
+        Comparison::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Comparison::Gt => ordering == Some(Ordering::Greater),
+        Comparison::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        Comparison::Contains => match (a, b) {
+            (Value::String(haystack), Value::String(needle)) => haystack.contains(needle.as_str()),
+            (Value::String(haystack), Value::Number(needle)) => {
+                haystack.contains(&needle.to_string())
+            }
+            (Value::Array(items), needle) => items
+                .iter()
+                .any(|item| compare(item, Comparison::Eq, needle)),
+            (Value::Object(map), Value::String(key)) => map.contains_key(key),
+            _ => false,
+        },
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Dot,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Compare(Comparison),
+    And,
+    Or,
+    Not,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Self::Ident(name) => format!("'{}'", name),
+            Self::Str(s) => format!("\"{}\"", s),
+            Self::Num(n) => n.to_string(),
+            Self::Dot => "'.'".into(),
+            Self::LBracket => "'['".into(),
+            Self::RBracket => "']'".into(),
+            Self::LParen => "'('".into(),
+            Self::RParen => "')'".into(),
+            Self::Compare(_) => "comparison".into(),
+            Self::And => "'and'".into(),
+            Self::Or => "'or'".into(),
+            Self::Not => "'not'".into(),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('.', _) => (Token::Dot, 1),
+            ('[', _) => (Token::LBracket, 1),
+            (']', _) => (Token::RBracket, 1),
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('=', Some('=')) => (Token::Compare(Comparison::Eq), 2),
+            ('!', Some('=')) => (Token::Compare(Comparison::Ne), 2),
+            ('<', Some('=')) => (Token::Compare(Comparison::Le), 2),
+            ('>', Some('=')) => (Token::Compare(Comparison::Ge), 2),
+            ('<', _) => (Token::Compare(Comparison::Lt), 1),
+            ('>', _) => (Token::Compare(Comparison::Gt), 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('!', _) => (Token::Not, 1),
+            ('"' | '\'', _) => {
+                let mut text = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err("unterminated string".into()),
+                        Some('\\') => {
+                            let escaped = chars.get(j + 1).ok_or("unterminated string")?;
+                            text.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                other => *other,
+                            });
+                            j += 2;
+                        }
+                        Some(q) if *q == c => break,
+                        Some(other) => {
+                            text.push(*other);
+                            j += 1;
+                        }
+                    }
+                }
+                (Token::Str(text), j + 1 - i)
+            }
+            (c, _)
+                if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) =>
+            {
+                let len = chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '.')
+                    .count()
+                    + 1;
+                let text: String = chars[i..i + len].iter().collect();
+                // A step named by number, as in `steps.2`, stays a name
+                let after_dot = tokens.last() == Some(&Token::Dot);
+                match text.parse::<f64>() {
+                    Ok(n) if !after_dot => (Token::Num(n), len),
+                    _ => (Token::Ident(text), len),
+                }
+            }
+            (c, _) if c.is_alphanumeric() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || **c == '_' || **c == '-')
+                    .count();
+                let word: String = chars[i..i + len].iter().collect();
+                let token = match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "contains" => Token::Compare(Comparison::Contains),
+                    _ => Token::Ident(word),
+                };
+                (token, len)
+            }
+            (c, _) => return Err(format!("unexpected '{}'", c)),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+type Parsed<T> = std::result::Result<T, String>;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Parsed<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!(
+                "expected {}, found {}",
+                expected.describe(),
+                token.describe()
+            )),
+            None => Err(format!("expected {} at the end", expected.describe())),
+        }
+    }
+
+    fn or(&mut self) -> Parsed<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Parsed<Expr> {
+        let mut expr = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Parsed<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Parsed<Expr> {
+        let left = self.operand()?;
+        if let Some(Token::Compare(comparison)) = self.peek().cloned() {
+            self.pos += 1;
+            let right = self.operand()?;
+            return Ok(Expr::Compare(Box::new(left), comparison, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Parsed<Expr> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Num(n)) => Ok(Expr::Literal(json!(n))),
+            Some(Token::Ident(word)) => match word.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "steps" => Ok(Expr::Path(Root::Steps, self.segments()?)),
+                "context" => Ok(Expr::Path(Root::Context, self.segments()?)),
+                other => Err(format!(
+                    "unknown name '{}'; paths start with steps or context",
+                    other
+                )),
+            },
+            Some(token) => Err(format!("unexpected {}", token.describe())),
+            None => Err("unexpected end".into()),
+        }
+    }
+
+    fn segments(&mut self) -> Parsed<Vec<String>> {
+        let mut segments = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Dot) => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(Token::Ident(name)) => segments.push(name),
+                        Some(token) => {
+                            return Err(format!(
+                                "expected a name after '.', found {}",
+                                token.describe()
+                            ))
+                        }
+                        None => return Err("expected a name after '.'".into()),
+                    }
+                }
+                Some(Token::LBracket) => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(Token::Str(key)) => segments.push(key),
+                        Some(Token::Num(n)) if n >= 0.0 && n.fract() == 0.0 => {
+                            segments.push((n as usize).to_string())
+                        }
+                        _ => return Err("expected a quoted key or an index in '[]'".into()),
+                    }
+                    self.expect(Token::RBracket)?;
+                }
+                _ => return Ok(segments),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::AgentOrchestrator;
+    use crate::workflow::{StepResult, StepState, WorkflowState, WorkflowStep};
+    use std::collections::HashMap;
+
+    fn reviewed() -> Workflow {
+        let mut workflow = Workflow::new("release");
+        let mut review = WorkflowStep::prompt("first review", "Review it");
+        review.complete(StepResult {
+            output: "Approved, with score 8".into(),
+            provider: Some("claude".into()),
+            responses: None,
+            duration_ms: 1200,
+            metadata: HashMap::from([("confidence".to_string(), json!(0.9))]),
+        });
+        workflow.add_step(review);
+        workflow.current_step = 1;
+        workflow.set_context("attempts", json!(2));
+        workflow
+    }
+
+    #[test]
+    fn test_conditions_read_steps_and_context() {
+        let workflow = reviewed();
+        let holds = |condition: &str| {
+            condition
+                .parse::<Condition>()
+                .unwrap()
+                .evaluate(&workflow)
+                .unwrap()
+        };
+
+        assert!(holds(r#"steps["first review"].output contains "Approved""#));
+        assert!(holds(
+            "steps['first review'].metadata.confidence >= 0.8 and context.attempts < 3"
+        ));
+        assert!(holds(r#"steps["first review"].state == "completed""#));
+        assert!(!holds(
+            r#"not (steps["first review"].provider == "claude" || false)"#
+        ));
+        assert!(!holds("context.missing"));
+        assert!(holds("context.missing == null"));
+        assert!(holds(r#"context.attempts == "2""#));
+
+        let unknown: Condition = "steps.deploy.output".parse().unwrap();
+        assert!(unknown.evaluate(&workflow).is_err());
+        for invalid in [
+            "steps.a ==",
+            "output contains 'x'",
+            "(context.a",
+            "context.a = 1",
+        ] {
+            assert!(invalid.parse::<Condition>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conditional_step_jumps() {
+        let orchestrator = AgentOrchestrator::new();
+        let mut workflow = reviewed();
+        workflow.add_step(WorkflowStep::conditional(
+            "gate",
+            r#"steps["first review"].output contains "Rejected""#,
+            "revise",
+            Some("publish".into()),
+        ));
+        workflow.add_step(WorkflowStep::prompt("revise", "Revise it"));
+        workflow.add_step(WorkflowStep::review("publish", "Publish?"));
+        let id = orchestrator.start_workflow(workflow).await.unwrap();
+
+        let result = orchestrator.execute_workflow_step(&id).await.unwrap();
+        assert_eq!(result.metadata["condition"], json!(false));
+        let workflow = orchestrator.get_workflow(&id).await.unwrap();
+        assert_eq!(workflow.current().unwrap().name, "publish");
+        assert_eq!(workflow.steps[2].state, StepState::Skipped);
+        assert_eq!(workflow.state, WorkflowState::Running);
+
+        // Jumps go forward to a step that exists
+        let mut backwards = reviewed();
+        backwards.add_step(WorkflowStep::conditional(
+            "gate",
+            "true",
+            "first review",
+            None,
+        ));
+        assert!(orchestrator.start_workflow(backwards).await.is_err());
+    }
+}
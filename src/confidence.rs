@@ -0,0 +1,126 @@
+//! Answer confidence estimation.
+//!
+//! A workflow step may ask for a verification pass: the provider that
+//! answered, or another one, rates how confident it is that the answer is
+//! correct and complete for the question asked. The rating is recorded with
+//! the step's responses, and a step rated below its threshold pauses the
+//! workflow for review, fails, or carries on, as the step's check says.
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Step result metadata key holding the answer's confidence (0.0-1.0).
+pub const CONFIDENCE_KEY: &str = "confidence";
+
+/// Step result metadata key holding the provider that rated the answer.
+pub const RATER_KEY: &str = "confidence_rater";
+
+/// Step result metadata key set when the confidence is below the threshold.
+pub const LOW_CONFIDENCE_KEY: &str = "low_confidence";
+
+/// What a step does when its answer is rated below the threshold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LowConfidence {
+    /// Pause the workflow until a reviewer approves the answer.
+    #[default]
+    Review,
+    /// Fail the step.
+    Fail,
+    /// Record the confidence and carry on.
+    Continue,
+}
+
+/// A step's confidence check.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfidenceCheck {
+    /// Provider rating the answer; the one that answered when `None`.
+    pub provider: Option<String>,
+    /// Lowest acceptable confidence (0.0-1.0).
+    pub min_confidence: f64,
+    /// What happens below `min_confidence`.
+    pub on_low: LowConfidence,
+}
+
+impl Default for ConfidenceCheck {
+    fn default() -> Self {
+        Self {
+            provider: None,
+            min_confidence: 0.6,
+            on_low: LowConfidence::Review,
+        }
+    }
+}
+
+impl ConfidenceCheck {
+    /// Check that the rater is a known provider and the threshold a
+    /// confidence.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.min_confidence) {
+            return Err(Error::InvalidParams(format!(
+                "min_confidence must be between 0 and 1, got {}",
+                self.min_confidence
+            )));
+        }
+        self.rater(None).map(|_| ())
+    }
+
+    /// Provider rating an answer by `answered`, if any is known.
+    pub fn rater(&self, answered: Option<Provider>) -> Result<Option<Provider>> {
+        match &self.provider {
+            Some(name) => Provider::from_string(name)
+                .map(Some)
+                .ok_or_else(|| Error::InvalidParams(format!("unknown rating provider: {}", name))),
+            None => Ok(answered),
+        }
+    }
+}
+
+/// Build the prompt asking a provider to rate an answer to `question`.
+pub fn confidence_prompt(question: &str, answer: &str) -> String {
+    format!(
+        "How confident are you that the answer below is correct and complete \
+         for the question? Check it against the question, then rate it on a \
+         scale from 0 to 10, where 10 means certainly right. Reply with the \
+         number first, then a one-sentence reason.\n\n\
+         Question:\n{}\n\nAnswer:\n{}",
+        question, answer
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confidence_check() {
+        let check: ConfidenceCheck = serde_json::from_str(r#"{ "on_low": "fail" }"#).unwrap();
+        assert_eq!(check.min_confidence, 0.6);
+        assert_eq!(check.on_low, LowConfidence::Fail);
+        check.validate().unwrap();
+        assert_eq!(
+            check.rater(Some(Provider::Claude)).unwrap(),
+            Some(Provider::Claude)
+        );
+
+        let second = ConfidenceCheck {
+            provider: Some("gemini".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            second.rater(Some(Provider::Claude)).unwrap(),
+            Some(Provider::Gemini)
+        );
+        let broken = ConfidenceCheck {
+            min_confidence: 6.0,
+            ..Default::default()
+        };
+        assert!(broken.validate().is_err());
+
+        let prompt = confidence_prompt("2+2?", "4");
+        assert!(prompt.ends_with("Question:\n2+2?\n\nAnswer:\n4"));
+    }
+}
@@ -0,0 +1,439 @@
+//! Agreement metrics and answer selection for multi-provider consensus.
+//!
+//! Responses are compared pairwise. Lexical word overlap (Jaccard) is used
+//! today; [`cosine_similarity`] can score embedding vectors once an embedding
+//! backend is available.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How the consensus answer is chosen among responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusStrategy {
+    /// The longest (usually most complete) response.
+    #[default]
+    Longest,
+    /// The response most similar to all the others.
+    SimilarityCluster,
+    /// The response a judge provider grades highest.
+    Judge,
+    /// The most common answer after normalization; ties go to the most
+    /// central response. Suited to short factual answers.
+    Vote,
+}
+
+impl fmt::Display for ConsensusStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Longest => "longest",
+            Self::SimilarityCluster => "similarity_cluster",
+            Self::Judge => "judge",
+            Self::Vote => "vote",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Consensus strategy and its parameters.
+#[derive(Debug, Clone, Default)]
+pub struct ConsensusOptions {
+    /// How to choose the consensus answer.
+    pub strategy: ConsensusStrategy,
+    /// Judge for the `judge` strategy (defaults to the grading policy's judge).
+    pub judge_provider: Option<String>,
+}
+
+/// Jaccard similarity of the lowercase word sets of two texts.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (word_set(a), word_set(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+/// Cosine similarity of two embedding vectors (0.0 if either is zero).
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
+    }
+}
+
+/// Lowercase alphanumeric words of a text.
+pub(crate) fn word_set(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Agreement among a set of responses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agreement {
+    /// Mean pairwise similarity across all responses (0.0-1.0).
+    pub score: f64,
+    /// Per-response mean similarity to the other responses, in input order.
+    pub confidence: Vec<f64>,
+}
+
+/// Measure agreement among responses with the given similarity function.
+///
+/// A single response trivially agrees with itself and scores 1.0.
+pub fn agreement(texts: &[&str], sim: impl Fn(&str, &str) -> f64) -> Agreement {
+    let n = texts.len();
+    if n < 2 {
+        return Agreement {
+            score: 1.0,
+            confidence: vec![1.0; n],
+        };
+    }
+
+    let mut totals = vec![0.0; n];
+    let mut sum = 0.0;
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let s = sim(texts[i], texts[j]);
+            totals[i] += s;
+            totals[j] += s;
+            sum += s;
+        }
+    }
+
+    let pairs = (n * (n - 1) / 2) as f64;
+    Agreement {
+        score: sum / pairs,
+        confidence: totals.into_iter().map(|t| t / (n - 1) as f64).collect(),
+    }
+}
+
+/// Minimum similarity for two statements to express the same claim.
+const SUPPORT_THRESHOLD: f64 = 0.5;
+
+/// Minimum topic similarity for two statements to be compared for conflict.
+const TOPIC_THRESHOLD: f64 = 0.6;
+
+/// Statements shorter than this many words are not treated as claims.
+const MIN_CLAIM_WORDS: usize = 4;
+
+/// Maximum partial claims reported.
+const MAX_PARTIAL_CLAIMS: usize = 10;
+
+/// Words that flip the polarity of a statement.
+const NEGATIONS: &[&str] = &[
+    "not", "no", "never", "none", "cannot", "isn", "aren", "wasn", "doesn", "don", "didn", "won",
+];
+
+/// A statement made by one provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statement {
+    /// Provider name.
+    pub provider: String,
+    /// Statement text.
+    pub text: String,
+}
+
+/// A claim made by some, but not all, providers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialClaim {
+    /// Claim text, as first stated.
+    pub text: String,
+    /// Providers making the claim.
+    pub held_by: Vec<String>,
+    /// Providers not making it.
+    pub missing_from: Vec<String>,
+}
+
+/// Two providers stating conflicting things about the same topic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contradiction {
+    /// First statement.
+    pub first: Statement,
+    /// Conflicting statement.
+    pub second: Statement,
+}
+
+/// Points of disagreement among responses.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Disagreements {
+    /// Claims held by only some providers.
+    pub partial_claims: Vec<PartialClaim>,
+    /// Direct contradictions between providers.
+    pub contradictions: Vec<Contradiction>,
+}
+
+impl Disagreements {
+    /// Check if the responses agree on everything detected.
+    pub fn is_empty(&self) -> bool {
+        self.partial_claims.is_empty() && self.contradictions.is_empty()
+    }
+}
+
+/// Find claims held by only some providers and direct contradictions.
+///
+/// Responses are split into sentences. Two sentences express the same claim
+/// when their words overlap enough and they don't conflict; they contradict
+/// when they share a topic but differ in negation or in the numbers stated.
+pub fn find_disagreements(responses: &[(&str, &str)]) -> Disagreements {
+    let claims: Vec<(&str, Vec<String>)> = responses
+        .iter()
+        .map(|(provider, text)| (*provider, split_claims(text)))
+        .collect();
+    let mut result = Disagreements::default();
+    if claims.len() < 2 {
+        return result;
+    }
+
+    for (i, (provider, sentences)) in claims.iter().enumerate() {
+        for sentence in sentences {
+            // Contradictions, each pair reported once
+            for (other, other_sentences) in &claims[i + 1..] {
+                for other_sentence in other_sentences {
+                    if conflicts(sentence, other_sentence) {
+                        result.contradictions.push(Contradiction {
+                            first: Statement {
+                                provider: provider.to_string(),
+                                text: sentence.clone(),
+                            },
+                            second: Statement {
+                                provider: other.to_string(),
+                                text: other_sentence.clone(),
+                            },
+                        });
+                    }
+                }
+            }
+
+            if result.partial_claims.len() >= MAX_PARTIAL_CLAIMS
+                || result
+                    .partial_claims
+                    .iter()
+                    .any(|c| supports(&c.text, sentence))
+            {
+                continue;
+            }
+            let (held_by, missing_from): (Vec<_>, Vec<_>) = claims
+                .iter()
+                .map(|(p, s)| (p.to_string(), s.iter().any(|o| supports(sentence, o))))
+                .partition(|(_, held)| *held);
+            if !missing_from.is_empty() {
+                result.partial_claims.push(PartialClaim {
+                    text: sentence.clone(),
+                    held_by: held_by.into_iter().map(|(p, _)| p).collect(),
+                    missing_from: missing_from.into_iter().map(|(p, _)| p).collect(),
+                });
+            }
+        }
+    }
+
+    result
+}
+
+/// Split a response into claim-sized sentences.
+pub(crate) fn split_claims(text: &str) -> Vec<String> {
+    let mut claims: Vec<String> = Vec::new();
+    for sentence in text.split_inclusive(['.', '!', '?', '\n']) {
+        let sentence = sentence
+            .trim()
+            .trim_start_matches(['-', '*', '#', '>', ' '])
+            .trim();
+        if sentence.split_whitespace().count() >= MIN_CLAIM_WORDS
+            && !claims.iter().any(|c| c == sentence)
+        {
+            claims.push(sentence.to_string());
+        }
+    }
+    claims
+}
+
+/// Check if two statements express the same claim.
+fn supports(a: &str, b: &str) -> bool {
+    similarity(a, b) >= SUPPORT_THRESHOLD && !conflicts(a, b)
+}
+
+/// Check if two statements share a topic but disagree.
+fn conflicts(a: &str, b: &str) -> bool {
+    let (a, b) = (word_set(a), word_set(b));
+    let is_marker = |w: &String| NEGATIONS.contains(&w.as_str()) || w.parse::<f64>().is_ok();
+    let topic = |s: &HashSet<String>| -> HashSet<String> {
+        s.iter().filter(|w| !is_marker(w)).cloned().collect()
+    };
+    let (ta, tb) = (topic(&a), topic(&b));
+    let union = ta.union(&tb).count();
+    if union == 0 || (ta.intersection(&tb).count() as f64 / union as f64) < TOPIC_THRESHOLD {
+        return false;
+    }
+
+    let negated = |s: &HashSet<String>| s.iter().any(|w| NEGATIONS.contains(&w.as_str()));
+    let numbers = |s: &HashSet<String>| -> HashSet<String> {
+        s.iter()
+            .filter(|w| w.parse::<f64>().is_ok())
+            .cloned()
+            .collect()
+    };
+    let (na, nb) = (numbers(&a), numbers(&b));
+    negated(&a) != negated(&b) || (!na.is_empty() && !nb.is_empty() && na != nb)
+}
+
+/// Select the consensus response without consulting providers.
+///
+/// The `judge` strategy needs grades from a provider; without them it falls
+/// back to the most central response. Returns `None` if there are no texts.
+pub fn select(strategy: ConsensusStrategy, texts: &[&str], agreement: &Agreement) -> Option<usize> {
+    match strategy {
+        ConsensusStrategy::Longest => argmax(texts.iter().map(|t| t.len() as f64)),
+        ConsensusStrategy::SimilarityCluster | ConsensusStrategy::Judge => {
+            argmax(agreement.confidence.iter().copied())
+        }
+        ConsensusStrategy::Vote => {
+            let answers: Vec<String> = texts.iter().map(|t| normalize(t)).collect();
+            let mut votes: HashMap<&str, usize> = HashMap::new();
+            for answer in &answers {
+                *votes.entry(answer.as_str()).or_default() += 1;
+            }
+            // Votes dominate; confidence (at most 1.0) only breaks ties
+            argmax(
+                answers
+                    .iter()
+                    .zip(&agreement.confidence)
+                    .map(|(a, c)| votes[a.as_str()] as f64 * 2.0 + c),
+            )
+        }
+    }
+}
+
+/// Default minimum similarity for two responses to count as duplicates.
+pub const DUPLICATE_THRESHOLD: f64 = 0.85;
+
+/// Group near-identical texts: each text joins the first group whose first
+/// member it is at least `threshold` similar to. Groups, and the indices
+/// within them, keep input order.
+pub fn group_duplicates(texts: &[&str], threshold: f64) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, text) in texts.iter().enumerate() {
+        match groups
+            .iter_mut()
+            .find(|g| similarity(texts[g[0]], text) >= threshold)
+        {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+    groups
+}
+
+/// Index of the largest value (first on ties).
+pub(crate) fn argmax(values: impl Iterator<Item = f64>) -> Option<usize> {
+    values
+        .enumerate()
+        .fold(None, |best: Option<(usize, f64)>, (i, v)| match best {
+            Some((_, b)) if b >= v => best,
+            _ => Some((i, v)),
+        })
+        .map(|(i, _)| i)
+}
+
+/// Lowercase words of a text, in order, joined by single spaces.
+fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        assert!((similarity("the capital is Paris", "Paris is the capital") - 1.0).abs() < 1e-9);
+        assert!((similarity("a b", "b c") - 1.0 / 3.0).abs() < 1e-9);
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_group_duplicates() {
+        let texts = [
+            "Use nouns for resources and HTTP verbs for actions.",
+            "Version the API from day one.",
+            "use nouns for resources, and HTTP verbs for actions",
+        ];
+        assert_eq!(
+            group_duplicates(&texts, DUPLICATE_THRESHOLD),
+            vec![vec![0, 2], vec![1]]
+        );
+        assert_eq!(group_duplicates(&texts, 1.1).len(), 3);
+    }
+
+    #[test]
+    fn test_agreement_flags_outlier() {
+        let texts = ["Paris is the capital", "the capital is Paris", "Lyon"];
+        let result = agreement(&texts, similarity);
+
+        assert!((result.score - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(result.confidence[0], 0.5);
+        assert_eq!(result.confidence[2], 0.0);
+        assert_eq!(agreement(&["only"], similarity).score, 1.0);
+    }
+
+    #[test]
+    fn test_select_strategies() {
+        let texts = ["Paris.", "paris", "It is Lyon, a large city in France"];
+        let agreement = agreement(&texts, similarity);
+
+        assert_eq!(
+            select(ConsensusStrategy::Longest, &texts, &agreement),
+            Some(2)
+        );
+        assert_eq!(select(ConsensusStrategy::Vote, &texts, &agreement), Some(0));
+        assert_eq!(
+            select(ConsensusStrategy::SimilarityCluster, &texts, &agreement),
+            Some(0)
+        );
+        assert_eq!(select(ConsensusStrategy::Vote, &[], &agreement), None);
+    }
+
+    #[test]
+    fn test_find_disagreements() {
+        let responses = [
+            (
+                "claude",
+                "The bridge opened in 1937. It is painted orange for visibility.",
+            ),
+            (
+                "gemini",
+                "The bridge opened in 1937. It was designed by Joseph Strauss himself.",
+            ),
+            (
+                "grok",
+                "The bridge opened in 1933. It is painted orange for visibility.",
+            ),
+        ];
+        let report = find_disagreements(&responses);
+
+        assert_eq!(report.contradictions.len(), 2);
+        assert!(report
+            .contradictions
+            .iter()
+            .all(|c| c.second.provider == "grok" && c.second.text.contains("1933")));
+
+        let strauss = report
+            .partial_claims
+            .iter()
+            .find(|c| c.text.contains("Strauss"))
+            .unwrap();
+        assert_eq!(strauss.held_by, ["gemini"]);
+        assert_eq!(strauss.missing_from, ["claude", "grok"]);
+        assert!(find_disagreements(&responses[..1]).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+//! Token and cost estimation for provider requests.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::{PROMPT_TOKENS_KEY, RESPONSE_TOKENS_KEY};
+use crate::workflow::StepResult;
+
+/// Approximate characters per token for English text and code.
+pub(crate) const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimate the number of tokens in a text.
+pub fn estimate_tokens(text: &str) -> u64 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u64
+}
+
+/// Per-provider pricing used to estimate request cost.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostModel {
+    /// USD per 1k tokens, keyed by lowercase provider name. Missing entries
+    /// (such as subscription-based browser providers) cost nothing.
+    #[serde(default)]
+    pub usd_per_1k_tokens: HashMap<String, f64>,
+}
+
+impl CostModel {
+    /// Set the rate for a provider.
+    pub fn with_rate(mut self, provider: impl Into<String>, usd_per_1k_tokens: f64) -> Self {
+        self.usd_per_1k_tokens
+            .insert(provider.into().to_lowercase(), usd_per_1k_tokens);
+        self
+    }
+
+    /// Whether a provider, or model, has a rate.
+    pub fn has_rate(&self, provider: &str) -> bool {
+        self.usd_per_1k_tokens.contains_key(&provider.to_lowercase())
+    }
+
+    /// Rate for a provider in USD per 1k tokens.
+    pub fn rate(&self, provider: &str) -> f64 {
+        self.usd_per_1k_tokens
+            .get(&provider.to_lowercase())
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Highest configured rate, used when the provider is not yet known.
+    pub fn max_rate(&self) -> f64 {
+        self.usd_per_1k_tokens.values().copied().fold(0.0, f64::max)
+    }
+
+    /// Estimate the cost of sending `tokens` tokens to a provider.
+    pub fn estimate(&self, provider: Option<&str>, tokens: u64) -> f64 {
+        let rate = provider.map_or_else(|| self.max_rate(), |p| self.rate(p));
+        rate * tokens as f64 / 1000.0
+    }
+
+    /// Estimate the cost of a completed workflow step from the token counts
+    /// in its response metadata.
+    pub fn estimate_step(&self, result: &StepResult) -> f64 {
+        match &result.responses {
+            Some(responses) => responses
+                .iter()
+                .map(|r| self.estimate(Some(&r.provider), token_total(&r.metadata)))
+                .sum(),
+            None => self.estimate(result.provider.as_deref(), token_total(&result.metadata)),
+        }
+    }
+}
+
+/// Prompt plus response tokens recorded in response metadata.
+fn token_total(metadata: &HashMap<String, serde_json::Value>) -> u64 {
+    [PROMPT_TOKENS_KEY, RESPONSE_TOKENS_KEY]
+        .iter()
+        .filter_map(|key| metadata.get(*key).and_then(|v| v.as_u64()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        let model = CostModel::default().with_rate("ChatGPT", 0.01);
+
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(model.estimate(Some("claude"), 1000), 0.0);
+        assert!((model.estimate(Some("chatgpt"), 2000) - 0.02).abs() < 1e-9);
+        assert!((model.estimate(None, 1000) - 0.01).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,151 @@
+//! Structured critique of content by one provider or a panel.
+//!
+//! The critic is asked for a JSON list of findings so the result can drive
+//! further work (such as a revision pass) instead of being read as prose.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Observation or nitpick.
+    Info,
+    /// Worth fixing.
+    Minor,
+    /// Should be fixed.
+    Major,
+    /// Must be fixed.
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Minor => "minor",
+            Severity::Major => "major",
+            Severity::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A single problem found by a critic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// Where in the content the problem is (line, section, or quote).
+    #[serde(default)]
+    pub location: Option<String>,
+    /// What is wrong.
+    pub issue: String,
+    /// How to fix it.
+    #[serde(default)]
+    pub suggestion: Option<String>,
+    /// Provider that reported the finding.
+    #[serde(default)]
+    pub provider: String,
+}
+
+/// Findings from every critic, most severe first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Critique {
+    /// All findings, most severe first.
+    pub findings: Vec<Finding>,
+    /// Providers that returned a critique.
+    pub critics: Vec<String>,
+    /// Providers that failed, with the error.
+    pub failures: Vec<(String, String)>,
+}
+
+impl Critique {
+    /// Add a critic's findings, keeping the list ordered by severity.
+    pub fn add(&mut self, provider: &str, findings: Vec<Finding>) {
+        self.critics.push(provider.to_string());
+        self.findings.extend(findings);
+        self.findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    }
+
+    /// Number of findings at or above a severity.
+    pub fn count_at_least(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity >= severity)
+            .count()
+    }
+}
+
+/// Build the prompt asking a critic for structured findings.
+pub fn critique_prompt(content: &str, criteria: &[String]) -> String {
+    let criteria = if criteria.is_empty() {
+        "correctness, clarity, completeness".to_string()
+    } else {
+        criteria.join("; ")
+    };
+    format!(
+        "Critique the content below against these criteria: {}.\n\n\
+         Respond with only a JSON array of findings, most serious first. Each finding \
+         is an object with \"severity\" (one of \"critical\", \"major\", \"minor\", \
+         \"info\"), \"location\" (line, section, or short quote), \"issue\", and \
+         \"suggestion\". Respond with [] if there are no problems.\n\n\
+         Content:\n\"\"\"\n{}\n\"\"\"",
+        criteria, content
+    )
+}
+
+/// Parse a critic's response into findings.
+///
+/// Accepts a bare JSON array or one wrapped in prose or a code fence. A
+/// response without parseable JSON becomes a single `info` finding holding
+/// the text, so nothing the critic said is lost.
+pub fn parse_findings(provider: &str, text: &str) -> Vec<Finding> {
+    let json = match (text.find('['), text.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text,
+    };
+
+    match serde_json::from_str::<Vec<Finding>>(json) {
+        Ok(mut findings) => {
+            for finding in &mut findings {
+                finding.provider = provider.to_string();
+            }
+            findings
+        }
+        Err(_) => vec![Finding {
+            severity: Severity::Info,
+            location: None,
+            issue: text.trim().to_string(),
+            suggestion: None,
+            provider: provider.to_string(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_findings() {
+        let text = "Here you go:\n```json\n[\
+            {\"severity\": \"minor\", \"location\": \"line 2\", \"issue\": \"typo\", \"suggestion\": \"fix it\"},\
+            {\"severity\": \"critical\", \"issue\": \"wrong formula\"}\
+            ]\n```";
+
+        let mut critique = Critique::default();
+        critique.add("claude", parse_findings("claude", text));
+        assert_eq!(critique.findings.len(), 2);
+        assert_eq!(critique.findings[0].severity, Severity::Critical);
+        assert_eq!(critique.findings[0].provider, "claude");
+        assert_eq!(critique.count_at_least(Severity::Major), 1);
+
+        let prose = parse_findings("grok", "Looks fine to me.");
+        assert_eq!(prose.len(), 1);
+        assert_eq!(prose[0].severity, Severity::Info);
+        assert!(parse_findings("gemini", "[]").is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Startup self-test diagnostics.
+//!
+//! A self-test checks, before any work arrives, what would otherwise fail
+//! halfway through a workflow: that state can be stored, that a browser
+//! starts, that configured credentials are present, and that at least one
+//! provider answers. Each check that finds a problem says how to fix it. The
+//! results are reported in the MCP `initialize` result and served as a
+//! resource.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// URI the latest self-test is served under as a resource.
+pub const DIAGNOSTICS_URI: &str = "diagnostics://self-test";
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// The check passed.
+    Ok,
+    /// The check was not needed with this configuration.
+    Skipped,
+    /// Something may fail later, but work can start.
+    Warning,
+    /// Something will fail when it is used.
+    Failed,
+}
+
+impl CheckStatus {
+    /// Marker shown before the check's name.
+    fn icon(self) -> &'static str {
+        match self {
+            Self::Ok => "✓",
+            Self::Skipped => "–",
+            Self::Warning => "⚠",
+            Self::Failed => "✗",
+        }
+    }
+}
+
+/// Result of one check.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Check {
+    /// What was checked, such as `storage`.
+    pub name: String,
+    /// How the check went.
+    pub status: CheckStatus,
+    /// What was found.
+    pub detail: String,
+    /// How to fix a failure or warning.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    /// A check that passed.
+    pub fn ok(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Ok, detail, None)
+    }
+
+    /// A check that was not needed.
+    pub fn skipped(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Skipped, detail, None)
+    }
+
+    /// A check that found something that may fail later.
+    pub fn warning(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warning, detail, Some(fix.into()))
+    }
+
+    /// A check that failed.
+    pub fn failed(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Failed, detail, Some(fix.into()))
+    }
+
+    fn new(
+        name: &str,
+        status: CheckStatus,
+        detail: impl Into<String>,
+        fix: Option<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            fix,
+        }
+    }
+
+    /// Whether the check found a problem.
+    pub fn is_problem(&self) -> bool {
+        matches!(self.status, CheckStatus::Warning | CheckStatus::Failed)
+    }
+
+    /// One line describing the check, with its fix.
+    pub fn line(&self) -> String {
+        let mut line = format!("{} {}: {}", self.status.icon(), self.name, self.detail);
+        if let Some(fix) = &self.fix {
+            line.push_str(&format!(" — {}", fix));
+        }
+        line
+    }
+}
+
+/// Results of a self-test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostics {
+    /// When the self-test ran.
+    pub ran_at: DateTime<Utc>,
+    /// Checks in the order they ran.
+    pub checks: Vec<Check>,
+}
+
+impl Diagnostics {
+    /// Collect `checks` run just now.
+    pub fn new(checks: Vec<Check>) -> Self {
+        Self {
+            ran_at: Utc::now(),
+            checks,
+        }
+    }
+
+    /// Checks that found a problem.
+    pub fn problems(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|c| c.is_problem())
+    }
+
+    /// Whether any check failed outright.
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == CheckStatus::Failed)
+    }
+
+    /// Short text for a client listing the problems and their fixes, or
+    /// `None` if no check found one.
+    pub fn summary(&self) -> Option<String> {
+        let problems: Vec<String> = self.problems().map(|c| format!("- {}", c.line())).collect();
+        if problems.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "Self-test found problems (details at {}):\n{}",
+            DIAGNOSTICS_URI,
+            problems.join("\n")
+        ))
+    }
+
+    /// Render every check as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let lines: Vec<String> = self
+            .checks
+            .iter()
+            .map(|c| format!("- {}", c.line()))
+            .collect();
+        format!(
+            "# Self-test\n\nRan {}\n\n{}\n",
+            self.ran_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            lines.join("\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::{HealthSweepPolicy, ProbeKind};
+    use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+    use crate::testkit::{MockProviders, MockReply, TestClient};
+    use crate::AgentMcpServer;
+    use embeddenator_webpuppet::Provider;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_self_test_reports_problems_on_initialize() {
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(MockProviders::new()),
+            ..Default::default()
+        });
+        let diagnostics = orchestrator.self_test().await;
+        assert!(
+            diagnostics.summary().is_none(),
+            "{}",
+            diagnostics.to_markdown()
+        );
+        let status = |name: &str| {
+            diagnostics
+                .checks
+                .iter()
+                .find(|c| c.name == name)
+                .map(|c| c.status)
+        };
+        assert_eq!(status("storage"), Some(CheckStatus::Ok));
+        assert_eq!(status("browser"), Some(CheckStatus::Skipped));
+        assert_eq!(status("providers"), Some(CheckStatus::Ok));
+
+        // Every provider down: the client is told at initialize
+        let mocks = MockProviders::new();
+        for provider in Provider::all() {
+            mocks.enqueue(provider, MockReply::Fail("down".into()));
+        }
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(mocks),
+            health_sweep: Some(HealthSweepPolicy {
+                probe: ProbeKind::Ping,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let client = TestClient::new(AgentMcpServer::new(orchestrator).with_self_test(true));
+        let result = client.initialize().await.unwrap();
+        let instructions = result["instructions"].as_str().unwrap();
+        assert!(instructions.contains("✗ providers"), "{}", instructions);
+
+        let resources = client.call("resources/list", json!({})).await.unwrap();
+        assert_eq!(resources["resources"][0]["uri"], DIAGNOSTICS_URI);
+        let read = client
+            .call("resources/read", json!({ "uri": DIAGNOSTICS_URI }))
+            .await
+            .unwrap();
+        let text = read["contents"][0]["text"].as_str().unwrap();
+        assert!(text.contains("✓ storage"), "{}", text);
+    }
+}
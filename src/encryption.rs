@@ -0,0 +1,173 @@
+//! Encryption at rest for persisted prompts, responses, and workflows.
+//!
+//! Payloads are sealed with AES-256-GCM and stored as `enc:v1:<base64>`
+//! strings. Opening a payload that was never sealed returns it unchanged, so
+//! readers work the same whether or not encryption was enabled when the data
+//! was written.
+
+use std::fmt;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+/// Environment variable holding the base64-encoded 256-bit state key.
+pub const KEY_ENV_VAR: &str = "AGENT_MCP_STATE_KEY";
+
+/// OS keyring service holding the state key.
+#[cfg(feature = "keyring")]
+pub const KEYRING_SERVICE: &str = "agent-mcp";
+
+/// OS keyring entry holding the base64-encoded state key.
+#[cfg(feature = "keyring")]
+pub const KEYRING_ENTRY: &str = "state-key";
+
+/// Prefix marking a sealed payload.
+const SEALED_PREFIX: &str = "enc:v1:";
+
+/// Length of the AES-GCM nonce in bytes.
+const NONCE_LEN: usize = 12;
+
+/// Cipher for sealing and opening stored payloads.
+#[derive(Clone)]
+pub struct PayloadCipher {
+    cipher: Aes256Gcm,
+}
+
+impl PayloadCipher {
+    /// Create a cipher from a raw 32-byte key.
+    pub fn new(key: &[u8]) -> Result<Self> {
+        if key.len() != 32 {
+            return Err(Error::Config(format!(
+                "encryption key must be 32 bytes, got {}",
+                key.len()
+            )));
+        }
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        })
+    }
+
+    /// Create a cipher from a base64-encoded key.
+    pub fn from_base64(key: &str) -> Result<Self> {
+        let bytes = STANDARD
+            .decode(key.trim())
+            .map_err(|e| Error::Config(format!("invalid encryption key: {}", e)))?;
+        Self::new(&bytes)
+    }
+
+    /// Create a cipher from the key in [`KEY_ENV_VAR`].
+    pub fn from_env() -> Result<Self> {
+        let key = std::env::var(KEY_ENV_VAR)
+            .map_err(|_| Error::Config(format!("{} is not set", KEY_ENV_VAR)))?;
+        Self::from_base64(&key)
+    }
+
+    /// Create a cipher from the key stored under [`KEYRING_SERVICE`] and
+    /// [`KEYRING_ENTRY`] in the OS keyring.
+    #[cfg(feature = "keyring")]
+    pub fn from_keyring() -> Result<Self> {
+        let key = keyring::Entry::new(KEYRING_SERVICE, KEYRING_ENTRY)
+            .and_then(|entry| entry.get_password())
+            .map_err(|e| Error::Config(format!("no state key in the OS keyring: {}", e)))?;
+        Self::from_base64(&key)
+    }
+
+    /// Create a cipher from [`KEY_ENV_VAR`], falling back to the OS keyring
+    /// when the variable is unset and the `keyring` feature is enabled.
+    pub fn load() -> Result<Self> {
+        match Self::from_env() {
+            #[cfg(feature = "keyring")]
+            Err(_) if std::env::var_os(KEY_ENV_VAR).is_none() => Self::from_keyring(),
+            result => result,
+        }
+    }
+
+    /// Seal a plaintext payload.
+    pub fn seal(&self, plaintext: &str) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| Error::Internal("encryption failed".into()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", SEALED_PREFIX, STANDARD.encode(sealed)))
+    }
+
+    /// Open a payload, passing through payloads that were never sealed.
+    pub fn open(&self, payload: &str) -> Result<String> {
+        let Some(encoded) = payload.strip_prefix(SEALED_PREFIX) else {
+            return Ok(payload.to_string());
+        };
+
+        let sealed = STANDARD
+            .decode(encoded)
+            .map_err(|e| Error::Internal(format!("corrupt sealed payload: {}", e)))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(Error::Internal("corrupt sealed payload: too short".into()));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::PermissionDenied("failed to decrypt payload (wrong key?)".into()))?;
+
+        String::from_utf8(plaintext).map_err(|e| Error::Internal(e.to_string()))
+    }
+
+    /// Serialize a value to JSON and seal it.
+    pub fn seal_json<T: Serialize>(&self, value: &T) -> Result<String> {
+        self.seal(&serde_json::to_string(value)?)
+    }
+
+    /// Open a payload and deserialize it from JSON.
+    pub fn open_json<T: DeserializeOwned>(&self, payload: &str) -> Result<T> {
+        Ok(serde_json::from_str(&self.open(payload)?)?)
+    }
+}
+
+impl fmt::Debug for PayloadCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PayloadCipher").finish_non_exhaustive()
+    }
+}
+
+/// Check if a stored payload is sealed.
+pub fn is_sealed(payload: &str) -> bool {
+    payload.starts_with(SEALED_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_roundtrip() {
+        let cipher = PayloadCipher::new(&[7u8; 32]).unwrap();
+
+        let sealed = cipher.seal("fn secret() {}").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("secret"));
+        assert_eq!(cipher.open(&sealed).unwrap(), "fn secret() {}");
+
+        // Plaintext passes through unchanged
+        assert_eq!(cipher.open("plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn test_wrong_key_rejected() {
+        let sealed = PayloadCipher::new(&[1u8; 32]).unwrap().seal("data").unwrap();
+        let other = PayloadCipher::new(&[2u8; 32]).unwrap();
+
+        assert!(other.open(&sealed).is_err());
+        assert!(PayloadCipher::new(&[0u8; 16]).is_err());
+    }
+}
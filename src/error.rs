@@ -1,5 +1,8 @@
 //! Error types for agent-mcp.
 
+use std::sync::OnceLock;
+
+use regex::Regex;
 use thiserror::Error;
 
 /// Result type for agent-mcp operations.
@@ -12,9 +15,56 @@ pub enum Error {
     #[error("no providers available: {0}")]
     NoProviders(String),
 
-    /// Provider error.
+    /// Provider error not covered by a more specific variant.
     #[error("provider error: {0}")]
-    Provider(#[from] embeddenator_webpuppet::Error),
+    Provider(#[source] embeddenator_webpuppet::Error),
+
+    /// The provider requires (re-)authentication.
+    #[error("authentication required for {provider}: {reason}")]
+    AuthRequired {
+        /// Provider name.
+        provider: String,
+        /// Reason reported by the provider.
+        reason: String,
+    },
+
+    /// The provider presented a captcha or verification wall.
+    #[error("captcha or verification required by {provider}")]
+    Captcha {
+        /// Provider name.
+        provider: String,
+    },
+
+    /// The provider refused to answer for content reasons.
+    #[error("content refused by {provider}: {reason}")]
+    ContentRefused {
+        /// Provider name.
+        provider: String,
+        /// Reason reported by the provider.
+        reason: String,
+    },
+
+    /// The prompt exceeds the provider's context window.
+    #[error("context too long for {provider}: {reason}")]
+    ContextTooLong {
+        /// Provider name.
+        provider: String,
+        /// Reason reported by the provider.
+        reason: String,
+    },
+
+    /// The provider's answer came back empty or was not read from the page.
+    #[error("incomplete response from {provider}: {reason}")]
+    IncompleteResponse {
+        /// Provider name.
+        provider: String,
+        /// What was wrong with the answer.
+        reason: String,
+    },
+
+    /// The provider could not be reached in time.
+    #[error("network timeout: {0}")]
+    NetworkTimeout(String),
 
     /// Workflow error.
     #[error("workflow error: {0}")]
@@ -40,14 +90,32 @@ pub enum Error {
     #[error("permission denied: {0}")]
     PermissionDenied(String),
 
+    /// A tenant's budget does not cover the request.
+    #[error("budget exceeded: {0}")]
+    BudgetExceeded(String),
+
     /// Rate limited.
-    #[error("rate limited: {0}")]
-    RateLimited(String),
+    #[error("rate limited: {message}")]
+    RateLimited {
+        /// What was rate limited.
+        message: String,
+        /// Suggested wait before retrying, in seconds.
+        retry_after_secs: Option<u64>,
+    },
 
     /// Timeout.
     #[error("timeout: {0}")]
     Timeout(String),
 
+    /// A tool call exceeded its time limit.
+    #[error("tool {tool} timed out after {elapsed_ms}ms")]
+    ToolTimeout {
+        /// Tool name.
+        tool: String,
+        /// How long the tool ran, in milliseconds.
+        elapsed_ms: u64,
+    },
+
     /// Invalid parameters.
     #[error("invalid parameters: {0}")]
     InvalidParams(String),
@@ -59,4 +127,314 @@ pub enum Error {
     /// Internal error.
     #[error("internal error: {0}")]
     Internal(String),
+
+    /// The orchestrator is in maintenance mode and takes no provider calls.
+    #[error("maintenance mode: {0}")]
+    Maintenance(String),
+}
+
+/// Every error class name [`Error::kind`] returns.
+pub const ERROR_KINDS: &[&str] = &[
+    "no_providers",
+    "provider",
+    "auth_required",
+    "captcha",
+    "content_refused",
+    "context_too_long",
+    "incomplete_response",
+    "network_timeout",
+    "workflow",
+    "invalid_state",
+    "config",
+    "serialization",
+    "io",
+    "permission_denied",
+    "budget_exceeded",
+    "rate_limited",
+    "timeout",
+    "tool_timeout",
+    "invalid_params",
+    "protocol",
+    "internal",
+    "maintenance",
+];
+
+impl Error {
+    /// Stable machine-readable name of the error class.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::NoProviders(_) => "no_providers",
+            Error::Provider(_) => "provider",
+            Error::AuthRequired { .. } => "auth_required",
+            Error::Captcha { .. } => "captcha",
+            Error::ContentRefused { .. } => "content_refused",
+            Error::ContextTooLong { .. } => "context_too_long",
+            Error::IncompleteResponse { .. } => "incomplete_response",
+            Error::NetworkTimeout(_) => "network_timeout",
+            Error::Workflow(_) => "workflow",
+            Error::InvalidState(_) => "invalid_state",
+            Error::Config(_) => "config",
+            Error::Serialization(_) => "serialization",
+            Error::Io(_) => "io",
+            Error::PermissionDenied(_) => "permission_denied",
+            Error::BudgetExceeded(_) => "budget_exceeded",
+            Error::RateLimited { .. } => "rate_limited",
+            Error::Timeout(_) => "timeout",
+            Error::ToolTimeout { .. } => "tool_timeout",
+            Error::InvalidParams(_) => "invalid_params",
+            Error::Protocol(_) => "protocol",
+            Error::Internal(_) => "internal",
+            Error::Maintenance(_) => "maintenance",
+        }
+    }
+
+    /// Structured JSON-RPC error data: the error kind plus any details.
+    pub fn data(&self) -> serde_json::Value {
+        let mut data = serde_json::json!({ "kind": self.kind() });
+        match self {
+            Error::ToolTimeout { tool, elapsed_ms } => {
+                data["tool"] = tool.clone().into();
+                data["elapsed_ms"] = (*elapsed_ms).into();
+            }
+            _ => {
+                if let Some(secs) = self.retry_after_secs() {
+                    data["retry_after_secs"] = secs.into();
+                }
+            }
+        }
+        data
+    }
+
+    /// Check if retrying the same request may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::RateLimited { .. }
+            | Error::NetworkTimeout(_)
+            | Error::IncompleteResponse { .. } => true,
+            Error::Provider(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// Check if another provider may succeed where this one failed.
+    ///
+    /// False for errors caused by the request itself or by local policy.
+    pub fn is_fallback_candidate(&self) -> bool {
+        matches!(
+            self,
+            Error::Provider(_)
+                | Error::AuthRequired { .. }
+                | Error::Captcha { .. }
+                | Error::ContentRefused { .. }
+                | Error::ContextTooLong { .. }
+                | Error::IncompleteResponse { .. }
+                | Error::NetworkTimeout(_)
+                | Error::RateLimited { .. }
+        )
+    }
+
+    /// Check if the error indicates the provider itself is unhealthy.
+    ///
+    /// Refusals and oversized prompts say nothing about provider health.
+    pub fn affects_health(&self) -> bool {
+        matches!(
+            self,
+            Error::Provider(_)
+                | Error::AuthRequired { .. }
+                | Error::Captcha { .. }
+                | Error::IncompleteResponse { .. }
+                | Error::NetworkTimeout(_)
+                | Error::RateLimited { .. }
+        )
+    }
+
+    /// Check if the browser itself failed, so its session should be
+    /// replaced rather than reused.
+    pub fn is_browser_failure(&self) -> bool {
+        use embeddenator_webpuppet::Error as E;
+
+        matches!(self, Error::Provider(E::Browser(_) | E::Io(_)))
+    }
+
+    /// Suggested wait before retrying, in seconds.
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            Error::RateLimited {
+                retry_after_secs, ..
+            } => *retry_after_secs,
+            Error::Provider(e) => e.retry_delay_secs(),
+            _ => None,
+        }
+    }
+}
+
+impl From<embeddenator_webpuppet::Error> for Error {
+    fn from(err: embeddenator_webpuppet::Error) -> Self {
+        use embeddenator_webpuppet::Error as E;
+
+        match err {
+            E::AuthenticationFailed { provider, reason } => {
+                Error::AuthRequired { provider, reason }
+            }
+            E::SessionExpired(provider) => Error::AuthRequired {
+                provider,
+                reason: "session expired".into(),
+            },
+            E::RateLimitExceeded { retry_after_secs } => Error::RateLimited {
+                message: format!("provider rate limit, retry after {}s", retry_after_secs),
+                retry_after_secs: Some(retry_after_secs),
+            },
+            E::Timeout(ms) => Error::NetworkTimeout(format!("no response after {}ms", ms)),
+            E::Navigation(reason) => Error::NetworkTimeout(reason),
+            E::ProviderError { provider, message } => classify_provider_message(provider, message),
+            other => Error::Provider(other),
+        }
+    }
+}
+
+/// Wording of provider messages indicating a captcha or verification wall.
+const CAPTCHA_MARKERS: &[&str] = &[
+    "captcha",
+    "verify you are human",
+    "are you a robot",
+    "unusual activity",
+];
+
+/// Wording indicating the provider wants the user to log in.
+const AUTH_MARKERS: &[&str] = &[
+    "log in",
+    "login",
+    "sign in",
+    "unauthorized",
+    "not authenticated",
+];
+
+/// Wording indicating a rate or usage limit.
+const RATE_LIMIT_MARKERS: &[&str] = &[
+    "rate limit",
+    "too many requests",
+    "usage limit",
+    "try again later",
+];
+
+/// Wording indicating the prompt exceeds the context window.
+const CONTEXT_MARKERS: &[&str] = &[
+    "too long",
+    "context length",
+    "maximum context",
+    "token limit",
+];
+
+/// Wording indicating a content refusal.
+const REFUSAL_MARKERS: &[&str] = &[
+    "content policy",
+    "can't help with",
+    "cannot help with",
+    "violates",
+    "refuse",
+];
+
+/// Matches a suggested wait such as "retry-after: 120", "try again in 30
+/// seconds", or "retry after 2 minutes".
+fn retry_after_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)(?:retry[- ]after|try again in|wait)\s*:?\s*(\d+)\s*(h|hours?|m|mins?|minutes?|s|secs?|seconds?)?\b")
+            .unwrap()
+    })
+}
+
+/// Extract a suggested wait, in seconds, from a rate limit message.
+fn parse_retry_after(message: &str) -> Option<u64> {
+    let caps = retry_after_re().captures(message)?;
+    let amount: u64 = caps[1].parse().ok()?;
+    let scale = match caps.get(2).map(|unit| unit.as_str().to_lowercase()) {
+        Some(unit) if unit.starts_with('h') => 3600,
+        Some(unit) if unit.starts_with('m') => 60,
+        _ => 1,
+    };
+    Some(amount * scale)
+}
+
+/// Classify a provider's error message by its wording.
+fn classify_provider_message(provider: String, message: String) -> Error {
+    let lower = message.to_lowercase();
+    let has = |markers: &[&str]| markers.iter().any(|m| lower.contains(m));
+
+    if has(CAPTCHA_MARKERS) {
+        Error::Captcha { provider }
+    } else if has(AUTH_MARKERS) {
+        Error::AuthRequired {
+            provider,
+            reason: message,
+        }
+    } else if has(RATE_LIMIT_MARKERS) {
+        Error::RateLimited {
+            retry_after_secs: parse_retry_after(&lower),
+            message: format!("{}: {}", provider, message),
+        }
+    } else if has(CONTEXT_MARKERS) {
+        Error::ContextTooLong {
+            provider,
+            reason: message,
+        }
+    } else if has(REFUSAL_MARKERS) {
+        Error::ContentRefused {
+            provider,
+            reason: message,
+        }
+    } else {
+        Error::Provider(embeddenator_webpuppet::Error::ProviderError { provider, message })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embeddenator_webpuppet::Error as WebError;
+
+    #[test]
+    fn test_classifies_webpuppet_errors() {
+        let provider_error = |message: &str| {
+            Error::from(WebError::ProviderError {
+                provider: "claude".into(),
+                message: message.into(),
+            })
+        };
+
+        assert_eq!(
+            provider_error("Please verify you are human").kind(),
+            "captcha"
+        );
+        assert_eq!(
+            provider_error("Prompt is too long").kind(),
+            "context_too_long"
+        );
+        assert_eq!(
+            provider_error("This violates our content policy").kind(),
+            "content_refused"
+        );
+        assert_eq!(provider_error("Something broke").kind(), "provider");
+        assert_eq!(
+            Error::from(WebError::SessionExpired("grok".into())).kind(),
+            "auth_required"
+        );
+
+        let limited = Error::from(WebError::RateLimitExceeded {
+            retry_after_secs: 30,
+        });
+        assert!(limited.is_retryable());
+        assert_eq!(limited.retry_after_secs(), Some(30));
+        assert!(!provider_error("Prompt is too long").affects_health());
+        assert_eq!(
+            provider_error("Too many requests. Try again in 2 minutes.").retry_after_secs(),
+            Some(120)
+        );
+        assert_eq!(
+            provider_error("Rate limit reached").retry_after_secs(),
+            None
+        );
+        assert!(Error::from(WebError::Browser("target closed".into())).is_browser_failure());
+        assert!(!provider_error("Something broke").is_browser_failure());
+    }
 }
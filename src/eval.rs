@@ -0,0 +1,297 @@
+//! Evaluation harness for benchmarking providers.
+//!
+//! Runs a suite of prompts across providers, scores each response against an
+//! optional reference answer (exact match, word overlap, or a judge provider),
+//! and produces a report whose per-provider averages can be turned into router
+//! priorities.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use embeddenator_webpuppet::{PromptResponse, Provider};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::consensus::similarity;
+use crate::error::{Error, Result};
+use crate::grading::GradingPolicy;
+use crate::orchestrator::{AgentOrchestrator, PromptOptions};
+
+/// How a response is scored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringMethod {
+    /// 1.0 if the response matches the reference exactly (ignoring case and
+    /// surrounding whitespace), else 0.0.
+    Exact,
+    /// Word overlap with the reference.
+    #[default]
+    Similarity,
+    /// A judge provider grades the response against the reference and rubric.
+    Judge,
+}
+
+/// A single prompt in an evaluation suite.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalCase {
+    /// Case identifier.
+    pub id: String,
+    /// Prompt sent to each provider.
+    pub prompt: String,
+    /// Reference answer.
+    #[serde(default)]
+    pub reference: Option<String>,
+    /// Scoring method overriding the suite default.
+    #[serde(default)]
+    pub scoring: Option<ScoringMethod>,
+    /// Judge rubric overriding the suite rubric.
+    #[serde(default)]
+    pub rubric: Option<String>,
+}
+
+/// A suite of evaluation cases.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalSuite {
+    /// Suite name.
+    pub name: String,
+    /// Providers to evaluate (empty evaluates all available providers).
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Default scoring method.
+    #[serde(default)]
+    pub scoring: ScoringMethod,
+    /// Provider that grades responses for judge scoring.
+    #[serde(default)]
+    pub judge_provider: Option<String>,
+    /// Grading criteria given to the judge.
+    #[serde(default)]
+    pub rubric: Option<String>,
+    /// Cases to run.
+    pub cases: Vec<EvalCase>,
+}
+
+impl EvalSuite {
+    /// Load a suite from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Resolve the providers to evaluate.
+    fn resolve_providers(&self, available: Vec<Provider>) -> Result<Vec<Provider>> {
+        if self.providers.is_empty() {
+            return Ok(available);
+        }
+        self.providers
+            .iter()
+            .map(|name| {
+                Provider::from_string(name)
+                    .ok_or_else(|| Error::Config(format!("unknown provider: {}", name)))
+            })
+            .collect()
+    }
+}
+
+/// Outcome of one case on one provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalResult {
+    /// Case identifier.
+    pub case_id: String,
+    /// Provider name.
+    pub provider: String,
+    /// Score from 0.0 to 1.0 (`None` if the case could not be scored).
+    pub score: Option<f64>,
+    /// Response latency in milliseconds.
+    pub latency_ms: u64,
+    /// Response text.
+    pub response: Option<String>,
+    /// Error, if the provider failed.
+    pub error: Option<String>,
+}
+
+/// Results of running a suite.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// Suite name.
+    pub suite: String,
+    /// When the run started.
+    pub started_at: DateTime<Utc>,
+    /// Per-case, per-provider results.
+    pub results: Vec<EvalResult>,
+}
+
+impl EvalReport {
+    /// Load a report from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Save the report as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Average score per provider. Failed requests count as 0.0; unscored
+    /// cases are ignored.
+    pub fn provider_scores(&self) -> HashMap<String, f64> {
+        let mut totals: HashMap<String, (f64, u32)> = HashMap::new();
+        for result in &self.results {
+            let score = match (&result.error, result.score) {
+                (Some(_), _) => 0.0,
+                (None, Some(score)) => score,
+                (None, None) => continue,
+            };
+            let entry = totals.entry(result.provider.clone()).or_default();
+            entry.0 += score;
+            entry.1 += 1;
+        }
+        totals
+            .into_iter()
+            .map(|(provider, (sum, count))| (provider, sum / count as f64))
+            .collect()
+    }
+
+    /// Router priorities (0-100) derived from average scores.
+    pub fn priorities(&self) -> HashMap<String, u32> {
+        self.provider_scores()
+            .into_iter()
+            .map(|(provider, score)| (provider, (score * 100.0).round() as u32))
+            .collect()
+    }
+}
+
+/// Run a suite across providers.
+pub async fn run_suite(orchestrator: &AgentOrchestrator, suite: &EvalSuite) -> Result<EvalReport> {
+    let available = orchestrator.status().await.available_providers;
+    let providers = suite.resolve_providers(available)?;
+    if providers.is_empty() {
+        return Err(Error::NoProviders("no providers to evaluate".into()));
+    }
+
+    let mut report = EvalReport {
+        suite: suite.name.clone(),
+        started_at: Utc::now(),
+        results: Vec::new(),
+    };
+
+    for case in &suite.cases {
+        let method = case.scoring.unwrap_or(suite.scoring);
+        for &provider in &providers {
+            let start = Instant::now();
+            let response = orchestrator
+                .prompt_provider_with(provider, case.prompt.clone(), PromptOptions::default())
+                .await;
+            let latency_ms = start.elapsed().as_millis() as u64;
+
+            let result = match response {
+                Ok(response) => {
+                    let score = score_response(orchestrator, suite, case, method, &response)
+                        .await
+                        .unwrap_or_else(|e| {
+                            warn!("Failed to score case {}: {}", case.id, e);
+                            None
+                        });
+                    EvalResult {
+                        case_id: case.id.clone(),
+                        provider: provider.to_string().to_lowercase(),
+                        score,
+                        latency_ms,
+                        response: Some(response.text),
+                        error: None,
+                    }
+                }
+                Err(e) => EvalResult {
+                    case_id: case.id.clone(),
+                    provider: provider.to_string().to_lowercase(),
+                    score: None,
+                    latency_ms,
+                    response: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            report.results.push(result);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Score a response with the given method.
+async fn score_response(
+    orchestrator: &AgentOrchestrator,
+    suite: &EvalSuite,
+    case: &EvalCase,
+    method: ScoringMethod,
+    response: &PromptResponse,
+) -> Result<Option<f64>> {
+    match (method, case.reference.as_deref()) {
+        (ScoringMethod::Exact, Some(reference)) => {
+            Ok(Some(score_exact(&response.text, reference)))
+        }
+        (ScoringMethod::Similarity, Some(reference)) => {
+            Ok(Some(similarity(&response.text, reference)))
+        }
+        (ScoringMethod::Judge, reference) => {
+            let judge = suite.judge_provider.as_deref().ok_or_else(|| {
+                Error::Config("judge scoring requires a judge_provider".into())
+            })?;
+            let mut policy = GradingPolicy::new(judge);
+            policy.rubric = case.rubric.clone().or_else(|| suite.rubric.clone());
+            let grade = orchestrator
+                .grade_response(&policy, &case.prompt, response, reference, None)
+                .await?;
+            Ok(Some(grade.score))
+        }
+        (_, None) => Ok(None),
+    }
+}
+
+/// Score 1.0 for a case- and whitespace-insensitive exact match.
+pub fn score_exact(response: &str, reference: &str) -> f64 {
+    if response.trim().eq_ignore_ascii_case(reference.trim()) {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoring() {
+        assert_eq!(score_exact(" Paris ", "paris"), 1.0);
+        assert_eq!(score_exact("Paris, France", "paris"), 0.0);
+    }
+
+    #[test]
+    fn test_report_priorities() {
+        let result = |provider: &str, score: Option<f64>, error: Option<&str>| EvalResult {
+            case_id: "c".into(),
+            provider: provider.into(),
+            score,
+            latency_ms: 0,
+            response: None,
+            error: error.map(String::from),
+        };
+        let report = EvalReport {
+            suite: "s".into(),
+            started_at: Utc::now(),
+            results: vec![
+                result("claude", Some(1.0), None),
+                result("claude", Some(0.5), None),
+                result("gemini", None, Some("timeout")),
+                result("gemini", Some(1.0), None),
+                result("grok", None, None),
+            ],
+        };
+
+        let priorities = report.priorities();
+        assert_eq!(priorities["claude"], 75);
+        assert_eq!(priorities["gemini"], 50);
+        assert!(!priorities.contains_key("grok"));
+    }
+}
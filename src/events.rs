@@ -0,0 +1,359 @@
+//! Orchestrator events pushed to clients and other subscribers.
+//!
+//! Interventions, review pauses, and budget alerts are forwarded to the MCP
+//! client. Every event, including per-request and per-step telemetry, can
+//! also be written as JSON lines to a file or socket for monitoring and
+//! replay tooling.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::error::{Error, Result};
+
+/// Number of events buffered for slow subscribers.
+const EVENT_BUFFER: usize = 256;
+
+/// Something clients may want to know about while a call is in flight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrchestratorEvent {
+    /// A provider needs a human to solve a captcha or verification wall.
+    InterventionRequired {
+        /// Intervention ID, used to resolve it.
+        id: String,
+        /// Provider name.
+        provider: String,
+        /// What the user needs to do.
+        reason: String,
+        /// Whether a visible browser was opened for the user.
+        browser_visible: bool,
+    },
+    /// A pending intervention was resolved or timed out.
+    InterventionResolved {
+        /// Intervention ID.
+        id: String,
+        /// Provider name.
+        provider: String,
+        /// Whether the user reported the challenge solved.
+        solved: bool,
+    },
+    /// A prompt was sent to a provider.
+    RequestStarted {
+        /// Request ID, shared with the matching `request_finished` event.
+        request_id: String,
+        /// Provider name.
+        provider: String,
+        /// Estimated prompt tokens, including context.
+        prompt_tokens: u64,
+    },
+    /// A workflow running in the background paused for human review.
+    ReviewRequired {
+        /// Workflow ID, used to approve the step.
+        workflow_id: String,
+        /// Workflow name.
+        workflow_name: String,
+        /// Name of the step awaiting review.
+        step_name: String,
+        /// What the reviewer is asked to check.
+        reason: String,
+    },
+    /// A provider answered or failed.
+    RequestFinished {
+        /// Request ID.
+        request_id: String,
+        /// Provider name.
+        provider: String,
+        /// Time from sending the prompt to the outcome, in milliseconds.
+        duration_ms: u64,
+        /// Error kind, if the request failed.
+        error: Option<String>,
+    },
+    /// The router chose providers for a prompt.
+    RoutingDecision {
+        /// Task type the prompt was routed as.
+        task: String,
+        /// Detected prompt language.
+        language: Option<String>,
+        /// Estimated prompt tokens, including context.
+        prompt_tokens: u64,
+        /// Chosen providers, best first.
+        providers: Vec<String>,
+    },
+    /// A workflow step changed state.
+    StepStateChanged {
+        /// Workflow ID.
+        workflow_id: String,
+        /// Step ID.
+        step_id: String,
+        /// Step name.
+        step_name: String,
+        /// New state: `running`, `waiting_for_human`, `completed`, or
+        /// `failed`.
+        state: String,
+        /// Failure reason, if the step failed.
+        error: Option<String>,
+    },
+    /// A run stopped because its next step would exceed a cost budget.
+    BudgetAlert {
+        /// What ran out of budget (`auto` or `research`).
+        scope: String,
+        /// Workflow ID or research question.
+        subject: String,
+        /// Estimated spend so far, in USD.
+        spent_usd: f64,
+        /// Budget, in USD.
+        limit_usd: f64,
+        /// Estimated cost of the step that was not run, in USD.
+        next_usd: f64,
+    },
+    /// Spend in the current hour or day climbed well past its baseline.
+    CostAnomaly {
+        /// Window the spend was judged over (`hour` or `day`).
+        window: String,
+        /// Estimated spend in the window so far, in USD.
+        spent_usd: f64,
+        /// Mean spend of earlier windows, in USD.
+        baseline_usd: f64,
+        /// Whether auto runs were paused until resumed.
+        paused_auto_runs: bool,
+    },
+    /// A canary of routing priorities regressed and was rolled back.
+    CanaryRolledBack {
+        /// How the canary did worse than the baseline.
+        reason: String,
+    },
+    /// A provider kept missing its response time objective and was demoted
+    /// in routing.
+    SloBreached {
+        /// Provider name.
+        provider: String,
+        /// Response time objective, in milliseconds.
+        target_ms: u64,
+        /// Recent requests that missed it.
+        misses: usize,
+        /// Recent requests judged.
+        requests: usize,
+    },
+    /// A provider in breach is back within its response time objective.
+    SloRecovered {
+        /// Provider name.
+        provider: String,
+        /// Response time objective, in milliseconds.
+        target_ms: u64,
+    },
+}
+
+impl OrchestratorEvent {
+    /// MCP logging level the event is forwarded to clients at, or `None`
+    /// for telemetry only written to the event stream.
+    pub fn client_level(&self) -> Option<&'static str> {
+        match self {
+            Self::InterventionRequired { .. }
+            | Self::ReviewRequired { .. }
+            | Self::BudgetAlert { .. }
+            | Self::CostAnomaly { .. }
+            | Self::CanaryRolledBack { .. }
+            | Self::SloBreached { .. } => Some("warning"),
+            Self::InterventionResolved { .. } | Self::SloRecovered { .. } => Some("info"),
+            Self::RequestStarted { .. }
+            | Self::RequestFinished { .. }
+            | Self::RoutingDecision { .. }
+            | Self::StepStateChanged { .. } => None,
+        }
+    }
+}
+
+/// Broadcast channel for orchestrator events.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<OrchestratorEvent>,
+}
+
+impl EventBus {
+    /// Create an event bus.
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+
+    /// Subscribe to future events.
+    pub fn subscribe(&self) -> broadcast::Receiver<OrchestratorEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Publish an event. Events without subscribers are dropped.
+    pub fn emit(&self, event: OrchestratorEvent) {
+        self.sender.send(event).ok();
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where the JSONL event stream is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventSink {
+    /// Append to a file.
+    File(PathBuf),
+    /// Connect to a TCP listener (`tcp://host:port`).
+    Tcp(String),
+    /// Connect to a Unix socket listener (`unix:///path`).
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for EventSink {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            return Ok(Self::Tcp(addr.to_string()));
+        }
+        if let Some(path) = s.strip_prefix("unix://") {
+            #[cfg(unix)]
+            return Ok(Self::Unix(PathBuf::from(path)));
+            #[cfg(not(unix))]
+            return Err(Error::Config(format!(
+                "Unix sockets are not supported on this platform: {}",
+                path
+            )));
+        }
+        if s.is_empty() {
+            return Err(Error::Config("empty event stream target".into()));
+        }
+        Ok(Self::File(PathBuf::from(s)))
+    }
+}
+
+impl EventSink {
+    async fn open(&self) -> Result<Box<dyn AsyncWrite + Send + Unpin>> {
+        Ok(match self {
+            Self::File(path) => Box::new(
+                tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?,
+            ),
+            Self::Tcp(addr) => Box::new(tokio::net::TcpStream::connect(addr).await?),
+            #[cfg(unix)]
+            Self::Unix(path) => Box::new(tokio::net::UnixStream::connect(path).await?),
+        })
+    }
+}
+
+/// An event as written to the stream.
+#[derive(Debug, Serialize)]
+struct StreamRecord<'a> {
+    /// When the event was written.
+    ts: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a OrchestratorEvent,
+}
+
+/// Write every event published on `bus` to `sink`, one JSON object per
+/// line.
+///
+/// A sink that cannot be opened or written is retried on the next event;
+/// events published meanwhile are lost, as are events dropped because the
+/// writer fell behind (noted in the stream as `events_dropped`).
+pub fn spawn_event_stream(bus: &EventBus, sink: EventSink) -> JoinHandle<()> {
+    let mut events = bus.subscribe();
+    tokio::spawn(async move {
+        let mut writer: Option<Box<dyn AsyncWrite + Send + Unpin>> = None;
+        loop {
+            let line = match events.recv().await {
+                Ok(event) => serde_json::to_string(&StreamRecord {
+                    ts: Utc::now(),
+                    event: &event,
+                }),
+                Err(broadcast::error::RecvError::Lagged(count)) => Ok(serde_json::json!({
+                    "ts": Utc::now(),
+                    "type": "events_dropped",
+                    "count": count,
+                })
+                .to_string()),
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let Ok(line) = line else { continue };
+
+            if writer.is_none() {
+                match sink.open().await {
+                    Ok(opened) => writer = Some(opened),
+                    Err(e) => {
+                        warn!("Failed to open event stream {:?}: {}", sink, e);
+                        continue;
+                    }
+                }
+            }
+            if let Some(w) = writer.as_mut() {
+                let written = async {
+                    w.write_all(line.as_bytes()).await?;
+                    w.write_all(b"\n").await?;
+                    w.flush().await
+                };
+                if let Err(e) = written.await {
+                    warn!("Failed to write event stream {:?}: {}", sink, e);
+                    writer = None;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_stream_writes_jsonl() {
+        let path = std::env::temp_dir().join(format!("events-{}.jsonl", uuid::Uuid::new_v4()));
+        let sink: EventSink = path.to_str().unwrap().parse().unwrap();
+        assert_eq!(sink, EventSink::File(path.clone()));
+        assert_eq!(
+            "tcp://127.0.0.1:9000".parse::<EventSink>().unwrap(),
+            EventSink::Tcp("127.0.0.1:9000".into())
+        );
+
+        let bus = EventBus::new();
+        let stream = spawn_event_stream(&bus, sink);
+        bus.emit(OrchestratorEvent::RequestFinished {
+            request_id: "r1".into(),
+            provider: "Claude".into(),
+            duration_ms: 1200,
+            error: None,
+        });
+        bus.emit(OrchestratorEvent::BudgetAlert {
+            scope: "auto".into(),
+            subject: "wf-1".into(),
+            spent_usd: 0.9,
+            limit_usd: 1.0,
+            next_usd: 0.2,
+        });
+        drop(bus);
+        stream.await.unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let lines: Vec<serde_json::Value> = written
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["type"], "request_finished");
+        assert_eq!(lines[0]["duration_ms"], 1200);
+        assert!(lines[0]["ts"].is_string());
+        assert_eq!(lines[1]["type"], "budget_alert");
+    }
+}
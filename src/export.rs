@@ -0,0 +1,358 @@
+//! Shareable Markdown and HTML reports of workflows and sessions.
+//!
+//! A workflow report lists each step's prompt, the per-provider responses,
+//! the chosen consensus answer, and an estimated cost from the response token
+//! counts. A session report lists each turn's prompt and response.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::auto::SYNTHESIS_KEY;
+use crate::cost::{estimate_tokens, CostModel};
+use crate::error::{Error, Result};
+use crate::session::Session;
+use crate::workflow::{StepConfig, Workflow, WorkflowStep};
+
+/// Report format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Markdown.
+    #[default]
+    Markdown,
+    /// Standalone HTML page.
+    Html,
+}
+
+impl ExportFormat {
+    /// Infer the format from a file extension, defaulting to Markdown.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                ExportFormat::Html
+            }
+            _ => ExportFormat::Markdown,
+        }
+    }
+}
+
+/// Building block of a report, rendered per format.
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    /// Provider output, kept verbatim in Markdown and preformatted in HTML.
+    Response(String),
+}
+
+/// Render a workflow report.
+pub fn render_workflow(workflow: &Workflow, costs: &CostModel, format: ExportFormat) -> String {
+    let blocks = workflow_blocks(workflow, costs);
+    match format {
+        ExportFormat::Markdown => render_markdown(&blocks),
+        ExportFormat::Html => render_html(&workflow.name, &blocks),
+    }
+}
+
+/// Render a workflow report and write it to `path`, returning its size.
+pub fn export_workflow(
+    workflow: &Workflow,
+    costs: &CostModel,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<usize> {
+    write_report(&render_workflow(workflow, costs, format), path)
+}
+
+/// Render a session report.
+pub fn render_session(session: &Session, costs: &CostModel, format: ExportFormat) -> String {
+    let blocks = session_blocks(session, costs);
+    match format {
+        ExportFormat::Markdown => render_markdown(&blocks),
+        ExportFormat::Html => render_html(&session.title(), &blocks),
+    }
+}
+
+/// Render a session report and write it to `path`, returning its size.
+pub fn export_session(
+    session: &Session,
+    costs: &CostModel,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<usize> {
+    write_report(&render_session(session, costs, format), path)
+}
+
+fn write_report(report: &str, path: &Path) -> Result<usize> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(Error::Io)?;
+    }
+    std::fs::write(path, report).map_err(Error::Io)?;
+    Ok(report.len())
+}
+
+fn session_blocks(session: &Session, costs: &CostModel) -> Vec<Block> {
+    let mut blocks = vec![
+        Block::Heading(1, session.title()),
+        Block::Paragraph(format!("Session `{}` · {}", session.id, session.summary())),
+    ];
+    let mut total_cost = 0.0;
+    for (index, turn) in session.turns.iter().enumerate() {
+        let provider = turn.provider.as_deref().unwrap_or(&session.provider);
+        let tokens = estimate_tokens(&turn.prompt) + estimate_tokens(&turn.response);
+        total_cost += costs.estimate(Some(provider), tokens);
+        blocks.push(Block::Heading(
+            2,
+            format!(
+                "Turn {} ({})",
+                index + 1,
+                turn.timestamp.format("%H:%M:%S UTC")
+            ),
+        ));
+        blocks.push(Block::Paragraph("**Prompt**".into()));
+        blocks.push(Block::Response(turn.prompt.clone()));
+        blocks.push(Block::Paragraph(format!("**{}**", provider)));
+        blocks.push(Block::Response(turn.response.clone()));
+    }
+    blocks.push(Block::Heading(2, "Summary".into()));
+    blocks.push(Block::Paragraph(format!(
+        "{} turns · estimated total cost ${:.4}",
+        session.turns.len(),
+        total_cost
+    )));
+    blocks
+}
+
+fn workflow_blocks(workflow: &Workflow, costs: &CostModel) -> Vec<Block> {
+    let mut blocks = vec![
+        Block::Heading(1, workflow.name.clone()),
+        Block::Paragraph(format!(
+            "Workflow `{}` · {:?} · created {} · updated {}",
+            workflow.id,
+            workflow.state,
+            workflow.created_at.format("%Y-%m-%d %H:%M UTC"),
+            workflow.updated_at.format("%Y-%m-%d %H:%M UTC"),
+        )),
+    ];
+
+    let mut total_cost = 0.0;
+    for (index, step) in workflow.steps.iter().enumerate() {
+        blocks.push(Block::Heading(
+            2,
+            format!("Step {}: {} ({:?})", index + 1, step.name, step.state),
+        ));
+        if let Some(prompt) = step_prompt(step) {
+            blocks.push(Block::Paragraph("**Prompt**".into()));
+            blocks.push(Block::Response(prompt.to_string()));
+        }
+        let Some(result) = &step.result else {
+            continue;
+        };
+
+        let cost = costs.estimate_step(result);
+        total_cost += cost;
+        blocks.push(Block::Paragraph(format!(
+            "Duration: {} ms · Estimated cost: ${:.4}",
+            result.duration_ms, cost
+        )));
+
+        match &result.responses {
+            Some(responses) => {
+                for response in responses {
+                    let mut title = format!("**{}**", response.provider);
+                    if let Some(confidence) = response.confidence {
+                        title.push_str(&format!(" ({:.0}% agreement)", confidence * 100.0));
+                    }
+                    if response.selected {
+                        title.push_str(" ✓ selected");
+                    }
+                    blocks.push(Block::Paragraph(title));
+                    blocks.push(Block::Response(response.text.clone()));
+                }
+                let combined = match step.config {
+                    StepConfig::Consensus { .. } => Some("**Consensus**"),
+                    StepConfig::Research { .. } => Some("**Answer**"),
+                    StepConfig::Extract { .. } => Some("**Records**"),
+                    _ => None,
+                };
+                if let Some(title) = combined {
+                    blocks.push(Block::Paragraph(title.into()));
+                    blocks.push(Block::Response(result.output.clone()));
+                }
+            }
+            None => {
+                let title = match &result.provider {
+                    Some(provider) => format!("**{}**", provider),
+                    None => "**Output**".into(),
+                };
+                blocks.push(Block::Paragraph(title));
+                blocks.push(Block::Response(result.output.clone()));
+            }
+        }
+    }
+
+    if let Some(synthesis) = workflow.get_context(SYNTHESIS_KEY).and_then(|v| v.as_str()) {
+        blocks.push(Block::Heading(2, "Synthesis".into()));
+        blocks.push(Block::Response(synthesis.to_string()));
+    }
+
+    blocks.push(Block::Heading(2, "Summary".into()));
+    blocks.push(Block::Paragraph(format!(
+        "{} steps · estimated total cost ${:.4}",
+        workflow.steps.len(),
+        total_cost
+    )));
+    blocks
+}
+
+/// The prompt a step sends, if it sends one.
+fn step_prompt(step: &WorkflowStep) -> Option<&str> {
+    match &step.config {
+        StepConfig::Prompt { message, .. }
+        | StepConfig::ParallelPrompt { message, .. }
+        | StepConfig::Consensus { message, .. }
+        | StepConfig::Map { message, .. }
+        | StepConfig::Aggregate { message, .. }
+        | StepConfig::Extract { message, .. } => Some(message),
+        StepConfig::Research { question, .. } => Some(question),
+        StepConfig::FactCheck {
+            focus: Some(focus), ..
+        } => Some(focus),
+        StepConfig::HumanReview { prompt } => Some(prompt),
+        _ => None,
+    }
+}
+
+fn render_markdown(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                out.push_str(&format!("{} {}\n\n", "#".repeat(*level), text))
+            }
+            Block::Paragraph(text) => out.push_str(&format!("{}\n\n", text)),
+            Block::Response(text) => {
+                for line in text.lines() {
+                    match line {
+                        "" => out.push_str(">\n"),
+                        line => out.push_str(&format!("> {}\n", line)),
+                    }
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn render_html(title: &str, blocks: &[Block]) -> String {
+    let mut body = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                body.push_str(&format!("<h{0}>{1}</h{0}>\n", level, escape_html(text)))
+            }
+            Block::Paragraph(text) => body.push_str(&format!("<p>{}</p>\n", inline_html(text))),
+            Block::Response(text) => body.push_str(&format!("<pre>{}</pre>\n", escape_html(text))),
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body{{font-family:sans-serif;max-width:50em;margin:auto}}\
+         pre{{white-space:pre-wrap;background:#f6f8fa;padding:1em}}</style>\n\
+         </head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+/// Escape text and render the `**bold**` and `` `code` `` spans used in
+/// report paragraphs.
+fn inline_html(text: &str) -> String {
+    let mut out = String::new();
+    for (i, part) in escape_html(text).split("**").enumerate() {
+        if i % 2 == 1 {
+            out.push_str(&format!("<strong>{}</strong>", part));
+        } else {
+            for (j, span) in part.split('`').enumerate() {
+                if j % 2 == 1 {
+                    out.push_str(&format!("<code>{}</code>", span));
+                } else {
+                    out.push_str(span);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::metadata::{PROMPT_TOKENS_KEY, RESPONSE_TOKENS_KEY};
+    use crate::session::Turn;
+    use crate::workflow::{StepResult, StepState};
+
+    #[test]
+    fn test_render_workflow_reports() {
+        let mut workflow = Workflow::new("Research <draft>");
+        let mut step = WorkflowStep::prompt("Ask", "What is Rust?").with_provider("chatgpt");
+        step.state = StepState::Completed;
+        step.result = Some(StepResult {
+            output: "A systems language.".into(),
+            provider: Some("chatgpt".into()),
+            responses: None,
+            duration_ms: 1200,
+            metadata: HashMap::from([
+                (PROMPT_TOKENS_KEY.to_string(), 400.into()),
+                (RESPONSE_TOKENS_KEY.to_string(), 600.into()),
+            ]),
+        });
+        workflow.add_step(step);
+        let costs = CostModel::default().with_rate("chatgpt", 0.01);
+
+        let markdown = render_workflow(&workflow, &costs, ExportFormat::Markdown);
+        assert!(markdown.starts_with("# Research <draft>"));
+        assert!(markdown.contains("> A systems language."));
+        assert!(markdown.contains("Estimated cost: $0.0100"));
+
+        let html = render_workflow(&workflow, &costs, ExportFormat::Html);
+        assert!(html.contains("<h1>Research &lt;draft&gt;</h1>"));
+        assert!(html.contains("<strong>chatgpt</strong>"));
+        assert_eq!(
+            ExportFormat::from_path(Path::new("report.HTML")),
+            ExportFormat::Html
+        );
+
+        let now = chrono::Utc::now();
+        let session = Session {
+            id: "s1".into(),
+            provider: "chatgpt".into(),
+            conversation_id: None,
+            archived: false,
+            started_at: now,
+            updated_at: now,
+            turns: vec![Turn {
+                prompt: "What is Rust?".into(),
+                response: "A systems language.".into(),
+                timestamp: now,
+                provider: None,
+            }],
+            forked_from: None,
+            tenant: crate::tenant::DEFAULT_TENANT.into(),
+        };
+        let markdown = render_session(&session, &costs, ExportFormat::Markdown);
+        assert!(markdown.contains("## Turn 1"));
+        assert!(markdown.contains("**chatgpt**\n\n> A systems language."));
+        assert!(markdown.contains("1 turns · estimated total cost"));
+    }
+}
@@ -0,0 +1,651 @@
+//! Code-aware context extraction.
+//!
+//! Inlining whole files into a code prompt spends most of its tokens on code
+//! the question is not about. The extractor instead pulls the definition of a
+//! symbol out of the workspace's source files, together with the definitions
+//! of the types it mentions and the items that call it. Items are found with
+//! the code chunker's line-based rules rather than a full parser, so any
+//! language with conventional definitions works, at the cost of missing items
+//! written in unusual layouts.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::{code_items, is_preamble, Chunk};
+use crate::cost::estimate_tokens;
+use crate::error::{Error, Result};
+
+/// File extensions searched for definitions.
+pub const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "c", "h", "cc", "cpp", "hpp", "cs",
+    "rb", "swift", "scala", "php",
+];
+
+/// Directories never searched: build output, dependencies, and VCS data.
+const SKIPPED_DIRS: &[&str] = &[
+    "target",
+    "node_modules",
+    "dist",
+    "build",
+    "vendor",
+    "__pycache__",
+];
+
+/// Files larger than this are skipped as generated or vendored.
+const MAX_FILE_BYTES: u64 = 1_000_000;
+
+/// Largest type or class listed whole in a public API, in estimated tokens;
+/// larger ones are listed by their declaration.
+const MAX_API_ITEM_TOKENS: u64 = 400;
+
+/// Token budget used when none is requested.
+pub const DEFAULT_MAX_TOKENS: u64 = 8_000;
+
+/// Callers included when none is requested.
+pub const DEFAULT_MAX_CALLERS: usize = 5;
+
+/// Why an item was extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemRole {
+    /// Defines the symbol.
+    Definition,
+    /// Defines a type the symbol's definition mentions.
+    Type,
+    /// Uses the symbol.
+    Caller,
+}
+
+impl ItemRole {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Definition => "Definition",
+            Self::Type => "Type",
+            Self::Caller => "Caller",
+        }
+    }
+}
+
+/// An item pulled from a source file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractedItem {
+    /// File, relative to the workspace root.
+    pub path: PathBuf,
+    /// Item name.
+    pub name: String,
+    /// Why the item was extracted.
+    pub role: ItemRole,
+    /// First line, counting from 1.
+    pub start_line: usize,
+    /// Last line, inclusive.
+    pub end_line: usize,
+    /// Item source.
+    pub text: String,
+}
+
+impl ExtractedItem {
+    fn new(path: &Path, role: ItemRole, chunk: Chunk) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            name: chunk.label.unwrap_or_default(),
+            role,
+            start_line: chunk.start_line,
+            end_line: chunk.end_line,
+            text: chunk.text,
+        }
+    }
+
+    /// Estimated tokens of the item.
+    pub fn tokens(&self) -> u64 {
+        estimate_tokens(&self.text)
+    }
+
+    fn same_lines(&self, other: &Self) -> bool {
+        self.path == other.path
+            && self.start_line <= other.end_line
+            && other.start_line <= self.end_line
+    }
+}
+
+/// Items relevant to a symbol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Extraction {
+    /// Symbol extracted.
+    pub symbol: String,
+    /// Definitions, then types, then callers.
+    pub items: Vec<ExtractedItem>,
+    /// Relevant items left out to stay within the token budget.
+    pub omitted: Vec<ExtractedItem>,
+    /// Source files searched.
+    pub files_scanned: usize,
+    /// Estimated tokens of the whole files the items come from.
+    pub file_tokens: u64,
+}
+
+impl Extraction {
+    /// Estimated tokens of the extracted items.
+    pub fn tokens(&self) -> u64 {
+        self.items.iter().map(ExtractedItem::tokens).sum()
+    }
+
+    /// Items as Markdown, each in a fenced block headed by its location.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Code context for `{}`\n\n{} items, ~{} tokens (the files they come from: ~{} tokens; {} files searched).\n",
+            self.symbol,
+            self.items.len(),
+            self.tokens(),
+            self.file_tokens,
+            self.files_scanned
+        );
+        for item in &self.items {
+            let language = item
+                .path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "\n## {}: `{}` ({}:{}-{})\n\n```{}\n{}\n```\n",
+                item.role.name(),
+                item.name,
+                item.path.display(),
+                item.start_line,
+                item.end_line,
+                language,
+                item.text
+            ));
+        }
+        if !self.omitted.is_empty() {
+            let names: Vec<String> = self
+                .omitted
+                .iter()
+                .map(|i| format!("`{}` ({})", i.name, i.role.name().to_lowercase()))
+                .collect();
+            out.push_str(&format!(
+                "\n*Left out for the token budget: {}.*\n",
+                names.join(", ")
+            ));
+        }
+        out
+    }
+}
+
+/// Pulls symbols and their surroundings out of a source tree.
+#[derive(Debug, Clone)]
+pub struct CodeExtractor {
+    root: PathBuf,
+    max_tokens: u64,
+    max_callers: usize,
+}
+
+impl CodeExtractor {
+    /// Search the source files under `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            max_callers: DEFAULT_MAX_CALLERS,
+        }
+    }
+
+    /// Set the token budget for extracted items. The first definition is
+    /// always included.
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Set the number of callers included.
+    pub fn max_callers(mut self, max_callers: usize) -> Self {
+        self.max_callers = max_callers;
+        self
+    }
+
+    /// Extract `symbol`'s definition, the types it mentions, and its callers.
+    pub fn extract(&self, symbol: &str) -> Result<Extraction> {
+        if !is_identifier(symbol) {
+            return Err(Error::InvalidParams(format!(
+                "'{}' is not a symbol name",
+                symbol
+            )));
+        }
+        let files = self.source_files()?;
+
+        let mut definitions: Vec<ExtractedItem> = files
+            .iter()
+            .flat_map(|file| {
+                file.items
+                    .iter()
+                    .filter(|chunk| chunk.label.as_deref() == Some(symbol))
+                    .map(|chunk| {
+                        ExtractedItem::new(&file.path, ItemRole::Definition, chunk.clone())
+                    })
+            })
+            .collect();
+        if definitions.is_empty() {
+            definitions.extend(files.iter().find_map(|file| {
+                let chunk = nested_definition(&file.text, symbol)?;
+                Some(ExtractedItem::new(&file.path, ItemRole::Definition, chunk))
+            }));
+        }
+        if definitions.is_empty() {
+            return Err(Error::InvalidParams(format!(
+                "no definition of '{}' found under {}",
+                symbol,
+                self.root.display()
+            )));
+        }
+
+        let mut types = Vec::new();
+        let mut seen: HashSet<&str> = HashSet::from([symbol]);
+        let mentioned: Vec<&str> = definitions
+            .iter()
+            .flat_map(|d| type_name_re().find_iter(&d.text).map(|m| m.as_str()))
+            .collect();
+        for name in mentioned {
+            if !seen.insert(name) {
+                continue;
+            }
+            types.extend(files.iter().find_map(|file| {
+                let chunk = file.items.iter().find(|chunk| {
+                    chunk.label.as_deref() == Some(name) && defines_type(&chunk.text, name)
+                })?;
+                Some(ExtractedItem::new(
+                    &file.path,
+                    ItemRole::Type,
+                    chunk.clone(),
+                ))
+            }));
+        }
+
+        let uses = Regex::new(&format!(r"\b{}\b", regex::escape(symbol)))
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        let callers: Vec<ExtractedItem> = files
+            .iter()
+            .flat_map(|file| {
+                file.items
+                    .iter()
+                    .filter(|chunk| chunk.label.is_some() && uses.is_match(&chunk.text))
+                    .map(|chunk| narrow_to_use(chunk, &uses))
+                    .map(|chunk| ExtractedItem::new(&file.path, ItemRole::Caller, chunk))
+            })
+            .filter(|caller| !definitions.iter().any(|d| d.same_lines(caller)))
+            .take(self.max_callers)
+            .collect();
+
+        let mut items: Vec<ExtractedItem> = Vec::new();
+        let mut omitted = Vec::new();
+        let mut used = 0;
+        for item in definitions.into_iter().chain(types).chain(callers) {
+            if items.iter().any(|i| i.same_lines(&item)) {
+                continue;
+            }
+            if !items.is_empty() && used + item.tokens() > self.max_tokens {
+                omitted.push(item);
+                continue;
+            }
+            used += item.tokens();
+            items.push(item);
+        }
+        let file_tokens = files
+            .iter()
+            .filter(|file| items.iter().any(|i| i.path == file.path))
+            .map(|file| estimate_tokens(&file.text))
+            .sum();
+
+        Ok(Extraction {
+            symbol: symbol.to_string(),
+            items,
+            omitted,
+            files_scanned: files.len(),
+            file_tokens,
+        })
+    }
+
+    /// The public API of every source file that has one, in path order.
+    pub fn public_api(&self) -> Result<Vec<ModuleApi>> {
+        Ok(self
+            .source_files()?
+            .into_iter()
+            .map(|file| ModuleApi {
+                items: file.items.iter().filter_map(api_item).collect(),
+                path: file.path,
+            })
+            .filter(|module| !module.items.is_empty())
+            .collect())
+    }
+
+    /// Every source file under the root, split into items, in path order.
+    fn source_files(&self) -> Result<Vec<SourceFile>> {
+        let mut paths = Vec::new();
+        source_files(&self.root, &mut paths)?;
+        paths.sort();
+        Ok(paths
+            .iter()
+            .filter_map(|path| {
+                let text = std::fs::read_to_string(path).ok()?;
+                let path = path.strip_prefix(&self.root).unwrap_or(path).to_path_buf();
+                Some(SourceFile {
+                    items: code_items(&text),
+                    path,
+                    text,
+                })
+            })
+            .collect())
+    }
+}
+
+/// The public items of one source file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleApi {
+    /// File, relative to the searched directory.
+    pub path: PathBuf,
+    /// Public items with their doc comments: functions by signature, types
+    /// whole unless large.
+    pub items: Vec<String>,
+}
+
+impl ModuleApi {
+    /// The items, separated by blank lines.
+    pub fn text(&self) -> String {
+        self.items.join("\n\n")
+    }
+}
+
+/// An item's public API, if it is public: its signature for functions and
+/// large types, the whole item for small types, and the public methods of
+/// a Rust `impl` block.
+fn api_item(chunk: &Chunk) -> Option<String> {
+    let lines: Vec<&str> = chunk.text.lines().collect();
+    let header = lines
+        .iter()
+        .position(|line| !line.trim().is_empty() && !is_preamble(line))?;
+    let head = lines[header];
+    let name = chunk.label.as_deref().unwrap_or_default();
+
+    if head.starts_with("impl") {
+        let methods: Vec<String> = (header + 1..lines.len())
+            .filter(|&i| public_fn_re().is_match(lines[i]))
+            .map(|i| {
+                let mut first = i;
+                while first > 0 && is_preamble(lines[first - 1]) {
+                    first -= 1;
+                }
+                signature(&lines[first..], i - first)
+            })
+            .collect();
+        return (!methods.is_empty())
+            .then(|| format!("{} {{\n{}\n}}", signature(&lines, header), methods.join("\n\n")));
+    }
+
+    let public = match head.split_whitespace().next()? {
+        "pub" | "export" | "public" => true,
+        restricted if restricted.starts_with("pub(") => false,
+        "def" | "class" | "async" => !name.starts_with('_'),
+        "func" | "type" => name.starts_with(|c: char| c.is_ascii_uppercase()),
+        _ => false,
+    };
+    if !public {
+        return None;
+    }
+    let function = head
+        .split_whitespace()
+        .any(|word| matches!(word, "fn" | "def" | "function" | "func"));
+    if !function && estimate_tokens(&chunk.text) <= MAX_API_ITEM_TOKENS {
+        return Some(chunk.text.trim_end().to_string());
+    }
+    Some(signature(&lines, header))
+}
+
+/// Doc comments and attributes, then the declaration from line `header` up
+/// to where its body starts.
+fn signature(lines: &[&str], header: usize) -> String {
+    let mut out: Vec<String> = lines[..header].iter().map(|l| l.to_string()).collect();
+    for line in &lines[header..] {
+        if let Some((declaration, _)) = line.split_once('{') {
+            out.push(declaration.trim_end().to_string());
+            break;
+        }
+        out.push(line.to_string());
+        let end = line.trim_end();
+        if end.ends_with(';') || end.ends_with(':') {
+            break;
+        }
+    }
+    out.join("\n")
+}
+
+/// A source file split into top-level items.
+struct SourceFile {
+    path: PathBuf,
+    text: String,
+    items: Vec<Chunk>,
+}
+
+/// Collect source files under `dir`, skipping hidden and build directories.
+fn source_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                source_files(&path, out)?;
+            }
+        } else if file_type.is_file()
+            && path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| SOURCE_EXTENSIONS.contains(&e))
+            && entry.metadata()?.len() <= MAX_FILE_BYTES
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_identifier(symbol: &str) -> bool {
+    let mut chars = symbol.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Matches capitalized identifiers, which name types in most languages.
+fn type_name_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b[A-Z][A-Za-z0-9_]*\b").unwrap())
+}
+
+/// Matches a type definition and captures its name.
+fn type_def_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(?m)^(?:pub(?:\([^)]*\))?\s+)?(?:export\s+)?(?:struct|enum|trait|type|union|class|interface)\s+([A-Za-z_][A-Za-z0-9_]*)",
+        )
+        .unwrap()
+    })
+}
+
+/// Matches an indented function or method definition and captures its name.
+fn nested_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^\s+(?:pub(?:\([^)]*\))?\s+)?(?:static\s+)?(?:async\s+)?(?:unsafe\s+)?(?:fn|def|function|func)\s+([A-Za-z_][A-Za-z0-9_]*)",
+        )
+        .unwrap()
+    })
+}
+
+/// Matches a public method inside a Rust `impl` block.
+fn public_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s+pub\s+(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?fn\s").unwrap()
+    })
+}
+
+/// Check if an item defines the type `name` (rather than, say, implementing it).
+fn defines_type(text: &str, name: &str) -> bool {
+    type_def_re()
+        .captures_iter(text)
+        .any(|caps| &caps[1] == name)
+}
+
+/// A method or nested function named `symbol`, for symbols that are not
+/// top-level items.
+fn nested_definition(text: &str, symbol: &str) -> Option<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.iter().position(|line| {
+        nested_fn_re()
+            .captures(line)
+            .is_some_and(|caps| &caps[1] == symbol)
+    })?;
+    Some(block(&lines, 0, start, symbol))
+}
+
+/// Narrow an item using a symbol to the method or nested function that does,
+/// so a call inside a large `impl` or class does not pull in all of it.
+fn narrow_to_use(chunk: &Chunk, uses: &Regex) -> Chunk {
+    let lines: Vec<&str> = chunk.text.lines().collect();
+    let Some(use_line) = lines.iter().position(|line| uses.is_match(line)) else {
+        return chunk.clone();
+    };
+    let offset = chunk.start_line - 1;
+    lines[..=use_line]
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, line)| {
+            let caps = nested_fn_re().captures(line)?;
+            let found = block(&lines, offset, i, &caps[1]);
+            (found.end_line > offset + use_line).then_some(found)
+        })
+        .unwrap_or_else(|| chunk.clone())
+}
+
+/// The indented block starting at `start`, with the doc comments and
+/// attributes above it, ending where the indentation drops back.
+fn block(lines: &[&str], offset: usize, start: usize, name: &str) -> Chunk {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let depth = indent(lines[start]);
+    let header = lines[start].trim_end();
+    let one_line = header.ends_with(';')
+        || (header.contains('{') && header.matches('{').count() == header.matches('}').count());
+    let mut end = start;
+    if !one_line {
+        end = lines.len() - 1;
+        for (j, line) in lines.iter().enumerate().skip(start + 1) {
+            if !line.trim().is_empty() && indent(line) <= depth {
+                let closes = line.trim_start().starts_with(['}', ')', ']']);
+                end = if closes { j } else { j - 1 };
+                break;
+            }
+        }
+        while end > start && lines[end].trim().is_empty() {
+            end -= 1;
+        }
+    }
+    let mut first = start;
+    while first > 0 && is_preamble(lines[first - 1]) {
+        first -= 1;
+    }
+    Chunk {
+        text: lines[first..=end].join("\n"),
+        start_line: offset + first + 1,
+        end_line: offset + end + 1,
+        label: Some(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_symbol_context() {
+        let root = std::env::temp_dir().join(format!("agent-mcp-extract-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(
+            root.join("src/lib.rs"),
+            "use std::fmt;\n\n/// A point.\npub struct Point {\n    x: i32,\n}\n\n/// Distance from the origin.\npub fn norm(p: &Point) -> i32 {\n    p.x.abs()\n}\n\npub fn unrelated() {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("src/app.rs"),
+            "struct App;\n\nimpl App {\n    fn new() -> Self {\n        App\n    }\n\n    fn run(&self) {\n        let p = make();\n        println!(\"{}\", norm(&p));\n    }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("target/gen.rs"), "fn caller() { norm(); }\n").unwrap();
+
+        let extraction = CodeExtractor::new(&root).extract("norm").unwrap();
+        let found: Vec<_> = extraction
+            .items
+            .iter()
+            .map(|i| (i.role, i.name.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (ItemRole::Definition, "norm"),
+                (ItemRole::Type, "Point"),
+                (ItemRole::Caller, "run")
+            ]
+        );
+        assert!(extraction.items[0].text.starts_with("/// Distance"));
+        assert_eq!(
+            (extraction.items[2].start_line, extraction.items[2].end_line),
+            (8, 11)
+        );
+        assert_eq!(extraction.files_scanned, 2);
+        assert!(extraction
+            .to_markdown()
+            .contains("## Caller: `run` (src/app.rs:8-11)"));
+
+        let method = CodeExtractor::new(&root).extract("run").unwrap();
+        assert_eq!(method.items[0].text.lines().count(), 4);
+
+        let tight = CodeExtractor::new(&root)
+            .max_tokens(1)
+            .extract("norm")
+            .unwrap();
+        assert_eq!(tight.items.len(), 1);
+        assert_eq!(tight.omitted.len(), 2);
+
+        assert!(CodeExtractor::new(&root).extract("missing").is_err());
+        assert!(CodeExtractor::new(&root).extract("../etc").is_err());
+
+        std::fs::write(
+            root.join("src/api.rs"),
+            "pub(crate) fn internal() {}\n\nimpl Api {\n    /// Builds one.\n    pub fn new() -> Self {\n        Api\n    }\n\n    fn hidden(&self) {}\n}\n",
+        )
+        .unwrap();
+        let api = CodeExtractor::new(&root).public_api().unwrap();
+        let paths: Vec<_> = api.iter().map(|m| m.path.to_str().unwrap()).collect();
+        assert_eq!(paths, ["src/api.rs", "src/lib.rs"]);
+        assert_eq!(
+            api[0].items,
+            ["impl Api {\n    /// Builds one.\n    pub fn new() -> Self\n}"]
+        );
+        assert_eq!(
+            api[1].items,
+            [
+                "/// A point.\npub struct Point {\n    x: i32,\n}",
+                "/// Distance from the origin.\npub fn norm(p: &Point) -> i32",
+                "pub fn unrelated()"
+            ]
+        );
+        std::fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,222 @@
+//! Fact-checking claims with a search-capable provider.
+//!
+//! The text is split into claims the same way consensus compares responses,
+//! and a provider with web access is asked to verify each one and cite its
+//! sources. The original text comes back annotated with each claim's verdict
+//! and confidence, followed by the notes and sources behind them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::split_claims;
+
+/// Default maximum number of claims checked.
+pub const DEFAULT_MAX_CLAIMS: usize = 10;
+/// Rough prompt and response tokens per claim, for cost estimates before the
+/// claims are known.
+pub const TOKENS_PER_CLAIM: u64 = 150;
+
+/// Outcome of checking one claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// Sources support the claim.
+    Supported,
+    /// Sources contradict the claim.
+    Refuted,
+    /// The claim could not be verified either way.
+    Unverified,
+}
+
+impl Verdict {
+    /// Short marker shown next to the claim.
+    pub fn marker(self) -> &'static str {
+        match self {
+            Self::Supported => "✓",
+            Self::Refuted => "✗",
+            Self::Unverified => "?",
+        }
+    }
+}
+
+/// Result of checking one claim.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaimCheck {
+    /// The claim, as it appears in the text.
+    pub claim: String,
+    /// Whether the sources support it.
+    pub verdict: Verdict,
+    /// Checker's confidence in the verdict (0.0-1.0).
+    pub confidence: f64,
+    /// URLs of the sources consulted.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Correction or explanation, if any.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// A fact-checked text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FactCheckReport {
+    /// The text with each checked claim annotated, followed by the notes.
+    pub annotated: String,
+    /// Every claim checked, in text order.
+    pub checks: Vec<ClaimCheck>,
+    /// Provider that checked the claims.
+    pub checker: String,
+}
+
+impl FactCheckReport {
+    /// Number of claims with a verdict.
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.checks.iter().filter(|c| c.verdict == verdict).count()
+    }
+}
+
+/// Checkable claims in a text: declarative sentences, in order, at most
+/// `max` of them.
+pub fn extract_claims(text: &str, max: usize) -> Vec<String> {
+    split_claims(text)
+        .into_iter()
+        .filter(|c| !c.ends_with('?') && !c.starts_with("```"))
+        .take(max)
+        .collect()
+}
+
+/// Build the prompt asking a provider to verify the numbered claims.
+pub fn fact_check_prompt(claims: &[String], focus: Option<&str>) -> String {
+    let list: Vec<String> = claims
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}. {}", i + 1, c))
+        .collect();
+    let focus = focus
+        .filter(|f| !f.trim().is_empty())
+        .map(|f| format!("Focus: {}\n\n", f))
+        .unwrap_or_default();
+    format!(
+        "Fact-check each numbered claim below against current web sources.\n\n\
+         Respond with only a JSON array containing one object per claim: \
+         {{\"id\": <claim number>, \"verdict\": \"supported\" | \"refuted\" | \"unverified\", \
+         \"confidence\": <0.0-1.0>, \"sources\": [<URLs you relied on>], \
+         \"note\": <correction or explanation, or null>}}. Use \"unverified\" when \
+         sources do not settle the claim.\n\n{}Claims:\n{}",
+        focus,
+        list.join("\n")
+    )
+}
+
+/// Parse a provider's verdicts. Claims it skipped or answered unreadably are
+/// reported as unverified with zero confidence.
+pub fn parse_checks(text: &str, claims: &[String]) -> Vec<ClaimCheck> {
+    #[derive(Deserialize)]
+    struct Entry {
+        id: usize,
+        verdict: Verdict,
+        #[serde(default)]
+        confidence: Option<f64>,
+        #[serde(default)]
+        sources: Vec<String>,
+        #[serde(default)]
+        note: Option<String>,
+    }
+
+    let json = match (text.find('['), text.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => "[]",
+    };
+    let entries: Vec<Entry> = serde_json::from_str(json).unwrap_or_default();
+
+    claims
+        .iter()
+        .enumerate()
+        .map(|(i, claim)| match entries.iter().find(|e| e.id == i + 1) {
+            Some(entry) => ClaimCheck {
+                claim: claim.clone(),
+                verdict: entry.verdict,
+                confidence: entry.confidence.unwrap_or(0.5).clamp(0.0, 1.0),
+                sources: entry.sources.clone(),
+                note: entry.note.clone().filter(|n| !n.trim().is_empty()),
+            },
+            None => ClaimCheck {
+                claim: claim.clone(),
+                verdict: Verdict::Unverified,
+                confidence: 0.0,
+                sources: Vec::new(),
+                note: Some("not checked".into()),
+            },
+        })
+        .collect()
+}
+
+/// Annotate each checked claim in the text with a numbered verdict marker,
+/// and append the notes and sources behind each verdict.
+pub fn annotate(text: &str, checks: &[ClaimCheck]) -> String {
+    let mut annotated = text.to_string();
+    let mut notes = Vec::new();
+    let mut cursor = 0;
+    for (i, check) in checks.iter().enumerate() {
+        let marker = format!(
+            " [{}{} {:.0}%]",
+            check.verdict.marker(),
+            i + 1,
+            check.confidence * 100.0
+        );
+        // Claims are in text order, so search onward from the last one.
+        if let Some(pos) = annotated[cursor..].find(&check.claim) {
+            let end = cursor + pos + check.claim.len();
+            annotated.insert_str(end, &marker);
+            cursor = end + marker.len();
+        }
+
+        let mut note = format!(
+            "{}. {} **{:?}** ({:.0}%): {}",
+            i + 1,
+            check.verdict.marker(),
+            check.verdict,
+            check.confidence * 100.0,
+            check.claim
+        );
+        if let Some(detail) = &check.note {
+            note.push_str(&format!(" — {}", detail));
+        }
+        for source in &check.sources {
+            note.push_str(&format!("\n   - {}", source));
+        }
+        notes.push(note);
+    }
+    if notes.is_empty() {
+        return annotated;
+    }
+    format!(
+        "{}\n\n## Fact Check\n\n{}",
+        annotated.trim_end(),
+        notes.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_and_annotate() {
+        let text = "Rust 1.0 was released in May 2015 by Mozilla.\n\
+                    Is it the fastest language ever made?\n\
+                    The borrow checker runs at compile time only.";
+        let claims = extract_claims(text, DEFAULT_MAX_CLAIMS);
+        assert_eq!(claims.len(), 2);
+        assert!(fact_check_prompt(&claims, Some("dates")).contains("2. The borrow checker"));
+
+        let reply = "```json\n[{\"id\": 1, \"verdict\": \"supported\", \"confidence\": 0.95, \
+                     \"sources\": [\"https://blog.rust-lang.org/2015/05/15/Rust-1.0.html\"]}]\n```";
+        let checks = parse_checks(reply, &claims);
+        assert_eq!(checks[0].verdict, Verdict::Supported);
+        assert_eq!(checks[1].verdict, Verdict::Unverified);
+
+        let annotated = annotate(text, &checks);
+        assert!(annotated.starts_with("Rust 1.0 was released in May 2015 by Mozilla. [✓1 95%]"));
+        assert!(annotated.contains("compile time only. [?2 0%]"));
+        assert!(annotated.contains("   - https://blog.rust-lang.org/2015/05/15/Rust-1.0.html"));
+    }
+}
@@ -0,0 +1,347 @@
+//! Fair-share scheduling of contended providers across tenants.
+//!
+//! Without it, calls waiting on a busy provider are served first come,
+//! first served, so a tenant that queues a long batch holds the provider
+//! until the whole batch is done. With fair-share scheduling, each provider
+//! has a number of slots, and when one frees up it goes to the waiting
+//! tenant that has used the provider least lately: the provider time its
+//! calls took, decayed with a half-life, plus the time its running calls
+//! have taken so far, divided by the tenant's share. A tenant's own calls
+//! are still served in order.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::error::{Error, Result};
+
+/// How contended providers are shared between tenants.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FairSharePolicy {
+    /// Calls each provider takes at once, keyed by provider name; providers
+    /// without an entry take `browser_pool_size` calls.
+    pub slots: HashMap<String, usize>,
+    /// Time after which half of a tenant's past usage is forgotten, in
+    /// seconds.
+    pub half_life_secs: u64,
+}
+
+impl Default for FairSharePolicy {
+    fn default() -> Self {
+        Self {
+            slots: HashMap::new(),
+            half_life_secs: 600,
+        }
+    }
+}
+
+impl FairSharePolicy {
+    /// Calls `provider` takes at once, or `default` if not configured.
+    pub fn slots_for(&self, provider: Provider, default: usize) -> usize {
+        self.slots
+            .get(&provider.to_string())
+            .copied()
+            .unwrap_or(default)
+            .max(1)
+    }
+}
+
+/// A tenant's provider time, decayed as it ages.
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    secs: f64,
+    at: Instant,
+}
+
+/// A call holding a slot.
+#[derive(Debug)]
+struct Running {
+    id: u64,
+    tenant: String,
+    started: Instant,
+}
+
+/// A call waiting for a slot.
+#[derive(Debug)]
+struct Waiter {
+    seq: u64,
+    tenant: String,
+    share: f64,
+    tx: oneshot::Sender<FairPermit>,
+}
+
+/// Slots, running calls, and waiting calls of one provider.
+#[derive(Debug, Default)]
+struct Queue {
+    slots: usize,
+    running: Vec<Running>,
+    waiting: Vec<Waiter>,
+    usage: HashMap<String, Usage>,
+    next_id: u64,
+}
+
+impl Queue {
+    /// Provider time `tenant`'s finished calls took, decayed.
+    fn past_secs(&self, tenant: &str, half_life: Duration, now: Instant) -> f64 {
+        self.usage.get(tenant).map_or(0.0, |u| {
+            let age = now.duration_since(u.at).as_secs_f64();
+            u.secs * 0.5f64.powf(age / half_life.as_secs_f64().max(1.0))
+        })
+    }
+
+    /// Recent provider time used by `tenant`, including its running calls.
+    fn used_secs(&self, tenant: &str, half_life: Duration, now: Instant) -> f64 {
+        let running: f64 = self
+            .running
+            .iter()
+            .filter(|r| r.tenant == tenant)
+            .map(|r| now.duration_since(r.started).as_secs_f64())
+            .sum();
+        self.past_secs(tenant, half_life, now) + running
+    }
+
+    /// Add `secs` of provider time to `tenant`'s usage.
+    fn charge(&mut self, tenant: &str, secs: f64, half_life: Duration, now: Instant) {
+        let past = self.past_secs(tenant, half_life, now);
+        self.usage.insert(
+            tenant.to_string(),
+            Usage {
+                secs: past + secs,
+                at: now,
+            },
+        );
+    }
+
+    /// Give a slot to `tenant`.
+    fn start(&mut self, tenant: &str) -> u64 {
+        self.next_id += 1;
+        self.running.push(Running {
+            id: self.next_id,
+            tenant: tenant.to_string(),
+            started: Instant::now(),
+        });
+        self.next_id
+    }
+
+    /// Index of the waiter served next: the least recent usage for its
+    /// share, and the earliest among equals.
+    fn next_waiter(&self, half_life: Duration) -> Option<usize> {
+        let now = Instant::now();
+        let mut used: HashMap<&str, f64> = HashMap::new();
+        self.waiting
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let secs = *used
+                    .entry(&w.tenant)
+                    .or_insert_with(|| self.used_secs(&w.tenant, half_life, now));
+                (i, secs / w.share, w.seq)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.2.cmp(&b.2)))
+            .map(|(i, _, _)| i)
+    }
+}
+
+type Queues = HashMap<Provider, Queue>;
+
+/// Shares provider slots between tenants.
+#[derive(Debug, Clone)]
+pub struct FairScheduler {
+    half_life: Duration,
+    queues: Arc<Mutex<Queues>>,
+}
+
+impl FairScheduler {
+    /// Create a scheduler forgetting half of past usage every `half_life`.
+    pub fn new(half_life: Duration) -> Self {
+        Self {
+            half_life,
+            queues: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Wait for one of `slots` slots on `provider` for a call by `tenant`,
+    /// holding it until the permit is dropped.
+    pub async fn acquire(
+        &self,
+        provider: Provider,
+        tenant: &str,
+        share: f64,
+        slots: usize,
+    ) -> Result<FairPermit> {
+        let rx = {
+            let mut queues = self.lock();
+            let queue = queues.entry(provider).or_default();
+            queue.slots = slots.max(1);
+            if queue.waiting.is_empty() && queue.running.len() < queue.slots {
+                let id = queue.start(tenant);
+                return Ok(self.permit(provider, id, tenant));
+            }
+            let (tx, rx) = oneshot::channel();
+            queue.next_id += 1;
+            let seq = queue.next_id;
+            queue.waiting.push(Waiter {
+                seq,
+                tenant: tenant.to_string(),
+                share: share.max(f64::MIN_POSITIVE),
+                tx,
+            });
+            rx
+        };
+        rx.await
+            .map_err(|_| Error::Internal(format!("fair-share queue for {} closed", provider)))
+    }
+
+    /// Calls waiting on `provider`, by tenant.
+    pub fn waiting(&self, provider: Provider) -> HashMap<String, usize> {
+        let mut waiting = HashMap::new();
+        if let Some(queue) = self.lock().get(&provider) {
+            for waiter in &queue.waiting {
+                *waiting.entry(waiter.tenant.clone()).or_default() += 1;
+            }
+        }
+        waiting
+    }
+
+    fn permit(&self, provider: Provider, id: u64, tenant: &str) -> FairPermit {
+        FairPermit {
+            scheduler: self.clone(),
+            provider,
+            id,
+            tenant: tenant.to_string(),
+        }
+    }
+
+    /// Free a slot and hand free slots to waiters.
+    fn release(&self, provider: Provider, id: u64, tenant: &str) {
+        // Permits a waiter stopped waiting for are dropped after unlocking,
+        // releasing their slots in turn
+        let mut abandoned = Vec::new();
+        {
+            let mut queues = self.lock();
+            let Some(queue) = queues.get_mut(&provider) else {
+                return;
+            };
+            let now = Instant::now();
+            if let Some(i) = queue.running.iter().position(|r| r.id == id) {
+                let running = queue.running.remove(i);
+                let secs = now.duration_since(running.started).as_secs_f64();
+                queue.charge(tenant, secs, self.half_life, now);
+            }
+            queue.waiting.retain(|w| !w.tx.is_closed());
+            while queue.running.len() < queue.slots {
+                let Some(i) = queue.next_waiter(self.half_life) else {
+                    break;
+                };
+                let waiter = queue.waiting.remove(i);
+                let id = queue.start(&waiter.tenant);
+                if let Err(permit) = waiter.tx.send(self.permit(provider, id, &waiter.tenant)) {
+                    abandoned.push(permit);
+                }
+            }
+        }
+        drop(abandoned);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queues> {
+        self.queues.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A slot on a provider, freed when dropped.
+#[derive(Debug)]
+pub struct FairPermit {
+    scheduler: FairScheduler,
+    provider: Provider,
+    id: u64,
+    tenant: String,
+}
+
+impl Drop for FairPermit {
+    fn drop(&mut self) {
+        self.scheduler.release(self.provider, self.id, &self.tenant);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+    use crate::testkit::MockProviders;
+
+    #[tokio::test]
+    async fn test_waiting_tenants_take_turns() {
+        let scheduler = FairScheduler::new(Duration::from_secs(600));
+        let claude = Provider::Claude;
+        let first = scheduler.acquire(claude, "batch", 1.0, 1).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        // A batch queues ahead of an interactive tenant
+        let mut batch = Vec::new();
+        for _ in 0..3 {
+            let scheduler = scheduler.clone();
+            batch.push(tokio::spawn(async move {
+                scheduler.acquire(claude, "batch", 1.0, 1).await.unwrap()
+            }));
+            tokio::task::yield_now().await;
+        }
+        let interactive = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(claude, "team", 1.0, 1).await.unwrap() })
+        };
+        while scheduler.waiting(claude).values().sum::<usize>() < 4 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(scheduler.waiting(claude)["batch"], 3);
+
+        // The freed slot goes to the tenant that has not used the provider
+        drop(first);
+        let permit = interactive.await.unwrap();
+        assert_eq!(permit.tenant, "team");
+        assert_eq!(scheduler.waiting(claude)["batch"], 3);
+
+        // Then the batch continues, in order
+        drop(permit);
+        for handle in batch {
+            drop(handle.await.unwrap());
+        }
+        assert!(scheduler.waiting(claude).is_empty());
+
+        // A waiter that gives up does not hold its slot
+        let held = scheduler.acquire(claude, "batch", 1.0, 1).await.unwrap();
+        let abandoned = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire(claude, "batch", 1.0, 1).await })
+        };
+        while scheduler.waiting(claude).is_empty() {
+            tokio::task::yield_now().await;
+        }
+        abandoned.abort();
+        let _ = abandoned.await;
+        drop(held);
+        let again = tokio::time::timeout(
+            Duration::from_secs(1),
+            scheduler.acquire(claude, "team", 1.0, 1),
+        )
+        .await;
+        assert!(again.is_ok());
+
+        // Providers answer through the scheduler when it is enabled
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(MockProviders::new().with_reply(claude, "Hi")),
+            fair_share: Some(FairSharePolicy::default()),
+            ..Default::default()
+        });
+        let response = orchestrator
+            .for_tenant("team")
+            .prompt_provider(claude, "Hello")
+            .await
+            .unwrap();
+        assert_eq!(response.text, "Hi");
+    }
+}
@@ -0,0 +1,421 @@
+//! GitHub pull requests, issues, and comments.
+//!
+//! Workflows can review a pull request's diff, triage a repository's issues,
+//! and post the result back as a comment. Requests go to the GitHub REST API,
+//! authenticated with the token in `GITHUB_TOKEN` when it is set; public
+//! repositories can be read without one, but commenting always needs it.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Environment variable holding the GitHub API token.
+pub const TOKEN_ENV_VAR: &str = "GITHUB_TOKEN";
+/// GitHub REST API root.
+const API_BASE: &str = "https://api.github.com";
+/// Deadline for a single API request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Most issues fetched for a repository's open issues.
+pub const MAX_ISSUES: usize = 20;
+
+/// A pull request or issue, written `owner/repo#number`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueRef {
+    /// Repository owner.
+    pub owner: String,
+    /// Repository name.
+    pub repo: String,
+    /// Pull request or issue number.
+    pub number: u64,
+}
+
+impl fmt::Display for IssueRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}#{}", self.owner, self.repo, self.number)
+    }
+}
+
+impl FromStr for IssueRef {
+    type Err = Error;
+
+    /// Parse `owner/repo#123` or a `https://github.com/owner/repo/pull/123`
+    /// (or `/issues/123`) URL.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidParams(format!(
+                "'{}' is not a pull request or issue (expected owner/repo#number)",
+                s
+            ))
+        };
+        let s = s.trim();
+        let (path, number) = match s.strip_prefix("https://github.com/") {
+            Some(rest) => {
+                let parts: Vec<&str> = rest.trim_end_matches('/').split('/').collect();
+                match parts[..] {
+                    [owner, repo, "pull" | "issues", number, ..] => {
+                        (format!("{}/{}", owner, repo), number.to_string())
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            None => {
+                let (path, number) = s.split_once('#').ok_or_else(invalid)?;
+                (path.to_string(), number.to_string())
+            }
+        };
+        let (owner, repo) = path.split_once('/').ok_or_else(invalid)?;
+        let valid = |part: &str| {
+            !matches!(part, "" | "." | "..")
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        if !valid(owner) || !valid(repo) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            number: number.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// The changes a diff makes to one file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDiff {
+    /// Path of the file after the change.
+    pub path: String,
+    /// The file's part of the diff, headers included.
+    pub diff: String,
+}
+
+/// Split a unified diff from `git diff` into one diff per file.
+pub fn split_diff(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            let path = header
+                .rsplit_once(" b/")
+                .map_or(header, |(_, path)| path)
+                .to_string();
+            files.push(FileDiff {
+                path,
+                diff: String::new(),
+            });
+        }
+        if let Some(file) = files.last_mut() {
+            file.diff.push_str(line);
+            file.diff.push('\n');
+        }
+    }
+    files
+}
+
+/// A GitHub issue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Issue {
+    /// Where the issue is.
+    pub reference: IssueRef,
+    /// Issue title.
+    pub title: String,
+    /// Issue description, empty if there is none.
+    pub body: String,
+    /// Labels already applied.
+    pub labels: Vec<String>,
+    /// Web page of the issue.
+    pub url: String,
+}
+
+/// Issues fetched together, with the labels their repositories define.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueBatch {
+    /// Labels defined in the issues' repositories.
+    pub labels: Vec<String>,
+    /// The issues, in the order requested or most recent first.
+    pub issues: Vec<Issue>,
+}
+
+/// Which issues to fetch: written `owner/repo` for a repository's most
+/// recent open issues, or as a comma-separated list of `owner/repo#number`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueSelection {
+    /// The most recent open issues of a repository.
+    Open {
+        /// Repository owner.
+        owner: String,
+        /// Repository name.
+        repo: String,
+    },
+    /// These issues.
+    Listed(Vec<IssueRef>),
+}
+
+impl FromStr for IssueSelection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.contains('#') || s.contains("github.com/") {
+            return s
+                .split(',')
+                .map(|issue| issue.trim().parse())
+                .collect::<Result<_>>()
+                .map(Self::Listed);
+        }
+        // Parse as an issue to reuse the name checks.
+        let repo: IssueRef = format!("{}#0", s.trim()).parse()?;
+        Ok(Self::Open {
+            owner: repo.owner,
+            repo: repo.repo,
+        })
+    }
+}
+
+/// An issue as the REST API returns it.
+#[derive(Deserialize)]
+struct ApiIssue {
+    number: u64,
+    title: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    labels: Vec<ApiLabel>,
+    html_url: String,
+    /// Present when the issue is a pull request.
+    #[serde(default)]
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ApiLabel {
+    name: String,
+}
+
+impl ApiIssue {
+    fn into_issue(self, owner: &str, repo: &str) -> Issue {
+        Issue {
+            reference: IssueRef {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                number: self.number,
+            },
+            title: self.title,
+            body: self.body.unwrap_or_default(),
+            labels: self.labels.into_iter().map(|l| l.name).collect(),
+            url: self.html_url,
+        }
+    }
+}
+
+/// Client for the GitHub REST API.
+#[derive(Clone)]
+pub struct GitHubClient {
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+impl GitHubClient {
+    /// Create a client using the token in `GITHUB_TOKEN`, if set.
+    pub fn from_env() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            token: std::env::var(TOKEN_ENV_VAR).ok().filter(|t| !t.is_empty()),
+        }
+    }
+
+    /// Unified diff of a pull request.
+    pub async fn pull_request_diff(&self, pr: &IssueRef) -> Result<String> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls/{}",
+            API_BASE, pr.owner, pr.repo, pr.number
+        );
+        let what = pr.to_string();
+        let response = self
+            .request(self.client.get(url))
+            .header(reqwest::header::ACCEPT, "application/vnd.github.diff")
+            .send()
+            .await
+            .map_err(|e| request_error(&what, e))?;
+        let response = check(&what, response).await?;
+        response.text().await.map_err(|e| request_error(&what, e))
+    }
+
+    /// An issue, with its description and labels.
+    pub async fn issue(&self, issue: &IssueRef) -> Result<Issue> {
+        let path = format!("{}/issues/{}", issue.repo, issue.number);
+        let found: ApiIssue = self.get_json(&issue.owner, &path).await?;
+        Ok(found.into_issue(&issue.owner, &issue.repo))
+    }
+
+    /// The most recently updated open issues of a repository, up to `limit`,
+    /// leaving out pull requests.
+    pub async fn open_issues(&self, owner: &str, repo: &str, limit: usize) -> Result<Vec<Issue>> {
+        let path = format!(
+            "{}/issues?state=open&sort=updated&per_page={}",
+            repo,
+            limit.clamp(1, 100)
+        );
+        let found: Vec<ApiIssue> = self.get_json(owner, &path).await?;
+        Ok(found
+            .into_iter()
+            .filter(|i| i.pull_request.is_none())
+            .take(limit)
+            .map(|i| i.into_issue(owner, repo))
+            .collect())
+    }
+
+    /// Names of the labels a repository defines.
+    pub async fn labels(&self, owner: &str, repo: &str) -> Result<Vec<String>> {
+        let path = format!("{}/labels?per_page=100", repo);
+        let found: Vec<ApiLabel> = self.get_json(owner, &path).await?;
+        Ok(found.into_iter().map(|l| l.name).collect())
+    }
+
+    /// The selected issues and their repositories' labels.
+    pub async fn issue_batch(&self, selection: &IssueSelection) -> Result<IssueBatch> {
+        let mut batch = IssueBatch::default();
+        let mut repos: Vec<(String, String)> = Vec::new();
+        match selection {
+            IssueSelection::Open { owner, repo } => {
+                batch.issues = self.open_issues(owner, repo, MAX_ISSUES).await?;
+                repos.push((owner.clone(), repo.clone()));
+            }
+            IssueSelection::Listed(issues) => {
+                for issue in issues {
+                    batch.issues.push(self.issue(issue).await?);
+                    let repo = (issue.owner.clone(), issue.repo.clone());
+                    if !repos.contains(&repo) {
+                        repos.push(repo);
+                    }
+                }
+            }
+        }
+        for (owner, repo) in &repos {
+            for label in self.labels(owner, repo).await? {
+                if !batch.labels.contains(&label) {
+                    batch.labels.push(label);
+                }
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Post a comment on a pull request or issue. Returns the comment's URL.
+    pub async fn comment(&self, issue: &IssueRef, body: &str) -> Result<String> {
+        if self.token.is_none() {
+            return Err(Error::Config(format!(
+                "set {} to comment on {}",
+                TOKEN_ENV_VAR, issue
+            )));
+        }
+        let url = format!(
+            "{}/repos/{}/{}/issues/{}/comments",
+            API_BASE, issue.owner, issue.repo, issue.number
+        );
+        let what = issue.to_string();
+        let response = self
+            .request(self.client.post(url))
+            .json(&serde_json::json!({ "body": body }))
+            .send()
+            .await
+            .map_err(|e| request_error(&what, e))?;
+        let comment: serde_json::Value = check(&what, response)
+            .await?
+            .json()
+            .await
+            .map_err(|e| request_error(&what, e))?;
+        Ok(comment["html_url"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// GET `repos/<owner>/<path>` and decode the JSON answer.
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, owner: &str, path: &str) -> Result<T> {
+        let what = format!("{}/{}", owner, path);
+        let url = format!("{}/repos/{}", API_BASE, what);
+        let response = self
+            .request(self.client.get(url))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await
+            .map_err(|e| request_error(&what, e))?;
+        check(&what, response)
+            .await?
+            .json()
+            .await
+            .map_err(|e| request_error(&what, e))
+    }
+
+    fn request(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request = request
+            .timeout(REQUEST_TIMEOUT)
+            .header(reqwest::header::USER_AGENT, "agent-mcp")
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+/// Turn an unsuccessful response into an error naming what was requested.
+async fn check(what: &str, response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let message = format!(
+        "GitHub request for {} failed: HTTP {} {}",
+        what, status, body
+    );
+    Err(match status.as_u16() {
+        401 | 403 => Error::PermissionDenied(message),
+        404 => Error::InvalidParams(message),
+        _ => Error::Internal(message),
+    })
+}
+
+fn request_error(what: &str, e: reqwest::Error) -> Error {
+    Error::Internal(format!("GitHub request for {} failed: {}", what, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_refs_and_diff_split() {
+        let pr: IssueRef = "octo/hello-world#42".parse().unwrap();
+        assert_eq!(
+            (pr.owner.as_str(), pr.repo.as_str(), pr.number),
+            ("octo", "hello-world", 42)
+        );
+        assert_eq!(pr.to_string(), "octo/hello-world#42");
+        let url: IssueRef = "https://github.com/octo/hello-world/pull/42/files"
+            .parse()
+            .unwrap();
+        assert_eq!(url, pr);
+        assert!("octo/hello-world".parse::<IssueRef>().is_err());
+        assert!("../x#1".parse::<IssueRef>().is_err());
+        assert_eq!(
+            "octo/hello-world".parse::<IssueSelection>().unwrap(),
+            IssueSelection::Open {
+                owner: "octo".into(),
+                repo: "hello-world".into()
+            }
+        );
+        let listed: IssueSelection = "octo/a#1, octo/b#2".parse().unwrap();
+        assert!(matches!(listed, IssueSelection::Listed(ref l) if l[1].number == 2));
+        assert!("octo".parse::<IssueSelection>().is_err());
+
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-a\n+b\ndiff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-x\n+y\n";
+        let files = split_diff(diff);
+        let paths: Vec<_> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["src/a.rs", "README.md"]);
+        assert!(files[0].diff.ends_with("+b\n"));
+        assert!(files[1].diff.starts_with("diff --git a/README.md"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Judge-graded response quality.
+//!
+//! A designated judge provider scores responses against a rubric or a golden
+//! answer. Grades are recorded as per-provider quality metrics in the router,
+//! where they weight future provider selection.
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Metadata key holding a response's grade (0.0-1.0).
+pub const QUALITY_SCORE_KEY: &str = "quality_score";
+
+/// Configuration for judge grading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradingPolicy {
+    /// Provider that grades responses.
+    pub judge_provider: String,
+    /// Grading criteria given to the judge.
+    #[serde(default)]
+    pub rubric: Option<String>,
+}
+
+impl GradingPolicy {
+    /// Create a policy with the given judge.
+    pub fn new(judge_provider: impl Into<String>) -> Self {
+        Self {
+            judge_provider: judge_provider.into(),
+            rubric: None,
+        }
+    }
+
+    /// Set the rubric.
+    pub fn with_rubric(mut self, rubric: impl Into<String>) -> Self {
+        self.rubric = Some(rubric.into());
+        self
+    }
+
+    /// Resolve the judge provider.
+    pub fn judge(&self) -> Result<Provider> {
+        Provider::from_string(&self.judge_provider).ok_or_else(|| {
+            Error::Config(format!("unknown judge provider: {}", self.judge_provider))
+        })
+    }
+}
+
+/// A judge's grade for one response.
+#[derive(Debug, Clone)]
+pub struct Grade {
+    /// Provider whose response was graded.
+    pub provider: Provider,
+    /// Score from 0.0 to 1.0.
+    pub score: f64,
+    /// The judge's raw reply.
+    pub verdict: String,
+}
+
+/// Build the prompt asking a judge to grade a response.
+pub fn grading_prompt(
+    question: &str,
+    response: &str,
+    reference: Option<&str>,
+    rubric: Option<&str>,
+) -> String {
+    let rubric = rubric
+        .map(|r| format!("Grading rubric:\n{}\n\n", r))
+        .unwrap_or_default();
+    let reference = reference
+        .map(|r| format!("Reference answer:\n{}\n\n", r))
+        .unwrap_or_default();
+    format!(
+        "Rate the following answer to the question on a scale from 0 to 10, \
+         where 10 is fully correct and complete. Reply with the number first, \
+         then a one-sentence justification.\n\n\
+         {}Question:\n{}\n\n{}Answer:\n{}",
+        rubric, question, reference, response
+    )
+}
+
+/// Parse the first number in a judge's reply as a 0-10 rating, scaled to 0.0-1.0.
+pub fn parse_score(text: &str) -> Option<f64> {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find_map(|token| token.parse::<f64>().ok())
+        .map(|score| (score / 10.0).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_score() {
+        assert_eq!(parse_score("Score: 8/10"), Some(0.8));
+        assert_eq!(parse_score("15 - generous"), Some(1.0));
+        assert_eq!(parse_score("no idea"), None);
+    }
+
+    #[test]
+    fn test_grading_prompt_includes_rubric_and_reference() {
+        let prompt = grading_prompt("2+2?", "4", Some("4"), Some("Exact arithmetic"));
+        assert!(prompt.contains("Grading rubric:\nExact arithmetic"));
+        assert!(prompt.contains("Reference answer:\n4"));
+        assert!(prompt.ends_with("Answer:\n4"));
+    }
+}
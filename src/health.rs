@@ -0,0 +1,179 @@
+//! Scheduled provider health sweeps.
+//!
+//! Provider health is otherwise only learned from real requests, so after a
+//! quiet night the router still trusts providers that went down and avoids
+//! ones that recovered. A sweep probes every usable provider, whether or not
+//! anything is being asked of it, and records the outcome in the router: an
+//! auth check (cheap, and no prompt is spent) or a short ping prompt (which
+//! also confirms the provider answers). Probes count toward a provider's
+//! health but not its request statistics.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// How a sweep probes a provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeKind {
+    /// Check the provider's sign-in (or API credentials) only.
+    #[default]
+    Auth,
+    /// Sign in and send a short ping prompt.
+    Ping,
+}
+
+impl FromStr for ProbeKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auth" => Ok(Self::Auth),
+            "ping" => Ok(Self::Ping),
+            _ => Err(Error::InvalidParams(format!(
+                "unknown health probe: {} (expected auth or ping)",
+                s
+            ))),
+        }
+    }
+}
+
+/// When and how providers are swept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthSweepPolicy {
+    /// Time between sweeps.
+    pub interval: Duration,
+    /// How each provider is probed.
+    pub probe: ProbeKind,
+    /// Prompt sent by ping probes.
+    pub ping_prompt: String,
+}
+
+impl Default for HealthSweepPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15 * 60),
+            probe: ProbeKind::Auth,
+            ping_prompt: "Reply with the single word OK.".into(),
+        }
+    }
+}
+
+/// Outcome of probing one provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeResult {
+    /// Provider probed.
+    pub provider: String,
+    /// Whether the probe succeeded.
+    pub ok: bool,
+    /// Time the probe took.
+    pub duration_ms: u64,
+    /// Why the probe failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of one sweep.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepReport {
+    /// How providers were probed.
+    pub probe: ProbeKind,
+    /// When the sweep started.
+    pub started_at: DateTime<Utc>,
+    /// One result per provider probed, in probe order.
+    pub results: Vec<ProbeResult>,
+}
+
+impl SweepReport {
+    /// Start an empty report.
+    pub fn new(probe: ProbeKind) -> Self {
+        Self {
+            probe,
+            started_at: Utc::now(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Results of the probes that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &ProbeResult> {
+        self.results.iter().filter(|r| !r.ok)
+    }
+
+    /// One-line summary, such as "3/4 providers up (gemini: timeout)".
+    pub fn summary(&self) -> String {
+        let up = self.results.iter().filter(|r| r.ok).count();
+        let mut summary = format!("{}/{} providers up", up, self.results.len());
+        let failed: Vec<String> = self
+            .failures()
+            .map(|r| match &r.error {
+                Some(error) => format!("{}: {}", r.provider, error),
+                None => r.provider.clone(),
+            })
+            .collect();
+        if !failed.is_empty() {
+            summary.push_str(&format!(" ({})", failed.join("; ")));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+    use crate::testkit::{MockProviders, MockReply};
+    use embeddenator_webpuppet::Provider;
+
+    #[tokio::test]
+    async fn test_sweeps_track_provider_health() {
+        let mocks = MockProviders::new();
+        for _ in 0..3 {
+            mocks.enqueue(Provider::Claude, MockReply::Fail("down".into()));
+        }
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(mocks.clone()),
+            ..Default::default()
+        });
+        let policy = HealthSweepPolicy {
+            probe: "ping".parse().unwrap(),
+            ..Default::default()
+        };
+
+        for _ in 0..3 {
+            let report = orchestrator.sweep_provider_health(&policy).await.unwrap();
+            assert_eq!(report.failures().count(), 1);
+            assert!(report.summary().contains(&Provider::Claude.to_string()));
+        }
+        let status = orchestrator.status().await;
+        assert!(!status.available_providers.contains(&Provider::Claude));
+        // Probes are not requests
+        assert!(status.provider_stats.is_empty());
+
+        // A successful probe brings the provider back
+        let report = orchestrator.sweep_provider_health(&policy).await.unwrap();
+        assert_eq!(report.failures().count(), 0);
+        assert!(orchestrator
+            .status()
+            .await
+            .available_providers
+            .contains(&Provider::Claude));
+        assert!(mocks
+            .calls()
+            .iter()
+            .all(|c| c.message == policy.ping_prompt));
+
+        // Auth checks send no prompt
+        let calls = mocks.calls().len();
+        orchestrator
+            .sweep_provider_health(&HealthSweepPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(mocks.calls().len(), calls);
+
+        assert!("nap".parse::<ProbeKind>().is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! HTTP transport for the MCP server.
+//!
+//! Accepts JSON-RPC messages via `POST /mcp`. Each client (its authenticated
+//! tenant, or else its IP address) is rate limited independently; rejected
+//! calls get `429 Too Many Requests` with a `Retry-After` header and a
+//! JSON-RPC error carrying `retry_after_secs`.
+//!
+//! When tenants are configured, clients authenticate with
+//! `Authorization: Bearer <api key>` and their requests are attributed to the
+//! key's tenant; calls without a valid key get `401 Unauthorized`. Otherwise
+//! requests are attributed to `http:<peer IP>`, never to the privileged
+//! default tenant of local callers.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::error::Result;
+use crate::protocol::{error_codes, McpRequest, McpResponse};
+use crate::ratelimit::{ClientLimits, ClientRateLimiter};
+use crate::server::AgentMcpServer;
+
+/// Prefix of the tenants unauthenticated HTTP callers are attributed to.
+pub const HTTP_TENANT_PREFIX: &str = "http:";
+
+/// Shared state for HTTP handlers.
+struct HttpState {
+    server: AgentMcpServer,
+    limiter: ClientRateLimiter,
+}
+
+/// Serve the MCP server over HTTP until the listener fails.
+pub async fn serve(server: AgentMcpServer, addr: SocketAddr, limits: ClientLimits) -> Result<()> {
+    let state = Arc::new(HttpState {
+        server,
+        limiter: ClientRateLimiter::new(limits),
+    });
+
+    let app = Router::new()
+        .route("/mcp", post(handle_rpc))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Starting Agent MCP Server on http://{}/mcp", addr);
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Handle a JSON-RPC message posted by a client.
+async fn handle_rpc(
+    State(state): State<Arc<HttpState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let tenants = state.server.tenants();
+    let authenticated = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|key| tenants.authenticate(key))
+        .map(str::to_string);
+    // Rate limits follow who the caller proved to be, not what it claims
+    let client = authenticated
+        .clone()
+        .unwrap_or_else(|| addr.ip().to_string());
+
+    let _permit = match state.limiter.acquire(&client) {
+        Ok(permit) => permit,
+        Err(rejection) => {
+            warn!("Rate limited client {}: {}", client, rejection);
+            let id = serde_json::from_str::<McpRequest>(&body)
+                .ok()
+                .and_then(|r| r.id);
+            let retry_after = rejection.retry_after_secs();
+            let response = McpResponse::error_with_data(
+                id,
+                error_codes::RATE_LIMITED,
+                rejection.to_string(),
+                json!({ "retry_after_secs": retry_after }),
+            );
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(response),
+            )
+                .into_response();
+        }
+    };
+
+    let tenant = if tenants.is_empty() {
+        format!("{}{}", HTTP_TENANT_PREFIX, addr.ip())
+    } else {
+        match authenticated {
+            Some(tenant) => tenant,
+            None => {
+                warn!("Rejected client {}: missing or unknown API key", client);
+                let id = serde_json::from_str::<McpRequest>(&body)
+                    .ok()
+                    .and_then(|r| r.id);
+                let response =
+                    McpResponse::error(id, error_codes::UNAUTHORIZED, "missing or unknown API key");
+                return (StatusCode::UNAUTHORIZED, Json(response)).into_response();
+            }
+        }
+    };
+
+    Json(state.server.handle_message_for(&body, Some(&tenant)).await).into_response()
+}
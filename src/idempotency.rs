@@ -0,0 +1,187 @@
+//! Idempotency keys for mutating tool calls.
+//!
+//! Clients on flaky transports may deliver a tool call twice. A call to a
+//! mutating tool, such as `agent_workflow_start`, may carry an
+//! `idempotency_key`; its result is kept under the key, and a later call
+//! with the same key gets that result back instead of running again. Keys
+//! are scoped to the tenant and tool, and reusing one with different
+//! arguments is an error. Failed calls are not kept, so they can be retried.
+//! Results are kept in storage for a day, so a duplicate delivered to
+//! another instance sharing the backend is caught too.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::audit::sha256_hex;
+use crate::error::{Error, Result};
+use crate::protocol::ToolCallResult;
+use crate::storage::Records;
+
+/// Name of the argument carrying the key.
+pub const IDEMPOTENCY_KEY: &str = "idempotency_key";
+
+/// Hours a result is kept for duplicates of its call.
+const RETENTION_HOURS: i64 = 24;
+
+/// The result of a call, kept under its key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeptResult {
+    /// SHA-256 of the call's arguments, without the key.
+    arguments_sha256: String,
+    /// What the call returned.
+    result: ToolCallResult,
+    /// When the call finished.
+    at: DateTime<Utc>,
+}
+
+/// Results of keyed calls, shared through storage.
+#[derive(Clone)]
+pub struct IdempotencyStore {
+    records: Records,
+    /// Keys with a call running on this instance, so duplicates wait for it.
+    running: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+}
+
+impl IdempotencyStore {
+    /// Keep results in `records`.
+    pub fn open(records: Records) -> Self {
+        Self {
+            records,
+            running: Arc::default(),
+        }
+    }
+
+    /// The key in `arguments`, if any.
+    pub fn key(arguments: &Value) -> Result<Option<&str>> {
+        match arguments.get(IDEMPOTENCY_KEY) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(key)) if !key.is_empty() => Ok(Some(key)),
+            Some(_) => Err(Error::InvalidParams(format!(
+                "{} must be a non-empty string",
+                IDEMPOTENCY_KEY
+            ))),
+        }
+    }
+
+    /// Run `call` for `tenant`'s call of `tool` with `arguments` keyed by
+    /// `key`, unless a call with that key already succeeded, in which case
+    /// its result is returned. A duplicate arriving while the call runs
+    /// waits for it.
+    pub async fn run<F>(
+        &self,
+        tenant: &str,
+        tool: &str,
+        key: &str,
+        arguments: &Value,
+        call: F,
+    ) -> Result<ToolCallResult>
+    where
+        F: Future<Output = Result<ToolCallResult>>,
+    {
+        let id = sha256_hex(format!("{}\n{}\n{}", tenant, tool, key).as_bytes());
+        let mut arguments = arguments.clone();
+        if let Some(arguments) = arguments.as_object_mut() {
+            arguments.remove(IDEMPOTENCY_KEY);
+        }
+        let arguments_sha256 = sha256_hex(serde_json::to_string(&arguments)?.as_bytes());
+
+        let lock = self
+            .running
+            .lock()
+            .await
+            .entry(id.clone())
+            .or_default()
+            .clone();
+        let _running = lock.lock().await;
+        let kept = self
+            .records
+            .load::<KeptResult>(&id)?
+            .filter(|kept| Utc::now() - kept.at < Duration::hours(RETENTION_HOURS));
+        let result = match kept {
+            Some(kept) if kept.arguments_sha256 != arguments_sha256 => {
+                Err(Error::InvalidParams(format!(
+                    "{} {} was already used with different arguments",
+                    IDEMPOTENCY_KEY, key
+                )))
+            }
+            Some(kept) => Ok(kept.result),
+            None => call.await.and_then(|result| {
+                if !result.is_error {
+                    self.records.save(
+                        &id,
+                        &KeptResult {
+                            arguments_sha256,
+                            result: result.clone(),
+                            at: Utc::now(),
+                        },
+                    )?;
+                }
+                Ok(result)
+            }),
+        };
+        self.running.lock().await.remove(&id);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ContentItem;
+    use crate::storage::{MemoryStorage, IDEMPOTENCY};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_duplicate_calls_return_first_result() {
+        let store = IdempotencyStore::open(Records::new(
+            Arc::new(MemoryStorage::new()),
+            IDEMPOTENCY,
+            None,
+        ));
+        let calls = AtomicUsize::new(0);
+        let start = || async {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            Ok(ToolCallResult {
+                content: vec![ContentItem::text(format!("workflow {}", n))],
+                is_error: false,
+            })
+        };
+        let arguments = json!({ "name": "fix", "idempotency_key": "k1" });
+        assert_eq!(IdempotencyStore::key(&arguments).unwrap(), Some("k1"));
+
+        let first = store
+            .run("default", "agent_workflow_start", "k1", &arguments, start())
+            .await
+            .unwrap();
+        let again = store
+            .run("default", "agent_workflow_start", "k1", &arguments, start())
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            serde_json::to_value(&again).unwrap(),
+            serde_json::to_value(&first).unwrap()
+        );
+
+        // Other tenants have their own keys
+        store
+            .run("ci", "agent_workflow_start", "k1", &arguments, start())
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let changed = json!({ "name": "other", "idempotency_key": "k1" });
+        let reused = store
+            .run("default", "agent_workflow_start", "k1", &changed, start())
+            .await;
+        assert!(matches!(reused, Err(Error::InvalidParams(_))));
+        assert!(IdempotencyStore::key(&json!({ "idempotency_key": 7 })).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Earlier step outputs and workflow context in step texts.
+//!
+//! `{{steps.<step>.output}}` and `{{context.<key>}}` placeholders in a
+//! step's message, and in the other texts it sends, are filled when the
+//! step runs, so a step can work on what an earlier one produced. A
+//! placeholder is any path of [`crate::condition`], so
+//! `{{steps.draft.metadata.confidence}}` works as well. Other `{{name}}`
+//! placeholders, such as those of map items and saved prompts, are left
+//! alone.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::condition::Condition;
+use crate::error::{Error, Result};
+use crate::workflow::{StepConfig, Workflow};
+
+/// Matches a `{{steps...}}` or `{{context...}}` placeholder.
+fn placeholder_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{\{\s*((?:steps|context)\s*[.\[][^}]*?)\s*\}\}").unwrap())
+}
+
+/// Fill the placeholders of `text` from `workflow`. Fails if one reads a
+/// step that is not an earlier step or has no value.
+pub fn interpolate(text: &str, workflow: &Workflow) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in placeholder_re().captures_iter(text) {
+        let (whole, path) = (caps.get(0).unwrap(), &caps[1]);
+        let value = path.parse::<Condition>()?.value(workflow)?;
+        out.push_str(&text[last..whole.start()]);
+        match value {
+            Value::Null => return Err(Error::Workflow(format!("{} has no value", whole.as_str()))),
+            Value::String(s) => out.push_str(&s),
+            other => out.push_str(&other.to_string()),
+        }
+        last = whole.end();
+    }
+    out.push_str(&text[last..]);
+    Ok(out)
+}
+
+/// Fill the placeholders of every text a step sends.
+pub fn interpolate_step(config: &mut StepConfig, workflow: &Workflow) -> Result<()> {
+    for text in config.texts_mut() {
+        *text = interpolate(text, workflow)?;
+    }
+    if let StepConfig::Tool { arguments, .. } = config {
+        interpolate_json(arguments, workflow)?;
+    }
+    Ok(())
+}
+
+fn interpolate_json(value: &mut Value, workflow: &Workflow) -> Result<()> {
+    match value {
+        Value::String(text) => *text = interpolate(text, workflow)?,
+        Value::Array(items) => {
+            for item in items {
+                interpolate_json(item, workflow)?;
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                interpolate_json(field, workflow)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::AgentOrchestrator;
+    use crate::workflow::{StepResult, WorkflowStep};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_steps_read_earlier_outputs() {
+        let mut workflow = Workflow::new("summary");
+        let mut draft = WorkflowStep::prompt("draft", "Write it");
+        draft.complete(StepResult {
+            output: "The draft".into(),
+            provider: Some("claude".into()),
+            responses: None,
+            duration_ms: 10,
+            metadata: HashMap::from([("confidence".to_string(), json!(0.75))]),
+        });
+        workflow.add_step(draft);
+        workflow.current_step = 1;
+        workflow.set_context("audience", json!("engineers"));
+
+        let text = interpolate(
+            "Summarize {{ steps.draft.output }} for {{context.audience}} \
+             ({{steps.draft.metadata.confidence}}), per {{item}}",
+            &workflow,
+        )
+        .unwrap();
+        assert_eq!(
+            text,
+            "Summarize The draft for engineers (0.75), per {{item}}"
+        );
+        assert!(interpolate("{{context.missing}}", &workflow).is_err());
+        assert!(interpolate("{{steps.later.output}}", &workflow).is_err());
+
+        // Placeholders are filled when the step runs, not when it is added
+        let orchestrator = AgentOrchestrator::new();
+        workflow.add_step(WorkflowStep::review(
+            "check",
+            "Approve {{steps.draft.output}}?",
+        ));
+        let id = orchestrator.start_workflow(workflow).await.unwrap();
+        orchestrator.execute_workflow_step(&id).await.unwrap_err();
+        orchestrator.approve_step(&id).await.unwrap();
+        let result = orchestrator.execute_workflow_step(&id).await.unwrap();
+        assert_eq!(result.output, "Approved: Approve The draft?");
+    }
+}
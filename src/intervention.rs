@@ -0,0 +1,127 @@
+//! Human intervention for captchas and verification walls.
+//!
+//! When a provider blocks a prompt behind a challenge, the pending prompt is
+//! parked here until the user reports the challenge solved (or it times out),
+//! and is then retried instead of failing.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+/// How captcha and verification walls are handled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterventionPolicy {
+    /// Pause and wait for the user instead of failing.
+    pub enabled: bool,
+    /// Open a visible browser on the provider for the user to solve the challenge.
+    pub show_browser: bool,
+    /// How long to wait for the user, in seconds.
+    pub timeout_secs: u64,
+}
+
+impl Default for InterventionPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            show_browser: true,
+            timeout_secs: 300,
+        }
+    }
+}
+
+/// A prompt waiting on a human.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingIntervention {
+    /// Intervention ID.
+    pub id: String,
+    /// Provider name.
+    pub provider: String,
+    /// What the user needs to do.
+    pub reason: String,
+    /// When the intervention was requested.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Pending interventions with the channel that resumes each waiting prompt.
+type PendingMap = HashMap<String, (PendingIntervention, oneshot::Sender<bool>)>;
+
+/// Pending interventions awaiting resolution.
+#[derive(Debug, Clone, Default)]
+pub struct InterventionRegistry {
+    pending: Arc<Mutex<PendingMap>>,
+}
+
+impl InterventionRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an intervention, returning it and a receiver for the outcome.
+    pub fn register(
+        &self,
+        provider: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> (PendingIntervention, oneshot::Receiver<bool>) {
+        let intervention = PendingIntervention {
+            id: Uuid::new_v4().to_string(),
+            provider: provider.into(),
+            reason: reason.into(),
+            created_at: Utc::now(),
+        };
+        let (tx, rx) = oneshot::channel();
+        self.lock()
+            .insert(intervention.id.clone(), (intervention.clone(), tx));
+        (intervention, rx)
+    }
+
+    /// Resolve an intervention, resuming (or abandoning) the waiting prompt.
+    pub fn resolve(&self, id: &str, solved: bool) -> Result<()> {
+        let (_, tx) = self
+            .lock()
+            .remove(id)
+            .ok_or_else(|| Error::InvalidParams(format!("no pending intervention: {}", id)))?;
+        tx.send(solved)
+            .map_err(|_| Error::InvalidState(format!("intervention {} is no longer waiting", id)))
+    }
+
+    /// Remove an intervention without resolving it.
+    pub fn remove(&self, id: &str) {
+        self.lock().remove(id);
+    }
+
+    /// List pending interventions, oldest first.
+    pub fn pending(&self) -> Vec<PendingIntervention> {
+        let mut pending: Vec<_> = self.lock().values().map(|(i, _)| i.clone()).collect();
+        pending.sort_by_key(|i| i.created_at);
+        pending
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PendingMap> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_resumes_waiter() {
+        let registry = InterventionRegistry::new();
+        let (intervention, rx) = registry.register("claude", "solve the captcha");
+
+        assert_eq!(registry.pending().len(), 1);
+        registry.resolve(&intervention.id, true).unwrap();
+        assert!(rx.await.unwrap());
+        assert!(registry.pending().is_empty());
+        assert!(registry.resolve(&intervention.id, true).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Persistent queue for background work.
+//!
+//! Background runs are queued as jobs in storage rather than spawned as
+//! detached tasks, so queued work survives a restart and can be inspected,
+//! cancelled, or re-prioritized. A worker claims a job by taking a lease on
+//! it and renews the lease while it runs; a job whose lease expires (because
+//! its worker's instance died) is claimed again by the next worker. Failed
+//! jobs are retried with backoff up to their attempt limit. A job may carry a
+//! callback URL that receives its final result (see [`crate::webhook`]).
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::security::DataClassification;
+use crate::storage::Records;
+
+/// How long a claimed job stays leased without a renewal.
+pub const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+/// Attempts before a failing job is given up on.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry; doubled for each further attempt.
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+/// Longest delay between retries.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Work a job performs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Run (or resume) an auto workflow to completion.
+    AutoRun {
+        /// Workflow to run.
+        workflow_id: String,
+        /// Stop before exceeding this estimated cost.
+        #[serde(default)]
+        max_cost_usd: Option<f64>,
+        /// Provider that writes the synthesis.
+        #[serde(default)]
+        synthesizer: Option<String>,
+        /// Data classification of the run.
+        #[serde(default)]
+        classification: Option<DataClassification>,
+        /// Run in a visible browser.
+        #[serde(default)]
+        visible: bool,
+        /// Tenant the run's requests are attributed to.
+        #[serde(default)]
+        tenant: Option<String>,
+    },
+}
+
+impl JobKind {
+    /// Short human-readable description.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::AutoRun { workflow_id, .. } => format!("auto run of workflow {}", workflow_id),
+        }
+    }
+}
+
+/// Lifecycle of a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for a worker.
+    Queued,
+    /// Leased by a worker.
+    Running,
+    /// Finished.
+    Completed,
+    /// Failed on every attempt.
+    Failed,
+    /// Cancelled before finishing.
+    Cancelled,
+}
+
+impl JobState {
+    /// Check if the job will not run again.
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// A queued unit of background work.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    /// Job ID.
+    pub id: String,
+    /// Work to perform.
+    pub kind: JobKind,
+    /// Higher priorities are claimed first.
+    pub priority: i32,
+    /// Current state.
+    pub state: JobState,
+    /// Attempts started so far.
+    pub attempts: u32,
+    /// Attempts allowed before the job fails.
+    pub max_attempts: u32,
+    /// Worker holding the lease while running.
+    #[serde(default)]
+    pub lease_owner: Option<String>,
+    /// When the lease lapses unless renewed.
+    #[serde(default)]
+    pub lease_expires: Option<DateTime<Utc>>,
+    /// Not claimed before this time (retry backoff).
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
+    /// Summary of the result once completed.
+    #[serde(default)]
+    pub result: Option<String>,
+    /// Error from the most recent failed attempt.
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// URL that receives the final result once the job finishes.
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// When the job was queued.
+    pub created_at: DateTime<Utc>,
+    /// When the job last changed.
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Job {
+    /// Check if a worker may claim the job at `now`: it is queued and past
+    /// its backoff, or its worker's lease has lapsed.
+    pub fn is_claimable(&self, now: DateTime<Utc>) -> bool {
+        match self.state {
+            JobState::Queued => self.not_before.is_none_or(|t| t <= now),
+            JobState::Running => self.lease_expires.is_none_or(|t| t <= now),
+            _ => false,
+        }
+    }
+}
+
+/// Jobs kept in storage, shared by every instance using the backend.
+#[derive(Clone)]
+pub struct JobQueue {
+    records: Records,
+    /// Identifies this instance's worker in leases.
+    owner: String,
+    lease: Duration,
+}
+
+impl JobQueue {
+    /// Open the queue kept in `records`.
+    pub fn open(records: Records) -> Self {
+        Self {
+            records,
+            owner: Uuid::new_v4().to_string(),
+            lease: DEFAULT_LEASE,
+        }
+    }
+
+    /// Set how long a claimed job stays leased without a renewal.
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// How long a claimed job stays leased without a renewal.
+    pub fn lease(&self) -> Duration {
+        self.lease
+    }
+
+    /// Queue a job.
+    pub fn enqueue(&self, kind: JobKind, priority: i32) -> Result<Job> {
+        self.enqueue_with_callback(kind, priority, None)
+    }
+
+    /// Queue a job whose final result is POSTed to `callback_url`.
+    pub fn enqueue_with_callback(
+        &self,
+        kind: JobKind,
+        priority: i32,
+        callback_url: Option<String>,
+    ) -> Result<Job> {
+        let now = Utc::now();
+        let job = Job {
+            id: Uuid::new_v4().to_string(),
+            kind,
+            priority,
+            state: JobState::Queued,
+            attempts: 0,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            lease_owner: None,
+            lease_expires: None,
+            not_before: None,
+            result: None,
+            last_error: None,
+            callback_url,
+            created_at: now,
+            updated_at: now,
+        };
+        self.records.save(&job.id, &job)?;
+        Ok(job)
+    }
+
+    /// Get a job by ID.
+    pub fn get(&self, id: &str) -> Result<Option<Job>> {
+        self.records.load(id)
+    }
+
+    /// All jobs: unfinished first, then by priority, oldest first.
+    pub fn list(&self) -> Result<Vec<Job>> {
+        let mut jobs: Vec<Job> = self.records.load_all()?;
+        jobs.sort_by_key(|j| {
+            (
+                j.state.is_finished(),
+                std::cmp::Reverse(j.priority),
+                j.created_at,
+            )
+        });
+        Ok(jobs)
+    }
+
+    /// Claim the highest-priority claimable job, leasing it to this worker.
+    pub fn claim(&self) -> Result<Option<Job>> {
+        let now = Utc::now();
+        let lease_expires = now + self.lease;
+        for candidate in self.list()?.into_iter().filter(|j| j.is_claimable(now)) {
+            // Another worker may have claimed the job since it was listed.
+            let claimed = self.modify(&candidate.id, |job| {
+                if !job.is_claimable(now) {
+                    return false;
+                }
+                job.state = JobState::Running;
+                job.attempts += 1;
+                job.lease_owner = Some(self.owner.clone());
+                job.lease_expires = Some(lease_expires);
+                true
+            })?;
+            if claimed.is_some() {
+                return Ok(claimed);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Extend this worker's lease on a running job. Returns false if the job
+    /// was cancelled or claimed by another worker, in which case this worker
+    /// should stop running it.
+    pub fn renew(&self, id: &str) -> Result<bool> {
+        let lease_expires = Utc::now() + self.lease;
+        let renewed = self.modify(id, |job| {
+            if !self.holds(job) {
+                return false;
+            }
+            job.lease_expires = Some(lease_expires);
+            true
+        })?;
+        Ok(renewed.is_some())
+    }
+
+    /// Mark a job this worker is running as completed. Returns the updated
+    /// job, or `None` if this worker no longer holds it.
+    pub fn complete(&self, id: &str, result: impl Into<String>) -> Result<Option<Job>> {
+        let result = result.into();
+        self.modify(id, |job| {
+            if !self.holds(job) {
+                return false;
+            }
+            job.state = JobState::Completed;
+            job.result = Some(result.clone());
+            release(job);
+            true
+        })
+    }
+
+    /// Record a failed attempt of a job this worker is running, queueing it
+    /// for a retry with backoff unless it has used all its attempts. Returns
+    /// the updated job, or `None` if this worker no longer holds it.
+    pub fn fail(&self, id: &str, error: impl Into<String>) -> Result<Option<Job>> {
+        let error = error.into();
+        let now = Utc::now();
+        self.modify(id, |job| {
+            if !self.holds(job) {
+                return false;
+            }
+            job.last_error = Some(error.clone());
+            if job.attempts < job.max_attempts {
+                job.state = JobState::Queued;
+                job.not_before = Some(now + retry_backoff(job.attempts));
+            } else {
+                job.state = JobState::Failed;
+            }
+            release(job);
+            true
+        })
+    }
+
+    /// Put a job this worker is running back in the queue without counting
+    /// the attempt, such as when its run was paused. Returns the updated
+    /// job, or `None` if this worker no longer holds it.
+    pub fn requeue(&self, id: &str) -> Result<Option<Job>> {
+        self.modify(id, |job| {
+            if !self.holds(job) {
+                return false;
+            }
+            job.state = JobState::Queued;
+            job.attempts = job.attempts.saturating_sub(1);
+            release(job);
+            true
+        })
+    }
+
+    /// Cancel a job that has not finished. A running job stops at its
+    /// worker's next lease renewal.
+    pub fn cancel(&self, id: &str) -> Result<Job> {
+        self.change(id, "cancel", |job| {
+            job.state = JobState::Cancelled;
+            release(job);
+        })
+    }
+
+    /// Change the priority of a job that has not finished.
+    pub fn set_priority(&self, id: &str, priority: i32) -> Result<Job> {
+        self.change(id, "re-prioritize", |job| job.priority = priority)
+    }
+
+    /// Apply a user-requested change to an unfinished job.
+    fn change(&self, id: &str, action: &str, mut f: impl FnMut(&mut Job)) -> Result<Job> {
+        let mut finished = None;
+        let changed = self.modify(id, |job| {
+            if job.state.is_finished() {
+                finished = Some(job.state);
+                return false;
+            }
+            f(job);
+            true
+        })?;
+        match (changed, finished) {
+            (Some(job), _) => Ok(job),
+            (None, Some(state)) => Err(Error::InvalidParams(format!(
+                "cannot {} job {}: it is already {:?}",
+                action, id, state
+            ))),
+            (None, None) => Err(Error::InvalidParams(format!("unknown job: {}", id))),
+        }
+    }
+
+    /// Atomically apply `f` to a stored job. Returns the updated job if `f`
+    /// changed it, or `None` if it did not or the job does not exist.
+    fn modify(&self, id: &str, mut f: impl FnMut(&mut Job) -> bool) -> Result<Option<Job>> {
+        let Some(current) = self.get(id)? else {
+            return Ok(None);
+        };
+        let mut changed = false;
+        let job = self.records.update(id, |stored: Option<Job>| {
+            let mut job = stored.unwrap_or_else(|| current.clone());
+            changed = f(&mut job);
+            if changed {
+                job.updated_at = Utc::now();
+            }
+            job
+        })?;
+        Ok(changed.then_some(job))
+    }
+
+    /// Check if this worker holds the lease on a running job.
+    fn holds(&self, job: &Job) -> bool {
+        job.state == JobState::Running && job.lease_owner.as_deref() == Some(self.owner.as_str())
+    }
+}
+
+fn release(job: &mut Job) {
+    job.lease_owner = None;
+    job.lease_expires = None;
+}
+
+/// Delay before retrying a job that has failed `attempts` times.
+fn retry_backoff(attempts: u32) -> Duration {
+    RETRY_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RETRY_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::storage::{MemoryStorage, JOBS};
+
+    fn auto_run(workflow_id: &str) -> JobKind {
+        JobKind::AutoRun {
+            workflow_id: workflow_id.into(),
+            max_cost_usd: None,
+            synthesizer: None,
+            classification: None,
+            visible: false,
+            tenant: None,
+        }
+    }
+
+    #[test]
+    fn test_jobs_lease_retry_and_survive_reopen() {
+        let storage = Arc::new(MemoryStorage::new());
+        let first = JobQueue::open(Records::new(storage.clone(), JOBS, None));
+        let second = JobQueue::open(Records::new(storage.clone(), JOBS, None));
+
+        let low = first.enqueue(auto_run("w1"), 0).unwrap();
+        let high = first.enqueue(auto_run("w2"), 5).unwrap();
+
+        let claimed = first.claim().unwrap().unwrap();
+        assert_eq!(claimed.id, high.id);
+        assert_eq!(second.claim().unwrap().unwrap().id, low.id);
+        assert!(first.claim().unwrap().is_none());
+
+        // Only the lease holder can finish a job.
+        second.complete(&high.id, "stolen").unwrap();
+        assert_eq!(
+            first.get(&high.id).unwrap().unwrap().state,
+            JobState::Running
+        );
+
+        first.fail(&high.id, "browser crashed").unwrap();
+        let retried = first.get(&high.id).unwrap().unwrap();
+        assert_eq!(retried.state, JobState::Queued);
+        assert!(!retried.is_claimable(Utc::now()));
+
+        second.cancel(&low.id).unwrap();
+        assert!(!second.renew(&low.id).unwrap());
+        assert!(second.set_priority(&low.id, 9).is_err());
+
+        // A lapsed lease lets another worker take over.
+        let reopened = JobQueue::open(Records::new(storage, JOBS, None)).with_lease(Duration::ZERO);
+        let mut job = reopened.get(&high.id).unwrap().unwrap();
+        job.not_before = None;
+        reopened.records.save(&job.id, &job).unwrap();
+        reopened.claim().unwrap().unwrap();
+        assert!(reopened.claim().unwrap().is_some());
+        assert_eq!(reopened.list().unwrap()[0].attempts, 3);
+        assert_eq!(retry_backoff(1), RETRY_BACKOFF);
+    }
+}
@@ -0,0 +1,161 @@
+//! Prompt language detection.
+//!
+//! Languages written in their own script are recognized by the script; for
+//! Latin-script languages the most frequent function words decide. Text that
+//! is too short or has no telling words is left undetected rather than
+//! guessed, so routing is only swayed when the language is clear.
+
+use std::collections::HashMap;
+
+/// Fewest letters a text needs before its language is detected.
+const MIN_LETTERS: usize = 8;
+/// Share of letters a non-Latin script needs to decide the language.
+const SCRIPT_SHARE: f64 = 0.3;
+
+/// Function words of Latin-script languages, keyed by ISO 639-1 code.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "of", "to", "in", "that", "what", "how", "with", "for",
+            "this", "you", "it", "be", "can", "why", "does", "an",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "de", "que", "y", "en", "es", "por", "para", "con", "una",
+            "cómo", "qué", "del", "se", "lo", "está", "pero",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "de", "des", "et", "est", "que", "en", "un", "une", "pour", "dans",
+            "avec", "qui", "pas", "ce", "du", "je", "vous",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "für", "auf", "den",
+            "von", "zu", "ich", "wie", "was", "es", "sie", "werden",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "gli", "di", "che", "e", "è", "per", "con", "una", "non", "come", "del",
+            "della", "sono", "un", "questo", "perché", "anche", "le",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "de", "que", "e", "é", "em", "um", "uma", "para", "com", "não", "do",
+            "da", "como", "por", "são", "você", "isso",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "niet", "dat", "met", "voor", "op", "zijn",
+            "hoe", "wat", "ik", "je", "wordt", "ook", "maar", "naar",
+        ],
+    ),
+];
+
+/// Detect the language of a text, as an ISO 639-1 code.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut scripts: HashMap<&'static str, usize> = HashMap::new();
+    let mut letters = 0;
+    let mut kana = false;
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        if let Some(language) = script_language(c) {
+            *scripts.entry(language).or_default() += 1;
+        }
+        kana |= matches!(c, '\u{3040}'..='\u{30FF}');
+    }
+    // Ideographic text packs a word into a character or two, so short text
+    // in a non-Latin script still counts.
+    if letters < MIN_LETTERS && scripts.values().sum::<usize>() < 2 {
+        return None;
+    }
+
+    if let Some((language, count)) = scripts.into_iter().max_by_key(|(_, count)| *count) {
+        if count as f64 >= letters as f64 * SCRIPT_SHARE {
+            // Japanese mixes kanji with kana; Chinese has no kana.
+            return Some(if language == "zh" && kana {
+                "ja"
+            } else {
+                language
+            });
+        }
+    }
+
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let (language, hits) = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|w| stopwords.contains(&w.as_str()))
+                .count();
+            (*language, hits)
+        })
+        // Ties go to the language listed first.
+        .rev()
+        .max_by_key(|(_, hits)| *hits)?;
+    (hits > 0).then_some(language)
+}
+
+/// Language written in the script a character belongs to, for scripts
+/// mostly used by one language.
+fn script_language(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{3040}'..='\u{30FF}' => "ja",
+        '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' => "zh",
+        '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' => "ko",
+        '\u{0400}'..='\u{04FF}' => "ru",
+        '\u{0600}'..='\u{06FF}' => "ar",
+        '\u{0590}'..='\u{05FF}' => "he",
+        '\u{0900}'..='\u{097F}' => "hi",
+        '\u{0370}'..='\u{03FF}' => "el",
+        '\u{0E00}'..='\u{0E7F}' => "th",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("What is the best way to learn Rust?"),
+            Some("en")
+        );
+        assert_eq!(
+            detect_language("¿Cuál es la mejor manera de aprender Rust para un proyecto?"),
+            Some("es")
+        );
+        assert_eq!(
+            detect_language("Wie funktioniert das Ownership-System in Rust?"),
+            Some("de")
+        );
+        assert_eq!(
+            detect_language("Rustの所有権について説明してください"),
+            Some("ja")
+        );
+        assert_eq!(detect_language("解释一下所有权"), Some("zh"));
+        assert_eq!(detect_language("Объясни владение в Rust"), Some("ru"));
+        assert_eq!(detect_language("fn main() {}"), None);
+        assert_eq!(detect_language("Explain Rust lifetimes"), None);
+    }
+}
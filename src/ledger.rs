@@ -0,0 +1,165 @@
+//! Ledger of routed requests.
+//!
+//! Every prompt the router places is recorded with what shaped the decision
+//! (task type, language, token count, classification, routing choice) and
+//! how it went (provider, latency, estimated cost, failure). Replaying the
+//! ledger against a proposed routing configuration projects what the change
+//! would have done to past traffic. Entries older than the retention period
+//! are pruned when the ledger is opened.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::Result;
+use crate::objectives::RoutingChoice;
+use crate::security::DataClassification;
+use crate::storage::Records;
+
+/// Days ledger entries are kept.
+pub const RETENTION_DAYS: i64 = 30;
+
+/// One routed request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Entry ID.
+    pub id: String,
+    /// When the request was sent.
+    pub at: DateTime<Utc>,
+    /// Tenant the request was attributed to.
+    pub tenant: String,
+    /// Task type the prompt was routed as.
+    pub task: String,
+    /// Detected language of the prompt.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Estimated tokens of the prompt and its context.
+    pub prompt_tokens: u64,
+    /// Prompt and response tokens of the answer (0 if it failed).
+    pub exchange_tokens: u64,
+    /// Data classification of the prompt.
+    pub classification: DataClassification,
+    /// Routing profile or weights the call asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingChoice>,
+    /// Provider the request was sent to.
+    pub provider: String,
+    /// Time until the answer or failure, in milliseconds.
+    pub latency_ms: u64,
+    /// Estimated cost of the answer, in USD.
+    pub cost_usd: f64,
+    /// Kind of error, if the request failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LedgerEntry {
+    /// Whether the request failed.
+    pub fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// Persisted ledger entries.
+#[derive(Clone)]
+pub struct RequestLedger {
+    records: Records,
+}
+
+impl RequestLedger {
+    /// Open the ledger over a collection, pruning entries past retention.
+    pub fn open(records: Records) -> Self {
+        let ledger = Self { records };
+        if let Err(e) = ledger.prune(Utc::now() - Duration::days(RETENTION_DAYS)) {
+            warn!("Failed to prune the request ledger: {}", e);
+        }
+        ledger
+    }
+
+    /// Store an entry.
+    pub fn record(&self, entry: &LedgerEntry) -> Result<()> {
+        self.records.save(&entry.id, entry)
+    }
+
+    /// Entries sent at or after `since` (all if `None`), oldest first.
+    pub fn list(&self, since: Option<DateTime<Utc>>) -> Result<Vec<LedgerEntry>> {
+        let mut entries: Vec<LedgerEntry> = self.records.load_all()?;
+        entries.retain(|e| since.is_none_or(|since| e.at >= since));
+        entries.sort_by_key(|e| e.at);
+        Ok(entries)
+    }
+
+    /// Remove entries sent before `before`, returning how many were removed.
+    pub fn prune(&self, before: DateTime<Utc>) -> Result<usize> {
+        let stale: Vec<LedgerEntry> = self
+            .records
+            .load_all::<LedgerEntry>()?
+            .into_iter()
+            .filter(|e| e.at < before)
+            .collect();
+        for entry in &stale {
+            self.records.remove(&entry.id)?;
+        }
+        Ok(stale.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::AgentOrchestrator;
+    use crate::projection::RoutingProposal;
+
+    fn entry(tenant: &str, at: DateTime<Utc>) -> LedgerEntry {
+        LedgerEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            at,
+            tenant: tenant.into(),
+            task: "code".into(),
+            language: None,
+            prompt_tokens: 200,
+            exchange_tokens: 600,
+            classification: DataClassification::Public,
+            routing: None,
+            provider: "claude".into(),
+            latency_ms: 1500,
+            cost_usd: 0.0,
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ledger_replays_under_proposal() {
+        let orchestrator = AgentOrchestrator::new();
+        let ledger = orchestrator.request_ledger();
+        let now = Utc::now();
+        ledger.record(&entry("default", now)).unwrap();
+        ledger.record(&entry("ci", now)).unwrap();
+        ledger
+            .record(&entry("default", now - Duration::days(3)))
+            .unwrap();
+
+        let proposal: RoutingProposal =
+            serde_json::from_value(serde_json::json!({ "disable": ["claude"] })).unwrap();
+        let projection = orchestrator.replay_routing(&proposal, None).await.unwrap();
+        assert_eq!(projection.requests, 3);
+        assert_eq!(projection.rerouted, 3);
+        assert_eq!(projection.shares["claude"], (3, 0));
+
+        // Tenants see only their own traffic, and a window drops older entries
+        let since = Some(now - Duration::days(1));
+        let tenant = orchestrator.for_tenant("ci");
+        let projection = tenant.replay_routing(&proposal, since).await.unwrap();
+        assert_eq!(projection.requests, 1);
+        let unchanged = RoutingProposal::default();
+        let projection = orchestrator
+            .replay_routing(&unchanged, since)
+            .await
+            .unwrap();
+        assert_eq!(projection.requests, 2);
+        assert_eq!(projection.rerouted, 0);
+
+        assert_eq!(ledger.prune(now - Duration::days(1)).unwrap(), 1);
+        assert_eq!(ledger.list(None).unwrap().len(), 2);
+    }
+}
@@ -43,22 +43,105 @@
 //! | `agent_prompt` | Send a prompt to best available provider |
 //! | `agent_workflow_start` | Start a multi-step workflow |
 //! | `agent_workflow_step` | Execute next step in workflow |
+//! | `agent_workflow_run` | Run a workflow until it completes, pauses, or fails |
+//! | `agent_workflow_review` | Approve or reject a step paused for review |
+//! | `agent_workflow_list` | List workflows with their state and progress |
+//! | `agent_workflow_status` | Show each step of a workflow and its result |
 //! | `agent_parallel_prompt` | Send same prompt to multiple providers |
 //! | `agent_consensus` | Get consensus answer from multiple providers |
 //! | `agent_status` | Get orchestration status and stats |
 //! | `agent_config` | Configure provider preferences |
 
+pub mod accounts;
+pub mod adapters;
+pub mod aggregate;
+pub mod anomaly;
+pub mod approval;
+pub mod audit;
+pub mod auto;
+pub mod backend;
+pub mod bench;
+pub mod canary;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod chunk;
+pub mod citations;
+pub mod cli;
+pub mod completeness;
+pub mod compose;
+pub mod condition;
+pub mod confidence;
+pub mod consensus;
+pub mod cost;
+pub mod critique;
+pub mod diagnostics;
+pub mod encryption;
 pub mod error;
+pub mod eval;
+pub mod events;
+pub mod export;
+pub mod extract;
+pub mod factcheck;
+pub mod fairshare;
+pub mod github;
+pub mod grading;
+pub mod health;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod idempotency;
+pub mod interpolate;
+pub mod intervention;
+pub mod jobs;
+pub mod language;
+pub mod ledger;
+pub mod library;
+pub mod maintenance;
+pub mod map;
+pub mod metadata;
+pub mod notify;
+pub mod objectives;
 pub mod orchestrator;
+pub mod output;
+pub mod patch;
+pub mod postprocess;
+pub mod projection;
+pub mod plan;
 pub mod protocol;
+#[cfg(feature = "api-providers")]
+pub mod providers;
+pub mod race;
+pub mod ratelimit;
+pub mod records;
+pub mod refusal;
+pub mod replay;
+pub mod research;
+pub mod retry;
 pub mod router;
+pub mod run;
+pub mod sanitize;
+pub mod security;
 pub mod server;
+pub mod session;
+pub mod settings;
+pub mod shadow;
+pub mod shared;
+pub mod simulate;
+pub mod slo;
+pub mod status;
+pub mod templates;
+pub mod tenant;
+pub mod storage;
+pub mod testkit;
 pub mod tools;
+pub mod translate;
+pub mod webhook;
 pub mod workflow;
+pub mod workspace;
 
 pub use error::{Error, Result};
 pub use orchestrator::AgentOrchestrator;
 pub use protocol::{McpRequest, McpResponse};
 pub use router::ProviderRouter;
+pub use security::{DataClassification, SecurityGuard};
 pub use server::AgentMcpServer;
 pub use workflow::{Workflow, WorkflowStep, WorkflowState};
@@ -0,0 +1,414 @@
+//! Server-side library of named, reusable prompts.
+//!
+//! Prompts are templates with `{{variable}}` placeholders, saved in the
+//! workspace's storage so everyone using the workspace shares them.
+//! They are managed with the `agent_prompt_*` tools and also surfaced through
+//! the MCP prompts capability.
+//!
+//! Every change to a prompt creates a new version and keeps the old one, so
+//! workflows can pin a step to the exact text they were written against and
+//! two versions can be compared.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::error::{Error, Result};
+use crate::storage::{MemoryStorage, Records, PROMPTS};
+
+/// A named prompt template.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPrompt {
+    /// Unique name.
+    pub name: String,
+    /// What the prompt is for.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Template text with `{{variable}}` placeholders.
+    pub template: String,
+    /// When the prompt was first saved.
+    pub created_at: DateTime<Utc>,
+    /// When the prompt was last changed.
+    pub updated_at: DateTime<Utc>,
+    /// Current version, starting at 1.
+    #[serde(default = "first_version")]
+    pub version: u32,
+    /// Earlier versions, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<PromptVersion>,
+}
+
+/// An earlier version of a saved prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptVersion {
+    /// Version number.
+    pub version: u32,
+    /// Template text of this version.
+    pub template: String,
+    /// Description of this version.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// When this version was saved.
+    pub created_at: DateTime<Utc>,
+}
+
+impl SavedPrompt {
+    /// The prompt as it was at `version`, without its history.
+    pub fn at_version(&self, version: u32) -> Result<SavedPrompt> {
+        if version == self.version {
+            return Ok(SavedPrompt {
+                history: Vec::new(),
+                ..self.clone()
+            });
+        }
+        let old = self
+            .history
+            .iter()
+            .find(|v| v.version == version)
+            .ok_or_else(|| {
+                Error::InvalidParams(format!(
+                    "prompt '{}' has no version {} (current version is {})",
+                    self.name, version, self.version
+                ))
+            })?;
+        Ok(SavedPrompt {
+            name: self.name.clone(),
+            description: old.description.clone(),
+            template: old.template.clone(),
+            created_at: self.created_at,
+            updated_at: old.created_at,
+            version: old.version,
+            history: Vec::new(),
+        })
+    }
+
+
+    /// Variables used by the template, in order of first use.
+    pub fn variables(&self) -> Vec<String> {
+        template_variables(&self.template)
+    }
+
+    /// Fill in the template's variables.
+    pub fn render(&self, arguments: &HashMap<String, String>) -> Result<String> {
+        let missing: Vec<_> = self
+            .variables()
+            .into_iter()
+            .filter(|v| !arguments.contains_key(v))
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::InvalidParams(format!(
+                "prompt '{}' is missing variables: {}",
+                self.name,
+                missing.join(", ")
+            )));
+        }
+
+        Ok(fill_variables(&self.template, arguments))
+    }
+}
+
+/// Variables used by a template, in order of first use.
+pub fn template_variables(template: &str) -> Vec<String> {
+    let mut variables: Vec<String> = Vec::new();
+    for caps in variable_re().captures_iter(template) {
+        if !variables.iter().any(|v| v == &caps[1]) {
+            variables.push(caps[1].to_string());
+        }
+    }
+    variables
+}
+
+/// Fill in a template's variables, leaving those without a value as they are.
+pub fn fill_variables(template: &str, arguments: &HashMap<String, String>) -> String {
+    variable_re()
+        .replace_all(template, |caps: &regex::Captures| {
+            arguments
+                .get(&caps[1])
+                .cloned()
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Fill in the variables of every string in a JSON value.
+pub fn fill_json_variables(value: &mut Value, arguments: &HashMap<String, String>) {
+    match value {
+        Value::String(text) => *text = fill_variables(text, arguments),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|v| fill_json_variables(v, arguments)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|v| fill_json_variables(v, arguments)),
+        _ => {}
+    }
+}
+
+/// A saved prompt pinned to a version, written `name@version`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptRef {
+    /// Prompt name.
+    pub name: String,
+    /// Pinned version (the current version if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+}
+
+impl fmt::Display for PromptRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            Some(version) => write!(f, "{}@{}", self.name, version),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+impl FromStr for PromptRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, version) = match s.rsplit_once('@') {
+            Some((name, version)) => {
+                let version = version.trim_start_matches('v').parse().map_err(|_| {
+                    Error::InvalidParams(format!("invalid prompt version in '{}'", s))
+                })?;
+                (name, Some(version))
+            }
+            None => (s, None),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            version,
+        })
+    }
+}
+
+/// Line-by-line diff of two texts: unchanged lines are prefixed with two
+/// spaces, removed lines with `- `, and added lines with `+ `.
+pub fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // Longest common subsequence lengths of every pair of suffixes.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    out.join("\n")
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+/// Matches a `{{variable}}` placeholder.
+fn variable_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap())
+}
+
+/// Saved prompts, one record per prompt.
+#[derive(Clone)]
+pub struct PromptLibrary {
+    records: Records,
+    /// Serializes read-modify-write changes within this process.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl Default for PromptLibrary {
+    fn default() -> Self {
+        Self::open(Records::new(Arc::new(MemoryStorage::new()), PROMPTS, None))
+    }
+}
+
+impl PromptLibrary {
+    /// An in-memory library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the library kept in `records`.
+    pub fn open(records: Records) -> Self {
+        Self {
+            records,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Save a new prompt. Fails if the name is taken.
+    pub fn create(
+        &self,
+        name: &str,
+        template: impl Into<String>,
+        description: Option<String>,
+    ) -> Result<SavedPrompt> {
+        let _guard = self.lock();
+        if self.records.load::<SavedPrompt>(name)?.is_some() {
+            return Err(Error::InvalidParams(format!(
+                "prompt '{}' already exists",
+                name
+            )));
+        }
+        let now = Utc::now();
+        let prompt = SavedPrompt {
+            name: name.to_string(),
+            description,
+            template: template.into(),
+            created_at: now,
+            updated_at: now,
+            version: first_version(),
+            history: Vec::new(),
+        };
+        self.records.save(name, &prompt)?;
+        Ok(prompt)
+    }
+
+    /// Change an existing prompt's template and/or description, saving the
+    /// result as a new version. An update that changes nothing keeps the
+    /// current version.
+    pub fn update(
+        &self,
+        name: &str,
+        template: Option<String>,
+        description: Option<String>,
+    ) -> Result<SavedPrompt> {
+        let _guard = self.lock();
+        let mut prompt = self.get(name)?;
+        let template = template.unwrap_or_else(|| prompt.template.clone());
+        let description = description.or_else(|| prompt.description.clone());
+        if template == prompt.template && description == prompt.description {
+            return Ok(prompt);
+        }
+        prompt.history.push(PromptVersion {
+            version: prompt.version,
+            template: std::mem::replace(&mut prompt.template, template),
+            description: std::mem::replace(&mut prompt.description, description),
+            created_at: prompt.updated_at,
+        });
+        prompt.version += 1;
+        prompt.updated_at = Utc::now();
+        self.records.save(name, &prompt)?;
+        Ok(prompt)
+    }
+
+    /// Delete a prompt.
+    pub fn delete(&self, name: &str) -> Result<()> {
+        let _guard = self.lock();
+        self.get(name)?;
+        self.records.remove(name)
+    }
+
+    /// Get a prompt by name.
+    pub fn get(&self, name: &str) -> Result<SavedPrompt> {
+        self.records.load(name)?.ok_or_else(|| not_found(name))
+    }
+
+    /// Get a prompt as it was at a version (the current one if unset).
+    pub fn get_version(&self, name: &str, version: Option<u32>) -> Result<SavedPrompt> {
+        let prompt = self.get(name)?;
+        match version {
+            Some(version) => prompt.at_version(version),
+            None => Ok(prompt),
+        }
+    }
+
+    /// All prompts, sorted by name. Unreadable storage yields an empty list.
+    pub fn list(&self) -> Vec<SavedPrompt> {
+        let mut prompts: Vec<SavedPrompt> = self.records.load_all().unwrap_or_else(|e| {
+            warn!("Failed to load saved prompts: {}", e);
+            Vec::new()
+        });
+        prompts.sort_by(|a, b| a.name.cmp(&b.name));
+        prompts
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.write_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn not_found(name: &str) -> Error {
+    Error::InvalidParams(format!("no saved prompt named '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+
+    #[test]
+    fn test_library_crud_and_render() {
+        let root =
+            std::env::temp_dir().join(format!("agent-mcp-prompts-{}", uuid::Uuid::new_v4()));
+        let records = || Records::new(Arc::new(FileStorage::new(&root)), PROMPTS, None);
+        let library = PromptLibrary::open(records());
+        library
+            .create("review", "Review this {{lang}} code:\n{{ code }}", None)
+            .unwrap();
+        assert!(library.create("review", "duplicate", None).is_err());
+
+        let prompt = library.get("review").unwrap();
+        assert_eq!(prompt.variables(), vec!["lang", "code"]);
+        let arguments = HashMap::from([
+            ("lang".to_string(), "Rust".to_string()),
+            ("code".to_string(), "fn main() {}".to_string()),
+        ]);
+        assert_eq!(
+            prompt.render(&arguments).unwrap(),
+            "Review this Rust code:\nfn main() {}"
+        );
+        assert!(prompt.render(&HashMap::new()).is_err());
+
+        library
+            .update("review", None, Some("Code review".into()))
+            .unwrap();
+        library
+            .update("review", Some("Review this {{lang}} code:\n{{code}}".into()), None)
+            .unwrap();
+        let reopened = PromptLibrary::open(records());
+        let current = reopened.get("review").unwrap();
+        assert_eq!(current.version, 3);
+        assert_eq!(current.description.as_deref(), Some("Code review"));
+        let first = reopened.get_version("review", Some(1)).unwrap();
+        assert_eq!(first.description, None);
+        assert!(reopened.get_version("review", Some(4)).is_err());
+        assert_eq!(
+            line_diff(&first.template, &current.template),
+            "  Review this {{lang}} code:\n- {{ code }}\n+ {{code}}"
+        );
+        let pinned: PromptRef = "review@v2".parse().unwrap();
+        assert_eq!(pinned.to_string(), "review@2");
+        reopened.delete("review").unwrap();
+        assert!(reopened.list().is_empty());
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
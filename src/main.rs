@@ -1,16 +1,53 @@
 //! Agent MCP Server - Multi-agent orchestration for VS Code/GitHub Copilot.
 
-use clap::Parser;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{CommandFactory, Parser, Subcommand};
 use tracing::info;
 use tracing_subscriber::{fmt, EnvFilter};
 
-use embeddenator_agent_mcp::{AgentMcpServer, AgentOrchestrator};
+use embeddenator_agent_mcp::accounts::AccountPolicy;
+use embeddenator_agent_mcp::adapters::PromptAdapters;
+use embeddenator_agent_mcp::audit::{self, AuditLog};
+use embeddenator_agent_mcp::bench::{self, BenchOptions};
+use embeddenator_agent_mcp::cli::{completion_script, OutputFormat, Shell};
+use embeddenator_agent_mcp::encryption::PayloadCipher;
+use embeddenator_agent_mcp::eval::{self, EvalReport, EvalSuite};
+use embeddenator_agent_mcp::events::{self, EventSink};
+use embeddenator_agent_mcp::health::{HealthSweepPolicy, ProbeKind};
+use embeddenator_agent_mcp::notify;
+use embeddenator_agent_mcp::objectives::{RoutingChoice, RoutingProfiles};
+use embeddenator_agent_mcp::orchestrator::PromptOptions;
+use embeddenator_agent_mcp::plan::WorkflowDef;
+use embeddenator_agent_mcp::projection::RoutingProposal;
+#[cfg(feature = "api-providers")]
+use embeddenator_agent_mcp::providers::api_providers;
+use embeddenator_agent_mcp::replay::ReplayMode;
+use embeddenator_agent_mcp::settings::{self, EditorSettings};
+use embeddenator_agent_mcp::status;
+use embeddenator_agent_mcp::storage::{MemoryStorage, StorageBackend};
+#[cfg(feature = "postgres")]
+use embeddenator_agent_mcp::storage::{PostgresStorage, DATABASE_URL_ENV_VAR};
+use embeddenator_agent_mcp::testkit::MockProviders;
+use embeddenator_agent_mcp::tools::ToolRegistry;
+use embeddenator_agent_mcp::webhook;
+use embeddenator_agent_mcp::workspace::Workspace;
+use embeddenator_agent_mcp::{
+    AgentMcpServer, AgentOrchestrator, DataClassification, Workflow, WorkflowState,
+};
+use embeddenator_webpuppet::Provider;
 
 /// Agent MCP Server - Multi-agent orchestration for AI providers.
 #[derive(Parser, Debug)]
 #[command(name = "agent-mcp")]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Run a one-off command instead of serving.
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Run browser in visible (non-headless) mode.
     #[arg(long, default_value = "false")]
     visible: bool,
@@ -22,6 +59,444 @@ struct Args {
     /// Output logs as JSON.
     #[arg(long, default_value = "false")]
     json_logs: bool,
+
+    /// Path to a JSON data classification policy.
+    #[arg(long)]
+    classification_policy: Option<PathBuf>,
+
+    /// Path to a JSON response sanitization policy.
+    #[arg(long)]
+    sanitization_policy: Option<PathBuf>,
+
+    /// Path to a JSON approval policy for workflow steps.
+    #[arg(long)]
+    approval_policy: Option<PathBuf>,
+
+    /// Path to a JSON judge grading policy.
+    #[arg(long)]
+    grading_policy: Option<PathBuf>,
+
+    /// Path to a JSON provider cost model.
+    #[arg(long)]
+    cost_model: Option<PathBuf>,
+
+    /// Path to a JSON map of provider name to maximum requests per minute.
+    #[arg(long)]
+    provider_quotas: Option<PathBuf>,
+
+    /// Path to a JSON schedule policy with daily provider limits and the
+    /// share of quotas background jobs may use.
+    #[arg(long)]
+    schedule_policy: Option<PathBuf>,
+
+    /// Path to a JSON map of provider name to per-language strength (0.0-1.0).
+    #[arg(long)]
+    language_weights: Option<PathBuf>,
+
+    /// Path to a JSON map of provider name to response time objective.
+    #[arg(long)]
+    latency_slos: Option<PathBuf>,
+
+    /// Encrypt persisted state with the key in AGENT_MCP_STATE_KEY, or in the
+    /// OS keyring when built with the `keyring` feature.
+    #[arg(long, default_value = "false")]
+    encrypt_state: bool,
+
+    /// Run an evaluation suite (JSON) across providers and exit.
+    #[arg(long)]
+    eval: Option<PathBuf>,
+
+    /// Write the evaluation report here instead of stdout.
+    #[arg(long, requires = "eval")]
+    eval_output: Option<PathBuf>,
+
+    /// Set routing priorities from a saved evaluation report.
+    #[arg(long)]
+    eval_results: Option<PathBuf>,
+
+    /// Path to a JSON list of tenants with their API keys, budgets, and
+    /// quotas.
+    #[arg(long)]
+    tenants: Option<PathBuf>,
+
+    /// Path to a JSON fair-share policy (provider slots, usage half-life)
+    /// sharing contended providers between tenants.
+    #[arg(long)]
+    fair_share: Option<PathBuf>,
+
+    /// Path to a JSON list of provider accounts (browser profiles) and the
+    /// workspaces and tenants using them.
+    #[arg(long)]
+    accounts: Option<PathBuf>,
+
+    /// Path to a JSON map of provider to prompt adapter.
+    #[arg(long)]
+    prompt_adapters: Option<PathBuf>,
+
+    /// Path to a JSON policy for prompts a provider declines to answer.
+    #[arg(long)]
+    refusal_policy: Option<PathBuf>,
+
+    /// Path to JSON routing profiles weighing quality, cost, latency, quota
+    /// headroom, and freshness.
+    #[arg(long)]
+    routing_profiles: Option<PathBuf>,
+
+    /// Path to a JSON map of provider names to API backends, answering
+    /// those providers through their API instead of a browser.
+    #[cfg(feature = "api-providers")]
+    #[arg(long)]
+    api_providers: Option<PathBuf>,
+
+    /// Path to a JSON cost anomaly policy alerting on spend well above its
+    /// rolling baseline.
+    #[arg(long)]
+    cost_anomaly_policy: Option<PathBuf>,
+
+    /// Start in maintenance mode, refusing provider calls until it is left.
+    #[arg(long, value_name = "REASON")]
+    maintenance: Option<String>,
+
+    /// Leave maintenance mode left on by an earlier run.
+    #[arg(long, conflicts_with = "maintenance")]
+    end_maintenance: bool,
+
+    /// Path to a JSON champion/challenger shadow testing policy.
+    #[arg(long)]
+    shadow_policy: Option<PathBuf>,
+
+    /// Write recorded shadow comparisons as an evaluation report and exit.
+    #[arg(long)]
+    shadow_report: Option<PathBuf>,
+
+    /// Path to a JSON canary policy trying routing priorities on a share of
+    /// prompts.
+    #[arg(long)]
+    canary_policy: Option<PathBuf>,
+
+    /// Path to a JSON policy listing the hosts webhook callbacks may reach
+    /// (callbacks are disabled without one).
+    #[arg(long)]
+    webhook_policy: Option<PathBuf>,
+
+    /// Path to a JSON token budget for prompts composed from context
+    /// sections.
+    #[arg(long)]
+    context_budget: Option<PathBuf>,
+
+    /// Command run on a scratch copy of the workspace to check patches in
+    /// `validate_patch` workflow steps (e.g. "cargo check").
+    #[arg(long)]
+    patch_check_command: Option<String>,
+
+    /// Pause prompts blocked by a captcha until the user solves it.
+    #[arg(long, default_value = "false")]
+    captcha_recovery: bool,
+
+    /// Path to a JSON captcha/verification intervention policy.
+    #[arg(long)]
+    intervention_policy: Option<PathBuf>,
+
+    /// Disable all cloud and browser providers (air-gapped mode).
+    #[arg(long, default_value = "false")]
+    local_only: bool,
+
+    /// Path to a JSON file of per-tool execution time limits.
+    #[arg(long)]
+    tool_timeouts: Option<PathBuf>,
+
+    /// Path to a JSON file of post-processors applied to tool results.
+    #[arg(long)]
+    post_processors: Option<PathBuf>,
+
+    /// Deadline for a single provider request, including rate-limit retries.
+    #[arg(long, default_value = "120")]
+    request_timeout_secs: u64,
+
+    /// Key persisted state by this workspace ID instead of the working directory.
+    #[arg(long)]
+    workspace_id: Option<String>,
+
+    /// Base directory for persisted state, overriding `AGENT_MCP_STATE_DIR`.
+    #[arg(long)]
+    state_dir: Option<PathBuf>,
+
+    /// Storage backend for persisted state: sqlite (the default), file,
+    /// memory, or, when built with the `postgres` feature, postgres.
+    #[arg(long)]
+    storage: Option<StorageBackend>,
+
+    /// Record provider responses, or replay recorded ones: off, record, or
+    /// replay.
+    #[arg(long, default_value = "off")]
+    replay: ReplayMode,
+
+    /// Seed routing tie-breaks and fault injection for reproducible runs.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Log out of providers after this many seconds without activity.
+    #[arg(long)]
+    idle_timeout_secs: Option<u64>,
+
+    /// Probe every provider this often, in seconds, even when idle, so
+    /// routing knows which are up before the first request.
+    #[arg(long)]
+    health_sweep_secs: Option<u64>,
+
+    /// How health sweeps probe providers: auth (sign-in check) or ping (a
+    /// short prompt).
+    #[arg(long, default_value = "auth")]
+    health_probe: ProbeKind,
+
+    /// Run a self-test (storage, credentials, browser, providers) when a
+    /// client initializes, reporting problems to it.
+    #[arg(long, default_value = "false")]
+    self_test: bool,
+
+    /// Append a hash-chained audit log of provider dispatches to this file.
+    #[arg(long)]
+    audit_log: Option<PathBuf>,
+
+    /// Keep a JSON status file (active workflows, paused reviews, budget
+    /// usage) at this path for editor integrations to poll.
+    #[arg(long)]
+    status_file: Option<PathBuf>,
+
+    /// Path to a VS Code `settings.json`-style block with provider
+    /// priorities, disabled providers, and budgets, applied again whenever
+    /// it changes.
+    #[arg(long)]
+    vscode_settings: Option<PathBuf>,
+
+    /// Raise a desktop notification when a background workflow pauses for
+    /// review.
+    #[arg(long, default_value = "false")]
+    desktop_notifications: bool,
+
+    /// Stream orchestration events as JSON lines to a file, `tcp://host:port`,
+    /// or `unix:///path/to/socket`.
+    #[arg(long)]
+    event_stream: Option<EventSink>,
+
+    /// Verify an audit log file and exit.
+    #[arg(long)]
+    verify_audit: Option<PathBuf>,
+
+    /// Path to a JSON fault injection policy (resilience testing only).
+    #[cfg(feature = "chaos")]
+    #[arg(long)]
+    chaos: Option<PathBuf>,
+
+    /// Serve over HTTP on this address instead of stdio.
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    http: Option<std::net::SocketAddr>,
+
+    /// Maximum requests per client per minute (HTTP).
+    #[cfg(feature = "http")]
+    #[arg(long, default_value = "60")]
+    client_rpm: u32,
+
+    /// Maximum concurrent calls per client (HTTP).
+    #[cfg(feature = "http")]
+    #[arg(long, default_value = "4")]
+    client_max_concurrent: usize,
+
+    /// Maximum concurrent provider requests.
+    #[arg(long, default_value = "5")]
+    max_concurrent: usize,
+
+    /// Browser contexts kept per provider account; each is signed in once.
+    #[arg(long, default_value = "2")]
+    browser_pool_size: usize,
+
+    /// Times an empty answer is asked for again.
+    #[arg(long, default_value = "1")]
+    incomplete_retries: u32,
+
+    /// Continuations requested for an answer cut short.
+    #[arg(long, default_value = "3")]
+    max_continuations: u32,
+}
+
+/// One-off commands.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Drive load through the orchestrator and report throughput, latency,
+    /// and saturation.
+    Bench {
+        /// Prompts to send in total.
+        #[arg(long, default_value = "50")]
+        requests: usize,
+
+        /// Prompts in flight at once.
+        #[arg(long, default_value = "5")]
+        concurrency: usize,
+
+        /// Prompt sent on every request.
+        #[arg(long, default_value = "Reply with OK.")]
+        prompt: String,
+
+        /// Send every prompt to this provider instead of routing.
+        #[arg(long)]
+        provider: Option<String>,
+
+        /// Answer with mock providers instead of real ones.
+        #[arg(long, default_value = "false")]
+        mock: bool,
+
+        /// Latency of each mock provider answer, in milliseconds.
+        #[arg(long, default_value = "500", requires = "mock")]
+        mock_latency_ms: u64,
+
+        /// Write the report here instead of stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Send one prompt through the orchestrator and print the answer.
+    Prompt {
+        /// Prompt to send. With `--stdin`, it comes before the piped text.
+        #[arg(required_unless_present = "stdin")]
+        message: Option<String>,
+
+        /// Read the prompt, or text to append to it, from stdin.
+        #[arg(long, default_value = "false")]
+        stdin: bool,
+
+        /// Send the prompt to this provider instead of routing.
+        #[arg(long, conflicts_with = "consensus")]
+        provider: Option<String>,
+
+        /// Ask at least this many providers and print their consensus.
+        #[arg(long)]
+        consensus: Option<usize>,
+
+        /// System context or instructions.
+        #[arg(long)]
+        context: Option<String>,
+
+        /// Data classification restricting which providers may receive the
+        /// prompt: public, internal, or confidential.
+        #[arg(long)]
+        classification: Option<DataClassification>,
+
+        /// Model, or alias such as `sonnet`, for an API provider.
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Routing profile choosing the provider, such as `cheap` or `fast`.
+        #[arg(long)]
+        routing: Option<String>,
+    },
+    /// Run a workflow definition to completion and print its final output.
+    Run {
+        /// Workflow definition: the object `agent_workflow_start` accepts, as
+        /// JSON or YAML.
+        file: PathBuf,
+
+        /// Value for a `{{key}}` placeholder in the definition, as key=value.
+        #[arg(long = "input", value_parser = parse_input)]
+        inputs: Vec<(String, String)>,
+
+        /// Approve review steps and steps the approval policy pauses instead
+        /// of stopping at them.
+        #[arg(long, default_value = "false")]
+        approve: bool,
+
+        /// Print the final output (text) or a report of every step (json).
+        #[arg(long, default_value = "text")]
+        output: OutputFormat,
+    },
+    /// Print active workflows, paused reviews, jobs, and budget usage in the
+    /// workspace.
+    Status {
+        /// Print a summary (text) or the status file's JSON (json).
+        #[arg(long, default_value = "text")]
+        output: OutputFormat,
+    },
+    /// Replay routed requests from the request ledger against a proposed
+    /// routing configuration and print the projected change.
+    ReplayRouting {
+        /// Proposed configuration: JSON with optional `routing_profiles`,
+        /// `priorities`, `disable`, and `enable`.
+        proposal: PathBuf,
+
+        /// Replay only the last this many days.
+        #[arg(long)]
+        days: Option<u32>,
+
+        /// Print the report (text) or the projection's JSON (json).
+        #[arg(long, default_value = "text")]
+        output: OutputFormat,
+    },
+    /// Print a shell completion script: bash, zsh, or fish.
+    Completions {
+        /// Shell to complete in.
+        shell: Shell,
+    },
+}
+
+/// Output of the last step of a run that is not a review.
+fn final_output(workflow: &Workflow) -> Option<&str> {
+    let steps = workflow.source_steps(&[]).ok()?;
+    let result = steps.into_iter().rev().find_map(|s| s.result.as_ref())?;
+    Some(&result.output)
+}
+
+/// JSON report of a run: the workflow's state, each step's outcome, and the
+/// final output.
+fn run_report(workflow: &Workflow, error: Option<&str>) -> serde_json::Value {
+    let steps: Vec<serde_json::Value> = workflow
+        .steps
+        .iter()
+        .map(|step| {
+            let result = step.result.as_ref();
+            serde_json::json!({
+                "name": step.name,
+                "state": step.state.name(),
+                "provider": result.and_then(|r| r.provider.as_deref()),
+                "duration_ms": result.map(|r| r.duration_ms),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "workflow_id": workflow.id,
+        "name": workflow.name,
+        "succeeded": error.is_none(),
+        "error": error,
+        "steps": steps,
+        "output": final_output(workflow),
+    })
+}
+
+/// Parse a `key=value` workflow input.
+fn parse_input(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected key=value, got '{}'", input)),
+    }
+}
+
+/// The prompt of a `prompt` command: the message, the text piped to stdin,
+/// or the message followed by the piped text.
+fn prompt_text(message: Option<&str>, stdin: bool) -> anyhow::Result<String> {
+    let piped = if stdin {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        String::new()
+    };
+    let text = match (message, piped.trim()) {
+        (Some(message), "") => message.to_string(),
+        (Some(message), piped) => format!("{}\n\n{}", message, piped),
+        (None, piped) => piped.to_string(),
+    };
+    if text.trim().is_empty() {
+        anyhow::bail!("the prompt is empty");
+    }
+    Ok(text)
 }
 
 #[tokio::main]
@@ -45,18 +520,452 @@ async fn main() -> anyhow::Result<()> {
             .init();
     }
 
+    let audit_key = std::env::var(audit::SIGNING_KEY_ENV_VAR).ok();
+
+    if let Some(Command::Completions { shell }) = &args.command {
+        print!("{}", completion_script(&Args::command(), *shell));
+        return Ok(());
+    }
+
+    if let Some(path) = &args.verify_audit {
+        let count = AuditLog::verify(path, audit_key.as_deref().map(str::as_bytes))?;
+        println!("audit log intact: {} entries verified", count);
+        return Ok(());
+    }
+
     info!("Agent MCP Server starting");
     info!("Visible mode: {}", args.visible);
 
     // Create orchestrator with configuration
-    let config = embeddenator_agent_mcp::orchestrator::OrchestratorConfig {
+    let mut config = embeddenator_agent_mcp::orchestrator::OrchestratorConfig {
         headless: !args.visible,
+        timeout: Duration::from_secs(args.request_timeout_secs),
+        idle_timeout: args.idle_timeout_secs.map(Duration::from_secs),
+        local_only: args.local_only,
+        max_concurrent: args.max_concurrent,
+        browser_pool_size: args.browser_pool_size,
+        incomplete_retries: args.incomplete_retries,
+        max_continuations: args.max_continuations,
         ..Default::default()
     };
+    if let Some(path) = &args.classification_policy {
+        config.classification_policy = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded classification policy from {}", path.display());
+    }
+    if let Some(path) = &args.sanitization_policy {
+        config.sanitization = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded sanitization policy from {}", path.display());
+    }
+    if let Some(path) = &args.approval_policy {
+        config.approval_policy = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded approval policy from {}", path.display());
+    }
+    if let Some(path) = &args.intervention_policy {
+        config.intervention = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded intervention policy from {}", path.display());
+    }
+    config.intervention.enabled |= args.captcha_recovery;
+    if let Some(path) = &args.grading_policy {
+        config.grading = Some(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+        info!("Loaded grading policy from {}", path.display());
+    }
+    if let Some(path) = &args.cost_model {
+        config.cost_model = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded cost model from {}", path.display());
+    }
+    if let Some(path) = &args.provider_quotas {
+        config.provider_quotas = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded provider quotas from {}", path.display());
+    }
+    if let Some(path) = &args.schedule_policy {
+        config.schedule = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded schedule policy from {}", path.display());
+    }
+    if let Some(path) = &args.language_weights {
+        config.language_weights = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded language weights from {}", path.display());
+    }
+    if let Some(path) = &args.latency_slos {
+        config.latency_slos = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded latency SLOs from {}", path.display());
+    }
+    if let Some(path) = &args.tenants {
+        config.tenants = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded tenants from {}", path.display());
+    }
+    if let Some(path) = &args.fair_share {
+        config.fair_share = Some(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+        info!("Loaded fair-share policy from {}", path.display());
+    }
+    if let Some(path) = &args.accounts {
+        let accounts: AccountPolicy = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        accounts.validate()?;
+        config.accounts = accounts;
+        info!("Loaded provider accounts from {}", path.display());
+    }
+    if let Some(path) = &args.prompt_adapters {
+        let adapters: PromptAdapters = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        adapters.validate()?;
+        config.prompt_adapters = adapters;
+        info!("Loaded prompt adapters from {}", path.display());
+    }
+    if let Some(path) = &args.refusal_policy {
+        config.refusal_policy = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded refusal policy from {}", path.display());
+    }
+    if let Some(path) = &args.routing_profiles {
+        let profiles: RoutingProfiles = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        profiles.validate()?;
+        config.routing_profiles = profiles;
+        info!("Loaded routing profiles from {}", path.display());
+    }
+    #[cfg(feature = "api-providers")]
+    if let Some(path) = &args.api_providers {
+        config.api_providers = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        // Fail on a missing key now rather than on the first prompt
+        api_providers(&config.api_providers)?;
+        info!("Loaded API providers from {}", path.display());
+    }
+    if let Some(path) = &args.cost_anomaly_policy {
+        config.cost_anomaly = Some(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+        info!("Loaded cost anomaly policy from {}", path.display());
+    }
+    if let Some(path) = &args.shadow_policy {
+        config.shadow = Some(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+        info!("Loaded shadow policy from {}", path.display());
+    }
+    if let Some(path) = &args.canary_policy {
+        config.canary = Some(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+        info!("Loaded canary policy from {}", path.display());
+    }
+    if let Some(path) = &args.webhook_policy {
+        config.webhook = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded webhook policy from {}", path.display());
+    }
+    if let Some(path) = &args.context_budget {
+        config.context_budget = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        info!("Loaded context budget from {}", path.display());
+    }
+    if let Some(command) = &args.patch_check_command {
+        config.patch_check_command = Some(command.clone());
+        info!("Patches are checked with `{}`", command);
+    }
+    #[cfg(feature = "chaos")]
+    if let Some(path) = &args.chaos {
+        config.chaos = Some(serde_json::from_str(&std::fs::read_to_string(path)?)?);
+        info!("Loaded chaos policy from {}", path.display());
+    }
+    if let Some(path) = &args.eval_results {
+        config.provider_priorities = EvalReport::load(path)?.priorities();
+        info!("Loaded routing priorities from {}", path.display());
+    }
+    if args.encrypt_state {
+        config.cipher = Some(PayloadCipher::load()?);
+        info!("Encryption at rest enabled");
+    }
+    if let Some(path) = &args.audit_log {
+        let mut log = AuditLog::open(path)?;
+        if let Some(key) = &audit_key {
+            log = log.with_signing_key(key.as_bytes())?;
+        }
+        config.audit_log = Some(Arc::new(log));
+        info!("Audit log: {}", path.display());
+    }
+    config.webhook_secret = std::env::var(webhook::SECRET_ENV_VAR).ok();
+    let workspace = Workspace::resolve(args.workspace_id.as_deref(), args.state_dir.as_deref())?;
+    info!("Workspace {} state: {}", workspace.id, workspace.state_dir.display());
+    match args.storage.unwrap_or_default() {
+        StorageBackend::File => {
+            info!("Storage: JSON files in {}", workspace.state_dir.display());
+        }
+        StorageBackend::Memory => {
+            config.storage = Some(Arc::new(MemoryStorage::new()));
+            info!("Storage: in memory, state will not survive a restart");
+        }
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => {
+            if args.storage.is_none() && workspace.has_file_state() {
+                tracing::warn!(
+                    "{} holds file storage state, which SQLite storage does not read; \
+                     pass --storage file to keep using it",
+                    workspace.state_dir.display()
+                );
+            }
+            config.storage = Some(Arc::new(workspace.sqlite_storage()?));
+            info!("Storage: SQLite database in {}", workspace.state_dir.display());
+        }
+        #[cfg(feature = "postgres")]
+        StorageBackend::Postgres => {
+            let url = std::env::var(DATABASE_URL_ENV_VAR)
+                .map_err(|_| anyhow::anyhow!("--storage postgres needs {}", DATABASE_URL_ENV_VAR))?;
+            config.storage = Some(Arc::new(PostgresStorage::connect(&url, &workspace.id)?));
+            info!("Storage: Postgres, workspace {}", workspace.id);
+        }
+    }
+    config.workspace = Some(workspace);
+    config.replay = args.replay;
+    config.seed = args.seed;
+    if let Some(secs) = args.health_sweep_secs {
+        config.health_sweep = Some(HealthSweepPolicy {
+            interval: Duration::from_secs(secs.max(1)),
+            probe: args.health_probe,
+            ..Default::default()
+        });
+        info!("Health sweep every {}s ({:?} probe)", secs, args.health_probe);
+    }
+    if args.replay != ReplayMode::Off {
+        info!("Replay mode: {:?}", args.replay);
+    }
+    if let Some(Command::Bench {
+        mock: true,
+        mock_latency_ms,
+        ..
+    }) = &args.command
+    {
+        config.mock_providers =
+            Some(MockProviders::new().with_latency(Duration::from_millis(*mock_latency_ms)));
+        config.storage = Some(Arc::new(MemoryStorage::new()));
+        info!("Benchmarking mock providers ({}ms per answer)", mock_latency_ms);
+    }
     let orchestrator = AgentOrchestrator::with_config(config);
+    if let Some(reason) = &args.maintenance {
+        orchestrator.enter_maintenance(reason)?;
+    } else if args.end_maintenance {
+        orchestrator.exit_maintenance()?;
+    }
+    if let Some(sink) = &args.event_stream {
+        events::spawn_event_stream(orchestrator.events(), sink.clone());
+        info!("Streaming events to {:?}", sink);
+    }
+    if let Some(path) = &args.status_file {
+        status::spawn_status_file(&orchestrator, path.clone());
+        info!("Status file: {}", path.display());
+    }
+    if let Some(path) = &args.vscode_settings {
+        orchestrator.apply_settings(&EditorSettings::load(path)?).await;
+        settings::spawn_settings_watcher(&orchestrator, path.clone());
+        info!("Loaded VS Code settings from {}", path.display());
+    }
+    if args.desktop_notifications {
+        notify::spawn_desktop_notifier(orchestrator.events());
+        info!("Desktop notifications enabled");
+    }
+    if let Some(Command::Bench {
+        requests,
+        concurrency,
+        prompt,
+        provider,
+        output,
+        ..
+    }) = &args.command
+    {
+        let provider = provider
+            .as_deref()
+            .map(|name| {
+                Provider::from_string(name)
+                    .ok_or_else(|| anyhow::anyhow!("unknown provider: {}", name))
+            })
+            .transpose()?;
+        let options = BenchOptions {
+            requests: *requests,
+            concurrency: *concurrency,
+            prompt: prompt.clone(),
+            provider,
+        };
+        let report = bench::run(&orchestrator, &options).await;
+        match output {
+            Some(path) => std::fs::write(path, serde_json::to_string_pretty(&report)?)?,
+            None => println!("{}", serde_json::to_string_pretty(&report)?),
+        }
+        info!("{}", report.summary());
+        return Ok(());
+    }
+
+    if let Some(Command::Prompt {
+        message,
+        stdin,
+        provider,
+        consensus,
+        context,
+        classification,
+        model,
+        routing,
+    }) = &args.command
+    {
+        let text = prompt_text(message.as_deref(), *stdin)?;
+        let options = PromptOptions {
+            context: context.clone(),
+            classification: *classification,
+            visible: args.visible,
+            model: model.clone(),
+            routing: routing.clone().map(RoutingChoice::Profile),
+        };
+        if let Some(min_providers) = consensus {
+            let result = orchestrator
+                .consensus_prompt_with(text, *min_providers, options)
+                .await?;
+            println!("{}", result.consensus_text);
+            info!(
+                "Consensus of {} providers, {:.0}% agreement",
+                result.responses.len(),
+                result.agreement_score * 100.0
+            );
+            return Ok(());
+        }
+        let response = match provider {
+            Some(name) => {
+                let provider = Provider::from_string(name)
+                    .ok_or_else(|| anyhow::anyhow!("unknown provider: {}", name))?;
+                orchestrator.prompt_provider_with(provider, text, options).await?
+            }
+            None => orchestrator.prompt_with(text, options).await?,
+        };
+        println!("{}", response.text);
+        info!("Answered by {}", response.provider);
+        return Ok(());
+    }
+
+    if let Some(Command::Run {
+        file,
+        inputs,
+        approve,
+        output,
+    }) = &args.command
+    {
+        let inputs = inputs.iter().cloned().collect();
+        let workflow =
+            WorkflowDef::load(file, &inputs)?.to_workflow_with(orchestrator.prompt_library())?;
+        // Register the MCP tools for the workflow's tool steps
+        ToolRegistry::new(orchestrator.clone());
+        let total = workflow.steps.len();
+        let id = orchestrator.start_workflow(workflow).await?;
+        info!("Running workflow {} from {}", id, file.display());
+        let mut failure = None;
+        loop {
+            let workflow = orchestrator
+                .get_workflow(&id)
+                .await
+                .ok_or_else(|| anyhow::anyhow!("workflow {} disappeared", id))?;
+            let Some(step) = workflow.current() else { break };
+            let progress = format!("[{}/{}] {}", workflow.current_step + 1, total, step.name);
+            info!("{}", progress);
+            match orchestrator.execute_workflow_step_with(&id, args.visible).await {
+                Ok(result) => info!("{}: done in {}ms", progress, result.duration_ms),
+                Err(e) => {
+                    let paused = orchestrator
+                        .get_workflow(&id)
+                        .await
+                        .is_some_and(|w| w.state == WorkflowState::Paused);
+                    if !paused {
+                        failure = Some(format!("{} failed: {}", progress, e));
+                        break;
+                    }
+                    if !*approve {
+                        failure = Some(format!(
+                            "{} needs approval ({}); rerun with --approve",
+                            progress, e
+                        ));
+                        break;
+                    }
+                    info!("{}: approved", progress);
+                    orchestrator.approve_step(&id).await?;
+                }
+            }
+        }
+
+        let workflow = orchestrator
+            .get_workflow(&id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("workflow {} disappeared", id))?;
+        match output {
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&run_report(&workflow, failure.as_deref()))?
+            ),
+            OutputFormat::Text if failure.is_none() => {
+                println!("{}", final_output(&workflow).unwrap_or_default())
+            }
+            OutputFormat::Text => {}
+        }
+        if let Some(failure) = failure {
+            anyhow::bail!(failure);
+        }
+        info!("Workflow {} completed", id);
+        return Ok(());
+    }
+
+    if let Some(path) = &args.eval {
+        let suite = EvalSuite::load(path)?;
+        let report = eval::run_suite(&orchestrator, &suite).await?;
+        match &args.eval_output {
+            Some(output) => report.save(output)?,
+            None => println!("{}", serde_json::to_string_pretty(&report)?),
+        }
+        for (provider, score) in report.provider_scores() {
+            info!("{}: {:.2}", provider, score);
+        }
+        return Ok(());
+    }
+    if let Some(path) = &args.shadow_report {
+        let report = orchestrator.shadow().report()?;
+        report.save(path)?;
+        for (provider, score) in report.provider_scores() {
+            info!("{}: {:.2}", provider, score);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Status { output }) = &args.command {
+        let snapshot = orchestrator.status_snapshot().await;
+        match output {
+            OutputFormat::Text => println!("{}", snapshot.to_text()),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&snapshot)?),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::ReplayRouting {
+        proposal,
+        days,
+        output,
+    }) = &args.command
+    {
+        let proposal: RoutingProposal = serde_json::from_str(&std::fs::read_to_string(proposal)?)?;
+        let since = days.map(|days| chrono::Utc::now() - chrono::Duration::days(i64::from(days)));
+        let projection = orchestrator.replay_routing(&proposal, since).await?;
+        match output {
+            OutputFormat::Text => println!("{}", projection.report()),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&projection)?),
+        }
+        return Ok(());
+    }
 
     // Create and run server
-    let mut server = AgentMcpServer::new(orchestrator);
+    orchestrator.spawn_idle_watchdog();
+    orchestrator.spawn_health_sweeper();
+    orchestrator.spawn_job_worker();
+    let mut server = AgentMcpServer::new(orchestrator).with_self_test(args.self_test);
+    if let Some(path) = &args.tool_timeouts {
+        let timeouts = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        server = server.with_tool_timeouts(timeouts);
+        info!("Loaded tool timeouts from {}", path.display());
+    }
+    if let Some(path) = &args.post_processors {
+        let post_processing = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        server = server.with_post_processing(post_processing);
+        info!("Loaded post-processors from {}", path.display());
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(addr) = args.http {
+        let limits = embeddenator_agent_mcp::ratelimit::ClientLimits {
+            requests_per_minute: args.client_rpm,
+            max_concurrent: args.client_max_concurrent,
+        };
+        embeddenator_agent_mcp::http::serve(server, addr, limits).await?;
+        return Ok(());
+    }
+
     server.run_stdio().await?;
 
     Ok(())
@@ -0,0 +1,98 @@
+//! Maintenance mode.
+//!
+//! While the orchestrator is in maintenance mode, such as during provider
+//! credential rotation, new provider calls are refused, auto runs stop before
+//! their next step, and queued jobs wait. Calls already in flight finish. The
+//! switch is kept in storage, so it applies to every instance sharing the
+//! backend and survives restarts until someone resumes.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::storage::Records;
+
+/// Key of the switch in its collection.
+const STATE_KEY: &str = "state";
+
+/// Why and since when the orchestrator is in maintenance mode.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Maintenance {
+    /// Why, as given by whoever started it.
+    pub reason: String,
+    /// When it started.
+    pub since: DateTime<Utc>,
+}
+
+impl Maintenance {
+    /// One-line description.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} (since {})",
+            self.reason,
+            self.since.format("%Y-%m-%d %H:%M UTC")
+        )
+    }
+}
+
+/// Maintenance mode switch shared through storage.
+#[derive(Clone)]
+pub struct MaintenanceSwitch {
+    records: Records,
+}
+
+impl MaintenanceSwitch {
+    /// Keep the switch in `records`.
+    pub fn open(records: Records) -> Self {
+        Self { records }
+    }
+
+    /// The current maintenance, if the orchestrator is in maintenance mode.
+    pub fn current(&self) -> Result<Option<Maintenance>> {
+        self.records.load(STATE_KEY)
+    }
+
+    /// Enter maintenance mode, or update the reason if already in it.
+    pub fn enter(&self, reason: &str) -> Result<Maintenance> {
+        self.records
+            .update(STATE_KEY, |current: Option<Maintenance>| Maintenance {
+                reason: reason.to_string(),
+                since: current.map_or_else(Utc::now, |m| m.since),
+            })
+    }
+
+    /// Leave maintenance mode, returning the maintenance that ended.
+    pub fn exit(&self) -> Result<Option<Maintenance>> {
+        let current = self.current()?;
+        if current.is_some() {
+            self.records.remove(STATE_KEY)?;
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStorage, MAINTENANCE};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_maintenance_switch() {
+        let storage = Arc::new(MemoryStorage::new());
+        let first = MaintenanceSwitch::open(Records::new(storage.clone(), MAINTENANCE, None));
+        let second = MaintenanceSwitch::open(Records::new(storage, MAINTENANCE, None));
+        assert_eq!(first.current().unwrap(), None);
+
+        let entered = first.enter("rotating credentials").unwrap();
+        let updated = second.enter("rotating Claude credentials").unwrap();
+        assert_eq!(updated.since, entered.since);
+        assert!(updated
+            .describe()
+            .starts_with("rotating Claude credentials (since "));
+
+        assert_eq!(first.exit().unwrap(), Some(updated));
+        assert_eq!(second.current().unwrap(), None);
+        assert_eq!(second.exit().unwrap(), None);
+    }
+}
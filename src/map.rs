@@ -0,0 +1,157 @@
+//! Mapping one prompt over a list of items.
+//!
+//! A `map` step sends the same prompt once per item, with `{{name}}` and
+//! `{{item}}` replaced by the item's name and text, such as one prompt per
+//! issue when triaging. Items run one after another, as prompts to browser
+//! providers do. With `json`, each answer is read as a JSON object and the
+//! step's output lists every item's fields, so the answers form a single
+//! structured report; the records are also kept in the step's metadata. A
+//! second provider can cross-check each answer against its item and correct
+//! it before it is recorded.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::library::fill_variables;
+
+/// An item a `map` step prompts for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapItem {
+    /// Short name identifying the item in the output.
+    pub name: String,
+    /// Text inserted into the prompt.
+    pub text: String,
+}
+
+/// The answer for one item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapAnswer {
+    /// Name of the item.
+    pub item: String,
+    /// Provider that answered, if one did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Answer text, or why there is none.
+    pub text: String,
+    /// Provider that cross-checked the answer, if one did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_by: Option<String>,
+    /// Whether the prompt failed.
+    #[serde(default)]
+    pub failed: bool,
+    /// The answer as a JSON object, for steps expecting one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<Map<String, Value>>,
+}
+
+/// Build the prompt for one item.
+pub fn item_prompt(message: &str, item: &MapItem) -> String {
+    let arguments = HashMap::from([
+        ("name".to_string(), item.name.clone()),
+        ("item".to_string(), item.text.clone()),
+    ]);
+    fill_variables(message, &arguments)
+}
+
+/// Build the prompt asking a second provider to check and correct the
+/// answer to `prompt`.
+pub fn check_prompt(prompt: &str, answer: &str) -> String {
+    format!(
+        "Another assistant was given the task below. Check its answer against \
+         the task and its input for errors, omissions, and claims the input does \
+         not support. Reply with only the corrected answer, in the same format; \
+         if nothing needs correcting, reply with the answer unchanged.\n\n\
+         Task:\n{}\n\nAnswer:\n{}",
+        prompt.trim(),
+        answer.trim()
+    )
+}
+
+/// The JSON object in an answer, which may be wrapped in prose or a fence.
+pub fn parse_record(text: &str) -> Option<Map<String, Value>> {
+    let (start, end) = (text.find('{')?, text.rfind('}')?);
+    match serde_json::from_str(text.get(start..=end)?) {
+        Ok(Value::Object(record)) => Some(record),
+        _ => None,
+    }
+}
+
+/// Markdown report with a section per item: the record's fields for
+/// answers read as JSON, otherwise the answer itself.
+pub fn render(answers: &[MapAnswer]) -> String {
+    let mut out = String::new();
+    for answer in answers {
+        out.push_str(&format!("## {}\n\n", answer.item));
+        match &answer.record {
+            _ if answer.failed => out.push_str(&format!("*Failed: {}*\n\n", answer.text)),
+            Some(record) => {
+                for (key, value) in record {
+                    out.push_str(&format!("- **{}**: {}\n", key, field(value)));
+                }
+                out.push('\n');
+            }
+            None => out.push_str(&format!("{}\n\n", answer.text.trim())),
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// A field value on one bullet, continuation lines indented under it.
+fn field(value: &Value) -> String {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(field).collect::<Vec<_>>().join(", "),
+        Value::Null => "-".into(),
+        other => other.to_string(),
+    };
+    text.trim().replace('\n', "\n  ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_prompts_and_report() {
+        let item = MapItem {
+            name: "octo/app#3".into(),
+            text: "Crash on {{start}}".into(),
+        };
+        assert_eq!(
+            item_prompt("Triage {{name}}:\n{{item}}", &item),
+            "Triage octo/app#3:\nCrash on {{start}}"
+        );
+
+        let answer = "Here you go:\n```json\n{\"priority\": \"P1\", \"labels\": [\"bug\", \"ui\"], \"response\": \"Thanks!\\nCan you share logs?\"}\n```";
+        let record = parse_record(answer).unwrap();
+        assert_eq!(record["priority"], "P1");
+        assert!(parse_record("No JSON here").is_none());
+        assert!(parse_record("[1, 2]").is_none());
+
+        let report = render(&[
+            MapAnswer {
+                item: item.name.clone(),
+                provider: Some("claude".into()),
+                text: answer.into(),
+                checked_by: Some("gemini".into()),
+                failed: false,
+                record: Some(record),
+            },
+            MapAnswer {
+                item: "octo/app#4".into(),
+                provider: None,
+                text: "rate limited".into(),
+                checked_by: None,
+                failed: true,
+                record: None,
+            },
+        ]);
+        assert!(report.starts_with("## octo/app#3\n\n"));
+        assert!(report.contains("- **labels**: bug, ui\n"));
+        assert!(report.contains("- **response**: Thanks!\n  Can you share logs?"));
+        assert!(report.ends_with("## octo/app#4\n\n*Failed: rate limited*"));
+        assert!(check_prompt("Triage", "P1").ends_with("Task:\nTriage\n\nAnswer:\nP1"));
+    }
+}
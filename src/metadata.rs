@@ -0,0 +1,192 @@
+//! Response metadata: model, token estimates, timings, cache status, and
+//! prompt language.
+//!
+//! Every provider response is annotated with these keys so callers can see
+//! where time went (waiting for a browser or rate limit versus waiting on the
+//! provider) and roughly how large the exchange was.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use embeddenator_webpuppet::{PromptRequest, PromptResponse};
+
+use crate::citations::{self, SOURCES_KEY};
+use crate::completeness::{self, CONTINUATIONS_KEY, INCOMPLETE_KEY};
+use crate::cost::estimate_tokens;
+use crate::race::RACED_AGAINST_KEY;
+use crate::refusal::REFUSED_BY_KEY;
+
+/// Model that produced the response.
+pub const MODEL_KEY: &str = "model";
+/// Estimated prompt tokens, including context.
+pub const PROMPT_TOKENS_KEY: &str = "prompt_tokens";
+/// Response tokens, as reported by the provider or estimated.
+pub const RESPONSE_TOKENS_KEY: &str = "response_tokens";
+/// Milliseconds spent before the prompt reached the provider.
+pub const QUEUE_MS_KEY: &str = "queue_ms";
+/// Milliseconds the provider took to respond.
+pub const PROVIDER_MS_KEY: &str = "provider_ms";
+/// Whether the response was served from a cache (`hit` or `miss`).
+pub const CACHE_KEY: &str = "cache";
+/// Detected language of the prompt (ISO 639-1), if it could be detected.
+pub const LANGUAGE_KEY: &str = "language";
+/// Why the model stopped (`stop`, `length`, ...), if the provider says.
+pub const FINISH_REASON_KEY: &str = "finish_reason";
+
+/// Keys recorded on every response, in display order.
+pub const RESPONSE_METADATA_KEYS: &[&str] = &[
+    MODEL_KEY,
+    PROMPT_TOKENS_KEY,
+    RESPONSE_TOKENS_KEY,
+    QUEUE_MS_KEY,
+    PROVIDER_MS_KEY,
+    CACHE_KEY,
+    LANGUAGE_KEY,
+];
+
+/// Where a response's latency was spent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseTiming {
+    /// Time before the prompt was sent (browser setup, login, rate limits).
+    pub queue: Duration,
+    /// Time the provider took to answer.
+    pub provider: Duration,
+}
+
+/// Estimated tokens sent for a request, including its context.
+pub fn prompt_tokens(request: &PromptRequest) -> u64 {
+    estimate_tokens(&request.message) + request.context.as_deref().map_or(0, estimate_tokens)
+}
+
+/// Record model, token, timing, and cache metadata on a response.
+///
+/// Values the provider already reported (model, cache status) are kept.
+pub fn enrich(response: &mut PromptResponse, prompt_tokens: u64, timing: ResponseTiming) {
+    let response_tokens = response
+        .tokens_used
+        .map_or_else(|| estimate_tokens(&response.text), u64::from);
+    let model = format!("{}-web", response.provider);
+    let metadata = &mut response.metadata;
+
+    metadata.entry(MODEL_KEY.into()).or_insert(model);
+    metadata.insert(PROMPT_TOKENS_KEY.into(), prompt_tokens.to_string());
+    metadata.insert(RESPONSE_TOKENS_KEY.into(), response_tokens.to_string());
+    metadata.insert(QUEUE_MS_KEY.into(), timing.queue.as_millis().to_string());
+    metadata.insert(
+        PROVIDER_MS_KEY.into(),
+        timing.provider.as_millis().to_string(),
+    );
+    metadata
+        .entry(CACHE_KEY.into())
+        .or_insert_with(|| "miss".into());
+}
+
+/// Prompt and response tokens of an exchange, as recorded by [`enrich`], or
+/// estimated from the response text if they were not.
+pub fn exchange_tokens(response: &PromptResponse) -> u64 {
+    let recorded: Vec<u64> = [PROMPT_TOKENS_KEY, RESPONSE_TOKENS_KEY]
+        .iter()
+        .filter_map(|key| response.metadata.get(*key)?.parse().ok())
+        .collect();
+    if recorded.is_empty() {
+        estimate_tokens(&response.text)
+    } else {
+        recorded.iter().sum()
+    }
+}
+
+/// Response metadata as JSON values, with numbers kept numeric and cited
+/// sources as a list.
+pub fn to_json(response: &PromptResponse) -> HashMap<String, serde_json::Value> {
+    let mut json: HashMap<String, serde_json::Value> = RESPONSE_METADATA_KEYS
+        .iter()
+        .chain(completeness::METADATA_KEYS)
+        .chain([&REFUSED_BY_KEY, &RACED_AGAINST_KEY])
+        .filter_map(|key| {
+            let value = response.metadata.get(*key)?;
+            let value = value
+                .parse::<u64>()
+                .map(serde_json::Value::from)
+                .unwrap_or_else(|_| value.clone().into());
+            Some((key.to_string(), value))
+        })
+        .collect();
+    let citations = citations::from_response(response);
+    if !citations.is_empty() {
+        json.insert(SOURCES_KEY.into(), serde_json::json!(citations));
+    }
+    json
+}
+
+/// One-line summary of the response metadata for tool output.
+pub fn summary(response: &PromptResponse) -> String {
+    let get = |key: &str| response.metadata.get(key).map_or("?", String::as_str);
+    let continued = response
+        .metadata
+        .get(CONTINUATIONS_KEY)
+        .map(|n| format!(" · continued {}×", n))
+        .unwrap_or_default();
+    let incomplete = response
+        .metadata
+        .get(INCOMPLETE_KEY)
+        .map(|reason| format!(" · incomplete ({})", reason))
+        .unwrap_or_default();
+    let refused = response
+        .metadata
+        .get(REFUSED_BY_KEY)
+        .map(|providers| format!(" · declined by {}", providers))
+        .unwrap_or_default();
+    let raced = response
+        .metadata
+        .get(RACED_AGAINST_KEY)
+        .map(|providers| format!(" · raced against {}", providers))
+        .unwrap_or_default();
+    format!(
+        "_{} · ~{} → ~{} tokens · queue {}ms · provider {}ms · cache {}{}{}{}{}_",
+        get(MODEL_KEY),
+        get(PROMPT_TOKENS_KEY),
+        get(RESPONSE_TOKENS_KEY),
+        get(QUEUE_MS_KEY),
+        get(PROVIDER_MS_KEY),
+        get(CACHE_KEY),
+        continued,
+        incomplete,
+        refused,
+        raced
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use embeddenator_webpuppet::Provider;
+
+    #[test]
+    fn test_enrich_records_estimates_and_timings() {
+        let mut response = PromptResponse {
+            text: "Paris is the capital.".into(),
+            provider: Provider::Claude,
+            conversation_id: None,
+            timestamp: Utc::now(),
+            tokens_used: None,
+            metadata: HashMap::new(),
+        };
+        let timing = ResponseTiming {
+            queue: Duration::from_millis(40),
+            provider: Duration::from_millis(1500),
+        };
+
+        assert_eq!(exchange_tokens(&response), 6);
+        enrich(&mut response, 7, timing);
+        assert_eq!(exchange_tokens(&response), 13);
+        let json = to_json(&response);
+
+        assert_eq!(json[MODEL_KEY], "claude-web");
+        assert_eq!(json[PROMPT_TOKENS_KEY], 7);
+        assert_eq!(json[RESPONSE_TOKENS_KEY], 6);
+        assert_eq!(json[QUEUE_MS_KEY], 40);
+        assert_eq!(json[PROVIDER_MS_KEY], 1500);
+        assert_eq!(json[CACHE_KEY], "miss");
+    }
+}
@@ -0,0 +1,145 @@
+//! Desktop notifications for events that need the user.
+//!
+//! A workflow running as a background job can stop at a review step with
+//! nobody watching. The notifier raises a native desktop notification when
+//! that happens: `notify-send` on Linux and other Unix desktops, `osascript`
+//! on macOS.
+
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::events::{EventBus, OrchestratorEvent};
+
+/// Application name notifications are shown under.
+const APP_NAME: &str = "agent-mcp";
+
+/// A desktop notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// Headline.
+    pub title: String,
+    /// Details.
+    pub body: String,
+}
+
+impl Notification {
+    /// The notification to raise for an event, if the event needs the user.
+    pub fn for_event(event: &OrchestratorEvent) -> Option<Self> {
+        match event {
+            OrchestratorEvent::ReviewRequired {
+                workflow_id,
+                workflow_name,
+                step_name,
+                reason,
+            } => Some(Self {
+                title: format!("Workflow '{}' needs review", workflow_name),
+                body: format!(
+                    "Step '{}' of workflow {}: {}",
+                    step_name, workflow_id, reason
+                ),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Command that shows the notification on this platform, if supported.
+    fn command(&self) -> Option<Command> {
+        if cfg!(target_os = "macos") {
+            let script = format!(
+                "display notification \"{}\" with title \"{}\"",
+                applescript_escape(&self.body),
+                applescript_escape(&self.title)
+            );
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(script);
+            Some(command)
+        } else if cfg!(unix) {
+            let mut command = Command::new("notify-send");
+            command
+                .args(["--app-name", APP_NAME])
+                .arg(&self.title)
+                .arg(&self.body);
+            Some(command)
+        } else {
+            None
+        }
+    }
+
+    /// Show the notification.
+    pub async fn show(&self) -> std::io::Result<()> {
+        let Some(mut command) = self.command() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "desktop notifications are not supported on this platform",
+            ));
+        };
+        let status = command.status().await?;
+        if !status.success() {
+            return Err(std::io::Error::other(format!(
+                "notification command exited with {}",
+                status
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Quote a string for an AppleScript string literal.
+fn applescript_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Raise a desktop notification for every event on `bus` that needs the
+/// user. Failures to notify are logged and otherwise ignored.
+pub fn spawn_desktop_notifier(bus: &EventBus) -> JoinHandle<()> {
+    let mut events = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let Some(notification) = Notification::for_event(&event) else {
+                continue;
+            };
+            debug!("Desktop notification: {}", notification.title);
+            if let Err(e) = notification.show().await {
+                warn!("Failed to show desktop notification: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_review_notification() {
+        let event = OrchestratorEvent::ReviewRequired {
+            workflow_id: "wf-1".into(),
+            workflow_name: "release".into(),
+            step_name: "sign-off".into(),
+            reason: "Check the \"final\" draft".into(),
+        };
+        let notification = Notification::for_event(&event).unwrap();
+        assert_eq!(notification.title, "Workflow 'release' needs review");
+        assert!(notification.body.contains("wf-1"));
+        assert_eq!(
+            applescript_escape(&notification.body)
+                .matches("\\\"")
+                .count(),
+            2
+        );
+
+        let resolved = OrchestratorEvent::InterventionResolved {
+            id: "i-1".into(),
+            provider: "Claude".into(),
+            solved: true,
+        };
+        assert_eq!(Notification::for_event(&resolved), None);
+    }
+}
@@ -0,0 +1,377 @@
+//! Multi-objective provider routing.
+//!
+//! Providers are ranked by a weighted sum of objectives, each normalized to
+//! 0.0-1.0 with higher better:
+//!
+//! - `quality`: the quality prior, the provider's priority adjusted for the
+//!   task and the prompt's language relative to the best candidate, moved
+//!   toward its mean judge-graded quality as grades come in.
+//! - `cost`: cheapness, from the cost model's rate.
+//! - `latency`: speed, from the average response time.
+//! - `quota`: share of the provider's shared per-minute quota left.
+//! - `freshness`: how recently the provider last answered, halved for each
+//!   failure since.
+//!
+//! Weights come from named profiles: `balanced` for most tasks and `quick`
+//! for quick questions unless configured otherwise, with `cheap` and `fast`
+//! also built in. A call may name a profile or pass its own weights.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::shared::QuotaUsage;
+
+/// Grades the quality prior counts as when blended with judged quality.
+const PRIOR_GRADES: f64 = 2.0;
+
+/// Rate, in USD per 1k tokens, scoring half the cost objective.
+const REFERENCE_RATE: f64 = 0.01;
+
+/// Average latency scoring half the latency objective; providers without
+/// one count as this slow.
+const REFERENCE_LATENCY_SECS: f64 = 10.0;
+
+/// Seconds after which a success counts half as much toward freshness.
+const FRESHNESS_HALF_LIFE_SECS: f64 = 600.0;
+
+/// Name of the profile used when no other applies.
+pub const BALANCED: &str = "balanced";
+
+/// Names of the built-in profiles.
+const BUILTIN_PROFILES: [&str; 4] = [BALANCED, "quick", "cheap", "fast"];
+
+/// Weights of the routing objectives. Unset weights take the `balanced`
+/// profile's.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectiveWeights {
+    /// Weight of the quality prior.
+    pub quality: f64,
+    /// Weight of cheapness.
+    pub cost: f64,
+    /// Weight of speed.
+    pub latency: f64,
+    /// Weight of quota headroom.
+    pub quota: f64,
+    /// Weight of recent successful answers.
+    pub freshness: f64,
+}
+
+impl ObjectiveWeights {
+    /// Quality first, with a little of everything else.
+    pub fn balanced() -> Self {
+        Self {
+            quality: 1.0,
+            cost: 0.1,
+            latency: 0.3,
+            quota: 0.3,
+            freshness: 0.2,
+        }
+    }
+
+    /// Fast, cheap answers matter as much as depth.
+    pub fn quick() -> Self {
+        Self {
+            cost: 1.0,
+            latency: 1.0,
+            ..Self::balanced()
+        }
+    }
+
+    /// Cheapness over everything else.
+    pub fn cheap() -> Self {
+        Self {
+            quality: 0.5,
+            cost: 2.0,
+            latency: 0.2,
+            ..Self::balanced()
+        }
+    }
+
+    /// Speed over everything else, avoiding providers near their quota.
+    pub fn fast() -> Self {
+        Self {
+            quality: 0.5,
+            latency: 2.0,
+            quota: 0.5,
+            freshness: 0.5,
+            ..Self::balanced()
+        }
+    }
+
+    /// Check that every weight is a non-negative number and one is positive.
+    pub fn validate(&self) -> Result<()> {
+        let weights = [
+            self.quality,
+            self.cost,
+            self.latency,
+            self.quota,
+            self.freshness,
+        ];
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(Error::InvalidParams(
+                "objective weights must be non-negative numbers".into(),
+            ));
+        }
+        if weights.iter().all(|w| *w == 0.0) {
+            return Err(Error::InvalidParams(
+                "objective weights are all zero".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Weighted sum of `objectives`.
+    pub fn score(&self, objectives: &Objectives) -> f64 {
+        self.quality * objectives.quality
+            + self.cost * objectives.cost
+            + self.latency * objectives.latency
+            + self.quota * objectives.quota
+            + self.freshness * objectives.freshness
+    }
+}
+
+impl Default for ObjectiveWeights {
+    fn default() -> Self {
+        Self::balanced()
+    }
+}
+
+/// A built-in profile by name.
+fn builtin(name: &str) -> Option<ObjectiveWeights> {
+    match name {
+        BALANCED => Some(ObjectiveWeights::balanced()),
+        "quick" => Some(ObjectiveWeights::quick()),
+        "cheap" => Some(ObjectiveWeights::cheap()),
+        "fast" => Some(ObjectiveWeights::fast()),
+        _ => None,
+    }
+}
+
+/// A provider's routing objectives, each 0.0-1.0 with higher better.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Objectives {
+    /// Quality prior blended with judged quality.
+    pub quality: f64,
+    /// Cheapness.
+    pub cost: f64,
+    /// Speed.
+    pub latency: f64,
+    /// Quota headroom.
+    pub quota: f64,
+    /// Recent successful answers.
+    pub freshness: f64,
+}
+
+impl Objectives {
+    /// Quality from the `prior` (0.0-1.0) and the mean of `grades` judged
+    /// responses, the prior counting as a couple of grades.
+    pub fn quality(prior: f64, judged: Option<f64>, grades: u64) -> f64 {
+        let prior = prior.clamp(0.0, 1.0);
+        match judged {
+            Some(judged) => {
+                let grades = grades.max(1) as f64;
+                (prior * PRIOR_GRADES + judged * grades) / (PRIOR_GRADES + grades)
+            }
+            _ => prior,
+        }
+    }
+
+    /// Cheapness at `rate` USD per 1k tokens: 1.0 when free.
+    pub fn cost(rate: f64) -> f64 {
+        1.0 / (1.0 + rate.max(0.0) / REFERENCE_RATE)
+    }
+
+    /// Speed at an average `latency`.
+    pub fn latency(latency: Option<Duration>) -> f64 {
+        let secs = latency.map_or(REFERENCE_LATENCY_SECS, |l| l.as_secs_f64());
+        1.0 / (1.0 + secs / REFERENCE_LATENCY_SECS)
+    }
+
+    /// Share of a quota left: 1.0 without one.
+    pub fn quota(usage: Option<&QuotaUsage>) -> f64 {
+        match usage {
+            Some(usage) if usage.limit > 0 => {
+                1.0 - (f64::from(usage.used) / f64::from(usage.limit)).min(1.0)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Freshness from the time since the last success, halved for each of
+    /// `failures` since. A provider never heard from counts as 0.5, as does
+    /// one whose last success is long past.
+    pub fn freshness(since_success: Option<Duration>, failures: u32) -> f64 {
+        let recent = since_success.map_or(0.0, |age| {
+            0.5f64.powf(age.as_secs_f64() / FRESHNESS_HALF_LIFE_SECS)
+        });
+        (0.5 + 0.5 * recent) * 0.5f64.powi(failures.min(32) as i32)
+    }
+}
+
+/// A call's choice of weights: a profile's name, or weights of its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RoutingChoice {
+    /// A configured or built-in profile.
+    Profile(String),
+    /// Weights of the call's own.
+    Weights(ObjectiveWeights),
+}
+
+/// Weight profiles and which one each task uses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutingProfiles {
+    /// Profiles keyed by name, adding to or replacing the built-in
+    /// `balanced`, `quick`, `cheap`, and `fast`.
+    #[serde(default)]
+    pub profiles: HashMap<String, ObjectiveWeights>,
+    /// Profile used by tasks without one of their own.
+    #[serde(default = "default_profile")]
+    pub default_profile: String,
+    /// Profiles keyed by task type, such as `quick` or `code`.
+    #[serde(default = "default_task_profiles")]
+    pub task_profiles: HashMap<String, String>,
+}
+
+fn default_profile() -> String {
+    BALANCED.into()
+}
+
+fn default_task_profiles() -> HashMap<String, String> {
+    HashMap::from([("quick".to_string(), "quick".to_string())])
+}
+
+impl Default for RoutingProfiles {
+    fn default() -> Self {
+        Self {
+            profiles: HashMap::new(),
+            default_profile: default_profile(),
+            task_profiles: default_task_profiles(),
+        }
+    }
+}
+
+impl RoutingProfiles {
+    /// Check that every profile's weights are valid and every profile
+    /// named exists.
+    pub fn validate(&self) -> Result<()> {
+        for (name, weights) in &self.profiles {
+            weights
+                .validate()
+                .map_err(|e| Error::Config(format!("routing profile {}: {}", name, e)))?;
+        }
+        let named = std::iter::once(&self.default_profile).chain(self.task_profiles.values());
+        for name in named {
+            if self.profile(name).is_none() {
+                return Err(Error::Config(format!("unknown routing profile: {}", name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Weights of the profile called `name`, configured or built in.
+    pub fn profile(&self, name: &str) -> Option<ObjectiveWeights> {
+        self.profiles.get(name).copied().or_else(|| builtin(name))
+    }
+
+    /// Names of the configured and built-in profiles, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_PROFILES
+            .into_iter()
+            .map(String::from)
+            .chain(self.profiles.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Name of the profile the task named `task` uses: its own, else the
+    /// default profile.
+    pub fn profile_name(&self, task: &str) -> &str {
+        self.task_profiles
+            .get(task)
+            .unwrap_or(&self.default_profile)
+    }
+
+    /// Weights a call for the task named `task` uses: its own choice, else
+    /// the task's profile.
+    pub fn weights(&self, task: &str, choice: Option<&RoutingChoice>) -> Result<ObjectiveWeights> {
+        let name = match choice {
+            Some(RoutingChoice::Weights(weights)) => {
+                weights.validate()?;
+                return Ok(*weights);
+            }
+            Some(RoutingChoice::Profile(name)) => name,
+            None => self.profile_name(task),
+        };
+        self.profile(name).ok_or_else(|| {
+            Error::InvalidParams(format!(
+                "unknown routing profile '{}', expected one of {}",
+                name,
+                self.names().join(", ")
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_and_objectives() {
+        let profiles: RoutingProfiles = serde_json::from_str(
+            r#"{
+                "profiles": { "thrifty": { "cost": 3.0 } },
+                "task_profiles": { "quick": "thrifty" }
+            }"#,
+        )
+        .unwrap();
+        profiles.validate().unwrap();
+
+        let thrifty = profiles.weights("quick", None).unwrap();
+        assert_eq!(thrifty.cost, 3.0);
+        assert_eq!(thrifty.quality, ObjectiveWeights::balanced().quality);
+        assert_eq!(
+            profiles.weights("code", None).unwrap(),
+            ObjectiveWeights::balanced()
+        );
+        let choice: RoutingChoice = serde_json::from_str(r#""fast""#).unwrap();
+        assert_eq!(
+            profiles.weights("code", Some(&choice)).unwrap(),
+            ObjectiveWeights::fast()
+        );
+        let choice: RoutingChoice = serde_json::from_str(r#"{ "latency": 5 }"#).unwrap();
+        assert_eq!(
+            profiles.weights("code", Some(&choice)).unwrap().latency,
+            5.0
+        );
+        let unknown = RoutingChoice::Profile("lavish".into());
+        assert!(profiles.weights("code", Some(&unknown)).is_err());
+
+        let broken = RoutingProfiles {
+            default_profile: "missing".into(),
+            ..RoutingProfiles::default()
+        };
+        assert!(matches!(broken.validate(), Err(Error::Config(_))));
+
+        assert_eq!(Objectives::cost(0.0), 1.0);
+        assert_eq!(Objectives::cost(REFERENCE_RATE), 0.5);
+        assert_eq!(Objectives::latency(None), 0.5);
+        assert!(Objectives::latency(Some(Duration::from_secs(1))) > 0.9);
+        let usage = QuotaUsage {
+            used: 15,
+            limit: 20,
+        };
+        assert_eq!(Objectives::quota(Some(&usage)), 0.25);
+        assert_eq!(Objectives::freshness(None, 0), 0.5);
+        assert_eq!(Objectives::freshness(Some(Duration::ZERO), 0), 1.0);
+        assert_eq!(Objectives::freshness(Some(Duration::ZERO), 1), 0.5);
+        assert!((Objectives::quality(1.0, Some(0.1), 1) - 0.7).abs() < 1e-9);
+    }
+}
@@ -1,143 +1,3008 @@
 //! Agent orchestrator for multi-provider prompt execution.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use tokio::sync::RwLock;
+use futures::future::join_all;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::{Mutex, OwnedMutexGuard, OwnedSemaphorePermit, RwLock, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
 use embeddenator_webpuppet::{Provider, PromptRequest, PromptResponse, WebPuppet};
 
+use crate::aggregate::{assemble, Aggregation};
+use crate::approval::{ApprovalDecision, ApprovalPolicy, StepRisk};
+use crate::audit::{AuditEvent, AuditLog};
+use crate::backend::{estimate_cost, BackendCapabilities, BackendRegistry, ProviderBackend};
+use crate::auto::{
+    spent_usd, synthesis_prompt, AutoOptions, AutoOutcome, AutoReport, GOAL_KEY, PLAN_KEY, SYNTHESIS_KEY,
+};
+#[cfg(feature = "chaos")]
+use crate::chaos::{ChaosPolicy, FaultInjector};
+use crate::citations::{self, Source, SourceList, SOURCES_KEY};
+use crate::condition::Condition;
+use crate::confidence::{
+    confidence_prompt, ConfidenceCheck, LowConfidence, CONFIDENCE_KEY, LOW_CONFIDENCE_KEY,
+    RATER_KEY,
+};
+use crate::consensus::{
+    agreement, argmax, find_disagreements, select, similarity, ConsensusOptions,
+    ConsensusStrategy, Disagreements,
+};
+use crate::cost::{estimate_tokens, CostModel};
+use crate::critique::{critique_prompt, parse_findings, Critique};
+use crate::encryption::PayloadCipher;
 use crate::error::{Error, Result};
-use crate::router::{ProviderRouter, TaskType};
+use crate::events::{EventBus, OrchestratorEvent};
+use crate::fairshare::{FairPermit, FairScheduler, FairSharePolicy};
+use crate::factcheck::{
+    annotate as annotate_claims, extract_claims, fact_check_prompt, parse_checks, FactCheckReport,
+    DEFAULT_MAX_CLAIMS,
+};
+use crate::github::{GitHubClient, IssueRef};
+use crate::diagnostics::{Check, Diagnostics};
+use crate::grading::{grading_prompt, parse_score, Grade, GradingPolicy, QUALITY_SCORE_KEY};
+use crate::health::{HealthSweepPolicy, ProbeKind, ProbeResult, SweepReport};
+use crate::idempotency::IdempotencyStore;
+use crate::interpolate::interpolate_step;
+use crate::intervention::{InterventionPolicy, InterventionRegistry};
+use crate::jobs::{Job, JobKind, JobQueue};
+use crate::language::detect_language;
+use crate::ledger::{LedgerEntry, RequestLedger};
+use crate::library::{fill_json_variables, PromptLibrary};
+use crate::map::{check_prompt, item_prompt, parse_record, render as render_map, MapAnswer};
+use crate::metadata::{self, ResponseTiming};
+use crate::objectives::{ObjectiveWeights, RoutingChoice, RoutingProfiles};
+use crate::patch::{
+    check_patch, check_runner, correction_prompt, extract_diff, PatchReport,
+    DEFAULT_MAX_REFINEMENTS,
+};
+use crate::plan::{parse_plan, plan_prompt, WorkflowDef};
+use crate::output::{check_writer, write_output, WRITTEN_TO_KEY};
+use crate::postprocess;
+use crate::protocol::ContentItem;
+use crate::projection::{RoutingProjection, RoutingProposal};
+use crate::race::{self, RACED_AGAINST_KEY, RACE_WIDTH};
+#[cfg(feature = "api-providers")]
+use crate::providers::{api_providers, ApiProviderConfig};
+use crate::records::{extraction_prompt, parse_records, Extraction};
+use crate::refusal::{self, RefusalPolicy, REFUSED_BY_KEY};
+use crate::replay::{ReplayMode, ReplayStore};
+use crate::run::{RunOutcome, RunReport, RunStep};
+use crate::retry::{RetriedAttempt, ATTEMPTS_KEY, RETRIED_KEY};
+use crate::research::{
+    parse_queries, query_prompt, refine_prompt, synthesis_prompt as research_synthesis_prompt,
+    Finding, ResearchOptions, ResearchReport, ResearchStop, DEFAULT_MAX_ROUNDS,
+    DEFAULT_TIME_BUDGET,
+};
+use crate::router::{
+    context_window, is_local_provider, ProviderPreferences, ProviderRouter, ProviderStats,
+    TaskType,
+};
+use crate::sanitize::{sanitize, SanitizationPolicy};
+use crate::security::{ClassificationPolicy, DataClassification, SecurityGuard};
+use crate::session::SessionStore;
+use crate::simulate::{self, RoutingSimulation, SimulatedStrategy};
+use crate::settings::EditorSettings;
+use crate::accounts::{AccountPolicy, ProviderAccount};
+use crate::adapters::PromptAdapters;
+use crate::anomaly::{AnomalyPolicy, SpendAnomaly, SpendMonitor};
+use crate::maintenance::{Maintenance, MaintenanceSwitch};
+use crate::canary::{CanaryArm, CanaryPolicy, CanaryRollout};
+use crate::completeness::{self, Incomplete, CONTINUATIONS_KEY, INCOMPLETE_KEY};
+use crate::compose::ContextBudget;
+use crate::shadow::{ShadowComparison, ShadowPolicy, ShadowStore};
+use crate::slo::{LatencySlo, SloChange};
+use crate::shared::{RequestClass, SchedulePolicy, SharedRouterState};
+use crate::status::StatusSnapshot;
+use crate::tenant::{can_access, TenantLedger, TenantPolicy, DEFAULT_TENANT};
+use crate::storage::{
+    MemoryStorage, Records, Storage, CANARY, DIAGNOSTICS, IDEMPOTENCY, JOBS, MAINTENANCE,
+    PREFERENCES, PROMPTS, REPLAY, REQUESTS, ROUTING, SESSIONS, SHADOW, SPEND, STATS, TENANTS,
+    WORKFLOWS,
+};
+use crate::testkit::MockProviders;
+use crate::tools::{ToolContext, ToolSet};
+use crate::translate::{
+    back_translation_prompt, compare as compare_translation, translation_prompt,
+    TranslationOptions, TranslationReport,
+};
+use crate::webhook::{WebhookPayload, WebhookPolicy, WebhookSender};
+use crate::workspace::Workspace;
 use crate::workflow::{
-    ProviderResponse, StepConfig, StepResult, StepState, Workflow, WorkflowState,
+    ProviderResponse, StepConfig, StepResult, StepReview, StepState, Workflow, WorkflowState,
+    WorkflowStep, REVIEW_KEY,
 };
 
-/// Orchestrator for multi-agent prompt execution.
-pub struct AgentOrchestrator {
-    /// WebPuppet instance for browser automation.
-    puppet: Arc<RwLock<Option<WebPuppet>>>,
-    /// Provider router for intelligent distribution.
-    router: Arc<RwLock<ProviderRouter>>,
-    /// Active workflows.
-    workflows: Arc<RwLock<HashMap<String, Workflow>>>,
-    /// Configuration.
-    config: OrchestratorConfig,
-}
+/// Orchestrator for multi-agent prompt execution.
+pub struct AgentOrchestrator {
+    /// WebPuppet instance for browser automation.
+    browsers: Arc<Mutex<HashMap<Option<String>, BrowserPool>>>,
+    /// Provider router for intelligent distribution.
+    router: Arc<RwLock<ProviderRouter>>,
+    /// Active workflows.
+    workflows: Arc<RwLock<HashMap<String, Workflow>>>,
+    /// IDs of the workflow steps running on this instance.
+    running_steps: RunningSteps,
+    /// Security guard enforcing data classification policy.
+    guard: Arc<SecurityGuard>,
+    /// Time of the last provider activity.
+    last_activity: Arc<RwLock<Instant>>,
+    /// Prompts waiting on a human to solve a challenge.
+    interventions: InterventionRegistry,
+    /// Events pushed to subscribers.
+    events: EventBus,
+    /// Persisted workflows.
+    workflow_store: Records,
+    /// Where the self-test checks that state can be stored.
+    diagnostics_store: Records,
+    /// Persisted provider statistics.
+    stats_store: Records,
+    /// Provider preferences changed at runtime.
+    preferences: Records,
+    /// Saved, reusable prompts.
+    prompts: PromptLibrary,
+    /// Conversation transcripts.
+    sessions: SessionStore,
+    /// Queued background work.
+    jobs: JobQueue,
+    /// Champion/challenger comparisons.
+    shadow: ShadowStore,
+    /// Routing priorities being tried on a share of prompts.
+    canary: Option<CanaryRollout>,
+    /// Recorded provider responses.
+    replay: ReplayStore,
+    /// Delivers finished job results to callback URLs.
+    webhooks: WebhookSender,
+    /// Per-tenant usage, budgets, and quotas.
+    tenants: TenantLedger,
+    /// Spend history checked for cost anomalies.
+    spend: Option<SpendMonitor>,
+    /// Maintenance mode switch.
+    maintenance: MaintenanceSwitch,
+    /// Results of tool calls made with an idempotency key.
+    idempotency: IdempotencyStore,
+    /// Ledger of routed requests.
+    requests: RequestLedger,
+    /// Tools workflow tool steps can call.
+    tools: ToolSet,
+    /// Provider calls in flight on this instance.
+    in_flight: Arc<AtomicUsize>,
+    /// Limits provider calls sent at once to `max_concurrent`.
+    provider_calls: Arc<Semaphore>,
+    /// Shares contended providers between tenants.
+    fair_share: Option<FairScheduler>,
+    /// Tenant this handle's requests are attributed to.
+    tenant: Option<String>,
+    /// Whether this handle's requests are background work.
+    request_class: RequestClass,
+    /// Set once this handle sends a prompt, so a raced call cancelled while
+    /// still queued is not charged.
+    dispatched: Option<Arc<AtomicBool>>,
+    /// Injects provider faults for resilience testing.
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<FaultInjector>>,
+    /// Backends answering providers instead of the browser.
+    backends: BackendRegistry,
+    /// Configuration.
+    config: OrchestratorConfig,
+}
+
+impl AgentOrchestrator {
+    /// Create a new orchestrator.
+    pub fn new() -> Self {
+        Self::with_config(OrchestratorConfig::default())
+    }
+
+    /// Create with custom configuration.
+    pub fn with_config(config: OrchestratorConfig) -> Self {
+        let mut router = ProviderRouter::with_preferences(configured_preferences(&config));
+        router.set_local_only(config.local_only);
+        router.set_cost_model(config.cost_model.clone());
+        router.set_profiles(config.routing_profiles.clone());
+        router.set_seed(config.seed);
+        for (name, slo) in &config.latency_slos {
+            if let Some(provider) = Provider::from_string(name) {
+                router.set_latency_slo(provider, slo.clone());
+            }
+        }
+        for (name, weights) in &config.language_weights {
+            if let Some(provider) = Provider::from_string(name) {
+                for (language, weight) in weights {
+                    router.set_language_weight(provider, language, *weight);
+                }
+            }
+        }
+
+        let storage = config.storage.clone().unwrap_or_else(|| match &config.workspace {
+            Some(ws) => Arc::new(ws.storage()),
+            None => Arc::new(MemoryStorage::new()),
+        });
+        let records = |collection| Records::new(storage.clone(), collection, config.cipher.clone());
+
+        let workflow_store = records(WORKFLOWS);
+        let workflows = match workflow_store.load_all::<Workflow>() {
+            Ok(loaded) => loaded.into_iter().map(|w| (w.id.clone(), w)).collect(),
+            Err(e) => {
+                warn!("Failed to load workspace workflows: {}", e);
+                HashMap::new()
+            }
+        };
+        let stats_store = records(STATS);
+        for provider in Provider::all() {
+            match stats_store.load(&provider.to_string()) {
+                Ok(Some(stats)) => router.set_stats(provider, stats),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to load {} stats: {}", provider, e),
+            }
+        }
+        let preferences = records(PREFERENCES);
+        match preferences.load::<ProviderPreferences>(PREFERENCES_KEY) {
+            Ok(Some(stored)) => router.set_preferences(stored),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load provider preferences: {}", e),
+        }
+        router.set_shared(
+            SharedRouterState::new(records(ROUTING), config.provider_quotas.clone())
+                .with_schedule(config.schedule.clone()),
+        );
+        let prompts = PromptLibrary::open(records(PROMPTS));
+        let sessions = SessionStore::open(records(SESSIONS));
+        let jobs = JobQueue::open(records(JOBS));
+        let shadow = ShadowStore::open(records(SHADOW));
+        let canary = config
+            .canary
+            .clone()
+            .map(|policy| CanaryRollout::open(records(CANARY), policy));
+        let replay = ReplayStore::open(records(REPLAY));
+        let webhooks = WebhookSender::new(config.webhook_secret.clone(), config.webhook.clone());
+        let tenants = TenantLedger::open(records(TENANTS), config.tenants.clone());
+        let spend = config
+            .cost_anomaly
+            .clone()
+            .map(|policy| SpendMonitor::open(records(SPEND), policy));
+        #[cfg(feature = "chaos")]
+        let chaos = config.chaos.clone().map(|mut policy| {
+            policy.seed = policy.seed.or(config.seed);
+            warn!("Chaos mode enabled: provider calls will fail, stall, and return malformed responses");
+            Arc::new(FaultInjector::new(policy))
+        });
+        let backends = BackendRegistry::default();
+        #[cfg(feature = "api-providers")]
+        match api_providers(&config.api_providers) {
+            Ok(api) => {
+                for (provider, api) in api {
+                    backends.register(provider, Arc::new(api));
+                }
+            }
+            Err(e) => warn!("Ignoring API providers: {}", e),
+        }
+        router.set_api_providers(backends.without_browser());
+
+        Self {
+            browsers: Arc::new(Mutex::new(HashMap::new())),
+            provider_calls: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            fair_share: config
+                .fair_share
+                .as_ref()
+                .map(|p| FairScheduler::new(Duration::from_secs(p.half_life_secs))),
+            router: Arc::new(RwLock::new(router)),
+            workflows: Arc::new(RwLock::new(workflows)),
+            running_steps: RunningSteps::default(),
+            guard: Arc::new(SecurityGuard::with_policy(
+                config.classification_policy.clone(),
+            )),
+            last_activity: Arc::new(RwLock::new(Instant::now())),
+            interventions: InterventionRegistry::new(),
+            events: EventBus::new(),
+            workflow_store,
+            diagnostics_store: records(DIAGNOSTICS),
+            stats_store,
+            preferences,
+            prompts,
+            sessions,
+            jobs,
+            shadow,
+            canary,
+            replay,
+            webhooks,
+            tenants,
+            spend,
+            maintenance: MaintenanceSwitch::open(records(MAINTENANCE)),
+            idempotency: IdempotencyStore::open(records(IDEMPOTENCY)),
+            requests: RequestLedger::open(records(REQUESTS)),
+            tools: ToolSet::default(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            tenant: None,
+            request_class: RequestClass::Interactive,
+            dispatched: None,
+            #[cfg(feature = "chaos")]
+            chaos,
+            backends,
+            config,
+        }
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &OrchestratorConfig {
+        &self.config
+    }
+
+    /// A handle whose requests are attributed to `tenant`, sharing all state
+    /// with this one. It only sees the workflows and sessions `tenant` may.
+    pub fn for_tenant(&self, tenant: impl Into<String>) -> Self {
+        let tenant = tenant.into();
+        let mut orchestrator = self.clone();
+        orchestrator.sessions = self.sessions.for_tenant(&tenant);
+        orchestrator.tenant = Some(tenant);
+        orchestrator
+    }
+
+    /// Handle whose requests are paced as background work, waiting for
+    /// their share of provider quotas instead of failing on them.
+    pub fn in_background(&self) -> Self {
+        let mut orchestrator = self.clone();
+        orchestrator.request_class = RequestClass::Background;
+        orchestrator
+    }
+
+    /// Tenant this handle's requests are attributed to.
+    pub fn tenant(&self) -> &str {
+        self.tenant.as_deref().unwrap_or(DEFAULT_TENANT)
+    }
+
+    /// Whether this handle's tenant may see `workflow`.
+    fn can_see(&self, workflow: &Workflow) -> bool {
+        can_access(self.tenant(), &workflow.tenant)
+    }
+
+    /// Per-tenant usage, budgets, and quotas.
+    pub fn tenants(&self) -> &TenantLedger {
+        &self.tenants
+    }
+
+    /// Results of tool calls made with an idempotency key.
+    pub fn idempotency(&self) -> &IdempotencyStore {
+        &self.idempotency
+    }
+
+    /// Tools workflow tool steps can call, filled by the tool registries
+    /// over this orchestrator.
+    pub fn tools(&self) -> &ToolSet {
+        &self.tools
+    }
+
+    /// Ledger of routed requests.
+    pub fn request_ledger(&self) -> &RequestLedger {
+        &self.requests
+    }
+
+    /// Answer `provider` through `backend` instead of the browser, from its
+    /// next prompt on. Returns the backend registered for it before.
+    pub async fn register_backend(
+        &self,
+        provider: Provider,
+        backend: Arc<dyn ProviderBackend>,
+    ) -> Option<Arc<dyn ProviderBackend>> {
+        let previous = self.backends.register(provider, backend);
+        let direct = self.backends.without_browser();
+        self.router.write().await.set_api_providers(direct);
+        previous
+    }
+
+    /// Go back to answering `provider` through the browser. Returns the
+    /// backend registered for it.
+    pub async fn unregister_backend(&self, provider: Provider) -> Option<Arc<dyn ProviderBackend>> {
+        let previous = self.backends.unregister(provider);
+        let direct = self.backends.without_browser();
+        self.router.write().await.set_api_providers(direct);
+        previous
+    }
+
+    /// Capabilities of the registered backends, keyed by provider.
+    pub fn backends(&self) -> HashMap<Provider, BackendCapabilities> {
+        self.backends.capabilities()
+    }
+
+    /// Get the spend history checked for cost anomalies, if configured.
+    pub fn spend(&self) -> Option<&SpendMonitor> {
+        self.spend.as_ref()
+    }
+
+    /// Apply provider preferences and budgets imported from VS Code settings
+    /// over the configured ones, or over those changed at runtime.
+    pub async fn apply_settings(&self, settings: &EditorSettings) {
+        let preferences = settings.preferences(&self.base_preferences());
+        self.router.write().await.set_preferences(preferences);
+        self.tenants.set_policy(settings.tenants(&self.config.tenants));
+    }
+
+    /// Provider preferences the router currently uses.
+    pub async fn provider_preferences(&self) -> ProviderPreferences {
+        self.router.read().await.preferences().clone()
+    }
+
+    /// Change provider preferences at runtime with `f`, persisting the
+    /// change so it outlives a restart, and return the preferences now used.
+    pub async fn update_preferences<F>(&self, f: F) -> Result<ProviderPreferences>
+    where
+        F: Fn(&mut ProviderPreferences),
+    {
+        self.preferences.update(PREFERENCES_KEY, |stored| {
+            let mut preferences = stored.unwrap_or_else(|| configured_preferences(&self.config));
+            f(&mut preferences);
+            preferences
+        })?;
+        let mut router = self.router.write().await;
+        let mut preferences = router.preferences().clone();
+        f(&mut preferences);
+        router.set_preferences(preferences.clone());
+        Ok(preferences)
+    }
+
+    /// Provider preferences changed at runtime, else the configured ones.
+    fn base_preferences(&self) -> ProviderPreferences {
+        self.preferences
+            .load(PREFERENCES_KEY)
+            .unwrap_or_else(|e| {
+                warn!("Failed to load provider preferences: {}", e);
+                None
+            })
+            .unwrap_or_else(|| configured_preferences(&self.config))
+    }
+
+    /// Let auto runs continue after a cost anomaly paused them.
+    pub fn resume_auto_runs(&self) -> Result<()> {
+        let Some(spend) = &self.spend else { return Ok(()) };
+        if let Some(anomaly) = spend.resume()? {
+            info!("Resumed auto runs paused after a cost anomaly: {}", anomaly.describe());
+        }
+        Ok(())
+    }
+
+    /// The cost anomaly auto runs are paused for, if they are.
+    fn spend_pause(&self) -> Option<SpendAnomaly> {
+        self.spend.as_ref()?.paused().unwrap_or_else(|e| {
+            warn!("Failed to check for a cost anomaly pause: {}", e);
+            None
+        })
+    }
+
+    /// Why auto runs and queued jobs are paused, if they are: maintenance
+    /// mode or a cost anomaly.
+    fn pause_reason(&self) -> Option<String> {
+        if let Some(maintenance) = self.maintenance() {
+            return Some(format!("maintenance mode: {}", maintenance.describe()));
+        }
+        self.spend_pause()
+            .map(|anomaly| format!("cost anomaly: {}", anomaly.describe()))
+    }
+
+    /// The current maintenance, if the orchestrator is in maintenance mode.
+    pub fn maintenance(&self) -> Option<Maintenance> {
+        self.maintenance.current().unwrap_or_else(|e| {
+            warn!("Failed to check for maintenance mode: {}", e);
+            None
+        })
+    }
+
+    /// Enter maintenance mode: new provider calls are refused, auto runs
+    /// stop before their next step, and queued jobs wait until
+    /// [`exit_maintenance`](Self::exit_maintenance). Calls in flight finish.
+    pub fn enter_maintenance(&self, reason: &str) -> Result<Maintenance> {
+        let maintenance = self.maintenance.enter(reason)?;
+        warn!("Entered maintenance mode: {}", maintenance.describe());
+        Ok(maintenance)
+    }
+
+    /// Leave maintenance mode, returning the maintenance that ended.
+    pub fn exit_maintenance(&self) -> Result<Option<Maintenance>> {
+        let ended = self.maintenance.exit()?;
+        if let Some(maintenance) = &ended {
+            info!("Left maintenance mode: {}", maintenance.describe());
+        }
+        Ok(ended)
+    }
+
+    /// Provider calls currently in flight on this instance.
+    pub fn calls_in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Wait up to `timeout` for the provider calls in flight on this
+    /// instance to finish. Returns whether they did.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        const POLL: Duration = Duration::from_millis(250);
+        let deadline = Instant::now() + timeout;
+        while self.calls_in_flight() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(POLL).await;
+        }
+        true
+    }
+
+    /// Refuse new provider calls in maintenance mode.
+    fn ensure_not_in_maintenance(&self) -> Result<()> {
+        match self.maintenance() {
+            Some(maintenance) => Err(Error::Maintenance(format!(
+                "{}; provider calls are refused until it ends",
+                maintenance.describe()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the security guard.
+    pub fn guard(&self) -> &SecurityGuard {
+        &self.guard
+    }
+
+    /// Get the sender of webhook callbacks.
+    pub fn webhooks(&self) -> &WebhookSender {
+        &self.webhooks
+    }
+
+    /// Get the cipher used for stored payloads, if encryption is enabled.
+    pub fn cipher(&self) -> Option<&PayloadCipher> {
+        self.config.cipher.as_ref()
+    }
+
+    /// Get the bus orchestrator events are published on.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Get the prompts waiting on human intervention.
+    pub fn interventions(&self) -> &InterventionRegistry {
+        &self.interventions
+    }
+
+    /// Get or create WebPuppet instance.
+    async fn get_puppet(&self, provider: Provider) -> Result<ProviderSession> {
+        self.get_puppet_with(provider, self.config.headless).await
+    }
+
+    /// Get a browser session for `provider` with the given visibility.
+    ///
+    /// Sessions with the configured visibility come from a pool of
+    /// `browser_pool_size` long-lived browser contexts per account, each
+    /// started on first use and kept between prompts until it fails or the
+    /// orchestrator is idle. A context serves one call at a time, and calls
+    /// wait for a free one. Calls with the other visibility start a browser
+    /// for that call only. With mock providers configured, or in replay
+    /// mode, no browser is started and scripted or recorded replies answer
+    /// instead. Providers with an API backend are answered through it.
+    async fn get_puppet_with(&self, provider: Provider, headless: bool) -> Result<ProviderSession> {
+        if let Some(session) = self.backend_session(provider) {
+            return Ok(session);
+        }
+        let size = self.config.browser_pool_size.max(1);
+        if headless != self.config.headless {
+            // One past the pool, so it never shares a profile with a context
+            let puppet = self.launch_puppet(provider, headless, size).await?;
+            return Ok(ProviderSession::Browser(Box::new(puppet)));
+        }
+
+        let account = self.account(provider).map(|(name, _)| name.to_string());
+        let pool = self
+            .browsers
+            .lock()
+            .await
+            .entry(account)
+            .or_insert_with(|| BrowserPool::new(size))
+            .clone();
+        let mut session = ProviderSession::Pooled(pool.lease().await?);
+        self.launch_if_closed(provider, &mut session).await?;
+        Ok(session)
+    }
+
+    /// Session answering `provider` without a browser: mock providers,
+    /// recorded replies, or a registered backend, if any applies.
+    fn backend_session(&self, provider: Provider) -> Option<ProviderSession> {
+        if let Some(mocks) = &self.config.mock_providers {
+            return Some(ProviderSession::Backend(Arc::new(mocks.clone())));
+        }
+        if self.config.replay == ReplayMode::Replay {
+            return Some(ProviderSession::Backend(Arc::new(self.replay.clone())));
+        }
+        self.backends.get(provider).map(ProviderSession::Backend)
+    }
+
+    /// Start the browser context `session` leases if it is not running.
+    async fn launch_if_closed(
+        &self,
+        provider: Provider,
+        session: &mut ProviderSession,
+    ) -> Result<()> {
+        if let ProviderSession::Pooled(lease) = session {
+            if lease.browser.is_none() {
+                let puppet = self
+                    .launch_puppet(provider, self.config.headless, lease.context)
+                    .await?;
+                *lease.browser = Some(puppet);
+                info!("Started browser context {} for {}", lease.context, provider);
+            }
+        }
+        Ok(())
+    }
+
+    /// Start browser context number `context`, signed into the account
+    /// mapped for `provider`.
+    async fn launch_puppet(
+        &self,
+        provider: Provider,
+        headless: bool,
+        context: usize,
+    ) -> Result<WebPuppet> {
+        let mut builder = WebPuppet::builder().with_all_providers();
+        if let Some((name, account)) = self.account(provider) {
+            info!("Using the {} account for {}", name, provider);
+            builder = builder.with_config(account.browser_config(context));
+        }
+        Ok(builder.headless(headless).build().await?)
+    }
+
+    /// Close the long-lived browser a call to `provider` used if the call
+    /// failed in the browser itself, so it is started again on next use.
+    /// Returns true if the browser was closed.
+    async fn close_failed_puppet<T>(
+        &self,
+        provider: Provider,
+        session: &mut ProviderSession,
+        result: &Result<T>,
+    ) -> bool {
+        let ProviderSession::Pooled(lease) = session else {
+            return false;
+        };
+        if !result.as_ref().is_err_and(|e| e.is_browser_failure()) {
+            return false;
+        }
+        if let Some(puppet) = lease.browser.take() {
+            puppet.close().await.ok();
+        }
+        warn!("Browser context {} for {} failed, reconnecting", lease.context, provider);
+        true
+    }
+
+    /// Account `provider` is signed into for this handle's tenant in the
+    /// workspace, if one is mapped.
+    fn account(&self, provider: Provider) -> Option<(&str, &ProviderAccount)> {
+        let workspace = self.config.workspace.as_ref().map(|w| w.id.as_str());
+        self.config
+            .accounts
+            .account_for(provider, workspace, self.tenant())
+    }
+
+    /// Whether a call with these options runs headless.
+    fn headless_for(&self, options: &PromptOptions) -> bool {
+        self.config.headless && !options.visible
+    }
+
+    /// Record provider activity, resetting the idle timer.
+    async fn touch(&self) {
+        *self.last_activity.write().await = Instant::now();
+    }
+
+    /// Time since the last provider activity.
+    pub async fn idle_time(&self) -> Duration {
+        self.last_activity.read().await.elapsed()
+    }
+
+    /// Close all browser sessions and drop the puppets (and their
+    /// credentials), archiving the conversation transcripts.
+    pub async fn close_sessions(&self) {
+        self.sessions.archive_active();
+        let pools: Vec<_> = self.browsers.lock().await.drain().map(|(_, p)| p).collect();
+        let mut closed = false;
+        for pool in pools {
+            closed |= pool.close().await;
+        }
+        if closed {
+            info!("Closed browser sessions");
+        }
+    }
+
+    /// Close browser sessions if idle longer than the configured timeout.
+    ///
+    /// Returns true if sessions were closed.
+    pub async fn close_idle_sessions(&self) -> bool {
+        let Some(timeout) = self.config.idle_timeout else {
+            return false;
+        };
+        if self.idle_time().await < timeout || self.browsers.lock().await.is_empty() {
+            return false;
+        }
+
+        info!("Idle for over {:?}, logging out of providers", timeout);
+        self.close_sessions().await;
+        true
+    }
+
+    /// Spawn a background task enforcing the idle timeout.
+    ///
+    /// Returns `None` if no idle timeout is configured.
+    pub fn spawn_idle_watchdog(&self) -> Option<JoinHandle<()>> {
+        let timeout = self.config.idle_timeout?;
+        let interval = (timeout / 4).clamp(Duration::from_secs(1), Duration::from_secs(30));
+        let orchestrator = self.clone();
+
+        Some(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                orchestrator.close_idle_sessions().await;
+            }
+        }))
+    }
+
+    /// Spawn a background task sweeping provider health on the configured
+    /// schedule, starting with a sweep right away.
+    ///
+    /// Returns `None` if no health sweep is configured.
+    pub fn spawn_health_sweeper(&self) -> Option<JoinHandle<()>> {
+        let policy = self.config.health_sweep.clone()?;
+        let orchestrator = self.clone();
+
+        Some(tokio::spawn(async move {
+            loop {
+                match orchestrator.sweep_provider_health(&policy).await {
+                    Ok(report) => info!("Health sweep: {}", report.summary()),
+                    Err(e) => warn!("Skipped health sweep: {}", e),
+                }
+                tokio::time::sleep(policy.interval).await;
+            }
+        }))
+    }
+
+    /// Probe every usable provider once, as `policy` says, and record in the
+    /// router which ones answered, so routing knows which providers are up
+    /// before real requests arrive.
+    ///
+    /// Probes are not charged to tenants, audited, or counted as activity
+    /// that keeps browsers signed in. Ping probes don't wait out rate
+    /// limits: a provider that is rate limited is left out of the sweep.
+    /// Nothing is probed in replay mode.
+    pub async fn sweep_provider_health(&self, policy: &HealthSweepPolicy) -> Result<SweepReport> {
+        self.ensure_not_in_maintenance()?;
+        let mut report = SweepReport::new(policy.probe);
+        if self.config.replay == ReplayMode::Replay {
+            return Ok(report);
+        }
+
+        let providers = self.router.read().await.usable_providers();
+        for provider in providers {
+            if policy.probe == ProbeKind::Ping {
+                let acquired = self.router.read().await.acquire(provider, RequestClass::Background);
+                if acquired.is_err() {
+                    continue;
+                }
+            }
+            let start = Instant::now();
+            let probe = self.probe_provider(provider, policy);
+            let result = match tokio::time::timeout(self.config.timeout, probe).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::Timeout(format!(
+                    "{} probe not answered within {}s",
+                    provider,
+                    self.config.timeout.as_secs()
+                ))),
+            };
+            let elapsed = start.elapsed();
+
+            let mut router = self.router.write().await;
+            match &result {
+                Ok(()) => {
+                    let latency = (policy.probe == ProbeKind::Ping).then_some(elapsed);
+                    router.record_probe_success(provider, latency);
+                }
+                Err(e) => {
+                    warn!("Health probe of {} failed: {}", provider, e);
+                    router.record_probe_failure(provider, e);
+                }
+            }
+            report.results.push(ProbeResult {
+                provider: provider.to_string(),
+                ok: result.is_ok(),
+                duration_ms: elapsed.as_millis() as u64,
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+        Ok(report)
+    }
+
+    /// Run the startup self-test: check that state can be stored, that a
+    /// browser starts (if any provider needs one), that configured API keys
+    /// and account profiles are present, and that at least one provider
+    /// answers a health probe, recording the probes like a health sweep.
+    ///
+    /// Checks that fail say how to fix them. Each check gets
+    /// `SELF_TEST_TIMEOUT` to finish.
+    pub async fn self_test(&self) -> Diagnostics {
+        const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+        async fn timed(name: &str, check: impl std::future::Future<Output = Check>) -> Check {
+            tokio::time::timeout(SELF_TEST_TIMEOUT, check)
+                .await
+                .unwrap_or_else(|_| {
+                    Check::failed(
+                        name,
+                        format!("no answer within {}s", SELF_TEST_TIMEOUT.as_secs()),
+                        "check the network and browser, then restart the server",
+                    )
+                })
+        }
+
+        let mut checks = vec![self.check_storage()];
+        checks.extend(self.check_credentials());
+        checks.push(timed("browser", self.check_browser()).await);
+        checks.push(timed("providers", self.check_providers()).await);
+        let diagnostics = Diagnostics::new(checks);
+        for check in diagnostics.problems() {
+            warn!("Self-test: {}", check.line());
+        }
+        diagnostics
+    }
+
+    /// Check that state can be written and read back.
+    fn check_storage(&self) -> Check {
+        const NAME: &str = "storage";
+        const KEY: &str = "probe";
+        let written = chrono::Utc::now().to_rfc3339();
+        let result = self
+            .diagnostics_store
+            .save(KEY, &written)
+            .and_then(|_| self.diagnostics_store.load::<String>(KEY));
+        self.diagnostics_store.remove(KEY).ok();
+        let fix = "check that the state directory (--state-dir) exists and is writable";
+        match result {
+            Ok(Some(read)) if read == written => Check::ok(NAME, "state is writable"),
+            Ok(_) => Check::failed(NAME, "a record written was not read back", fix),
+            Err(e) => Check::failed(NAME, format!("cannot write state: {}", e), fix),
+        }
+    }
+
+    /// Check that configured API keys and account profiles are present.
+    fn check_credentials(&self) -> Vec<Check> {
+        const NAME: &str = "credentials";
+        let mut checks = Vec::new();
+        #[cfg(feature = "api-providers")]
+        for (name, api) in &self.config.api_providers {
+            if api.api_key.is_none() && std::env::var(api.api_key_env()).is_err() {
+                checks.push(Check::failed(
+                    NAME,
+                    format!("no API key for {}", name),
+                    format!("set {} or api_key in the API provider file", api.api_key_env()),
+                ));
+            }
+        }
+        let mut accounts: Vec<_> = self.config.accounts.accounts.iter().collect();
+        accounts.sort_by_key(|(name, _)| *name);
+        for (name, account) in accounts {
+            if !account.profile_dir.exists() {
+                checks.push(Check::warning(
+                    NAME,
+                    format!(
+                        "profile of the {} account not found at {}",
+                        name,
+                        account.profile_dir.display()
+                    ),
+                    "run once with --visible and sign in to create it",
+                ));
+            }
+        }
+        if checks.is_empty() {
+            checks.push(Check::ok(NAME, "configured API keys and account profiles present"));
+        }
+        checks
+    }
+
+    /// Check that a browser starts, if a usable provider needs one. The
+    /// browser is kept for the first prompts.
+    async fn check_browser(&self) -> Check {
+        const NAME: &str = "browser";
+        if self.config.mock_providers.is_some() || self.config.replay == ReplayMode::Replay {
+            return Check::skipped(NAME, "providers are mocked or replayed");
+        }
+        let usable = self.router.read().await.usable_providers();
+        let Some(provider) = usable.into_iter().find(|p| self.backends.get(*p).is_none()) else {
+            return Check::skipped(NAME, "every usable provider has an API backend");
+        };
+        match self.get_puppet(provider).await {
+            Ok(_) => Check::ok(NAME, format!("browser started for {}", provider)),
+            Err(e) => Check::failed(
+                NAME,
+                format!("browser failed to start: {}", e),
+                "install Chrome or Chromium, or configure API providers (--api-providers)",
+            ),
+        }
+    }
+
+    /// Check that at least one provider answers a health probe, as the
+    /// configured health sweep probes them (an auth check by default).
+    async fn check_providers(&self) -> Check {
+        const NAME: &str = "providers";
+        let policy = self.config.health_sweep.clone().unwrap_or_default();
+        let report = match self.sweep_provider_health(&policy).await {
+            Ok(report) => report,
+            Err(e) => {
+                return Check::warning(
+                    NAME,
+                    format!("not probed: {}", e),
+                    "leave maintenance mode to use providers",
+                )
+            }
+        };
+        let up = report.results.iter().filter(|r| r.ok).count();
+        if report.results.is_empty() {
+            Check::failed(
+                NAME,
+                "no provider may be used",
+                "enable a provider with agent_config, or leave local-only mode",
+            )
+        } else if up == 0 {
+            Check::failed(
+                NAME,
+                report.summary(),
+                "run with --visible and sign in to a provider, or configure API providers",
+            )
+        } else if report.failures().next().is_some() {
+            Check::warning(
+                NAME,
+                report.summary(),
+                "sign in to the failing providers with --visible",
+            )
+        } else {
+            Check::ok(NAME, report.summary())
+        }
+    }
+
+    /// Probe one provider: check its sign-in, then send the ping prompt if
+    /// `policy` asks for one.
+    async fn probe_provider(&self, provider: Provider, policy: &HealthSweepPolicy) -> Result<()> {
+        // Browser probes start a browser of their own, closed below, rather
+        // than a pooled one that would stay signed in after an idle logout
+        let session = match self.backend_session(provider) {
+            Some(session) => session,
+            None => {
+                let context = self.config.browser_pool_size.max(1);
+                let puppet = self
+                    .launch_puppet(provider, self.config.headless, context)
+                    .await?;
+                ProviderSession::Browser(Box::new(puppet))
+            }
+        };
+        let result = async {
+            session.authenticate(provider).await?;
+            if policy.probe == ProbeKind::Ping {
+                let request = PromptRequest::new(policy.ping_prompt.clone());
+                session.prompt(provider, request).await?;
+            }
+            Ok(())
+        }
+        .await;
+        session.close().await.ok();
+        result
+    }
+
+    /// Spawn a background worker that runs queued jobs one at a time.
+    ///
+    /// The worker renews its lease on a job while running it and abandons
+    /// the job if it is cancelled or its lease is lost. No jobs are claimed
+    /// while auto runs are paused, and a job whose run pauses goes back in
+    /// the queue.
+    pub fn spawn_job_worker(&self) -> JoinHandle<()> {
+        const IDLE_POLL: Duration = Duration::from_secs(2);
+        let orchestrator = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                if orchestrator.pause_reason().is_some() {
+                    tokio::time::sleep(IDLE_POLL).await;
+                    continue;
+                }
+                match orchestrator.jobs.claim() {
+                    Ok(Some(job)) => orchestrator.process_job(job).await,
+                    Ok(None) => tokio::time::sleep(IDLE_POLL).await,
+                    Err(e) => {
+                        warn!("Failed to claim a job: {}", e);
+                        tokio::time::sleep(IDLE_POLL).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Run a claimed job, renewing its lease until it finishes.
+    async fn process_job(&self, job: Job) {
+        info!("Running job {}: {} (attempt {})", job.id, job.kind.describe(), job.attempts);
+        let renew_every = (self.jobs.lease() / 3).max(Duration::from_secs(1));
+        let keep_lease = async {
+            loop {
+                tokio::time::sleep(renew_every).await;
+                match self.jobs.renew(&job.id) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(e) => warn!("Failed to renew lease on job {}: {}", job.id, e),
+                }
+            }
+        };
+
+        let finished = tokio::select! {
+            result = self.run_job(&job.kind) => result,
+            _ = keep_lease => {
+                info!("Job {} was cancelled or taken over, stopping", job.id);
+                return;
+            }
+        };
+        let (recorded, result) = match finished {
+            Ok(Some((summary, result))) => (self.jobs.complete(&job.id, summary), Some(result)),
+            Ok(None) => (self.jobs.requeue(&job.id), None),
+            Err(e) => {
+                warn!("Job {} failed: {}", job.id, e);
+                (self.jobs.fail(&job.id, e.to_string()), None)
+            }
+        };
+        let updated = match recorded {
+            Ok(updated) => updated,
+            Err(e) => {
+                warn!("Failed to record the result of job {}: {}", job.id, e);
+                return;
+            }
+        };
+
+        // Delivery is retried with backoff, so it runs apart from the worker.
+        if let Some(job) = updated.filter(|j| j.state.is_finished()) {
+            if let Some(url) = job.callback_url.clone() {
+                let JobKind::AutoRun { classification, .. } = &job.kind;
+                let classification = self.guard.classify(*classification);
+                let webhooks = self.webhooks.clone();
+                let payload = WebhookPayload::new(&job, result);
+                tokio::spawn(async move {
+                    if let Err(e) = webhooks.deliver(&url, classification, &payload).await {
+                        warn!("{}", e);
+                    }
+                });
+            }
+        }
+    }
+
+    /// Perform a job's work, returning a summary and the full result, or
+    /// `None` if the work was paused and should run again once resumed.
+    async fn run_job(&self, kind: &JobKind) -> Result<Option<(String, serde_json::Value)>> {
+        match kind {
+            JobKind::AutoRun {
+                workflow_id,
+                max_cost_usd,
+                synthesizer,
+                classification,
+                visible,
+                tenant,
+            } => {
+                let orchestrator = match tenant {
+                    Some(tenant) => self.for_tenant(tenant.clone()),
+                    None => self.clone(),
+                }
+                .in_background();
+                let auto = AutoOptions {
+                    max_cost_usd: *max_cost_usd,
+                    synthesizer: synthesizer.as_deref().and_then(Provider::from_string),
+                    ..Default::default()
+                };
+                let options = PromptOptions {
+                    classification: *classification,
+                    visible: *visible,
+                    ..Default::default()
+                };
+                let report = orchestrator.auto_run(workflow_id, &auto, options).await?;
+                let summary = match &report.outcome {
+                    AutoOutcome::Completed => "completed".to_string(),
+                    AutoOutcome::AwaitingApproval { reason } => {
+                        self.emit_review_required(workflow_id, reason).await;
+                        format!("paused for approval: {}", reason)
+                    }
+                    AutoOutcome::OverBudget { next_step_usd } => {
+                        format!("stopped over budget (next step ${:.4})", next_step_usd)
+                    }
+                    AutoOutcome::Failed { reason } => format!("workflow failed: {}", reason),
+                    AutoOutcome::Paused { reason } => {
+                        info!("Auto run of workflow {} paused: {}", workflow_id, reason);
+                        return Ok(None);
+                    }
+                };
+                Ok(Some((summary, serde_json::to_value(&report)?)))
+            }
+        }
+    }
+
+    /// Publish that a background workflow is waiting on a reviewer. A review
+    /// step's own prompt is what the reviewer is asked; an approval pause
+    /// gives the policy's reason.
+    async fn emit_review_required(&self, workflow_id: &str, reason: &str) {
+        let workflows = self.workflows.read().await;
+        let Some(workflow) = workflows.get(workflow_id) else {
+            return;
+        };
+        let Some(step) = workflow.current() else {
+            return;
+        };
+        let reason = match &step.config {
+            StepConfig::HumanReview { prompt } => prompt.clone(),
+            _ => reason.to_string(),
+        };
+        self.events.emit(OrchestratorEvent::ReviewRequired {
+            workflow_id: workflow_id.to_string(),
+            workflow_name: workflow.name.clone(),
+            step_name: step.name.clone(),
+            reason,
+        });
+    }
+
+    /// Send a prompt to the best available provider.
+    pub async fn prompt(&self, message: impl Into<String>) -> Result<PromptResponse> {
+        self.prompt_with(message, PromptOptions::default()).await
+    }
+
+    /// Send a prompt to the best available provider with options.
+    pub async fn prompt_with(
+        &self,
+        message: impl Into<String>,
+        options: PromptOptions,
+    ) -> Result<PromptResponse> {
+        let message = message.into();
+        let classification = self.guard.classify(options.classification);
+        let language = detect_language(&message);
+        let tokens = metadata::prompt_tokens(&options.request(&message));
+        let task = TaskType::General.refine(&message, tokens);
+
+        let arm = self
+            .canary
+            .as_ref()
+            .map_or(CanaryArm::Baseline, |c| c.assign());
+        let routed = LedgerEntry {
+            id: String::new(),
+            at: chrono::Utc::now(),
+            tenant: self.tenant().to_string(),
+            task: task.name().to_string(),
+            language: language.map(String::from),
+            prompt_tokens: tokens,
+            exchange_tokens: 0,
+            classification,
+            routing: options.routing.clone(),
+            provider: String::new(),
+            latency_ms: 0,
+            cost_usd: 0.0,
+            error: None,
+        };
+
+        // A prompt the provider declines is sent to the next best provider,
+        // up to the refusal policy's reroutes. One it fails for a reason
+        // another provider may not share falls back the same way.
+        let mut refused = Vec::new();
+        let mut failed = Vec::new();
+        let mut last_error = None;
+        let (provider, start, mut response) = loop {
+            let eligible = |p| {
+                !refused.contains(&p)
+                    && !failed.contains(&p)
+                    && context_window(p) >= tokens
+                    && self.guard.is_allowed(classification, p)
+            };
+            let priorities = match (&self.canary, arm) {
+                (Some(canary), CanaryArm::Canary) => Some(&canary.policy().priorities),
+                _ => None,
+            };
+            let router = self.router.read().await;
+            let selected = router
+                .weights_for(&task, options.routing.as_ref())
+                .and_then(|weights| {
+                    let task = task.clone();
+                    router.select_best_weighted(task, language, priorities, &weights, eligible)
+                });
+            drop(router);
+            let provider = match (selected, last_error.take()) {
+                (Ok(provider), _) => provider,
+                // Every other provider was tried or may not take the prompt
+                (Err(_), Some(error)) => return Err(error),
+                (Err(Error::NoProviders(_)), None) => {
+                    return Err(Error::PermissionDenied(format!(
+                        "no available provider may receive {} data",
+                        classification
+                    )))
+                }
+                (Err(e), None) => return Err(e),
+            };
+            self.emit_routing(&task, language, tokens, &[provider]);
+
+            let start = Instant::now();
+            let response = self
+                .prompt_provider_with(provider, message.clone(), options.clone())
+                .await;
+            if let Some(canary) = &self.canary {
+                self.record_canary(canary, arm, provider, &response);
+            }
+            self.record_request(routed.clone(), provider, start.elapsed(), &response);
+            match response {
+                Err(e @ Error::ContentRefused { .. })
+                    if refused.len() < self.config.refusal_policy.reroutes as usize =>
+                {
+                    info!("Rerouting prompt declined by {}: {}", provider, e);
+                    refused.push(provider);
+                    last_error = Some(e);
+                }
+                Err(e)
+                    if e.is_fallback_candidate() && !matches!(e, Error::ContentRefused { .. }) =>
+                {
+                    warn!("Falling back from {}: {}", provider, e);
+                    failed.push(provider);
+                    last_error = Some(e);
+                }
+                response => break (provider, start, response?),
+            }
+        };
+        if !refused.is_empty() {
+            let refused: Vec<String> = refused.iter().map(|p| p.to_string()).collect();
+            response
+                .metadata
+                .insert(REFUSED_BY_KEY.into(), refused.join(","));
+        }
+        if let Some(policy) = &self.config.shadow {
+            if let Some(challenger) = self.shadow.challenger_for(policy, provider) {
+                if self.guard.is_allowed(classification, challenger) {
+                    let orchestrator = self.clone();
+                    let policy = policy.clone();
+                    let champion = response.clone();
+                    let latency = start.elapsed();
+                    tokio::spawn(async move {
+                        orchestrator
+                            .shadow_prompt(&policy, challenger, message, options, champion, latency)
+                            .await;
+                    });
+                }
+            }
+        }
+        Ok(response)
+    }
+
+    /// Send a prompt to the fastest healthy providers at once and return the
+    /// first acceptable answer, cancelling the other calls. Every call is
+    /// charged; see [`crate::race`].
+    pub async fn race_prompt(
+        &self,
+        message: impl Into<String>,
+        options: PromptOptions,
+    ) -> Result<PromptResponse> {
+        let message = message.into();
+        self.ensure_not_in_maintenance()?;
+        let classification = self.guard.classify(options.classification);
+        let language = detect_language(&message);
+        let tokens = metadata::prompt_tokens(&options.request(&message));
+        let task = TaskType::General.refine(&message, tokens);
+        let contenders = self.router.read().await.fastest_providers(RACE_WIDTH, |p| {
+            context_window(p) >= tokens && self.guard.is_allowed(classification, p)
+        });
+        match contenders.as_slice() {
+            [] => {
+                return Err(Error::NoProviders(format!(
+                    "no available provider may receive {} data",
+                    classification
+                )))
+            }
+            [provider] => return self.prompt_provider_with(*provider, message, options).await,
+            _ => {}
+        }
+        self.emit_routing(&task, language, tokens, &contenders);
+
+        let routed = LedgerEntry {
+            id: String::new(),
+            at: chrono::Utc::now(),
+            tenant: self.tenant().to_string(),
+            task: task.name().to_string(),
+            language: language.map(String::from),
+            prompt_tokens: tokens,
+            exchange_tokens: 0,
+            classification,
+            routing: options.routing.clone(),
+            provider: String::new(),
+            latency_ms: 0,
+            cost_usd: 0.0,
+            error: None,
+        };
+        let start = Instant::now();
+        // Each contender runs on a handle recording whether it got as far as
+        // sending, rather than waiting for a slot
+        let dispatched: Vec<Arc<AtomicBool>> =
+            contenders.iter().map(|_| Arc::new(AtomicBool::new(false))).collect();
+        let mut calls: FuturesUnordered<_> = contenders
+            .iter()
+            .zip(&dispatched)
+            .map(|(&provider, dispatched)| {
+                let mut contender = self.clone();
+                contender.dispatched = Some(dispatched.clone());
+                let (message, options) = (message.clone(), options.clone());
+                async move {
+                    let response = contender
+                        .prompt_provider_with(provider, message, options)
+                        .await;
+                    (provider, response)
+                }
+            })
+            .collect();
+        let mut failures = Vec::new();
+        let mut finished = Vec::new();
+        while let Some((provider, response)) = calls.next().await {
+            self.record_request(routed.clone(), provider, start.elapsed(), &response);
+            finished.push(provider);
+            match response {
+                Ok(mut response) if race::is_acceptable(&response) => {
+                    // Dropping the remaining calls cancels them. Those already
+                    // sent are charged as estimated; those still queued were
+                    // never sent and cost nothing
+                    drop(calls);
+                    let request = options.request(&message);
+                    let sent = contenders.iter().zip(&dispatched).filter(|(p, dispatched)| {
+                        !finished.contains(p) && dispatched.load(Ordering::SeqCst)
+                    });
+                    for (&loser, _) in sent {
+                        self.record_cancelled(routed.clone(), loser, &request, start.elapsed());
+                    }
+                    let others: Vec<String> = contenders
+                        .iter()
+                        .filter(|p| **p != provider)
+                        .map(|p| p.to_string())
+                        .collect();
+                    response
+                        .metadata
+                        .insert(RACED_AGAINST_KEY.into(), others.join(","));
+                    return Ok(response);
+                }
+                Ok(_) => failures.push(format!("{}: empty or incomplete answer", provider)),
+                Err(e) => failures.push(format!("{}: {}", provider, e)),
+            }
+        }
+        Err(Error::NoProviders(format!(
+            "every raced provider failed ({})",
+            failures.join("; ")
+        )))
+    }
+
+    /// Count a routed prompt's outcome towards the canary, rolling it back
+    /// if it regressed. Refusals by policy, not by the provider, are not
+    /// counted.
+    fn record_canary(
+        &self,
+        canary: &CanaryRollout,
+        arm: CanaryArm,
+        provider: Provider,
+        response: &Result<PromptResponse>,
+    ) {
+        let cost = match response {
+            Ok(response) => self.config.cost_model.estimate(
+                Some(&provider.to_string()),
+                metadata::exchange_tokens(response),
+            ),
+            Err(
+                Error::BudgetExceeded(_) | Error::PermissionDenied(_) | Error::InvalidParams(_),
+            ) => return,
+            Err(_) => 0.0,
+        };
+        match canary.record(arm, response.is_ok(), cost) {
+            Ok(Some(reason)) => {
+                warn!("Rolled back routing canary: {}", reason);
+                self.events
+                    .emit(OrchestratorEvent::CanaryRolledBack { reason });
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to record canary outcome: {}", e),
+        }
+    }
+
+    /// Add a routed prompt's outcome to the request ledger. Refusals by
+    /// policy, not by the provider, are not recorded.
+    fn record_request(
+        &self,
+        mut entry: LedgerEntry,
+        provider: Provider,
+        latency: Duration,
+        response: &Result<PromptResponse>,
+    ) {
+        match response {
+            Ok(response) => {
+                entry.exchange_tokens = metadata::exchange_tokens(response);
+                entry.cost_usd = self
+                    .config
+                    .cost_model
+                    .estimate(Some(&provider.to_string()), entry.exchange_tokens);
+            }
+            Err(
+                Error::BudgetExceeded(_) | Error::PermissionDenied(_) | Error::InvalidParams(_),
+            ) => return,
+            Err(e) => entry.error = Some(e.kind().to_string()),
+        }
+        entry.id = uuid::Uuid::new_v4().to_string();
+        entry.provider = provider.to_string();
+        entry.latency_ms = latency.as_millis() as u64;
+        if let Err(e) = self.requests.record(&entry) {
+            warn!("Failed to record request in the ledger: {}", e);
+        }
+    }
+
+    /// Project what `proposal` would have done to the routed requests in
+    /// the ledger since `since`: each is ranked again under the proposed
+    /// profiles and preferences, with the router's current provider health
+    /// and statistics. Tenants other than the local one replay only their
+    /// own requests.
+    pub async fn replay_routing(
+        &self,
+        proposal: &RoutingProposal,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<RoutingProjection> {
+        proposal.validate()?;
+        let mut entries = self.requests.list(since)?;
+        if self.tenant() != DEFAULT_TENANT {
+            entries.retain(|e| e.tenant == self.tenant());
+        }
+
+        let router = self.router.read().await;
+        let preferences = proposal.preferences(router.preferences());
+        let profiles = proposal
+            .routing_profiles
+            .clone()
+            .unwrap_or_else(|| router.profiles().clone());
+        let choose = |entry: &LedgerEntry| {
+            let task = TaskType::from_name(&entry.task).unwrap_or(TaskType::General);
+            // A profile the proposal drops falls back to the task's profile
+            let weights = profiles
+                .weights(task.name(), entry.routing.as_ref())
+                .or_else(|_| profiles.weights(task.name(), None))
+                .ok()?;
+            let eligible = |p| {
+                context_window(p) >= entry.prompt_tokens
+                    && self.guard.is_allowed(entry.classification, p)
+            };
+            let language = entry.language.as_deref();
+            let ranked = router.rank_with(&preferences, &task, language, None, &weights, eligible);
+            ranked.first().map(|r| r.provider)
+        };
+        Ok(RoutingProjection::project(
+            &entries,
+            since,
+            &self.config.cost_model,
+            choose,
+        ))
+    }
+
+    /// Get the routing canary, if one is configured.
+    pub fn canary(&self) -> Option<&CanaryRollout> {
+        self.canary.as_ref()
+    }
+
+    /// Have a challenger answer a prompt the champion already answered, and
+    /// record how a judge grades the two answers.
+    ///
+    /// Runs in the background; failures are logged, never returned, and the
+    /// grades do not feed the router's quality scores.
+    async fn shadow_prompt(
+        &self,
+        policy: &ShadowPolicy,
+        challenger: Provider,
+        message: String,
+        options: PromptOptions,
+        champion: PromptResponse,
+        champion_latency: Duration,
+    ) {
+        let options = PromptOptions {
+            visible: false,
+            ..options
+        };
+        let start = Instant::now();
+        let answer = self
+            .prompt_provider_with(challenger, message.clone(), options.clone())
+            .await;
+        let challenger_latency = start.elapsed();
+
+        let grading = policy.grading();
+        let classification = options.classification;
+        let score = |text: String| {
+            let grading = &grading;
+            let message = &message;
+            async move {
+                match self.judge(grading, message, &text, None, classification).await {
+                    Ok((score, _)) => Some(score),
+                    Err(e) => {
+                        warn!("Failed to grade shadow answer: {}", e);
+                        None
+                    }
+                }
+            }
+        };
+        let champion_score = score(champion.text).await;
+        let (challenger_score, challenger_error) = match answer {
+            Ok(response) => (score(response.text).await, None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let comparison = ShadowComparison {
+            id: uuid::Uuid::new_v4().to_string(),
+            prompt: message,
+            champion: champion.provider.to_string(),
+            challenger: challenger.to_string(),
+            champion_score,
+            challenger_score,
+            champion_latency_ms: champion_latency.as_millis() as u64,
+            challenger_latency_ms: challenger_latency.as_millis() as u64,
+            challenger_error,
+            created_at: chrono::Utc::now(),
+        };
+        match self.shadow.record(&comparison) {
+            Ok(()) => info!(
+                "Shadowed {} with {}: winner {}",
+                comparison.champion,
+                comparison.challenger,
+                comparison.winner().unwrap_or("none")
+            ),
+            Err(e) => warn!("Failed to record shadow comparison: {}", e),
+        }
+    }
+
+    /// Get the champion/challenger shadow comparisons.
+    pub fn shadow(&self) -> &ShadowStore {
+        &self.shadow
+    }
+
+    /// Publish the providers the router chose for a prompt.
+    /// Route a prompt under every routing profile, the call's own weights,
+    /// and the canary's priorities without sending it, reporting which
+    /// provider each would choose and why. `task` overrides the task type
+    /// refined from the prompt.
+    pub async fn simulate_routing(
+        &self,
+        message: &str,
+        task: Option<TaskType>,
+        options: &PromptOptions,
+    ) -> Result<RoutingSimulation> {
+        let classification = self.guard.classify(options.classification);
+        let language = detect_language(message);
+        let tokens = metadata::prompt_tokens(&options.request(message));
+        let task = task.unwrap_or_else(|| TaskType::General.refine(message, tokens));
+        let eligible =
+            |p| context_window(p) >= tokens && self.guard.is_allowed(classification, p);
+
+        let router = self.router.read().await;
+        let weights = router.weights_for(&task, options.routing.as_ref())?;
+        let profiles = router.profiles();
+        let used = match &options.routing {
+            Some(RoutingChoice::Profile(name)) => name.as_str(),
+            Some(RoutingChoice::Weights(_)) => simulate::CUSTOM,
+            None => profiles.profile_name(task.name()),
+        };
+        let strategy = |name: String,
+                        weights: ObjectiveWeights,
+                        priorities: Option<&BTreeMap<String, u32>>| SimulatedStrategy {
+            ranking: router.rank(&task, language, priorities, &weights, eligible),
+            used: name == used,
+            name,
+            weights,
+        };
+        let mut strategies: Vec<SimulatedStrategy> = profiles
+            .names()
+            .into_iter()
+            .filter_map(|name| {
+                let weights = profiles.profile(&name)?;
+                Some(strategy(name, weights, None))
+            })
+            .collect();
+        if let Some(RoutingChoice::Weights(weights)) = &options.routing {
+            strategies.push(strategy(simulate::CUSTOM.into(), *weights, None));
+        }
+        if let Some(canary) = &self.canary {
+            let priorities = Some(&canary.policy().priorities);
+            strategies.push(strategy(simulate::CANARY.into(), weights, priorities));
+        }
+        strategies.sort_by_key(|s| !s.used);
+
+        let excluded = Provider::all()
+            .into_iter()
+            .filter_map(|p| {
+                let reason = if router.is_local_only() && !is_local_provider(p) {
+                    "local-only mode".to_string()
+                } else if router.preferences().is_disabled(p) {
+                    "disabled".to_string()
+                } else if !router.is_healthy(p) {
+                    "unhealthy after recent failures or rate limits".to_string()
+                } else if context_window(p) < tokens {
+                    format!("context window of {} tokens is too small", context_window(p))
+                } else if !self.guard.is_allowed(classification, p) {
+                    format!("may not receive {} data", classification)
+                } else {
+                    return None;
+                };
+                Some((p, reason))
+            })
+            .collect();
+
+        Ok(RoutingSimulation {
+            task,
+            language: language.map(String::from),
+            prompt_tokens: tokens,
+            classification,
+            strategies,
+            excluded,
+        })
+    }
+
+    fn emit_routing(
+        &self,
+        task: &TaskType,
+        language: Option<&str>,
+        prompt_tokens: u64,
+        providers: &[Provider],
+    ) {
+        self.events.emit(OrchestratorEvent::RoutingDecision {
+            task: task.name().into(),
+            language: language.map(String::from),
+            prompt_tokens,
+            providers: providers.iter().map(|p| p.to_string()).collect(),
+        });
+    }
+
+    /// Send a prompt to a specific provider.
+    pub async fn prompt_provider(
+        &self,
+        provider: Provider,
+        message: impl Into<String>,
+    ) -> Result<PromptResponse> {
+        self.prompt_provider_with(provider, message, PromptOptions::default())
+            .await
+    }
+
+    /// Send a prompt to a specific provider with options.
+    pub async fn prompt_provider_with(
+        &self,
+        provider: Provider,
+        message: impl Into<String>,
+        options: PromptOptions,
+    ) -> Result<PromptResponse> {
+        let message = message.into();
+        self.ensure_not_in_maintenance()?;
+        let classification = self.guard.classify(options.classification);
+        self.router.read().await.ensure_usable(provider)?;
+        self.guard.check(classification, provider)?;
+        self.audit_dispatch(provider, classification, &message)?;
+        self.charge_tenant(provider, &options.request(&message))?;
+        self.touch().await;
+
+        let start = Instant::now();
+
+        let _slot = self.fair_slot(provider).await?;
+        let mut puppet = self.get_puppet_with(provider, self.headless_for(&options)).await?;
+        let mut reconnected = false;
+        let mut resent = 0;
+        let mut rephrased = false;
+        let mut request = options.request(&message);
+
+        // Authenticate if needed, then send prompt, waiting out rate limits
+        // (including cooldowns and quotas shared with other instances) that
+        // clear before the request deadline, or whenever they clear for
+        // background work. A long-lived browser that fails is replaced and
+        // the prompt tried once more, an empty answer is asked for again up
+        // to `incomplete_retries` times, and a declined prompt is sent once
+        // more rephrased, if the provider's adapter can rephrase it.
+        let result = loop {
+            let acquired = self.router.read().await.acquire(provider, self.request_class);
+            let result = match acquired {
+                Ok(()) => {
+                    let attempt = Instant::now();
+                    let request = request.clone();
+                    let result = match puppet.authenticate(provider).await {
+                        Ok(_) => self.dispatch(&puppet, provider, request, start).await,
+                        Err(e) => Err(e),
+                    };
+                    // Record every attempt, so other instances back off while
+                    // this one waits out a rate limit
+                    self.record_result(provider, &result, attempt.elapsed()).await;
+                    result
+                }
+                Err(e) => Err(e),
+            };
+            if self.close_failed_puppet(provider, &mut puppet, &result).await && !reconnected {
+                reconnected = true;
+                self.launch_if_closed(provider, &mut puppet).await?;
+                continue;
+            }
+            if let Err(Error::IncompleteResponse { reason, .. }) = &result {
+                if resent < self.config.incomplete_retries {
+                    resent += 1;
+                    warn!("Incomplete response from {} ({}), asking again", provider, reason);
+                    continue;
+                }
+            }
+            if let Err(Error::ContentRefused { reason, .. }) = &result {
+                let adapters = &self.config.prompt_adapters;
+                let next = (self.config.refusal_policy.rephrase && !rephrased)
+                    .then(|| adapters.rephrase(provider, request.clone()))
+                    .flatten();
+                if let Some(next) = next {
+                    info!("{} declined the prompt ({}), rephrasing it", provider, reason);
+                    rephrased = true;
+                    request = next;
+                    continue;
+                }
+            }
+            match result.as_ref().err().and_then(|e| self.rate_limit_wait(e, start)) {
+                Some(wait) => {
+                    info!("{} rate limited, retrying in {:?}", provider, wait);
+                    tokio::time::sleep(wait).await;
+                }
+                None => break result,
+            }
+        };
+        let result = match result {
+            Ok(response) => {
+                Ok(self.continue_truncated(&puppet, provider, &request, response, start).await)
+            }
+            other => other,
+        };
+
+        // Cleanup
+        puppet.close().await.ok();
+
+        let result = match result {
+            Err(Error::Captcha { .. }) if self.config.intervention.enabled => {
+                self.await_intervention(provider, options.request(&message), start)
+                    .await
+            }
+            other => other,
+        };
+
+        result.map(|response| {
+            let response = self.sanitize_response(response);
+            self.sessions.record(&message, &response);
+            response
+        })
+    }
+
+    /// Ask `provider` to continue an answer to `request` that stops partway,
+    /// up to `max_continuations` times, stitching the parts together. An
+    /// answer still cut short is returned as it is, marked `incomplete`.
+    async fn continue_truncated(
+        &self,
+        puppet: &ProviderSession,
+        provider: Provider,
+        request: &PromptRequest,
+        mut response: PromptResponse,
+        start: Instant,
+    ) -> PromptResponse {
+        let mut continuations = 0;
+        while let Some(Incomplete::Truncated(reason)) = completeness::check(&response) {
+            let result = if continuations < self.config.max_continuations {
+                continuations += 1;
+                info!("{} answer {}, asking it to continue", provider, reason);
+                let acquired = self.router.read().await.acquire(provider, self.request_class);
+                match acquired {
+                    Ok(()) => {
+                        let attempt = Instant::now();
+                        let next = completeness::continuation(request, &response);
+                        let result = self.dispatch(puppet, provider, next, start).await;
+                        self.record_result(provider, &result, attempt.elapsed()).await;
+                        result
+                    }
+                    Err(e) => Err(e),
+                }
+            } else {
+                Err(Error::IncompleteResponse {
+                    provider: provider.to_string(),
+                    reason: format!("still incomplete after {} continuations", continuations),
+                })
+            };
+            match result {
+                Ok(more) => completeness::append(&mut response, more),
+                Err(e) => {
+                    warn!("Returning {} answer that {}: {}", provider, reason, e);
+                    response
+                        .metadata
+                        .insert(INCOMPLETE_KEY.into(), reason.into());
+                    break;
+                }
+            }
+        }
+        if continuations > 0 {
+            response
+                .metadata
+                .insert(CONTINUATIONS_KEY.into(), continuations.to_string());
+        }
+        response
+    }
+
+    /// Send a prompt, publishing request started and finished events around
+    /// it. Waits first while `max_concurrent` calls are already being sent.
+    async fn dispatch(
+        &self,
+        puppet: &ProviderSession,
+        provider: Provider,
+        request: PromptRequest,
+        queued_at: Instant,
+    ) -> Result<PromptResponse> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let _in_flight = InFlight(&self.in_flight);
+        let _permit = self
+            .provider_calls
+            .acquire()
+            .await
+            .map_err(|_| Error::Internal("provider calls closed".into()))?;
+        if let Some(dispatched) = &self.dispatched {
+            dispatched.store(true, Ordering::SeqCst);
+        }
+        let request_id = uuid::Uuid::new_v4().to_string();
+        self.events.emit(OrchestratorEvent::RequestStarted {
+            request_id: request_id.clone(),
+            provider: provider.to_string(),
+            prompt_tokens: metadata::prompt_tokens(&request),
+        });
+        let sent = Instant::now();
+        let result = self.send_prompt(puppet, provider, request, queued_at).await;
+        self.events.emit(OrchestratorEvent::RequestFinished {
+            request_id,
+            provider: provider.to_string(),
+            duration_ms: sent.elapsed().as_millis() as u64,
+            error: result.as_ref().err().map(|e| e.kind().to_string()),
+        });
+        if let Ok(response) = &result {
+            self.record_tenant_usage(provider, response);
+        }
+        result
+    }
+
+    /// Wait for the tenant's turn on `provider` under fair-share
+    /// scheduling, holding the slot until the returned permit is dropped.
+    async fn fair_slot(&self, provider: Provider) -> Result<Option<FairPermit>> {
+        let (Some(scheduler), Some(policy)) = (&self.fair_share, &self.config.fair_share) else {
+            return Ok(None);
+        };
+        let share = self
+            .config
+            .tenants
+            .tenants
+            .get(self.tenant())
+            .and_then(|t| t.share)
+            .unwrap_or(1.0);
+        let slots = policy.slots_for(provider, self.config.browser_pool_size);
+        scheduler
+            .acquire(provider, self.tenant(), share, slots)
+            .await
+            .map(Some)
+    }
+
+    /// Reserve a request to a provider against the tenant's budget and
+    /// quota.
+    fn charge_tenant(&self, provider: Provider, request: &PromptRequest) -> Result<()> {
+        self.tenants
+            .acquire(self.tenant(), self.request_cost(provider, request))
+    }
+
+    /// Estimated cost of sending `request` to `provider`.
+    fn request_cost(&self, provider: Provider, request: &PromptRequest) -> f64 {
+        let costs = &self.config.cost_model;
+        match self.backends.get(provider) {
+            Some(backend) => backend.cost_estimate(provider, request, costs),
+            None => estimate_cost(provider, request, costs),
+        }
+    }
+
+    /// Charge a call cancelled after it was sent, such as the loser of a
+    /// raced prompt, at its estimated cost: to the tenant, the spend
+    /// history, and the request ledger.
+    fn record_cancelled(
+        &self,
+        mut entry: LedgerEntry,
+        provider: Provider,
+        request: &PromptRequest,
+        latency: Duration,
+    ) {
+        let cost = self.request_cost(provider, request);
+        let tokens = metadata::prompt_tokens(request);
+        if let Err(e) = self
+            .tenants
+            .record(self.tenant(), &provider.to_string(), tokens, 0, cost)
+        {
+            warn!("Failed to record usage for tenant {}: {}", self.tenant(), e);
+        }
+        self.record_spend(cost);
+        entry.id = uuid::Uuid::new_v4().to_string();
+        entry.provider = provider.to_string();
+        entry.latency_ms = latency.as_millis() as u64;
+        entry.exchange_tokens = tokens;
+        entry.cost_usd = cost;
+        entry.error = Some("cancelled".into());
+        if let Err(e) = self.requests.record(&entry) {
+            warn!("Failed to record request in the ledger: {}", e);
+        }
+    }
+
+    /// Attribute an answered request's tokens and estimated cost to the
+    /// tenant.
+    fn record_tenant_usage(&self, provider: Provider, response: &PromptResponse) {
+        let tokens = |key: &str| {
+            response
+                .metadata
+                .get(key)
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or_default()
+        };
+        let prompt_tokens = tokens(metadata::PROMPT_TOKENS_KEY);
+        let response_tokens = tokens(metadata::RESPONSE_TOKENS_KEY);
+        let provider = provider.to_string();
+        let cost = self
+            .config
+            .cost_model
+            .estimate(Some(&provider), prompt_tokens + response_tokens);
+        if let Err(e) =
+            self.tenants
+                .record(self.tenant(), &provider, prompt_tokens, response_tokens, cost)
+        {
+            warn!("Failed to record usage for tenant {}: {}", self.tenant(), e);
+        }
+        self.record_spend(cost);
+    }
+
+    /// Add an answered request's estimated cost to the spend history,
+    /// alerting on a cost anomaly it causes.
+    fn record_spend(&self, cost: f64) {
+        let Some(spend) = &self.spend else { return };
+        let anomaly = match spend.record(cost, chrono::Utc::now()) {
+            Ok(Some(anomaly)) => anomaly,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to record spend: {}", e);
+                return;
+            }
+        };
+        let paused_auto_runs = spend.policy().pause_auto_runs;
+        warn!(
+            "Cost anomaly: {}{}",
+            anomaly.describe(),
+            if paused_auto_runs { "; pausing auto runs" } else { "" }
+        );
+        self.events.emit(OrchestratorEvent::CostAnomaly {
+            window: anomaly.window.name().into(),
+            spent_usd: anomaly.spent_usd,
+            baseline_usd: anomaly.baseline_usd,
+            paused_auto_runs,
+        });
+    }
+
+    /// Send a prompt, recording model, token estimates, and timings in the
+    /// response metadata. `queued_at` is when the call started waiting.
+    async fn send_prompt(
+        &self,
+        puppet: &ProviderSession,
+        provider: Provider,
+        request: PromptRequest,
+        queued_at: Instant,
+    ) -> Result<PromptResponse> {
+        let capabilities = puppet.backend()?.capabilities();
+        let request = if capabilities.continuation {
+            request
+        } else {
+            completeness::restate(request)
+        };
+        let prompt_tokens = metadata::prompt_tokens(&request);
+        let language = detect_language(&request.message);
+        let recording = (self.config.replay == ReplayMode::Record).then(|| request.clone());
+        let sent = Instant::now();
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = &self.chaos {
+            if let Some(delay) = chaos.latency(provider) {
+                warn!("Chaos: delaying {} by {:?}", provider, delay);
+                tokio::time::sleep(delay).await;
+            }
+            if let Some(fault) = chaos.failure(provider) {
+                warn!("Chaos: failing {} call: {}", provider, fault);
+                return Err(Error::from(fault));
+            }
+        }
+        let system_prompt = capabilities.system_prompt;
+        let adapted = self.config.prompt_adapters.adapt(provider, request, system_prompt);
+        let mut response = puppet.prompt(provider, adapted).await?;
+        if let Some(request) = recording {
+            if let Err(e) = self.replay.record(provider, &request, &response) {
+                warn!("Failed to record {} response for replay: {}", provider, e);
+            }
+        }
+        #[cfg(feature = "chaos")]
+        if let Some(malformation) = self.chaos.as_ref().and_then(|c| c.malform(&mut response)) {
+            warn!("Chaos: malformed {} response ({:?})", provider, malformation);
+        }
+        completeness::ensure_answered(&response)?;
+        if self.config.refusal_policy.detect {
+            refusal::ensure_not_refused(&response)?;
+        }
+        let timing = ResponseTiming {
+            queue: sent - queued_at,
+            provider: sent.elapsed(),
+        };
+        metadata::enrich(&mut response, prompt_tokens, timing);
+        if let Some(language) = language {
+            response
+                .metadata
+                .insert(metadata::LANGUAGE_KEY.into(), language.into());
+        }
+        Ok(response)
+    }
+
+    /// How long to wait before retrying a rate-limited prompt started at
+    /// `start`, if the provider suggested a wait that ends before the
+    /// request deadline. Background work waits however long it takes.
+    fn rate_limit_wait(&self, error: &Error, start: Instant) -> Option<Duration> {
+        if !matches!(error, Error::RateLimited { .. }) {
+            return None;
+        }
+        let wait = Duration::from_secs(error.retry_after_secs()?.max(1));
+        let background = self.request_class == RequestClass::Background;
+        (background || start.elapsed() + wait < self.config.timeout).then_some(wait)
+    }
+
+    /// Park a prompt blocked by a captcha until the user solves it, then
+    /// retry it.
+    ///
+    /// Opens a visible browser on the provider if the policy asks for one and
+    /// notifies subscribers. Fails with `Captcha` if the user gives up, or
+    /// `Timeout` if nobody resolves the intervention in time.
+    async fn await_intervention(
+        &self,
+        provider: Provider,
+        request: PromptRequest,
+        queued_at: Instant,
+    ) -> Result<PromptResponse> {
+        let policy = &self.config.intervention;
+        let (intervention, resolved) = self
+            .interventions
+            .register(provider.to_string(), "solve the captcha or verification challenge");
+        warn!(
+            "{} requires human verification (intervention {})",
+            provider, intervention.id
+        );
+
+        let browser = if policy.show_browser {
+            match self.get_puppet_with(provider, false).await {
+                Ok(puppet) => {
+                    puppet.authenticate(provider).await.ok();
+                    Some(puppet)
+                }
+                Err(e) => {
+                    warn!("Failed to open a visible browser: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        self.events.emit(OrchestratorEvent::InterventionRequired {
+            id: intervention.id.clone(),
+            provider: intervention.provider.clone(),
+            reason: intervention.reason.clone(),
+            browser_visible: browser.is_some(),
+        });
+
+        let outcome =
+            tokio::time::timeout(Duration::from_secs(policy.timeout_secs), resolved).await;
+        self.interventions.remove(&intervention.id);
+        let solved = matches!(outcome, Ok(Ok(true)));
+        self.events.emit(OrchestratorEvent::InterventionResolved {
+            id: intervention.id,
+            provider: intervention.provider,
+            solved,
+        });
+
+        if !solved {
+            if let Some(puppet) = browser {
+                puppet.close().await.ok();
+            }
+            return Err(match outcome {
+                Err(_) => Error::Timeout(format!(
+                    "verification for {} not completed within {}s",
+                    provider, policy.timeout_secs
+                )),
+                Ok(_) => Error::Captcha {
+                    provider: provider.to_string(),
+                },
+            });
+        }
+
+        // Resume in the browser the user just verified in, if there is one
+        self.touch().await;
+        let start = Instant::now();
+        let puppet = match browser {
+            Some(puppet) => puppet,
+            None => self.get_puppet(provider).await?,
+        };
+        let result = self.dispatch(&puppet, provider, request, queued_at).await;
+        puppet.close().await.ok();
+
+        self.record_result(provider, &result, start.elapsed()).await;
+        result
+    }
+
+    /// Record a prompt's outcome in the router and persist the provider's
+    /// updated statistics.
+    async fn record_result(
+        &self,
+        provider: Provider,
+        result: &Result<PromptResponse>,
+        latency: Duration,
+    ) {
+        // Replayed answers say nothing about a provider's health or speed,
+        // and leaving the statistics alone keeps routing reproducible.
+        if self.config.replay == ReplayMode::Replay {
+            return;
+        }
+        let mut router = self.router.write().await;
+        let slo_latency = match result {
+            Ok(response) => {
+                router.record_success(provider, latency);
+                router.record_tokens(provider, metadata::exchange_tokens(response));
+                if let Some(model) = response.metadata.get(metadata::MODEL_KEY) {
+                    router.record_model(provider, model);
+                }
+                Some(Some(latency))
+            }
+            Err(e) => {
+                router.record_error(provider, e);
+                // Timeouts miss the objective; other failures say nothing
+                // about speed
+                matches!(e, Error::Timeout(_) | Error::NetworkTimeout(_)).then_some(None)
+            }
+        };
+        let change = slo_latency.and_then(|latency| router.record_slo(provider, latency));
+        let target_ms = router.latency_slo(provider).map_or(0, |slo| slo.target_ms);
+        let stats = router.get_stats().remove(&provider).unwrap_or_default();
+        drop(router);
+        self.persist_stats(provider, &stats);
+        if let Some(change) = change {
+            self.report_slo_change(provider, target_ms, change);
+        }
+    }
+
+    /// Log and emit a provider entering or leaving breach of its response
+    /// time objective.
+    fn report_slo_change(&self, provider: Provider, target_ms: u64, change: SloChange) {
+        let provider = provider.to_string();
+        let event = match change {
+            SloChange::Breached { misses, requests } => {
+                warn!(
+                    "{} missed its {}ms response time objective on {} of the last {} requests; \
+                     demoting it in routing",
+                    provider, target_ms, misses, requests
+                );
+                OrchestratorEvent::SloBreached {
+                    provider,
+                    target_ms,
+                    misses,
+                    requests,
+                }
+            }
+            SloChange::Recovered => {
+                info!("{} is back within its {}ms response time objective", provider, target_ms);
+                OrchestratorEvent::SloRecovered {
+                    provider,
+                    target_ms,
+                }
+            }
+        };
+        self.events.emit(event);
+    }
+
+    /// Save a provider's statistics. Failures are logged rather than failing
+    /// the prompt.
+    fn persist_stats(&self, provider: Provider, stats: &ProviderStats) {
+        if let Err(e) = self.stats_store.save(&provider.to_string(), stats) {
+            warn!("Failed to persist {} stats: {}", provider, e);
+        }
+    }
+
+    /// Send a prompt to multiple providers in parallel.
+    pub async fn parallel_prompt(
+        &self,
+        message: impl Into<String>,
+        providers: Vec<Provider>,
+    ) -> Result<Vec<(Provider, Result<PromptResponse>)>> {
+        self.parallel_prompt_with(message, providers, PromptOptions::default())
+            .await
+    }
+
+    /// Send a prompt to multiple providers with options.
+    ///
+    /// Providers not permitted to receive the prompt's classification get a
+    /// `PermissionDenied` result instead of being queried.
+    pub async fn parallel_prompt_with(
+        &self,
+        message: impl Into<String>,
+        providers: Vec<Provider>,
+        options: PromptOptions,
+    ) -> Result<Vec<(Provider, Result<PromptResponse>)>> {
+        self.parallel_prompt_using(message, providers, options, ParallelOptions::default())
+            .await
+    }
+
+    /// Send a prompt to multiple providers with partial-failure semantics.
+    ///
+    /// Up to `browser_pool_size` providers are queried at once. Fails if
+    /// fewer than `min_success` providers respond, querying no more once that
+    /// becomes certain. With `fail_fast`, providers not yet queried when
+    /// `min_success` responses are in are skipped and omitted from the
+    /// results.
+    pub async fn parallel_prompt_using(
+        &self,
+        message: impl Into<String>,
+        providers: Vec<Provider>,
+        options: PromptOptions,
+        parallel: ParallelOptions,
+    ) -> Result<Vec<(Provider, Result<PromptResponse>)>> {
+        let message = message.into();
+        self.ensure_not_in_maintenance()?;
+        let requested = providers.len();
+        let start = Instant::now();
+        let classification = self.guard.classify(options.classification);
+        self.touch().await;
+        let usable: Vec<_> = {
+            let router = self.router.read().await;
+            providers.iter().map(|p| router.ensure_usable(*p)).collect()
+        };
+        // Query as many providers at once as there are browser contexts, or
+        // one at a time for seeded runs so they stay reproducible
+        let width = match self.config.seed {
+            Some(_) => 1,
+            None => self.config.browser_pool_size.max(1),
+        };
+        let (message, options) = (&message, &options);
+        let mut pending = providers.into_iter().zip(usable).enumerate();
+        let mut running = FuturesUnordered::new();
+        let mut results: Vec<(usize, Provider, Result<PromptResponse>)> = Vec::new();
+        loop {
+            while running.len() < width {
+                let successes = results.iter().filter(|(_, _, r)| r.is_ok()).count();
+                if parallel.fail_fast && successes >= parallel.min_success.max(1) {
+                    break;
+                }
+                if successes + (requested - results.len()) < parallel.min_success {
+                    break;
+                }
+                let Some((index, (provider, usable))) = pending.next() else {
+                    break;
+                };
+                running.push(async move {
+                    let result = self
+                        .parallel_query(provider, usable, message, options, classification, start)
+                        .await;
+                    (index, provider, result)
+                });
+            }
+            match running.next().await {
+                Some(done) => results.push(done),
+                None => break,
+            }
+        }
+        results.sort_by_key(|(index, _, _)| *index);
+        let mut results: Vec<(Provider, Result<PromptResponse>)> = results
+            .into_iter()
+            .map(|(_, provider, result)| (provider, result))
+            .collect();
+
+        let successes = results.iter().filter(|(_, r)| r.is_ok()).count();
+        if successes < parallel.min_success {
+            return Err(Error::NoProviders(format!(
+                "only {} of {} providers responded, need {}",
+                successes, requested, parallel.min_success
+            )));
+        }
+
+        if let Some(policy) = &self.config.grading {
+            for (_, result) in &mut results {
+                let Ok(response) = result else { continue };
+                match self
+                    .grade_response(policy, message, response, None, options.classification)
+                    .await
+                {
+                    Ok(grade) => {
+                        response
+                            .metadata
+                            .insert(QUALITY_SCORE_KEY.into(), format!("{:.2}", grade.score));
+                    }
+                    Err(e) => warn!("Failed to grade response from {}: {}", response.provider, e),
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Query one provider of a parallel prompt.
+    async fn parallel_query(
+        &self,
+        provider: Provider,
+        usable: Result<()>,
+        message: &str,
+        options: &PromptOptions,
+        classification: DataClassification,
+        start: Instant,
+    ) -> Result<PromptResponse> {
+        usable
+            .and_then(|_| self.ensure_not_in_maintenance())
+            .and_then(|_| self.guard.check(classification, provider))
+            .and_then(|_| self.audit_dispatch(provider, classification, message))
+            .and_then(|_| self.charge_tenant(provider, &options.request(message)))?;
+
+        // Authenticate, then send prompt
+        let _slot = self.fair_slot(provider).await?;
+        let mut puppet = self.get_puppet_with(provider, self.headless_for(options)).await?;
+        let result = match puppet.authenticate(provider).await {
+            Ok(()) => self.dispatch(&puppet, provider, options.request(message), start).await,
+            Err(e) => Err(e),
+        };
+        self.close_failed_puppet(provider, &mut puppet, &result).await;
+        // Free the browser context before waiting on a person
+        puppet.close().await.ok();
+        let result = match result {
+            Err(Error::Captcha { .. }) if self.config.intervention.enabled => {
+                self.await_intervention(provider, options.request(message), start)
+                    .await
+            }
+            other => other,
+        };
+        result.map(|response| self.sanitize_response(response))
+    }
+
+    /// Have one provider, or a panel, critique content against criteria.
+    ///
+    /// With no providers named, the best available provider is the critic.
+    /// Fails only if no critic responds.
+    pub async fn critique(
+        &self,
+        content: &str,
+        criteria: &[String],
+        providers: Vec<Provider>,
+        options: PromptOptions,
+    ) -> Result<Critique> {
+        let prompt = critique_prompt(content, criteria);
+        let results = match providers.as_slice() {
+            [] => {
+                let response = self.prompt_with(prompt, options).await?;
+                vec![(response.provider, Ok(response))]
+            }
+            [provider] => vec![(
+                *provider,
+                self.prompt_provider_with(*provider, prompt, options).await,
+            )],
+            _ => self.parallel_prompt_with(prompt, providers, options).await?,
+        };
+
+        let mut critique = Critique::default();
+        for (provider, result) in results {
+            match result {
+                Ok(response) => {
+                    let name = provider.to_string();
+                    critique.add(&name, parse_findings(&name, &response.text));
+                }
+                Err(e) => critique.failures.push((provider.to_string(), e.to_string())),
+            }
+        }
+
+        if critique.critics.is_empty() {
+            let reasons: Vec<_> = critique
+                .failures
+                .iter()
+                .map(|(p, e)| format!("{}: {}", p, e))
+                .collect();
+            return Err(Error::NoProviders(format!(
+                "no critic responded ({})",
+                reasons.join("; ")
+            )));
+        }
+        Ok(critique)
+    }
+
+    /// Ask a provider to decompose a goal into a workflow definition of at
+    /// most `max_steps` steps, ready for `agent_workflow_start`.
+    pub async fn plan(
+        &self,
+        goal: &str,
+        context: Option<&str>,
+        max_steps: usize,
+        provider: Option<Provider>,
+        options: PromptOptions,
+    ) -> Result<WorkflowDef> {
+        let prompt = plan_prompt(goal, max_steps, context);
+        let response = match provider {
+            Some(provider) => self.prompt_provider_with(provider, prompt, options).await?,
+            None => self.prompt_with(prompt, options).await?,
+        };
+        parse_plan(&response.text, max_steps)
+    }
+
+    /// Plan a goal and start the resulting workflow for an auto run,
+    /// returning its ID. Every step inherits the run's classification.
+    pub async fn auto_start(
+        &self,
+        goal: &str,
+        context: Option<&str>,
+        auto: &AutoOptions,
+        options: PromptOptions,
+    ) -> Result<String> {
+        let plan = self
+            .plan(goal, context, auto.max_steps, auto.planner, options.clone())
+            .await?;
+        let mut workflow = plan.to_workflow()?;
+        for step in &mut workflow.steps {
+            step.classification = options.classification;
+        }
+        workflow.set_context(GOAL_KEY, goal.into());
+        workflow.set_context(PLAN_KEY, serde_json::to_value(&plan)?);
+        self.start_workflow(workflow).await
+    }
+
+    /// Run the remaining steps of an auto workflow, then synthesize the
+    /// final answer from the step outputs.
+    ///
+    /// Steps go through the approval policy as usual; the run stops at the
+    /// first step that needs approval, fails, or would take the estimated
+    /// cost past `max_cost_usd`. Calling this again resumes the run.
+    pub async fn auto_run(
+        &self,
+        workflow_id: &str,
+        auto: &AutoOptions,
+        options: PromptOptions,
+    ) -> Result<AutoReport> {
+        let costs = &self.config.cost_model;
+        let over_budget =
+            |spent: f64, next: f64| auto.max_cost_usd.is_some_and(|max| spent + next > max);
+
+        let mut outcome = loop {
+            let workflow = self.require_workflow(workflow_id).await?;
+            if let WorkflowState::Failed(reason) = &workflow.state {
+                break AutoOutcome::Failed {
+                    reason: reason.clone(),
+                };
+            }
+            let Some(step) = workflow.current() else {
+                break AutoOutcome::Completed;
+            };
+            if let Some(reason) = self.pause_reason() {
+                break AutoOutcome::Paused { reason };
+            }
+
+            let classification = self.guard.classify(step.classification);
+            let next = StepRisk::assess(step, classification, costs, &self.config.approval_policy)
+                .estimated_cost_usd;
+            if over_budget(spent_usd(&workflow, costs), next) {
+                break AutoOutcome::OverBudget { next_step_usd: next };
+            }
+
+            if let Err(e) = self.execute_workflow_step_with(workflow_id, options.visible).await {
+                let workflow = self.require_workflow(workflow_id).await?;
+                if workflow.state == WorkflowState::Paused {
+                    break AutoOutcome::AwaitingApproval {
+                        reason: e.to_string(),
+                    };
+                }
+                if let Some(workflow) = self.workflows.write().await.get_mut(workflow_id) {
+                    workflow.fail(e.to_string());
+                }
+                self.persist_workflow(workflow_id).await;
+                break AutoOutcome::Failed {
+                    reason: e.to_string(),
+                };
+            }
+        };
+
+        let workflow = self.require_workflow(workflow_id).await?;
+        let mut spent = spent_usd(&workflow, costs);
+        let mut synthesis = workflow
+            .get_context(SYNTHESIS_KEY)
+            .and_then(|v| v.as_str())
+            .map(String::from);
+
+        if outcome == AutoOutcome::Completed && synthesis.is_none() {
+            let goal = workflow
+                .get_context(GOAL_KEY)
+                .and_then(|v| v.as_str())
+                .unwrap_or(&workflow.name);
+            let prompt = synthesis_prompt(goal, &workflow);
+            let synthesizer = auto.synthesizer.map(|p| p.to_string());
+            let next = costs.estimate(synthesizer.as_deref(), estimate_tokens(&prompt));
+
+            if over_budget(spent, next) {
+                outcome = AutoOutcome::OverBudget { next_step_usd: next };
+            } else {
+                let tokens = estimate_tokens(&prompt);
+                let response = match auto.synthesizer {
+                    Some(provider) => self.prompt_provider_with(provider, prompt, options).await?,
+                    None => self.prompt_with(prompt, options).await?,
+                };
+                spent += costs.estimate(
+                    Some(&response.provider.to_string()),
+                    tokens + estimate_tokens(&response.text),
+                );
+                if let Some(workflow) = self.workflows.write().await.get_mut(workflow_id) {
+                    workflow.set_context(SYNTHESIS_KEY, response.text.clone().into());
+                }
+                self.persist_workflow(workflow_id).await;
+                synthesis = Some(response.text);
+            }
+        }
+
+        if let AutoOutcome::OverBudget { next_step_usd } = outcome {
+            self.events.emit(OrchestratorEvent::BudgetAlert {
+                scope: "auto".into(),
+                subject: workflow_id.to_string(),
+                spent_usd: spent,
+                limit_usd: auto.max_cost_usd.unwrap_or_default(),
+                next_usd: next_step_usd,
+            });
+        }
+
+        let plan = match workflow.get_context(PLAN_KEY) {
+            Some(plan) => serde_json::from_value(plan.clone())?,
+            None => WorkflowDef {
+                name: workflow.name.clone(),
+                steps: Vec::new(),
+            },
+        };
+        Ok(AutoReport {
+            workflow_id: workflow_id.to_string(),
+            plan,
+            outcome,
+            synthesis,
+            steps_completed: workflow
+                .steps
+                .iter()
+                .filter(|s| s.state == StepState::Completed)
+                .count(),
+            spent_usd: spent,
+        })
+    }
+
+    /// Run a time-boxed research sprint on a question.
+    ///
+    /// Each round sends the open queries to the search providers, then asks
+    /// the synthesizer for follow-up queries covering what the findings leave
+    /// open. Rounds stop when none are left or a budget runs out; the
+    /// findings are then synthesized into an answer citing their sources.
+    pub async fn research(
+        &self,
+        question: &str,
+        research: ResearchOptions,
+        options: PromptOptions,
+    ) -> Result<ResearchReport> {
+        let start = Instant::now();
+        let costs = &self.config.cost_model;
+        let classification = self.guard.classify(options.classification);
+        let providers = if research.providers.is_empty() {
+            let router = self.router.read().await;
+            Provider::search_providers()
+                .into_iter()
+                .filter(|p| router.ensure_usable(*p).is_ok() && self.guard.is_allowed(classification, *p))
+                .collect()
+        } else {
+            research.providers.clone()
+        };
+        if providers.is_empty() {
+            return Err(Error::NoProviders(
+                "no search-capable provider may receive this question".into(),
+            ));
+        }
+
+        let mut findings: Vec<Finding> = Vec::new();
+        let mut sources = SourceList::default();
+        let mut asked: Vec<String> = Vec::new();
+        let mut queries = vec![question.to_string()];
+        let mut spent = 0.0;
+        let mut rounds = 0;
+
+        let stopped = 'sprint: loop {
+            if queries.is_empty() {
+                break ResearchStop::Answered;
+            }
+            if rounds == research.max_rounds {
+                break ResearchStop::MaxRounds;
+            }
+            rounds += 1;
+
+            for query in std::mem::take(&mut queries) {
+                let Some(remaining) = research.time_budget.checked_sub(start.elapsed()) else {
+                    break 'sprint ResearchStop::TimeBudget;
+                };
+                let prompt = query_prompt(question, &query);
+                let tokens = estimate_tokens(&prompt);
+                let next: f64 = providers
+                    .iter()
+                    .map(|p| costs.estimate(Some(&p.to_string()), tokens))
+                    .sum();
+                if let Some(max) = research.max_cost_usd.filter(|max| spent + next > *max) {
+                    self.events.emit(OrchestratorEvent::BudgetAlert {
+                        scope: "research".into(),
+                        subject: question.to_string(),
+                        spent_usd: spent,
+                        limit_usd: max,
+                        next_usd: next,
+                    });
+                    break 'sprint ResearchStop::CostBudget;
+                }
+
+                let sent = self.parallel_prompt_with(prompt, providers.clone(), options.clone());
+                let Ok(results) = tokio::time::timeout(remaining, sent).await else {
+                    break 'sprint ResearchStop::TimeBudget;
+                };
+                for (provider, result) in results? {
+                    let response = match result {
+                        Ok(response) => response,
+                        Err(e) => {
+                            warn!("Research query to {} failed: {}", provider, e);
+                            continue;
+                        }
+                    };
+                    let name = provider.to_string();
+                    let used = tokens + estimate_tokens(&response.text);
+                    spent += costs.estimate(Some(&name), used);
+                    sources.add(&name, &citations::from_response(&response));
+                    findings.push(Finding {
+                        round: rounds,
+                        query: query.clone(),
+                        provider: name,
+                        text: response.text,
+                        tokens: used,
+                    });
+                }
+                asked.push(query);
+            }
+
+            if rounds < research.max_rounds && !findings.is_empty() {
+                let prompt = refine_prompt(question, &findings);
+                let tokens = estimate_tokens(&prompt);
+                let refined = match research.synthesizer {
+                    Some(provider) => {
+                        self.prompt_provider_with(provider, prompt, options.clone())
+                            .await?
+                    }
+                    None => self.prompt_with(prompt, options.clone()).await?,
+                };
+                spent += costs.estimate(
+                    Some(&refined.provider.to_string()),
+                    tokens + estimate_tokens(&refined.text),
+                );
+                queries = parse_queries(&refined.text, &asked);
+            }
+        };
 
-impl AgentOrchestrator {
-    /// Create a new orchestrator.
-    pub fn new() -> Self {
-        Self {
-            puppet: Arc::new(RwLock::new(None)),
-            router: Arc::new(RwLock::new(ProviderRouter::new())),
-            workflows: Arc::new(RwLock::new(HashMap::new())),
-            config: OrchestratorConfig::default(),
+        if findings.is_empty() {
+            return Err(Error::NoProviders(format!(
+                "no provider answered the research question ({})",
+                stopped.describe()
+            )));
         }
-    }
 
-    /// Create with custom configuration.
-    pub fn with_config(config: OrchestratorConfig) -> Self {
-        Self {
-            puppet: Arc::new(RwLock::new(None)),
-            router: Arc::new(RwLock::new(ProviderRouter::new())),
-            workflows: Arc::new(RwLock::new(HashMap::new())),
-            config,
-        }
+        let sources = sources.into_vec();
+        let prompt = research_synthesis_prompt(question, &findings, &sources);
+        let tokens = estimate_tokens(&prompt);
+        let synthesis = match research.synthesizer {
+            Some(provider) => self.prompt_provider_with(provider, prompt, options).await?,
+            None => self.prompt_with(prompt, options).await?,
+        };
+        spent += costs.estimate(
+            Some(&synthesis.provider.to_string()),
+            tokens + estimate_tokens(&synthesis.text),
+        );
+
+        Ok(ResearchReport {
+            question: question.to_string(),
+            synthesis: synthesis.text,
+            sources,
+            findings,
+            rounds,
+            stopped,
+            spent_usd: spent,
+            duration_ms: start.elapsed().as_millis() as u64,
+        })
     }
 
-    /// Get or create WebPuppet instance.
-    async fn get_puppet(&self) -> Result<WebPuppet> {
-        let guard = self.puppet.read().await;
-        if guard.is_some() {
-            drop(guard);
+    /// Fact-check the claims in a text with a search-capable provider,
+    /// returning the text annotated with each claim's verdict.
+    pub async fn fact_check(
+        &self,
+        text: &str,
+        focus: Option<&str>,
+        checker: Option<Provider>,
+        max_claims: usize,
+        options: PromptOptions,
+    ) -> Result<FactCheckReport> {
+        let claims = extract_claims(text, max_claims);
+        if claims.is_empty() {
+            return Err(Error::InvalidParams(
+                "the text contains no checkable claims".into(),
+            ));
         }
 
-        // Create new puppet
-        let puppet = WebPuppet::builder()
-            .with_all_providers()
-            .headless(self.config.headless)
-            .build()
-            .await?;
+        let checker = match checker {
+            Some(provider) => provider,
+            None => {
+                let classification = self.guard.classify(options.classification);
+                let search = Provider::search_providers();
+                self.router
+                    .read()
+                    .await
+                    .select_best_where(TaskType::Search, |p| {
+                        search.contains(&p) && self.guard.is_allowed(classification, p)
+                    })
+                    .map_err(|_| {
+                        Error::NoProviders(
+                            "no search-capable provider may receive this text".into(),
+                        )
+                    })?
+            }
+        };
+        let prompt = fact_check_prompt(&claims, focus);
+        let response = self.prompt_provider_with(checker, prompt, options).await?;
+        let checks = parse_checks(&response.text, &claims);
 
-        Ok(puppet)
+        Ok(FactCheckReport {
+            annotated: annotate_claims(text, &checks),
+            checks,
+            checker: checker.to_string(),
+        })
     }
 
-    /// Send a prompt to the best available provider.
-    pub async fn prompt(&self, message: impl Into<String>) -> Result<PromptResponse> {
-        let router = self.router.read().await;
-        let provider = router.select_best(TaskType::General)?;
-        drop(router);
-
-        self.prompt_provider(provider, message).await
+    /// Apply a diff to a scratch copy of the workspace and, if `check` is
+    /// set, run the configured check command there. While the diff fails, a
+    /// provider is asked for a corrected one, at most `max_refinements`
+    /// times. Only local callers may set `check`.
+    pub async fn validate_patch(
+        &self,
+        diff: String,
+        check: bool,
+        provider: Option<Provider>,
+        max_refinements: u32,
+        options: PromptOptions,
+    ) -> Result<PatchReport> {
+        if check {
+            check_runner(self.tenant())?;
+        }
+        let root = std::env::current_dir()?;
+        let command = self.config.patch_check_command.as_deref().filter(|_| check);
+        let mut report = PatchReport {
+            diff,
+            attempts: Vec::new(),
+        };
+        loop {
+            let attempt = check_patch(&root, &report.diff, command).await?;
+            let prompt = (!attempt.passed).then(|| correction_prompt(&report.diff, &attempt));
+            report.attempts.push(attempt);
+            let Some(prompt) = prompt else {
+                return Ok(report);
+            };
+            if report.attempts.len() > max_refinements as usize {
+                return Ok(report);
+            }
+            let response = match provider {
+                Some(p) => self.prompt_provider_with(p, prompt, options.clone()).await?,
+                None => self.prompt_with(prompt, options.clone()).await?,
+            };
+            report.diff = extract_diff(&response.text).ok_or_else(|| {
+                Error::Workflow("the corrected patch contains no diff".into())
+            })?;
+        }
     }
 
-    /// Send a prompt to a specific provider.
-    pub async fn prompt_provider(
+    /// Translate text with one provider and verify it by having a different
+    /// provider translate it back, flagging divergences from the source.
+    pub async fn translate(
         &self,
-        provider: Provider,
-        message: impl Into<String>,
-    ) -> Result<PromptResponse> {
-        let message = message.into();
-        let start = Instant::now();
+        text: &str,
+        target_language: &str,
+        translation: TranslationOptions,
+        options: PromptOptions,
+    ) -> Result<TranslationReport> {
+        let source_language = translation.source_language().to_string();
+        if translation.translator.is_some() && translation.translator == translation.verifier {
+            return Err(Error::InvalidParams(
+                "translator and verifier must be different providers".into(),
+            ));
+        }
+
+        let prompt = translation_prompt(text, &source_language, target_language);
+        let translated = match translation.translator {
+            Some(provider) => {
+                self.prompt_provider_with(provider, prompt, options.clone())
+                    .await?
+            }
+            None => self.prompt_with(prompt, options.clone()).await?,
+        };
+        let translator = translated.provider;
+
+        let verifier = match translation.verifier {
+            Some(provider) => provider,
+            None => {
+                let classification = self.guard.classify(options.classification);
+                self.router
+                    .read()
+                    .await
+                    .select_best_where(TaskType::General, |p| {
+                        p != translator && self.guard.is_allowed(classification, p)
+                    })
+                    .map_err(|_| {
+                        Error::NoProviders(format!(
+                            "no provider other than {} is available to verify the translation",
+                            translator
+                        ))
+                    })?
+            }
+        };
+        let prompt = back_translation_prompt(&translated.text, &source_language);
+        let back = self.prompt_provider_with(verifier, prompt, options).await?;
 
-        let puppet = self.get_puppet().await?;
-        
-        // Authenticate if needed
-        puppet.authenticate(provider).await?;
+        let (fidelity, divergences) = compare_translation(text, &back.text);
+        Ok(TranslationReport {
+            translation: translated.text,
+            back_translation: back.text,
+            source_language,
+            target_language: target_language.to_string(),
+            translator: translator.to_string(),
+            verifier: verifier.to_string(),
+            fidelity,
+            divergences,
+        })
+    }
 
-        // Send prompt
-        let request = PromptRequest::new(&message);
-        let result = puppet.prompt(provider, request).await;
+    /// Have the judge grade a response, recording the grade as the responding
+    /// provider's quality in the router.
+    ///
+    /// The judge receives the question and response, so it must be permitted
+    /// to receive the original classification.
+    pub async fn grade_response(
+        &self,
+        policy: &GradingPolicy,
+        question: &str,
+        response: &PromptResponse,
+        reference: Option<&str>,
+        classification: Option<DataClassification>,
+    ) -> Result<Grade> {
+        let (score, verdict) = self
+            .judge(policy, question, &response.text, reference, classification)
+            .await?;
 
-        // Record result in router
         let mut router = self.router.write().await;
-        match &result {
-            Ok(_) => router.record_success(provider, start.elapsed()),
-            Err(_) => router.record_failure(provider),
-        }
+        router.record_quality(response.provider, score);
+        let stats = router.get_stats().remove(&response.provider).unwrap_or_default();
+        drop(router);
+        self.persist_stats(response.provider, &stats);
 
-        // Cleanup
-        puppet.close().await.ok();
+        Ok(Grade {
+            provider: response.provider,
+            score,
+            verdict,
+        })
+    }
 
-        result.map_err(Error::from)
+    /// Have the policy's judge score an answer, returning the score and the
+    /// judge's full verdict.
+    async fn judge(
+        &self,
+        policy: &GradingPolicy,
+        question: &str,
+        answer: &str,
+        reference: Option<&str>,
+        classification: Option<DataClassification>,
+    ) -> Result<(f64, String)> {
+        let judge = policy.judge()?;
+        let prompt = grading_prompt(question, answer, reference, policy.rubric.as_deref());
+        let options = PromptOptions {
+            classification,
+            ..Default::default()
+        };
+        let verdict = self.prompt_provider_with(judge, prompt, options).await?;
+        let score = parse_score(&verdict.text).ok_or_else(|| {
+            Error::Internal(format!("judge {} returned no score", judge))
+        })?;
+        Ok((score, verdict.text))
     }
 
-    /// Send a prompt to multiple providers in parallel.
-    ///
-    /// Note: Due to browser automation constraints, this actually runs sequentially
-    /// for web-based providers. API providers can run truly in parallel.
-    pub async fn parallel_prompt(
+    /// Have a provider rate its confidence in a step's answers to
+    /// `question`: each provider response not yet scored, or else the
+    /// step's output. Returns the step's confidence: the selected
+    /// response's, else the lowest.
+    async fn rate_confidence(
         &self,
-        message: impl Into<String>,
-        providers: Vec<Provider>,
-    ) -> Result<Vec<(Provider, Result<PromptResponse>)>> {
-        let message = message.into();
-        let puppet = self.get_puppet().await?;
+        check: &ConfidenceCheck,
+        question: &str,
+        result: &mut StepResult,
+        classification: Option<DataClassification>,
+    ) -> Result<f64> {
+        let rate = |answered: Option<Provider>, answer: String| async move {
+            let rater = check.rater(answered)?;
+            let prompt = confidence_prompt(question, &answer);
+            let options = PromptOptions {
+                classification,
+                ..Default::default()
+            };
+            let reply = match rater {
+                Some(rater) => self.prompt_provider_with(rater, prompt, options).await?,
+                None => self.prompt_with(prompt, options).await?,
+            };
+            let score = parse_score(&reply.text).ok_or_else(|| {
+                Error::Internal(format!("{} returned no confidence rating", reply.provider))
+            })?;
+            Ok::<_, Error>((score, reply.provider.to_string()))
+        };
 
-        let mut results = Vec::new();
-        
-        // Run sequentially for browser-based providers
-        // Future: API providers could run in parallel
-        for provider in providers {
-            // Authenticate
-            let auth_result = puppet.authenticate(provider).await;
-            if let Err(e) = auth_result {
-                results.push((provider, Err(Error::from(e))));
-                continue;
+        let score = match result.responses.as_mut().filter(|r| !r.is_empty()) {
+            Some(responses) => {
+                let unrated: Vec<&mut ProviderResponse> =
+                    responses.iter_mut().filter(|r| r.confidence.is_none()).collect();
+                let ratings = join_all(
+                    unrated
+                        .iter()
+                        .map(|r| rate(Provider::from_string(&r.provider), r.text.clone())),
+                )
+                .await;
+                for (response, rating) in unrated.into_iter().zip(ratings) {
+                    let (score, rater) = rating?;
+                    response.confidence = Some(score);
+                    response.metadata.insert(RATER_KEY.into(), rater.into());
+                }
+                let scores = || responses.iter().filter_map(|r| r.confidence);
+                let selected = responses.iter().find(|r| r.selected);
+                selected
+                    .and_then(|r| r.confidence)
+                    .unwrap_or_else(|| scores().fold(1.0, f64::min))
             }
+            None => {
+                let answered = result.provider.as_deref().and_then(Provider::from_string);
+                let (score, rater) = rate(answered, result.output.clone()).await?;
+                result.metadata.insert(RATER_KEY.into(), rater.into());
+                score
+            }
+        };
+        result.metadata.insert(CONFIDENCE_KEY.into(), score.into());
+        Ok(score)
+    }
 
-            // Send prompt
-            let request = PromptRequest::new(&message);
-            let prompt_result = puppet.prompt(provider, request).await;
-            
-            results.push((provider, prompt_result.map_err(Error::from)));
+    /// Sanitize a provider response, noting removed content in its metadata.
+    fn sanitize_response(&self, mut response: PromptResponse) -> PromptResponse {
+        let report = sanitize(&response.text, &self.config.sanitization);
+        if !report.is_clean() {
+            warn!("Sanitized response from {}: {}", response.provider, report.summary());
+            response
+                .metadata
+                .insert(SANITIZATION_SUMMARY_KEY.into(), report.summary());
+            response.metadata.insert(
+                SANITIZATION_REPORT_KEY.into(),
+                serde_json::to_string(&report.removed).unwrap_or_default(),
+            );
         }
+        response.text = report.text;
+        // Citations are parsed from the text the caller will see.
+        citations::annotate(&mut response);
+        response
+    }
 
-        puppet.close().await.ok();
-
-        Ok(results)
+    /// Record a prompt dispatch in the audit log, if one is configured.
+    ///
+    /// Fails closed: a prompt is not sent if it cannot be audited.
+    fn audit_dispatch(
+        &self,
+        provider: Provider,
+        classification: DataClassification,
+        message: &str,
+    ) -> Result<()> {
+        if let Some(log) = &self.config.audit_log {
+            log.record(AuditEvent::prompt_dispatch(
+                provider.to_string(),
+                classification,
+                message,
+            ))?;
+        }
+        Ok(())
     }
 
     /// Get consensus from multiple providers.
@@ -145,22 +3010,92 @@ impl AgentOrchestrator {
         &self,
         message: impl Into<String>,
         min_providers: usize,
+    ) -> Result<ConsensusResult> {
+        self.consensus_prompt_with(message, min_providers, PromptOptions::default())
+            .await
+    }
+
+    /// Get consensus from multiple providers with options.
+    pub async fn consensus_prompt_with(
+        &self,
+        message: impl Into<String>,
+        min_providers: usize,
+        options: PromptOptions,
+    ) -> Result<ConsensusResult> {
+        self.consensus_prompt_using(message, min_providers, options, ConsensusOptions::default())
+            .await
+    }
+
+    /// Get consensus from multiple providers, choosing the answer with the
+    /// given strategy.
+    pub async fn consensus_prompt_using(
+        &self,
+        message: impl Into<String>,
+        min_providers: usize,
+        options: PromptOptions,
+        consensus: ConsensusOptions,
     ) -> Result<ConsensusResult> {
         let message = message.into();
-        
-        // Select providers
+        let judge = match consensus.strategy {
+            ConsensusStrategy::Judge => Some(self.judge_policy(&consensus)?),
+            _ => None,
+        };
+        let classification = self.guard.classify(options.classification);
+        let language = detect_language(&message);
+        let tokens = metadata::prompt_tokens(&options.request(&message));
+        let task = TaskType::General.refine(&message, tokens);
+        let eligible =
+            |p| context_window(p) >= tokens && self.guard.is_allowed(classification, p);
+
+        // Select providers permitted to receive this data
         let router = self.router.read().await;
-        let providers = router.select_multiple(min_providers.max(3), TaskType::General)?;
+        let weights = router.weights_for(&task, options.routing.as_ref())?;
+        let providers = router.select_multiple_weighted(
+            min_providers.max(3),
+            task.clone(),
+            language,
+            &weights,
+            eligible,
+        )?;
         drop(router);
+        self.emit_routing(&task, language, tokens, &providers);
 
-        // Get responses in parallel
-        let results = self.parallel_prompt(&message, providers).await?;
+        // Get responses in parallel, topping up with untried providers until
+        // the quorum is met or no candidates remain
+        let mut tried = providers.clone();
+        let mut responses = Vec::new();
+        let mut batch = providers;
+        while !batch.is_empty() {
+            let results = self
+                .parallel_prompt_with(&message, batch, options.clone())
+                .await?;
+            responses.extend(
+                results
+                    .into_iter()
+                    .filter_map(|(p, r)| r.ok().map(|resp| (p, resp))),
+            );
+            if responses.len() >= min_providers {
+                break;
+            }
 
-        // Collect successful responses
-        let responses: Vec<_> = results
-            .into_iter()
-            .filter_map(|(p, r)| r.ok().map(|resp| (p, resp)))
-            .collect();
+            batch = self.router.read().await.select_up_to_weighted(
+                min_providers - responses.len(),
+                task.clone(),
+                language,
+                &weights,
+                |p| !tried.contains(&p) && eligible(p),
+            );
+            if !batch.is_empty() {
+                info!(
+                    "Consensus has {} of {} responses, adding {:?}",
+                    responses.len(),
+                    min_providers,
+                    batch
+                );
+            }
+            tried.extend(&batch);
+        }
+        let classification = options.classification;
 
         if responses.len() < min_providers {
             return Err(Error::NoProviders(format!(
@@ -170,73 +3105,721 @@ impl AgentOrchestrator {
             )));
         }
 
-        // Simple consensus: find common themes
-        // In a real implementation, this would use semantic similarity
-        let consensus = self.find_consensus(&responses);
+        let grades = match &judge {
+            Some(policy) => Some(
+                self.grade_all(policy, &message, &responses, classification)
+                    .await,
+            ),
+            None => None,
+        };
+
+        Ok(self.find_consensus(&responses, consensus.strategy, grades.as_deref()))
+    }
+
+    /// Resolve the judge for the `judge` consensus strategy.
+    fn judge_policy(&self, consensus: &ConsensusOptions) -> Result<GradingPolicy> {
+        match (&consensus.judge_provider, &self.config.grading) {
+            (Some(judge), grading) => Ok(GradingPolicy {
+                judge_provider: judge.clone(),
+                rubric: grading.as_ref().and_then(|g| g.rubric.clone()),
+            }),
+            (None, Some(grading)) => Ok(grading.clone()),
+            (None, None) => Err(Error::InvalidParams(
+                "judge strategy requires a judge_provider".into(),
+            )),
+        }
+    }
+
+    /// Grade each response, reusing grades already attached by the grading
+    /// policy when the same judge is used. Failed grades are `None`.
+    async fn grade_all(
+        &self,
+        policy: &GradingPolicy,
+        message: &str,
+        responses: &[(Provider, PromptResponse)],
+        classification: Option<DataClassification>,
+    ) -> Vec<Option<f64>> {
+        let reuse = self
+            .config
+            .grading
+            .as_ref()
+            .is_some_and(|g| g.judge_provider == policy.judge_provider);
 
-        Ok(consensus)
+        let mut grades = Vec::with_capacity(responses.len());
+        for (provider, response) in responses {
+            let existing = response
+                .metadata
+                .get(QUALITY_SCORE_KEY)
+                .and_then(|s| s.parse().ok())
+                .filter(|_| reuse);
+            let grade = match existing {
+                Some(score) => Some(score),
+                None => match self
+                    .grade_response(policy, message, response, None, classification)
+                    .await
+                {
+                    Ok(grade) => Some(grade.score),
+                    Err(e) => {
+                        warn!("Failed to grade response from {}: {}", provider, e);
+                        None
+                    }
+                },
+            };
+            grades.push(grade);
+        }
+        grades
     }
 
-    /// Find consensus among responses (simple implementation).
-    fn find_consensus(&self, responses: &[(Provider, PromptResponse)]) -> ConsensusResult {
-        // For now, just return the longest response as "consensus"
-        // A real implementation would use semantic similarity
-        let best = responses
+    /// Find consensus among responses.
+    ///
+    /// Scores agreement by word overlap; each response's confidence is its
+    /// mean similarity to the others. With grades, the best-graded response
+    /// wins; otherwise the strategy chooses.
+    fn find_consensus(
+        &self,
+        responses: &[(Provider, PromptResponse)],
+        strategy: ConsensusStrategy,
+        grades: Option<&[Option<f64>]>,
+    ) -> ConsensusResult {
+        let texts: Vec<&str> = responses.iter().map(|(_, r)| r.text.as_str()).collect();
+        let agreement = agreement(&texts, similarity);
+
+        let index = match grades {
+            Some(grades) if grades.iter().any(Option::is_some) => {
+                argmax(grades.iter().map(|g| g.unwrap_or(-1.0)))
+            }
+            _ => select(strategy, &texts, &agreement),
+        };
+        let best = index.map(|i| (responses[i].0, responses[i].1.clone()));
+
+        let names: Vec<String> = responses.iter().map(|(p, _)| p.to_string()).collect();
+        let statements: Vec<(&str, &str)> = names
             .iter()
-            .max_by_key(|(_, r)| r.text.len())
-            .map(|(p, r)| (*p, r.clone()));
+            .map(String::as_str)
+            .zip(texts.iter().copied())
+            .collect();
+        let disagreements = find_disagreements(&statements);
 
         let provider_responses: Vec<_> = responses
             .iter()
-            .map(|(p, r)| ProviderResponse {
+            .zip(&agreement.confidence)
+            .map(|((p, r), confidence)| ProviderResponse {
                 provider: p.to_string(),
                 text: r.text.clone(),
-                selected: best.as_ref().map_or(false, |(bp, _)| bp == p),
-                confidence: None,
+                selected: best.as_ref().is_some_and(|(bp, _)| bp == p),
+                confidence: Some(*confidence),
+                metadata: metadata::to_json(r),
             })
             .collect();
 
         ConsensusResult {
             consensus_text: best.map(|(_, r)| r.text).unwrap_or_default(),
+            sources: citations::merge(&provider_responses),
             responses: provider_responses,
-            agreement_score: 0.5, // Placeholder
+            agreement_score: agreement.score,
+            strategy,
+            disagreements,
         }
     }
 
-    /// Start a new workflow.
-    pub async fn start_workflow(&self, workflow: Workflow) -> Result<String> {
+    /// Start a new workflow, owned by this handle's tenant.
+    pub async fn start_workflow(&self, mut workflow: Workflow) -> Result<String> {
+        self.validate_workflow_providers(&workflow).await?;
+        workflow.validate_jumps()?;
+        workflow.validate_dependencies()?;
+        for step in &workflow.steps {
+            if let StepConfig::Tool { tool_name, .. } = &step.config {
+                self.check_step_tool(tool_name)?;
+            }
+            if step.write_to.is_some() {
+                check_writer(self.tenant())?;
+            }
+            if let StepConfig::ValidatePatch { check: true, .. } = step.config {
+                check_runner(self.tenant())?;
+            }
+        }
+
+        workflow.tenant = self.tenant().to_string();
         let id = workflow.id.clone();
         let mut workflows = self.workflows.write().await;
         workflows.insert(id.clone(), workflow);
+        drop(workflows);
+        self.persist_workflow(&id).await;
         Ok(id)
     }
 
+    /// Check that a tool step can call `name`: a registered tool that does
+    /// not itself run workflow steps.
+    fn check_step_tool(&self, name: &str) -> Result<()> {
+        if matches!(
+            name,
+            "agent_workflow_step" | "agent_workflow_run" | "agent_workflow_review"
+        ) {
+            return Err(Error::InvalidParams(format!("a tool step cannot call {}", name)));
+        }
+        if self.tools.get(name).is_none() {
+            return Err(Error::InvalidParams(format!("unknown tool in tool step: {}", name)));
+        }
+        Ok(())
+    }
+
+    /// Save a workflow to storage. Failures are logged rather than failing
+    /// the workflow.
+    async fn persist_workflow(&self, workflow_id: &str) {
+        if let Some(workflow) = self.workflows.read().await.get(workflow_id) {
+            if let Err(e) = self.workflow_store.save(workflow_id, workflow) {
+                warn!("Failed to persist workflow {}: {}", workflow_id, e);
+            }
+        }
+    }
+
+    /// Pick up a newer copy of a workflow from storage, such as one advanced
+    /// by another instance sharing the backend.
+    async fn sync_workflow(&self, workflow_id: &str) {
+        let stored = match self.workflow_store.load::<Workflow>(workflow_id) {
+            Ok(Some(stored)) => stored,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to load workflow {}: {}", workflow_id, e);
+                return;
+            }
+        };
+        let mut workflows = self.workflows.write().await;
+        let newer = workflows
+            .get(workflow_id)
+            .is_none_or(|w| stored.updated_at > w.updated_at);
+        if newer {
+            workflows.insert(workflow_id.to_string(), stored);
+        }
+    }
+
+    /// Ensure every provider a workflow names may be used in the current mode.
+    async fn validate_workflow_providers(&self, workflow: &Workflow) -> Result<()> {
+        let router = self.router.read().await;
+        for step in &workflow.steps {
+            let names: Vec<&String> = match &step.config {
+                StepConfig::Prompt {
+                    provider: Some(p), ..
+                } => vec![p],
+                StepConfig::ParallelPrompt { providers, .. }
+                | StepConfig::Research { providers, .. }
+                | StepConfig::Extract { providers, .. } => providers.iter().collect(),
+                StepConfig::Consensus {
+                    judge_provider: Some(p),
+                    ..
+                }
+                | StepConfig::FactCheck {
+                    provider: Some(p), ..
+                }
+                | StepConfig::ValidatePatch {
+                    provider: Some(p), ..
+                }
+                | StepConfig::Aggregate {
+                    provider: Some(p), ..
+                } => vec![p],
+                StepConfig::Map {
+                    provider, checker, ..
+                } => provider.iter().chain(checker).collect(),
+                _ => Vec::new(),
+            };
+            for provider in names.into_iter().filter_map(|n| Provider::from_string(n)) {
+                router.ensure_usable(provider).map_err(|e| {
+                    Error::InvalidParams(format!("workflow step '{}': {}", step.name, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
     /// Execute the next step in a workflow.
     pub async fn execute_workflow_step(&self, workflow_id: &str) -> Result<StepResult> {
+        self.execute_workflow_step_with(workflow_id, false).await
+    }
+
+    /// Execute the next step in a workflow, optionally in a visible browser.
+    ///
+    /// A step with a retry policy that fails with an error the policy
+    /// retries is run again after backing off, unless it paused the
+    /// workflow. A step that succeeds after retrying records its attempts
+    /// in its metadata.
+    pub async fn execute_workflow_step_with(
+        &self,
+        workflow_id: &str,
+        visible: bool,
+    ) -> Result<StepResult> {
+        self.sync_workflow(workflow_id).await;
+        let step_id = {
+            let workflows = self.workflows.read().await;
+            let workflow = workflows
+                .get(workflow_id)
+                .filter(|w| self.can_see(w))
+                .ok_or_else(|| Error::Workflow(format!("workflow not found: {}", workflow_id)))?;
+            if workflow.is_complete() {
+                return Err(Error::InvalidState("workflow already complete".into()));
+            }
+            workflow
+                .current()
+                .ok_or_else(|| Error::InvalidState("no current step".into()))?
+                .id
+                .clone()
+        };
+        self.execute_step_with_retries(workflow_id, &step_id, visible)
+            .await
+    }
+
+    /// Run the step `step_id` of a workflow, retrying it as its retry
+    /// policy says.
+    async fn execute_step_with_retries(
+        &self,
+        workflow_id: &str,
+        step_id: &str,
+        visible: bool,
+    ) -> Result<StepResult> {
+        let before = self.step_state(workflow_id, step_id).await;
+        let retry = self.workflows.read().await.get(workflow_id).and_then(|w| {
+            let step = w.steps.iter().find(|s| s.id == step_id)?;
+            Some((step.id.clone(), step.name.clone(), step.retry.clone()?))
+        });
+
+        let mut retried = Vec::new();
+        let mut result = loop {
+            let result = self.run_workflow_step(workflow_id, step_id, visible).await;
+            let (Err(e), Some((step_id, step_name, policy))) = (&result, &retry) else {
+                break result;
+            };
+            let attempt = retried.len() as u32 + 1;
+            if attempt > policy.retries
+                || !policy.retries_on(e)
+                || !self.step_retryable(workflow_id, step_id).await
+            {
+                break result;
+            }
+            let backoff = policy.backoff(attempt, e);
+            warn!(
+                "Step '{}' failed (attempt {}): {}; retrying in {:?}",
+                step_name, attempt, e, backoff
+            );
+            retried.push(RetriedAttempt {
+                attempt,
+                kind: e.kind().into(),
+                error: e.to_string(),
+                backoff_ms: backoff.as_millis() as u64,
+            });
+            tokio::time::sleep(backoff).await;
+        };
+
+        if let (Ok(output), Some((step_id, _, _))) = (&mut result, &retry) {
+            if !retried.is_empty() {
+                let attempts = serde_json::json!(retried.len() + 1);
+                let retried = serde_json::to_value(&retried)?;
+                output.metadata.insert(ATTEMPTS_KEY.into(), attempts.clone());
+                output.metadata.insert(RETRIED_KEY.into(), retried.clone());
+                let mut workflows = self.workflows.write().await;
+                let recorded = workflows
+                    .get_mut(workflow_id)
+                    .and_then(|w| w.steps.iter_mut().find(|s| &s.id == step_id))
+                    .and_then(|s| s.result.as_mut());
+                if let Some(recorded) = recorded {
+                    recorded.metadata.insert(ATTEMPTS_KEY.into(), attempts);
+                    recorded.metadata.insert(RETRIED_KEY.into(), retried);
+                }
+            }
+        }
+        self.persist_workflow(workflow_id).await;
+        self.emit_step_change(workflow_id, before).await;
+        result
+    }
+
+    /// Whether a step that just failed can be run again: it is still
+    /// pending or running in a workflow that is running, not paused for
+    /// review.
+    async fn step_retryable(&self, workflow_id: &str, step_id: &str) -> bool {
+        let workflows = self.workflows.read().await;
+        let Some(workflow) = workflows.get(workflow_id) else {
+            return false;
+        };
+        !workflow.is_complete()
+            && workflow.state != WorkflowState::Paused
+            && workflow.steps.iter().any(|s| {
+                s.id == step_id && matches!(s.state, StepState::Pending | StepState::Running)
+            })
+    }
+
+    /// Run a workflow's steps until it completes, a step waits for human
+    /// review or approval, a step fails, or `max_steps` steps have run,
+    /// and report the steps run and how the run ended.
+    ///
+    /// A failed step fails the workflow. Runs stop before a step while
+    /// workflow runs are paused, as auto runs do. Calling this again after
+    /// a review resumes the workflow.
+    pub async fn run_workflow(
+        &self,
+        workflow_id: &str,
+        visible: bool,
+        max_steps: usize,
+    ) -> Result<RunReport> {
+        let start = Instant::now();
+        let (outcome, steps) = if self.require_workflow(workflow_id).await?.is_graph() {
+            self.run_graph(workflow_id, visible, max_steps).await?
+        } else {
+            self.run_in_order(workflow_id, visible, max_steps).await?
+        };
+        let workflow = self.require_workflow(workflow_id).await?;
+        let duration_ms = start.elapsed().as_millis() as u64;
+        Ok(RunReport::new(&workflow, outcome, steps, duration_ms))
+    }
+
+    /// Run a workflow's steps one at a time, in order.
+    async fn run_in_order(
+        &self,
+        workflow_id: &str,
+        visible: bool,
+        max_steps: usize,
+    ) -> Result<(RunOutcome, Vec<RunStep>)> {
+        let mut steps = Vec::new();
+        let outcome = loop {
+            let workflow = self.require_workflow(workflow_id).await?;
+            if let WorkflowState::Failed(reason) = &workflow.state {
+                break RunOutcome::Failed {
+                    step: None,
+                    reason: reason.clone(),
+                };
+            }
+            let Some(step) = workflow.current().cloned() else {
+                break RunOutcome::Completed;
+            };
+            if step.state == StepState::WaitingForHuman {
+                break RunOutcome::AwaitingReview {
+                    step: step.name,
+                    reason: "waiting for human review".into(),
+                };
+            }
+            if steps.len() >= max_steps {
+                break RunOutcome::StepLimit { max_steps };
+            }
+            if let Some(reason) = self.pause_reason() {
+                break RunOutcome::Paused { reason };
+            }
+
+            match self.execute_workflow_step_with(workflow_id, visible).await {
+                Ok(result) => steps.push(RunStep::new(&step, &result)),
+                Err(e) => break self.stop_run(workflow_id, step, e).await?,
+            }
+        };
+        Ok((outcome, steps))
+    }
+
+    /// Run a graph workflow's steps as their dependencies complete, up to
+    /// `max_concurrent` independent steps at once. Once a step fails or
+    /// waits for review, no more steps start, and the run ends when the
+    /// running ones finish.
+    async fn run_graph(
+        &self,
+        workflow_id: &str,
+        visible: bool,
+        max_steps: usize,
+    ) -> Result<(RunOutcome, Vec<RunStep>)> {
+        let mut steps = Vec::new();
+        let mut started = 0;
+        let mut launched = HashSet::new();
+        let mut running = FuturesUnordered::new();
+        let mut outcome = None;
+        loop {
+            let workflow = self.require_workflow(workflow_id).await?;
+            if let WorkflowState::Failed(reason) = &workflow.state {
+                outcome.get_or_insert_with(|| RunOutcome::Failed {
+                    step: None,
+                    reason: reason.clone(),
+                });
+            }
+            if outcome.is_none() {
+                if let Some(reason) = self.pause_reason() {
+                    outcome = Some(RunOutcome::Paused { reason });
+                }
+            }
+            if outcome.is_none() && workflow.state != WorkflowState::Paused {
+                // Steps left running by an earlier call that failed are run
+                // again, as the current step of a workflow run in order is
+                let claimed = self
+                    .running_steps
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clone();
+                let mut ready = workflow.ready_steps();
+                ready.extend((0..workflow.steps.len()).filter(|&i| {
+                    let step = &workflow.steps[i];
+                    step.state == StepState::Running && !claimed.contains(&step.id)
+                }));
+                ready.sort_unstable();
+                for index in ready {
+                    if running.len() >= self.config.max_concurrent.max(1) || started >= max_steps {
+                        break;
+                    }
+                    let step = workflow.steps[index].clone();
+                    if !launched.insert(step.id.clone()) {
+                        continue;
+                    }
+                    started += 1;
+                    running.push(async move {
+                        let result = self
+                            .execute_step_with_retries(workflow_id, &step.id, visible)
+                            .await;
+                        (step, result)
+                    });
+                }
+            }
+
+            let Some((step, result)) = running.next().await else {
+                break;
+            };
+            launched.remove(&step.id);
+            match result {
+                Ok(result) => steps.push(RunStep::new(&step, &result)),
+                Err(e) => {
+                    let stopped = self.stop_run(workflow_id, step, e).await?;
+                    outcome.get_or_insert(stopped);
+                }
+            }
+        }
+
+        let outcome = match outcome {
+            Some(outcome) => outcome,
+            None => {
+                let workflow = self.require_workflow(workflow_id).await?;
+                let waiting = workflow
+                    .steps
+                    .iter()
+                    .find(|s| s.state == StepState::WaitingForHuman);
+                if workflow.state == WorkflowState::Completed {
+                    RunOutcome::Completed
+                } else if let Some(step) = waiting {
+                    RunOutcome::AwaitingReview {
+                        step: step.name.clone(),
+                        reason: "waiting for human review".into(),
+                    }
+                } else if started >= max_steps {
+                    RunOutcome::StepLimit { max_steps }
+                } else {
+                    RunOutcome::Paused {
+                        reason: "the steps left are running in another call".into(),
+                    }
+                }
+            }
+        };
+        Ok((outcome, steps))
+    }
+
+    /// How a run ends after `step` failed with `error`: awaiting review if
+    /// the step paused the workflow, or else failed, failing the step and
+    /// the workflow.
+    async fn stop_run(
+        &self,
+        workflow_id: &str,
+        step: WorkflowStep,
+        error: Error,
+    ) -> Result<RunOutcome> {
+        let reason = error.to_string();
+        let workflow = self.require_workflow(workflow_id).await?;
+        if workflow.state == WorkflowState::Paused {
+            return Ok(RunOutcome::AwaitingReview {
+                step: step.name,
+                reason,
+            });
+        }
+        let before = self.step_state(workflow_id, &step.id).await;
+        if let Some(workflow) = self.workflows.write().await.get_mut(workflow_id) {
+            if let Some(failed) = workflow.steps.iter_mut().find(|s| s.id == step.id) {
+                failed.fail(reason.clone());
+            }
+            workflow.fail(format!("step '{}': {}", step.name, reason));
+        }
+        self.persist_workflow(workflow_id).await;
+        self.emit_step_change(workflow_id, before).await;
+        Ok(RunOutcome::Failed {
+            step: Some(step.name),
+            reason,
+        })
+    }
+
+    /// ID and state of a workflow's current step.
+    async fn current_step_state(&self, workflow_id: &str) -> Option<(String, StepState)> {
+        let workflows = self.workflows.read().await;
+        let step = workflows.get(workflow_id)?.current()?;
+        Some((step.id.clone(), step.state.clone()))
+    }
+
+    /// ID and state of the step `step_id` of a workflow.
+    async fn step_state(&self, workflow_id: &str, step_id: &str) -> Option<(String, StepState)> {
+        let workflows = self.workflows.read().await;
+        let workflow = workflows.get(workflow_id)?;
+        let step = workflow.steps.iter().find(|s| s.id == step_id)?;
+        Some((step.id.clone(), step.state.clone()))
+    }
+
+    /// Publish the state a step reached, if it differs from `before`.
+    async fn emit_step_change(&self, workflow_id: &str, before: Option<(String, StepState)>) {
+        let Some((step_id, before)) = before else { return };
+        let workflows = self.workflows.read().await;
+        let Some(step) = workflows
+            .get(workflow_id)
+            .and_then(|w| w.steps.iter().find(|s| s.id == step_id))
+        else {
+            return;
+        };
+        if step.state == before {
+            return;
+        }
+        self.events.emit(OrchestratorEvent::StepStateChanged {
+            workflow_id: workflow_id.to_string(),
+            step_id,
+            step_name: step.name.clone(),
+            state: step.state.name().into(),
+            error: match &step.state {
+                StepState::Failed(reason) => Some(reason.clone()),
+                _ => None,
+            },
+        });
+    }
+
+    /// Run the step `step_id` of a workflow and advance the workflow.
+    ///
+    /// The step runs on a copy of the workflow, so other steps, including
+    /// other branches of a graph workflow, can run meanwhile; the step's
+    /// outcome is copied back when it finishes.
+    async fn run_workflow_step(
+        &self,
+        workflow_id: &str,
+        step_id: &str,
+        visible: bool,
+    ) -> Result<StepResult> {
+        let (mut snapshot, index, _claim) = {
+            let mut workflows = self.workflows.write().await;
+            let workflow = workflows
+                .get_mut(workflow_id)
+                .ok_or_else(|| Error::Workflow(format!("workflow not found: {}", workflow_id)))?;
+            if workflow.is_complete() {
+                return Err(Error::InvalidState("workflow already complete".into()));
+            }
+            let index = workflow
+                .steps
+                .iter()
+                .position(|s| s.id == step_id)
+                .ok_or_else(|| Error::InvalidState(format!("no step {}", step_id)))?;
+            let claim = RunningStep::claim(&self.running_steps, &workflow.steps[index])?;
+            let mut snapshot = workflow.clone();
+            snapshot.current_step = index;
+            workflow.steps[index].start();
+            (snapshot, index, claim)
+        };
+        let executed = self.execute_step(workflow_id, &mut snapshot, visible).await;
+
         let mut workflows = self.workflows.write().await;
         let workflow = workflows
             .get_mut(workflow_id)
-            .ok_or_else(|| Error::Workflow(format!("workflow not found: {}", workflow_id)))?;
+            .filter(|w| w.steps.get(index).is_some_and(|s| s.id == step_id))
+            .ok_or_else(|| Error::Workflow("workflow changed while its step ran".into()))?;
+        workflow.steps[index] = snapshot.steps[index].clone();
+        if snapshot.state == WorkflowState::Paused {
+            workflow.state = WorkflowState::Paused;
+            workflow.current_step = index;
+        } else if !workflow.is_complete() && workflow.state != WorkflowState::Paused {
+            workflow.state = snapshot.state;
+        }
+        workflow.updated_at = chrono::Utc::now();
+
+        let (result, jump) = executed?;
+        if workflow.is_graph() {
+            workflow.settle();
+        } else {
+            match jump {
+                Some(index) => workflow.jump_to(index)?,
+                None => workflow.advance()?,
+            }
+        }
+        Ok(result)
+    }
 
-        if workflow.is_complete() {
-            return Err(Error::InvalidState("workflow already complete".into()));
+    /// Run the current step of `workflow`, returning its result and the
+    /// index of the step a conditional step jumps to.
+    async fn execute_step(
+        &self,
+        workflow_id: &str,
+        workflow: &mut Workflow,
+        visible: bool,
+    ) -> Result<(StepResult, Option<usize>)> {
+        // An answer held for review over its low confidence is kept once
+        // the step is approved
+        let held = workflow
+            .current()
+            .filter(|step| step.approved)
+            .and_then(|step| Some((step.result.clone()?, step.review.clone())));
+        if let Some((mut held, review)) = held {
+            if let Some(review) = review {
+                held.metadata.insert(REVIEW_KEY.into(), serde_json::to_value(review)?);
+            }
+            if let Some(step) = workflow.current_mut() {
+                step.complete(held.clone());
+            }
+            return Ok((held, None));
         }
 
         // Get step config (clone to avoid borrow issues)
-        let step_config = workflow
+        let step = workflow
             .current()
-            .ok_or_else(|| Error::InvalidState("no current step".into()))?
-            .config
-            .clone();
+            .ok_or_else(|| Error::InvalidState("no current step".into()))?;
+        let mut step_config = step.config.clone();
+        interpolate_step(&mut step_config, workflow)?;
+        let step_approved = step.approved;
+        let review = step.review.clone();
+        let post_processors = step.post_processors.clone();
+        let write_to = step.write_to.clone();
+        if write_to.is_some() {
+            check_writer(self.tenant())?;
+        }
+        let confidence = step.confidence.clone();
+        let classification = step.classification;
+        let options = PromptOptions {
+            classification,
+            visible,
+            ..Default::default()
+        };
+
+        // Check the approval policy before running anything
+        if !step_approved {
+            let classification = self.guard.classify(step.classification);
+            let policy = &self.config.approval_policy;
+            let risk = StepRisk::assess(step, classification, &self.config.cost_model, policy);
+            let (decision, reason) = policy.evaluate(&risk);
+
+            if decision == ApprovalDecision::RequireApproval {
+                let message = format!(
+                    "step '{}' requires approval ({}, estimated cost ${:.4})",
+                    step.name, reason, risk.estimated_cost_usd
+                );
+                if let Some(step) = workflow.current_mut() {
+                    step.state = StepState::WaitingForHuman;
+                }
+                workflow.state = WorkflowState::Paused;
+                return Err(Error::Workflow(message));
+            }
+        }
 
         // Mark step as running
         if let Some(step) = workflow.current_mut() {
             step.start();
+            self.events.emit(OrchestratorEvent::StepStateChanged {
+                workflow_id: workflow_id.to_string(),
+                step_id: step.id.clone(),
+                step_name: step.name.clone(),
+                state: step.state.name().into(),
+                error: None,
+            });
         }
         workflow.state = WorkflowState::Running;
 
         let start = Instant::now();
-        let result = match &step_config {
+        let mut jump = None;
+        let mut result = match &step_config {
             StepConfig::Prompt { message, provider, context } => {
                 let provider = provider
                     .as_ref()
@@ -250,22 +3833,31 @@ impl AgentOrchestrator {
                         _ => None,
                     });
 
-                // Note: context is currently not used in prompt_provider
-                // Future: pass context as system message
-                let _context_for_future = context;
+                let options = PromptOptions {
+                    context: context.clone(),
+                    ..options
+                };
 
                 let response = if let Some(p) = provider {
-                    self.prompt_provider(p, message.clone()).await?
+                    self.prompt_provider_with(p, message.clone(), options).await?
                 } else {
-                    self.prompt(message.clone()).await?
+                    self.prompt_with(message.clone(), options).await?
                 };
 
+                let mut metadata = metadata::to_json(&response);
+                if let Some(report) = response.metadata.get(SANITIZATION_REPORT_KEY) {
+                    metadata.insert(
+                        "sanitization".into(),
+                        serde_json::from_str(report).unwrap_or_default(),
+                    );
+                }
+
                 StepResult {
                     output: response.text,
                     provider: Some(response.provider.to_string()),
                     responses: None,
                     duration_ms: start.elapsed().as_millis() as u64,
-                    metadata: HashMap::new(),
+                    metadata,
                 }
             }
             StepConfig::ParallelPrompt { message, providers } => {
@@ -282,7 +3874,9 @@ impl AgentOrchestrator {
                     })
                     .collect();
 
-                let results = self.parallel_prompt(message.clone(), providers).await?;
+                let results = self
+                    .parallel_prompt_with(message.clone(), providers, options)
+                    .await?;
                 
                 let responses: Vec<_> = results
                     .iter()
@@ -292,6 +3886,7 @@ impl AgentOrchestrator {
                             text: resp.text.clone(),
                             selected: false,
                             confidence: None,
+                            metadata: metadata::to_json(resp),
                         })
                     })
                     .collect();
@@ -301,17 +3896,33 @@ impl AgentOrchestrator {
                     .map(|r| format!("**{}**:\n{}", r.provider, r.text))
                     .collect::<Vec<_>>()
                     .join("\n\n---\n\n");
+                let sources = citations::merge(&responses);
+                let mut metadata = HashMap::new();
+                if !sources.is_empty() {
+                    metadata.insert(SOURCES_KEY.to_string(), serde_json::json!(sources));
+                }
 
                 StepResult {
                     output,
                     provider: None,
                     responses: Some(responses),
                     duration_ms: start.elapsed().as_millis() as u64,
-                    metadata: HashMap::new(),
+                    metadata,
                 }
             }
-            StepConfig::Consensus { message, min_providers } => {
-                let consensus = self.consensus_prompt(message.clone(), *min_providers).await?;
+            StepConfig::Consensus {
+                message,
+                min_providers,
+                strategy,
+                judge_provider,
+            } => {
+                let method = ConsensusOptions {
+                    strategy: *strategy,
+                    judge_provider: judge_provider.clone(),
+                };
+                let consensus = self
+                    .consensus_prompt_using(message.clone(), *min_providers, options, method)
+                    .await?;
 
                 StepResult {
                     output: consensus.consensus_text,
@@ -324,10 +3935,400 @@ impl AgentOrchestrator {
                             "agreement_score".into(),
                             serde_json::json!(consensus.agreement_score),
                         );
+                        m.insert(
+                            "strategy".into(),
+                            serde_json::json!(consensus.strategy),
+                        );
+                        m.insert(
+                            "disagreements".into(),
+                            serde_json::json!(consensus.disagreements),
+                        );
+                        if !consensus.sources.is_empty() {
+                            m.insert(SOURCES_KEY.into(), serde_json::json!(consensus.sources));
+                        }
                         m
                     },
                 }
             }
+            StepConfig::Research {
+                question,
+                providers,
+                max_rounds,
+                time_budget_secs,
+                max_cost_usd,
+            } => {
+                let research = ResearchOptions {
+                    providers: providers.iter().filter_map(|p| Provider::from_string(p)).collect(),
+                    max_rounds: max_rounds.unwrap_or(DEFAULT_MAX_ROUNDS),
+                    time_budget: time_budget_secs
+                        .map_or(DEFAULT_TIME_BUDGET, Duration::from_secs),
+                    max_cost_usd: *max_cost_usd,
+                    synthesizer: None,
+                };
+                let report = self.research(question, research, options).await?;
+
+                let responses = report
+                    .findings
+                    .iter()
+                    .map(|f| ProviderResponse {
+                        provider: f.provider.clone(),
+                        text: format!("**{}**\n\n{}", f.query, f.text),
+                        selected: false,
+                        confidence: None,
+                        metadata: HashMap::from([(
+                            metadata::RESPONSE_TOKENS_KEY.to_string(),
+                            serde_json::json!(f.tokens),
+                        )]),
+                    })
+                    .collect();
+                StepResult {
+                    output: report.to_markdown(),
+                    provider: None,
+                    responses: Some(responses),
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([
+                        (SOURCES_KEY.to_string(), serde_json::json!(report.sources)),
+                        ("rounds".to_string(), serde_json::json!(report.rounds)),
+                        ("stopped".to_string(), serde_json::json!(report.stopped)),
+                    ]),
+                }
+            }
+            StepConfig::FactCheck {
+                source_step,
+                focus,
+                provider,
+                max_claims,
+            } => {
+                let text = workflow
+                    .step_output(source_step.as_deref())
+                    .ok_or_else(|| {
+                        Error::Workflow(match source_step {
+                            Some(source) => format!("no completed step '{}' to fact-check", source),
+                            None => "no completed step to fact-check".into(),
+                        })
+                    })?
+                    .to_string();
+                let checker = provider.as_deref().and_then(Provider::from_string);
+                let report = self
+                    .fact_check(
+                        &text,
+                        focus.as_deref(),
+                        checker,
+                        max_claims.unwrap_or(DEFAULT_MAX_CLAIMS),
+                        options,
+                    )
+                    .await?;
+
+                let citations: Vec<_> = report
+                    .checks
+                    .iter()
+                    .flat_map(|c| &c.sources)
+                    .map(|url| citations::Citation {
+                        url: citations::normalize_url(url),
+                        title: None,
+                    })
+                    .collect();
+                let mut merged = SourceList::default();
+                merged.add(&report.checker, &citations);
+                StepResult {
+                    output: report.annotated,
+                    provider: Some(report.checker),
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([
+                        ("checks".to_string(), serde_json::json!(report.checks)),
+                        (SOURCES_KEY.to_string(), serde_json::json!(merged.into_vec())),
+                    ]),
+                }
+            }
+            StepConfig::ValidatePatch {
+                source_step,
+                check,
+                provider,
+                max_refinements,
+            } => {
+                if *check {
+                    check_runner(self.tenant())?;
+                }
+                let text = workflow.step_output(source_step.as_deref()).ok_or_else(|| {
+                    Error::Workflow(match source_step {
+                        Some(source) => format!("no completed step '{}' to validate", source),
+                        None => "no completed step to validate".into(),
+                    })
+                })?;
+                let diff = extract_diff(text)
+                    .ok_or_else(|| Error::Workflow("the step output contains no diff".into()))?;
+                let report = self
+                    .validate_patch(
+                        diff,
+                        *check,
+                        provider.as_deref().and_then(Provider::from_string),
+                        max_refinements.unwrap_or(DEFAULT_MAX_REFINEMENTS),
+                        options,
+                    )
+                    .await?;
+
+                // A failing patch fails the step, so no later step applies it.
+                if let Some(last) = report.attempts.last().filter(|_| !report.passed()) {
+                    let reason = format!(
+                        "patch {} after {} attempt(s):\n{}",
+                        last.summary(),
+                        report.attempts.len(),
+                        last.errors
+                    );
+                    if let Some(step) = workflow.current_mut() {
+                        step.fail(reason.clone());
+                    }
+                    return Err(Error::Workflow(reason));
+                }
+                StepResult {
+                    output: report.to_markdown(),
+                    provider: None,
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([(
+                        "attempts".to_string(),
+                        serde_json::json!(report.attempts),
+                    )]),
+                }
+            }
+            StepConfig::Map {
+                message,
+                items,
+                provider,
+                json,
+                checker,
+            } => {
+                if items.is_empty() {
+                    return Err(Error::Workflow("the map step has no items".into()));
+                }
+                let provider = provider.as_deref().and_then(Provider::from_string);
+                let checker = checker.as_deref().and_then(Provider::from_string);
+                let mut answers = Vec::new();
+                for item in items {
+                    let prompt = item_prompt(message, item);
+                    let response = match provider {
+                        Some(p) => {
+                            self.prompt_provider_with(p, prompt.clone(), options.clone())
+                                .await
+                        }
+                        None => self.prompt_with(prompt.clone(), options.clone()).await,
+                    };
+                    let mut answer = match response {
+                        Ok(response) => MapAnswer {
+                            item: item.name.clone(),
+                            provider: Some(response.provider.to_string()),
+                            text: response.text,
+                            checked_by: None,
+                            failed: false,
+                            record: None,
+                        },
+                        Err(e) => MapAnswer {
+                            item: item.name.clone(),
+                            provider: None,
+                            text: e.to_string(),
+                            checked_by: None,
+                            failed: true,
+                            record: None,
+                        },
+                    };
+                    // An answer the checker cannot check is kept as written.
+                    if let Some(checker) = checker.filter(|_| !answer.failed) {
+                        let check = check_prompt(&prompt, &answer.text);
+                        match self.prompt_provider_with(checker, check, options.clone()).await {
+                            Ok(checked) => {
+                                answer.text = checked.text;
+                                answer.checked_by = Some(checked.provider.to_string());
+                            }
+                            Err(e) => warn!("Cross-check of '{}' failed: {}", item.name, e),
+                        }
+                    }
+                    if *json && !answer.failed {
+                        answer.record = parse_record(&answer.text);
+                    }
+                    answers.push(answer);
+                }
+                if answers.iter().all(|a| a.failed) {
+                    return Err(Error::Workflow(format!(
+                        "every item of the map step failed, the first with: {}",
+                        answers[0].text
+                    )));
+                }
+                StepResult {
+                    output: render_map(&answers),
+                    provider: None,
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([("answers".to_string(), serde_json::json!(answers))]),
+                }
+            }
+            StepConfig::Aggregate {
+                message,
+                source_steps,
+                provider,
+            } => {
+                // Each provider of a multi-provider step is a separate source.
+                let mut outputs: Vec<(String, &str)> = Vec::new();
+                for step in workflow.source_steps(source_steps)? {
+                    let Some(result) = &step.result else { continue };
+                    match &result.responses {
+                        Some(responses) => outputs.extend(responses.iter().map(|r| {
+                            (format!("{} ({})", step.name, r.provider), r.text.as_str())
+                        })),
+                        None => outputs.push((step.name.clone(), result.output.as_str())),
+                    }
+                }
+                let aggregation = Aggregation::collect(
+                    outputs.iter().map(|(source, text)| (source.as_str(), *text)),
+                );
+                if aggregation.findings.is_empty() {
+                    return Err(Error::Workflow("no findings to aggregate".into()));
+                }
+
+                let prompt = aggregation.prompt(message);
+                let response = match provider.as_deref().and_then(Provider::from_string) {
+                    Some(p) => self.prompt_provider_with(p, prompt, options).await?,
+                    None => self.prompt_with(prompt, options).await?,
+                };
+                let mut metadata = metadata::to_json(&response);
+                metadata.insert("findings".into(), serde_json::json!(aggregation.findings));
+                metadata.insert("duplicates".into(), serde_json::json!(aggregation.duplicates));
+                StepResult {
+                    output: response.text,
+                    provider: Some(response.provider.to_string()),
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata,
+                }
+            }
+            StepConfig::Assemble {
+                title,
+                source_steps,
+            } => {
+                let outputs: Vec<&str> = workflow
+                    .source_steps(source_steps)?
+                    .into_iter()
+                    .filter_map(|s| Some(s.result.as_ref()?.output.as_str()))
+                    .collect();
+                if outputs.is_empty() {
+                    return Err(Error::Workflow("no step output to assemble".into()));
+                }
+                StepResult {
+                    output: assemble(title, &outputs),
+                    provider: None,
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([(
+                        "sources".to_string(),
+                        serde_json::json!(outputs.len()),
+                    )]),
+                }
+            }
+            StepConfig::Extract {
+                message,
+                fields,
+                source_steps,
+                providers,
+                min_agreement,
+            } => {
+                let outputs: Vec<&str> = workflow
+                    .source_steps(source_steps)?
+                    .into_iter()
+                    .filter_map(|s| Some(s.result.as_ref()?.output.as_str()))
+                    .collect();
+                if outputs.is_empty() {
+                    return Err(Error::Workflow("no step output to extract records from".into()));
+                }
+                let prompt = extraction_prompt(message, fields, &outputs.join("\n\n"));
+                let providers: Vec<Provider> =
+                    providers.iter().filter_map(|p| Provider::from_string(p)).collect();
+                let results = if providers.is_empty() {
+                    let response = self.prompt_with(prompt, options).await?;
+                    vec![(response.provider, Ok(response))]
+                } else {
+                    self.parallel_prompt_with(prompt, providers, options).await?
+                };
+
+                let mut answers = Vec::new();
+                let mut failures = Vec::new();
+                let mut responses = Vec::new();
+                for (provider, result) in results {
+                    let response = match result {
+                        Ok(response) => response,
+                        Err(e) => {
+                            failures.push((provider.to_string(), e.to_string()));
+                            continue;
+                        }
+                    };
+                    match parse_records(&response.text, fields) {
+                        Ok(records) => answers.push((provider.to_string(), records)),
+                        Err(e) => failures.push((provider.to_string(), e)),
+                    }
+                    responses.push(ProviderResponse {
+                        provider: provider.to_string(),
+                        text: response.text.clone(),
+                        selected: false,
+                        confidence: None,
+                        metadata: metadata::to_json(&response),
+                    });
+                }
+                if answers.is_empty() {
+                    return Err(Error::Workflow(format!(
+                        "no provider answered with records: {}",
+                        failures
+                            .iter()
+                            .map(|(p, e)| format!("{}: {}", p, e))
+                            .collect::<Vec<_>>()
+                            .join("; ")
+                    )));
+                }
+
+                let min_agreement = min_agreement.unwrap_or(answers.len() / 2 + 1);
+                let mut extraction = Extraction::vote(answers, fields, min_agreement);
+                extraction.failures = failures;
+                StepResult {
+                    output: extraction.to_markdown(),
+                    provider: None,
+                    responses: Some(responses),
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([
+                        ("records".to_string(), serde_json::json!(extraction.records)),
+                        ("rejected".to_string(), serde_json::json!(extraction.rejected)),
+                        ("failures".to_string(), serde_json::json!(extraction.failures)),
+                        ("min_agreement".to_string(), serde_json::json!(min_agreement)),
+                    ]),
+                }
+            }
+            StepConfig::GitHubComment {
+                pull_request,
+                source_step,
+            } => {
+                let body = workflow.step_output(source_step.as_deref()).ok_or_else(|| {
+                    Error::Workflow(match source_step {
+                        Some(source) => format!("no completed step '{}' to post", source),
+                        None => "no completed step to post".into(),
+                    })
+                })?;
+                let target: IssueRef = pull_request.parse()?;
+                let url = GitHubClient::from_env().comment(&target, body).await?;
+                StepResult {
+                    output: format!("Posted a comment on {}: {}", target, url),
+                    provider: None,
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([("comment_url".to_string(), serde_json::json!(url))]),
+                }
+            }
+            StepConfig::HumanReview { prompt } if step_approved => StepResult {
+                output: match review.as_ref().and_then(|r| r.comments.as_ref()) {
+                    Some(comments) => format!("Approved: {}\n\nComments: {}", prompt, comments),
+                    None => format!("Approved: {}", prompt),
+                },
+                provider: None,
+                responses: None,
+                duration_ms: start.elapsed().as_millis() as u64,
+                metadata: HashMap::new(),
+            },
             StepConfig::HumanReview { prompt: _ } => {
                 // Set step to waiting and return
                 let step = workflow.current_mut().unwrap();
@@ -336,23 +4337,305 @@ impl AgentOrchestrator {
                 
                 return Err(Error::Workflow("waiting for human review".into()));
             }
-            _ => {
-                return Err(Error::Workflow("unsupported step type".into()));
+            StepConfig::Conditional {
+                condition,
+                then_step,
+                else_step,
+            } => {
+                let holds = condition.parse::<Condition>()?.evaluate(workflow)?;
+                let target = if holds { Some(then_step) } else { else_step.as_ref() };
+                if let Some(target) = target {
+                    jump = Some(workflow.jump_target(workflow.current_step, target)?);
+                }
+                let next = jump.or(Some(workflow.current_step + 1));
+                let next = next.and_then(|i| workflow.steps.get(i)).map(|s| s.name.clone());
+                StepResult {
+                    output: match &next {
+                        Some(name) => format!("Condition {}; continuing at '{}'", holds, name),
+                        None => format!("Condition {}; no steps left", holds),
+                    },
+                    provider: None,
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([
+                        ("condition".to_string(), serde_json::json!(holds)),
+                        ("next_step".to_string(), serde_json::json!(next)),
+                    ]),
+                }
+            }
+            StepConfig::Tool {
+                tool_name,
+                arguments,
+            } => {
+                self.check_step_tool(tool_name)?;
+                let variables: HashMap<String, String> = workflow
+                    .context
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            serde_json::Value::String(text) => text.clone(),
+                            other => other.to_string(),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect();
+                let mut arguments = arguments.clone();
+                fill_json_variables(&mut arguments, &variables);
+                let context = ToolContext {
+                    orchestrator: Arc::new(self.clone()),
+                    visible,
+                };
+                let called = self.tools.call(tool_name, arguments, &context).await?;
+                let output = called
+                    .content
+                    .iter()
+                    .filter_map(|item| match item {
+                        ContentItem::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                if called.is_error {
+                    return Err(Error::Workflow(format!("{} failed: {}", tool_name, output)));
+                }
+                StepResult {
+                    output,
+                    provider: None,
+                    responses: None,
+                    duration_ms: start.elapsed().as_millis() as u64,
+                    metadata: HashMap::from([
+                        (TOOL_KEY.to_string(), serde_json::json!(tool_name)),
+                        (TOOL_RESULT_KEY.to_string(), serde_json::to_value(&called)?),
+                    ]),
+                }
             }
         };
 
-        // Mark step complete and advance
+        if !post_processors.is_empty() {
+            result.output = postprocess::apply(&result.output, &post_processors);
+            for response in result.responses.iter_mut().flatten() {
+                response.text = postprocess::apply(&response.text, &post_processors);
+            }
+        }
+        if let Some(path) = &write_to {
+            let written = write_output(path, &result.output).await?;
+            result
+                .metadata
+                .insert(WRITTEN_TO_KEY.into(), serde_json::to_value(written)?);
+        }
+        if let (Some(check), Some(question)) = (&confidence, step_config.question()) {
+            let score = self
+                .rate_confidence(check, question, &mut result, classification)
+                .await?;
+            if score < check.min_confidence {
+                result.metadata.insert(LOW_CONFIDENCE_KEY.into(), true.into());
+                let step = workflow.current_mut().unwrap();
+                let reason = format!(
+                    "step '{}' answer confidence {:.2} is below {:.2}",
+                    step.name, score, check.min_confidence
+                );
+                match check.on_low {
+                    LowConfidence::Continue => warn!("{}", reason),
+                    LowConfidence::Fail => return Err(Error::Workflow(reason)),
+                    LowConfidence::Review => {
+                        // Hold the answer until a reviewer approves it
+                        step.result = Some(result);
+                        step.state = StepState::WaitingForHuman;
+                        workflow.state = WorkflowState::Paused;
+                        return Err(Error::Workflow(format!("{}; awaiting review", reason)));
+                    }
+                }
+            }
+        }
+
+        if let Some(review) = review {
+            result.metadata.insert(REVIEW_KEY.into(), serde_json::to_value(review)?);
+        }
+
         let step = workflow.current_mut().unwrap();
         step.complete(result.clone());
-        workflow.advance()?;
+        Ok((result, jump))
+    }
 
-        Ok(result)
+    /// Approve the current step of a paused workflow so the next
+    /// `execute_workflow_step` call runs it.
+    pub async fn approve_step(&self, workflow_id: &str) -> Result<()> {
+        self.sync_workflow(workflow_id).await;
+        let mut workflows = self.workflows.write().await;
+        let workflow = workflows
+            .get_mut(workflow_id)
+            .filter(|w| self.can_see(w))
+            .ok_or_else(|| Error::Workflow(format!("workflow not found: {}", workflow_id)))?;
+
+        let step = workflow
+            .current_mut()
+            .ok_or_else(|| Error::InvalidState("no current step".into()))?;
+        if step.state != StepState::WaitingForHuman {
+            return Err(Error::InvalidState("current step is not awaiting approval".into()));
+        }
+
+        step.approved = true;
+        step.state = StepState::Pending;
+        workflow.state = WorkflowState::Running;
+        workflow.updated_at = chrono::Utc::now();
+        drop(workflows);
+        self.persist_workflow(workflow_id).await;
+        Ok(())
+    }
+
+    /// Resolve the current step of a paused workflow with a reviewer's
+    /// decision, recorded in the step result. An approval resumes the
+    /// workflow: a human review step, or an answer held for review, is
+    /// completed at once and its result returned, while a step paused by the
+    /// approval policy runs on the next `execute_workflow_step` call. A
+    /// rejection fails the step and the workflow.
+    pub async fn review_step(
+        &self,
+        workflow_id: &str,
+        review: StepReview,
+    ) -> Result<Option<StepResult>> {
+        self.sync_workflow(workflow_id).await;
+        let before = self.current_step_state(workflow_id).await;
+        let mut workflows = self.workflows.write().await;
+        let workflow = workflows
+            .get_mut(workflow_id)
+            .filter(|w| self.can_see(w))
+            .ok_or_else(|| Error::Workflow(format!("workflow not found: {}", workflow_id)))?;
+
+        let step = workflow
+            .current_mut()
+            .ok_or_else(|| Error::InvalidState("no current step".into()))?;
+        if step.state != StepState::WaitingForHuman {
+            return Err(Error::InvalidState("current step is not awaiting review".into()));
+        }
+
+        step.review = Some(review.clone());
+        let resumes_now =
+            matches!(step.config, StepConfig::HumanReview { .. }) || step.result.is_some();
+        if review.approved {
+            step.approved = true;
+            step.state = StepState::Pending;
+            workflow.state = WorkflowState::Running;
+        } else {
+            let mut result = step.result.take().unwrap_or_else(|| StepResult {
+                output: String::new(),
+                provider: None,
+                responses: None,
+                duration_ms: 0,
+                metadata: HashMap::new(),
+            });
+            if result.output.is_empty() {
+                result.output = review.describe();
+            }
+            result.metadata.insert(REVIEW_KEY.into(), serde_json::to_value(&review)?);
+            let reason = format!("step '{}': {}", step.name, review.describe());
+            step.result = Some(result);
+            step.fail(reason.clone());
+            workflow.fail(reason);
+        }
+        workflow.updated_at = chrono::Utc::now();
+        drop(workflows);
+        self.persist_workflow(workflow_id).await;
+        self.emit_step_change(workflow_id, before).await;
+
+        if review.approved && resumes_now {
+            return self.execute_workflow_step(workflow_id).await.map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Get the saved prompt library.
+    pub fn prompt_library(&self) -> &PromptLibrary {
+        &self.prompts
+    }
+
+    /// Get the conversation transcripts.
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
     }
 
-    /// Get a workflow by ID.
+    /// Get the background job queue.
+    pub fn jobs(&self) -> &JobQueue {
+        &self.jobs
+    }
+
+    /// Get the provider cost model.
+    pub fn cost_model(&self) -> &CostModel {
+        &self.config.cost_model
+    }
+
+    async fn require_workflow(&self, id: &str) -> Result<Workflow> {
+        self.get_workflow(id)
+            .await
+            .ok_or_else(|| Error::Workflow(format!("workflow not found: {}", id)))
+    }
+
+    /// Get a workflow by ID, if this handle's tenant may see it.
     pub async fn get_workflow(&self, id: &str) -> Option<Workflow> {
+        self.sync_workflow(id).await;
         let workflows = self.workflows.read().await;
-        workflows.get(id).cloned()
+        workflows.get(id).filter(|w| self.can_see(w)).cloned()
+    }
+
+    /// Every workflow this handle's tenant may see, including ones started
+    /// by other instances sharing the backend, most recently updated first.
+    pub async fn list_workflows(&self) -> Vec<Workflow> {
+        match self.workflow_store.load_all::<Workflow>() {
+            Ok(stored) => {
+                let mut workflows = self.workflows.write().await;
+                for workflow in stored {
+                    let newer = workflows
+                        .get(&workflow.id)
+                        .is_none_or(|w| workflow.updated_at > w.updated_at);
+                    if newer {
+                        workflows.insert(workflow.id.clone(), workflow);
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to load workflows: {}", e),
+        }
+        let mut workflows: Vec<Workflow> = self
+            .workflows
+            .read()
+            .await
+            .values()
+            .filter(|w| self.can_see(w))
+            .cloned()
+            .collect();
+        workflows.sort_by_key(|w| std::cmp::Reverse(w.updated_at));
+        workflows
+    }
+
+    /// Workflows this handle's tenant may see with a step rendered from the
+    /// saved prompt `name`, oldest first.
+    pub async fn workflows_using_prompt(&self, name: &str) -> Vec<Workflow> {
+        let mut workflows: Vec<Workflow> = self
+            .workflows
+            .read()
+            .await
+            .values()
+            .filter(|w| {
+                self.can_see(w)
+                    && w.steps
+                        .iter()
+                        .any(|s| s.saved_prompt.as_ref().is_some_and(|p| p.name == name))
+            })
+            .cloned()
+            .collect();
+        workflows.sort_by_key(|w| w.created_at);
+        workflows
+    }
+
+    /// Snapshot of active workflows, paused reviews, and budget usage for
+    /// the status file.
+    pub async fn status_snapshot(&self) -> StatusSnapshot {
+        let jobs = self.jobs.list().unwrap_or_else(|e| {
+            warn!("Failed to list jobs for the status file: {}", e);
+            Vec::new()
+        });
+        let quotas = self.router.read().await.quota_usage();
+        let workflows: Vec<Workflow> = self.workflows.read().await.values().cloned().collect();
+        StatusSnapshot::build(&workflows, &jobs, &self.config.cost_model, quotas)
     }
 
     /// Get orchestrator status.
@@ -374,26 +4657,179 @@ impl Default for AgentOrchestrator {
     }
 }
 
+/// Key of the preferences record changed at runtime.
+const PREFERENCES_KEY: &str = "router";
+
+/// Default provider preferences with the configured priority overrides.
+fn configured_preferences(config: &OrchestratorConfig) -> ProviderPreferences {
+    let mut preferences = ProviderPreferences::default();
+    for (name, priority) in &config.provider_priorities {
+        if let Some(provider) = Provider::from_string(name) {
+            preferences.set_priority(provider, *priority);
+        }
+    }
+    preferences
+}
+
 impl Clone for AgentOrchestrator {
     fn clone(&self) -> Self {
         Self {
-            puppet: self.puppet.clone(),
+            browsers: self.browsers.clone(),
+            provider_calls: self.provider_calls.clone(),
+            fair_share: self.fair_share.clone(),
             router: self.router.clone(),
             workflows: self.workflows.clone(),
+            running_steps: self.running_steps.clone(),
+            guard: self.guard.clone(),
+            last_activity: self.last_activity.clone(),
+            interventions: self.interventions.clone(),
+            events: self.events.clone(),
+            workflow_store: self.workflow_store.clone(),
+            diagnostics_store: self.diagnostics_store.clone(),
+            stats_store: self.stats_store.clone(),
+            preferences: self.preferences.clone(),
+            prompts: self.prompts.clone(),
+            sessions: self.sessions.clone(),
+            jobs: self.jobs.clone(),
+            shadow: self.shadow.clone(),
+            canary: self.canary.clone(),
+            replay: self.replay.clone(),
+            webhooks: self.webhooks.clone(),
+            tenants: self.tenants.clone(),
+            spend: self.spend.clone(),
+            maintenance: self.maintenance.clone(),
+            idempotency: self.idempotency.clone(),
+            requests: self.requests.clone(),
+            tools: self.tools.clone(),
+            in_flight: self.in_flight.clone(),
+            tenant: self.tenant.clone(),
+            request_class: self.request_class,
+            dispatched: self.dispatched.clone(),
+            #[cfg(feature = "chaos")]
+            chaos: self.chaos.clone(),
+            backends: self.backends.clone(),
             config: self.config.clone(),
         }
     }
 }
 
+/// Step metadata key holding the tool a tool step called.
+pub const TOOL_KEY: &str = "tool";
+
+/// Step metadata key holding the result of a tool step's call.
+pub const TOOL_RESULT_KEY: &str = "tool_result";
+
+/// Response metadata key holding a summary of sanitized content.
+pub const SANITIZATION_SUMMARY_KEY: &str = "sanitization_summary";
+
+/// Response metadata key holding the JSON list of sanitized content.
+pub const SANITIZATION_REPORT_KEY: &str = "sanitization_report";
+
 /// Orchestrator configuration.
 #[derive(Debug, Clone)]
 pub struct OrchestratorConfig {
     /// Run browsers in headless mode.
     pub headless: bool,
-    /// Default timeout for operations.
+    /// Deadline for a single provider request, including rate-limit retries.
     pub timeout: Duration,
     /// Maximum concurrent requests.
     pub max_concurrent: usize,
+    /// Browser contexts kept per provider account, bounding how many
+    /// prompts run in browsers at once.
+    pub browser_pool_size: usize,
+    /// Times an empty answer is asked for again before failing.
+    pub incomplete_retries: u32,
+    /// Continuations requested for an answer cut short, such as by the
+    /// provider's output limit, before it is returned incomplete.
+    pub max_continuations: u32,
+    /// Which providers may receive each data classification.
+    pub classification_policy: ClassificationPolicy,
+    /// Cipher for encrypting persisted payloads (disabled when `None`).
+    pub cipher: Option<PayloadCipher>,
+    /// Tamper-evident log of provider dispatches (disabled when `None`).
+    pub audit_log: Option<Arc<AuditLog>>,
+    /// What to strip from provider responses before returning them.
+    pub sanitization: SanitizationPolicy,
+    /// Close browser sessions and drop credentials after this much idle time.
+    pub idle_timeout: Option<Duration>,
+    /// Periodic probing of every provider, even when idle (disabled when
+    /// `None`).
+    pub health_sweep: Option<HealthSweepPolicy>,
+    /// Fair-share scheduling of contended providers across tenants
+    /// (first come, first served when `None`).
+    pub fair_share: Option<FairSharePolicy>,
+    /// Handling of captchas and verification walls.
+    pub intervention: InterventionPolicy,
+    /// Judge grading of multi-provider responses (disabled when `None`).
+    pub grading: Option<GradingPolicy>,
+    /// Routing priority overrides keyed by provider name (e.g. from an eval report).
+    pub provider_priorities: HashMap<String, u32>,
+    /// Maximum requests per minute keyed by provider name, enforced across
+    /// every instance sharing the storage backend.
+    pub provider_quotas: HashMap<String, u32>,
+    /// Daily provider limits and the share of each quota background work
+    /// may use.
+    pub schedule: SchedulePolicy,
+    /// Strength (0.0-1.0) of each provider per language code, keyed by
+    /// provider name; routing favors providers strong in the prompt's language.
+    pub language_weights: HashMap<String, HashMap<String, f64>>,
+    /// Response time objectives keyed by provider name; providers that keep
+    /// missing theirs are demoted in routing.
+    pub latency_slos: HashMap<String, LatencySlo>,
+    /// Disable all cloud and browser providers (air-gapped operation).
+    pub local_only: bool,
+    /// Rules deciding which workflow steps need human approval.
+    pub approval_policy: ApprovalPolicy,
+    /// Provider pricing used for cost estimates.
+    pub cost_model: CostModel,
+    /// Workspace whose state directory holds persisted state (in-memory
+    /// only when `None`).
+    pub workspace: Option<Workspace>,
+    /// Storage backend, overriding the workspace's file storage.
+    pub storage: Option<Arc<dyn Storage>>,
+    /// Secret signing webhook deliveries (unsigned when `None`).
+    pub webhook_secret: Option<String>,
+    /// Hosts and classifications webhook callbacks are allowed for.
+    pub webhook: WebhookPolicy,
+    /// Champion/challenger shadow testing of routed prompts (disabled when
+    /// `None`).
+    pub shadow: Option<ShadowPolicy>,
+    /// Routing priorities rolled out to a share of routed prompts, rolled
+    /// back automatically if they regress (disabled when `None`).
+    pub canary: Option<CanaryPolicy>,
+    /// Token budget for prompts composed from context sections.
+    pub context_budget: ContextBudget,
+    /// Shell command run on a scratch copy of the workspace to check
+    /// provider-generated patches, such as `cargo check` (patches are only
+    /// checked to apply when `None`).
+    pub patch_check_command: Option<String>,
+    /// Tenants, their API keys, budgets, and quotas.
+    pub tenants: TenantPolicy,
+    /// Provider accounts and the workspaces and tenants using them.
+    pub accounts: AccountPolicy,
+    /// How prompts are rewritten for each provider.
+    pub prompt_adapters: PromptAdapters,
+    /// How prompts declined by a provider are rephrased or rerouted.
+    pub refusal_policy: RefusalPolicy,
+    /// Routing objective weight profiles and which task uses which.
+    pub routing_profiles: RoutingProfiles,
+    /// Alerting on spend well above its baseline (disabled when `None`).
+    pub cost_anomaly: Option<AnomalyPolicy>,
+    /// Scripted provider replies used instead of a browser (tests only).
+    pub mock_providers: Option<MockProviders>,
+    /// Record provider responses, or replay recorded ones instead of calling
+    /// providers.
+    pub replay: ReplayMode,
+    /// Seed for routing tie-breaks and fault injection, making runs
+    /// reproducible (unseeded when `None`).
+    pub seed: Option<u64>,
+    /// Fault injection for resilience testing (disabled when `None`).
+    #[cfg(feature = "chaos")]
+    pub chaos: Option<ChaosPolicy>,
+    /// API backends keyed by the name of the provider they answer for,
+    /// used instead of its web app.
+    #[cfg(feature = "api-providers")]
+    pub api_providers: HashMap<String, ApiProviderConfig>,
 }
 
 impl Default for OrchestratorConfig {
@@ -402,10 +4838,256 @@ impl Default for OrchestratorConfig {
             headless: true,
             timeout: Duration::from_secs(120),
             max_concurrent: 5,
+            browser_pool_size: 2,
+            incomplete_retries: 1,
+            max_continuations: 3,
+            classification_policy: ClassificationPolicy::default(),
+            cipher: None,
+            audit_log: None,
+            sanitization: SanitizationPolicy::default(),
+            idle_timeout: None,
+            health_sweep: None,
+            fair_share: None,
+            intervention: InterventionPolicy::default(),
+            grading: None,
+            provider_priorities: HashMap::new(),
+            provider_quotas: HashMap::new(),
+            schedule: SchedulePolicy::default(),
+            language_weights: HashMap::new(),
+            latency_slos: HashMap::new(),
+            local_only: false,
+            approval_policy: ApprovalPolicy::default(),
+            cost_model: CostModel::default(),
+            workspace: None,
+            storage: None,
+            webhook_secret: None,
+            webhook: WebhookPolicy::default(),
+            shadow: None,
+            canary: None,
+            context_budget: ContextBudget::default(),
+            patch_check_command: None,
+            tenants: TenantPolicy::default(),
+            accounts: AccountPolicy::default(),
+            prompt_adapters: PromptAdapters::default(),
+            refusal_policy: RefusalPolicy::default(),
+            routing_profiles: RoutingProfiles::default(),
+            cost_anomaly: None,
+            mock_providers: None,
+            replay: ReplayMode::Off,
+            seed: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+            #[cfg(feature = "api-providers")]
+            api_providers: HashMap::new(),
+        }
+    }
+}
+
+/// Counts a provider call in flight until dropped, including when the call
+/// is cancelled.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// IDs of the workflow steps running.
+type RunningSteps = Arc<std::sync::Mutex<HashSet<String>>>;
+
+/// Marks a workflow step as running until dropped, so it is not run twice
+/// at once.
+struct RunningStep {
+    steps: RunningSteps,
+    id: String,
+}
+
+impl RunningStep {
+    /// Mark `step` as running, failing if it already is.
+    fn claim(steps: &RunningSteps, step: &WorkflowStep) -> Result<Self> {
+        let mut running = steps.lock().unwrap_or_else(|e| e.into_inner());
+        if !running.insert(step.id.clone()) {
+            return Err(Error::InvalidState(format!(
+                "step '{}' is already running",
+                step.name
+            )));
+        }
+        Ok(Self {
+            steps: steps.clone(),
+            id: step.id.clone(),
+        })
+    }
+}
+
+impl Drop for RunningStep {
+    fn drop(&mut self) {
+        self.steps
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+/// Long-lived browser contexts of one account, each started on first use
+/// and leased to one call at a time.
+#[derive(Clone)]
+struct BrowserPool {
+    /// One permit per context not leased.
+    free: Arc<Semaphore>,
+    /// The contexts, empty until started or after failing.
+    contexts: Vec<Arc<Mutex<Option<WebPuppet>>>>,
+}
+
+impl BrowserPool {
+    fn new(size: usize) -> Self {
+        Self {
+            free: Arc::new(Semaphore::new(size)),
+            contexts: (0..size).map(|_| Arc::default()).collect(),
+        }
+    }
+
+    /// Lease a context, waiting for one to be free.
+    async fn lease(&self) -> Result<BrowserLease> {
+        let permit = self
+            .free
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::Internal("browser pool closed".into()))?;
+        let (context, browser) = self
+            .contexts
+            .iter()
+            .enumerate()
+            .find_map(|(context, slot)| Some((context, slot.clone().try_lock_owned().ok()?)))
+            .ok_or_else(|| Error::Internal("no free browser context".into()))?;
+        Ok(BrowserLease {
+            context,
+            browser,
+            _permit: permit,
+        })
+    }
+
+    /// Close every context once the calls using them finish. Returns true if
+    /// any was running.
+    async fn close(&self) -> bool {
+        let Ok(_all) = self.free.acquire_many(self.contexts.len() as u32).await else {
+            return false;
+        };
+        let mut closed = false;
+        for slot in &self.contexts {
+            if let Some(puppet) = slot.lock().await.take() {
+                puppet.close().await.ok();
+                closed = true;
+            }
+        }
+        closed
+    }
+}
+
+/// A browser context leased to one call.
+struct BrowserLease {
+    /// Position of the context in its pool.
+    context: usize,
+    /// The context's browser (empty after it failed).
+    browser: OwnedMutexGuard<Option<WebPuppet>>,
+    /// Frees the context for the next call when dropped.
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Where a prompt's provider calls go.
+enum ProviderSession {
+    /// A browser session driving the providers' web apps, closed after the
+    /// call.
+    Browser(Box<WebPuppet>),
+    /// A long-lived browser context kept open between calls, leased to
+    /// this call.
+    Pooled(BrowserLease),
+    /// A backend registered for the provider, such as its API, or scripted
+    /// or recorded replies standing in for it.
+    Backend(Arc<dyn ProviderBackend>),
+}
+
+impl ProviderSession {
+    /// The backend the session's calls go to.
+    fn backend(&self) -> Result<&dyn ProviderBackend> {
+        match self {
+            Self::Browser(puppet) => Ok(puppet.as_ref()),
+            Self::Pooled(lease) => Ok(lease.browser()?),
+            Self::Backend(backend) => Ok(backend.as_ref()),
+        }
+    }
+
+    /// Authenticate with a provider.
+    async fn authenticate(&self, provider: Provider) -> Result<()> {
+        self.backend()?.authenticate(provider).await
+    }
+
+    /// Send a prompt to a provider.
+    async fn prompt(&self, provider: Provider, request: PromptRequest) -> Result<PromptResponse> {
+        self.backend()?.prompt(provider, request).await
+    }
+
+    /// Close the session, unless it is kept between calls.
+    async fn close(&self) -> Result<()> {
+        match self {
+            Self::Browser(puppet) => Ok(puppet.close().await?),
+            Self::Pooled(_) | Self::Backend(_) => Ok(()),
         }
     }
 }
 
+impl BrowserLease {
+    /// The leased browser, unless it was closed after failing.
+    fn browser(&self) -> Result<&WebPuppet> {
+        self.browser
+            .as_ref()
+            .ok_or_else(|| Error::Internal("browser context closed after a failure".into()))
+    }
+}
+
+/// Per-call options for prompt execution.
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+    /// System context or instructions.
+    pub context: Option<String>,
+    /// Data classification (falls back to the policy default).
+    pub classification: Option<DataClassification>,
+    /// Run this call in a visible browser even if headless is configured.
+    pub visible: bool,
+    /// Model, or model alias such as `sonnet`, to ask an API provider for
+    /// (its configured model when `None`; browser providers ignore it).
+    pub model: Option<String>,
+    /// Routing objective profile or weights choosing the provider (the
+    /// task's profile when `None`).
+    pub routing: Option<RoutingChoice>,
+}
+
+impl PromptOptions {
+    /// Build a webpuppet request for a message.
+    fn request(&self, message: &str) -> PromptRequest {
+        let mut request = PromptRequest::new(message);
+        if let Some(model) = &self.model {
+            request
+                .metadata
+                .insert(metadata::MODEL_KEY.into(), model.clone());
+        }
+        match &self.context {
+            Some(context) => request.with_context(context.clone()),
+            None => request,
+        }
+    }
+}
+
+/// Partial-failure semantics for parallel prompts.
+#[derive(Debug, Clone, Default)]
+pub struct ParallelOptions {
+    /// Minimum number of providers that must respond successfully.
+    pub min_success: usize,
+    /// Skip the remaining providers once `min_success` responses arrive.
+    pub fail_fast: bool,
+}
+
 /// Result of a consensus operation.
 #[derive(Debug, Clone)]
 pub struct ConsensusResult {
@@ -415,6 +5097,12 @@ pub struct ConsensusResult {
     pub responses: Vec<ProviderResponse>,
     /// Agreement score (0.0 - 1.0).
     pub agreement_score: f64,
+    /// Strategy used to choose the consensus text.
+    pub strategy: ConsensusStrategy,
+    /// Claims not shared by all providers and direct contradictions.
+    pub disagreements: Disagreements,
+    /// Sources cited across the responses, deduplicated.
+    pub sources: Vec<Source>,
 }
 
 /// Orchestrator status.
@@ -427,3 +5115,115 @@ pub struct OrchestratorStatus {
     /// Provider statistics.
     pub provider_stats: HashMap<Provider, crate::router::ProviderStats>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_idle_timeout_closes_browser_sessions() {
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            idle_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        // Lease a pooled session as a browser call does, without a browser
+        let pool = orchestrator
+            .browsers
+            .lock()
+            .await
+            .entry(None)
+            .or_insert_with(|| BrowserPool::new(1))
+            .clone();
+        drop(pool.lease().await.unwrap());
+        orchestrator.touch().await;
+        assert!(!orchestrator.close_idle_sessions().await);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(orchestrator.close_idle_sessions().await);
+        assert!(orchestrator.browsers.lock().await.is_empty());
+        assert!(!orchestrator.close_idle_sessions().await);
+    }
+
+    #[tokio::test]
+    async fn test_routed_prompt_falls_back_on_provider_failure() {
+        use crate::testkit::{MockProviders, MockReply};
+
+        let mocks = MockProviders::new();
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(mocks.clone()),
+            ..Default::default()
+        });
+        let options = PromptOptions::default();
+        let first = orchestrator
+            .prompt_with("Explain ownership", options.clone())
+            .await
+            .unwrap()
+            .provider;
+
+        // Enough failures to outlast any retries against the same provider
+        for _ in 0..5 {
+            mocks.enqueue(first, MockReply::Fail("Something broke".into()));
+        }
+        let response = orchestrator
+            .prompt_with("Explain ownership", options)
+            .await
+            .unwrap();
+        assert_ne!(response.provider, first);
+        assert!(mocks.calls().iter().skip(1).any(|call| call.provider == first));
+    }
+
+    #[tokio::test]
+    async fn test_remote_tenant_cannot_run_patch_checks() {
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            patch_check_command: Some("cargo check".into()),
+            ..Default::default()
+        });
+        let remote = orchestrator.for_tenant("http:203.0.113.7");
+
+        let mut workflow = Workflow::new("Fix");
+        workflow.add_step(WorkflowStep::validate_patch("Validate", None));
+        assert!(matches!(
+            remote.start_workflow(workflow.clone()).await,
+            Err(Error::PermissionDenied(_))
+        ));
+        let result = remote
+            .validate_patch(String::new(), true, None, 0, PromptOptions::default())
+            .await;
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+
+        // Nor can a remote caller run the step of a workflow started locally
+        let id = orchestrator.start_workflow(workflow).await.unwrap();
+        assert!(remote.execute_workflow_step(&id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_workflows_are_scoped_to_their_tenant() {
+        let orchestrator = AgentOrchestrator::new();
+        let owner = orchestrator.for_tenant("http:198.51.100.1");
+        let other = orchestrator.for_tenant("http:198.51.100.2");
+
+        let mut workflow = Workflow::new("Review");
+        workflow.add_step(WorkflowStep::review("check", "Ship it?"));
+        let id = owner.start_workflow(workflow).await.unwrap();
+        owner.execute_workflow_step(&id).await.unwrap_err();
+
+        assert!(other.get_workflow(&id).await.is_none());
+        assert!(other.list_workflows().await.is_empty());
+        assert!(matches!(
+            other.execute_workflow_step(&id).await,
+            Err(Error::Workflow(_))
+        ));
+        assert!(matches!(other.approve_step(&id).await, Err(Error::Workflow(_))));
+        let review = StepReview::new(true, None, None);
+        assert!(matches!(
+            other.review_step(&id, review.clone()).await,
+            Err(Error::Workflow(_))
+        ));
+
+        // The owner and the local tenant both see it
+        assert_eq!(owner.list_workflows().await.len(), 1);
+        let workflow = orchestrator.get_workflow(&id).await.unwrap();
+        assert_eq!(workflow.tenant, "http:198.51.100.1");
+        owner.review_step(&id, review).await.unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+//! Writing large results to files in the workspace.
+//!
+//! A tool call or workflow step given `write_to` streams its output to that
+//! file, relative to the working directory the server was started in, and
+//! answers with the path and a short summary instead, keeping huge results,
+//! such as a map step over hundreds of items, out of MCP payloads.
+//!
+//! Only local callers may write files, and never under `.git/`, so provider
+//! output cannot replace a hook or config that git would then run.
+
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::error::{Error, Result};
+use crate::tenant::DEFAULT_TENANT;
+
+/// Name of the argument or step field naming the file.
+pub const WRITE_TO: &str = "write_to";
+
+/// Step result metadata key describing the file the output was written to.
+pub const WRITTEN_TO_KEY: &str = "written_to";
+
+/// Lines of the output shown in the summary.
+const PREVIEW_LINES: usize = 10;
+
+/// Headings of the output listed in the summary.
+const OUTLINE_HEADINGS: usize = 20;
+
+/// Bytes written per chunk.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// A file an output was written to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WrittenOutput {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// Bytes written.
+    pub bytes: u64,
+    /// Lines written.
+    pub lines: usize,
+}
+
+impl WrittenOutput {
+    /// The path and size, the output's Markdown headings, and its first
+    /// lines.
+    pub fn summary(&self, output: &str) -> String {
+        let mut summary = format!(
+            "Wrote {} bytes ({} lines) to `{}`.",
+            self.bytes,
+            self.lines,
+            self.path.display()
+        );
+        let headings: Vec<&str> = output
+            .lines()
+            .filter(|line| line.starts_with('#'))
+            .take(OUTLINE_HEADINGS)
+            .collect();
+        if !headings.is_empty() {
+            summary.push_str("\n\n## Outline\n\n");
+            summary.push_str(&headings.join("\n"));
+        }
+        let preview: Vec<&str> = output.lines().take(PREVIEW_LINES).collect();
+        summary.push_str(&format!("\n\n## Preview\n\n{}", preview.join("\n")));
+        if self.lines > PREVIEW_LINES {
+            summary.push_str(&format!("\n\n… {} more lines", self.lines - PREVIEW_LINES));
+        }
+        summary
+    }
+}
+
+/// `path` under the workspace root, which it may not leave, and outside
+/// any `.git` directory.
+pub fn workspace_path(path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(Error::InvalidParams(format!(
+            "path '{}' must be relative to the workspace root",
+            path
+        )));
+    }
+    if relative
+        .components()
+        .any(|c| c.as_os_str().eq_ignore_ascii_case(".git"))
+    {
+        return Err(Error::InvalidParams(format!(
+            "path '{}' may not be inside a .git directory",
+            path
+        )));
+    }
+    Ok(std::env::current_dir()?.join(relative))
+}
+
+/// Check that `tenant` may write files to the workspace: only local callers
+/// may.
+pub fn check_writer(tenant: &str) -> Result<()> {
+    if tenant == DEFAULT_TENANT {
+        Ok(())
+    } else {
+        Err(Error::PermissionDenied(format!(
+            "only local callers may use {}",
+            WRITE_TO
+        )))
+    }
+}
+
+/// Write `output` to `path` under the workspace root, creating missing
+/// directories and replacing any earlier file.
+pub async fn write_output(path: &str, output: &str) -> Result<WrittenOutput> {
+    if path.trim().is_empty() {
+        return Err(Error::InvalidParams(format!(
+            "{} needs a file name",
+            WRITE_TO
+        )));
+    }
+    let full = workspace_path(path)?;
+    if let Some(parent) = full.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(&full).await?);
+    for chunk in output.as_bytes().chunks(CHUNK_BYTES) {
+        file.write_all(chunk).await?;
+    }
+    file.flush().await?;
+    Ok(WrittenOutput {
+        path: PathBuf::from(path),
+        bytes: output.len() as u64,
+        lines: output.lines().count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_output_and_summary() {
+        assert!(workspace_path("../outside.md").is_err());
+        assert!(workspace_path("/etc/passwd").is_err());
+        assert!(workspace_path(".git/hooks/pre-commit").is_err());
+        assert!(workspace_path("vendor/.GIT/config").is_err());
+        assert!(check_writer("http:203.0.113.7").is_err());
+        assert!(check_writer(DEFAULT_TENANT).is_ok());
+
+        let dir = format!("target/output-test-{}", uuid::Uuid::new_v4());
+        let path = format!("{}/out/results.md", dir);
+        let output = (1..=30)
+            .map(|n| match n {
+                1 => "# Results".to_string(),
+                n => format!("item {}", n),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let written = write_output(&path, &output).await.unwrap();
+        assert_eq!(written.lines, 30);
+        let full = workspace_path(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(full).unwrap(), output);
+
+        let summary = written.summary(&output);
+        assert!(summary.starts_with(&format!("Wrote {} bytes (30 lines)", output.len())));
+        assert!(summary.contains("## Outline\n\n# Results"));
+        assert!(summary.contains("item 10\n\n… 20 more lines"));
+        std::fs::remove_dir_all(workspace_path(&dir).unwrap()).ok();
+    }
+}
@@ -0,0 +1,304 @@
+//! Validating provider-generated patches.
+//!
+//! A diff written by a provider may not apply, or may apply and not build. A
+//! `validate_patch` step tries it on a scratch copy of the workspace first:
+//! the diff is applied with `git apply` and, when a check command such as
+//! `cargo check` is configured, the command is run in the copy. A patch that
+//! fails is sent back to a provider with the errors for a corrected version,
+//! a few times at most; if no version passes, the step fails, so later steps
+//! that would apply the patch never run. The workspace itself is not touched.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::audit::sha256_hex;
+use crate::error::{Error, Result};
+use crate::tenant::DEFAULT_TENANT;
+
+/// Corrections requested before a patch is given up on.
+pub const DEFAULT_MAX_REFINEMENTS: u32 = 2;
+
+/// Estimated tokens of a correction request and its answer, used to estimate
+/// a validation step's cost before it runs.
+pub const TOKENS_PER_REFINEMENT: u64 = 2_000;
+
+/// How long the check command may run.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Error output kept per attempt and sent back to the provider.
+const MAX_ERROR_CHARS: usize = 4_000;
+
+/// Directories not copied into the scratch workspace.
+const SKIPPED_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// Outcome of trying one version of a patch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchCheck {
+    /// Whether the diff applied cleanly.
+    pub applied: bool,
+    /// Check command run after applying, if any.
+    pub command: Option<String>,
+    /// Whether the diff applied and the check command succeeded.
+    pub passed: bool,
+    /// Output of `git apply` or the check command on failure, shortened.
+    pub errors: String,
+}
+
+impl PatchCheck {
+    /// One-line description of the outcome.
+    pub fn summary(&self) -> String {
+        match (self.applied, self.passed, &self.command) {
+            (false, _, _) => "does not apply".into(),
+            (true, true, Some(command)) => format!("applies and passes `{}`", command),
+            (true, true, None) => "applies cleanly".into(),
+            (true, false, command) => format!(
+                "applies but fails `{}`",
+                command.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// Every version of a patch tried by a validation step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchReport {
+    /// Last version of the patch tried.
+    pub diff: String,
+    /// Outcome of each version, in order.
+    pub attempts: Vec<PatchCheck>,
+}
+
+impl PatchReport {
+    /// Whether the last version passed.
+    pub fn passed(&self) -> bool {
+        self.attempts.last().is_some_and(|a| a.passed)
+    }
+
+    /// Markdown report ending with the patch.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Patch Validation\n\n");
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            out.push_str(&format!("- Attempt {}: {}\n", i + 1, attempt.summary()));
+        }
+        out.push_str(&format!("\n```diff\n{}\n```\n", self.diff.trim_end()));
+        out
+    }
+}
+
+/// The diff in a provider's answer: the first ```diff or ```patch block, or
+/// the whole answer if it is a bare unified diff.
+pub fn extract_diff(text: &str) -> Option<String> {
+    for fence in ["```diff", "```patch"] {
+        if let Some(start) = text.find(fence) {
+            let body = &text[start + fence.len()..];
+            let body = body.split_once('\n').map_or("", |(_, rest)| rest);
+            let end = body.find("\n```").unwrap_or(body.len());
+            return Some(format!("{}\n", &body[..end]));
+        }
+    }
+    let trimmed = text.trim_start();
+    (trimmed.starts_with("diff --git") || trimmed.starts_with("--- "))
+        .then(|| format!("{}\n", trimmed.trim_end()))
+}
+
+/// Build the prompt asking a provider to correct a failed patch.
+pub fn correction_prompt(diff: &str, check: &PatchCheck) -> String {
+    format!(
+        "The patch below {} when applied to the repository.\n\n\
+         Errors:\n```\n{}\n```\n\n\
+         Patch:\n```diff\n{}\n```\n\n\
+         Respond with a corrected unified diff against the same files in a \
+         single ```diff block, changing only what is needed to fix the errors.",
+        check.summary(),
+        check.errors.trim(),
+        diff.trim_end()
+    )
+}
+
+/// Apply `diff` to a scratch copy of `root` and run `command` there.
+///
+/// Cargo builds use a target directory kept for `root` outside the
+/// workspace, so a check does not start from a cold build and build scripts
+/// of a provider's patch never write into the workspace.
+pub async fn check_patch(root: &Path, diff: &str, command: Option<&str>) -> Result<PatchCheck> {
+    let scratch = std::env::temp_dir().join(format!("agent-mcp-patch-{}", uuid::Uuid::new_v4()));
+    let tree = scratch.join("tree");
+    let patch_file = scratch.join("patch.diff");
+    let result = async {
+        let (from, to) = (root.to_path_buf(), tree.clone());
+        tokio::task::spawn_blocking(move || copy_tree(&from, &to))
+            .await
+            .map_err(|e| Error::Internal(format!("copying the workspace failed: {}", e)))??;
+        tokio::fs::write(&patch_file, diff).await?;
+        run_check(root, &tree, &patch_file, command).await
+    }
+    .await;
+    let _ = tokio::fs::remove_dir_all(&scratch).await;
+    result
+}
+
+async fn run_check(
+    root: &Path,
+    tree: &Path,
+    patch_file: &Path,
+    command: Option<&str>,
+) -> Result<PatchCheck> {
+    let apply = Command::new("git")
+        .args(["apply", "--whitespace=nowarn"])
+        .arg(patch_file)
+        .current_dir(tree)
+        .output()
+        .await
+        .map_err(|e| Error::Config(format!("git is needed to apply patches: {}", e)))?;
+    if !apply.status.success() {
+        return Ok(PatchCheck {
+            applied: false,
+            command: command.map(String::from),
+            passed: false,
+            errors: shorten(&String::from_utf8_lossy(&apply.stderr)),
+        });
+    }
+    let Some(command) = command else {
+        return Ok(PatchCheck {
+            applied: true,
+            command: None,
+            passed: true,
+            errors: String::new(),
+        });
+    };
+
+    let run = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(tree)
+        .env("CARGO_TARGET_DIR", check_target_dir(root))
+        .kill_on_drop(true)
+        .output();
+    let (passed, errors) = match tokio::time::timeout(CHECK_TIMEOUT, run).await {
+        Ok(output) => {
+            let output = output?;
+            let text = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stderr),
+                String::from_utf8_lossy(&output.stdout)
+            );
+            (output.status.success(), text)
+        }
+        Err(_) => (
+            false,
+            format!("timed out after {}s", CHECK_TIMEOUT.as_secs()),
+        ),
+    };
+    Ok(PatchCheck {
+        applied: true,
+        command: Some(command.to_string()),
+        passed,
+        errors: if passed {
+            String::new()
+        } else {
+            shorten(&errors)
+        },
+    })
+}
+
+/// Copy a directory tree, skipping VCS data and build output.
+/// Cargo target directory for checks of patches to `root`, in the system
+/// temporary directory.
+fn check_target_dir(root: &Path) -> PathBuf {
+    let key = sha256_hex(root.to_string_lossy().as_bytes());
+    std::env::temp_dir()
+        .join("agent-mcp-patch-target")
+        .join(&key[..16])
+}
+
+fn copy_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target: PathBuf = to.join(entry.file_name());
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                copy_tree(&entry.path(), &target)?;
+            }
+        } else if file_type.is_file() {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fail unless `tenant` may run the check command. The command builds a
+/// diff a provider wrote, and builds run code (build scripts, proc macros),
+/// so only local callers may run it.
+pub fn check_runner(tenant: &str) -> Result<()> {
+    if tenant == DEFAULT_TENANT {
+        Ok(())
+    } else {
+        Err(Error::PermissionDenied(
+            "only local callers may run patch checks; set \"check\": false".into(),
+        ))
+    }
+}
+
+/// Keep the start of long error output, where the first errors are.
+fn shorten(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(MAX_ERROR_CHARS) {
+        Some((end, _)) => format!("{}\n[... truncated]", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_patch() {
+        let root =
+            std::env::temp_dir().join(format!("agent-mcp-patch-root-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.txt"), "one\ntwo\n").unwrap();
+
+        let answer = "Here is the fix:\n\n```diff\n--- a/src/lib.txt\n+++ b/src/lib.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+three\n```\n";
+        let diff = extract_diff(answer).unwrap();
+        assert!(diff.starts_with("--- a/src/lib.txt"));
+        assert_eq!(extract_diff(&diff), Some(diff.clone()));
+        assert_eq!(extract_diff("No changes needed."), None);
+
+        let check = check_patch(&root, &diff, Some("grep -q three src/lib.txt"))
+            .await
+            .unwrap();
+        assert!(check.passed, "{:?}", check);
+        assert_eq!(
+            std::fs::read_to_string(root.join("src/lib.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        let failing = check_patch(&root, &diff, Some("echo broken >&2; false"))
+            .await
+            .unwrap();
+        assert!(failing.applied && !failing.passed);
+        assert_eq!(failing.errors, "broken");
+
+        // Builds do not use the workspace's target directory
+        let target = check_patch(&root, &diff, Some("echo $CARGO_TARGET_DIR >&2; false"))
+            .await
+            .unwrap();
+        assert_eq!(target.errors, check_target_dir(&root).to_string_lossy());
+        assert!(!target.errors.starts_with(&*root.to_string_lossy()));
+
+        let stale = diff.replace(" one\n", " uno\n");
+        let rejected = check_patch(&root, &stale, None).await.unwrap();
+        assert!(!rejected.applied);
+        assert!(correction_prompt(&stale, &rejected).contains("does not apply"));
+        std::fs::remove_dir_all(root).ok();
+
+        assert!(check_runner("http:203.0.113.7").is_err());
+        assert!(check_runner(DEFAULT_TENANT).is_ok());
+    }
+}
@@ -0,0 +1,527 @@
+//! Decomposing a goal into an executable workflow.
+//!
+//! A provider is asked to break a goal into steps and answer in the same
+//! shape `agent_workflow_start` accepts, so a plan can be started as-is or
+//! edited by the client first.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::condition::Condition;
+use crate::confidence::ConfidenceCheck;
+use crate::retry::RetryPolicy;
+use crate::consensus::ConsensusStrategy;
+use crate::error::{Error, Result};
+use crate::github::IssueRef;
+use crate::library::{fill_json_variables, PromptLibrary, PromptRef};
+use crate::map::MapItem;
+use crate::postprocess::PostProcessor;
+use crate::records::RecordField;
+use crate::security::DataClassification;
+use crate::workflow::{StepConfig, Workflow, WorkflowStep};
+
+/// Step types a workflow definition may use.
+pub const STEP_TYPES: [&str; 14] = [
+    "prompt",
+    "parallel",
+    "map",
+    "consensus",
+    "research",
+    "fact_check",
+    "validate_patch",
+    "aggregate",
+    "assemble",
+    "extract",
+    "github_comment",
+    "review",
+    "conditional",
+    "tool",
+];
+
+/// Default upper bound on the number of planned steps.
+pub const DEFAULT_MAX_STEPS: usize = 8;
+
+/// A workflow definition: the arguments of `agent_workflow_start`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowDef {
+    /// Name of the workflow.
+    pub name: String,
+    /// Steps, in execution order.
+    pub steps: Vec<StepDef>,
+}
+
+impl WorkflowDef {
+    /// Load a definition from a file, filling the `{{name}}` placeholders in
+    /// its strings from `inputs`.
+    ///
+    /// The file holds the object `agent_workflow_start` accepts, as JSON or,
+    /// with a `.yaml` or `.yml` extension, as YAML.
+    pub fn load(path: &Path, inputs: &HashMap<String, String>) -> Result<Self> {
+        let invalid =
+            |e: &dyn std::fmt::Display| Error::Config(format!("{}: {}", path.display(), e));
+        let text = std::fs::read_to_string(path)?;
+        let yaml = path
+            .extension()
+            .is_some_and(|ext| ext == "yaml" || ext == "yml");
+        let mut definition: serde_json::Value = if yaml {
+            serde_yaml::from_str(&text).map_err(|e| invalid(&e))?
+        } else {
+            serde_json::from_str(&text).map_err(|e| invalid(&e))?
+        };
+        fill_json_variables(&mut definition, inputs);
+        serde_json::from_value(definition).map_err(|e| invalid(&e))
+    }
+
+    /// Build the workflow this definition describes. Fails if a step uses a
+    /// saved prompt.
+    pub fn to_workflow(&self) -> Result<Workflow> {
+        self.build(None)
+    }
+
+    /// Build the workflow, rendering steps that use saved prompts from
+    /// `library`.
+    pub fn to_workflow_with(&self, library: &PromptLibrary) -> Result<Workflow> {
+        self.build(Some(library))
+    }
+
+    fn build(&self, library: Option<&PromptLibrary>) -> Result<Workflow> {
+        let mut workflow = Workflow::new(self.name.clone());
+        for step in &self.steps {
+            let step = match (&step.prompt, library) {
+                (None, _) => step.to_step()?,
+                (Some(reference), Some(library)) => step.to_step_from(reference, library)?,
+                (Some(_), None) => {
+                    return Err(Error::InvalidParams(format!(
+                        "step '{}' uses a saved prompt, which is not available here",
+                        step.name
+                    )))
+                }
+            };
+            workflow.add_step(step);
+        }
+        workflow.validate_jumps()?;
+        workflow.validate_dependencies()?;
+        Ok(workflow)
+    }
+}
+
+/// A single step of a workflow definition.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepDef {
+    /// Step name.
+    pub name: String,
+    /// One of [`STEP_TYPES`].
+    #[serde(rename = "type")]
+    pub step_type: String,
+    /// Prompt sent by the step, shown to the reviewer, or, for an `assemble`
+    /// step, the document's title.
+    #[serde(default)]
+    pub message: String,
+    /// Saved prompt to render as the message instead, as `name` (current
+    /// version) or `name@version`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Variables for the saved prompt.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub arguments: HashMap<String, String>,
+    /// Provider for a `prompt`, `fact_check`, `validate_patch`, `map`, or
+    /// `aggregate` step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Providers for a `parallel`, `research`, or `extract` step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub providers: Option<Vec<String>>,
+    /// Data classification of the step's prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<DataClassification>,
+    /// Strategy for a `consensus` step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<ConsensusStrategy>,
+    /// Judge for a `consensus` step using the judge strategy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub judge_provider: Option<String>,
+    /// Step whose output a `fact_check`, `validate_patch`, or
+    /// `github_comment` step uses (defaults to the previous step).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_step: Option<String>,
+    /// Steps an `aggregate`, `assemble`, or `extract` step uses (defaults
+    /// to every earlier step except reviews and conditionals).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_steps: Vec<String>,
+    /// Pull request or issue a `github_comment` step posts to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_request: Option<String>,
+    /// Expression a `conditional` step evaluates; see [`crate::condition`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// Later step a `conditional` step continues at if its condition holds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub then_step: Option<String>,
+    /// Later step a `conditional` step continues at otherwise (defaults to
+    /// the next step).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub else_step: Option<String>,
+    /// Registered tool a `tool` step calls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Arguments of a `tool` step's call; `{{key}}` placeholders in their
+    /// strings are filled from the workflow context.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_arguments: Option<serde_json::Value>,
+    /// Items a `map` step sends its message for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<MapItem>,
+    /// Whether a `map` step reads each answer as a JSON object.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub json: bool,
+    /// Provider cross-checking each answer of a `map` step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checker: Option<String>,
+    /// Fields of the records an `extract` step extracts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<RecordField>,
+    /// Providers that must extract a record for an `extract` step to keep it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_agreement: Option<usize>,
+    /// Corrections a `validate_patch` step requests before failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_refinements: Option<u32>,
+    /// Post-processors applied to the step's output.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_processors: Vec<PostProcessor>,
+    /// File, relative to the workspace root, the step's output is written
+    /// to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_to: Option<String>,
+    /// Verification pass rating the confidence of the step's answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<ConfidenceCheck>,
+    /// Times the step is run again after failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Wait before the first retry in milliseconds, doubled for each retry
+    /// after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff_ms: Option<u64>,
+    /// Error kinds that are retried (defaults to transient failures).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retry_on: Vec<String>,
+    /// Earlier steps that must complete before this one runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+}
+
+impl StepDef {
+    /// Build the workflow step this definition describes.
+    pub fn to_step(&self) -> Result<WorkflowStep> {
+        let name = self.name.clone();
+        let message = self.message.clone();
+        let mut step = match self.step_type.as_str() {
+            "prompt" => {
+                let step = WorkflowStep::prompt(name, message);
+                match &self.provider {
+                    Some(provider) => step.with_provider(provider.clone()),
+                    None => step,
+                }
+            }
+            "parallel" => {
+                WorkflowStep::parallel(name, message, self.providers.clone().unwrap_or_default())
+            }
+            "map" => {
+                let mut step = WorkflowStep::map(name, message, self.items.clone());
+                if let StepConfig::Map { json, checker, .. } = &mut step.config {
+                    *json = self.json;
+                    *checker = self.checker.clone();
+                }
+                match &self.provider {
+                    Some(provider) => step.with_provider(provider.clone()),
+                    None => step,
+                }
+            }
+            "consensus" => WorkflowStep::consensus(name, message).with_consensus_strategy(
+                self.strategy.unwrap_or_default(),
+                self.judge_provider.clone(),
+            ),
+            "research" => {
+                WorkflowStep::research(name, message, self.providers.clone().unwrap_or_default())
+            }
+            "fact_check" => {
+                let mut step = WorkflowStep::fact_check(name, self.source_step.clone());
+                if let StepConfig::FactCheck { focus, .. } = &mut step.config {
+                    *focus = Some(message).filter(|m| !m.trim().is_empty());
+                }
+                match &self.provider {
+                    Some(provider) => step.with_provider(provider.clone()),
+                    None => step,
+                }
+            }
+            "validate_patch" => {
+                let mut step = WorkflowStep::validate_patch(name, self.source_step.clone());
+                if let StepConfig::ValidatePatch {
+                    max_refinements, ..
+                } = &mut step.config
+                {
+                    *max_refinements = self.max_refinements;
+                }
+                match &self.provider {
+                    Some(provider) => step.with_provider(provider.clone()),
+                    None => step,
+                }
+            }
+            "aggregate" => {
+                let step = WorkflowStep::aggregate(name, message, self.source_steps.clone());
+                match &self.provider {
+                    Some(provider) => step.with_provider(provider.clone()),
+                    None => step,
+                }
+            }
+            "assemble" => WorkflowStep::assemble(name, message, self.source_steps.clone()),
+            "extract" => {
+                if self.fields.is_empty() {
+                    return Err(Error::InvalidParams(format!(
+                        "extract step '{}' needs fields",
+                        self.name
+                    )));
+                }
+                let mut step = WorkflowStep::extract(
+                    name,
+                    message,
+                    self.fields.clone(),
+                    self.source_steps.clone(),
+                    self.providers.clone().unwrap_or_default(),
+                );
+                if let StepConfig::Extract { min_agreement, .. } = &mut step.config {
+                    *min_agreement = self.min_agreement;
+                }
+                step
+            }
+            "github_comment" => {
+                let pull_request = self.pull_request.clone().ok_or_else(|| {
+                    Error::InvalidParams(format!(
+                        "github_comment step '{}' needs a pull_request",
+                        self.name
+                    ))
+                })?;
+                pull_request.parse::<IssueRef>()?;
+                WorkflowStep::github_comment(name, pull_request, self.source_step.clone())
+            }
+            "review" => WorkflowStep::review(name, message),
+            "conditional" => {
+                let (Some(condition), Some(then_step)) = (&self.condition, &self.then_step) else {
+                    return Err(Error::InvalidParams(format!(
+                        "conditional step '{}' needs a condition and a then_step",
+                        self.name
+                    )));
+                };
+                condition.parse::<Condition>()?;
+                WorkflowStep::conditional(name, condition, then_step, self.else_step.clone())
+            }
+            "tool" => {
+                let tool = self.tool.clone().ok_or_else(|| {
+                    Error::InvalidParams(format!("tool step '{}' needs a tool", self.name))
+                })?;
+                let arguments = self
+                    .tool_arguments
+                    .clone()
+                    .unwrap_or_else(|| serde_json::json!({}));
+                if !arguments.is_object() {
+                    return Err(Error::InvalidParams(format!(
+                        "tool step '{}' needs tool_arguments as an object",
+                        self.name
+                    )));
+                }
+                WorkflowStep::tool(name, tool, arguments)
+            }
+            other => {
+                return Err(Error::InvalidParams(format!(
+                    "unknown step type: {}",
+                    other
+                )))
+            }
+        };
+        step.classification = self.classification;
+        step.depends_on = self.depends_on.clone();
+        step.post_processors = self.post_processors.clone();
+        step.write_to = self.write_to.clone();
+        if let Some(check) = &self.confidence {
+            check.validate()?;
+            if step.config.question().is_none() {
+                return Err(Error::InvalidParams(format!(
+                    "{} step '{}' has no answer to rate the confidence of",
+                    self.step_type, self.name
+                )));
+            }
+            step.confidence = Some(check.clone());
+        }
+        match self.retries {
+            Some(retries) => {
+                let mut retry = RetryPolicy::new(retries);
+                retry.backoff_ms = self.backoff_ms.unwrap_or(retry.backoff_ms);
+                retry.retry_on = self.retry_on.clone();
+                retry.validate()?;
+                step.retry = Some(retry);
+            }
+            None if self.backoff_ms.is_some() || !self.retry_on.is_empty() => {
+                return Err(Error::InvalidParams(format!(
+                    "step '{}' sets backoff_ms or retry_on without retries",
+                    self.name
+                )));
+            }
+            None => {}
+        }
+        Ok(step)
+    }
+
+    /// Build the step with a saved prompt, rendered with the step's
+    /// arguments, as its message. The step records the version it used.
+    fn to_step_from(&self, reference: &str, library: &PromptLibrary) -> Result<WorkflowStep> {
+        if !self.message.trim().is_empty() {
+            return Err(Error::InvalidParams(format!(
+                "step '{}' sets both a message and a saved prompt",
+                self.name
+            )));
+        }
+        let reference: PromptRef = reference.parse()?;
+        let prompt = library.get_version(&reference.name, reference.version)?;
+        let mut step = StepDef {
+            message: prompt.render(&self.arguments)?,
+            ..self.clone()
+        }
+        .to_step()?;
+        step.saved_prompt = Some(PromptRef {
+            name: prompt.name,
+            version: Some(prompt.version),
+        });
+        Ok(step)
+    }
+}
+
+/// Build the prompt asking a provider to plan a goal.
+pub fn plan_prompt(goal: &str, max_steps: usize, context: Option<&str>) -> String {
+    let context = context
+        .map(|c| format!("Background:\n{}\n\n", c))
+        .unwrap_or_default();
+    format!(
+        "Break the goal below into at most {} steps that other AI assistants will \
+         carry out in order. Each step's message must be a complete, self-contained \
+         prompt.\n\n\
+         Respond with only a JSON object of the form \
+         {{\"name\": \"<short workflow name>\", \"steps\": [...]}} where each step has \
+         \"name\", \"type\", and \"message\". The type is one of: \"prompt\" (a single \
+         answer), \"parallel\" (independent answers from several assistants), \
+         \"consensus\" (several answers reconciled into one, for facts or decisions \
+         that must be right), \"research\" (a time-boxed web search sprint returning \
+         an answer with cited sources; the message is the research question), \
+         \"fact_check\" (a web-search provider verifies the claims in the previous \
+         step's output and annotates them; the message says what to focus on), \
+         \"validate_patch\" (the diff in the previous step's output is applied to a \
+         scratch copy of the repository and checked, with errors sent back for a \
+         corrected diff), \"aggregate\" (findings from the earlier steps are \
+         deduplicated and consolidated into one answer; the message says what \
+         the answer should be), or \
+         \"review\" (a human checks the work so far before \
+         continuing; the message says what to check).\n\n\
+         {}Goal:\n{}",
+        max_steps, context, goal
+    )
+}
+
+/// Parse a provider's plan into a workflow definition.
+///
+/// Accepts a bare JSON object or one wrapped in prose or a code fence, and
+/// rejects plans that are empty, too long, or would not start as a workflow.
+pub fn parse_plan(text: &str, max_steps: usize) -> Result<WorkflowDef> {
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text,
+    };
+    let plan: WorkflowDef = serde_json::from_str(json)
+        .map_err(|e| Error::Workflow(format!("planner returned an unusable plan: {}", e)))?;
+
+    if plan.steps.is_empty() {
+        return Err(Error::Workflow("planner returned no steps".into()));
+    }
+    if plan.steps.len() > max_steps {
+        return Err(Error::Workflow(format!(
+            "planner returned {} steps, more than the limit of {}",
+            plan.steps.len(),
+            max_steps
+        )));
+    }
+    plan.to_workflow()?;
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plan() {
+        let text = "Sure:\n```json\n{\"name\": \"Launch post\", \"steps\": [\
+            {\"name\": \"Research\", \"type\": \"parallel\", \"message\": \"Find facts\"},\
+            {\"name\": \"Draft\", \"type\": \"prompt\", \"message\": \"Write it\"},\
+            {\"name\": \"Check\", \"type\": \"review\", \"message\": \"Approve the draft\"}\
+            ]}\n```";
+
+        let plan = parse_plan(text, DEFAULT_MAX_STEPS).unwrap();
+        assert_eq!(plan.name, "Launch post");
+        assert_eq!(plan.to_workflow().unwrap().steps.len(), 3);
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["steps"][0]["type"], "parallel");
+        assert!(json["steps"][0].get("provider").is_none());
+
+        assert!(parse_plan(text, 2).is_err());
+        assert!(parse_plan("{\"name\": \"x\", \"steps\": []}", 8).is_err());
+        assert!(parse_plan(
+            "{\"name\": \"x\", \"steps\": [{\"name\": \"a\", \"type\": \"loop\", \"message\": \"m\"}]}",
+            8
+        )
+        .is_err());
+
+        let library = PromptLibrary::new();
+        library.create("summary", "Summarize {{topic}}", None).unwrap();
+        library
+            .update("summary", Some("Summarize {{topic}} briefly".into()), None)
+            .unwrap();
+        let pinned: WorkflowDef = serde_json::from_value(serde_json::json!({
+            "name": "brief",
+            "steps": [{
+                "name": "Summarize",
+                "type": "prompt",
+                "prompt": "summary@1",
+                "arguments": { "topic": "Rust" }
+            }]
+        }))
+        .unwrap();
+        assert!(pinned.to_workflow().is_err());
+        let step = &pinned.to_workflow_with(&library).unwrap().steps[0];
+        assert!(matches!(
+            &step.config,
+            StepConfig::Prompt { message, .. } if message == "Summarize Rust"
+        ));
+        assert_eq!(step.saved_prompt.as_ref().unwrap().to_string(), "summary@1");
+
+        let json = text[text.find('{').unwrap()..=text.rfind('}').unwrap()]
+            .replace("Write it", "Write it for {{audience}}");
+        let path =
+            std::env::temp_dir().join(format!("agent-mcp-plan-{}.yaml", uuid::Uuid::new_v4()));
+        let yaml = "name: Launch post\nsteps:\n  - name: draft\n    type: prompt\n    \
+                    message: Draft {{topic}}\n";
+        std::fs::write(&path, yaml).unwrap();
+        let inputs = HashMap::from([("topic".to_string(), "it".to_string())]);
+        let loaded = WorkflowDef::load(&path, &inputs).unwrap();
+        assert_eq!(loaded.name, "Launch post");
+        assert_eq!(loaded.steps[0].message, "Draft it");
+        std::fs::write(&path, "name: [unclosed\n").unwrap();
+        assert!(WorkflowDef::load(&path, &HashMap::new()).is_err());
+        std::fs::write(&path, json).unwrap();
+        let inputs = HashMap::from([("audience".to_string(), "beginners".to_string())]);
+        let loaded = WorkflowDef::load(&path, &inputs).unwrap();
+        assert_eq!(loaded.steps[1].message, "Write it for beginners");
+        std::fs::remove_file(path).ok();
+    }
+}
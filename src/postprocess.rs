@@ -0,0 +1,276 @@
+//! Post-processing of results before they are returned or stored.
+//!
+//! A pipeline is an ordered list of processors. Tools take theirs from the
+//! server's configuration (a default plus per-tool overrides), and workflow
+//! steps carry their own, applied to the step result before it is recorded.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Marker appended to text cut short by [`PostProcessor::MaxLength`].
+pub const TRUNCATION_MARKER: &str = "… [truncated]";
+
+/// A single post-processing step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostProcessor {
+    /// Drop pleasantries providers wrap answers in ("Sure! Here's...",
+    /// "I hope this helps!", "Let me know if...").
+    StripBoilerplate,
+    /// Use backtick fences with lowercase language tags, and close a fence
+    /// left open at the end.
+    NormalizeCodeFences,
+    /// Cut the text to at most `max_chars` characters, preferring a
+    /// paragraph or sentence boundary.
+    MaxLength {
+        /// Maximum length in characters, including the truncation marker.
+        max_chars: usize,
+    },
+    /// Convert Markdown to plain text.
+    PlainText,
+}
+
+impl PostProcessor {
+    /// Apply the processor to a text.
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Self::StripBoilerplate => strip_boilerplate(text),
+            Self::NormalizeCodeFences => normalize_code_fences(text),
+            Self::MaxLength { max_chars } => truncate(text, *max_chars),
+            Self::PlainText => plain_text(text),
+        }
+    }
+}
+
+/// Run a text through processors in order.
+pub fn apply(text: &str, processors: &[PostProcessor]) -> String {
+    processors
+        .iter()
+        .fold(text.to_string(), |text, processor| processor.apply(&text))
+}
+
+/// Post-processing pipelines for tool results.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostProcessing {
+    /// Pipeline for tools without an entry.
+    pub default: Vec<PostProcessor>,
+    /// Pipelines keyed by tool name.
+    pub per_tool: HashMap<String, Vec<PostProcessor>>,
+}
+
+impl PostProcessing {
+    /// Set the pipeline for a tool.
+    pub fn with_tool(mut self, tool: impl Into<String>, processors: Vec<PostProcessor>) -> Self {
+        self.per_tool.insert(tool.into(), processors);
+        self
+    }
+
+    /// Pipeline for a tool.
+    pub fn for_tool(&self, tool: &str) -> &[PostProcessor] {
+        self.per_tool.get(tool).unwrap_or(&self.default)
+    }
+}
+
+/// Drop boilerplate opening and closing lines.
+fn strip_boilerplate(text: &str) -> String {
+    static OPENER: OnceLock<Regex> = OnceLock::new();
+    static CLOSER: OnceLock<Regex> = OnceLock::new();
+    let opener = OPENER.get_or_init(|| {
+        Regex::new(
+            r"(?i)^(sure|certainly|of course|absolutely|great question|good question|happy to help|here(?:'s| is| are)\b[^.!]*:$|as an ai\b)",
+        )
+        .expect("valid regex")
+    });
+    let closer = CLOSER.get_or_init(|| {
+        Regex::new(
+            r"(?i)^(i hope (this|that) helps|hope (this|that) helps|let me know if|feel free to|is there anything else|if you have any (other|more|further) questions|happy coding)",
+        )
+        .expect("valid regex")
+    });
+
+    let mut lines: Vec<&str> = text.lines().collect();
+    while let Some(first) = lines.iter().position(|l| !l.trim().is_empty()) {
+        let line = lines[first].trim();
+        // Only short, chatty lines; a long line opening with "Sure" is content.
+        if opener.is_match(line) && line.split_whitespace().count() <= 20 {
+            lines.drain(..=first);
+        } else {
+            break;
+        }
+    }
+    while let Some(last) = lines.iter().rposition(|l| !l.trim().is_empty()) {
+        if closer.is_match(lines[last].trim()) {
+            lines.truncate(last);
+        } else {
+            break;
+        }
+    }
+    let start = lines
+        .iter()
+        .position(|l| !l.trim().is_empty())
+        .unwrap_or(lines.len());
+    lines[start..].join("\n").trim_end().to_string()
+}
+
+/// Normalize fence markers and close an unterminated block.
+fn normalize_code_fences(text: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut open = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let fence = ["```", "~~~"]
+            .iter()
+            .find(|f| trimmed.starts_with(**f))
+            .map(|f| trimmed.trim_start_matches(f.chars().next().unwrap_or('`')));
+        match fence {
+            Some(info) if open => {
+                // A closing fence carries no info string; anything else is content.
+                if info.trim().is_empty() {
+                    out.push("```".into());
+                    open = false;
+                } else {
+                    out.push(line.to_string());
+                }
+            }
+            Some(info) => {
+                out.push(format!("```{}", info.trim().to_lowercase()));
+                open = true;
+            }
+            None => out.push(line.to_string()),
+        }
+    }
+    if open {
+        out.push("```".into());
+    }
+    out.join("\n")
+}
+
+/// Cut text to `max_chars` characters, marker included.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let budget = max_chars.saturating_sub(TRUNCATION_MARKER.chars().count());
+    let cut = text
+        .char_indices()
+        .nth(budget)
+        .map_or(text.len(), |(i, _)| i);
+    let head = &text[..cut];
+    // Back up to a paragraph or sentence end if one is reasonably close.
+    let boundary = head
+        .rfind("\n\n")
+        .or_else(|| head.rfind(". ").map(|i| i + 1))
+        .filter(|i| *i >= cut / 2)
+        .unwrap_or(cut);
+    let mut out = head[..boundary].trim_end().to_string();
+    if out.matches("```").count() % 2 == 1 {
+        out = normalize_code_fences(&out);
+    }
+    out.push('\n');
+    out.push_str(TRUNCATION_MARKER);
+    out
+}
+
+/// Strip Markdown syntax, keeping the text, link targets, and code.
+fn plain_text(text: &str) -> String {
+    static IMAGE: OnceLock<Regex> = OnceLock::new();
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    static EMPHASIS: OnceLock<Vec<Regex>> = OnceLock::new();
+    static HTML: OnceLock<Regex> = OnceLock::new();
+    let image = IMAGE.get_or_init(|| Regex::new(r"!\[([^\]]*)\]\([^)]*\)").expect("valid regex"));
+    let link = LINK.get_or_init(|| Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").expect("valid regex"));
+    // Inline code, bold, italic, strikethrough. Single underscores are left
+    // alone so snake_case identifiers survive.
+    let emphasis = EMPHASIS.get_or_init(|| {
+        [
+            r"`([^`]+)`",
+            r"\*\*([^*]+)\*\*",
+            r"__([^_]+)__",
+            r"\*([^*\s][^*]*)\*",
+            r"~~([^~]+)~~",
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("valid regex"))
+        .collect()
+    });
+    let html = HTML.get_or_init(|| Regex::new(r"</?[a-zA-Z][^>]*>").expect("valid regex"));
+
+    let mut out: Vec<String> = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            out.push(line.to_string());
+            continue;
+        }
+        if trimmed.chars().all(|c| "-*_ ".contains(c)) && trimmed.len() >= 3 {
+            continue; // horizontal rule
+        }
+        let line = if trimmed.starts_with(['#', '>']) {
+            trimmed.trim_start_matches(['#', '>']).trim_start()
+        } else {
+            line
+        };
+        let mut line = image.replace_all(line, "$1").into_owned();
+        line = link.replace_all(&line, "$1 ($2)").into_owned();
+        for pattern in emphasis {
+            line = pattern.replace_all(&line, "$1").into_owned();
+        }
+        out.push(html.replace_all(&line, "").into_owned());
+    }
+    out.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline() {
+        let text = "Sure! Here's a quick example:\n\n\
+                    ## Reading a file\n\n\
+                    Use **`std::fs`** and see [the docs](https://doc.rust-lang.org/std/fs).\n\n\
+                    ~~~ Rust\nlet s = std::fs::read_to_string(\"a.txt\")?;\n\n\
+                    I hope this helps! Let me know if you have questions.";
+        let cleaned = apply(
+            text,
+            &[
+                PostProcessor::StripBoilerplate,
+                PostProcessor::NormalizeCodeFences,
+            ],
+        );
+        assert!(cleaned.starts_with("## Reading a file"));
+        assert!(cleaned.contains("```rust\nlet s"));
+        assert!(cleaned.ends_with("```"));
+
+        let plain = PostProcessor::PlainText.apply(&cleaned);
+        assert!(plain.starts_with("Reading a file\n\nUse std::fs and see the docs (https://"));
+        assert!(!plain.contains("```"));
+
+        let short = PostProcessor::MaxLength { max_chars: 40 }.apply(&cleaned);
+        assert!(short.chars().count() <= 40);
+        assert!(short.ends_with(TRUNCATION_MARKER));
+
+        let config: PostProcessing = serde_json::from_str(
+            r#"{"default": [{"type": "strip_boilerplate"}],
+                "per_tool": {"agent_prompt": [{"type": "max_length", "max_chars": 100}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.for_tool("agent_consensus"),
+            [PostProcessor::StripBoilerplate]
+        );
+        assert_eq!(
+            config.for_tool("agent_prompt"),
+            [PostProcessor::MaxLength { max_chars: 100 }]
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! Projecting a routing change from past traffic.
+//!
+//! Each request in the ledger is routed again under a proposed
+//! configuration. Where the proposal picks the provider that actually
+//! answered, the recorded outcome stands. Where it picks another, the cost
+//! is estimated from the cost model and the request's tokens, and the latency
+//! and failure rate are that provider's averages in the ledger, so the
+//! difference can be judged before the change is applied.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::cost::CostModel;
+use crate::error::{Error, Result};
+use crate::ledger::LedgerEntry;
+use crate::objectives::RoutingProfiles;
+use crate::router::ProviderPreferences;
+
+/// A proposed routing configuration: profiles, and preference changes on
+/// top of the current preferences.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoutingProposal {
+    /// Routing profiles replacing the current ones.
+    #[serde(default)]
+    pub routing_profiles: Option<RoutingProfiles>,
+    /// Routing priorities keyed by provider name.
+    #[serde(default)]
+    pub priorities: BTreeMap<String, u32>,
+    /// Providers to stop routing to.
+    #[serde(default)]
+    pub disable: Vec<String>,
+    /// Disabled providers to route to again.
+    #[serde(default)]
+    pub enable: Vec<String>,
+}
+
+impl RoutingProposal {
+    /// Check the profiles and every provider named.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(profiles) = &self.routing_profiles {
+            profiles.validate()?;
+        }
+        let mut names = self
+            .priorities
+            .keys()
+            .chain(&self.disable)
+            .chain(&self.enable);
+        if let Some(unknown) = names.find(|n| Provider::from_string(n).is_none()) {
+            return Err(Error::InvalidParams(format!(
+                "unknown provider in routing proposal: {}",
+                unknown
+            )));
+        }
+        Ok(())
+    }
+
+    /// `current` with the proposed priorities and disabled providers.
+    pub fn preferences(&self, current: &ProviderPreferences) -> ProviderPreferences {
+        let mut preferences = current.clone();
+        for (name, priority) in &self.priorities {
+            if let Some(provider) = Provider::from_string(name) {
+                preferences.set_priority(provider, *priority);
+            }
+        }
+        for (names, disabled) in [(&self.disable, true), (&self.enable, false)] {
+            for provider in names.iter().filter_map(|n| Provider::from_string(n)) {
+                preferences.set_disabled(provider, disabled);
+            }
+        }
+        preferences
+    }
+}
+
+/// Cost, latency, and failures of the replayed requests routed one way.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Outcome {
+    /// Estimated spend, in USD.
+    pub cost_usd: f64,
+    /// Total latency, in milliseconds.
+    pub latency_ms: f64,
+    /// Failed requests, or the expected number for projected ones.
+    pub failures: f64,
+}
+
+/// A provider's record in the ledger.
+#[derive(Debug, Default)]
+struct History {
+    requests: u64,
+    failures: u64,
+    latency_ms: u64,
+}
+
+impl History {
+    fn failure_rate(&self) -> f64 {
+        self.failures as f64 / self.requests as f64
+    }
+
+    fn mean_latency_ms(&self) -> f64 {
+        self.latency_ms as f64 / self.requests as f64
+    }
+}
+
+/// What a proposed routing configuration would have done to past traffic.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutingProjection {
+    /// Start of the replayed traffic, if limited.
+    pub since: Option<DateTime<Utc>>,
+    /// Requests replayed.
+    pub requests: usize,
+    /// What the requests actually cost and how they went.
+    pub actual: Outcome,
+    /// What they would have cost and how they would have gone.
+    pub projected: Outcome,
+    /// Requests the proposal sends to a different provider.
+    pub rerouted: usize,
+    /// Requests no provider could take under the proposal, counted as
+    /// failures.
+    pub unroutable: usize,
+    /// Requests per provider, actually and under the proposal.
+    pub shares: BTreeMap<String, (usize, usize)>,
+    /// Providers the proposal picks that have no history in the ledger;
+    /// their requests are assumed to go as they actually went.
+    pub unobserved: Vec<String>,
+}
+
+impl RoutingProjection {
+    /// Project `entries` onto the providers `choose` picks for them, or
+    /// `None` when no provider could take one.
+    pub fn project(
+        entries: &[LedgerEntry],
+        since: Option<DateTime<Utc>>,
+        costs: &CostModel,
+        choose: impl Fn(&LedgerEntry) -> Option<Provider>,
+    ) -> Self {
+        let mut history: HashMap<String, History> = HashMap::new();
+        for entry in entries {
+            let record = history.entry(entry.provider.to_lowercase()).or_default();
+            record.requests += 1;
+            record.failures += u64::from(entry.failed());
+            record.latency_ms += entry.latency_ms;
+        }
+
+        let mut projection = Self {
+            since,
+            requests: entries.len(),
+            actual: Outcome::default(),
+            projected: Outcome::default(),
+            rerouted: 0,
+            unroutable: 0,
+            shares: BTreeMap::new(),
+            unobserved: Vec::new(),
+        };
+        for entry in entries {
+            let failed = f64::from(u8::from(entry.failed()));
+            projection.actual.cost_usd += entry.cost_usd;
+            projection.actual.latency_ms += entry.latency_ms as f64;
+            projection.actual.failures += failed;
+            let actual = entry.provider.to_lowercase();
+            projection.shares.entry(actual.clone()).or_default().0 += 1;
+
+            let Some(chosen) = choose(entry) else {
+                projection.unroutable += 1;
+                projection.projected.failures += 1.0;
+                continue;
+            };
+            let name = chosen.to_string().to_lowercase();
+            projection.shares.entry(name.clone()).or_default().1 += 1;
+            if name == actual {
+                projection.projected.cost_usd += entry.cost_usd;
+                projection.projected.latency_ms += entry.latency_ms as f64;
+                projection.projected.failures += failed;
+                continue;
+            }
+
+            projection.rerouted += 1;
+            let tokens = entry.exchange_tokens.max(entry.prompt_tokens);
+            let (latency_ms, failure_rate) = match history.get(&name) {
+                Some(record) => (record.mean_latency_ms(), record.failure_rate()),
+                None => {
+                    if !projection.unobserved.contains(&name) {
+                        projection.unobserved.push(name.clone());
+                    }
+                    (entry.latency_ms as f64, failed)
+                }
+            };
+            projection.projected.cost_usd +=
+                costs.estimate(Some(&name), tokens) * (1.0 - failure_rate);
+            projection.projected.latency_ms += latency_ms;
+            projection.projected.failures += failure_rate;
+        }
+        projection.unobserved.sort();
+        projection
+    }
+
+    /// Actual and projected cost, latency, and failure rate, and each
+    /// provider's share, in Markdown.
+    pub fn report(&self) -> String {
+        let since = self
+            .since
+            .map(|s| format!(" since {}", s.format("%Y-%m-%d %H:%M")))
+            .unwrap_or_default();
+        let mut report = format!(
+            "# Routing Replay\n\n**Requests:** {}{} · **Rerouted:** {} · **Unroutable:** {}\n\n",
+            self.requests, since, self.rerouted, self.unroutable
+        );
+        if self.requests == 0 {
+            report.push_str("No routed requests in the ledger to replay.");
+            return report;
+        }
+
+        let n = self.requests as f64;
+        let (actual, projected) = (&self.actual, &self.projected);
+        report.push_str(&format!(
+            "| | Actual | Projected | Change |\n|---|---|---|---|\n\
+             | Cost | ${:.4} | ${:.4} | {} |\n\
+             | Mean latency | {:.0}ms | {:.0}ms | {} |\n\
+             | Failure rate | {:.1}% | {:.1}% | {:+.1} pts |\n",
+            actual.cost_usd,
+            projected.cost_usd,
+            relative_change(actual.cost_usd, projected.cost_usd),
+            actual.latency_ms / n,
+            projected.latency_ms / n,
+            relative_change(actual.latency_ms, projected.latency_ms),
+            actual.failures / n * 100.0,
+            projected.failures / n * 100.0,
+            (projected.failures - actual.failures) / n * 100.0
+        ));
+
+        report
+            .push_str("\n## Provider Share\n\n| Provider | Actual | Projected |\n|---|---|---|\n");
+        for (provider, (actual, projected)) in &self.shares {
+            report.push_str(&format!("| {} | {} | {} |\n", provider, actual, projected));
+        }
+        if !self.unobserved.is_empty() {
+            report.push_str(&format!(
+                "\n{} {} no history in the ledger; requests moved to {} are assumed to go as they \
+                 actually went.\n",
+                self.unobserved.join(", "),
+                if self.unobserved.len() == 1 {
+                    "has"
+                } else {
+                    "have"
+                },
+                if self.unobserved.len() == 1 {
+                    "it"
+                } else {
+                    "them"
+                }
+            ));
+        }
+        report
+    }
+}
+
+/// Change from `before` to `after` as a percentage.
+fn relative_change(before: f64, after: f64) -> String {
+    if before == 0.0 {
+        if after == 0.0 {
+            "none".to_string()
+        } else {
+            "new".to_string()
+        }
+    } else {
+        format!("{:+.1}%", (after - before) / before * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::DataClassification;
+
+    fn entry(provider: &str, latency_ms: u64, error: Option<&str>) -> LedgerEntry {
+        LedgerEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            at: Utc::now(),
+            tenant: "default".into(),
+            task: "general".into(),
+            language: None,
+            prompt_tokens: 500,
+            exchange_tokens: if error.is_some() { 0 } else { 1000 },
+            classification: DataClassification::Public,
+            routing: None,
+            provider: provider.into(),
+            latency_ms,
+            cost_usd: if provider == "chatgpt" && error.is_none() {
+                0.01
+            } else {
+                0.0
+            },
+            error: error.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_projection_moves_traffic() {
+        let costs = CostModel {
+            usd_per_1k_tokens: [("chatgpt".to_string(), 0.01)].into_iter().collect(),
+        };
+        let entries = vec![
+            entry("chatgpt", 2000, None),
+            entry("chatgpt", 4000, None),
+            entry("claude", 1000, None),
+            entry("claude", 3000, Some("timeout")),
+        ];
+
+        // Moving every request to Claude drops the spend and takes on its
+        // failure rate and mean latency
+        let projection =
+            RoutingProjection::project(&entries, None, &costs, |_| Some(Provider::Claude));
+        assert_eq!(projection.rerouted, 2);
+        assert!((projection.actual.cost_usd - 0.02).abs() < 1e-9);
+        assert_eq!(projection.projected.cost_usd, 0.0);
+        assert_eq!(projection.projected.latency_ms, 2000.0 * 2.0 + 4000.0);
+        assert_eq!(projection.projected.failures, 2.0);
+        assert_eq!(projection.shares["chatgpt"], (2, 0));
+        assert_eq!(projection.shares["claude"], (2, 4));
+
+        // A provider without history keeps the recorded outcome
+        let projection = RoutingProjection::project(&entries, None, &costs, |e| {
+            (e.provider == "chatgpt").then_some(Provider::Gemini)
+        });
+        assert_eq!(projection.unroutable, 2);
+        assert_eq!(projection.unobserved, vec!["gemini".to_string()]);
+        assert_eq!(projection.projected.failures, 2.0);
+        let report = projection.report();
+        assert!(report.contains("| Cost | $0.0200 | $0.0000 | -100.0% |"));
+        assert!(report.contains("gemini has no history"));
+
+        let proposal: RoutingProposal =
+            serde_json::from_value(serde_json::json!({ "disable": ["nobody"] })).unwrap();
+        assert!(proposal.validate().is_err());
+    }
+}
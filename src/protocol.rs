@@ -57,6 +57,43 @@ impl McpResponse {
             }),
         }
     }
+
+    /// Create an error response with additional error data.
+    pub fn error_with_data(
+        id: Option<Value>,
+        code: i32,
+        message: impl Into<String>,
+        data: Value,
+    ) -> Self {
+        let mut response = Self::error(id, code, message);
+        if let Some(error) = response.error.as_mut() {
+            error.data = Some(data);
+        }
+        response
+    }
+}
+
+/// MCP JSON-RPC notification (no response expected).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpNotification {
+    /// JSON-RPC version (always "2.0").
+    pub jsonrpc: String,
+    /// Method name.
+    pub method: String,
+    /// Method parameters.
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl McpNotification {
+    /// Create a notification.
+    pub fn new(method: impl Into<String>, params: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".into(),
+            method: method.into(),
+            params,
+        }
+    }
 }
 
 /// MCP error object.
@@ -78,6 +115,12 @@ pub mod error_codes {
     pub const METHOD_NOT_FOUND: i32 = -32601;
     pub const INVALID_PARAMS: i32 = -32602;
     pub const INTERNAL_ERROR: i32 = -32603;
+    /// Server-defined: the client exceeded its rate limit.
+    pub const RATE_LIMITED: i32 = -32029;
+    /// Server-defined: the client did not present a valid API key.
+    pub const UNAUTHORIZED: i32 = -32001;
+    /// MCP: the requested resource does not exist.
+    pub const RESOURCE_NOT_FOUND: i32 = -32002;
 }
 
 /// Tool definition for MCP.
@@ -0,0 +1,548 @@
+//! Providers answered through their HTTP API instead of a browser.
+//!
+//! A provider configured with an API backend is sent prompts over the OpenAI
+//! chat completions API or the Anthropic Messages API, so it needs no
+//! browser, sign-in, or captcha solving. The backend stands in for the
+//! provider's web app: routing, statistics, budgets, and tool arguments all
+//! use the same provider name, so `chatgpt` (or its alias `openai`) is
+//! answered by the API once one is configured. Any endpoint speaking the
+//! OpenAI API, such as xAI's for `grok`, works by pointing `base_url` at it.
+//!
+//! A prompt may ask for another model than the configured one by naming it,
+//! or an alias such as `sonnet`, in the request's `model` metadata.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use chrono::Utc;
+use embeddenator_webpuppet::{Error as WebError, PromptRequest, PromptResponse, Provider};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::backend::{estimate_cost, BackendCapabilities, ProviderBackend};
+use crate::completeness::{CONTINUE_FROM_KEY, CONTINUE_PROMPT};
+use crate::cost::CostModel;
+use crate::error::{Error, Result};
+use crate::metadata::{prompt_tokens, FINISH_REASON_KEY, MODEL_KEY};
+
+/// Base URL of the OpenAI API.
+pub const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+
+/// Environment variable holding the OpenAI API key.
+pub const OPENAI_KEY_ENV_VAR: &str = "OPENAI_API_KEY";
+
+/// Base URL of the Anthropic API.
+pub const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+
+/// Environment variable holding the Anthropic API key.
+pub const ANTHROPIC_KEY_ENV_VAR: &str = "ANTHROPIC_API_KEY";
+
+/// Version of the Anthropic Messages API requested.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Response tokens requested from the Anthropic API, which needs a limit,
+/// when `max_tokens` is unset.
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Model aliases understood by every Anthropic backend.
+const ANTHROPIC_MODEL_ALIASES: &[(&str, &str)] = &[
+    ("opus", "claude-opus-4-1"),
+    ("sonnet", "claude-sonnet-4-5"),
+    ("haiku", "claude-haiku-4-5"),
+];
+
+fn default_timeout_secs() -> u64 {
+    120
+}
+
+/// API spoken by a backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiKind {
+    /// OpenAI chat completions, also spoken by xAI and many others.
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Anthropic Messages.
+    #[serde(rename = "anthropic")]
+    Anthropic,
+}
+
+/// How to reach a provider's API.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiProviderConfig {
+    /// API spoken by the endpoint.
+    #[serde(default)]
+    pub api: ApiKind,
+    /// Model requested unless a prompt asks for another, such as `gpt-4o`.
+    pub model: String,
+    /// Model names keyed by alias, added to the API's built-in aliases.
+    #[serde(default)]
+    pub models: HashMap<String, String>,
+    /// Base URL of the API (the API's own when `None`).
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// API key. Prefer `api_key_env`, which keeps the key out of the file.
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<String>,
+    /// Environment variable holding the API key, read if `api_key` is unset
+    /// (the API's usual variable when `None`).
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// Maximum response tokens (the model's default when `None`).
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// Time limit per request, in seconds.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl ApiProviderConfig {
+    /// Base URL of the API.
+    pub fn base_url(&self) -> &str {
+        match (&self.base_url, self.api) {
+            (Some(url), _) => url,
+            (None, ApiKind::OpenAi) => OPENAI_API_BASE,
+            (None, ApiKind::Anthropic) => ANTHROPIC_API_BASE,
+        }
+    }
+
+    /// Environment variable holding the API key.
+    pub fn api_key_env(&self) -> &str {
+        match (&self.api_key_env, self.api) {
+            (Some(var), _) => var,
+            (None, ApiKind::OpenAi) => OPENAI_KEY_ENV_VAR,
+            (None, ApiKind::Anthropic) => ANTHROPIC_KEY_ENV_VAR,
+        }
+    }
+
+    /// Model to request when a prompt asks for `requested`, by name or
+    /// alias, or for no model in particular.
+    pub fn resolve_model<'a>(&'a self, requested: Option<&'a str>) -> &'a str {
+        let Some(requested) = requested else {
+            return &self.model;
+        };
+        let builtin = match self.api {
+            ApiKind::OpenAi => &[][..],
+            ApiKind::Anthropic => ANTHROPIC_MODEL_ALIASES,
+        };
+        self.models
+            .get(requested)
+            .map(String::as_str)
+            .or_else(|| {
+                builtin
+                    .iter()
+                    .find(|(alias, _)| *alias == requested)
+                    .map(|(_, model)| *model)
+            })
+            .unwrap_or(requested)
+    }
+}
+
+impl fmt::Debug for ApiProviderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiProviderConfig")
+            .field("api", &self.api)
+            .field("model", &self.model)
+            .field("models", &self.models)
+            .field("base_url", &self.base_url)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .field("api_key_env", &self.api_key_env)
+            .field("max_tokens", &self.max_tokens)
+            .field("timeout_secs", &self.timeout_secs)
+            .finish()
+    }
+}
+
+/// A provider answered through the OpenAI or Anthropic API.
+#[derive(Clone)]
+pub struct ApiProvider {
+    provider: Provider,
+    config: ApiProviderConfig,
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl ApiProvider {
+    /// Create a backend for `provider`. Fails if no API key is configured.
+    pub fn new(provider: Provider, config: ApiProviderConfig) -> Result<Self> {
+        let api_key = config
+            .api_key
+            .clone()
+            .or_else(|| std::env::var(config.api_key_env()).ok())
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| {
+                Error::Config(format!(
+                    "no API key for {}: set {} or api_key",
+                    provider,
+                    config.api_key_env()
+                ))
+            })?;
+        Ok(Self {
+            provider,
+            config,
+            api_key,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// The provider this backend answers for.
+    pub fn provider(&self) -> Provider {
+        self.provider
+    }
+
+    /// The model requested unless a prompt asks for another.
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    /// The API spoken.
+    pub fn api(&self) -> ApiKind {
+        self.config.api
+    }
+
+    /// Send a prompt and wait for the whole answer.
+    pub async fn prompt(&self, request: &PromptRequest) -> Result<PromptResponse> {
+        let base_url = self.config.base_url().trim_end_matches('/');
+        let http = match self.config.api {
+            ApiKind::OpenAi => self
+                .client
+                .post(format!("{}/chat/completions", base_url))
+                .bearer_auth(&self.api_key),
+            ApiKind::Anthropic => self
+                .client
+                .post(format!("{}/messages", base_url))
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION),
+        };
+        let response = http
+            .timeout(Duration::from_secs(self.config.timeout_secs))
+            .json(&self.request_body(request))
+            .send()
+            .await
+            .map_err(|e| self.request_error(e))?;
+
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok()?.trim().parse().ok());
+        let body = response.text().await.map_err(|e| self.request_error(e))?;
+        if !(200..300).contains(&status) {
+            return Err(self.status_error(status, retry_after, &body));
+        }
+        let body = serde_json::from_str(&body)?;
+        match self.config.api {
+            ApiKind::OpenAi => self.parse_completion(&body),
+            ApiKind::Anthropic => self.parse_message(&body),
+        }
+    }
+
+    /// Request for a prompt, with its context as the system prompt. A
+    /// continuation sends the answer so far as the assistant's turn, which
+    /// the Anthropic API picks up from directly and the OpenAI API is asked
+    /// to continue.
+    fn request_body(&self, request: &PromptRequest) -> Value {
+        let requested = request.metadata.get(MODEL_KEY).map(String::as_str);
+        let model = self.config.resolve_model(requested);
+        let partial = request.metadata.get(CONTINUE_FROM_KEY);
+        let user = json!({ "role": "user", "content": request.message });
+        match self.config.api {
+            ApiKind::OpenAi => {
+                let mut messages = Vec::new();
+                if let Some(context) = &request.context {
+                    messages.push(json!({ "role": "system", "content": context }));
+                }
+                messages.push(user);
+                if let Some(partial) = partial {
+                    messages.push(json!({ "role": "assistant", "content": partial }));
+                    messages.push(json!({ "role": "user", "content": CONTINUE_PROMPT }));
+                }
+                let mut body = json!({ "model": model, "messages": messages });
+                if let Some(max_tokens) = self.config.max_tokens {
+                    body["max_tokens"] = max_tokens.into();
+                }
+                body
+            }
+            ApiKind::Anthropic => {
+                let max_tokens = self
+                    .config
+                    .max_tokens
+                    .unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS);
+                let mut messages = vec![user];
+                if let Some(partial) = partial {
+                    // The API refuses a final assistant turn ending in
+                    // whitespace
+                    let partial = partial.trim_end();
+                    messages.push(json!({ "role": "assistant", "content": partial }));
+                }
+                let mut body = json!({
+                    "model": model,
+                    "max_tokens": max_tokens,
+                    "messages": messages,
+                });
+                if let Some(context) = &request.context {
+                    body["system"] = context.clone().into();
+                }
+                body
+            }
+        }
+    }
+
+    /// Read the answer from a chat completions response.
+    fn parse_completion(&self, body: &Value) -> Result<PromptResponse> {
+        let choice = &body["choices"][0];
+        let finish_reason = choice["finish_reason"].as_str().unwrap_or_default();
+        if finish_reason == "content_filter" {
+            return Err(self.refused("response blocked by the content filter"));
+        }
+        let text = choice["message"]["content"]
+            .as_str()
+            .ok_or_else(|| self.provider_error(format!("no answer in response: {}", body)))?;
+        let tokens = body["usage"]["completion_tokens"].as_u64();
+        Ok(self.response(body, text.to_string(), finish_reason, tokens))
+    }
+
+    /// Read the answer from a Messages response.
+    fn parse_message(&self, body: &Value) -> Result<PromptResponse> {
+        let stop_reason = body["stop_reason"].as_str().unwrap_or_default();
+        if stop_reason == "refusal" {
+            return Err(self.refused("the model declined to answer"));
+        }
+        let blocks = body["content"]
+            .as_array()
+            .ok_or_else(|| self.provider_error(format!("no answer in response: {}", body)))?;
+        let text: String = blocks
+            .iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        let tokens = body["usage"]["output_tokens"].as_u64();
+        Ok(self.response(body, text, stop_reason, tokens))
+    }
+
+    /// A response answered by `body`'s model, recording why it stopped.
+    fn response(
+        &self,
+        body: &Value,
+        text: String,
+        finish_reason: &str,
+        tokens: Option<u64>,
+    ) -> PromptResponse {
+        let mut metadata = HashMap::new();
+        let model = body["model"].as_str().unwrap_or(&self.config.model);
+        metadata.insert(MODEL_KEY.to_string(), model.to_string());
+        if !finish_reason.is_empty() {
+            metadata.insert(FINISH_REASON_KEY.to_string(), finish_reason.to_string());
+        }
+        PromptResponse {
+            text,
+            provider: self.provider,
+            conversation_id: None,
+            timestamp: Utc::now(),
+            tokens_used: tokens.and_then(|n| u32::try_from(n).ok()),
+            metadata,
+        }
+    }
+
+    fn refused(&self, reason: &str) -> Error {
+        Error::ContentRefused {
+            provider: self.provider.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Classify an unsuccessful response.
+    fn status_error(&self, status: u16, retry_after_secs: Option<u64>, body: &str) -> Error {
+        let message = serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+            .unwrap_or_else(|| body.trim().to_string());
+        let message = format!("HTTP {}: {}", status, message);
+        match status {
+            401 | 403 => Error::AuthRequired {
+                provider: self.provider.to_string(),
+                reason: message,
+            },
+            429 => Error::RateLimited {
+                message: format!("{}: {}", self.provider, message),
+                retry_after_secs,
+            },
+            // Anthropic answers 529 when overloaded
+            500..=599 => Error::NetworkTimeout(format!("{}: {}", self.provider, message)),
+            _ => self.provider_error(message),
+        }
+    }
+
+    /// An error classified by its wording, like a web provider's.
+    fn provider_error(&self, message: String) -> Error {
+        Error::from(WebError::ProviderError {
+            provider: self.provider.to_string(),
+            message,
+        })
+    }
+
+    fn request_error(&self, e: reqwest::Error) -> Error {
+        Error::NetworkTimeout(format!("{} API request failed: {}", self.provider, e))
+    }
+}
+
+/// Backends for the configured providers, keyed by provider. Fails on an
+/// unknown provider name or a missing API key.
+pub fn api_providers(
+    configs: &HashMap<String, ApiProviderConfig>,
+) -> Result<HashMap<Provider, ApiProvider>> {
+    configs
+        .iter()
+        .map(|(name, config)| {
+            let provider = Provider::from_string(name).ok_or_else(|| {
+                Error::Config(format!("unknown provider in API providers: {}", name))
+            })?;
+            Ok((provider, ApiProvider::new(provider, config.clone())?))
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl ProviderBackend for ApiProvider {
+    async fn authenticate(&self, _provider: Provider) -> Result<()> {
+        Ok(())
+    }
+
+    async fn prompt(&self, _provider: Provider, request: PromptRequest) -> Result<PromptResponse> {
+        ApiProvider::prompt(self, &request).await
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            kind: match self.config.api {
+                ApiKind::OpenAi => "openai",
+                ApiKind::Anthropic => "anthropic",
+            },
+            browser: false,
+            system_prompt: true,
+            continuation: true,
+        }
+    }
+
+    /// Priced at the requested model's rate, if the cost model has one,
+    /// else the provider's.
+    fn cost_estimate(&self, provider: Provider, request: &PromptRequest, costs: &CostModel) -> f64 {
+        let requested = request.metadata.get(MODEL_KEY).map(String::as_str);
+        let model = self.config.resolve_model(requested);
+        if costs.has_rate(model) {
+            costs.estimate(Some(model), prompt_tokens(request))
+        } else {
+            estimate_cost(provider, request, costs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_request_and_response() {
+        let config: ApiProviderConfig = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "api_key": "sk-test",
+            "max_tokens": 512
+        }))
+        .unwrap();
+        assert_eq!(config.base_url(), OPENAI_API_BASE);
+        assert!(!format!("{:?}", config).contains("sk-test"));
+        assert!(serde_json::to_value(&config)
+            .unwrap()
+            .get("api_key")
+            .is_none());
+
+        let api = ApiProvider::new(Provider::ChatGpt, config).unwrap();
+        let request = PromptRequest::new("Hello").with_context("Be brief");
+        let body = api.request_body(&request);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"], "Hello");
+        assert_eq!(body["max_tokens"], 512);
+
+        let response = api
+            .parse_completion(&json!({
+                "id": "chatcmpl-1",
+                "model": "gpt-4o-2024-08-06",
+                "choices": [{ "message": { "content": "Hi!" }, "finish_reason": "stop" }],
+                "usage": { "prompt_tokens": 12, "completion_tokens": 3 }
+            }))
+            .unwrap();
+        assert_eq!(response.text, "Hi!");
+        assert_eq!(response.tokens_used, Some(3));
+        assert_eq!(response.metadata[MODEL_KEY], "gpt-4o-2024-08-06");
+
+        assert_eq!(
+            api.status_error(429, Some(20), "{}").retry_after_secs(),
+            Some(20)
+        );
+        assert_eq!(api.status_error(401, None, "").kind(), "auth_required");
+        let too_long =
+            r#"{"error":{"message":"This model's maximum context length is 128000 tokens"}}"#;
+        assert_eq!(
+            api.status_error(400, None, too_long).kind(),
+            "context_too_long"
+        );
+        assert!(api.status_error(503, None, "").is_retryable());
+    }
+
+    #[test]
+    fn test_anthropic_request_and_response() {
+        let config: ApiProviderConfig = serde_json::from_value(json!({
+            "api": "anthropic",
+            "model": "claude-sonnet-4-5",
+            "models": { "fast": "claude-3-5-haiku-latest" },
+            "api_key": "sk-ant-test"
+        }))
+        .unwrap();
+        assert_eq!(config.base_url(), ANTHROPIC_API_BASE);
+        assert_eq!(config.api_key_env(), ANTHROPIC_KEY_ENV_VAR);
+        assert_eq!(config.resolve_model(None), "claude-sonnet-4-5");
+        assert_eq!(config.resolve_model(Some("opus")), "claude-opus-4-1");
+        assert_eq!(
+            config.resolve_model(Some("fast")),
+            "claude-3-5-haiku-latest"
+        );
+        assert_eq!(config.resolve_model(Some("claude-x")), "claude-x");
+
+        let api = ApiProvider::new(Provider::Claude, config).unwrap();
+        let mut request = PromptRequest::new("Hello").with_context("Be brief");
+        request.metadata.insert(MODEL_KEY.into(), "haiku".into());
+        let body = api.request_body(&request);
+        assert_eq!(body["model"], "claude-haiku-4-5");
+        assert_eq!(body["system"], "Be brief");
+        assert_eq!(body["messages"][0]["content"], "Hello");
+        assert_eq!(body["max_tokens"], ANTHROPIC_DEFAULT_MAX_TOKENS);
+        request
+            .metadata
+            .insert(CONTINUE_FROM_KEY.into(), "Hi, how can \n".into());
+        let body = api.request_body(&request);
+        assert_eq!(body["messages"][1]["role"], "assistant");
+        assert_eq!(body["messages"][1]["content"], "Hi, how can");
+
+        let response = api
+            .parse_message(&json!({
+                "id": "msg_1",
+                "type": "message",
+                "model": "claude-haiku-4-5-20251001",
+                "content": [{ "type": "text", "text": "Hi" }, { "type": "text", "text": "!" }],
+                "stop_reason": "max_tokens",
+                "usage": { "input_tokens": 12, "output_tokens": 2 }
+            }))
+            .unwrap();
+        assert_eq!(response.text, "Hi!");
+        assert_eq!(response.tokens_used, Some(2));
+        assert_eq!(response.metadata[FINISH_REASON_KEY], "max_tokens");
+        assert_eq!(
+            api.parse_message(&json!({ "content": [], "stop_reason": "refusal" }))
+                .unwrap_err()
+                .kind(),
+            "content_refused"
+        );
+        assert!(api.status_error(529, None, "").is_retryable());
+    }
+}
@@ -0,0 +1,155 @@
+//! Speculative dual dispatch for latency-critical prompts.
+//!
+//! A raced prompt is sent to the two fastest healthy providers at once, and
+//! the first acceptable answer wins: one that is not empty and was not cut
+//! short. The other call is cancelled. Both calls are charged once sent
+//! (one cancelled while still queued is not), so racing trades cost for
+//! latency, which suits interactive calls where a slow provider keeps
+//! someone waiting.
+
+use embeddenator_webpuppet::PromptResponse;
+
+use crate::completeness::INCOMPLETE_KEY;
+
+/// Providers a raced prompt is sent to.
+pub const RACE_WIDTH: usize = 2;
+
+/// Response metadata key listing the providers a raced answer beat.
+pub const RACED_AGAINST_KEY: &str = "raced_against";
+
+/// Whether a raced answer can be returned: it has text and is not marked
+/// incomplete.
+pub fn is_acceptable(response: &PromptResponse) -> bool {
+    !response.text.trim().is_empty() && !response.metadata.contains_key(INCOMPLETE_KEY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::CostModel;
+    use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig, PromptOptions};
+    use crate::router::ProviderRouter;
+    use crate::tenant::DEFAULT_TENANT;
+    use crate::testkit::{MockProviders, MockReply};
+    use embeddenator_webpuppet::Provider;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_race_returns_first_acceptable_answer() {
+        let mut router = ProviderRouter::new();
+        router.record_success(Provider::Gemini, Duration::from_millis(900));
+        router.record_success(Provider::Grok, Duration::from_millis(300));
+        assert_eq!(
+            router.fastest_providers(RACE_WIDTH, |_| true),
+            vec![Provider::Grok, Provider::Gemini]
+        );
+        assert_eq!(
+            router.fastest_providers(RACE_WIDTH, |p| p != Provider::Grok)[0],
+            Provider::Gemini
+        );
+
+        let mocks = MockProviders::new()
+            .with_reply(Provider::Claude, "Claude answer")
+            .with_reply(Provider::Gemini, "Gemini answer");
+        let racer = |max_concurrent| {
+            let mocks = mocks.clone();
+            async move {
+                let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+                    mock_providers: Some(mocks.clone()),
+                    cost_model: CostModel::default()
+                        .with_rate("claude", 1.0)
+                        .with_rate("gemini", 1.0),
+                    max_concurrent,
+                    ..Default::default()
+                });
+                // Measured latencies make these two the fastest
+                for provider in [Provider::Claude, Provider::Gemini] {
+                    orchestrator
+                        .prompt_provider(provider, "warm up")
+                        .await
+                        .unwrap();
+                }
+                orchestrator
+            }
+        };
+        let orchestrator = racer(RACE_WIDTH).await;
+        let queued = racer(1).await;
+
+        // A cancelled call that was already sent is still charged to the
+        // tenant
+        let spent_with = |orchestrator: &AgentOrchestrator, provider: &str| {
+            let usage = orchestrator.tenants().report(DEFAULT_TENANT).unwrap().usage;
+            usage
+                .by_provider
+                .get(provider)
+                .map(|u| (u.requests, u.cost_usd))
+        };
+        let spent = |provider: &str| spent_with(&orchestrator, provider);
+        let (claude_requests, claude_cost) = spent("claude").unwrap();
+        mocks.enqueue(
+            Provider::Claude,
+            MockReply::Delayed(Duration::from_secs(30), "Too late".into()),
+        );
+        mocks.enqueue(
+            Provider::Gemini,
+            MockReply::Delayed(Duration::from_millis(20), "Gemini answer".into()),
+        );
+        let response = orchestrator
+            .race_prompt("Quick question", PromptOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.text, "Gemini answer");
+        let (requests, cost) = spent("claude").unwrap();
+        assert_eq!(requests, claude_requests + 1);
+        assert!(cost > claude_cost);
+
+        // A call still waiting for its turn to be sent is not charged
+        let before = [spent_with(&queued, "claude"), spent_with(&queued, "gemini")];
+        let response = queued
+            .race_prompt("Quick question", PromptOptions::default())
+            .await
+            .unwrap();
+        let loser = match response.provider {
+            Provider::Claude => 1,
+            _ => 0,
+        };
+        let after = [spent_with(&queued, "claude"), spent_with(&queued, "gemini")];
+        assert_eq!(after[loser], before[loser]);
+        assert_ne!(after[1 - loser], before[1 - loser]);
+
+        // A failed call loses the race to the other
+        mocks.enqueue(Provider::Claude, MockReply::Fail("down".into()));
+        let response = orchestrator
+            .race_prompt("Quick question", PromptOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.text, "Gemini answer");
+        assert_eq!(response.metadata[RACED_AGAINST_KEY], "claude");
+
+        // Empty or cut-short answers are not acceptable
+        let mut answer = PromptResponse {
+            text: " ".into(),
+            provider: Provider::Claude,
+            conversation_id: None,
+            timestamp: chrono::Utc::now(),
+            tokens_used: None,
+            metadata: HashMap::new(),
+        };
+        assert!(!is_acceptable(&answer));
+        answer.text = "Paris".into();
+        assert!(is_acceptable(&answer));
+        answer
+            .metadata
+            .insert(INCOMPLETE_KEY.into(), "length limit".into());
+        assert!(!is_acceptable(&answer));
+
+        // The race fails only when both calls do
+        mocks.enqueue(Provider::Claude, MockReply::Fail("down".into()));
+        mocks.enqueue(Provider::Gemini, MockReply::Fail("down".into()));
+        assert!(orchestrator
+            .race_prompt("Quick question", PromptOptions::default())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Per-client rate limiting for networked transports.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Length of the request-rate window.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits applied to each client independently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientLimits {
+    /// Maximum requests per client per minute.
+    pub requests_per_minute: u32,
+    /// Maximum in-flight calls per client.
+    pub max_concurrent: usize,
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 60,
+            max_concurrent: 4,
+        }
+    }
+}
+
+/// Rejection returned when a client exceeds its limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitRejection {
+    /// Which limit was exceeded.
+    pub reason: String,
+    /// How long the client should wait before retrying.
+    pub retry_after: Duration,
+}
+
+impl RateLimitRejection {
+    /// Retry delay in whole seconds, rounded up (at least 1).
+    pub fn retry_after_secs(&self) -> u64 {
+        (self.retry_after.as_millis().div_ceil(1000) as u64).max(1)
+    }
+}
+
+impl fmt::Display for RateLimitRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, retry after {}s",
+            self.reason,
+            self.retry_after_secs()
+        )
+    }
+}
+
+/// Sliding-window request counter and concurrency tracker per client.
+#[derive(Debug, Default)]
+struct ClientState {
+    /// Start times of requests in the current window.
+    requests: VecDeque<Instant>,
+    /// Calls currently in flight.
+    in_flight: usize,
+}
+
+/// Rate limiter keyed by client identity.
+#[derive(Debug, Clone, Default)]
+pub struct ClientRateLimiter {
+    limits: ClientLimits,
+    clients: Arc<Mutex<HashMap<String, ClientState>>>,
+}
+
+impl ClientRateLimiter {
+    /// Create a limiter with the given per-client limits.
+    pub fn new(limits: ClientLimits) -> Self {
+        Self {
+            limits,
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the configured limits.
+    pub fn limits(&self) -> &ClientLimits {
+        &self.limits
+    }
+
+    /// Admit a request from a client, or reject it with a retry delay.
+    ///
+    /// The returned permit counts as an in-flight call until dropped.
+    pub fn acquire(&self, client: &str) -> Result<ClientPermit, RateLimitRejection> {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        // Forget idle clients so the map only holds recent ones
+        clients.retain(|_, state| {
+            state.in_flight > 0
+                || state
+                    .requests
+                    .back()
+                    .is_some_and(|t| now.duration_since(*t) < WINDOW)
+        });
+        let state = clients.entry(client.to_string()).or_default();
+
+        while state
+            .requests
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= WINDOW)
+        {
+            state.requests.pop_front();
+        }
+
+        if state.in_flight >= self.limits.max_concurrent {
+            return Err(RateLimitRejection {
+                reason: format!(
+                    "client {} has {} calls in flight (limit {})",
+                    client, state.in_flight, self.limits.max_concurrent
+                ),
+                retry_after: Duration::from_secs(1),
+            });
+        }
+
+        if state.requests.len() >= self.limits.requests_per_minute as usize {
+            let oldest = state.requests.front().copied().unwrap_or(now);
+            return Err(RateLimitRejection {
+                reason: format!(
+                    "client {} exceeded {} requests per minute",
+                    client, self.limits.requests_per_minute
+                ),
+                retry_after: WINDOW.saturating_sub(now.duration_since(oldest)),
+            });
+        }
+
+        state.requests.push_back(now);
+        state.in_flight += 1;
+
+        Ok(ClientPermit {
+            client: client.to_string(),
+            clients: self.clients.clone(),
+        })
+    }
+}
+
+/// In-flight call slot held by a client; released on drop.
+pub struct ClientPermit {
+    client: String,
+    clients: Arc<Mutex<HashMap<String, ClientState>>>,
+}
+
+impl Drop for ClientPermit {
+    fn drop(&mut self) {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(state) = clients.get_mut(&self.client) {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_per_minute() {
+        let limiter = ClientRateLimiter::new(ClientLimits {
+            requests_per_minute: 2,
+            max_concurrent: 10,
+        });
+
+        assert!(limiter.acquire("a").is_ok());
+        assert!(limiter.acquire("a").is_ok());
+        let rejection = limiter.acquire("a").err().unwrap();
+        assert!(rejection.retry_after_secs() > 0);
+
+        // Other clients are unaffected
+        assert!(limiter.acquire("b").is_ok());
+
+        // Waits are rounded up, so clients do not retry early
+        let rejection = RateLimitRejection {
+            reason: String::new(),
+            retry_after: Duration::from_millis(1900),
+        };
+        assert_eq!(rejection.retry_after_secs(), 2);
+
+        // Clients idle for a whole window are forgotten
+        if let Some(old) = Instant::now().checked_sub(WINDOW) {
+            let mut idle = ClientState::default();
+            idle.requests.push_back(old);
+            limiter.clients.lock().unwrap().insert("idle".into(), idle);
+            assert!(limiter.acquire("c").is_ok());
+            assert!(!limiter.clients.lock().unwrap().contains_key("idle"));
+        }
+    }
+
+    #[test]
+    fn test_concurrent_calls() {
+        let limiter = ClientRateLimiter::new(ClientLimits {
+            requests_per_minute: 100,
+            max_concurrent: 1,
+        });
+
+        let permit = limiter.acquire("a").unwrap();
+        assert!(limiter.acquire("a").is_err());
+        drop(permit);
+        assert!(limiter.acquire("a").is_ok());
+    }
+}
@@ -0,0 +1,260 @@
+//! Extracting structured records validated by several providers.
+//!
+//! An `extract` step asks each of its providers for a JSON array of records
+//! with the step's fields, such as the action items in meeting notes, read
+//! from the outputs of earlier steps. Records from different providers that
+//! describe the same thing are matched by the words of their required fields,
+//! and a record is kept only when enough providers extracted it, so an item
+//! one provider invented or misread is set aside rather than reported.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::consensus::group_duplicates;
+
+/// Word overlap at which two records count as the same.
+pub const MATCH_THRESHOLD: f64 = 0.5;
+
+/// Estimated tokens of an extraction answer per provider, used to estimate an
+/// extract step's cost before it runs.
+pub const TOKENS_PER_EXTRACTION: u64 = 1_500;
+
+/// A field of the extracted records.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordField {
+    /// Field name.
+    pub name: String,
+    /// What the field holds, shown to the providers.
+    pub description: String,
+    /// Whether a record without the field is dropped.
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl RecordField {
+    /// A required field.
+    pub fn required(name: &str, description: &str) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            required: true,
+        }
+    }
+
+    /// An optional field.
+    pub fn optional(name: &str, description: &str) -> Self {
+        Self {
+            required: false,
+            ..Self::required(name, description)
+        }
+    }
+}
+
+/// A record and the providers that extracted it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Field values, as the first provider to extract the record gave them.
+    pub fields: Map<String, Value>,
+    /// Providers that extracted it, in order.
+    pub providers: Vec<String>,
+}
+
+/// Records extracted by several providers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extraction {
+    /// Records extracted by at least the required number of providers.
+    pub records: Vec<Record>,
+    /// Records too few providers extracted.
+    pub rejected: Vec<Record>,
+    /// Providers whose answer was used.
+    pub providers: Vec<String>,
+    /// Providers whose answer held no JSON array, with the reason.
+    pub failures: Vec<(String, String)>,
+}
+
+impl Extraction {
+    /// Match the records of each `(provider, records)` answer and keep those
+    /// at least `min_agreement` providers extracted.
+    pub fn vote(
+        answers: Vec<(String, Vec<Map<String, Value>>)>,
+        fields: &[RecordField],
+        min_agreement: usize,
+    ) -> Self {
+        let providers: Vec<String> = answers.iter().map(|(p, _)| p.clone()).collect();
+        let all: Vec<(String, Map<String, Value>)> = answers
+            .into_iter()
+            .flat_map(|(provider, records)| records.into_iter().map(move |r| (provider.clone(), r)))
+            .collect();
+        let keys: Vec<String> = all.iter().map(|(_, r)| key(r, fields)).collect();
+        let texts: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+        let mut extraction = Self {
+            providers,
+            ..Self::default()
+        };
+        for group in group_duplicates(&texts, MATCH_THRESHOLD) {
+            let mut providers: Vec<String> = Vec::new();
+            for &i in &group {
+                if !providers.contains(&all[i].0) {
+                    providers.push(all[i].0.clone());
+                }
+            }
+            let record = Record {
+                fields: all[group[0]].1.clone(),
+                providers,
+            };
+            if record.providers.len() >= min_agreement {
+                extraction.records.push(record);
+            } else {
+                extraction.rejected.push(record);
+            }
+        }
+        extraction
+    }
+
+    /// Markdown report: the kept records as a JSON array, then those set
+    /// aside.
+    pub fn to_markdown(&self) -> String {
+        let records: Vec<&Map<String, Value>> = self.records.iter().map(|r| &r.fields).collect();
+        let mut out = format!(
+            "```json\n{}\n```\n",
+            serde_json::to_string_pretty(&records).unwrap_or_default()
+        );
+        if !self.rejected.is_empty() {
+            out.push_str("\nNot confirmed by enough providers:\n\n");
+            for record in &self.rejected {
+                out.push_str(&format!(
+                    "- {} ({})\n",
+                    Value::Object(record.fields.clone()),
+                    record.providers.join(", ")
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// Build the prompt asking a provider for the records in `input`.
+pub fn extraction_prompt(instructions: &str, fields: &[RecordField], input: &str) -> String {
+    let fields = fields
+        .iter()
+        .map(|f| {
+            let required = if f.required { "" } else { " (null if unknown)" };
+            format!("- \"{}\": {}{}", f.name, f.description, required)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "{}\n\n\
+         Respond with only a JSON array of objects with these fields:\n{}\n\n\
+         Include only what the input states; respond with [] if there is nothing \
+         to extract.\n\nInput:\n\"\"\"\n{}\n\"\"\"",
+        instructions.trim(),
+        fields,
+        input.trim()
+    )
+}
+
+/// Parse a provider's answer into records with `fields`.
+///
+/// Accepts a JSON array wrapped in prose or a code fence. Fields not listed
+/// are dropped, missing optional fields become null, and records missing a
+/// required field are skipped.
+pub fn parse_records(
+    text: &str,
+    fields: &[RecordField],
+) -> std::result::Result<Vec<Map<String, Value>>, String> {
+    let json = match (text.find('['), text.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err("no JSON array in the answer".into()),
+    };
+    let values: Vec<Value> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    Ok(values
+        .into_iter()
+        .filter_map(|value| {
+            let Value::Object(mut object) = value else {
+                return None;
+            };
+            let mut record = Map::new();
+            for field in fields {
+                match object.remove(&field.name) {
+                    Some(Value::Null) | None if field.required => return None,
+                    Some(value) => record.insert(field.name.clone(), value),
+                    None => record.insert(field.name.clone(), Value::Null),
+                };
+            }
+            Some(record)
+        })
+        .collect())
+}
+
+/// Text records are matched on: their required fields, or every field if
+/// none is required.
+fn key(record: &Map<String, Value>, fields: &[RecordField]) -> String {
+    let any_required = fields.iter().any(|f| f.required);
+    fields
+        .iter()
+        .filter(|f| f.required || !any_required)
+        .filter_map(|f| match record.get(&f.name)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Null => None,
+            other => Some(other.to_string()),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_and_vote() {
+        let fields = [
+            RecordField::required("task", "what is to be done"),
+            RecordField::optional("owner", "who does it"),
+        ];
+        let prompt = extraction_prompt(
+            "List the action items.",
+            &fields,
+            "Ann: I'll send the slides.",
+        );
+        assert!(prompt.contains("- \"owner\": who does it (null if unknown)\n"));
+        assert!(prompt.ends_with("\"\"\"\nAnn: I'll send the slides.\n\"\"\""));
+
+        let claude = parse_records(
+            "```json\n[{\"task\": \"Send the slides to the team\", \"owner\": \"Ann\", \"extra\": 1}, {\"owner\": \"Bo\"}]\n```",
+            &fields,
+        )
+        .unwrap();
+        assert_eq!(claude.len(), 1);
+        assert_eq!(
+            Value::Object(claude[0].clone()),
+            serde_json::json!({"task": "Send the slides to the team", "owner": "Ann"})
+        );
+        let gemini = parse_records(
+            "[{\"task\": \"Send slides to the team\"}, {\"task\": \"Book a room for Friday\"}]",
+            &fields,
+        )
+        .unwrap();
+        assert_eq!(gemini[0]["owner"], Value::Null);
+        assert!(parse_records("Nothing to report.", &fields).is_err());
+
+        let extraction = Extraction::vote(
+            vec![("claude".into(), claude), ("gemini".into(), gemini)],
+            &fields,
+            2,
+        );
+        assert_eq!(extraction.records.len(), 1);
+        assert_eq!(extraction.records[0].providers, ["claude", "gemini"]);
+        assert_eq!(
+            extraction.rejected[0].fields["task"],
+            "Book a room for Friday"
+        );
+        let report = extraction.to_markdown();
+        assert!(report.starts_with("```json\n[\n  {\n    \"owner\": \"Ann\""));
+        assert!(
+            report.ends_with("- {\"owner\":null,\"task\":\"Book a room for Friday\"} (gemini)\n")
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Detecting answers that decline the prompt.
+//!
+//! API backends report a refusal with their stop reason, and a web app's
+//! error banner is classified by its wording, but a web app mostly declines
+//! in an ordinary answer: "I'm sorry, but I can't help with that." Such an
+//! answer would be returned, and kept as a workflow step's output, as if it
+//! answered the prompt. A short answer opening with a refusal is told apart
+//! here and fails with `content_refused` instead, which the
+//! [`RefusalPolicy`] recovers from by rephrasing the prompt or sending it to
+//! another provider.
+
+use embeddenator_webpuppet::PromptResponse;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Response metadata key: providers that declined the prompt before the one
+/// that answered, comma-separated.
+pub const REFUSED_BY_KEY: &str = "refused_by";
+
+/// Openings of an answer declining the prompt, lowercase.
+const REFUSAL_OPENINGS: &[&str] = &[
+    "i'm sorry, but i can't",
+    "i'm sorry, but i cannot",
+    "i am sorry, but i cannot",
+    "sorry, but i can't",
+    "i can't help with",
+    "i cannot help with",
+    "i can't assist with",
+    "i cannot assist with",
+    "i can't provide",
+    "i cannot provide",
+    "i'm unable to help",
+    "i'm not able to help",
+    "i won't be able to help",
+    "i must decline",
+    "as an ai language model, i cannot",
+];
+
+/// Characters of an answer searched for a refusal opening.
+const OPENING_CHARS: usize = 120;
+
+/// Longest answer taken for a refusal. Longer ones decline part of the
+/// prompt at most, and answer the rest.
+const MAX_REFUSAL_CHARS: usize = 600;
+
+/// How prompts declined by a provider are handled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RefusalPolicy {
+    /// Fail answers declining the prompt with `content_refused`, instead of
+    /// returning them as answers.
+    pub detect: bool,
+    /// Send a refused prompt to the same provider once more, rephrased by
+    /// the `rephrase` template of its prompt adapter, if it has one.
+    pub rephrase: bool,
+    /// Other providers a routed prompt is sent to after a refusal, best
+    /// first. Prompts sent to a named provider are not rerouted.
+    pub reroutes: u32,
+}
+
+impl Default for RefusalPolicy {
+    fn default() -> Self {
+        Self {
+            detect: true,
+            rephrase: true,
+            reroutes: 2,
+        }
+    }
+}
+
+/// The opening of the answer declining the prompt, if it does.
+pub fn check(response: &PromptResponse) -> Option<String> {
+    let text = response.text.trim();
+    if text.chars().count() > MAX_REFUSAL_CHARS {
+        return None;
+    }
+    let opening = text
+        .chars()
+        .take(OPENING_CHARS)
+        .collect::<String>()
+        .to_lowercase()
+        .replace('’', "'");
+    if !REFUSAL_OPENINGS.iter().any(|m| opening.contains(m)) {
+        return None;
+    }
+    let sentence = text
+        .split_inclusive(['.', '!', '\n'])
+        .next()
+        .unwrap_or(text);
+    Some(sentence.trim().to_string())
+}
+
+/// Fail with `ContentRefused` if the answer declines the prompt.
+pub fn ensure_not_refused(response: &PromptResponse) -> Result<()> {
+    match check(response) {
+        Some(opening) => Err(Error::ContentRefused {
+            provider: response.provider.to_string(),
+            reason: format!("declined to answer: {}", opening),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use embeddenator_webpuppet::Provider;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_detects_refusals() {
+        let response = |text: &str| PromptResponse {
+            text: text.into(),
+            provider: Provider::Claude,
+            conversation_id: None,
+            timestamp: Utc::now(),
+            tokens_used: None,
+            metadata: HashMap::new(),
+        };
+
+        let refused = response("I’m sorry, but I can’t help with that. Is there anything else?");
+        assert_eq!(
+            check(&refused).as_deref(),
+            Some("I’m sorry, but I can’t help with that.")
+        );
+        let error = ensure_not_refused(&refused).unwrap_err();
+        assert_eq!(error.kind(), "content_refused");
+        assert!(!error.affects_health());
+
+        assert_eq!(check(&response("Here is the fix: use a mutex.")), None);
+        // A long answer declining one part still answers the rest
+        let partial = format!(
+            "I can't provide the key itself, but {}",
+            "here is how. ".repeat(60)
+        );
+        assert_eq!(check(&response(&partial)), None);
+    }
+}
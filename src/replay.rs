@@ -0,0 +1,215 @@
+//! Recording and replaying provider responses.
+//!
+//! In record mode every provider answer is stored, keyed by the provider and
+//! the exact prompt. In replay mode the stored answers are served instead of
+//! calling any provider, so a workflow run can be reproduced exactly; a
+//! prompt asked several times gets its recorded answers back in order.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use embeddenator_webpuppet::{PromptRequest, PromptResponse, Provider};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::sha256_hex;
+use crate::backend::{BackendCapabilities, ProviderBackend};
+use crate::error::{Error, Result};
+use crate::storage::Records;
+
+/// Whether provider responses are recorded or replayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayMode {
+    /// Call providers; record nothing.
+    #[default]
+    Off,
+    /// Call providers and record their responses.
+    Record,
+    /// Serve recorded responses without calling providers.
+    Replay,
+}
+
+impl FromStr for ReplayMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "record" => Ok(Self::Record),
+            "replay" => Ok(Self::Replay),
+            _ => Err(Error::InvalidParams(format!(
+                "unknown replay mode: {} (expected off, record, or replay)",
+                s
+            ))),
+        }
+    }
+}
+
+/// A recorded provider answer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedAnswer {
+    /// Response text.
+    pub text: String,
+    /// Metadata the provider returned.
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// When the answer was recorded.
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Every recorded answer to one prompt from one provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayEntry {
+    /// Provider name.
+    pub provider: String,
+    /// Prompt message.
+    pub message: String,
+    /// System context sent with the prompt.
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Answers in the order they were given.
+    pub answers: Vec<RecordedAnswer>,
+}
+
+/// Recorded provider responses.
+///
+/// Counts how often each prompt has been seen in this run, so the n-th
+/// occurrence records or replays the n-th answer.
+#[derive(Clone)]
+pub struct ReplayStore {
+    records: Records,
+    seen: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl ReplayStore {
+    /// Open the store over a collection.
+    pub fn open(records: Records) -> Self {
+        Self {
+            records,
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Key identifying a prompt to a provider.
+    pub fn key(provider: Provider, request: &PromptRequest) -> String {
+        let context = request.context.as_deref().unwrap_or_default();
+        sha256_hex(format!("{}\0{}\0{}", provider, context, request.message).as_bytes())
+    }
+
+    /// Record a provider's answer to a prompt, replacing the answer recorded
+    /// for the same occurrence in an earlier run.
+    pub fn record(
+        &self,
+        provider: Provider,
+        request: &PromptRequest,
+        response: &PromptResponse,
+    ) -> Result<()> {
+        let key = Self::key(provider, request);
+        let occurrence = self.next_occurrence(&key);
+        let mut entry = self
+            .records
+            .load::<ReplayEntry>(&key)?
+            .unwrap_or_else(|| ReplayEntry {
+                provider: provider.to_string(),
+                message: request.message.clone(),
+                context: request.context.clone(),
+                answers: Vec::new(),
+            });
+        entry.answers.truncate(occurrence);
+        entry.answers.push(RecordedAnswer {
+            text: response.text.clone(),
+            metadata: response.metadata.clone(),
+            recorded_at: Utc::now(),
+        });
+        self.records.save(&key, &entry)
+    }
+
+    /// Replay a provider's recorded answer to a prompt. A prompt asked more
+    /// often than it was recorded gets the last recorded answer again.
+    pub fn replay(&self, provider: Provider, request: &PromptRequest) -> Result<PromptResponse> {
+        let key = Self::key(provider, request);
+        let occurrence = self.next_occurrence(&key);
+        let entry = self.records.load::<ReplayEntry>(&key)?;
+        let answer = entry
+            .as_ref()
+            .and_then(|e| e.answers.get(occurrence).or(e.answers.last()))
+            .ok_or_else(|| {
+                Error::InvalidState(format!(
+                    "no recorded {} response for this prompt (key {})",
+                    provider,
+                    &key[..12]
+                ))
+            })?;
+        Ok(PromptResponse {
+            text: answer.text.clone(),
+            provider,
+            conversation_id: None,
+            timestamp: answer.recorded_at,
+            tokens_used: None,
+            metadata: answer.metadata.clone(),
+        })
+    }
+
+    fn next_occurrence(&self, key: &str) -> usize {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let count = seen.entry(key.to_string()).or_default();
+        *count += 1;
+        *count - 1
+    }
+}
+
+#[async_trait::async_trait]
+impl ProviderBackend for ReplayStore {
+    async fn authenticate(&self, _provider: Provider) -> Result<()> {
+        Ok(())
+    }
+
+    async fn prompt(&self, provider: Provider, request: PromptRequest) -> Result<PromptResponse> {
+        self.replay(provider, &request)
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            kind: "replay",
+            browser: false,
+            system_prompt: true,
+            continuation: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn test_record_and_replay() {
+        let storage = Arc::new(MemoryStorage::new());
+        let recorder = ReplayStore::open(Records::new(storage.clone(), "replay", None));
+        let request = PromptRequest::new("Name a color");
+        for text in ["red", "blue"] {
+            let response = PromptResponse {
+                text: text.into(),
+                provider: Provider::Claude,
+                conversation_id: None,
+                timestamp: Utc::now(),
+                tokens_used: None,
+                metadata: HashMap::new(),
+            };
+            recorder
+                .record(Provider::Claude, &request, &response)
+                .unwrap();
+        }
+
+        let player = ReplayStore::open(Records::new(storage, "replay", None));
+        let answers: Vec<String> = (0..3)
+            .map(|_| player.replay(Provider::Claude, &request).unwrap().text)
+            .collect();
+        assert_eq!(answers, ["red", "blue", "blue"]);
+        assert!(player.replay(Provider::Gemini, &request).is_err());
+        assert_eq!("Replay".parse::<ReplayMode>().unwrap(), ReplayMode::Replay);
+    }
+}
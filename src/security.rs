@@ -0,0 +1,198 @@
+//! Security guard for controlling which providers may receive which data.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Sensitivity classification of prompt data.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DataClassification {
+    /// Data that may be shared with any provider.
+    #[default]
+    Public,
+    /// Internal data, not for public release.
+    Internal,
+    /// Confidential data that must stay on trusted (local) providers.
+    Confidential,
+}
+
+impl fmt::Display for DataClassification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Public => "public",
+            Self::Internal => "internal",
+            Self::Confidential => "confidential",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DataClassification {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "public" => Ok(Self::Public),
+            "internal" => Ok(Self::Internal),
+            "confidential" => Ok(Self::Confidential),
+            _ => Err(Error::InvalidParams(format!("unknown classification: {}", s))),
+        }
+    }
+}
+
+/// Policy mapping data classifications to the providers allowed to receive them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassificationPolicy {
+    /// Allowed providers per classification. A missing entry allows all
+    /// providers, except for confidential data, which none may receive
+    /// unless listed.
+    #[serde(deserialize_with = "refuse_confidential_by_default")]
+    allowed: HashMap<DataClassification, Vec<String>>,
+    /// Classification applied to untagged requests.
+    pub default_classification: DataClassification,
+}
+
+fn refuse_confidential_by_default<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<DataClassification, Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut allowed = HashMap::<DataClassification, Vec<String>>::deserialize(deserializer)?;
+    allowed.entry(DataClassification::Confidential).or_default();
+    Ok(allowed)
+}
+
+impl ClassificationPolicy {
+    /// Restrict a classification to the given providers.
+    pub fn allow(mut self, classification: DataClassification, providers: Vec<String>) -> Self {
+        self.allowed.insert(classification, providers);
+        self
+    }
+
+    /// Remove any restriction for a classification.
+    pub fn allow_all(mut self, classification: DataClassification) -> Self {
+        self.allowed.remove(&classification);
+        self
+    }
+
+    /// Check if a provider may receive data of the given classification.
+    pub fn is_allowed(&self, classification: DataClassification, provider: Provider) -> bool {
+        match self.allowed.get(&classification) {
+            Some(providers) => providers
+                .iter()
+                .any(|p| p.to_lowercase() == provider.to_string().to_lowercase()),
+            None => true,
+        }
+    }
+}
+
+impl Default for ClassificationPolicy {
+    fn default() -> Self {
+        let mut allowed = HashMap::new();
+        // Confidential data is local-only; no browser provider qualifies
+        allowed.insert(DataClassification::Confidential, Vec::new());
+
+        Self {
+            allowed,
+            default_classification: DataClassification::Public,
+        }
+    }
+}
+
+/// Guard enforcing security policy before prompts are routed.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityGuard {
+    /// Classification routing policy.
+    policy: ClassificationPolicy,
+}
+
+impl SecurityGuard {
+    /// Create a guard with the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a guard with a custom classification policy.
+    pub fn with_policy(policy: ClassificationPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Get the classification policy.
+    pub fn policy(&self) -> &ClassificationPolicy {
+        &self.policy
+    }
+
+    /// Resolve an optional classification to the effective one.
+    pub fn classify(&self, classification: Option<DataClassification>) -> DataClassification {
+        classification.unwrap_or(self.policy.default_classification)
+    }
+
+    /// Check if a provider may receive data of the given classification.
+    pub fn is_allowed(&self, classification: DataClassification, provider: Provider) -> bool {
+        self.policy.is_allowed(classification, provider)
+    }
+
+    /// Ensure a provider may receive data of the given classification.
+    pub fn check(&self, classification: DataClassification, provider: Provider) -> Result<()> {
+        if self.is_allowed(classification, provider) {
+            Ok(())
+        } else {
+            Err(Error::PermissionDenied(format!(
+                "provider {} may not receive {} data",
+                provider, classification
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy_blocks_confidential() {
+        let guard = SecurityGuard::new();
+
+        assert!(guard.is_allowed(DataClassification::Public, Provider::Claude));
+        assert!(guard.is_allowed(DataClassification::Internal, Provider::Claude));
+        assert!(guard
+            .check(DataClassification::Confidential, Provider::Claude)
+            .is_err());
+    }
+
+    #[test]
+    fn test_custom_policy() {
+        let policy = ClassificationPolicy::default()
+            .allow(DataClassification::Internal, vec!["Claude".into()]);
+        let guard = SecurityGuard::with_policy(policy);
+
+        assert!(guard.is_allowed(DataClassification::Internal, Provider::Claude));
+        assert!(!guard.is_allowed(DataClassification::Internal, Provider::Grok));
+        assert_eq!(guard.classify(None), DataClassification::Public);
+    }
+
+    #[test]
+    fn test_policy_file_without_confidential_entry_refuses_it() {
+        let policy: ClassificationPolicy =
+            serde_json::from_str(r#"{ "allowed": { "internal": ["claude"] } }"#).unwrap();
+        let guard = SecurityGuard::with_policy(policy);
+        assert!(guard.is_allowed(DataClassification::Internal, Provider::Claude));
+        assert!(!guard.is_allowed(DataClassification::Confidential, Provider::Claude));
+
+        let policy: ClassificationPolicy =
+            serde_json::from_str(r#"{ "default_classification": "internal" }"#).unwrap();
+        let guard = SecurityGuard::with_policy(policy);
+        assert!(!guard.is_allowed(DataClassification::Confidential, Provider::Gemini));
+        assert_eq!(guard.classify(None), DataClassification::Internal);
+    }
+}
@@ -1,16 +1,28 @@
 //! MCP server implementation for agent orchestration.
 
-use std::io::{BufRead, BufReader, Write};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::Serialize;
 use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tracing::{debug, error, info};
 
+use crate::diagnostics::{Diagnostics, DIAGNOSTICS_URI};
 use crate::error::{Error, Result};
+use crate::events::{EventBus, OrchestratorEvent};
+use crate::library::PromptLibrary;
 use crate::orchestrator::AgentOrchestrator;
+use crate::postprocess::PostProcessing;
 use crate::protocol::{
-    error_codes, McpRequest, McpResponse, ServerCapabilities, ServerInfo, ToolCapabilities,
+    error_codes, McpNotification, McpRequest, McpResponse, PromptCapabilities, ResourceCapabilities,
+    ServerCapabilities,
+    ServerInfo, ToolCapabilities,
 };
-use crate::tools::ToolRegistry;
+use crate::session::{SessionStore, SESSION_SCHEME};
+use crate::tenant::TenantPolicy;
+use crate::tools::{ToolRegistry, ToolTimeouts};
 
 /// Agent MCP Server.
 pub struct AgentMcpServer {
@@ -19,49 +31,129 @@ pub struct AgentMcpServer {
     /// Server info.
     server_info: ServerInfo,
     /// Whether the server is initialized.
-    initialized: bool,
+    initialized: AtomicBool,
+    /// Orchestrator events forwarded to the client.
+    events: EventBus,
+    /// Saved prompts served through the prompts capability.
+    prompts: PromptLibrary,
+    /// Conversation transcripts served as resources.
+    sessions: SessionStore,
+    /// Tenants networked clients authenticate as.
+    tenants: TenantPolicy,
+    /// Orchestrator the startup self-test checks.
+    orchestrator: AgentOrchestrator,
+    /// Whether `initialize` runs the self-test.
+    self_test: bool,
+    /// Results of the latest self-test, served as a resource.
+    diagnostics: std::sync::RwLock<Option<Diagnostics>>,
 }
 
 impl AgentMcpServer {
     /// Create a new MCP server.
     pub fn new(orchestrator: AgentOrchestrator) -> Self {
+        let events = orchestrator.events().clone();
+        let prompts = orchestrator.prompt_library().clone();
+        let sessions = orchestrator.sessions().clone();
+        let tenants = orchestrator.tenants().policy();
         Self {
-            registry: ToolRegistry::new(orchestrator),
+            registry: ToolRegistry::new(orchestrator.clone()),
             server_info: ServerInfo::default(),
-            initialized: false,
+            initialized: AtomicBool::new(false),
+            events,
+            prompts,
+            sessions,
+            tenants,
+            orchestrator,
+            self_test: false,
+            diagnostics: std::sync::RwLock::new(None),
         }
     }
 
+    /// Run the startup self-test on `initialize`, reporting problems in
+    /// the result's instructions and at [`DIAGNOSTICS_URI`].
+    pub fn with_self_test(mut self, enabled: bool) -> Self {
+        self.self_test = enabled;
+        self
+    }
+
+    /// Results of the latest self-test, if one ran.
+    pub fn diagnostics(&self) -> Option<Diagnostics> {
+        self.diagnostics
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Tenants networked clients authenticate as.
+    pub fn tenants(&self) -> &TenantPolicy {
+        &self.tenants
+    }
+
+    /// Set per-tool execution time limits.
+    pub fn with_tool_timeouts(mut self, timeouts: ToolTimeouts) -> Self {
+        self.registry.set_timeouts(timeouts);
+        self
+    }
+
+    /// Set the post-processing applied to tool results.
+    pub fn with_post_processing(mut self, post_processing: PostProcessing) -> Self {
+        self.registry.set_post_processing(post_processing);
+        self
+    }
+
     /// Run the server on stdio.
+    ///
+    /// Requests are handled concurrently, so a call waiting on the user (such
+    /// as a captcha intervention) does not block the call that resolves it.
+    /// Orchestrator events are forwarded as `notifications/message`.
     pub async fn run_stdio(&mut self) -> Result<()> {
         info!("Starting Agent MCP Server on stdio");
 
-        let stdin = std::io::stdin();
-        let mut stdout = std::io::stdout();
-        let reader = BufReader::new(stdin.lock());
+        let this = &*self;
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        let mut events = this.events.subscribe();
+        let mut in_flight = FuturesUnordered::new();
 
-        for line in reader.lines() {
-            let line = line.map_err(|e| Error::Io(e))?;
-            if line.is_empty() {
-                continue;
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let Some(line) = line.map_err(Error::Io)? else {
+                        break;
+                    };
+                    if line.is_empty() {
+                        continue;
+                    }
+                    debug!("Received: {}", line);
+                    in_flight.push(async move { this.handle_message(&line).await });
+                }
+                Some(response) = in_flight.next() => {
+                    write_message(&mut stdout, &response).await?;
+                }
+                Ok(event) = events.recv() => {
+                    if let Some(notification) = event_notification(&event) {
+                        write_message(&mut stdout, &notification).await?;
+                    }
+                }
             }
+        }
 
-            debug!("Received: {}", line);
-
-            let response = self.handle_message(&line).await;
-            let response_json = serde_json::to_string(&response)?;
-
-            debug!("Sending: {}", response_json);
-
-            writeln!(stdout, "{}", response_json).map_err(|e| Error::Io(e))?;
-            stdout.flush().map_err(|e| Error::Io(e))?;
+        while let Some(response) = in_flight.next().await {
+            write_message(&mut stdout, &response).await?;
         }
 
         Ok(())
     }
 
-    /// Handle a single message.
-    async fn handle_message(&mut self, message: &str) -> McpResponse {
+    /// Handle a single JSON-RPC message.
+    pub async fn handle_message(&self, message: &str) -> McpResponse {
+        self.handle_message_for(message, None).await
+    }
+
+    /// Handle a single JSON-RPC message from a tenant, attributing the
+    /// provider requests it makes to that tenant (the default tenant if
+    /// `None`).
+    pub async fn handle_message_for(&self, message: &str, tenant: Option<&str>) -> McpResponse {
         // Parse request
         let request: McpRequest = match serde_json::from_str(message) {
             Ok(req) => req,
@@ -73,10 +165,15 @@ impl AgentMcpServer {
 
         // Handle method
         match request.method.as_str() {
-            "initialize" => self.handle_initialize(&request),
+            "initialize" => self.handle_initialize(&request).await,
             "initialized" => self.handle_initialized(&request),
             "tools/list" => self.handle_tools_list(&request),
-            "tools/call" => self.handle_tools_call(&request).await,
+            "tools/call" => self.handle_tools_call(&request, tenant).await,
+            "prompts/list" => self.handle_prompts_list(&request),
+            "prompts/get" => self.handle_prompts_get(&request),
+            "resources/list" => self.handle_resources_list(&request, tenant),
+            "resources/templates/list" => self.handle_resource_templates_list(&request),
+            "resources/read" => self.handle_resources_read(&request, tenant),
             "ping" => self.handle_ping(&request),
             _ => {
                 McpResponse::error(
@@ -88,29 +185,41 @@ impl AgentMcpServer {
         }
     }
 
-    /// Handle initialize request.
-    fn handle_initialize(&mut self, request: &McpRequest) -> McpResponse {
+    /// Handle initialize request, running the self-test first if enabled.
+    async fn handle_initialize(&self, request: &McpRequest) -> McpResponse {
         info!("Initializing MCP server");
+        let instructions = if self.self_test {
+            let diagnostics = self.orchestrator.self_test().await;
+            let summary = diagnostics.summary();
+            *self.diagnostics.write().unwrap_or_else(|e| e.into_inner()) = Some(diagnostics);
+            summary
+        } else {
+            None
+        };
 
         let capabilities = ServerCapabilities {
             tools: Some(ToolCapabilities { list_changed: false }),
-            resources: None,
-            prompts: None,
+            resources: Some(ResourceCapabilities {
+                subscribe: false,
+                list_changed: false,
+            }),
+            prompts: Some(PromptCapabilities { list_changed: false }),
         };
 
-        McpResponse::success(
-            request.id.clone(),
-            json!({
-                "protocolVersion": "2024-11-05",
-                "capabilities": capabilities,
-                "serverInfo": self.server_info
-            }),
-        )
+        let mut result = json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": capabilities,
+            "serverInfo": self.server_info
+        });
+        if let Some(instructions) = instructions {
+            result["instructions"] = instructions.into();
+        }
+        McpResponse::success(request.id.clone(), result)
     }
 
     /// Handle initialized notification.
-    fn handle_initialized(&mut self, request: &McpRequest) -> McpResponse {
-        self.initialized = true;
+    fn handle_initialized(&self, request: &McpRequest) -> McpResponse {
+        self.initialized.store(true, Ordering::SeqCst);
         info!("MCP server initialized");
 
         // This is a notification, no response needed
@@ -130,7 +239,7 @@ impl AgentMcpServer {
     }
 
     /// Handle tools/call request.
-    async fn handle_tools_call(&self, request: &McpRequest) -> McpResponse {
+    async fn handle_tools_call(&self, request: &McpRequest, tenant: Option<&str>) -> McpResponse {
         // Extract tool name and arguments
         let name = request.params.get("name").and_then(|v| v.as_str());
         let arguments = request
@@ -153,21 +262,199 @@ impl AgentMcpServer {
         info!("Calling tool: {}", name);
 
         // Execute tool
-        match self.registry.execute(name, arguments).await {
+        match self.registry.execute_for(name, arguments, tenant).await {
             Ok(result) => McpResponse::success(request.id.clone(), serde_json::to_value(result).unwrap()),
             Err(e) => {
                 error!("Tool execution failed: {}", e);
-                McpResponse::error(
+                McpResponse::error_with_data(
                     request.id.clone(),
                     error_codes::INTERNAL_ERROR,
                     e.to_string(),
+                    e.data(),
                 )
             }
         }
     }
 
+    /// Handle prompts/list request.
+    fn handle_prompts_list(&self, request: &McpRequest) -> McpResponse {
+        let prompts: Vec<_> = self
+            .prompts
+            .list()
+            .iter()
+            .map(|p| {
+                let arguments: Vec<_> = p
+                    .variables()
+                    .into_iter()
+                    .map(|name| json!({ "name": name, "required": true }))
+                    .collect();
+                json!({
+                    "name": p.name,
+                    "description": p.description,
+                    "arguments": arguments,
+                })
+            })
+            .collect();
+
+        McpResponse::success(request.id.clone(), json!({ "prompts": prompts }))
+    }
+
+    /// Handle prompts/get request, filling in the prompt's variables.
+    fn handle_prompts_get(&self, request: &McpRequest) -> McpResponse {
+        let Some(name) = request.params.get("name").and_then(|v| v.as_str()) else {
+            return McpResponse::error(
+                request.id.clone(),
+                error_codes::INVALID_PARAMS,
+                "missing prompt name",
+            );
+        };
+        let arguments: HashMap<String, String> = request
+            .params
+            .get("arguments")
+            .cloned()
+            .and_then(|a| serde_json::from_value(a).ok())
+            .unwrap_or_default();
+
+        match self.prompts.get(name).and_then(|p| Ok((p.render(&arguments)?, p))) {
+            Ok((text, prompt)) => McpResponse::success(
+                request.id.clone(),
+                json!({
+                    "description": prompt.description,
+                    "messages": [{
+                        "role": "user",
+                        "content": { "type": "text", "text": text }
+                    }]
+                }),
+            ),
+            Err(e) => McpResponse::error(request.id.clone(), error_codes::INVALID_PARAMS, e.to_string()),
+        }
+    }
+
+    /// Handle resources/list request, listing the self-test results (once
+    /// a self-test ran) and the tenant's session transcripts.
+    fn handle_resources_list(&self, request: &McpRequest, tenant: Option<&str>) -> McpResponse {
+        let diagnostics = self.diagnostics().map(|d| {
+            json!({
+                "uri": DIAGNOSTICS_URI,
+                "name": "Self-test",
+                "description": match d.problems().count() {
+                    0 => "Startup self-test: no problems found".to_string(),
+                    n => format!("Startup self-test: {} problem(s) found", n),
+                },
+                "mimeType": "text/markdown",
+            })
+        });
+        let sessions = self.sessions_for(tenant).list();
+        let resources: Vec<_> = diagnostics
+            .into_iter()
+            .chain(sessions.iter().map(|s| {
+                json!({
+                    "uri": s.uri(),
+                    "name": s.title(),
+                    "description": s.summary(),
+                    "mimeType": "text/markdown",
+                })
+            }))
+            .collect();
+
+        McpResponse::success(request.id.clone(), json!({ "resources": resources }))
+    }
+
+    /// The session transcripts `tenant` may see.
+    fn sessions_for(&self, tenant: Option<&str>) -> SessionStore {
+        match tenant {
+            Some(tenant) => self.sessions.for_tenant(tenant),
+            None => self.sessions.clone(),
+        }
+    }
+
+    /// Handle resources/templates/list request.
+    fn handle_resource_templates_list(&self, request: &McpRequest) -> McpResponse {
+        McpResponse::success(
+            request.id.clone(),
+            json!({
+                "resourceTemplates": [{
+                    "uriTemplate": format!("{}{{id}}", SESSION_SCHEME),
+                    "name": "Conversation session",
+                    "description": "Transcript of a conversation with a provider",
+                    "mimeType": "text/markdown",
+                }]
+            }),
+        )
+    }
+
+    /// Handle resources/read request, returning a session transcript or
+    /// the self-test results.
+    fn handle_resources_read(&self, request: &McpRequest, tenant: Option<&str>) -> McpResponse {
+        let Some(uri) = request.params.get("uri").and_then(|v| v.as_str()) else {
+            return McpResponse::error(
+                request.id.clone(),
+                error_codes::INVALID_PARAMS,
+                "missing resource uri",
+            );
+        };
+        if uri == DIAGNOSTICS_URI {
+            if let Some(diagnostics) = self.diagnostics() {
+                return McpResponse::success(
+                    request.id.clone(),
+                    json!({
+                        "contents": [{
+                            "uri": uri,
+                            "mimeType": "text/markdown",
+                            "text": diagnostics.to_markdown(),
+                        }]
+                    }),
+                );
+            }
+        }
+
+        match self.sessions_for(tenant).get_by_uri(uri) {
+            Some(session) => McpResponse::success(
+                request.id.clone(),
+                json!({
+                    "contents": [{
+                        "uri": uri,
+                        "mimeType": "text/markdown",
+                        "text": session.to_markdown(),
+                    }]
+                }),
+            ),
+            None => McpResponse::error(
+                request.id.clone(),
+                error_codes::RESOURCE_NOT_FOUND,
+                format!("unknown resource: {}", uri),
+            ),
+        }
+    }
+
     /// Handle ping request.
     fn handle_ping(&self, request: &McpRequest) -> McpResponse {
         McpResponse::success(request.id.clone(), json!({}))
     }
 }
+
+/// Write a JSON-RPC message as one line on stdout.
+async fn write_message(stdout: &mut tokio::io::Stdout, message: &impl Serialize) -> Result<()> {
+    let json = serde_json::to_string(message)?;
+    debug!("Sending: {}", json);
+
+    stdout
+        .write_all(format!("{}\n", json).as_bytes())
+        .await
+        .map_err(Error::Io)?;
+    stdout.flush().await.map_err(Error::Io)
+}
+
+/// Wrap an orchestrator event as an MCP logging notification, unless it is
+/// telemetry not meant for the client.
+fn event_notification(event: &OrchestratorEvent) -> Option<McpNotification> {
+    let level = event.client_level()?;
+    Some(McpNotification::new(
+        "notifications/message",
+        json!({
+            "level": level,
+            "logger": "agent-mcp",
+            "data": event,
+        }),
+    ))
+}
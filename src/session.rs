@@ -0,0 +1,519 @@
+//! Conversation transcripts, exposed as `session://{id}` MCP resources.
+//!
+//! Every prompt sent to a provider is recorded as a turn in the session for
+//! that provider conversation, so the host can pull earlier exchanges back
+//! into its own context on demand. Sessions are active until the browser
+//! sessions are closed (or the server restarts), then archived. Sessions are
+//! saved in the workspace's storage, sealed if encryption is enabled.
+//!
+//! A session can be forked at a turn to explore another direction, possibly
+//! with another provider: the branch starts with the turns up to the fork
+//! and grows on its own, leaving the original intact.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use embeddenator_webpuppet::PromptResponse;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::library::line_diff;
+use crate::storage::{MemoryStorage, Records, SESSIONS};
+use crate::tenant::{can_access, default_tenant};
+
+/// URI scheme of session resources.
+pub const SESSION_SCHEME: &str = "session://";
+
+/// One prompt and its response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turn {
+    /// Prompt sent to the provider.
+    pub prompt: String,
+    /// Provider's response.
+    pub response: String,
+    /// When the response arrived.
+    pub timestamp: DateTime<Utc>,
+    /// Provider that answered, if not the session's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+}
+
+/// Where a branch was forked from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForkPoint {
+    /// Session the branch was forked from.
+    pub session_id: String,
+    /// Turns of that session the branch starts with.
+    pub turn: usize,
+}
+
+/// A conversation with one provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Session ID.
+    pub id: String,
+    /// Provider the conversation is with.
+    pub provider: String,
+    /// Provider's conversation ID, if it reported one.
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    /// Whether the conversation has ended.
+    #[serde(default)]
+    pub archived: bool,
+    /// When the first prompt was sent.
+    pub started_at: DateTime<Utc>,
+    /// When the last response arrived.
+    pub updated_at: DateTime<Utc>,
+    /// Exchanges, oldest first.
+    pub turns: Vec<Turn>,
+    /// Where the session was forked from, if it is a branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<ForkPoint>,
+    /// Tenant whose prompts the session records.
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+}
+
+impl Session {
+    /// Resource URI of the session.
+    pub fn uri(&self) -> String {
+        format!("{}{}", SESSION_SCHEME, self.id)
+    }
+
+    /// Short human-readable title.
+    pub fn title(&self) -> String {
+        format!(
+            "{} conversation, {}",
+            self.provider,
+            self.started_at.format("%Y-%m-%d %H:%M UTC")
+        )
+    }
+
+    /// One-line summary: status, size, and the opening prompt.
+    pub fn summary(&self) -> String {
+        let status = if self.archived { "archived" } else { "active" };
+        let opening = self
+            .turns
+            .first()
+            .map(|t| t.prompt.lines().next().unwrap_or_default())
+            .unwrap_or_default();
+        let opening: String = opening.chars().take(80).collect();
+        let fork = self
+            .forked_from
+            .as_ref()
+            .map(|f| format!(", forked from {} at turn {}", f.session_id, f.turn))
+            .unwrap_or_default();
+        format!("{}, {} turns{}: {}", status, self.turns.len(), fork, opening)
+    }
+
+    /// Render the transcript as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n{}\n", self.title(), self.summary());
+        out.push_str(&self.turns_markdown(0));
+        out
+    }
+
+    /// The turns from index `from` on, in Markdown.
+    fn turns_markdown(&self, from: usize) -> String {
+        let mut out = String::new();
+        for (i, turn) in self.turns.iter().enumerate().skip(from) {
+            out.push_str(&format!(
+                "\n## Turn {} ({})\n\n**User:**\n\n{}\n\n**{}:**\n\n{}\n",
+                i + 1,
+                turn.timestamp.format("%H:%M:%S"),
+                turn.prompt,
+                turn.provider.as_deref().unwrap_or(&self.provider),
+                turn.response
+            ));
+        }
+        out
+    }
+
+    /// Number of leading turns the two sessions share.
+    pub fn shared_turns(&self, other: &Session) -> usize {
+        self.turns
+            .iter()
+            .zip(&other.turns)
+            .take_while(|(a, b)| a.prompt == b.prompt && a.response == b.response)
+            .count()
+    }
+
+    /// Markdown comparison with another session: the turns they share and
+    /// a diff of the transcripts after them.
+    pub fn diff(&self, other: &Session) -> String {
+        let shared = self.shared_turns(other);
+        let mut out = format!(
+            "# Sessions `{}` and `{}`\n\n**Shared turns:** {} · **After them:** {} and {}\n\n",
+            self.id,
+            other.id,
+            shared,
+            self.turns.len() - shared,
+            other.turns.len() - shared
+        );
+        let (ours, theirs) = (self.turns_markdown(shared), other.turns_markdown(shared));
+        if ours == theirs {
+            out.push_str("The transcripts are identical.");
+        } else {
+            out.push_str(&format!("```diff\n{}\n```", line_diff(&ours, &theirs)));
+        }
+        out
+    }
+}
+
+/// Session transcripts, one record per session.
+///
+/// Sessions this instance is still recording are kept in memory as well;
+/// every other stored session (including those left by an earlier run or by
+/// another instance sharing the storage) is reported as archived.
+///
+/// A store handle is scoped to a tenant: it records sessions as that
+/// tenant's and only finds the sessions that tenant may see.
+#[derive(Clone)]
+pub struct SessionStore {
+    records: Records,
+    active: Arc<Mutex<BTreeMap<String, Session>>>,
+    tenant: String,
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::open(Records::new(Arc::new(MemoryStorage::new()), SESSIONS, None))
+    }
+}
+
+impl SessionStore {
+    /// An in-memory store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the store kept in `records`.
+    pub fn open(records: Records) -> Self {
+        Self {
+            records,
+            active: Arc::new(Mutex::new(BTreeMap::new())),
+            tenant: default_tenant(),
+        }
+    }
+
+    /// The same store, scoped to `tenant`.
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Record an exchange in the active session for the response's
+    /// conversation, starting a session if there is none. Returns the
+    /// session ID.
+    pub fn record(&self, prompt: &str, response: &PromptResponse) -> String {
+        let provider = response.provider.to_string();
+        let mut active = self.lock();
+        let existing = active
+            .values()
+            .find(|s| {
+                s.forked_from.is_none()
+                    && s.tenant == self.tenant
+                    && s.provider == provider
+                    && s.conversation_id == response.conversation_id
+            })
+            .map(|s| s.id.clone());
+        let id = existing.unwrap_or_else(|| {
+            let session = Session {
+                id: Uuid::new_v4().to_string(),
+                provider,
+                conversation_id: response.conversation_id.clone(),
+                archived: false,
+                started_at: response.timestamp,
+                updated_at: response.timestamp,
+                turns: Vec::new(),
+                forked_from: None,
+                tenant: self.tenant.clone(),
+            };
+            let id = session.id.clone();
+            active.insert(id.clone(), session);
+            id
+        });
+
+        if let Some(session) = active.get_mut(&id) {
+            session.turns.push(Turn {
+                prompt: prompt.to_string(),
+                response: response.text.clone(),
+                timestamp: response.timestamp,
+                provider: None,
+            });
+            session.updated_at = response.timestamp;
+            self.persist(session);
+        }
+        id
+    }
+
+    /// Fork a session after its first `turn` turns, optionally continuing
+    /// with another provider. The branch is active and the original is left
+    /// as it is.
+    pub fn fork(&self, id: &str, turn: usize, provider: Option<String>) -> Result<Session> {
+        let original = self
+            .get(id)
+            .ok_or_else(|| Error::InvalidParams(format!("no session with ID {}", id)))?;
+        if turn == 0 || turn > original.turns.len() {
+            return Err(Error::InvalidParams(format!(
+                "session {} has turns 1 to {}, not {}",
+                id,
+                original.turns.len(),
+                turn
+            )));
+        }
+        let now = Utc::now();
+        let provider = provider.unwrap_or_else(|| original.provider.clone());
+        // Earlier turns keep the provider that answered them
+        let answered_by = (provider != original.provider).then(|| original.provider.clone());
+        let branch = Session {
+            id: Uuid::new_v4().to_string(),
+            provider,
+            conversation_id: None,
+            archived: false,
+            started_at: now,
+            updated_at: now,
+            turns: original.turns[..turn]
+                .iter()
+                .map(|t| Turn {
+                    provider: t.provider.clone().or_else(|| answered_by.clone()),
+                    ..t.clone()
+                })
+                .collect(),
+            forked_from: Some(ForkPoint {
+                session_id: original.id,
+                turn,
+            }),
+            tenant: self.tenant.clone(),
+        };
+        self.persist(&branch);
+        self.lock().insert(branch.id.clone(), branch.clone());
+        Ok(branch)
+    }
+
+    /// Add an exchange to a session, such as a branch being continued,
+    /// reopening it if it was archived.
+    pub fn append(&self, id: &str, prompt: &str, response: &PromptResponse) -> Result<()> {
+        let mut session = self
+            .get(id)
+            .ok_or_else(|| Error::InvalidParams(format!("no session with ID {}", id)))?;
+        let provider = response.provider.to_string();
+        session.turns.push(Turn {
+            prompt: prompt.to_string(),
+            response: response.text.clone(),
+            timestamp: response.timestamp,
+            provider: (provider != session.provider).then_some(provider),
+        });
+        session.updated_at = response.timestamp;
+        session.archived = false;
+        self.persist(&session);
+        self.lock().insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    /// The sessions forked from the same original as `id`, the original
+    /// first and each branch after the session it was forked from, with
+    /// its depth in the tree.
+    pub fn branches(&self, id: &str) -> Result<Vec<(usize, Session)>> {
+        let sessions = self.list();
+        let find = |id: &str| sessions.iter().find(|s| s.id == id);
+        let mut root = find(id)
+            .ok_or_else(|| Error::InvalidParams(format!("no session with ID {}", id)))?;
+        while let Some(parent) = root.forked_from.as_ref().and_then(|f| find(&f.session_id)) {
+            root = parent;
+        }
+
+        let mut tree = Vec::new();
+        let mut stack = vec![(0, root)];
+        while let Some((depth, session)) = stack.pop() {
+            tree.push((depth, session.clone()));
+            let mut children: Vec<&Session> = sessions
+                .iter()
+                .filter(|s| s.forked_from.as_ref().is_some_and(|f| f.session_id == session.id))
+                .collect();
+            children.sort_by_key(|s| std::cmp::Reverse(s.started_at));
+            stack.extend(children.into_iter().map(|s| (depth + 1, s)));
+        }
+        Ok(tree)
+    }
+
+    /// Archive every active session.
+    pub fn archive_active(&self) {
+        let mut active = self.lock();
+        for (_, mut session) in std::mem::take(&mut *active) {
+            session.archived = true;
+            self.persist(&session);
+        }
+    }
+
+    /// Get a session by ID, if the store's tenant may see it.
+    pub fn get(&self, id: &str) -> Option<Session> {
+        let session = self.get_any(id)?;
+        can_access(&self.tenant, &session.tenant).then_some(session)
+    }
+
+    fn get_any(&self, id: &str) -> Option<Session> {
+        if let Some(session) = self.lock().get(id) {
+            return Some(session.clone());
+        }
+        match self.records.load::<Session>(id) {
+            Ok(session) => session.map(archived),
+            Err(e) => {
+                warn!("Failed to load session {}: {}", id, e);
+                None
+            }
+        }
+    }
+
+    /// Get a session by its `session://{id}` URI.
+    pub fn get_by_uri(&self, uri: &str) -> Option<Session> {
+        self.get(uri.strip_prefix(SESSION_SCHEME)?)
+    }
+
+    /// All sessions the store's tenant may see, most recently updated first.
+    pub fn list(&self) -> Vec<Session> {
+        let stored: Vec<Session> = self.records.load_all().unwrap_or_else(|e| {
+            warn!("Failed to load sessions: {}", e);
+            Vec::new()
+        });
+        let active = self.lock();
+        let mut sessions: Vec<_> = stored
+            .into_iter()
+            .filter(|s| !active.contains_key(&s.id))
+            .map(archived)
+            .chain(active.values().cloned())
+            .filter(|s| can_access(&self.tenant, &s.tenant))
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        sessions
+    }
+
+    /// Save a session. Failures are logged rather than failing the prompt.
+    fn persist(&self, session: &Session) {
+        if let Err(e) = self.records.save(&session.id, session) {
+            warn!("Failed to persist session {}: {}", session.id, e);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, Session>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A stored session seen from outside the instance recording it.
+fn archived(mut session: Session) -> Session {
+    session.archived = true;
+    session
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embeddenator_webpuppet::Provider;
+
+    fn response(provider: Provider, conversation_id: Option<&str>, text: &str) -> PromptResponse {
+        PromptResponse {
+            text: text.into(),
+            provider,
+            conversation_id: conversation_id.map(String::from),
+            timestamp: Utc::now(),
+            tokens_used: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_sessions_record_and_archive() {
+        let storage = Arc::new(MemoryStorage::new());
+        let store = SessionStore::open(Records::new(storage.clone(), SESSIONS, None));
+
+        let first = store.record("Hi", &response(Provider::Claude, Some("c1"), "Hello"));
+        let again = store.record("More", &response(Provider::Claude, Some("c1"), "Sure"));
+        let other = store.record("Hi", &response(Provider::Grok, Some("c1"), "Hey"));
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+
+        let session = store.get_by_uri(&format!("session://{}", first)).unwrap();
+        assert_eq!(session.turns.len(), 2);
+        assert!(session.to_markdown().contains("**claude:**\n\nSure"));
+
+        store.archive_active();
+        let next = store.record("New", &response(Provider::Claude, Some("c1"), "Ok"));
+        assert_ne!(next, first);
+
+        let reopened = SessionStore::open(Records::new(storage, SESSIONS, None));
+        assert_eq!(reopened.list().len(), 3);
+        assert!(reopened.list().iter().all(|s| s.archived));
+    }
+
+    #[test]
+    fn test_sessions_fork_into_branches() {
+        let store = SessionStore::new();
+        let original = store.record("Plan it", &response(Provider::Claude, None, "Step one"));
+        store.record("Go on", &response(Provider::Claude, None, "Step two"));
+        store.record("Finish", &response(Provider::Claude, None, "Done"));
+        assert!(store.fork(&original, 4, None).is_err());
+
+        // The branch switches provider; its copied turns keep who answered
+        let branch = store.fork(&original, 1, Some("grok".into())).unwrap();
+        assert_eq!(branch.turns.len(), 1);
+        assert_eq!(branch.turns[0].provider.as_deref(), Some("claude"));
+        store
+            .append(&branch.id, "Try another way", &response(Provider::Grok, None, "Other"))
+            .unwrap();
+        let nested = store.fork(&branch.id, 2, None).unwrap();
+
+        // New exchanges of the original conversation stay out of branches
+        store.record("Again", &response(Provider::Grok, None, "Hm"));
+        assert_eq!(store.get(&original).unwrap().turns.len(), 3);
+        assert_eq!(store.get(&branch.id).unwrap().turns.len(), 2);
+
+        let tree: Vec<(usize, String)> = store
+            .branches(&nested.id)
+            .unwrap()
+            .into_iter()
+            .map(|(depth, s)| (depth, s.id))
+            .collect();
+        assert_eq!(
+            tree,
+            vec![(0, original.clone()), (1, branch.id.clone()), (2, nested.id)]
+        );
+
+        let diff = store
+            .get(&original)
+            .unwrap()
+            .diff(&store.get(&branch.id).unwrap());
+        assert!(diff.contains("**Shared turns:** 1 · **After them:** 2 and 1"));
+        assert!(diff.contains("- Step two") && diff.contains("+ Other"));
+        assert!(diff.contains("+ **grok:**"));
+    }
+
+    #[test]
+    fn test_sessions_are_scoped_to_their_tenant() {
+        let store = SessionStore::new();
+        let owner = store.for_tenant("http:198.51.100.1");
+        let other = store.for_tenant("http:198.51.100.2");
+
+        let id = owner.record("Hi", &response(Provider::Claude, Some("c1"), "Hello"));
+        // Another tenant on the same conversation gets a session of its own
+        let theirs = other.record("Hi", &response(Provider::Claude, Some("c1"), "Hey"));
+        assert_ne!(id, theirs);
+
+        assert!(other.get(&id).is_none());
+        assert!(other.fork(&id, 1, None).is_err());
+        assert!(other.branches(&id).is_err());
+        let reply = response(Provider::Claude, None, "Sure");
+        assert!(other.append(&id, "More", &reply).is_err());
+        assert_eq!(other.list().len(), 1);
+
+        let branch = owner.fork(&id, 1, None).unwrap();
+        assert_eq!(branch.tenant, "http:198.51.100.1");
+        assert_eq!(owner.list().len(), 2);
+        assert_eq!(store.list().len(), 3);
+    }
+}
@@ -0,0 +1,228 @@
+//! Provider preferences imported from VS Code settings.
+//!
+//! The companion VS Code extension exports its settings as a
+//! `settings.json`-style block, so users configure providers and budgets in
+//! one place. Keys in the `embeddenator.agent` section set provider routing
+//! priorities, disable providers, and set tenant budgets. Other keys are
+//! ignored, and so are the comments and trailing commas VS Code allows. The
+//! server reads the block at startup and applies it again whenever the file
+//! changes. Settings apply over the command-line configuration, so removing
+//! one from the file restores what was configured there.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::error::{Error, Result};
+use crate::orchestrator::AgentOrchestrator;
+use crate::router::ProviderPreferences;
+use crate::tenant::TenantPolicy;
+
+/// Prefix of the keys read from the block.
+const SECTION: &str = "embeddenator.agent.";
+
+/// How often the file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings read from the `embeddenator.agent` section.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditorSettings {
+    /// Routing priority keyed by provider name (higher = more preferred).
+    #[serde(default)]
+    pub provider_priorities: HashMap<String, u32>,
+    /// Providers never routed to or called.
+    #[serde(default)]
+    pub disabled_providers: Vec<String>,
+    /// Estimated spend allowed per budget period, in USD, keyed by tenant.
+    #[serde(default)]
+    pub budgets: HashMap<String, f64>,
+}
+
+impl EditorSettings {
+    /// Read the section from a `settings.json`-style block.
+    pub fn parse(text: &str) -> Result<Self> {
+        let block: Map<String, Value> = serde_json::from_str(&strip_jsonc(text))?;
+        let section = block
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(SECTION)?.to_string(), value)))
+            .collect();
+        let settings: Self = serde_json::from_value(Value::Object(section))?;
+
+        let mut names = settings
+            .provider_priorities
+            .keys()
+            .chain(&settings.disabled_providers);
+        if let Some(unknown) = names.find(|n| Provider::from_string(n).is_none()) {
+            return Err(Error::Config(format!(
+                "unknown provider in VS Code settings: {}",
+                unknown
+            )));
+        }
+        if let Some((tenant, _)) = settings.budgets.iter().find(|(_, usd)| **usd < 0.0) {
+            return Err(Error::Config(format!(
+                "negative budget for tenant {} in VS Code settings",
+                tenant
+            )));
+        }
+        Ok(settings)
+    }
+
+    /// Read the section from the file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// `base` with these settings' priorities and disabled providers.
+    pub fn preferences(&self, base: &ProviderPreferences) -> ProviderPreferences {
+        let mut preferences = base.clone();
+        for (name, priority) in &self.provider_priorities {
+            if let Some(provider) = Provider::from_string(name) {
+                preferences.set_priority(provider, *priority);
+            }
+        }
+        for name in &self.disabled_providers {
+            if let Some(provider) = Provider::from_string(name) {
+                preferences.set_disabled(provider, true);
+            }
+        }
+        preferences
+    }
+
+    /// `base` with these settings' budgets.
+    pub fn tenants(&self, base: &TenantPolicy) -> TenantPolicy {
+        let mut policy = base.clone();
+        for (tenant, budget) in &self.budgets {
+            policy.tenants.entry(tenant.clone()).or_default().budget_usd = Some(*budget);
+        }
+        policy
+    }
+}
+
+/// Remove the comments and trailing commas VS Code allows in settings files.
+fn strip_jsonc(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|c| *c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                out.push(' ');
+            }
+            ('}' | ']', _) => {
+                let kept = out.trim_end().len();
+                if out[..kept].ends_with(',') {
+                    out.remove(kept - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Apply the settings at `path` to the orchestrator again whenever the file
+/// changes. A file that fails to parse is reported and the settings applied
+/// last stay in effect.
+pub fn spawn_settings_watcher(orchestrator: &AgentOrchestrator, path: PathBuf) -> JoinHandle<()> {
+    let orchestrator = orchestrator.clone();
+    tokio::spawn(async move {
+        let mut last = modified(&path);
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            poll.tick().await;
+            let current = modified(&path);
+            if current == last {
+                continue;
+            }
+            last = current;
+            match EditorSettings::load(&path) {
+                Ok(settings) => {
+                    orchestrator.apply_settings(&settings).await;
+                    info!("Reloaded VS Code settings from {}", path.display());
+                }
+                Err(e) => warn!("Ignoring VS Code settings {}: {}", path.display(), e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::TenantConfig;
+
+    #[test]
+    fn test_editor_settings() {
+        let settings = EditorSettings::parse(
+            r#"{
+                // Exported by the extension
+                "editor.fontSize": 14,
+                "embeddenator.agent.providerPriorities": { "grok": 120, }, /* over claude */
+                "embeddenator.agent.disabledProviders": ["chatgpt"],
+                "embeddenator.agent.budgets": { "default": 5.0, "ci": 20 },
+                "embeddenator.agent.note": "a // b, }",
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(settings.provider_priorities["grok"], 120);
+
+        let preferences = settings.preferences(&ProviderPreferences::default());
+        assert_eq!(preferences.priority(Provider::Grok), 120);
+        assert_eq!(preferences.priority(Provider::Claude), 100);
+        assert!(preferences.is_disabled(Provider::ChatGpt));
+        assert!(!preferences.is_disabled(Provider::Claude));
+
+        let mut base = TenantPolicy::default();
+        base.tenants.insert(
+            "ci".into(),
+            TenantConfig {
+                requests_per_minute: Some(10),
+                ..TenantConfig::default()
+            },
+        );
+        let policy = settings.tenants(&base);
+        assert_eq!(policy.tenants["ci"].budget_usd, Some(20.0));
+        assert_eq!(policy.tenants["ci"].requests_per_minute, Some(10));
+        assert_eq!(policy.tenants["default"].budget_usd, Some(5.0));
+
+        let unknown = r#"{ "embeddenator.agent.disabledProviders": ["nobody"] }"#;
+        assert!(matches!(
+            EditorSettings::parse(unknown),
+            Err(Error::Config(_))
+        ));
+    }
+}
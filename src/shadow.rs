@@ -0,0 +1,229 @@
+//! Champion/challenger shadow testing.
+//!
+//! A sample of automatically routed prompts is duplicated, in the
+//! background, to a challenger provider. A judge grades both answers and the
+//! comparison is stored; the caller only ever sees the champion's answer.
+//! Stored comparisons export as an evaluation report, so the challenger's
+//! record can feed routing priorities like any other evaluation.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::eval::{EvalReport, EvalResult};
+use crate::grading::GradingPolicy;
+use crate::storage::Records;
+
+/// Name of the evaluation suite shadow comparisons export as.
+pub const SHADOW_SUITE: &str = "shadow";
+
+fn default_sample_rate() -> f64 {
+    0.1
+}
+
+/// Which prompts are shadowed, by whom, and who judges.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowPolicy {
+    /// Provider that answers shadowed prompts in the background.
+    pub challenger: String,
+    /// Only shadow prompts this provider answered (any provider if unset).
+    #[serde(default)]
+    pub champion: Option<String>,
+    /// Share of eligible prompts shadowed (0.0-1.0).
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Provider that grades both answers.
+    pub judge_provider: String,
+    /// Grading criteria given to the judge.
+    #[serde(default)]
+    pub rubric: Option<String>,
+}
+
+impl ShadowPolicy {
+    /// Resolve the challenger provider.
+    pub fn challenger(&self) -> Result<Provider> {
+        Provider::from_string(&self.challenger).ok_or_else(|| {
+            Error::Config(format!("unknown challenger provider: {}", self.challenger))
+        })
+    }
+
+    /// Check if prompts answered by `provider` are eligible for shadowing.
+    pub fn shadows(&self, provider: Provider) -> bool {
+        self.champion
+            .as_deref()
+            .is_none_or(|c| Provider::from_string(c) == Some(provider))
+    }
+
+    /// Grading policy the judge applies to both answers.
+    pub fn grading(&self) -> GradingPolicy {
+        GradingPolicy {
+            judge_provider: self.judge_provider.clone(),
+            rubric: self.rubric.clone(),
+        }
+    }
+}
+
+/// A champion's and a challenger's answers to one prompt, graded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowComparison {
+    /// Comparison ID.
+    pub id: String,
+    /// Prompt both providers answered.
+    pub prompt: String,
+    /// Provider whose answer the caller saw.
+    pub champion: String,
+    /// Provider that answered in the background.
+    pub challenger: String,
+    /// Judge's grade of the champion's answer.
+    pub champion_score: Option<f64>,
+    /// Judge's grade of the challenger's answer.
+    pub challenger_score: Option<f64>,
+    /// Champion response latency in milliseconds.
+    pub champion_latency_ms: u64,
+    /// Challenger response latency in milliseconds.
+    pub challenger_latency_ms: u64,
+    /// Error, if the challenger failed to answer.
+    pub challenger_error: Option<String>,
+    /// When the comparison was made.
+    pub created_at: DateTime<Utc>,
+}
+
+impl ShadowComparison {
+    /// Provider with the better grade, if both were graded and differ.
+    pub fn winner(&self) -> Option<&str> {
+        match (self.champion_score, self.challenger_score) {
+            (Some(a), Some(b)) if a > b => Some(&self.champion),
+            (Some(a), Some(b)) if b > a => Some(&self.challenger),
+            _ => None,
+        }
+    }
+}
+
+/// Persisted shadow comparisons, and the sampler choosing prompts to shadow.
+#[derive(Clone)]
+pub struct ShadowStore {
+    records: Records,
+    eligible: Arc<AtomicU64>,
+}
+
+impl ShadowStore {
+    /// Open the store over a collection.
+    pub fn open(records: Records) -> Self {
+        Self {
+            records,
+            eligible: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The challenger that should shadow a prompt `champion` answered, if
+    /// the policy covers it and the prompt falls in the sample.
+    ///
+    /// Sampling is even rather than random: with a rate of 0.25, every
+    /// fourth eligible prompt is shadowed.
+    pub fn challenger_for(&self, policy: &ShadowPolicy, champion: Provider) -> Option<Provider> {
+        let challenger = policy.challenger().ok()?;
+        if challenger == champion || !policy.shadows(champion) {
+            return None;
+        }
+        let rate = policy.sample_rate.clamp(0.0, 1.0);
+        let n = self.eligible.fetch_add(1, Ordering::Relaxed) as f64;
+        let sampled = ((n + 1.0) * rate).floor() > (n * rate).floor();
+        sampled.then_some(challenger)
+    }
+
+    /// Store a comparison.
+    pub fn record(&self, comparison: &ShadowComparison) -> Result<()> {
+        self.records.save(&comparison.id, comparison)
+    }
+
+    /// All comparisons, oldest first.
+    pub fn list(&self) -> Result<Vec<ShadowComparison>> {
+        let mut comparisons: Vec<ShadowComparison> = self.records.load_all()?;
+        comparisons.sort_by_key(|c| c.created_at);
+        Ok(comparisons)
+    }
+
+    /// Comparisons as an evaluation report with one case per comparison.
+    pub fn report(&self) -> Result<EvalReport> {
+        let comparisons = self.list()?;
+        let started_at = comparisons.first().map_or_else(Utc::now, |c| c.created_at);
+        let results = comparisons
+            .into_iter()
+            .flat_map(|c| {
+                let champion = EvalResult {
+                    case_id: c.id.clone(),
+                    provider: c.champion.to_lowercase(),
+                    score: c.champion_score,
+                    latency_ms: c.champion_latency_ms,
+                    response: None,
+                    error: None,
+                };
+                let challenger = EvalResult {
+                    case_id: c.id,
+                    provider: c.challenger.to_lowercase(),
+                    score: c.challenger_score,
+                    latency_ms: c.challenger_latency_ms,
+                    response: None,
+                    error: c.challenger_error,
+                };
+                [champion, challenger]
+            })
+            .collect();
+        Ok(EvalReport {
+            suite: SHADOW_SUITE.into(),
+            started_at,
+            results,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn test_sampling_and_report() {
+        let store = ShadowStore::open(Records::new(
+            Arc::new(MemoryStorage::new()),
+            "shadow",
+            None,
+        ));
+        let policy = ShadowPolicy {
+            challenger: "gemini".into(),
+            champion: Some("claude".into()),
+            sample_rate: 0.25,
+            judge_provider: "chatgpt".into(),
+            rubric: None,
+        };
+        assert_eq!(store.challenger_for(&policy, Provider::ChatGpt), None);
+        assert_eq!(store.challenger_for(&policy, Provider::Gemini), None);
+        let sampled = (0..8)
+            .filter(|_| store.challenger_for(&policy, Provider::Claude).is_some())
+            .count();
+        assert_eq!(sampled, 2);
+
+        let comparison = ShadowComparison {
+            id: "c1".into(),
+            prompt: "Explain borrowing".into(),
+            champion: "Claude".into(),
+            challenger: "Gemini".into(),
+            champion_score: Some(0.7),
+            challenger_score: Some(0.9),
+            champion_latency_ms: 1200,
+            challenger_latency_ms: 900,
+            challenger_error: None,
+            created_at: Utc::now(),
+        };
+        assert_eq!(comparison.winner(), Some("Gemini"));
+        store.record(&comparison).unwrap();
+
+        let scores = store.report().unwrap().provider_scores();
+        assert_eq!(scores["claude"], 0.7);
+        assert_eq!(scores["gemini"], 0.9);
+    }
+}
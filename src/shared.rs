@@ -0,0 +1,414 @@
+//! Router state shared between server instances.
+//!
+//! Each instance keeps its own router, but provider health, rate-limit
+//! cooldowns, and per-provider request quotas are kept in storage, so
+//! instances sharing a backend act on the same counters. Without this, each
+//! instance would retry a rate-limited provider on its own schedule and spend
+//! the full quota by itself.
+//!
+//! Background work, such as queued auto runs, may only use a share of each
+//! quota, and its share of a provider's daily limit is spread evenly over the
+//! day, so it neither spends a consumer plan's messages in the first hour nor
+//! leaves nothing for interactive use.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use chrono::{DateTime, DurationRound, Utc};
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::{Error, Result};
+use crate::storage::Records;
+
+/// Length of the request quota window.
+const QUOTA_WINDOW: Duration = Duration::from_secs(60);
+/// Length of the daily limit window.
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// Consecutive failures after which a provider is considered unhealthy.
+const FAILURE_THRESHOLD: u32 = 3;
+/// How long a failing provider stays unhealthy after its last failure.
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(300);
+/// Cooldown after a rate limit that did not say when to retry.
+const DEFAULT_RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Shared routing state for one provider.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SharedProviderState {
+    /// Failures since the last success, across all instances.
+    pub consecutive_failures: u32,
+    /// When the last failure happened.
+    pub last_failure: Option<DateTime<Utc>>,
+    /// No instance may send to the provider before this time.
+    pub rate_limited_until: Option<DateTime<Utc>>,
+    /// Start of the current quota window.
+    pub window_start: Option<DateTime<Utc>>,
+    /// Requests sent in the current quota window.
+    pub window_requests: u32,
+    /// Background requests sent in the current quota window.
+    #[serde(default)]
+    pub window_background_requests: u32,
+    /// Start of the current daily limit window (midnight UTC).
+    #[serde(default)]
+    pub day_start: Option<DateTime<Utc>>,
+    /// Requests sent today.
+    #[serde(default)]
+    pub day_requests: u32,
+    /// Background requests sent today.
+    #[serde(default)]
+    pub day_background_requests: u32,
+}
+
+impl SharedProviderState {
+    /// Check if the provider is usable at `now`.
+    pub fn is_healthy(&self, now: DateTime<Utc>) -> bool {
+        let failing = self.consecutive_failures >= FAILURE_THRESHOLD
+            && self
+                .last_failure
+                .is_some_and(|t| now < t + FAILURE_COOLDOWN);
+        let cooling = self.rate_limited_until.is_some_and(|t| now < t);
+        !failing && !cooling
+    }
+}
+
+/// Requests spent against a provider's quota in the current minute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// Requests sent in the current window, across all instances.
+    pub used: u32,
+    /// Requests allowed per window.
+    pub limit: u32,
+}
+
+fn default_background_share() -> f64 {
+    0.5
+}
+
+/// Daily provider limits and how much of each quota background work may
+/// use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchedulePolicy {
+    /// Maximum requests per day (UTC) keyed by provider name, such as a
+    /// consumer plan's message cap.
+    #[serde(default)]
+    pub daily_limits: HashMap<String, u32>,
+    /// Share (0.0-1.0) of each provider's per-minute quota and daily limit
+    /// background work may use.
+    #[serde(default = "default_background_share")]
+    pub background_share: f64,
+}
+
+impl Default for SchedulePolicy {
+    fn default() -> Self {
+        Self {
+            daily_limits: HashMap::new(),
+            background_share: default_background_share(),
+        }
+    }
+}
+
+/// Who is waiting on a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RequestClass {
+    /// A client waiting for the answer.
+    #[default]
+    Interactive,
+    /// Queued work nobody is waiting on, paced to leave room for
+    /// interactive use.
+    Background,
+}
+
+/// Provider health, cooldowns, and quotas kept in shared storage.
+#[derive(Clone)]
+pub struct SharedRouterState {
+    records: Records,
+    /// Maximum requests per minute, keyed by lowercase provider name.
+    quotas: HashMap<String, u32>,
+    /// Daily limits, keyed by lowercase provider name, and the background
+    /// share.
+    schedule: SchedulePolicy,
+}
+
+impl SharedRouterState {
+    /// Keep shared state in `records`, enforcing `quotas` (requests per
+    /// minute per provider).
+    pub fn new(records: Records, quotas: HashMap<String, u32>) -> Self {
+        let quotas = quotas
+            .into_iter()
+            .map(|(name, quota)| (name.to_lowercase(), quota))
+            .collect();
+        Self {
+            records,
+            quotas,
+            schedule: SchedulePolicy::default(),
+        }
+    }
+
+    /// Enforce daily limits and pace background requests by `schedule`.
+    pub fn with_schedule(mut self, mut schedule: SchedulePolicy) -> Self {
+        schedule.daily_limits = schedule
+            .daily_limits
+            .into_iter()
+            .map(|(name, limit)| (name.to_lowercase(), limit))
+            .collect();
+        schedule.background_share = schedule.background_share.clamp(0.0, 1.0);
+        self.schedule = schedule;
+        self
+    }
+
+    /// Current shared state of a provider.
+    pub fn state(&self, provider: Provider) -> SharedProviderState {
+        self.records
+            .load(&provider.to_string())
+            .unwrap_or_else(|e| {
+                warn!("Failed to load shared state for {}: {}", provider, e);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check if no instance has found the provider failing or rate limited.
+    pub fn is_healthy(&self, provider: Provider) -> bool {
+        self.state(provider).is_healthy(Utc::now())
+    }
+
+    /// Quota usage of every provider with a quota, keyed by provider name.
+    pub fn quota_usage(&self) -> BTreeMap<String, QuotaUsage> {
+        let now = Utc::now();
+        Provider::all()
+            .into_iter()
+            .filter_map(|provider| {
+                let limit = *self.quotas.get(&provider.to_string())?;
+                let state = self.state(provider);
+                let current = state.window_start.is_some_and(|t| now < t + QUOTA_WINDOW);
+                let used = if current { state.window_requests } else { 0 };
+                Some((provider.to_string(), QuotaUsage { used, limit }))
+            })
+            .collect()
+    }
+
+    /// Reserve a request to a provider.
+    ///
+    /// Fails with `RateLimited` while the provider is cooling down after a
+    /// rate limit, once its quota for the current minute or its daily limit
+    /// is spent, or, for background requests, until their next paced slot.
+    pub fn acquire(&self, provider: Provider, class: RequestClass) -> Result<()> {
+        self.acquire_at(provider, class, Utc::now())
+    }
+
+    fn acquire_at(
+        &self,
+        provider: Provider,
+        class: RequestClass,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let name = provider.to_string();
+        let quota = self.quotas.get(&name).copied();
+        let daily_limit = self.schedule.daily_limits.get(&name).copied();
+        let background = class == RequestClass::Background;
+        // Background work gets at least one request, so it is slowed, never
+        // stopped
+        let share = |limit: u32| ((limit as f64 * self.schedule.background_share) as u32).max(1);
+        let mut refusal = None;
+        self.update(provider, |state| {
+            refusal = None;
+            if let Some(until) = state.rate_limited_until.filter(|t| now < *t) {
+                refusal = Some(("cooling down after a rate limit", until));
+                return;
+            }
+            if let Some(quota) = quota {
+                let window_end = state.window_start.map(|t| t + QUOTA_WINDOW);
+                if window_end.is_none_or(|end| now >= end) {
+                    state.window_start = Some(now);
+                    state.window_requests = 0;
+                    state.window_background_requests = 0;
+                }
+                let end = window_end.unwrap_or(now + QUOTA_WINDOW);
+                if state.window_requests >= quota {
+                    refusal = Some(("request quota for this minute is spent", end));
+                    return;
+                }
+                if background && state.window_background_requests >= share(quota) {
+                    refusal = Some(("background share of this minute's quota is spent", end));
+                    return;
+                }
+            }
+            if let Some(limit) = daily_limit {
+                let day_start = now.duration_trunc(chrono::Duration::days(1)).unwrap_or(now);
+                if state.day_start != Some(day_start) {
+                    state.day_start = Some(day_start);
+                    state.day_requests = 0;
+                    state.day_background_requests = 0;
+                }
+                let tomorrow = day_start + DAY;
+                if state.day_requests >= limit {
+                    refusal = Some(("daily limit is spent", tomorrow));
+                    return;
+                }
+                if background {
+                    let allowance = share(limit);
+                    let used = state.day_background_requests;
+                    if used >= allowance {
+                        refusal = Some(("background share of the daily limit is spent", tomorrow));
+                        return;
+                    }
+                    // Spread the allowance evenly: the next slot opens once
+                    // `used / allowance` of the day has passed
+                    let next_slot = day_start + DAY.mul_f64(used as f64 / allowance as f64);
+                    if now < next_slot {
+                        refusal = Some(("background requests are paced", next_slot));
+                        return;
+                    }
+                }
+            }
+            if quota.is_some() {
+                state.window_requests += 1;
+                if background {
+                    state.window_background_requests += 1;
+                }
+            }
+            if daily_limit.is_some() {
+                state.day_requests += 1;
+                if background {
+                    state.day_background_requests += 1;
+                }
+            }
+        })?;
+
+        match refusal {
+            Some((reason, until)) => {
+                let wait = (until - now).num_seconds().max(1) as u64;
+                Err(Error::RateLimited {
+                    message: format!("{} {} (shared across instances)", provider, reason),
+                    retry_after_secs: Some(wait),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Record a successful request.
+    pub fn record_success(&self, provider: Provider) {
+        self.update_logged(provider, |state| state.consecutive_failures = 0);
+    }
+
+    /// Record a failed request. Rate limits start a cooldown for every
+    /// instance; other errors count against health if they indicate a
+    /// provider problem.
+    pub fn record_error(&self, provider: Provider, error: &Error) {
+        let now = Utc::now();
+        if matches!(error, Error::RateLimited { .. }) {
+            let cooldown = error
+                .retry_after_secs()
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_RATE_LIMIT_COOLDOWN);
+            self.update_logged(provider, |state| {
+                let until = now + cooldown;
+                state.rate_limited_until =
+                    Some(state.rate_limited_until.map_or(until, |t| t.max(until)));
+            });
+        } else if error.affects_health() {
+            self.update_logged(provider, |state| {
+                state.consecutive_failures += 1;
+                state.last_failure = Some(now);
+            });
+        }
+    }
+
+    fn update(
+        &self,
+        provider: Provider,
+        mut f: impl FnMut(&mut SharedProviderState),
+    ) -> Result<SharedProviderState> {
+        self.records.update(
+            &provider.to_string(),
+            |state: Option<SharedProviderState>| {
+                let mut state = state.unwrap_or_default();
+                f(&mut state);
+                state
+            },
+        )
+    }
+
+    /// Update shared state, logging failures rather than failing the request.
+    fn update_logged(&self, provider: Provider, f: impl FnMut(&mut SharedProviderState)) {
+        if let Err(e) = self.update(provider, f) {
+            warn!("Failed to update shared state for {}: {}", provider, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use chrono::TimeZone;
+
+    use crate::storage::{MemoryStorage, ROUTING};
+
+    #[test]
+    fn test_instances_share_quotas_and_cooldowns() {
+        let storage = Arc::new(MemoryStorage::new());
+        let quotas = HashMap::from([("ChatGPT".to_string(), 2)]);
+        let first =
+            SharedRouterState::new(Records::new(storage.clone(), ROUTING, None), quotas.clone());
+        let second = SharedRouterState::new(Records::new(storage, ROUTING, None), quotas);
+
+        first.acquire(Provider::ChatGpt, RequestClass::Interactive).unwrap();
+        second.acquire(Provider::ChatGpt, RequestClass::Interactive).unwrap();
+        let refused = first.acquire(Provider::ChatGpt, RequestClass::Interactive).unwrap_err();
+        assert!(matches!(refused, Error::RateLimited { .. }));
+        assert!(refused.retry_after_secs().is_some_and(|s| s <= 60));
+        second.acquire(Provider::Claude, RequestClass::Interactive).unwrap();
+        assert_eq!(
+            first.quota_usage()["chatgpt"],
+            QuotaUsage { used: 2, limit: 2 }
+        );
+
+        let limited = Error::RateLimited {
+            message: "slow down".into(),
+            retry_after_secs: Some(30),
+        };
+        first.record_error(Provider::Claude, &limited);
+        assert!(!second.is_healthy(Provider::Claude));
+        assert!(second.acquire(Provider::Claude, RequestClass::Interactive).is_err());
+
+        second.record_success(Provider::Gemini);
+        assert!(first.is_healthy(Provider::Gemini));
+    }
+
+    #[test]
+    fn test_background_requests_are_paced() {
+        let records = Records::new(Arc::new(MemoryStorage::new()), ROUTING, None);
+        let shared = SharedRouterState::new(records, HashMap::new()).with_schedule(SchedulePolicy {
+            daily_limits: HashMap::from([("Claude".to_string(), 8)]),
+            background_share: 0.5,
+        });
+        let midnight = Utc.with_ymd_and_hms(2026, 10, 15, 0, 0, 0).unwrap();
+        let at = |hours| midnight + chrono::Duration::hours(hours);
+        let background = |now| shared.acquire_at(Provider::Claude, RequestClass::Background, now);
+
+        // Four background requests a day, one every six hours
+        background(at(0)).unwrap();
+        let paced = background(at(1)).unwrap_err();
+        assert_eq!(paced.retry_after_secs(), Some(5 * 60 * 60));
+        background(at(6)).unwrap();
+        background(at(13)).unwrap();
+        background(at(18)).unwrap();
+        assert!(background(at(23)).is_err());
+
+        // Interactive use gets the rest of the daily limit
+        for _ in 0..4 {
+            shared
+                .acquire_at(Provider::Claude, RequestClass::Interactive, at(23))
+                .unwrap();
+        }
+        let spent = shared
+            .acquire_at(Provider::Claude, RequestClass::Interactive, at(23))
+            .unwrap_err();
+        assert_eq!(spent.retry_after_secs(), Some(60 * 60));
+        background(at(24)).unwrap();
+    }
+}
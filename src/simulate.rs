@@ -0,0 +1,173 @@
+//! Routing dry runs.
+//!
+//! A simulation routes a hypothetical prompt under every routing profile, and
+//! under the canary's priorities while a rollout runs, without sending
+//! anything. It reports the provider each strategy would choose, the
+//! objectives behind the choice, and why other providers were not candidates,
+//! so profiles, priorities, and rules can be tuned before real traffic sees
+//! them.
+
+use embeddenator_webpuppet::Provider;
+
+use crate::objectives::ObjectiveWeights;
+use crate::router::{RankedProvider, TaskType};
+use crate::security::DataClassification;
+
+/// Name of the strategy using weights passed with the call.
+pub const CUSTOM: &str = "custom";
+
+/// Name of the strategy using the canary's priorities.
+pub const CANARY: &str = "canary";
+
+/// How one strategy would route the prompt.
+#[derive(Debug, Clone)]
+pub struct SimulatedStrategy {
+    /// Profile name, or `custom` or `canary`.
+    pub name: String,
+    /// Objective weights the strategy ranks by.
+    pub weights: ObjectiveWeights,
+    /// Candidates, best first.
+    pub ranking: Vec<RankedProvider>,
+    /// Whether a real call would use this strategy.
+    pub used: bool,
+}
+
+impl SimulatedStrategy {
+    /// The provider the strategy would choose.
+    pub fn chosen(&self) -> Option<&RankedProvider> {
+        self.ranking.first()
+    }
+}
+
+/// How a prompt would be routed, without sending it.
+#[derive(Debug, Clone)]
+pub struct RoutingSimulation {
+    /// Task type the prompt is routed as.
+    pub task: TaskType,
+    /// Detected language of the prompt.
+    pub language: Option<String>,
+    /// Estimated tokens of the prompt and its context.
+    pub prompt_tokens: u64,
+    /// Data classification of the prompt.
+    pub classification: DataClassification,
+    /// The strategies, the one a real call would use first.
+    pub strategies: Vec<SimulatedStrategy>,
+    /// Providers that are not candidates, with the reason.
+    pub excluded: Vec<(Provider, String)>,
+}
+
+impl RoutingSimulation {
+    /// The strategy a real call would use.
+    pub fn used(&self) -> Option<&SimulatedStrategy> {
+        self.strategies.iter().find(|s| s.used)
+    }
+
+    /// The choices of every strategy, the objectives behind them, and the
+    /// providers left out, in Markdown.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "# Routing Simulation\n\n**Task:** {} · **Language:** {} · **Prompt tokens:** {} · **Classification:** {}\n\n## Choices\n\n| Strategy | Provider | Score | Margin |\n|---|---|---|---|\n",
+            self.task.name(),
+            self.language.as_deref().unwrap_or("undetected"),
+            self.prompt_tokens,
+            self.classification
+        );
+        for strategy in &self.strategies {
+            let name = if strategy.used {
+                format!("**{}** (used)", strategy.name)
+            } else {
+                strategy.name.clone()
+            };
+            let row = match (strategy.chosen(), strategy.ranking.get(1)) {
+                (Some(best), Some(next)) => format!(
+                    "| {} | {} | {:.1} | +{:.1} over {} |",
+                    name,
+                    best.provider,
+                    best.score,
+                    best.score - next.score,
+                    next.provider
+                ),
+                (Some(best), None) => {
+                    format!(
+                        "| {} | {} | {:.1} | only candidate |",
+                        name, best.provider, best.score
+                    )
+                }
+                (None, _) => format!("| {} | none | - | - |", name),
+            };
+            report.push_str(&row);
+            report.push('\n');
+        }
+
+        if let Some(used) = self.used().filter(|s| !s.ranking.is_empty()) {
+            report.push_str(&format!(
+                "\n## Objectives\n\nWeights of {}: quality {}, cost {}, latency {}, quota {}, freshness {}.\n\n| Provider | Quality | Cost | Latency | Quota | Freshness | Score |\n|---|---|---|---|---|---|---|\n",
+                used.name,
+                used.weights.quality,
+                used.weights.cost,
+                used.weights.latency,
+                used.weights.quota,
+                used.weights.freshness
+            ));
+            for ranked in &used.ranking {
+                let o = &ranked.objectives;
+                report.push_str(&format!(
+                    "| {} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.1} |\n",
+                    ranked.provider,
+                    o.quality,
+                    o.cost,
+                    o.latency,
+                    o.quota,
+                    o.freshness,
+                    ranked.score
+                ));
+            }
+        }
+
+        if !self.excluded.is_empty() {
+            report.push_str("\n## Not Considered\n\n");
+            for (provider, reason) in &self.excluded {
+                report.push_str(&format!("- {}: {}\n", provider, reason));
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::{AgentOrchestrator, PromptOptions};
+
+    #[tokio::test]
+    async fn test_simulation_reports_each_strategy() {
+        let orchestrator = AgentOrchestrator::new();
+        let options = PromptOptions {
+            classification: Some(DataClassification::Public),
+            ..Default::default()
+        };
+        let simulation = orchestrator
+            .simulate_routing("What is the capital of Peru?", None, &options)
+            .await
+            .unwrap();
+        assert_eq!(simulation.task, TaskType::Quick);
+        assert_eq!(simulation.used().unwrap().name, "quick");
+        assert!(simulation.strategies.iter().any(|s| s.name == "balanced"));
+
+        let long = "word ".repeat(150_000);
+        let simulation = orchestrator
+            .simulate_routing(&long, Some(TaskType::LargeContext), &options)
+            .await
+            .unwrap();
+        let chosen = simulation.used().unwrap().chosen().unwrap();
+        assert_eq!(chosen.provider, Provider::Gemini);
+        assert!(simulation
+            .excluded
+            .iter()
+            .any(|(p, reason)| *p == Provider::Perplexity && reason.contains("context window")));
+
+        let report = simulation.report();
+        assert!(report.contains("| **balanced** (used) | gemini |"));
+        assert!(report.contains("## Not Considered"));
+    }
+}
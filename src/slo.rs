@@ -0,0 +1,165 @@
+//! Per-provider response time objectives.
+//!
+//! A latency SLO names the response time a provider should stay within.
+//! Each request is counted as meeting or missing it, with timeouts counted
+//! as misses, over a sliding window of recent requests. A provider missing
+//! its objective too often in the window is in breach: the router lowers its
+//! score and an alert is raised. It recovers once as many requests in a
+//! row as the misses that put it in breach are within the objective.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+fn default_window() -> usize {
+    20
+}
+
+fn default_max_misses() -> usize {
+    5
+}
+
+fn default_penalty() -> f64 {
+    30.0
+}
+
+/// Response time objective for one provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencySlo {
+    /// Response time a request should stay within, in milliseconds.
+    pub target_ms: u64,
+    /// Recent requests the objective is judged over.
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Misses within the window that put the provider in breach.
+    #[serde(default = "default_max_misses")]
+    pub max_misses: usize,
+    /// Routing score penalty while in breach.
+    #[serde(default = "default_penalty")]
+    pub penalty: f64,
+}
+
+impl LatencySlo {
+    /// An objective of `target_ms` with the default window, misses, and
+    /// penalty.
+    pub fn new(target_ms: u64) -> Self {
+        Self {
+            target_ms,
+            window: default_window(),
+            max_misses: default_max_misses(),
+            penalty: default_penalty(),
+        }
+    }
+}
+
+/// A provider entering or leaving breach of its objective.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SloChange {
+    /// The provider missed its objective too often.
+    Breached {
+        /// Misses in the window.
+        misses: usize,
+        /// Requests in the window.
+        requests: usize,
+    },
+    /// The provider is back within its objective.
+    Recovered,
+}
+
+/// Recent outcomes of a provider's requests against its objective.
+#[derive(Debug, Clone)]
+pub struct SloTracker {
+    slo: LatencySlo,
+    /// Whether each recent request missed the objective, oldest first.
+    recent: VecDeque<bool>,
+    /// Requests in a row within the objective since the last miss.
+    streak: usize,
+    breached: bool,
+}
+
+impl SloTracker {
+    /// Track requests against `slo`.
+    pub fn new(slo: LatencySlo) -> Self {
+        Self {
+            slo,
+            recent: VecDeque::new(),
+            streak: 0,
+            breached: false,
+        }
+    }
+
+    /// The tracked objective.
+    pub fn slo(&self) -> &LatencySlo {
+        &self.slo
+    }
+
+    /// Whether the provider is in breach of its objective.
+    pub fn is_breached(&self) -> bool {
+        self.breached
+    }
+
+    /// Misses among the recent requests.
+    pub fn misses(&self) -> usize {
+        self.recent.iter().filter(|missed| **missed).count()
+    }
+
+    /// Count a request that took `latency`, or timed out when `None`.
+    /// Returns the change if the provider entered or left breach.
+    pub fn record(&mut self, latency: Option<Duration>) -> Option<SloChange> {
+        let target = Duration::from_millis(self.slo.target_ms);
+        let missed = latency.is_none_or(|latency| latency > target);
+        self.recent.push_back(missed);
+        while self.recent.len() > self.slo.window.max(1) {
+            self.recent.pop_front();
+        }
+        self.streak = if missed { 0 } else { self.streak + 1 };
+
+        let misses = self.misses();
+        if !self.breached && misses >= self.slo.max_misses.max(1) {
+            self.breached = true;
+            return Some(SloChange::Breached {
+                misses,
+                requests: self.recent.len(),
+            });
+        }
+        if self.breached && self.streak >= self.slo.max_misses.max(1) {
+            // Start afresh, so misses from before the recovery do not put
+            // the provider straight back in breach
+            self.breached = false;
+            self.recent.clear();
+            return Some(SloChange::Recovered);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slo_breach_and_recovery() {
+        let slo: LatencySlo =
+            serde_json::from_str(r#"{"target_ms": 1000, "max_misses": 2}"#).unwrap();
+        assert_eq!(slo.window, 20);
+        let mut tracker = SloTracker::new(slo);
+        let ms = |ms| Some(Duration::from_millis(ms));
+
+        assert_eq!(tracker.record(ms(1500)), None);
+        assert_eq!(tracker.record(ms(900)), None);
+        assert_eq!(
+            tracker.record(None),
+            Some(SloChange::Breached {
+                misses: 2,
+                requests: 3
+            })
+        );
+        assert!(tracker.is_breached());
+        assert_eq!(tracker.record(ms(1200)), None);
+        assert_eq!(tracker.record(ms(1000)), None);
+        assert_eq!(tracker.record(ms(300)), Some(SloChange::Recovered));
+        assert!(!tracker.is_breached());
+        assert_eq!(tracker.misses(), 0);
+    }
+}
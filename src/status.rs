@@ -0,0 +1,320 @@
+//! Machine-readable status file for editor integrations.
+//!
+//! Stdio clients cannot query the server out of band, so a companion VS Code
+//! extension or status bar script polls a small JSON file instead. The file
+//! lists active workflows, steps paused for human review, and budget usage,
+//! and is rewritten whenever a step changes state and every few seconds
+//! otherwise. Writes go through a temporary file and a rename, so readers
+//! never see a partial file.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::auto::spent_usd;
+use crate::cost::CostModel;
+use crate::error::Result;
+use crate::events::OrchestratorEvent;
+use crate::jobs::{Job, JobKind, JobState};
+use crate::orchestrator::AgentOrchestrator;
+use crate::shared::QuotaUsage;
+use crate::workflow::{StepConfig, StepState, Workflow, WorkflowState};
+
+/// How often the file is rewritten when nothing happens.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A workflow that has not finished.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowSummary {
+    /// Workflow ID.
+    pub id: String,
+    /// Workflow name.
+    pub name: String,
+    /// Current state.
+    pub state: WorkflowState,
+    /// Name of the step that runs next.
+    pub current_step: Option<String>,
+    /// Completed steps.
+    pub steps_completed: usize,
+    /// Total steps.
+    pub steps_total: usize,
+    /// Estimated spend so far, in USD.
+    pub spent_usd: f64,
+    /// Cost budget of a queued or running background run, in USD.
+    pub budget_usd: Option<f64>,
+    /// When the workflow last changed.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A step waiting for a human.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PausedReview {
+    /// Workflow ID.
+    pub workflow_id: String,
+    /// Workflow name.
+    pub workflow_name: String,
+    /// Name of the step awaiting review.
+    pub step_name: String,
+    /// What the reviewer is asked to check.
+    pub reason: String,
+    /// When the workflow paused.
+    pub since: DateTime<Utc>,
+}
+
+/// Spend and quota usage.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    /// Estimated spend of active workflows, in USD.
+    pub spent_usd: f64,
+    /// Total budget of active background runs, in USD.
+    pub budget_usd: Option<f64>,
+    /// Requests spent against each provider's per-minute quota.
+    pub provider_quotas: BTreeMap<String, QuotaUsage>,
+}
+
+/// Contents of the status file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    /// When the snapshot was taken.
+    pub updated_at: DateTime<Utc>,
+    /// Workflows that have not finished, most recently updated first.
+    pub active_workflows: Vec<WorkflowSummary>,
+    /// Steps waiting for a human, oldest first.
+    pub paused_reviews: Vec<PausedReview>,
+    /// Background jobs waiting for a worker.
+    pub queued_jobs: usize,
+    /// Background jobs being run.
+    pub running_jobs: usize,
+    /// Spend and quota usage.
+    pub budget: BudgetUsage,
+}
+
+impl StatusSnapshot {
+    /// Summarize workflows, jobs, and quota usage.
+    pub fn build(
+        workflows: &[Workflow],
+        jobs: &[Job],
+        costs: &CostModel,
+        provider_quotas: BTreeMap<String, QuotaUsage>,
+    ) -> Self {
+        let budgets: HashMap<&str, f64> = jobs
+            .iter()
+            .filter(|j| !j.state.is_finished())
+            .filter_map(|j| match &j.kind {
+                JobKind::AutoRun {
+                    workflow_id,
+                    max_cost_usd,
+                    ..
+                } => Some((workflow_id.as_str(), (*max_cost_usd)?)),
+            })
+            .collect();
+
+        let mut active_workflows: Vec<WorkflowSummary> = workflows
+            .iter()
+            .filter(|w| !matches!(w.state, WorkflowState::Completed | WorkflowState::Failed(_)))
+            .map(|w| WorkflowSummary {
+                id: w.id.clone(),
+                name: w.name.clone(),
+                state: w.state.clone(),
+                current_step: w.current().map(|s| s.name.clone()),
+                steps_completed: w.steps.iter().filter(|s| s.result.is_some()).count(),
+                steps_total: w.steps.len(),
+                spent_usd: spent_usd(w, costs),
+                budget_usd: budgets.get(w.id.as_str()).copied(),
+                updated_at: w.updated_at,
+            })
+            .collect();
+        active_workflows.sort_by_key(|w| std::cmp::Reverse(w.updated_at));
+
+        let mut paused_reviews: Vec<PausedReview> = workflows
+            .iter()
+            .filter_map(|w| {
+                let step = w
+                    .current()
+                    .filter(|s| s.state == StepState::WaitingForHuman)?;
+                Some(PausedReview {
+                    workflow_id: w.id.clone(),
+                    workflow_name: w.name.clone(),
+                    step_name: step.name.clone(),
+                    reason: match &step.config {
+                        StepConfig::HumanReview { prompt } => prompt.clone(),
+                        _ => "step requires approval".into(),
+                    },
+                    since: w.updated_at,
+                })
+            })
+            .collect();
+        paused_reviews.sort_by_key(|r| r.since);
+
+        let budget = BudgetUsage {
+            spent_usd: active_workflows.iter().map(|w| w.spent_usd).sum(),
+            budget_usd: active_workflows
+                .iter()
+                .filter_map(|w| w.budget_usd)
+                .reduce(|a, b| a + b),
+            provider_quotas,
+        };
+        let count = |state: JobState| jobs.iter().filter(|j| j.state == state).count();
+
+        Self {
+            updated_at: Utc::now(),
+            active_workflows,
+            paused_reviews,
+            queued_jobs: count(JobState::Queued),
+            running_jobs: count(JobState::Running),
+            budget,
+        }
+    }
+
+    /// Human-readable summary, one line per workflow, review, and quota.
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![format!("Active workflows: {}", self.active_workflows.len())];
+        for w in &self.active_workflows {
+            let state = match &w.state {
+                WorkflowState::Pending => "pending",
+                WorkflowState::Running => "running",
+                WorkflowState::Paused => "paused",
+                WorkflowState::Completed => "completed",
+                WorkflowState::Failed(_) => "failed",
+            };
+            lines.push(format!(
+                "  {} ({}): {}, {}/{} steps, next {}, {} spent",
+                w.name,
+                w.id,
+                state,
+                w.steps_completed,
+                w.steps_total,
+                w.current_step.as_deref().unwrap_or("-"),
+                usd(w.spent_usd)
+            ));
+        }
+        lines.push(format!("Paused reviews: {}", self.paused_reviews.len()));
+        for r in &self.paused_reviews {
+            lines.push(format!("  {} / {}: {}", r.workflow_name, r.step_name, r.reason));
+        }
+        lines.push(format!(
+            "Jobs: {} queued, {} running",
+            self.queued_jobs, self.running_jobs
+        ));
+        let budget = self
+            .budget
+            .budget_usd
+            .map(|b| format!(" of {} budgeted", usd(b)))
+            .unwrap_or_default();
+        lines.push(format!("Spent: {}{}", usd(self.budget.spent_usd), budget));
+        for (provider, quota) in &self.budget.provider_quotas {
+            lines.push(format!("Quota {}: {}/{} per minute", provider, quota.used, quota.limit));
+        }
+        lines.join("\n")
+    }
+
+    /// Write the snapshot to `path`, replacing the previous one atomically.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Format an amount in USD. Spend is never negative, and `abs` turns the
+/// `-0.0` of an empty sum into `0.0`.
+fn usd(amount: f64) -> String {
+    format!("${:.4}", amount.abs())
+}
+
+/// Keep a status file at `path` up to date with the orchestrator's state.
+pub fn spawn_status_file(orchestrator: &AgentOrchestrator, path: PathBuf) -> JoinHandle<()> {
+    let orchestrator = orchestrator.clone();
+    let mut events = orchestrator.events().subscribe();
+    tokio::spawn(async move {
+        let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => {}
+                event = events.recv() => match event {
+                    Ok(
+                        OrchestratorEvent::StepStateChanged { .. }
+                        | OrchestratorEvent::ReviewRequired { .. }
+                        | OrchestratorEvent::BudgetAlert { .. },
+                    )
+                    | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+            let snapshot = orchestrator.status_snapshot().await;
+            if let Err(e) = snapshot.write(&path) {
+                warn!("Failed to write status file {}: {}", path.display(), e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStorage, Records};
+    use crate::workflow::WorkflowStep;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_status_snapshot() {
+        let mut review = Workflow::new("release");
+        review.add_step(WorkflowStep::review("sign-off", "Check the changelog"));
+        review.steps[0].state = StepState::WaitingForHuman;
+        review.state = WorkflowState::Paused;
+        let mut done = Workflow::new("done");
+        done.state = WorkflowState::Completed;
+
+        let queue =
+            crate::jobs::JobQueue::open(Records::new(Arc::new(MemoryStorage::new()), "jobs", None));
+        let job = queue
+            .enqueue(
+                JobKind::AutoRun {
+                    workflow_id: review.id.clone(),
+                    max_cost_usd: Some(0.5),
+                    synthesizer: None,
+                    classification: None,
+                    visible: false,
+                    tenant: None,
+                },
+                0,
+            )
+            .unwrap();
+        let quotas = BTreeMap::from([("claude".to_string(), QuotaUsage { used: 3, limit: 10 })]);
+
+        let snapshot = StatusSnapshot::build(
+            &[review.clone(), done],
+            &[job],
+            &CostModel::default(),
+            quotas,
+        );
+        assert_eq!(snapshot.active_workflows.len(), 1);
+        assert_eq!(snapshot.active_workflows[0].budget_usd, Some(0.5));
+        assert_eq!(snapshot.paused_reviews[0].reason, "Check the changelog");
+        assert_eq!(snapshot.queued_jobs, 1);
+        assert_eq!(snapshot.budget.provider_quotas["claude"].used, 3);
+        let text = snapshot.to_text();
+        assert!(text.contains("  release / sign-off: Check the changelog\n"));
+        assert!(text.contains("Spent: $0.0000 of $0.5000 budgeted\n"));
+        assert!(text.ends_with("Quota claude: 3/10 per minute"));
+
+        let dir = std::env::temp_dir().join(format!("agent-mcp-status-{}", review.id));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("status.json");
+        snapshot.write(&path).unwrap();
+        let written: StatusSnapshot =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, snapshot);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
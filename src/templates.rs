@@ -0,0 +1,660 @@
+//! Built-in workflow templates.
+//!
+//! A template is a workflow definition for a common job, with `{{variable}}`
+//! placeholders filled in when a workflow is started from it. File
+//! variables take a path relative to the workspace, and the file's text is
+//! available to the steps as `{{<name>_contents}}`; pull request variables
+//! take `owner/repo#number`, and the pull request's diff is available as
+//! `{{<name>_diff}}`; issue variables take `owner/repo` or a list of issues,
+//! fetched into `{{<name>_json}}`; directory variables take a workspace
+//! directory, whose public API is extracted into `{{<name>_api}}`. Some
+//! templates build their steps from these, such as one review step per
+//! changed file or one summary per part of a long transcript.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::chunk::Chunker;
+use crate::error::{Error, Result};
+use crate::extract::ModuleApi;
+use crate::github::{split_diff, IssueBatch};
+use crate::library::{fill_json_variables, fill_variables};
+use crate::map::MapItem;
+use crate::plan::WorkflowDef;
+use crate::records::RecordField;
+
+/// Largest part of a file's diff reviewed in one step, in estimated tokens.
+pub const MAX_REVIEW_TOKENS: u64 = 6_000;
+
+/// Largest part of an issue's description sent for triage, in estimated
+/// tokens.
+pub const MAX_ISSUE_TOKENS: u64 = 1_500;
+
+/// Largest part of a module's public API documented in one prompt, in
+/// estimated tokens.
+pub const MAX_API_TOKENS: u64 = 4_000;
+
+/// Largest part of a transcript summarized in one prompt, in estimated
+/// tokens.
+pub const MAX_TRANSCRIPT_TOKENS: u64 = 3_000;
+
+/// Tokens repeated between parts of a transcript, so a discussion cut at a
+/// part boundary is seen whole in one of them.
+const TRANSCRIPT_OVERLAP_TOKENS: u64 = 200;
+
+/// What kind of value a template variable takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableKind {
+    /// Plain text.
+    Text,
+    /// A workspace file whose text the steps use.
+    File,
+    /// A GitHub pull request whose diff the steps use.
+    PullRequest,
+    /// GitHub issues, as `owner/repo` for its open issues or a list of
+    /// `owner/repo#number`, whose text and labels the steps use.
+    Issues,
+    /// A workspace directory whose public API the steps use.
+    Directory,
+}
+
+/// A value a template needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TemplateVariable {
+    /// Variable name.
+    pub name: &'static str,
+    /// What the value should be.
+    pub description: &'static str,
+    /// What kind of value it is.
+    pub kind: VariableKind,
+    /// Value used when none is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<&'static str>,
+}
+
+impl TemplateVariable {
+    /// Name of the variable holding the file's text or the pull request's
+    /// diff, which the caller supplies alongside the variable itself.
+    pub fn derived(&self) -> Option<String> {
+        match self.kind {
+            VariableKind::Text => None,
+            VariableKind::File => Some(format!("{}_contents", self.name)),
+            VariableKind::PullRequest => Some(format!("{}_diff", self.name)),
+            VariableKind::Issues => Some(format!("{}_json", self.name)),
+            VariableKind::Directory => Some(format!("{}_api", self.name)),
+        }
+    }
+}
+
+/// A built-in workflow definition with placeholders.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WorkflowTemplate {
+    /// Template name.
+    pub name: &'static str,
+    /// What workflows started from the template do.
+    pub description: &'static str,
+    /// Values the template needs.
+    pub variables: &'static [TemplateVariable],
+    #[serde(skip)]
+    definition: fn(&HashMap<String, String>) -> Result<Value>,
+}
+
+impl WorkflowTemplate {
+    /// Workflow definition with every variable filled in from `arguments`,
+    /// or from its default. File and pull request variables need their
+    /// [`derived`](TemplateVariable::derived) variable as well.
+    pub fn instantiate(&self, arguments: &HashMap<String, String>) -> Result<WorkflowDef> {
+        let mut arguments = arguments.clone();
+        for variable in self.variables {
+            if let Some(default) = variable.default {
+                arguments
+                    .entry(variable.name.to_string())
+                    .or_insert_with(|| default.to_string());
+            }
+        }
+        let missing: Vec<String> = self
+            .variables
+            .iter()
+            .flat_map(|v| std::iter::once(v.name.to_string()).chain(v.derived()))
+            .filter(|name| !arguments.contains_key(name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::InvalidParams(format!(
+                "template '{}' is missing variables: {}",
+                self.name,
+                missing.join(", ")
+            )));
+        }
+        Ok(serde_json::from_value((self.definition)(&arguments)?)?)
+    }
+}
+
+/// Every built-in template.
+pub fn templates() -> &'static [WorkflowTemplate] {
+    &TEMPLATES
+}
+
+/// The built-in template called `name`.
+pub fn template(name: &str) -> Result<&'static WorkflowTemplate> {
+    TEMPLATES.iter().find(|t| t.name == name).ok_or_else(|| {
+        let names: Vec<&str> = TEMPLATES.iter().map(|t| t.name).collect();
+        Error::InvalidParams(format!(
+            "unknown workflow template '{}' (available: {})",
+            name,
+            names.join(", ")
+        ))
+    })
+}
+
+const TEMPLATES: [WorkflowTemplate; 5] = [
+    WorkflowTemplate {
+        name: "test_generation",
+        description: "Write tests for a file, run them on a scratch copy of the workspace \
+                      with errors fed back for fixes, then review before applying.",
+        variables: &[TemplateVariable {
+            name: "target",
+            description: "File to write tests for",
+            kind: VariableKind::File,
+            default: None,
+        }],
+        definition: test_generation,
+    },
+    WorkflowTemplate {
+        name: "pr_review",
+        description: "Review a GitHub pull request file by file with several providers, \
+                      merge their findings into one review, and post it as a comment \
+                      once approved.",
+        variables: &[
+            TemplateVariable {
+                name: "pr",
+                description: "Pull request to review, as owner/repo#number or a URL",
+                kind: VariableKind::PullRequest,
+                default: None,
+            },
+            TemplateVariable {
+                name: "providers",
+                description: "Comma-separated providers reviewing each file",
+                kind: VariableKind::Text,
+                default: Some("claude,chatgpt,gemini"),
+            },
+        ],
+        definition: pr_review,
+    },
+    WorkflowTemplate {
+        name: "issue_triage",
+        description: "Classify GitHub issues, suggest labels and a priority, and draft a \
+                      first response for each, collected into one triage report for \
+                      review.",
+        variables: &[
+            TemplateVariable {
+                name: "issues",
+                description: "Issues to triage: owner/repo for its most recently updated \
+                              open issues, or a comma-separated list of owner/repo#number",
+                kind: VariableKind::Issues,
+                default: None,
+            },
+            TemplateVariable {
+                name: "categories",
+                description: "Comma-separated categories an issue is classified into",
+                kind: VariableKind::Text,
+                default: Some("bug,feature,question,documentation"),
+            },
+        ],
+        definition: issue_triage,
+    },
+    WorkflowTemplate {
+        name: "docs_generation",
+        description: "Extract the public API of a workspace directory, draft reference \
+                      docs per module with one provider while a second cross-checks \
+                      them, and assemble one document for review.",
+        variables: &[
+            TemplateVariable {
+                name: "path",
+                description: "Directory to document, relative to the workspace",
+                kind: VariableKind::Directory,
+                default: Some("src"),
+            },
+            TemplateVariable {
+                name: "writer",
+                description: "Provider drafting the docs",
+                kind: VariableKind::Text,
+                default: Some("claude"),
+            },
+            TemplateVariable {
+                name: "checker",
+                description: "Provider cross-checking the drafts against the API",
+                kind: VariableKind::Text,
+                default: Some("gemini"),
+            },
+            TemplateVariable {
+                name: "title",
+                description: "Title of the document",
+                kind: VariableKind::Text,
+                default: Some("API Reference"),
+            },
+        ],
+        definition: docs_generation,
+    },
+    WorkflowTemplate {
+        name: "meeting_notes",
+        description: "Summarize a long meeting transcript part by part, merge the \
+                      summaries into meeting notes, and extract the action items as \
+                      JSON, keeping only those several providers agree on.",
+        variables: &[
+            TemplateVariable {
+                name: "transcript",
+                description: "Transcript file, relative to the workspace",
+                kind: VariableKind::File,
+                default: None,
+            },
+            TemplateVariable {
+                name: "providers",
+                description: "Comma-separated providers extracting the action items",
+                kind: VariableKind::Text,
+                default: Some("claude,chatgpt,gemini"),
+            },
+        ],
+        definition: meeting_notes,
+    },
+];
+
+/// Tests are written as a diff so the validation step can apply and run them;
+/// `--patch-check-command` should run the test suite.
+fn test_generation(arguments: &HashMap<String, String>) -> Result<Value> {
+    let mut definition = json!({
+        "name": "Tests for {{target}}",
+        "steps": [
+            {
+                "name": "Write tests",
+                "type": "prompt",
+                "message": "Write unit tests for `{{target}}`, covering its public behavior, \
+                            edge cases, and error paths. Follow the test conventions already \
+                            used in the file or its project. Respond with a single unified \
+                            diff against the repository, with paths relative to its root, in \
+                            a ```diff block.\n\n`{{target}}`:\n```\n{{target_contents}}\n```"
+            },
+            {
+                "name": "Run tests",
+                "type": "validate_patch",
+                "source_step": "Write tests",
+                "max_refinements": 3
+            },
+            {
+                "name": "Review tests",
+                "type": "review",
+                "message": "Check that the tests for {{target}} are meaningful and pass before \
+                            applying the validated patch."
+            }
+        ]
+    });
+    fill_json_variables(&mut definition, arguments);
+    Ok(definition)
+}
+
+/// One parallel review step per changed file, or per part of a large file's
+/// diff, so each provider sees a diff small enough to review closely. The
+/// diff is inserted as is rather than through placeholders.
+fn pr_review(arguments: &HashMap<String, String>) -> Result<Value> {
+    let pr = &arguments["pr"];
+    let providers: Vec<&str> = arguments["providers"]
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    let files = split_diff(&arguments["pr_diff"]);
+    if files.is_empty() {
+        return Err(Error::InvalidParams(format!("{} changes no files", pr)));
+    }
+
+    let chunker = Chunker::FixedTokens {
+        max_tokens: MAX_REVIEW_TOKENS,
+        overlap_tokens: 0,
+    };
+    let mut steps = Vec::new();
+    for file in &files {
+        let parts = chunker.chunk(&file.diff);
+        for (i, part) in parts.iter().enumerate() {
+            let (name, scope) = match parts.len() {
+                1 => (format!("Review {}", file.path), "the diff".to_string()),
+                n => (
+                    format!("Review {} ({}/{})", file.path, i + 1, n),
+                    format!("part {} of {} of the diff", i + 1, n),
+                ),
+            };
+            steps.push(json!({
+                "name": name,
+                "type": "parallel",
+                "providers": providers,
+                "message": format!(
+                    "Review {} for `{}` in pull request {}. List each bug, security \
+                     problem, or maintainability issue as a separate bullet naming the \
+                     line it concerns, most important first. Do not restate what the \
+                     change does.\n\n```diff\n{}\n```",
+                    scope, file.path, pr, part.text
+                )
+            }));
+        }
+    }
+    steps.push(json!({
+        "name": "Consolidate review",
+        "type": "aggregate",
+        "message": format!(
+            "Write a single code review of pull request {} in Markdown: a one-line \
+             verdict, then the issues grouped by file.",
+            pr
+        )
+    }));
+    steps.push(json!({
+        "name": "Approve review",
+        "type": "review",
+        "message": format!("Check the consolidated review before it is posted on {}.", pr)
+    }));
+    steps.push(json!({
+        "name": "Post review",
+        "type": "github_comment",
+        "pull_request": pr,
+        "source_step": "Consolidate review"
+    }));
+    Ok(json!({ "name": format!("Review of {}", pr), "steps": steps }))
+}
+
+/// Asks for one JSON object per issue, so the map step's output is a
+/// report with the same fields for every issue.
+const TRIAGE_MESSAGE: &str = "Triage GitHub issue {{name}}.\n\n{{item}}\n\n\
+    Classify it as exactly one of: {{categories}}. Suggest labels, choosing only \
+    from the repository's labels: {{labels}}. Assign a priority from P0 (broken \
+    for many users, no workaround) to P3 (nice to have). Draft a short, friendly \
+    first response to the reporter that asks for anything missing to reproduce \
+    or decide on the issue.\n\n\
+    Respond with only a JSON object: {\"category\": \"...\", \"labels\": [\"...\"], \
+    \"priority\": \"P0-P3\", \"summary\": \"<one sentence>\", \"response\": \"<draft>\"}";
+
+/// One map item per issue, with its description shortened to fit.
+fn issue_triage(arguments: &HashMap<String, String>) -> Result<Value> {
+    let batch: IssueBatch = serde_json::from_str(&arguments["issues_json"])?;
+    if batch.issues.is_empty() {
+        return Err(Error::InvalidParams(format!(
+            "{} has no issues to triage",
+            arguments["issues"]
+        )));
+    }
+    let chunker = Chunker::FixedTokens {
+        max_tokens: MAX_ISSUE_TOKENS,
+        overlap_tokens: 0,
+    };
+    let items: Vec<MapItem> = batch
+        .issues
+        .iter()
+        .map(|issue| {
+            let parts = chunker.chunk(&issue.body);
+            let body = match &parts[..] {
+                [] => "(no description)".to_string(),
+                [only] => only.text.clone(),
+                [first, ..] => format!("{}\n[... truncated]", first.text),
+            };
+            let labels = match issue.labels.is_empty() {
+                true => "none".to_string(),
+                false => issue.labels.join(", "),
+            };
+            MapItem {
+                name: issue.reference.to_string(),
+                text: format!(
+                    "Title: {}\nCurrent labels: {}\n\n{}",
+                    issue.title, labels, body
+                ),
+            }
+        })
+        .collect();
+    let labels = match batch.labels.is_empty() {
+        true => "(none defined; suggest new ones)".to_string(),
+        false => batch.labels.join(", "),
+    };
+    let variables = HashMap::from([
+        ("categories".to_string(), arguments["categories"].clone()),
+        ("labels".to_string(), labels),
+    ]);
+    Ok(json!({
+        "name": format!("Triage of {}", arguments["issues"]),
+        "steps": [
+            {
+                "name": "Triage issues",
+                "type": "map",
+                "message": fill_variables(TRIAGE_MESSAGE, &variables),
+                "items": items,
+                "json": true
+            },
+            {
+                "name": "Review triage",
+                "type": "review",
+                "message": "Check the categories, labels, priorities, and drafted responses \
+                            before applying them."
+            }
+        ]
+    }))
+}
+
+const DOCS_MESSAGE: &str = "Write reference documentation in Markdown for `{{name}}` \
+    from its public API below. Open with a paragraph on what the module is for, then \
+    document each public item under a `###` heading: what it does, its parameters and \
+    return value, the errors it returns, and a short example where one helps. Describe \
+    only what the signatures and doc comments show.\n\n```\n{{item}}\n```";
+
+/// One map item per module, or per part of a module with a large API.
+fn docs_generation(arguments: &HashMap<String, String>) -> Result<Value> {
+    let modules: Vec<ModuleApi> = serde_json::from_str(&arguments["path_api"])?;
+    if modules.is_empty() {
+        return Err(Error::InvalidParams(format!(
+            "no public API found under {}",
+            arguments["path"]
+        )));
+    }
+    let chunker = Chunker::FixedTokens {
+        max_tokens: MAX_API_TOKENS,
+        overlap_tokens: 0,
+    };
+    let mut items = Vec::new();
+    for module in &modules {
+        let path = module.path.display();
+        let parts = chunker.chunk(&module.text());
+        for (i, part) in parts.iter().enumerate() {
+            items.push(MapItem {
+                name: match parts.len() {
+                    1 => path.to_string(),
+                    n => format!("{} ({}/{})", path, i + 1, n),
+                },
+                text: part.text.clone(),
+            });
+        }
+    }
+    Ok(json!({
+        "name": format!("Docs for {}", arguments["path"]),
+        "steps": [
+            {
+                "name": "Draft module docs",
+                "type": "map",
+                "message": DOCS_MESSAGE,
+                "items": items,
+                "provider": arguments["writer"],
+                "checker": arguments["checker"]
+            },
+            {
+                "name": "Assemble document",
+                "type": "assemble",
+                "message": arguments["title"],
+                "source_steps": ["Draft module docs"]
+            },
+            {
+                "name": "Review docs",
+                "type": "review",
+                "message": "Check the assembled documentation for accuracy and completeness \
+                            before publishing it."
+            }
+        ]
+    }))
+}
+
+const SUMMARY_MESSAGE: &str = "Summarize {{name}} of a meeting transcript as bullet \
+    points. Give one bullet per decision, open question, and commitment, and for each \
+    commitment say who made it and any deadline mentioned. Report only what the \
+    transcript says, naming speakers as it does.\n\n\"\"\"\n{{item}}\n\"\"\"";
+
+/// The action items are extracted from the part summaries rather than the
+/// merged notes, so each provider sees every commitment as it was first
+/// reported.
+fn meeting_notes(arguments: &HashMap<String, String>) -> Result<Value> {
+    let parts = Chunker::FixedTokens {
+        max_tokens: MAX_TRANSCRIPT_TOKENS,
+        overlap_tokens: TRANSCRIPT_OVERLAP_TOKENS,
+    }
+    .chunk(&arguments["transcript_contents"]);
+    if parts.is_empty() {
+        return Err(Error::InvalidParams(format!(
+            "{} is empty",
+            arguments["transcript"]
+        )));
+    }
+    let items: Vec<MapItem> = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| MapItem {
+            name: format!("part {} of {}", i + 1, parts.len()),
+            text: part.text.clone(),
+        })
+        .collect();
+    let providers: Vec<&str> = arguments["providers"]
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    Ok(json!({
+        "name": format!("Notes for {}", arguments["transcript"]),
+        "steps": [
+            {
+                "name": "Summarize parts",
+                "type": "map",
+                "message": SUMMARY_MESSAGE,
+                "items": items
+            },
+            {
+                "name": "Meeting notes",
+                "type": "aggregate",
+                "message": "Write meeting notes in Markdown: a short overview, then \
+                            Decisions, Open questions, and Action items sections.",
+                "source_steps": ["Summarize parts"]
+            },
+            {
+                "name": "Extract action items",
+                "type": "extract",
+                "message": "List every action item someone in the meeting committed to \
+                            or was assigned.",
+                "source_steps": ["Summarize parts"],
+                "providers": providers,
+                "fields": [
+                    RecordField::required("task", "what is to be done"),
+                    RecordField::optional("owner", "who does it"),
+                    RecordField::optional("due", "the deadline, as stated"),
+                    RecordField::optional("part", "the part of the transcript it is from")
+                ]
+            },
+            {
+                "name": "Review notes",
+                "type": "review",
+                "message": "Check the meeting notes and action items against the \
+                            transcript before sharing them."
+            }
+        ]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_instantiate() {
+        for template in templates() {
+            let arguments: HashMap<String, String> = template
+                .variables
+                .iter()
+                .flat_map(|v| {
+                    let value = match v.kind {
+                        VariableKind::Issues => ISSUES,
+                        VariableKind::Directory => API,
+                        _ => DIFF,
+                    };
+                    let derived = v.derived().map(|name| (name, value.into()));
+                    std::iter::once((v.name.to_string(), format!("o/r#{}", v.name.len())))
+                        .chain(derived)
+                })
+                .collect();
+            let definition = template.instantiate(&arguments).unwrap();
+            // Map steps fill in their own placeholders per item.
+            let json = serde_json::to_string(&definition)
+                .unwrap()
+                .replace("{{name}}", "")
+                .replace("{{item}}", "");
+            assert!(!json.contains("{{"), "{}: {}", template.name, json);
+            definition.to_workflow().unwrap();
+        }
+
+        let tests = template("test_generation").unwrap();
+        let arguments = HashMap::from([("target".to_string(), "src/lib.rs".to_string())]);
+        let missing = tests.instantiate(&arguments).unwrap_err();
+        assert!(missing.to_string().contains("target_contents"));
+        assert!(template("unknown").is_err());
+
+        let review = template("pr_review").unwrap();
+        let arguments = HashMap::from([
+            ("pr".to_string(), "o/r#7".to_string()),
+            ("pr_diff".to_string(), format!("{}{}", DIFF, DIFF.replace("a.rs", "b.rs"))),
+        ]);
+        let steps = review.instantiate(&arguments).unwrap().steps;
+        let names: Vec<_> = steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names[..3], ["Review a.rs", "Review b.rs", "Consolidate review"]);
+        assert_eq!(steps[0].providers.as_ref().unwrap().len(), 3);
+        assert_eq!(steps.last().unwrap().pull_request.as_deref(), Some("o/r#7"));
+
+        let triage = template("issue_triage").unwrap();
+        let arguments = HashMap::from([
+            ("issues".to_string(), "o/r".to_string()),
+            ("issues_json".to_string(), ISSUES.to_string()),
+        ]);
+        let step = &triage.instantiate(&arguments).unwrap().steps[0];
+        assert!(step.json);
+        assert!(step.message.contains("one of: bug,feature,question,documentation"));
+        assert!(step.message.contains("{{item}}"));
+        assert_eq!(step.items[0].name, "o/r#1");
+        assert!(step.items[0].text.ends_with("It breaks"));
+
+        let docs = template("docs_generation").unwrap();
+        let arguments = HashMap::from([("path_api".to_string(), API.to_string())]);
+        let steps = docs.instantiate(&arguments).unwrap().steps;
+        assert_eq!(steps[0].items[0].name, "src/lib.rs");
+        assert_eq!(steps[0].checker.as_deref(), Some("gemini"));
+        assert_eq!(steps[1].message, "API Reference");
+
+        let notes = template("meeting_notes").unwrap();
+        let transcript = (1..=900)
+            .map(|i| format!("Ann: point {} about the release.\n", i))
+            .collect::<String>();
+        let arguments = HashMap::from([
+            ("transcript".to_string(), "standup.txt".to_string()),
+            ("transcript_contents".to_string(), transcript),
+        ]);
+        let steps = notes.instantiate(&arguments).unwrap().steps;
+        assert!(steps[0].items.len() > 1);
+        assert_eq!(steps[0].items[1].name, format!("part 2 of {}", steps[0].items.len()));
+        assert_eq!(steps[2].step_type, "extract");
+        assert_eq!(steps[2].fields[0], RecordField::required("task", "what is to be done"));
+        assert_eq!(steps[2].providers.as_ref().unwrap().len(), 3);
+    }
+
+    const ISSUES: &str = r#"{"labels": ["bug"], "issues": [{"reference": {"owner": "o", "repo": "r", "number": 1}, "title": "Crash", "body": "It breaks", "labels": [], "url": "https://github.com/o/r/issues/1"}]}"#;
+
+    const API: &str = r#"[{"path": "src/lib.rs", "items": ["pub fn run()"]}]"#;
+
+    const DIFF: &str = "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-a\n+b\n";
+}
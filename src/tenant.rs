@@ -0,0 +1,387 @@
+//! Per-tenant usage attribution, budgets, and quotas.
+//!
+//! Every provider request is attributed to a tenant: the owner of the API key
+//! a networked client authenticated with, or [`DEFAULT_TENANT`] for stdio
+//! clients. Usage is kept in storage per tenant and budget period, so
+//! instances sharing a backend enforce the same limits. Each tenant's budget
+//! and request quota apply independently of every other tenant's.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::sha256_hex;
+use crate::error::{Error, Result};
+use crate::storage::Records;
+
+/// Tenant requests are attributed to when the transport has no identity.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Tenant stored records created before they had an owner belong to.
+pub fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+/// Whether `tenant` may see a workflow or session `owner` created. The local
+/// tenant sees everything; any other sees only its own.
+pub fn can_access(tenant: &str, owner: &str) -> bool {
+    tenant == DEFAULT_TENANT || tenant == owner
+}
+
+/// Length of the request quota window.
+const QUOTA_WINDOW: Duration = Duration::from_secs(60);
+
+/// How often a tenant's budget resets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    /// Every day at midnight UTC.
+    Daily,
+    /// On the first of every month, UTC.
+    #[default]
+    Monthly,
+}
+
+impl BudgetPeriod {
+    /// Start of the period containing `now`.
+    pub fn start(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let day = match self {
+            Self::Daily => now.day(),
+            Self::Monthly => 1,
+        };
+        Utc.with_ymd_and_hms(now.year(), now.month(), day, 0, 0, 0)
+            .single()
+            .unwrap_or(now)
+    }
+}
+
+/// Limits and credentials of one tenant.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantConfig {
+    /// SHA-256 hex digests of the API keys that authenticate as this tenant.
+    #[serde(default)]
+    pub key_sha256: Vec<String>,
+    /// Estimated spend allowed per period, in USD (unlimited if unset).
+    #[serde(default)]
+    pub budget_usd: Option<f64>,
+    /// How often the budget resets.
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    /// Maximum provider requests per minute (unlimited if unset).
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// Relative share of a contended provider under fair-share scheduling
+    /// (1.0 if unset).
+    #[serde(default)]
+    pub share: Option<f64>,
+}
+
+/// Tenants known to the server, keyed by tenant name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantPolicy {
+    /// Configured tenants.
+    #[serde(default)]
+    pub tenants: HashMap<String, TenantConfig>,
+}
+
+impl TenantPolicy {
+    /// Check if any tenants are configured.
+    pub fn is_empty(&self) -> bool {
+        self.tenants.is_empty()
+    }
+
+    /// Tenant an API key authenticates as.
+    pub fn authenticate(&self, api_key: &str) -> Option<&str> {
+        let digest = sha256_hex(api_key.as_bytes());
+        self.tenants
+            .iter()
+            .find(|(_, config)| {
+                config
+                    .key_sha256
+                    .iter()
+                    .any(|k| k.eq_ignore_ascii_case(&digest))
+            })
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Requests and spend attributed to one provider.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderUsage {
+    /// Requests answered.
+    pub requests: u64,
+    /// Estimated spend, in USD.
+    pub cost_usd: f64,
+}
+
+/// A tenant's usage in the current budget period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantUsage {
+    /// Tenant name.
+    pub tenant: String,
+    /// Start of the budget period.
+    pub period_start: DateTime<Utc>,
+    /// Requests sent.
+    pub requests: u64,
+    /// Estimated prompt tokens of answered requests.
+    pub prompt_tokens: u64,
+    /// Estimated response tokens of answered requests.
+    pub response_tokens: u64,
+    /// Estimated spend, in USD.
+    pub cost_usd: f64,
+    /// Usage by provider name.
+    #[serde(default)]
+    pub by_provider: BTreeMap<String, ProviderUsage>,
+    /// Start of the current quota window.
+    #[serde(default)]
+    pub window_start: Option<DateTime<Utc>>,
+    /// Requests sent in the current quota window.
+    #[serde(default)]
+    pub window_requests: u32,
+}
+
+impl TenantUsage {
+    fn new(tenant: &str, period_start: DateTime<Utc>) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            period_start,
+            requests: 0,
+            prompt_tokens: 0,
+            response_tokens: 0,
+            cost_usd: 0.0,
+            by_provider: BTreeMap::new(),
+            window_start: None,
+            window_requests: 0,
+        }
+    }
+}
+
+/// A tenant's usage next to its budget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantReport {
+    /// Usage in the current period.
+    pub usage: TenantUsage,
+    /// Budget for the period, in USD.
+    pub budget_usd: Option<f64>,
+    /// How often the budget resets.
+    pub period: BudgetPeriod,
+    /// Maximum provider requests per minute.
+    pub requests_per_minute: Option<u32>,
+}
+
+/// Persisted per-tenant usage, checked against each tenant's limits.
+#[derive(Clone)]
+pub struct TenantLedger {
+    records: Records,
+    policy: Arc<RwLock<TenantPolicy>>,
+}
+
+impl TenantLedger {
+    /// Keep usage in `records`, enforcing the limits in `policy`.
+    pub fn open(records: Records, policy: TenantPolicy) -> Self {
+        Self {
+            records,
+            policy: Arc::new(RwLock::new(policy)),
+        }
+    }
+
+    /// Tenants known to the server.
+    pub fn policy(&self) -> TenantPolicy {
+        self.policy.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the tenants' limits. Recorded usage is kept.
+    pub fn set_policy(&self, policy: TenantPolicy) {
+        *self.policy.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    /// Reserve a request for a tenant expected to cost `estimated_usd`.
+    ///
+    /// Fails with `BudgetExceeded` if the request would take the tenant over
+    /// its budget for the period, or `RateLimited` once its requests for the
+    /// current minute are spent.
+    pub fn acquire(&self, tenant: &str, estimated_usd: f64) -> Result<()> {
+        let config = self.config(tenant);
+        let now = Utc::now();
+        let mut refusal = None;
+        self.update(tenant, now, |usage| {
+            refusal = None;
+            if let Some(budget) = config.budget_usd {
+                if usage.cost_usd + estimated_usd > budget {
+                    refusal = Some(Error::BudgetExceeded(format!(
+                        "tenant {} has spent ${:.4} of its ${:.2} {} budget",
+                        tenant,
+                        usage.cost_usd,
+                        budget,
+                        match config.period {
+                            BudgetPeriod::Daily => "daily",
+                            BudgetPeriod::Monthly => "monthly",
+                        }
+                    )));
+                    return;
+                }
+            }
+            if let Some(quota) = config.requests_per_minute {
+                let window_end = usage.window_start.map(|t| t + QUOTA_WINDOW);
+                if window_end.is_none_or(|end| now >= end) {
+                    usage.window_start = Some(now);
+                    usage.window_requests = 0;
+                }
+                if usage.window_requests >= quota {
+                    let end = window_end.unwrap_or(now + QUOTA_WINDOW);
+                    refusal = Some(Error::RateLimited {
+                        message: format!(
+                            "tenant {} request quota for this minute is spent",
+                            tenant
+                        ),
+                        retry_after_secs: Some((end - now).num_seconds().max(1) as u64),
+                    });
+                    return;
+                }
+                usage.window_requests += 1;
+            }
+            usage.requests += 1;
+        })?;
+        refusal.map_or(Ok(()), Err)
+    }
+
+    /// Attribute an answered request to a tenant.
+    pub fn record(
+        &self,
+        tenant: &str,
+        provider: &str,
+        prompt_tokens: u64,
+        response_tokens: u64,
+        cost_usd: f64,
+    ) -> Result<()> {
+        self.update(tenant, Utc::now(), |usage| {
+            usage.prompt_tokens += prompt_tokens;
+            usage.response_tokens += response_tokens;
+            usage.cost_usd += cost_usd;
+            let by_provider = usage
+                .by_provider
+                .entry(provider.to_lowercase())
+                .or_default();
+            by_provider.requests += 1;
+            by_provider.cost_usd += cost_usd;
+        })
+        .map(|_| ())
+    }
+
+    /// A tenant's usage in the current period, with its limits.
+    pub fn report(&self, tenant: &str) -> Result<TenantReport> {
+        let config = self.config(tenant);
+        let now = Utc::now();
+        let period_start = config.period.start(now);
+        let usage = self
+            .records
+            .load::<TenantUsage>(tenant)?
+            .filter(|u| u.period_start == period_start)
+            .unwrap_or_else(|| TenantUsage::new(tenant, period_start));
+        Ok(TenantReport {
+            usage,
+            budget_usd: config.budget_usd,
+            period: config.period,
+            requests_per_minute: config.requests_per_minute,
+        })
+    }
+
+    /// Every tenant with recorded or configured usage, by name.
+    pub fn reports(&self) -> Result<Vec<TenantReport>> {
+        let mut names: Vec<String> = self
+            .records
+            .load_all::<TenantUsage>()?
+            .into_iter()
+            .map(|u| u.tenant)
+            .chain(self.policy().tenants.into_keys())
+            .collect();
+        names.sort();
+        names.dedup();
+        names.iter().map(|name| self.report(name)).collect()
+    }
+
+    fn config(&self, tenant: &str) -> TenantConfig {
+        self.policy
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .tenants
+            .get(tenant)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Update a tenant's usage, starting afresh when a new period began.
+    fn update(
+        &self,
+        tenant: &str,
+        now: DateTime<Utc>,
+        mut f: impl FnMut(&mut TenantUsage),
+    ) -> Result<TenantUsage> {
+        let period_start = self.config(tenant).period.start(now);
+        self.records.update(tenant, |usage: Option<TenantUsage>| {
+            let mut usage = usage
+                .filter(|u| u.period_start == period_start)
+                .unwrap_or_else(|| TenantUsage::new(tenant, period_start));
+            f(&mut usage);
+            usage
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_tenants_are_limited_independently() {
+        let policy: TenantPolicy = serde_json::from_value(serde_json::json!({
+            "tenants": {
+                "team-a": {
+                    "key_sha256": [sha256_hex(b"key-a")],
+                    "budget_usd": 0.05,
+                    "requests_per_minute": 2
+                },
+                "team-b": { "key_sha256": [sha256_hex(b"key-b")] }
+            }
+        }))
+        .unwrap();
+        assert_eq!(policy.authenticate("key-a"), Some("team-a"));
+        assert_eq!(policy.authenticate("wrong"), None);
+
+        let ledger = TenantLedger::open(
+            Records::new(Arc::new(MemoryStorage::new()), "tenants", None),
+            policy,
+        );
+        ledger.acquire("team-a", 0.01).unwrap();
+        ledger.record("team-a", "ChatGPT", 1000, 500, 0.03).unwrap();
+        let over = ledger.acquire("team-a", 0.03).unwrap_err();
+        assert_eq!(over.kind(), "budget_exceeded");
+        ledger.acquire("team-a", 0.01).unwrap();
+        let limited = ledger.acquire("team-a", 0.0).unwrap_err();
+        assert!(matches!(limited, Error::RateLimited { .. }));
+
+        for _ in 0..5 {
+            ledger.acquire("team-b", 1.0).unwrap();
+        }
+        let report = ledger.report("team-a").unwrap();
+        assert_eq!(report.usage.requests, 2);
+        assert_eq!(report.usage.by_provider["chatgpt"].requests, 1);
+        assert_eq!(ledger.report("team-b").unwrap().usage.cost_usd, 0.0);
+        assert_eq!(ledger.reports().unwrap().len(), 2);
+
+        let now = Utc.with_ymd_and_hms(2026, 3, 17, 15, 4, 5).unwrap();
+        assert_eq!(
+            BudgetPeriod::Monthly.start(now),
+            Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            BudgetPeriod::Daily.start(now),
+            Utc.with_ymd_and_hms(2026, 3, 17, 0, 0, 0).unwrap()
+        );
+    }
+}
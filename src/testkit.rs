@@ -0,0 +1,361 @@
+//! End-to-end test harness.
+//!
+//! [`MockProviders`] stands in for the browser: each provider answers with
+//! scripted replies, and every prompt it receives is recorded. [`TestClient`]
+//! drives an [`AgentMcpServer`] in-process with the same JSON-RPC messages an
+//! MCP client sends, so workflow definitions can be tested end to end without
+//! a browser or network:
+//!
+//! ```no_run
+//! # async fn example() -> embeddenator_agent_mcp::error::Result<()> {
+//! use embeddenator_agent_mcp::testkit::{MockProviders, TestClient};
+//! use embeddenator_webpuppet::Provider;
+//! use serde_json::json;
+//!
+//! let mocks = MockProviders::new().with_reply(Provider::Claude, "Paris");
+//! let client = TestClient::with_mocks(mocks.clone());
+//! client.initialize().await?;
+//! let text = client
+//!     .call_tool_text("agent_prompt", json!({ "message": "Capital of France?", "provider": "claude" }))
+//!     .await?;
+//! assert!(text.contains("Paris"));
+//! assert_eq!(mocks.calls().len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use embeddenator_webpuppet::{PromptRequest, PromptResponse, Provider};
+use serde_json::{json, Value};
+
+use crate::backend::{BackendCapabilities, ProviderBackend};
+use crate::error::{Error, Result};
+use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+use crate::protocol::{ContentItem, McpRequest, McpResponse, ToolCallResult, ToolDefinition};
+use crate::server::AgentMcpServer;
+use crate::storage::MemoryStorage;
+
+/// A scripted provider reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockReply {
+    /// Answer with this text.
+    Text(String),
+    /// Fail with this provider error message. Messages are classified like
+    /// real ones, so "rate limit exceeded" fails as a rate limit and
+    /// "captcha" as a verification wall.
+    Fail(String),
+    /// Answer with this text after waiting this long.
+    Delayed(Duration, String),
+}
+
+/// A prompt a mock provider received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCall {
+    /// Provider the prompt was sent to.
+    pub provider: Provider,
+    /// Prompt message.
+    pub message: String,
+    /// System context sent with the prompt.
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    queued: HashMap<Provider, VecDeque<MockReply>>,
+    fallback: HashMap<Provider, MockReply>,
+    calls: Vec<MockCall>,
+}
+
+/// Scripted stand-ins for providers.
+///
+/// A provider answers with its queued replies in order, then with its
+/// standing reply. A provider with neither echoes the prompt. Clones share
+/// scripts and the call log.
+#[derive(Debug, Clone, Default)]
+pub struct MockProviders {
+    state: Arc<Mutex<MockState>>,
+    latency: Duration,
+}
+
+impl MockProviders {
+    /// Create mock providers that echo every prompt.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every prompt to `provider` with `text`, after any queued
+    /// replies.
+    pub fn with_reply(self, provider: Provider, text: impl Into<String>) -> Self {
+        self.lock()
+            .fallback
+            .insert(provider, MockReply::Text(text.into()));
+        self
+    }
+
+    /// Queue a one-off reply from `provider`.
+    pub fn enqueue(&self, provider: Provider, reply: MockReply) {
+        self.lock()
+            .queued
+            .entry(provider)
+            .or_default()
+            .push_back(reply);
+    }
+
+    /// Delay every reply by `latency`.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Prompts received so far, oldest first.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.lock().calls.clone()
+    }
+
+    /// Answer a prompt as `provider`.
+    pub async fn respond(
+        &self,
+        provider: Provider,
+        request: &PromptRequest,
+    ) -> Result<PromptResponse> {
+        let reply = {
+            let mut state = self.lock();
+            state.calls.push(MockCall {
+                provider,
+                message: request.message.clone(),
+                context: request.context.clone(),
+            });
+            state
+                .queued
+                .get_mut(&provider)
+                .and_then(VecDeque::pop_front)
+                .or_else(|| state.fallback.get(&provider).cloned())
+        };
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        let text = match reply {
+            Some(MockReply::Text(text)) => text,
+            Some(MockReply::Delayed(delay, text)) => {
+                tokio::time::sleep(delay).await;
+                text
+            }
+            Some(MockReply::Fail(message)) => {
+                return Err(Error::from(embeddenator_webpuppet::Error::ProviderError {
+                    provider: provider.to_string(),
+                    message,
+                }))
+            }
+            None => format!("{} echo: {}", provider, request.message),
+        };
+        Ok(PromptResponse {
+            text,
+            provider,
+            conversation_id: None,
+            timestamp: Utc::now(),
+            tokens_used: None,
+            metadata: HashMap::new(),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait::async_trait]
+impl ProviderBackend for MockProviders {
+    async fn authenticate(&self, _provider: Provider) -> Result<()> {
+        Ok(())
+    }
+
+    async fn prompt(&self, provider: Provider, request: PromptRequest) -> Result<PromptResponse> {
+        self.respond(provider, &request).await
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            kind: "mock",
+            browser: false,
+            system_prompt: true,
+            continuation: false,
+        }
+    }
+}
+
+/// An in-process MCP client driving a server.
+pub struct TestClient {
+    server: AgentMcpServer,
+    next_id: AtomicU64,
+}
+
+impl TestClient {
+    /// Drive an existing server.
+    pub fn new(server: AgentMcpServer) -> Self {
+        Self {
+            server,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Drive a server whose providers are `mocks`, with in-memory state.
+    pub fn with_mocks(mocks: MockProviders) -> Self {
+        let config = OrchestratorConfig {
+            mock_providers: Some(mocks),
+            storage: Some(Arc::new(MemoryStorage::new())),
+            ..Default::default()
+        };
+        Self::new(AgentMcpServer::new(AgentOrchestrator::with_config(config)))
+    }
+
+    /// The server being driven.
+    pub fn server(&self) -> &AgentMcpServer {
+        &self.server
+    }
+
+    /// Send a request and return the raw response.
+    pub async fn request(&self, method: &str, params: Value) -> McpResponse {
+        let request = McpRequest {
+            jsonrpc: "2.0".into(),
+            id: Some(self.next_id.fetch_add(1, Ordering::Relaxed).into()),
+            method: method.into(),
+            params,
+        };
+        let message = serde_json::to_string(&request).unwrap_or_default();
+        self.server.handle_message(&message).await
+    }
+
+    /// Send a request and return its result, failing on an error response.
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response = self.request(method, params).await;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(Error::Protocol(format!(
+                "{} failed ({}): {}",
+                method, error.code, error.message
+            ))),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(Error::Protocol(format!("{} returned no result", method))),
+        }
+    }
+
+    /// Perform the initialize handshake, returning the server's reply.
+    pub async fn initialize(&self) -> Result<Value> {
+        let result = self
+            .call(
+                "initialize",
+                json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {},
+                    "clientInfo": { "name": "testkit", "version": env!("CARGO_PKG_VERSION") }
+                }),
+            )
+            .await?;
+        self.call("initialized", json!({})).await?;
+        Ok(result)
+    }
+
+    /// List the server's tools.
+    pub async fn list_tools(&self) -> Result<Vec<ToolDefinition>> {
+        let result = self.call("tools/list", json!({})).await?;
+        Ok(serde_json::from_value(result["tools"].clone())?)
+    }
+
+    /// Call a tool.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<ToolCallResult> {
+        let result = self
+            .call(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Call a tool and return its text, failing if the tool reports an
+    /// error.
+    pub async fn call_tool_text(&self, name: &str, arguments: Value) -> Result<String> {
+        let result = self.call_tool(name, arguments).await?;
+        let text = result
+            .content
+            .iter()
+            .filter_map(|item| match item {
+                ContentItem::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if result.is_error {
+            return Err(Error::Protocol(format!(
+                "{} reported an error: {}",
+                name, text
+            )));
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_client_drives_workflow() {
+        let mocks = MockProviders::new().with_reply(Provider::Claude, "Ownership moves values.");
+        mocks.enqueue(Provider::Claude, MockReply::Text("Draft outline".into()));
+        let client = TestClient::with_mocks(mocks.clone());
+
+        let init = client.initialize().await.unwrap();
+        assert_eq!(init["protocolVersion"], "2024-11-05");
+        let tools = client.list_tools().await.unwrap();
+        assert!(tools.iter().any(|t| t.name == "agent_workflow_start"));
+
+        let started = client
+            .call_tool_text(
+                "agent_workflow_start",
+                json!({
+                    "name": "explain",
+                    "steps": [
+                        { "name": "outline", "type": "prompt", "message": "Outline ownership", "provider": "claude" },
+                        { "name": "write", "type": "prompt", "message": "Explain ownership", "provider": "claude" }
+                    ]
+                }),
+            )
+            .await
+            .unwrap();
+        let id = started.split('`').nth(1).unwrap().to_string();
+        let first = client
+            .call_tool_text("agent_workflow_step", json!({ "workflow_id": id }))
+            .await
+            .unwrap();
+        assert!(first.contains("Draft outline"));
+        let second = client
+            .call_tool_text("agent_workflow_step", json!({ "workflow_id": id }))
+            .await
+            .unwrap();
+        assert!(second.contains("Workflow Complete"));
+        assert!(second.contains("Ownership moves values."));
+
+        let calls = mocks.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].message, "Outline ownership");
+
+        mocks.enqueue(
+            Provider::Gemini,
+            MockReply::Fail("rate limit exceeded".into()),
+        );
+        let error = client
+            .call_tool(
+                "agent_prompt",
+                json!({ "message": "Hi", "provider": "gemini" }),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("rate limit"));
+    }
+}
@@ -1,17 +1,50 @@
 //! Tool definitions for agent-mcp.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use embeddenator_webpuppet::Provider;
+use embeddenator_webpuppet::{PromptResponse, Provider};
 
+use crate::auto::{AutoOptions, AutoOutcome};
+use crate::chunk::Chunker;
+use crate::citations::{self, bibliography, Source, SourceList};
+use crate::compose::{ContextSection, SectionKind, DEFAULT_MAX_TOKENS};
+use crate::consensus::{
+    argmax, group_duplicates, ConsensusOptions, ConsensusStrategy, Disagreements,
+    DUPLICATE_THRESHOLD,
+};
 use crate::error::{Error, Result};
-use crate::orchestrator::AgentOrchestrator;
+use crate::export::{export_session, export_workflow, ExportFormat};
+use crate::extract::{CodeExtractor, Extraction};
+use crate::github::GitHubClient;
+use crate::idempotency::{IdempotencyStore, IDEMPOTENCY_KEY};
+use crate::jobs::{Job, JobKind};
+use crate::library::line_diff;
+use crate::metadata;
+use crate::objectives::RoutingChoice;
+use crate::output::{
+    check_writer, workspace_path, write_output, WrittenOutput, WRITE_TO, WRITTEN_TO_KEY,
+};
+use crate::orchestrator::{
+    AgentOrchestrator, ParallelOptions, PromptOptions, SANITIZATION_SUMMARY_KEY,
+};
+use crate::plan::{WorkflowDef, DEFAULT_MAX_STEPS, STEP_TYPES};
+use crate::postprocess::{self, PostProcessing};
+use crate::projection::RoutingProposal;
 use crate::protocol::{ContentItem, ToolCallResult, ToolDefinition};
-use crate::workflow::{Workflow, WorkflowStep};
+use crate::research::{ResearchOptions, DEFAULT_MAX_ROUNDS};
+use crate::run::{RunOutcome, DEFAULT_MAX_RUN_STEPS};
+use crate::router::{context_window, TaskType};
+use crate::security::DataClassification;
+use crate::templates::{template, templates, VariableKind};
+use crate::tenant::DEFAULT_TENANT;
+use crate::translate::{TranslationOptions, SOURCE_LABEL};
+use crate::workflow::{StepReview, StepState, Workflow, WorkflowState, WorkflowStep};
 
 /// Tool trait for implementing MCP tools.
 #[async_trait::async_trait]
@@ -19,6 +52,18 @@ pub trait Tool: Send + Sync {
     /// Get the tool definition.
     fn definition(&self) -> ToolDefinition;
 
+    /// Whether the tool changes state, so it accepts an `idempotency_key`
+    /// and a duplicate call with the same key returns the first result.
+    fn mutating(&self) -> bool {
+        false
+    }
+
+    /// Whether the tool's output can be large, so it accepts `write_to`
+    /// and can answer with a file instead.
+    fn large_output(&self) -> bool {
+        false
+    }
+
     /// Execute the tool with the given arguments.
     async fn execute(
         &self,
@@ -44,6 +89,14 @@ impl ToolContext {
         }
     }
 
+    /// A copy of this context whose requests are attributed to `tenant`.
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        Self {
+            orchestrator: Arc::new(self.orchestrator.for_tenant(tenant)),
+            visible: self.visible,
+        }
+    }
+
     /// Create a tool context with visible browser.
     pub fn with_visible_browser(orchestrator: AgentOrchestrator) -> Self {
         Self {
@@ -53,9 +106,198 @@ impl ToolContext {
     }
 }
 
+/// Per-tool execution time limits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolTimeouts {
+    /// Limit for tools without an entry, in seconds.
+    pub default_secs: u64,
+    /// Limits keyed by tool name, in seconds. Entries read from a file are
+    /// merged over the built-in ones.
+    #[serde(deserialize_with = "merge_tool_timeouts")]
+    pub per_tool: HashMap<String, u64>,
+}
+
+fn merge_tool_timeouts<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<String, u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut per_tool = ToolTimeouts::default().per_tool;
+    per_tool.extend(HashMap::<String, u64>::deserialize(deserializer)?);
+    Ok(per_tool)
+}
+
+impl ToolTimeouts {
+    /// Set the limit for a tool.
+    pub fn with_tool(mut self, tool: impl Into<String>, secs: u64) -> Self {
+        self.per_tool.insert(tool.into(), secs);
+        self
+    }
+
+    /// Time limit for a tool.
+    pub fn for_tool(&self, tool: &str) -> Duration {
+        Duration::from_secs(self.per_tool.get(tool).copied().unwrap_or(self.default_secs))
+    }
+}
+
+impl Default for ToolTimeouts {
+    fn default() -> Self {
+        Self {
+            default_secs: 600,
+            per_tool: HashMap::new(),
+        }
+        .with_tool("agent_workflow_list", 30)
+        .with_tool("agent_workflow_status", 30)
+        .with_tool("agent_status", 30)
+        .with_tool("agent_usage", 30)
+        .with_tool("agent_config", 30)
+        .with_tool("agent_routing_simulate", 30)
+        .with_tool("agent_routing_replay", 30)
+        .with_tool("agent_list_providers", 30)
+        .with_tool("agent_interventions", 30)
+        .with_tool("agent_intervention_resolve", 30)
+        .with_tool("agent_session_fork", 30)
+        .with_tool("agent_session_branches", 30)
+        .with_tool("agent_session_diff", 30)
+        // Multi-step runs get longer, so they are not cut off partway
+        .with_tool("agent_workflow_run", 1200)
+        .with_tool("agent_auto", 1200)
+        .with_tool("agent_research", 1200)
+    }
+}
+
+#[derive(Default)]
+struct Tools {
+    tools: HashMap<String, Arc<dyn Tool>>,
+    timeouts: ToolTimeouts,
+    post_processing: PostProcessing,
+}
+
+/// Tools with their time limits and post-processing, shared by an
+/// orchestrator's tool registries and the tool steps of its workflows.
+#[derive(Clone, Default)]
+pub struct ToolSet {
+    inner: Arc<std::sync::RwLock<Tools>>,
+}
+
+impl ToolSet {
+    /// Register a tool, replacing any tool of the same name.
+    pub fn register(&self, tool: Arc<dyn Tool>) {
+        let name = tool.definition().name.clone();
+        self.write().tools.insert(name, tool);
+    }
+
+    /// Set per-tool execution time limits.
+    pub fn set_timeouts(&self, timeouts: ToolTimeouts) {
+        self.write().timeouts = timeouts;
+    }
+
+    /// Set the post-processing applied to tool results.
+    pub fn set_post_processing(&self, post_processing: PostProcessing) {
+        self.write().post_processing = post_processing;
+    }
+
+    /// The tool registered under `name`, if any.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.read().tools.get(name).cloned()
+    }
+
+    /// Call a tool by name, returning the first result for a mutating call
+    /// repeated with the same idempotency key.
+    pub async fn call(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| Error::InvalidParams(format!("unknown tool: {}", name)))?;
+
+        match IdempotencyStore::key(&arguments)? {
+            Some(key) if tool.mutating() => {
+                let orchestrator = &context.orchestrator;
+                let call = self.run(tool.as_ref(), name, arguments.clone(), context);
+                orchestrator
+                    .idempotency()
+                    .run(orchestrator.tenant(), name, key, &arguments, call)
+                    .await
+            }
+            _ => self.run(tool.as_ref(), name, arguments, context).await,
+        }
+    }
+
+    /// Run a tool under its time limit and post-process its result, writing
+    /// it to the file named by `write_to` if the tool takes one.
+    async fn run(
+        &self,
+        tool: &dyn Tool,
+        name: &str,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let write_to = match arguments.get(WRITE_TO) {
+            Some(path) if tool.large_output() => {
+                let path = path.as_str().ok_or_else(|| {
+                    Error::InvalidParams(format!("{} must be a string", WRITE_TO))
+                })?;
+                check_writer(context.orchestrator.tenant())?;
+                Some(path.to_string())
+            }
+            _ => None,
+        };
+        let limit = self.read().timeouts.for_tool(name);
+        let start = Instant::now();
+        let mut result =
+            match tokio::time::timeout(limit, tool.execute(arguments, context)).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    return Err(Error::ToolTimeout {
+                        tool: name.to_string(),
+                        elapsed_ms: start.elapsed().as_millis() as u64,
+                    })
+                }
+            };
+
+        let processors = self.read().post_processing.for_tool(name).to_vec();
+        if !result.is_error && !processors.is_empty() {
+            for item in &mut result.content {
+                if let ContentItem::Text { text } = item {
+                    *text = postprocess::apply(text, &processors);
+                }
+            }
+        }
+
+        if let Some(path) = write_to.filter(|_| !result.is_error) {
+            let output = result
+                .content
+                .iter()
+                .filter_map(|item| match item {
+                    ContentItem::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let written = write_output(&path, &output).await?;
+            result.content = vec![ContentItem::text(written.summary(&output))];
+        }
+        Ok(result)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Tools> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Tools> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
 /// Registry of available tools.
 pub struct ToolRegistry {
-    tools: HashMap<String, Arc<dyn Tool>>,
+    tools: ToolSet,
     context: Arc<ToolContext>,
 }
 
@@ -65,12 +307,12 @@ impl ToolRegistry {
         Self::with_context(ToolContext::new(orchestrator))
     }
 
-    /// Create a tool registry with custom context.
+    /// Create a tool registry with custom context. Its tools are the
+    /// orchestrator's, so workflow tool steps can call them.
     pub fn with_context(context: ToolContext) -> Self {
-        let context = Arc::new(context);
         let mut registry = Self {
-            tools: HashMap::new(),
-            context,
+            tools: context.orchestrator.tools().clone(),
+            context: Arc::new(context),
         };
         registry.register_default_tools();
         registry
@@ -81,31 +323,100 @@ impl ToolRegistry {
         self.register(Arc::new(PromptTool));
         self.register(Arc::new(ParallelPromptTool));
         self.register(Arc::new(ConsensusTool));
+        self.register(Arc::new(PlanTool));
+        self.register(Arc::new(AutoTool));
         self.register(Arc::new(WorkflowStartTool));
         self.register(Arc::new(WorkflowStepTool));
+        self.register(Arc::new(WorkflowRunTool));
+        self.register(Arc::new(WorkflowReviewTool));
+        self.register(Arc::new(WorkflowListTool));
+        self.register(Arc::new(WorkflowStatusTool));
+        self.register(Arc::new(WorkflowTemplatesTool));
+        self.register(Arc::new(WorkflowFromTemplateTool));
         self.register(Arc::new(StatusTool));
+        self.register(Arc::new(UsageTool));
+        self.register(Arc::new(MaintenanceTool));
+        self.register(Arc::new(ConfigTool));
+        self.register(Arc::new(RoutingSimulateTool));
+        self.register(Arc::new(RoutingReplayTool));
         self.register(Arc::new(ListProvidersTool));
+        self.register(Arc::new(InterventionsTool));
+        self.register(Arc::new(InterventionResolveTool));
+        self.register(Arc::new(JobsTool));
+        self.register(Arc::new(JobUpdateTool));
+        self.register(Arc::new(CritiqueTool));
+        self.register(Arc::new(TranslateTool));
+        self.register(Arc::new(ResearchTool));
+        self.register(Arc::new(ExportTool));
+        self.register(Arc::new(PromptSaveTool));
+        self.register(Arc::new(PromptListTool));
+        self.register(Arc::new(PromptUpdateTool));
+        self.register(Arc::new(PromptDeleteTool));
+        self.register(Arc::new(PromptDiffTool));
+        self.register(Arc::new(CodeContextTool));
+        self.register(Arc::new(SessionForkTool));
+        self.register(Arc::new(SessionBranchesTool));
+        self.register(Arc::new(SessionDiffTool));
+    }
+
+    /// Set per-tool execution time limits.
+    pub fn set_timeouts(&mut self, timeouts: ToolTimeouts) {
+        self.tools.set_timeouts(timeouts);
+    }
+
+    /// Set the post-processing applied to tool results.
+    pub fn set_post_processing(&mut self, post_processing: PostProcessing) {
+        self.tools.set_post_processing(post_processing);
     }
 
     /// Register a tool.
     pub fn register(&mut self, tool: Arc<dyn Tool>) {
-        let name = tool.definition().name.clone();
-        self.tools.insert(name, tool);
+        self.tools.register(tool);
     }
 
     /// Get all tool definitions.
     pub fn definitions(&self) -> Vec<ToolDefinition> {
-        self.tools.values().map(|t| t.definition()).collect()
+        self.tools
+            .read()
+            .tools
+            .values()
+            .map(|tool| {
+                let mut definition = tool.definition();
+                if tool.mutating() {
+                    definition.input_schema["properties"][IDEMPOTENCY_KEY] = json!({
+                        "type": "string",
+                        "description": "Optional: unique key for this call; a retried call with the same key returns the first result instead of running again"
+                    });
+                }
+                if tool.large_output() {
+                    definition.input_schema["properties"][WRITE_TO] = json!({
+                        "type": "string",
+                        "description": "Optional: file, relative to the workspace root, to write the output to; the result is then the path and a summary"
+                    });
+                }
+                definition
+            })
+            .collect()
     }
 
     /// Execute a tool by name.
     pub async fn execute(&self, name: &str, arguments: serde_json::Value) -> Result<ToolCallResult> {
-        let tool = self
-            .tools
-            .get(name)
-            .ok_or_else(|| Error::InvalidParams(format!("unknown tool: {}", name)))?;
+        self.execute_for(name, arguments, None).await
+    }
 
-        tool.execute(arguments, &self.context).await
+    /// Execute a tool by name, attributing its requests to `tenant` (the
+    /// default tenant if `None`).
+    pub async fn execute_for(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+        tenant: Option<&str>,
+    ) -> Result<ToolCallResult> {
+        let context = match tenant {
+            Some(tenant) => Arc::new(self.context.for_tenant(tenant)),
+            None => self.context.clone(),
+        };
+        self.tools.call(name, arguments, &context).await
     }
 }
 
@@ -121,6 +432,18 @@ struct PromptArgs {
     message: String,
     provider: Option<String>,
     context: Option<String>,
+    #[serde(default)]
+    sections: Vec<ContextSection>,
+    #[serde(default)]
+    symbols: Vec<String>,
+    session_id: Option<String>,
+    max_context_tokens: Option<u64>,
+    classification: Option<DataClassification>,
+    visible: Option<bool>,
+    model: Option<String>,
+    routing: Option<RoutingChoice>,
+    #[serde(default)]
+    race: bool,
 }
 
 #[async_trait::async_trait]
@@ -144,6 +467,87 @@ impl Tool for PromptTool {
                     "context": {
                         "type": "string",
                         "description": "Optional: system context or instructions"
+                    },
+                    "sections": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "kind": {
+                                    "type": "string",
+                                    "enum": ["system", "retrieved", "history"]
+                                },
+                                "name": { "type": "string" },
+                                "text": { "type": "string" },
+                                "priority": { "type": "integer", "minimum": 0 },
+                                "truncation": {
+                                    "type": "string",
+                                    "enum": ["keep_start", "keep_end", "drop"]
+                                },
+                                "chunker": {
+                                    "type": "object",
+                                    "properties": {
+                                        "type": {
+                                            "type": "string",
+                                            "enum": ["fixed_tokens", "markdown", "code"]
+                                        },
+                                        "max_tokens": { "type": "integer", "minimum": 1 },
+                                        "overlap_tokens": { "type": "integer", "minimum": 0 }
+                                    },
+                                    "required": ["type"],
+                                    "description": "Cut the section between whole chunks when truncating"
+                                }
+                            },
+                            "required": ["kind", "text"]
+                        },
+                        "description": "Optional: context sections (retrieved documents, history) fitted into the token budget by priority"
+                    },
+                    "symbols": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Optional: code symbols whose definitions, types, and callers are extracted from the workspace as retrieved sections"
+                    },
+                    "session_id": {
+                        "type": "string",
+                        "description": "Optional: include this session's transcript as history; an exchange with a forked branch is added to the branch, by default with its provider"
+                    },
+                    "max_context_tokens": {
+                        "type": "integer",
+                        "description": "Optional: token budget for the context and message together",
+                        "minimum": 1
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the prompt"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this call in a visible browser for debugging or manual intervention"
+                    },
+                    "model": {
+                        "type": "string",
+                        "description": "Optional: model for an API provider, by name or alias (opus, sonnet, haiku for the Anthropic API)"
+                    },
+                    "routing": {
+                        "oneOf": [
+                            { "type": "string" },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "quality": { "type": "number", "minimum": 0 },
+                                    "cost": { "type": "number", "minimum": 0 },
+                                    "latency": { "type": "number", "minimum": 0 },
+                                    "quota": { "type": "number", "minimum": 0 },
+                                    "freshness": { "type": "number", "minimum": 0 }
+                                }
+                            }
+                        ],
+                        "description": "Optional: routing profile (balanced, quick, cheap, fast, or a configured one) or objective weights choosing the provider when none is given"
+                    },
+                    "race": {
+                        "type": "boolean",
+                        "description": "Optional: send to the two fastest healthy providers at once and return the first acceptable answer; costs both calls"
                     }
                 },
                 "required": ["message"]
@@ -151,6 +555,10 @@ impl Tool for PromptTool {
         }
     }
 
+    fn large_output(&self) -> bool {
+        true
+    }
+
     async fn execute(
         &self,
         arguments: serde_json::Value,
@@ -158,18 +566,112 @@ impl Tool for PromptTool {
     ) -> Result<ToolCallResult> {
         let args: PromptArgs =
             serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let session = args
+            .session_id
+            .as_deref()
+            .map(|id| {
+                context
+                    .orchestrator
+                    .sessions()
+                    .get(id)
+                    .ok_or_else(|| Error::InvalidParams(format!("no session with ID {}", id)))
+            })
+            .transpose()?;
+        // A branch is continued in place, by default with its own provider
+        let branch = session.as_ref().filter(|s| s.forked_from.is_some());
+        let provider = match (&args.provider, branch) {
+            (Some(name), _) => Some(parse_provider(name)?),
+            (None, Some(branch)) => Some(parse_provider(&branch.provider)?),
+            (None, None) => None,
+        };
+        if args.race && provider.is_some() {
+            return Err(Error::InvalidParams(
+                "race picks its own providers; omit provider or session branch".into(),
+            ));
+        }
+
+        // Fit context, sections, and history into the budget when any were
+        // given beyond plain context.
+        let composed = if args.sections.is_empty()
+            && args.symbols.is_empty()
+            && args.session_id.is_none()
+            && args.max_context_tokens.is_none()
+        {
+            None
+        } else {
+            let mut sections: Vec<ContextSection> = args
+                .context
+                .iter()
+                .map(|c| ContextSection::new(SectionKind::System, c.clone()))
+                .collect();
+            sections.extend(args.sections);
+            for symbol in args.symbols {
+                let extractor = CodeExtractor::new(workspace_root(None)?);
+                let extraction = extract_code(extractor, symbol).await?;
+                sections.push(ContextSection {
+                    name: Some(format!("code: {}", extraction.symbol)),
+                    chunker: Some(Chunker::Markdown {
+                        max_tokens: extraction.tokens().max(1),
+                    }),
+                    ..ContextSection::new(SectionKind::Retrieved, extraction.to_markdown())
+                });
+            }
+            if let Some(session) = &session {
+                sections.push(ContextSection::new(SectionKind::History, session.to_markdown()));
+            }
+            let budget = &context.orchestrator.config().context_budget;
+            let max_tokens = args
+                .max_context_tokens
+                .or(budget.max_tokens)
+                .or(provider.map(context_window))
+                .unwrap_or(DEFAULT_MAX_TOKENS);
+            Some(budget.compose(&sections, &args.message, max_tokens)?)
+        };
+
+        let options = PromptOptions {
+            context: match &composed {
+                Some(composed) => composed.context.clone(),
+                None => args.context,
+            },
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            model: args.model,
+            routing: args.routing,
+        };
 
-        let response = if let Some(provider_str) = args.provider {
-            let provider = parse_provider(&provider_str)?;
-            context.orchestrator.prompt_provider(provider, args.message).await?
+        let response = if let Some(provider) = provider {
+            context
+                .orchestrator
+                .prompt_provider_with(provider, args.message.clone(), options)
+                .await?
+        } else if args.race {
+            context
+                .orchestrator
+                .race_prompt(args.message.clone(), options)
+                .await?
         } else {
-            context.orchestrator.prompt(args.message).await?
+            context
+                .orchestrator
+                .prompt_with(args.message.clone(), options)
+                .await?
         };
+        if let Some(branch) = branch {
+            context
+                .orchestrator
+                .sessions()
+                .append(&branch.id, &args.message, &response)?;
+        }
 
         Ok(ToolCallResult {
             content: vec![ContentItem::text(format!(
-                "**Response from {}:**\n\n{}",
-                response.provider, response.text
+                "**Response from {}:**\n\n{}{}\n\n{}{}",
+                response.provider,
+                response.text,
+                sanitization_notice(&response),
+                metadata::summary(&response),
+                composed
+                    .map(|c| format!("\n\n*{}.*", c.summary()))
+                    .unwrap_or_default()
             ))],
             is_error: false,
         })
@@ -183,6 +685,15 @@ pub struct ParallelPromptTool;
 struct ParallelPromptArgs {
     message: String,
     providers: Vec<String>,
+    classification: Option<DataClassification>,
+    visible: Option<bool>,
+    #[serde(default = "default_min_success")]
+    min_success: usize,
+    #[serde(default)]
+    fail_fast: bool,
+    #[serde(default = "default_true")]
+    dedupe: bool,
+    dedupe_threshold: Option<f64>,
 }
 
 #[async_trait::async_trait]
@@ -206,6 +717,34 @@ impl Tool for ParallelPromptTool {
                         },
                         "description": "List of providers to query",
                         "minItems": 2
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the prompt"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this call in a visible browser for debugging or manual intervention"
+                    },
+                    "min_success": {
+                        "type": "integer",
+                        "description": "Minimum successful responses; the call fails with fewer (default: 1)",
+                        "minimum": 0
+                    },
+                    "fail_fast": {
+                        "type": "boolean",
+                        "description": "Skip remaining providers once min_success responses arrive (default: false)"
+                    },
+                    "dedupe": {
+                        "type": "boolean",
+                        "description": "Collapse near-identical answers into one section (default: true)"
+                    },
+                    "dedupe_threshold": {
+                        "type": "number",
+                        "description": "Optional: word-overlap similarity (0.0-1.0) at which answers count as duplicates (default: 0.85)",
+                        "minimum": 0,
+                        "maximum": 1
                     }
                 },
                 "required": ["message", "providers"]
@@ -213,6 +752,10 @@ impl Tool for ParallelPromptTool {
         }
     }
 
+    fn large_output(&self) -> bool {
+        true
+    }
+
     async fn execute(
         &self,
         arguments: serde_json::Value,
@@ -231,24 +774,81 @@ impl Tool for ParallelPromptTool {
             return Err(Error::InvalidParams("need at least 2 valid providers".into()));
         }
 
+        let options = PromptOptions {
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            ..Default::default()
+        };
+
+        let parallel = ParallelOptions {
+            min_success: args.min_success,
+            fail_fast: args.fail_fast,
+        };
+        let requested = providers.len();
         let results = context
             .orchestrator
-            .parallel_prompt(args.message, providers)
+            .parallel_prompt_using(args.message, providers, options, parallel)
             .await?;
+        let skipped = requested - results.len();
 
-        let text = results
+        // Near-identical answers share one section showing the most complete.
+        let answered: Vec<(Provider, &PromptResponse)> = results
             .iter()
-            .map(|(provider, result)| match result {
-                Ok(resp) => format!("## {}\n\n{}", provider, resp.text),
-                Err(e) => format!("## {} (Error)\n\n{}", provider, e),
+            .filter_map(|(provider, result)| Some((*provider, result.as_ref().ok()?)))
+            .collect();
+        let texts: Vec<&str> = answered.iter().map(|(_, r)| r.text.as_str()).collect();
+        let threshold = if args.dedupe {
+            args.dedupe_threshold.unwrap_or(DUPLICATE_THRESHOLD)
+        } else {
+            f64::INFINITY
+        };
+        let mut sections: Vec<String> = group_duplicates(&texts, threshold)
+            .into_iter()
+            .map(|group| {
+                let longest = argmax(group.iter().map(|&i| texts[i].len() as f64));
+                let shown = group[longest.unwrap_or(0)];
+                let (provider, resp) = answered[shown];
+                let heading = if group.len() > 1 {
+                    format!(
+                        "{} agree\n\n*Showing {}'s answer; the others are near-identical.*",
+                        join_names(group.iter().map(|&i| answered[i].0.to_string())),
+                        provider
+                    )
+                } else {
+                    provider.to_string()
+                };
+                format!(
+                    "## {}\n\n{}{}\n\n{}",
+                    heading,
+                    resp.text,
+                    sanitization_notice(resp),
+                    metadata::summary(resp)
+                )
             })
-            .collect::<Vec<_>>()
-            .join("\n\n---\n\n");
+            .collect();
+        sections.extend(results.iter().filter_map(|(provider, result)| {
+            let e = result.as_ref().err()?;
+            Some(format!("## {} (Error)\n\n{}", provider, e))
+        }));
+        let text = sections.join("\n\n---\n\n");
+        let skipped_note = if skipped > 0 {
+            format!("\n\n*{} provider(s) skipped after enough responses arrived.*", skipped)
+        } else {
+            String::new()
+        };
+        let mut sources = SourceList::default();
+        for (provider, result) in &results {
+            if let Ok(resp) = result {
+                sources.add(&provider.to_string(), &citations::from_response(resp));
+            }
+        }
 
         Ok(ToolCallResult {
             content: vec![ContentItem::text(format!(
-                "# Parallel Responses\n\n{}",
-                text
+                "# Parallel Responses\n\n{}{}{}",
+                text,
+                skipped_note,
+                sources_section(&sources.into_vec())
             ))],
             is_error: false,
         })
@@ -262,6 +862,11 @@ pub struct ConsensusTool;
 struct ConsensusArgs {
     message: String,
     min_providers: Option<usize>,
+    classification: Option<DataClassification>,
+    strategy: Option<ConsensusStrategy>,
+    judge_provider: Option<String>,
+    visible: Option<bool>,
+    routing: Option<RoutingChoice>,
 }
 
 #[async_trait::async_trait]
@@ -282,6 +887,40 @@ impl Tool for ConsensusTool {
                         "description": "Minimum providers to query (default: 3)",
                         "minimum": 2,
                         "default": 3
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the prompt"
+                    },
+                    "strategy": {
+                        "type": "string",
+                        "enum": ["longest", "similarity_cluster", "judge", "vote"],
+                        "description": "How to choose the consensus answer (default: longest)"
+                    },
+                    "judge_provider": {
+                        "type": "string",
+                        "description": "Optional: provider grading responses for the judge strategy"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this call in a visible browser for debugging or manual intervention"
+                    },
+                    "routing": {
+                        "oneOf": [
+                            { "type": "string" },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "quality": { "type": "number", "minimum": 0 },
+                                    "cost": { "type": "number", "minimum": 0 },
+                                    "latency": { "type": "number", "minimum": 0 },
+                                    "quota": { "type": "number", "minimum": 0 },
+                                    "freshness": { "type": "number", "minimum": 0 }
+                                }
+                            }
+                        ],
+                        "description": "Optional: routing profile (balanced, quick, cheap, fast, or a configured one) or objective weights choosing the providers"
                     }
                 },
                 "required": ["message"]
@@ -289,6 +928,10 @@ impl Tool for ConsensusTool {
         }
     }
 
+    fn large_output(&self) -> bool {
+        true
+    }
+
     async fn execute(
         &self,
         arguments: serde_json::Value,
@@ -299,9 +942,24 @@ impl Tool for ConsensusTool {
 
         let min_providers = args.min_providers.unwrap_or(3);
 
+        let options = PromptOptions {
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            routing: args.routing,
+            ..Default::default()
+        };
+
         let result = context
             .orchestrator
-            .consensus_prompt(args.message, min_providers)
+            .consensus_prompt_using(
+                args.message,
+                min_providers,
+                options,
+                ConsensusOptions {
+                    strategy: args.strategy.unwrap_or_default(),
+                    judge_provider: args.judge_provider,
+                },
+            )
             .await?;
 
         let responses_text = result
@@ -309,78 +967,81 @@ impl Tool for ConsensusTool {
             .iter()
             .map(|r| {
                 let marker = if r.selected { "✓" } else { "○" };
-                format!("{} **{}**: {}", marker, r.provider, r.text.chars().take(200).collect::<String>())
+                let confidence = r
+                    .confidence
+                    .map(|c| format!(" ({:.0}% agreement)", c * 100.0))
+                    .unwrap_or_default();
+                format!("{} **{}**{}: {}", marker, r.provider, confidence, r.text.chars().take(200).collect::<String>())
             })
             .collect::<Vec<_>>()
             .join("\n\n");
 
         Ok(ToolCallResult {
             content: vec![ContentItem::text(format!(
-                "# Consensus Result\n\n**Agreement Score:** {:.0}%\n\n## Consensus Answer\n\n{}\n\n## Individual Responses\n\n{}",
+                "# Consensus Result\n\n**Strategy:** {}\n**Agreement Score:** {:.0}%\n\n## Consensus Answer\n\n{}{}\n\n## Individual Responses\n\n{}{}",
+                result.strategy,
                 result.agreement_score * 100.0,
                 result.consensus_text,
-                responses_text
+                disagreement_section(&result.disagreements),
+                responses_text,
+                sources_section(&result.sources)
             ))],
             is_error: false,
         })
     }
 }
 
-/// Tool for starting a new workflow.
-pub struct WorkflowStartTool;
-
-#[derive(Debug, Deserialize)]
-struct WorkflowStartArgs {
-    name: String,
-    steps: Vec<WorkflowStepDef>,
-}
+/// Tool for decomposing a goal into a workflow definition.
+pub struct PlanTool;
 
 #[derive(Debug, Deserialize)]
-struct WorkflowStepDef {
-    name: String,
-    #[serde(rename = "type")]
-    step_type: String,
-    message: String,
+struct PlanArgs {
+    goal: String,
+    context: Option<String>,
+    max_steps: Option<usize>,
     provider: Option<String>,
-    providers: Option<Vec<String>>,
+    classification: Option<DataClassification>,
+    visible: Option<bool>,
 }
 
 #[async_trait::async_trait]
-impl Tool for WorkflowStartTool {
+impl Tool for PlanTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
-            name: "agent_workflow_start".into(),
-            description: "Start a new multi-step workflow.".into(),
+            name: "agent_plan".into(),
+            description: "Decompose a goal into workflow steps. Returns the arguments for agent_workflow_start, ready to pass as-is or edit first.".into(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
-                    "name": {
+                    "goal": {
                         "type": "string",
-                        "description": "Name of the workflow"
+                        "description": "What the workflow should accomplish"
                     },
-                    "steps": {
-                        "type": "array",
-                        "items": {
-                            "type": "object",
-                            "properties": {
-                                "name": { "type": "string" },
-                                "type": {
-                                    "type": "string",
-                                    "enum": ["prompt", "parallel", "consensus", "review"]
-                                },
-                                "message": { "type": "string" },
-                                "provider": { "type": "string" },
-                                "providers": {
-                                    "type": "array",
-                                    "items": { "type": "string" }
-                                }
-                            },
-                            "required": ["name", "type", "message"]
-                        },
-                        "description": "Workflow steps to execute"
+                    "context": {
+                        "type": "string",
+                        "description": "Optional: background the planner should take into account"
+                    },
+                    "max_steps": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional: maximum number of steps (default: 8)"
+                    },
+                    "provider": {
+                        "type": "string",
+                        "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"],
+                        "description": "Optional: provider that plans (default: best available)"
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the goal"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this call in a visible browser for debugging or manual intervention"
                     }
                 },
-                "required": ["name", "steps"]
+                "required": ["goal"]
             }),
         }
     }
@@ -390,197 +1051,3133 @@ impl Tool for WorkflowStartTool {
         arguments: serde_json::Value,
         context: &ToolContext,
     ) -> Result<ToolCallResult> {
-        let args: WorkflowStartArgs =
+        let args: PlanArgs =
             serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
 
-        let mut workflow = Workflow::new(args.name);
-
-        for step_def in args.steps {
-            let step = match step_def.step_type.as_str() {
-                "prompt" => WorkflowStep::prompt(step_def.name, step_def.message),
-                "parallel" => WorkflowStep::parallel(
-                    step_def.name,
-                    step_def.message,
-                    step_def.providers.unwrap_or_default(),
-                ),
-                "consensus" => WorkflowStep::consensus(step_def.name, step_def.message),
-                "review" => WorkflowStep::review(step_def.name, step_def.message),
-                _ => return Err(Error::InvalidParams(format!("unknown step type: {}", step_def.step_type))),
-            };
-            workflow.add_step(step);
+        let max_steps = args.max_steps.unwrap_or(DEFAULT_MAX_STEPS);
+        if max_steps == 0 {
+            return Err(Error::InvalidParams("max_steps must be at least 1".into()));
         }
+        let provider = args.provider.as_deref().map(parse_provider).transpose()?;
+        let options = PromptOptions {
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            ..Default::default()
+        };
 
-        let id = context.orchestrator.start_workflow(workflow).await?;
+        let plan = context
+            .orchestrator
+            .plan(&args.goal, args.context.as_deref(), max_steps, provider, options)
+            .await?;
+
+        let steps: Vec<String> = plan
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}. **{}** ({}): {}", i + 1, s.name, s.step_type, s.message))
+            .collect();
 
         Ok(ToolCallResult {
-            content: vec![ContentItem::text(format!(
-                "# Workflow Started\n\n**ID:** `{}`\n\nUse `agent_workflow_step` with this ID to execute steps.",
-                id
-            ))],
+            content: vec![
+                ContentItem::text(format!(
+                    "# Plan: {}\n\n{}\n\nPass the JSON below to `agent_workflow_start` to run it.",
+                    plan.name,
+                    steps.join("\n")
+                )),
+                ContentItem::text(serde_json::to_string_pretty(&plan)?),
+            ],
             is_error: false,
         })
     }
 }
 
-/// Tool for executing the next step in a workflow.
-pub struct WorkflowStepTool;
+/// Tool for planning a goal, running the plan, and synthesizing the result.
+pub struct AutoTool;
 
 #[derive(Debug, Deserialize)]
-struct WorkflowStepArgs {
-    workflow_id: String,
+struct AutoArgs {
+    goal: Option<String>,
+    workflow_id: Option<String>,
+    context: Option<String>,
+    max_steps: Option<usize>,
+    max_cost_usd: Option<f64>,
+    planner: Option<String>,
+    synthesizer: Option<String>,
+    classification: Option<DataClassification>,
+    visible: Option<bool>,
+    #[serde(default = "default_true")]
+    wait: bool,
+    #[serde(default)]
+    priority: i32,
+    callback_url: Option<String>,
 }
 
 #[async_trait::async_trait]
-impl Tool for WorkflowStepTool {
+impl Tool for AutoTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
-            name: "agent_workflow_step".into(),
-            description: "Execute the next step in a workflow.".into(),
+            name: "agent_auto".into(),
+            description: "Plan a goal into a workflow, run it in the background under the approval policy and an optional budget, and return the synthesized result. Pass workflow_id to resume a paused run.".into(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
+                    "goal": {
+                        "type": "string",
+                        "description": "What to accomplish (required unless resuming)"
+                    },
                     "workflow_id": {
                         "type": "string",
-                        "description": "ID of the workflow to execute"
+                        "description": "Optional: resume this earlier auto run instead of planning a new one"
+                    },
+                    "context": {
+                        "type": "string",
+                        "description": "Optional: background the planner should take into account"
+                    },
+                    "max_steps": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional: maximum number of planned steps (default: 8)"
+                    },
+                    "max_cost_usd": {
+                        "type": "number",
+                        "minimum": 0,
+                        "description": "Optional: stop before exceeding this estimated cost"
+                    },
+                    "planner": {
+                        "type": "string",
+                        "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"],
+                        "description": "Optional: provider that plans (default: best available)"
+                    },
+                    "synthesizer": {
+                        "type": "string",
+                        "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"],
+                        "description": "Optional: provider that writes the final answer (default: best available)"
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification applied to every step"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run in a visible browser for debugging or manual intervention"
+                    },
+                    "wait": {
+                        "type": "boolean",
+                        "description": "Optional: wait for the run to finish (default: true). If false, queues the run as a background job and returns immediately."
+                    },
+                    "priority": {
+                        "type": "integer",
+                        "description": "Optional: job queue priority when not waiting; higher runs first (default: 0)"
+                    },
+                    "callback_url": {
+                        "type": "string",
+                        "description": "Optional: http(s) URL on a host the operator allowed that receives the final result JSON; implies wait: false"
                     }
-                },
-                "required": ["workflow_id"]
+                }
             }),
         }
     }
 
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
     async fn execute(
         &self,
         arguments: serde_json::Value,
         context: &ToolContext,
     ) -> Result<ToolCallResult> {
-        let args: WorkflowStepArgs =
+        let args: AutoArgs =
             serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
 
-        let result = context
-            .orchestrator
-            .execute_workflow_step(&args.workflow_id)
-            .await?;
+        if let Some(url) = &args.callback_url {
+            let orchestrator = &context.orchestrator;
+            let classification = orchestrator.guard().classify(args.classification);
+            orchestrator.webhooks().policy().check(url, classification)?;
+        }
+        let auto = AutoOptions {
+            max_steps: args.max_steps.unwrap_or(DEFAULT_MAX_STEPS),
+            max_cost_usd: args.max_cost_usd,
+            planner: args.planner.as_deref().map(parse_provider).transpose()?,
+            synthesizer: args.synthesizer.as_deref().map(parse_provider).transpose()?,
+        };
+        if auto.max_steps == 0 {
+            return Err(Error::InvalidParams("max_steps must be at least 1".into()));
+        }
+        let options = PromptOptions {
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            ..Default::default()
+        };
 
-        let workflow = context
-            .orchestrator
-            .get_workflow(&args.workflow_id)
-            .await
-            .ok_or_else(|| Error::Workflow("workflow not found".into()))?;
+        let workflow_id = match (args.workflow_id, args.goal) {
+            (Some(id), _) => id,
+            (None, Some(goal)) => {
+                context
+                    .orchestrator
+                    .auto_start(&goal, args.context.as_deref(), &auto, options.clone())
+                    .await?
+            }
+            (None, None) => {
+                return Err(Error::InvalidParams(
+                    "either goal or workflow_id is required".into(),
+                ))
+            }
+        };
+
+        if !args.wait || args.callback_url.is_some() {
+            let job = context.orchestrator.jobs().enqueue_with_callback(
+                JobKind::AutoRun {
+                    workflow_id: workflow_id.clone(),
+                    max_cost_usd: auto.max_cost_usd,
+                    synthesizer: auto.synthesizer.map(|p| p.to_string()),
+                    classification: options.classification,
+                    visible: options.visible,
+                    tenant: Some(context.orchestrator.tenant().to_string()),
+                },
+                args.priority,
+                args.callback_url,
+            )?;
+            return Ok(ToolCallResult {
+                content: vec![ContentItem::text(format!(
+                    "# Auto Run Queued\n\n**ID:** `{}`\n**Job:** `{}`\n\nThe run continues in the background. \
+                     Check progress with `agent_jobs`, then call `agent_auto` with this `workflow_id` to get the result.",
+                    workflow_id, job.id
+                ))],
+                is_error: false,
+            });
+        }
+
+        // The run continues even if this call is cancelled or times out.
+        let orchestrator = context.orchestrator.clone();
+        let id = workflow_id.clone();
+        let run = tokio::spawn(async move { orchestrator.auto_run(&id, &auto, options).await });
+
+        let report = run
+            .await
+            .map_err(|e| Error::Internal(format!("auto run stopped unexpectedly: {}", e)))??;
+
+        let status = match &report.outcome {
+            AutoOutcome::Completed => "✓ Completed".to_string(),
+            AutoOutcome::AwaitingApproval { reason } => format!(
+                "⏸ Paused: {}. Approve the step, then call `agent_auto` with this `workflow_id` to continue.",
+                reason
+            ),
+            AutoOutcome::OverBudget { next_step_usd } => format!(
+                "⏸ Stopped: the next step (about ${:.4}) would exceed the budget.",
+                next_step_usd
+            ),
+            AutoOutcome::Failed { reason } => format!("✗ Failed: {}", reason),
+            AutoOutcome::Paused { reason } => format!(
+                "⏸ Paused: {}. Once resumed, call `agent_auto` with this `workflow_id` to continue.",
+                reason
+            ),
+        };
+        let synthesis = report
+            .synthesis
+            .as_deref()
+            .map(|s| format!("\n\n## Result\n\n{}", s))
+            .unwrap_or_default();
+
+        Ok(ToolCallResult {
+            content: vec![
+                ContentItem::text(format!(
+                    "# {}\n\n**ID:** `{}`\n\n{}\n\n{}/{} steps completed · estimated cost ${:.4}{}",
+                    report.plan.name,
+                    report.workflow_id,
+                    status,
+                    report.steps_completed,
+                    report.plan.steps.len(),
+                    report.spent_usd,
+                    synthesis
+                )),
+                ContentItem::text(serde_json::to_string_pretty(&report)?),
+            ],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for starting a new workflow.
+pub struct WorkflowStartTool;
+
+#[async_trait::async_trait]
+impl Tool for WorkflowStartTool {
+    fn definition(&self) -> ToolDefinition {
+        let mut input_schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "description": "Name of the workflow"
+                },
+                "steps": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "type": {
+                                "type": "string",
+                                "enum": STEP_TYPES
+                            },
+                            "message": { "type": "string" },
+                            "prompt": {
+                                "type": "string",
+                                "description": "Saved prompt to use as the message, as name or name@version to pin a version"
+                            },
+                            "arguments": {
+                                "type": "object",
+                                "additionalProperties": { "type": "string" },
+                                "description": "Variables for the saved prompt"
+                            },
+                            "provider": { "type": "string" },
+                            "providers": {
+                                "type": "array",
+                                "items": { "type": "string" }
+                            },
+                            "classification": {
+                                "type": "string",
+                                "enum": ["public", "internal", "confidential"]
+                            },
+                            "strategy": {
+                                "type": "string",
+                                "enum": ["longest", "similarity_cluster", "judge", "vote"]
+                            },
+                            "judge_provider": { "type": "string" },
+                            "source_step": {
+                                "type": "string",
+                                "description": "Step (ID or name) a fact_check, validate_patch, or github_comment step uses; defaults to the previous step"
+                            },
+                            "source_steps": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Steps (IDs or names) an aggregate step consolidates, an assemble step joins, or an extract step reads; defaults to every earlier step except reviews and conditionals"
+                            },
+                            "pull_request": {
+                                "type": "string",
+                                "description": "Pull request or issue a github_comment step posts to, as owner/repo#number or a URL"
+                            },
+                            "items": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "name": { "type": "string" },
+                                        "text": { "type": "string" }
+                                    },
+                                    "required": ["name", "text"]
+                                },
+                                "description": "Items a map step sends its message for, filling {{name}} and {{item}}"
+                            },
+                            "json": {
+                                "type": "boolean",
+                                "description": "Whether a map step reads each answer as a JSON object and reports its fields"
+                            },
+                            "checker": {
+                                "type": "string",
+                                "description": "Provider that cross-checks and corrects each answer of a map step"
+                            },
+                            "fields": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "name": { "type": "string" },
+                                        "description": { "type": "string" },
+                                        "required": { "type": "boolean" }
+                                    },
+                                    "required": ["name", "description"]
+                                },
+                                "description": "Fields of the records an extract step asks its providers for"
+                            },
+                            "min_agreement": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Providers that must extract a record for an extract step to keep it (default a majority)"
+                            },
+                            "max_refinements": {
+                                "type": "integer",
+                                "minimum": 0,
+                                "description": "Corrections a validate_patch step requests before failing (default 2)"
+                            },
+                            "post_processors": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "type": {
+                                            "type": "string",
+                                            "enum": ["strip_boilerplate", "normalize_code_fences", "max_length", "plain_text"]
+                                        },
+                                        "max_chars": { "type": "integer" }
+                                    },
+                                    "required": ["type"]
+                                },
+                                "description": "Applied in order to the step's output"
+                            },
+                            "write_to": {
+                                "type": "string",
+                                "description": "File, relative to the workspace root, the step's output is written to; step results then show a summary instead, for very large outputs such as map steps"
+                            },
+                            "confidence": {
+                                "type": "object",
+                                "properties": {
+                                    "provider": {
+                                        "type": "string",
+                                        "description": "Provider rating the answer (default the one that answered)"
+                                    },
+                                    "min_confidence": {
+                                        "type": "number",
+                                        "minimum": 0,
+                                        "maximum": 1,
+                                        "description": "Lowest acceptable confidence (default 0.6)"
+                                    },
+                                    "on_low": {
+                                        "type": "string",
+                                        "enum": ["review", "fail", "continue"],
+                                        "description": "Pause for review of the answer (default), fail the step, or carry on"
+                                    }
+                                },
+                                "description": "Have a provider rate its confidence in the answer of a prompt, parallel, consensus, research, or aggregate step"
+                            }
+                        },
+                        "required": ["name", "type"]
+                    },
+                    "description": "Workflow steps to execute; each needs a message or a saved prompt"
+                }
+            },
+            "required": ["name", "steps"]
+        });
+        // Added separately to stay within the `json!` recursion limit
+        let step_properties = &mut input_schema["properties"]["steps"]["items"]["properties"];
+        step_properties["condition"] = json!({
+            "type": "string",
+            "description": "Expression a conditional step evaluates over the workflow context and earlier steps, e.g. steps.review.output contains \"approved\""
+        });
+        step_properties["then_step"] = json!({
+            "type": "string",
+            "description": "Later step (ID or name) a conditional step continues at if its condition holds"
+        });
+        step_properties["else_step"] = json!({
+            "type": "string",
+            "description": "Later step (ID or name) a conditional step continues at otherwise; defaults to the next step"
+        });
+        step_properties["tool"] = json!({
+            "type": "string",
+            "description": "Registered tool a tool step calls, e.g. agent_code_context"
+        });
+        step_properties["tool_arguments"] = json!({
+            "type": "object",
+            "description": "Arguments of a tool step's call; {{key}} in their strings is filled from the workflow context"
+        });
+        step_properties["retries"] = json!({
+            "type": "integer",
+            "minimum": 0,
+            "maximum": 10,
+            "description": "Times the step is run again after failing with an error in retry_on"
+        });
+        step_properties["backoff_ms"] = json!({
+            "type": "integer",
+            "minimum": 0,
+            "description": "Wait before the first retry in milliseconds (default 1000), doubled for each retry after it"
+        });
+        step_properties["retry_on"] = json!({
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Error kinds that are retried, e.g. rate_limited or network_timeout; defaults to transient failures"
+        });
+        step_properties["depends_on"] = json!({
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Earlier steps (IDs or names) that must complete before this one; when any step sets it, steps run as their dependencies complete and independent branches run at once"
+        });
+        ToolDefinition {
+            name: "agent_workflow_start".into(),
+            description: "Start a new multi-step workflow.".into(),
+            input_schema,
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: WorkflowDef =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let workflow = args.to_workflow_with(context.orchestrator.prompt_library())?;
+        let id = context.orchestrator.start_workflow(workflow).await?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Workflow Started\n\n**ID:** `{}`\n\nUse `agent_workflow_step` with this ID to execute steps one at a time, or `agent_workflow_run` to run them all.",
+                id
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for listing the built-in workflow templates.
+pub struct WorkflowTemplatesTool;
+
+#[async_trait::async_trait]
+impl Tool for WorkflowTemplatesTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_workflow_templates".into(),
+            description: "List the built-in workflow templates and the variables they need.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        _arguments: serde_json::Value,
+        _context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let mut text = String::from("# Workflow Templates\n");
+        for template in templates() {
+            text.push_str(&format!("\n## {}\n\n{}\n\n", template.name, template.description));
+            for variable in template.variables {
+                let kind = match variable.kind {
+                    VariableKind::Text => "",
+                    VariableKind::File => " (workspace file path)",
+                    VariableKind::PullRequest => " (pull request)",
+                    VariableKind::Issues => " (GitHub issues)",
+                    VariableKind::Directory => " (workspace directory)",
+                };
+                let default = variable
+                    .default
+                    .map(|d| format!(" (default `{}`)", d))
+                    .unwrap_or_default();
+                text.push_str(&format!(
+                    "- `{}`{}: {}{}\n",
+                    variable.name, kind, variable.description, default
+                ));
+            }
+        }
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(text)],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for starting a workflow from a built-in template.
+pub struct WorkflowFromTemplateTool;
+
+#[derive(Debug, Deserialize)]
+struct WorkflowFromTemplateArgs {
+    template: String,
+    #[serde(default)]
+    arguments: HashMap<String, String>,
+    name: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Tool for WorkflowFromTemplateTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_workflow_from_template".into(),
+            description: "Start a workflow from a built-in template.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "template": {
+                        "type": "string",
+                        "enum": templates().iter().map(|t| t.name).collect::<Vec<_>>(),
+                        "description": "Template name (see agent_workflow_templates)"
+                    },
+                    "arguments": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" },
+                        "description": "Values for the template's variables; file variables take a path relative to the workspace, pull request variables owner/repo#number, issue variables owner/repo or a list of owner/repo#number, directory variables a path relative to the workspace"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Optional: workflow name overriding the template's"
+                    }
+                },
+                "required": ["template"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: WorkflowFromTemplateArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let template = template(&args.template)?;
+
+        let mut arguments = args.arguments;
+        for variable in template.variables {
+            let value = arguments
+                .get(variable.name)
+                .cloned()
+                .or(variable.default.map(String::from));
+            let (Some(value), Some(derived)) = (value, variable.derived()) else {
+                continue;
+            };
+            let text = match variable.kind {
+                VariableKind::File => {
+                    std::fs::read_to_string(workspace_root(Some(&value))?).map_err(|e| {
+                        Error::InvalidParams(format!(
+                            "cannot read {} '{}': {}",
+                            variable.name, value, e
+                        ))
+                    })?
+                }
+                VariableKind::PullRequest => {
+                    GitHubClient::from_env()
+                        .pull_request_diff(&value.parse()?)
+                        .await?
+                }
+                VariableKind::Issues => {
+                    let batch = GitHubClient::from_env()
+                        .issue_batch(&value.parse()?)
+                        .await?;
+                    serde_json::to_string(&batch)?
+                }
+                VariableKind::Directory => {
+                    let extractor = CodeExtractor::new(workspace_root(Some(&value))?);
+                    let api = tokio::task::spawn_blocking(move || extractor.public_api())
+                        .await
+                        .map_err(|e| Error::Internal(format!("API extraction failed: {}", e)))??;
+                    serde_json::to_string(&api)?
+                }
+                VariableKind::Text => continue,
+            };
+            arguments.insert(derived, text);
+        }
+        let mut definition = template.instantiate(&arguments)?;
+        if let Some(name) = args.name {
+            definition.name = name;
+        }
+        let steps = definition.steps.len();
+        let workflow = definition.to_workflow_with(context.orchestrator.prompt_library())?;
+        let id = context.orchestrator.start_workflow(workflow).await?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Workflow Started\n\n**ID:** `{}`\n**Template:** {} ({} steps)\n\nUse `agent_workflow_step` with this ID to execute steps one at a time, or `agent_workflow_run` to run them all.",
+                id, template.name, steps
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for executing the next step in a workflow.
+pub struct WorkflowStepTool;
+
+#[derive(Debug, Deserialize)]
+struct WorkflowStepArgs {
+    workflow_id: String,
+    visible: Option<bool>,
+}
+
+#[async_trait::async_trait]
+impl Tool for WorkflowStepTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_workflow_step".into(),
+            description: "Execute the next step in a workflow.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "workflow_id": {
+                        "type": "string",
+                        "description": "ID of the workflow to execute"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this step in a visible browser for debugging or manual intervention"
+                    }
+                },
+                "required": ["workflow_id"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: WorkflowStepArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let result = context
+            .orchestrator
+            .execute_workflow_step_with(&args.workflow_id, args.visible.unwrap_or(context.visible))
+            .await?;
+
+        let workflow = context
+            .orchestrator
+            .get_workflow(&args.workflow_id)
+            .await
+            .ok_or_else(|| Error::Workflow("workflow not found".into()))?;
+
+        let status = if workflow.is_complete() {
+            "✅ Workflow Complete"
+        } else {
+            &format!("Step {}/{}", workflow.current_step, workflow.steps.len())
+        };
+        // Steps writing their output to a file show a summary instead
+        let output = match result.metadata.get(WRITTEN_TO_KEY) {
+            Some(written) => serde_json::from_value::<WrittenOutput>(written.clone())?
+                .summary(&result.output),
+            None => result.output,
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Workflow Step Result\n\n**Status:** {}\n**Duration:** {}ms\n\n## Output\n\n{}",
+                status, result.duration_ms, output
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for running a workflow to completion.
+pub struct WorkflowRunTool;
+
+#[derive(Debug, Deserialize)]
+struct WorkflowRunArgs {
+    workflow_id: String,
+    visible: Option<bool>,
+    max_steps: Option<usize>,
+}
+
+#[async_trait::async_trait]
+impl Tool for WorkflowRunTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_workflow_run".into(),
+            description: "Run a workflow's remaining steps in one call, stopping at a step that awaits human review or approval, at a failed step (which fails the workflow), or after max_steps. Returns a report of the steps run and how the run ended; after a review, call again to continue.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "workflow_id": {
+                        "type": "string",
+                        "description": "ID of the workflow to run"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run the steps in a visible browser for debugging or manual intervention"
+                    },
+                    "max_steps": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional: most steps to run in this call (default 100)"
+                    }
+                },
+                "required": ["workflow_id"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: WorkflowRunArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let visible = args.visible.unwrap_or(context.visible);
+        let max_steps = args.max_steps.unwrap_or(DEFAULT_MAX_RUN_STEPS).max(1);
+
+        // The run continues even if this call is cancelled or times out.
+        let orchestrator = context.orchestrator.clone();
+        let id = args.workflow_id;
+        let run =
+            tokio::spawn(async move { orchestrator.run_workflow(&id, visible, max_steps).await });
+        let report = run
+            .await
+            .map_err(|e| Error::Internal(format!("workflow run stopped unexpectedly: {}", e)))??;
+
+        let next = match &report.outcome {
+            RunOutcome::AwaitingReview { .. } => {
+                "\n\nReview the step with `agent_workflow_review`, then call `agent_workflow_run` again to continue."
+            }
+            RunOutcome::Paused { .. } | RunOutcome::StepLimit { .. } => {
+                "\n\nCall `agent_workflow_run` again to continue."
+            }
+            RunOutcome::Completed | RunOutcome::Failed { .. } => "",
+        };
+        Ok(ToolCallResult {
+            content: vec![
+                ContentItem::text(format!("{}{}", report.markdown(), next)),
+                ContentItem::text(serde_json::to_string_pretty(&report)?),
+            ],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for approving or rejecting a workflow step paused for review.
+pub struct WorkflowReviewTool;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ReviewDecision {
+    Approve,
+    Reject,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkflowReviewArgs {
+    workflow_id: String,
+    decision: ReviewDecision,
+    reviewer: Option<String>,
+    comments: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Tool for WorkflowReviewTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_workflow_review".into(),
+            description: "Approve or reject the step a workflow is paused on for human review or approval. The decision and comments are recorded in the step result; approving resumes the workflow and rejecting fails it.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "workflow_id": {
+                        "type": "string",
+                        "description": "ID of the paused workflow"
+                    },
+                    "decision": {
+                        "type": "string",
+                        "enum": ["approve", "reject"],
+                        "description": "Whether to approve or reject the step"
+                    },
+                    "reviewer": {
+                        "type": "string",
+                        "description": "Optional: who reviewed the step"
+                    },
+                    "comments": {
+                        "type": "string",
+                        "description": "Optional: the reviewer's comments"
+                    }
+                },
+                "required": ["workflow_id", "decision"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: WorkflowReviewArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let orchestrator = &context.orchestrator;
+        let step_name = orchestrator
+            .get_workflow(&args.workflow_id)
+            .await
+            .and_then(|w| w.current().map(|step| step.name.clone()))
+            .ok_or_else(|| Error::Workflow("workflow not found".into()))?;
+        let review = StepReview::new(
+            matches!(args.decision, ReviewDecision::Approve),
+            args.reviewer,
+            args.comments,
+        );
+        let result = orchestrator.review_step(&args.workflow_id, review.clone()).await?;
+
+        let workflow = orchestrator
+            .get_workflow(&args.workflow_id)
+            .await
+            .ok_or_else(|| Error::Workflow("workflow not found".into()))?;
+        let status = match &workflow.state {
+            WorkflowState::Failed(reason) => format!("❌ Workflow Failed: {}", reason),
+            _ if workflow.is_complete() => "✅ Workflow Complete".to_string(),
+            _ => format!(
+                "Step {}/{}; run agent_workflow_step to continue",
+                workflow.current_step,
+                workflow.steps.len()
+            ),
+        };
+        let mut text = format!(
+            "# Step Reviewed\n\n**Step:** {}\n**Decision:** {}\n**Status:** {}",
+            step_name,
+            review.describe(),
+            status
+        );
+        if let Some(result) = result {
+            text.push_str(&format!("\n\n## Output\n\n{}", result.output));
+        }
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(text)],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for listing workflows.
+pub struct WorkflowListTool;
+
+#[derive(Debug, Deserialize)]
+struct WorkflowListArgs {
+    state: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Tool for WorkflowListTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_workflow_list".into(),
+            description: "List workflows with their state, progress, and timestamps, most recently updated first.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "state": {
+                        "type": "string",
+                        "enum": ["pending", "running", "paused", "completed", "failed"],
+                        "description": "Optional: list only workflows in this state"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: WorkflowListArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let workflows: Vec<Workflow> = context
+            .orchestrator
+            .list_workflows()
+            .await
+            .into_iter()
+            .filter(|w| args.state.as_deref().is_none_or(|s| w.state.name() == s))
+            .collect();
+
+        let text = if workflows.is_empty() {
+            "No workflows".to_string()
+        } else {
+            workflows.iter().map(workflow_line).collect::<Vec<_>>().join("\n")
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!("# Workflows\n\n{}", text))],
+            is_error: false,
+        })
+    }
+}
+
+fn workflow_line(workflow: &Workflow) -> String {
+    let reason = match &workflow.state {
+        WorkflowState::Failed(reason) => format!(" ({})", reason),
+        _ => String::new(),
+    };
+    format!(
+        "- `{}` **{}**{} {} · {}/{} steps done · created {} · updated {}",
+        workflow.id,
+        workflow.state.name(),
+        reason,
+        workflow.name,
+        workflow.completed_steps(),
+        workflow.steps.len(),
+        workflow.created_at.format("%Y-%m-%d %H:%M:%S"),
+        workflow.updated_at.format("%Y-%m-%d %H:%M:%S")
+    )
+}
+
+/// Tool for inspecting one workflow step by step.
+pub struct WorkflowStatusTool;
+
+#[derive(Debug, Deserialize)]
+struct WorkflowStatusArgs {
+    workflow_id: String,
+}
+
+#[async_trait::async_trait]
+impl Tool for WorkflowStatusTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_workflow_status".into(),
+            description: "Show a workflow step by step without running anything: each step's type, state, review, and full result, including provider responses and metadata.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "workflow_id": {
+                        "type": "string",
+                        "description": "ID of the workflow to show"
+                    }
+                },
+                "required": ["workflow_id"]
+            }),
+        }
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: WorkflowStatusArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let workflow = context
+            .orchestrator
+            .get_workflow(&args.workflow_id)
+            .await
+            .ok_or_else(|| Error::Workflow("workflow not found".into()))?;
+
+        let mut text = format!(
+            "# Workflow: {}\n\n{}",
+            workflow.name,
+            workflow_line(&workflow).trim_start_matches("- ")
+        );
+        if !workflow.is_complete() {
+            if let Some(step) = workflow.current() {
+                text.push_str(&format!("\n\n**Next step:** {}", step.name));
+            }
+        }
+        for (i, step) in workflow.steps.iter().enumerate() {
+            text.push_str(&format!("\n\n{}", step_section(i + 1, step)?));
+        }
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(text)],
+            is_error: false,
+        })
+    }
+}
+
+fn step_section(number: usize, step: &WorkflowStep) -> Result<String> {
+    let state = match &step.state {
+        StepState::Failed(reason) => format!("failed ({})", reason),
+        state => state.name().to_string(),
+    };
+    let mut text = format!(
+        "## {}. {}\n\n**ID:** `{}` · **Type:** {:?} · **State:** {}",
+        number, step.name, step.id, step.step_type, state
+    );
+    if let Some(classification) = step.classification {
+        text.push_str(&format!(" · **Classification:** {}", classification));
+    }
+    if step.approved {
+        text.push_str(" · approved");
+    }
+    if !step.depends_on.is_empty() {
+        text.push_str(&format!("\n**Depends on:** {}", step.depends_on.join(", ")));
+    }
+    if let Some(review) = &step.review {
+        text.push_str(&format!("\n**Review:** {}", review.describe()));
+    }
+    let Some(result) = &step.result else {
+        return Ok(text);
+    };
+
+    text.push_str(&format!(
+        "\n**Provider:** {} · **Duration:** {}ms\n\n### Output\n\n{}",
+        result.provider.as_deref().unwrap_or("none"),
+        result.duration_ms,
+        result.output
+    ));
+    for response in result.responses.iter().flatten() {
+        let confidence = response
+            .confidence
+            .map(|c| format!(" · confidence {:.2}", c))
+            .unwrap_or_default();
+        let selected = if response.selected { " · selected" } else { "" };
+        text.push_str(&format!(
+            "\n\n### Response from {}{}{}\n\n{}",
+            response.provider, confidence, selected, response.text
+        ));
+    }
+    if !result.metadata.is_empty() {
+        let metadata: BTreeMap<_, _> = result.metadata.iter().collect();
+        text.push_str(&format!(
+            "\n\n### Metadata\n\n```json\n{}\n```",
+            serde_json::to_string_pretty(&metadata)?
+        ));
+    }
+    Ok(text)
+}
+
+/// Tool for getting orchestrator status.
+pub struct StatusTool;
+
+#[async_trait::async_trait]
+impl Tool for StatusTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_status".into(),
+            description: "Get the status of the agent orchestrator.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        _arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let status = context.orchestrator.status().await;
+
+        let providers_text = status
+            .available_providers
+            .iter()
+            .map(|p| format!("- ✅ {}", p))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let costs = &context.orchestrator.config().cost_model;
+        let stats_text = status
+            .provider_stats
+            .iter()
+            .map(|(p, s)| {
+                let usage = s
+                    .total_tokens
+                    .map(|tokens| {
+                        let cost = costs.estimate(Some(&p.to_string()), tokens);
+                        format!(", ~{} tokens (~${:.4})", tokens, cost)
+                    })
+                    .unwrap_or_default();
+                let mut models: Vec<String> = s
+                    .models
+                    .iter()
+                    .map(|(model, n)| format!("{} ×{}", model, n))
+                    .collect();
+                models.sort();
+                let models = if models.is_empty() {
+                    String::new()
+                } else {
+                    format!("; models: {}", models.join(", "))
+                };
+                let refusals = match s.refusals {
+                    0 => String::new(),
+                    n => format!(", {} declined", n),
+                };
+                format!(
+                    "- **{}**: {} total, {} success, {} failed{}{}{}",
+                    p,
+                    s.total_requests,
+                    s.successful_requests,
+                    s.failed_requests,
+                    refusals,
+                    usage,
+                    models
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let canary_text = match context.orchestrator.canary().map(|c| (c, c.status())) {
+            Some((canary, Ok(run))) => format!(
+                "\n\n## Routing Canary\n\n{}% of routed prompts; {}\n\n\
+                 - **Canary**: {} requests, {:.1}% failed, ${:.5} per request\n\
+                 - **Baseline**: {} requests, {:.1}% failed, ${:.5} per request",
+                canary.policy().percent,
+                match &run.rolled_back {
+                    Some(reason) => format!("**rolled back**: {}", reason),
+                    None => "running".into(),
+                },
+                run.canary.requests,
+                run.canary.failure_rate() * 100.0,
+                run.canary.mean_cost_usd(),
+                run.baseline.requests,
+                run.baseline.failure_rate() * 100.0,
+                run.baseline.mean_cost_usd()
+            ),
+            Some((_, Err(e))) => format!("\n\n## Routing Canary\n\nUnavailable: {}", e),
+            None => String::new(),
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Agent Orchestrator Status\n\n## Available Providers\n\n{}\n\n## Active Workflows\n\n{}\n\n## Provider Statistics\n\n{}{}",
+                providers_text,
+                status.active_workflows,
+                if stats_text.is_empty() { "No requests yet".into() } else { stats_text },
+                canary_text
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for reporting per-tenant usage against budgets.
+pub struct UsageTool;
+
+#[async_trait::async_trait]
+impl Tool for UsageTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_usage".into(),
+            description: "Report requests, tokens, and estimated spend against the budget for the calling tenant (every tenant when called locally), and recent spend against its baseline.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "resume_auto_runs": {
+                        "type": "boolean",
+                        "description": "Resume auto runs paused after a cost anomaly (local callers only)"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let orchestrator = &context.orchestrator;
+        let resume = arguments
+            .get("resume_auto_runs")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let spend_text = match orchestrator.spend() {
+            Some(spend) => {
+                if resume {
+                    if orchestrator.tenant() != DEFAULT_TENANT {
+                        return Err(Error::PermissionDenied(
+                            "only local callers may resume auto runs".into(),
+                        ));
+                    }
+                    orchestrator.resume_auto_runs()?;
+                }
+                let windows = spend
+                    .report(chrono::Utc::now())?
+                    .iter()
+                    .map(|(window, spent, baseline)| match baseline {
+                        Some(baseline) => format!(
+                            "- **This {}**: ${:.4} (baseline ${:.4})",
+                            window.name(),
+                            spent,
+                            baseline
+                        ),
+                        None => format!(
+                            "- **This {}**: ${:.4} (no baseline yet)",
+                            window.name(),
+                            spent
+                        ),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let paused = match spend.paused()? {
+                    Some(anomaly) => format!(
+                        "\n\n**Auto runs paused** after a cost anomaly: {}. Call with `resume_auto_runs` to continue.",
+                        anomaly.describe()
+                    ),
+                    None => String::new(),
+                };
+                format!("\n\n# Spend\n\n{}{}", windows, paused)
+            }
+            None if resume => {
+                return Err(Error::InvalidParams(
+                    "cost anomaly detection is not configured".into(),
+                ))
+            }
+            None => String::new(),
+        };
+        let reports = match orchestrator.tenant() {
+            DEFAULT_TENANT => orchestrator.tenants().reports()?,
+            tenant => vec![orchestrator.tenants().report(tenant)?],
+        };
+
+        let sections = reports
+            .iter()
+            .map(|r| {
+                let budget = match r.budget_usd {
+                    Some(budget) => format!(
+                        "${:.4} of ${:.2} ({:?} budget since {})",
+                        r.usage.cost_usd,
+                        budget,
+                        r.period,
+                        r.usage.period_start.format("%Y-%m-%d")
+                    ),
+                    None => format!("${:.4} (no budget)", r.usage.cost_usd),
+                };
+                let providers = r
+                    .usage
+                    .by_provider
+                    .iter()
+                    .map(|(p, u)| format!("- **{}**: {} requests, ${:.4}", p, u.requests, u.cost_usd))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!(
+                    "## {}\n\n**Spent:** {}\n**Requests:** {}\n**Tokens:** {} prompt, {} response\n\n{}",
+                    r.usage.tenant,
+                    budget,
+                    r.usage.requests,
+                    r.usage.prompt_tokens,
+                    r.usage.response_tokens,
+                    if providers.is_empty() { "No requests yet".into() } else { providers }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Tenant Usage\n\n{}{}",
+                sections, spend_text
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for entering, leaving, and checking maintenance mode.
+pub struct MaintenanceTool;
+
+#[async_trait::async_trait]
+impl Tool for MaintenanceTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_maintenance".into(),
+            description: "Put the orchestrator into maintenance mode, such as before rotating provider credentials: calls in flight finish, auto runs and background jobs pause, and new provider calls are refused until it is left. Local callers only, except for checking status.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["enter", "exit", "status"],
+                        "description": "Enter or leave maintenance mode, or report it (default status)"
+                    },
+                    "reason": {
+                        "type": "string",
+                        "description": "Why, shown in refused calls (for enter)"
+                    },
+                    "wait_secs": {
+                        "type": "integer",
+                        "description": "Seconds to wait for calls in flight to finish (for enter, default 0)"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let orchestrator = &context.orchestrator;
+        let action = arguments
+            .get("action")
+            .and_then(|v| v.as_str())
+            .unwrap_or("status");
+        if action != "status" && orchestrator.tenant() != DEFAULT_TENANT {
+            return Err(Error::PermissionDenied(
+                "only local callers may change maintenance mode".into(),
+            ));
+        }
+
+        let text = match action {
+            "enter" => {
+                let reason = arguments
+                    .get("reason")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("maintenance");
+                let wait_secs = arguments
+                    .get("wait_secs")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+                let maintenance = orchestrator.enter_maintenance(reason)?;
+                let drained = orchestrator.drain(Duration::from_secs(wait_secs)).await;
+                let in_flight = if drained {
+                    "No calls in flight.".to_string()
+                } else {
+                    format!(
+                        "{} calls still in flight; they will finish.",
+                        orchestrator.calls_in_flight()
+                    )
+                };
+                format!(
+                    "⏸ Entered maintenance mode: {}\n\n{}",
+                    maintenance.describe(),
+                    in_flight
+                )
+            }
+            "exit" => match orchestrator.exit_maintenance()? {
+                Some(maintenance) => format!(
+                    "▶ Left maintenance mode: {}\n\nPaused auto runs continue when called again; queued jobs are picked up by the background worker.",
+                    maintenance.describe()
+                ),
+                None => "Not in maintenance mode.".to_string(),
+            },
+            "status" => match orchestrator.maintenance() {
+                Some(maintenance) => format!(
+                    "⏸ In maintenance mode: {}\n\n{} calls in flight.",
+                    maintenance.describe(),
+                    orchestrator.calls_in_flight()
+                ),
+                None => format!(
+                    "Not in maintenance mode. {} calls in flight.",
+                    orchestrator.calls_in_flight()
+                ),
+            },
+            other => {
+                return Err(Error::InvalidParams(format!(
+                    "unknown action '{}', expected enter, exit, or status",
+                    other
+                )))
+            }
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(text)],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for reading and changing provider preferences at runtime.
+pub struct ConfigTool;
+
+/// A change to provider preferences.
+enum PreferenceChange {
+    Priority(Provider, u32),
+    Disabled(Provider, bool),
+    Setting(Provider, Option<serde_json::Value>),
+}
+
+impl ConfigTool {
+    /// The changes `arguments` ask for, checking every provider named.
+    fn changes(arguments: &serde_json::Value) -> Result<Vec<PreferenceChange>> {
+        let mut changes = Vec::new();
+        if let Some(priorities) = arguments.get("priorities").and_then(|v| v.as_object()) {
+            for (name, priority) in priorities {
+                let priority = priority
+                    .as_u64()
+                    .and_then(|p| u32::try_from(p).ok())
+                    .ok_or_else(|| {
+                        Error::InvalidParams(format!(
+                            "priority of {} must be a non-negative integer",
+                            name
+                        ))
+                    })?;
+                changes.push(PreferenceChange::Priority(parse_provider(name)?, priority));
+            }
+        }
+        for (key, disabled) in [("disable", true), ("enable", false)] {
+            let names = arguments.get(key).and_then(|v| v.as_array());
+            for name in names.into_iter().flatten() {
+                let name = name.as_str().ok_or_else(|| {
+                    Error::InvalidParams(format!("{} must list provider names", key))
+                })?;
+                changes.push(PreferenceChange::Disabled(parse_provider(name)?, disabled));
+            }
+        }
+        if let Some(settings) = arguments.get("settings").and_then(|v| v.as_object()) {
+            for (name, setting) in settings {
+                let setting = (!setting.is_null()).then(|| setting.clone());
+                changes.push(PreferenceChange::Setting(parse_provider(name)?, setting));
+            }
+        }
+        Ok(changes)
+    }
+}
+
+#[async_trait::async_trait]
+impl Tool for ConfigTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_config".into(),
+            description: "Read or change provider preferences without restarting the server: routing priorities, disabled providers, and per-provider settings. Changes are persisted. Without arguments, reports the current preferences. Local callers only, except for reading.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "priorities": {
+                        "type": "object",
+                        "additionalProperties": { "type": "integer", "minimum": 0 },
+                        "description": "Routing priorities keyed by provider; higher is preferred"
+                    },
+                    "disable": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Providers to stop routing to"
+                    },
+                    "enable": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Disabled providers to route to again"
+                    },
+                    "settings": {
+                        "type": "object",
+                        "description": "Provider-specific settings keyed by provider; null clears them"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let orchestrator = &context.orchestrator;
+        let changes = Self::changes(&arguments)?;
+        let preferences = if changes.is_empty() {
+            orchestrator.provider_preferences().await
+        } else {
+            if orchestrator.tenant() != DEFAULT_TENANT {
+                return Err(Error::PermissionDenied(
+                    "only local callers may change provider preferences".into(),
+                ));
+            }
+            orchestrator
+                .update_preferences(|preferences| {
+                    for change in &changes {
+                        match change {
+                            PreferenceChange::Priority(provider, priority) => {
+                                preferences.set_priority(*provider, *priority)
+                            }
+                            PreferenceChange::Disabled(provider, disabled) => {
+                                preferences.set_disabled(*provider, *disabled)
+                            }
+                            PreferenceChange::Setting(provider, setting) => {
+                                preferences.set_setting(*provider, setting.clone())
+                            }
+                        }
+                    }
+                })
+                .await?
+        };
+
+        let mut providers: Vec<Provider> = Provider::all().into_iter().collect();
+        providers.sort_by_key(|p| std::cmp::Reverse(preferences.priority(*p)));
+        let rows = providers
+            .iter()
+            .map(|provider| {
+                let state = if preferences.is_disabled(*provider) {
+                    "disabled"
+                } else {
+                    "enabled"
+                };
+                let settings = preferences
+                    .setting(*provider)
+                    .map(|s| format!("`{}`", s))
+                    .unwrap_or_else(|| "-".into());
+                format!(
+                    "| {} | {} | {} | {} |",
+                    provider,
+                    preferences.priority(*provider),
+                    state,
+                    settings
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let heading = if changes.is_empty() {
+            "# Provider Preferences"
+        } else {
+            "# Provider Preferences Updated"
+        };
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "{}\n\n| Provider | Priority | State | Settings |\n|---|---|---|---|\n{}",
+                heading, rows
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for showing how a prompt would be routed without sending it.
+pub struct RoutingSimulateTool;
+
+#[derive(Debug, Deserialize)]
+struct RoutingSimulateArgs {
+    message: Option<String>,
+    task_type: Option<String>,
+    context: Option<String>,
+    classification: Option<DataClassification>,
+    routing: Option<RoutingChoice>,
+}
+
+#[async_trait::async_trait]
+impl Tool for RoutingSimulateTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_routing_simulate".into(),
+            description: "Show which provider each routing profile would choose for a hypothetical prompt or task type, with the objectives behind each choice and why other providers are left out. Nothing is sent.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "message": {
+                        "type": "string",
+                        "description": "The prompt to route; its size, shape, and language decide the task type"
+                    },
+                    "task_type": {
+                        "type": "string",
+                        "enum": ["general", "search", "large_context", "code", "creative", "quick"],
+                        "description": "Optional: route as this task type instead of the one refined from the message"
+                    },
+                    "context": {
+                        "type": "string",
+                        "description": "Optional: system context counted toward the prompt's size"
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the prompt"
+                    },
+                    "routing": {
+                        "oneOf": [
+                            { "type": "string" },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "quality": { "type": "number", "minimum": 0 },
+                                    "cost": { "type": "number", "minimum": 0 },
+                                    "latency": { "type": "number", "minimum": 0 },
+                                    "quota": { "type": "number", "minimum": 0 },
+                                    "freshness": { "type": "number", "minimum": 0 }
+                                }
+                            }
+                        ],
+                        "description": "Optional: routing profile or objective weights the call would pass"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: RoutingSimulateArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let task = args
+            .task_type
+            .as_deref()
+            .map(|name| {
+                TaskType::from_name(name)
+                    .ok_or_else(|| Error::InvalidParams(format!("unknown task type: {}", name)))
+            })
+            .transpose()?;
+        if args.message.is_none() && task.is_none() {
+            return Err(Error::InvalidParams("give a message or a task_type".into()));
+        }
+        let options = PromptOptions {
+            context: args.context,
+            classification: args.classification,
+            routing: args.routing,
+            ..Default::default()
+        };
+
+        let simulation = context
+            .orchestrator
+            .simulate_routing(args.message.as_deref().unwrap_or_default(), task, &options)
+            .await?;
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(simulation.report())],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for projecting a routing change from past traffic.
+pub struct RoutingReplayTool;
+
+#[derive(Debug, Deserialize)]
+struct RoutingReplayArgs {
+    #[serde(flatten)]
+    proposal: RoutingProposal,
+    days: Option<u32>,
+}
+
+#[async_trait::async_trait]
+impl Tool for RoutingReplayTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_routing_replay".into(),
+            description: "Replay past routed requests from the request ledger against a proposed routing configuration and report the projected change in cost, latency, and failure rate, and in each provider's share, before applying it with agent_config. Nothing is sent or changed.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "routing_profiles": {
+                        "type": "object",
+                        "description": "Optional: routing profiles replacing the current ones, in the --routing-profiles format"
+                    },
+                    "priorities": {
+                        "type": "object",
+                        "additionalProperties": { "type": "integer", "minimum": 0 },
+                        "description": "Optional: routing priorities keyed by provider; higher is preferred"
+                    },
+                    "disable": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Optional: providers to stop routing to"
+                    },
+                    "enable": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Optional: disabled providers to route to again"
+                    },
+                    "days": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional: replay only the last this many days (default: the whole ledger)"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: RoutingReplayArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let since = args
+            .days
+            .map(|days| chrono::Utc::now() - chrono::Duration::days(i64::from(days)));
+        let projection = context
+            .orchestrator
+            .replay_routing(&args.proposal, since)
+            .await?;
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(projection.report())],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for listing available providers.
+pub struct ListProvidersTool;
+
+#[async_trait::async_trait]
+impl Tool for ListProvidersTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_list_providers".into(),
+            description: "List all available AI providers and their capabilities.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        _arguments: serde_json::Value,
+        _context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let providers = [
+            ("claude", "Claude (Anthropic)", "200k context, artifacts, code execution"),
+            ("grok", "Grok (X/xAI)", "Real-time info, X integration"),
+            ("gemini", "Gemini (Google)", "2M context, Google integration"),
+            ("chatgpt", "ChatGPT (OpenAI)", "GPT-4o, vision, web search, code"),
+            ("perplexity", "Perplexity AI", "Search-focused, sources cited"),
+            ("notebooklm", "NotebookLM (Google)", "500k context, research assistant"),
+        ];
+
+        let text = providers
+            .iter()
+            .map(|(id, name, caps)| format!("## {} (`{}`)\n\n{}\n", name, id, caps))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Available AI Providers\n\n{}",
+                text
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for listing prompts waiting on human intervention.
+pub struct InterventionsTool;
+
+#[async_trait::async_trait]
+impl Tool for InterventionsTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_interventions".into(),
+            description: "List prompts paused until a captcha or verification challenge is solved.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        _arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let pending = context.orchestrator.interventions().pending();
+
+        let text = if pending.is_empty() {
+            "No pending interventions".to_string()
+        } else {
+            pending
+                .iter()
+                .map(|i| {
+                    format!(
+                        "- `{}` **{}**: {} (since {})",
+                        i.id,
+                        i.provider,
+                        i.reason,
+                        i.created_at.format("%H:%M:%S")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!("# Pending Interventions\n\n{}", text))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for resuming a prompt paused on human intervention.
+pub struct InterventionResolveTool;
+
+#[derive(Debug, Deserialize)]
+struct InterventionResolveArgs {
+    id: String,
+    #[serde(default = "default_true")]
+    solved: bool,
+}
+
+#[async_trait::async_trait]
+impl Tool for InterventionResolveTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_intervention_resolve".into(),
+            description: "Resume a paused prompt once its captcha or verification challenge is solved.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Intervention ID"
+                    },
+                    "solved": {
+                        "type": "boolean",
+                        "description": "Whether the challenge was solved; false abandons the prompt (default: true)",
+                        "default": true
+                    }
+                },
+                "required": ["id"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: InterventionResolveArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        context
+            .orchestrator
+            .interventions()
+            .resolve(&args.id, args.solved)?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(if args.solved {
+                format!("Intervention `{}` resolved; resuming the prompt.", args.id)
+            } else {
+                format!("Intervention `{}` abandoned.", args.id)
+            })],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for inspecting the background job queue.
+pub struct JobsTool;
+
+#[derive(Debug, Deserialize)]
+struct JobsArgs {
+    job_id: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Tool for JobsTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_jobs".into(),
+            description: "List queued, running, and finished background jobs, or show one job.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "Optional: show only this job"
+                    }
+                },
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: JobsArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let queue = context.orchestrator.jobs();
+
+        let jobs = match &args.job_id {
+            Some(id) => vec![queue
+                .get(id)?
+                .ok_or_else(|| Error::InvalidParams(format!("unknown job: {}", id)))?],
+            None => queue.list()?,
+        };
+
+        let text = if jobs.is_empty() {
+            "No jobs".to_string()
+        } else {
+            jobs.iter().map(job_line).collect::<Vec<_>>().join("\n")
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!("# Jobs\n\n{}", text))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for cancelling or re-prioritizing a background job.
+pub struct JobUpdateTool;
+
+#[derive(Debug, Deserialize)]
+struct JobUpdateArgs {
+    job_id: String,
+    priority: Option<i32>,
+    #[serde(default)]
+    cancel: bool,
+}
+
+#[async_trait::async_trait]
+impl Tool for JobUpdateTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_job_update".into(),
+            description: "Cancel a background job or change its priority.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "Job ID"
+                    },
+                    "priority": {
+                        "type": "integer",
+                        "description": "Optional: new priority; higher runs first"
+                    },
+                    "cancel": {
+                        "type": "boolean",
+                        "description": "Optional: cancel the job; a running job stops shortly after (default: false)"
+                    }
+                },
+                "required": ["job_id"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: JobUpdateArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let queue = context.orchestrator.jobs();
+
+        let job = match (args.cancel, args.priority) {
+            (true, _) => queue.cancel(&args.job_id)?,
+            (false, Some(priority)) => queue.set_priority(&args.job_id, priority)?,
+            (false, None) => {
+                return Err(Error::InvalidParams(
+                    "either priority or cancel is required".into(),
+                ))
+            }
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!("Job updated:\n\n{}", job_line(&job)))],
+            is_error: false,
+        })
+    }
+}
+
+/// Render a job as a Markdown list item.
+fn job_line(job: &Job) -> String {
+    let detail = match (&job.result, &job.last_error) {
+        (Some(result), _) => format!(": {}", result),
+        (None, Some(error)) => format!(" (last error: {})", error),
+        (None, None) => String::new(),
+    };
+    format!(
+        "- `{}` **{:?}** {} · priority {} · attempt {}/{} · queued {}{}",
+        job.id,
+        job.state,
+        job.kind.describe(),
+        job.priority,
+        job.attempts,
+        job.max_attempts,
+        job.created_at.format("%Y-%m-%d %H:%M:%S"),
+        detail
+    )
+}
+
+/// Tool for critiquing content with one provider or a panel.
+pub struct CritiqueTool;
+
+#[derive(Debug, Deserialize)]
+struct CritiqueArgs {
+    content: String,
+    #[serde(default)]
+    criteria: Vec<String>,
+    #[serde(default)]
+    providers: Vec<String>,
+    classification: Option<DataClassification>,
+    visible: Option<bool>,
+}
+
+#[async_trait::async_trait]
+impl Tool for CritiqueTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_critique".into(),
+            description: "Critique content against criteria with one provider or a panel, returning structured findings (severity, location, suggestion).".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "content": {
+                        "type": "string",
+                        "description": "The content to critique"
+                    },
+                    "criteria": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Optional: what to judge the content on (default: correctness, clarity, completeness)"
+                    },
+                    "providers": {
+                        "type": "array",
+                        "items": {
+                            "type": "string",
+                            "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"]
+                        },
+                        "description": "Optional: critics to use; several form a panel (default: best available)"
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the content"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this call in a visible browser for debugging or manual intervention"
+                    }
+                },
+                "required": ["content"]
+            }),
+        }
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: CritiqueArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let providers = args
+            .providers
+            .iter()
+            .map(|p| parse_provider(p))
+            .collect::<Result<Vec<_>>>()?;
+        let options = PromptOptions {
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            ..Default::default()
+        };
+
+        let critique = context
+            .orchestrator
+            .critique(&args.content, &args.criteria, providers, options)
+            .await?;
+
+        let findings = if critique.findings.is_empty() {
+            "No problems found.".to_string()
+        } else {
+            critique
+                .findings
+                .iter()
+                .map(|f| {
+                    let mut line = format!("- **{}** ({})", f.severity, f.provider);
+                    if let Some(location) = &f.location {
+                        line.push_str(&format!(" `{}`", location));
+                    }
+                    line.push_str(&format!(": {}", f.issue));
+                    if let Some(suggestion) = &f.suggestion {
+                        line.push_str(&format!("\n  - Suggestion: {}", suggestion));
+                    }
+                    line
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let failures: String = critique
+            .failures
+            .iter()
+            .map(|(p, e)| format!("\n\n*{} failed: {}*", p, e))
+            .collect();
+
+        Ok(ToolCallResult {
+            content: vec![
+                ContentItem::text(format!(
+                    "# Critique ({})\n\n{}{}",
+                    critique.critics.join(", "),
+                    findings,
+                    failures
+                )),
+                ContentItem::text(serde_json::to_string_pretty(&critique)?),
+            ],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for translating text and verifying it by back-translation.
+pub struct TranslateTool;
+
+#[derive(Debug, Deserialize)]
+struct TranslateArgs {
+    text: String,
+    target_language: String,
+    source_language: Option<String>,
+    translator: Option<String>,
+    verifier: Option<String>,
+    classification: Option<DataClassification>,
+    visible: Option<bool>,
+}
+
+#[async_trait::async_trait]
+impl Tool for TranslateTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_translate".into(),
+            description: "Translate text with one provider and verify it by back-translating with another, flagging meaning lost, added, or changed.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "The text to translate"
+                    },
+                    "target_language": {
+                        "type": "string",
+                        "description": "Language to translate into"
+                    },
+                    "source_language": {
+                        "type": "string",
+                        "description": "Optional: language of the text (default: English)"
+                    },
+                    "translator": {
+                        "type": "string",
+                        "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"],
+                        "description": "Optional: provider that translates (default: best available)"
+                    },
+                    "verifier": {
+                        "type": "string",
+                        "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"],
+                        "description": "Optional: provider that back-translates (default: best other provider)"
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the text"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this call in a visible browser for debugging or manual intervention"
+                    }
+                },
+                "required": ["text", "target_language"]
+            }),
+        }
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: TranslateArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let translation = TranslationOptions {
+            source_language: args.source_language,
+            translator: args.translator.as_deref().map(parse_provider).transpose()?,
+            verifier: args.verifier.as_deref().map(parse_provider).transpose()?,
+        };
+        let options = PromptOptions {
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            ..Default::default()
+        };
+
+        let report = context
+            .orchestrator
+            .translate(&args.text, &args.target_language, translation, options)
+            .await?;
+
+        let verdict = if report.is_flagged() {
+            "⚠ Flagged for review"
+        } else {
+            "✓ Verified"
+        };
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Translation ({} → {})\n\n{}\n\n## Verification\n\n{}: back-translated by {} \
+                 (translated by {}), {:.0}% word overlap with the source.\n\n\
+                 **Back-translation:**\n\n{}{}",
+                report.source_language,
+                report.target_language,
+                report.translation,
+                verdict,
+                report.verifier,
+                report.translator,
+                report.fidelity * 100.0,
+                report.back_translation,
+                divergence_section(&report.divergences)
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for running a time-boxed research sprint.
+pub struct ResearchTool;
+
+#[derive(Debug, Deserialize)]
+struct ResearchArgs {
+    question: String,
+    #[serde(default)]
+    providers: Vec<String>,
+    max_rounds: Option<usize>,
+    time_budget_secs: Option<u64>,
+    max_cost_usd: Option<f64>,
+    synthesizer: Option<String>,
+    classification: Option<DataClassification>,
+    visible: Option<bool>,
+}
+
+#[async_trait::async_trait]
+impl Tool for ResearchTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_research".into(),
+            description: "Research a question with search-capable providers in refining rounds under a time and cost budget, returning an answer with cited sources.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "question": {
+                        "type": "string",
+                        "description": "The research question"
+                    },
+                    "providers": {
+                        "type": "array",
+                        "items": {
+                            "type": "string",
+                            "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"]
+                        },
+                        "description": "Optional: providers that search (default: search-capable providers)"
+                    },
+                    "max_rounds": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional: maximum query rounds (default: 3)"
+                    },
+                    "time_budget_secs": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional: stop querying after this many seconds (default: 300)"
+                    },
+                    "max_cost_usd": {
+                        "type": "number",
+                        "minimum": 0,
+                        "description": "Optional: stop querying before exceeding this estimated cost"
+                    },
+                    "synthesizer": {
+                        "type": "string",
+                        "enum": ["claude", "grok", "gemini", "chatgpt", "perplexity", "notebooklm"],
+                        "description": "Optional: provider that refines queries and writes the answer (default: best available)"
+                    },
+                    "classification": {
+                        "type": "string",
+                        "enum": ["public", "internal", "confidential"],
+                        "description": "Optional: data classification restricting which providers may receive the question"
+                    },
+                    "visible": {
+                        "type": "boolean",
+                        "description": "Optional: run this call in a visible browser for debugging or manual intervention"
+                    }
+                },
+                "required": ["question"]
+            }),
+        }
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: ResearchArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let research = ResearchOptions {
+            providers: args
+                .providers
+                .iter()
+                .map(|p| parse_provider(p))
+                .collect::<Result<_>>()?,
+            max_rounds: args.max_rounds.unwrap_or(DEFAULT_MAX_ROUNDS).max(1),
+            time_budget: args
+                .time_budget_secs
+                .map_or(ResearchOptions::default().time_budget, Duration::from_secs),
+            max_cost_usd: args.max_cost_usd,
+            synthesizer: args.synthesizer.as_deref().map(parse_provider).transpose()?,
+        };
+        let options = PromptOptions {
+            classification: args.classification,
+            visible: args.visible.unwrap_or(context.visible),
+            ..Default::default()
+        };
+
+        let report = context
+            .orchestrator
+            .research(&args.question, research, options)
+            .await?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "# Research: {}\n\n{}\n\n---\n\n{} rounds, {} findings, {} sources · stopped: {} · \
+                 estimated cost ${:.4} · {:.1}s",
+                report.question,
+                report.to_markdown(),
+                report.rounds,
+                report.findings.len(),
+                report.sources.len(),
+                report.stopped.describe(),
+                report.spent_usd,
+                report.duration_ms as f64 / 1000.0
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for exporting a workflow or session as a Markdown or HTML report.
+pub struct ExportTool;
+
+#[derive(Debug, Deserialize)]
+struct ExportArgs {
+    workflow_id: Option<String>,
+    session_id: Option<String>,
+    path: String,
+    format: Option<ExportFormat>,
+}
+
+#[async_trait::async_trait]
+impl Tool for ExportTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_export".into(),
+            description: "Write a shareable Markdown or HTML report of a workflow or session: prompts, per-provider responses, consensus, and estimated costs (local callers only).".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "workflow_id": {
+                        "type": "string",
+                        "description": "Workflow ID (or give session_id)"
+                    },
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session ID (or give workflow_id)"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File to write the report to, relative to the workspace root"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["markdown", "html"],
+                        "description": "Optional: report format (default: from the path extension, else markdown)"
+                    }
+                },
+                "required": ["path"]
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: ExportArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let orchestrator = &context.orchestrator;
+        if orchestrator.tenant() != DEFAULT_TENANT {
+            return Err(Error::PermissionDenied(
+                "only local callers may export reports".into(),
+            ));
+        }
+        let path = workspace_path(&args.path)?;
+        let format = args
+            .format
+            .unwrap_or_else(|| ExportFormat::from_path(&path));
+        let costs = orchestrator.cost_model();
+
+        let (exported, bytes) = match (&args.workflow_id, &args.session_id) {
+            (Some(id), None) => {
+                let workflow = orchestrator
+                    .get_workflow(id)
+                    .await
+                    .ok_or_else(|| Error::Workflow(format!("workflow not found: {}", id)))?;
+                let bytes = export_workflow(&workflow, costs, format, &path)?;
+                (format!("workflow `{}`", workflow.name), bytes)
+            }
+            (None, Some(id)) => {
+                let session = orchestrator
+                    .sessions()
+                    .get(id)
+                    .ok_or_else(|| Error::InvalidParams(format!("no session with ID {}", id)))?;
+                let bytes = export_session(&session, costs, format, &path)?;
+                (format!("session `{}`", session.id), bytes)
+            }
+            _ => {
+                return Err(Error::InvalidParams(
+                    "give either workflow_id or session_id".into(),
+                ))
+            }
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "Exported {} to {} ({} bytes).",
+                exported, args.path, bytes
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for saving a named, reusable prompt.
+pub struct PromptSaveTool;
+
+#[derive(Debug, Deserialize)]
+struct PromptSaveArgs {
+    name: String,
+    template: String,
+    description: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Tool for PromptSaveTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_prompt_save".into(),
+            description: "Save a named, reusable prompt to the shared library. Use {{variable}} placeholders for values filled in later.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Unique prompt name"
+                    },
+                    "template": {
+                        "type": "string",
+                        "description": "Prompt text with {{variable}} placeholders"
+                    },
+                    "description": {
+                        "type": "string",
+                        "description": "Optional: what the prompt is for"
+                    }
+                },
+                "required": ["name", "template"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: PromptSaveArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let prompt = context.orchestrator.prompt_library().create(
+            &args.name,
+            args.template,
+            args.description,
+        )?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "Saved prompt `{}` (version {}){}.",
+                prompt.name,
+                prompt.version,
+                variables_note(&prompt.variables())
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for listing saved prompts.
+pub struct PromptListTool;
+
+#[async_trait::async_trait]
+impl Tool for PromptListTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_prompt_list".into(),
+            description: "List saved prompts in the shared library.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        _arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let prompts = context.orchestrator.prompt_library().list();
+
+        let text = if prompts.is_empty() {
+            "No saved prompts".to_string()
+        } else {
+            prompts
+                .iter()
+                .map(|p| {
+                    format!(
+                        "## {} (v{})\n\n{}{}\n\n```\n{}\n```",
+                        p.name,
+                        p.version,
+                        p.description.as_deref().unwrap_or("(no description)"),
+                        variables_note(&p.variables()),
+                        p.template
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        };
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!("# Saved Prompts\n\n{}", text))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for updating a saved prompt.
+pub struct PromptUpdateTool;
+
+#[derive(Debug, Deserialize)]
+struct PromptUpdateArgs {
+    name: String,
+    template: Option<String>,
+    description: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Tool for PromptUpdateTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_prompt_update".into(),
+            description: "Update the template or description of a saved prompt. Each update creates a new version; earlier versions are kept.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Name of the saved prompt"
+                    },
+                    "template": {
+                        "type": "string",
+                        "description": "Optional: new prompt text"
+                    },
+                    "description": {
+                        "type": "string",
+                        "description": "Optional: new description"
+                    }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: PromptUpdateArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let prompt = context.orchestrator.prompt_library().update(
+            &args.name,
+            args.template,
+            args.description,
+        )?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!(
+                "Updated prompt `{}` to version {}{}.",
+                prompt.name,
+                prompt.version,
+                variables_note(&prompt.variables())
+            ))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for deleting a saved prompt.
+pub struct PromptDeleteTool;
+
+#[derive(Debug, Deserialize)]
+struct PromptDeleteArgs {
+    name: String,
+}
+
+#[async_trait::async_trait]
+impl Tool for PromptDeleteTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_prompt_delete".into(),
+            description: "Delete a saved prompt from the shared library.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Name of the saved prompt"
+                    }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: PromptDeleteArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        context.orchestrator.prompt_library().delete(&args.name)?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(format!("Deleted prompt `{}`.", args.name))],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for comparing two versions of a saved prompt.
+pub struct PromptDiffTool;
+
+#[derive(Debug, Deserialize)]
+struct PromptDiffArgs {
+    name: String,
+    from: Option<u32>,
+    to: Option<u32>,
+}
+
+#[async_trait::async_trait]
+impl Tool for PromptDiffTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_prompt_diff".into(),
+            description: "Compare two versions of a saved prompt and list the workflows that use each.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Name of the saved prompt"
+                    },
+                    "from": {
+                        "type": "integer",
+                        "description": "Optional: older version (defaults to the one before `to`)"
+                    },
+                    "to": {
+                        "type": "integer",
+                        "description": "Optional: newer version (defaults to the current version)"
+                    }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
 
-        let status = if workflow.is_complete() {
-            "✅ Workflow Complete"
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: PromptDiffArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let prompt = context.orchestrator.prompt_library().get(&args.name)?;
+        let to = prompt.at_version(args.to.unwrap_or(prompt.version))?;
+        let from = prompt.at_version(args.from.unwrap_or(to.version.saturating_sub(1).max(1)))?;
+
+        let mut text = format!(
+            "# Prompt `{}`: v{} → v{}\n\n",
+            prompt.name, from.version, to.version
+        );
+        if from.description != to.description {
+            text.push_str(&format!(
+                "**Description:** {} → {}\n\n",
+                from.description.as_deref().unwrap_or("(none)"),
+                to.description.as_deref().unwrap_or("(none)")
+            ));
+        }
+        if from.template == to.template {
+            text.push_str("Templates are identical.\n\n");
         } else {
-            &format!("Step {}/{}", workflow.current_step, workflow.steps.len())
-        };
+            text.push_str(&format!(
+                "```diff\n{}\n```\n\n",
+                line_diff(&from.template, &to.template)
+            ));
+        }
+
+        text.push_str("## Workflows\n");
+        let workflows = context.orchestrator.workflows_using_prompt(&prompt.name).await;
+        for version in [from.version, to.version] {
+            let using: Vec<String> = workflows
+                .iter()
+                .filter(|w| {
+                    w.steps.iter().any(|s| {
+                        s.saved_prompt
+                            .as_ref()
+                            .is_some_and(|p| p.name == prompt.name && p.version == Some(version))
+                    })
+                })
+                .map(|w| format!("{} (`{}`)", w.name, w.id))
+                .collect();
+            text.push_str(&format!(
+                "\n- **v{}:** {}",
+                version,
+                if using.is_empty() {
+                    "none".to_string()
+                } else {
+                    using.join(", ")
+                }
+            ));
+            if from.version == to.version {
+                break;
+            }
+        }
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(text)],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for extracting the code relevant to a symbol from the workspace.
+pub struct CodeContextTool;
+
+#[derive(Debug, Deserialize)]
+struct CodeContextArgs {
+    symbol: String,
+    path: Option<String>,
+    max_tokens: Option<u64>,
+    max_callers: Option<usize>,
+}
+
+#[async_trait::async_trait]
+impl Tool for CodeContextTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_code_context".into(),
+            description: "Extract a symbol's definition, the types it uses, and its callers from the workspace instead of inlining whole files.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "symbol": {
+                        "type": "string",
+                        "description": "Function, method, or type name"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Optional: directory to search, relative to the workspace root"
+                    },
+                    "max_tokens": {
+                        "type": "integer",
+                        "description": "Optional: token budget for the extracted items (default 8000)",
+                        "minimum": 1
+                    },
+                    "max_callers": {
+                        "type": "integer",
+                        "description": "Optional: callers to include (default 5)",
+                        "minimum": 0
+                    }
+                },
+                "required": ["symbol"]
+            }),
+        }
+    }
+
+    fn large_output(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        _context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: CodeContextArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let mut extractor = CodeExtractor::new(workspace_root(args.path.as_deref())?);
+        if let Some(max_tokens) = args.max_tokens {
+            extractor = extractor.max_tokens(max_tokens);
+        }
+        if let Some(max_callers) = args.max_callers {
+            extractor = extractor.max_callers(max_callers);
+        }
+        let extraction = extract_code(extractor, args.symbol).await?;
+
+        Ok(ToolCallResult {
+            content: vec![ContentItem::text(extraction.to_markdown())],
+            is_error: false,
+        })
+    }
+}
+
+/// Tool for forking a session at a turn.
+pub struct SessionForkTool;
+
+#[derive(Debug, Deserialize)]
+struct SessionForkArgs {
+    session_id: String,
+    turn: usize,
+    provider: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl Tool for SessionForkTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "agent_session_fork".into(),
+            description: "Fork a conversation session at a turn to explore another direction, leaving the original intact.".into(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session to fork"
+                    },
+                    "turn": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Last turn the branch keeps"
+                    },
+                    "provider": {
+                        "type": "string",
+                        "description": "Optional: provider the branch continues with (defaults to the session's)"
+                    }
+                },
+                "required": ["session_id", "turn"]
+            }),
+        }
+    }
+
+    fn mutating(&self) -> bool {
+        true
+    }
+
+    async fn execute(
+        &self,
+        arguments: serde_json::Value,
+        context: &ToolContext,
+    ) -> Result<ToolCallResult> {
+        let args: SessionForkArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let provider = args
+            .provider
+            .as_deref()
+            .map(|name| parse_provider(name).map(|p| p.to_string()))
+            .transpose()?;
+
+        let branch = context
+            .orchestrator
+            .sessions()
+            .fork(&args.session_id, args.turn, provider)?;
 
         Ok(ToolCallResult {
             content: vec![ContentItem::text(format!(
-                "# Workflow Step Result\n\n**Status:** {}\n**Duration:** {}ms\n\n## Output\n\n{}",
-                status, result.duration_ms, result.output
+                "Forked session `{}` at turn {} as `{}` ({}), continuing with {}. Pass its ID \
+                 as `session_id` to agent_prompt to continue the branch.",
+                args.session_id,
+                args.turn,
+                branch.id,
+                branch.uri(),
+                branch.provider
             ))],
             is_error: false,
         })
     }
 }
 
-/// Tool for getting orchestrator status.
-pub struct StatusTool;
+/// Tool for listing the branches of a session.
+pub struct SessionBranchesTool;
+
+#[derive(Debug, Deserialize)]
+struct SessionBranchesArgs {
+    session_id: String,
+}
 
 #[async_trait::async_trait]
-impl Tool for StatusTool {
+impl Tool for SessionBranchesTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
-            name: "agent_status".into(),
-            description: "Get the status of the agent orchestrator.".into(),
+            name: "agent_session_branches".into(),
+            description: "List a session's original and every branch forked from it.".into(),
             input_schema: json!({
                 "type": "object",
-                "properties": {},
-                "required": []
+                "properties": {
+                    "session_id": {
+                        "type": "string",
+                        "description": "The original session or any of its branches"
+                    }
+                },
+                "required": ["session_id"]
             }),
         }
     }
 
     async fn execute(
         &self,
-        _arguments: serde_json::Value,
+        arguments: serde_json::Value,
         context: &ToolContext,
     ) -> Result<ToolCallResult> {
-        let status = context.orchestrator.status().await;
-
-        let providers_text = status
-            .available_providers
-            .iter()
-            .map(|p| format!("- ✅ {}", p))
-            .collect::<Vec<_>>()
-            .join("\n");
+        let args: SessionBranchesArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
 
-        let stats_text = status
-            .provider_stats
+        let tree = context.orchestrator.sessions().branches(&args.session_id)?;
+        let lines: Vec<String> = tree
             .iter()
-            .map(|(p, s)| {
+            .map(|(depth, session)| {
                 format!(
-                    "- **{}**: {} total, {} success, {} failed",
-                    p, s.total_requests, s.successful_requests, s.failed_requests
+                    "{}- `{}` {}: {}",
+                    "  ".repeat(*depth),
+                    session.id,
+                    session.provider,
+                    session.summary()
                 )
             })
-            .collect::<Vec<_>>()
-            .join("\n");
+            .collect();
 
         Ok(ToolCallResult {
             content: vec![ContentItem::text(format!(
-                "# Agent Orchestrator Status\n\n## Available Providers\n\n{}\n\n## Active Workflows\n\n{}\n\n## Provider Statistics\n\n{}",
-                providers_text,
-                status.active_workflows,
-                if stats_text.is_empty() { "No requests yet".into() } else { stats_text }
+                "# Session Branches\n\n{}",
+                lines.join("\n")
             ))],
             is_error: false,
         })
     }
 }
 
-/// Tool for listing available providers.
-pub struct ListProvidersTool;
+/// Tool for comparing two sessions, such as a branch and its original.
+pub struct SessionDiffTool;
+
+#[derive(Debug, Deserialize)]
+struct SessionDiffArgs {
+    session_id: String,
+    other_session_id: Option<String>,
+}
 
 #[async_trait::async_trait]
-impl Tool for ListProvidersTool {
+impl Tool for SessionDiffTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
-            name: "agent_list_providers".into(),
-            description: "List all available AI providers and their capabilities.".into(),
+            name: "agent_session_diff".into(),
+            description: "Compare two sessions: the turns they share and a diff of the transcripts after them.".into(),
             input_schema: json!({
                 "type": "object",
-                "properties": {},
-                "required": []
+                "properties": {
+                    "session_id": {
+                        "type": "string",
+                        "description": "Session to compare"
+                    },
+                    "other_session_id": {
+                        "type": "string",
+                        "description": "Optional: session to compare with (defaults to the session the first was forked from)"
+                    }
+                },
+                "required": ["session_id"]
             }),
         }
     }
 
     async fn execute(
         &self,
-        _arguments: serde_json::Value,
-        _context: &ToolContext,
+        arguments: serde_json::Value,
+        context: &ToolContext,
     ) -> Result<ToolCallResult> {
-        let providers = vec![
-            ("claude", "Claude (Anthropic)", "200k context, artifacts, code execution"),
-            ("grok", "Grok (X/xAI)", "Real-time info, X integration"),
-            ("gemini", "Gemini (Google)", "2M context, Google integration"),
-            ("chatgpt", "ChatGPT (OpenAI)", "GPT-4o, vision, web search, code"),
-            ("perplexity", "Perplexity AI", "Search-focused, sources cited"),
-            ("notebooklm", "NotebookLM (Google)", "500k context, research assistant"),
-        ];
+        let args: SessionDiffArgs =
+            serde_json::from_value(arguments).map_err(|e| Error::InvalidParams(e.to_string()))?;
+        let sessions = context.orchestrator.sessions();
+        let get = |id: &str| {
+            sessions
+                .get(id)
+                .ok_or_else(|| Error::InvalidParams(format!("no session with ID {}", id)))
+        };
 
-        let text = providers
-            .iter()
-            .map(|(id, name, caps)| format!("## {} (`{}`)\n\n{}\n", name, id, caps))
-            .collect::<Vec<_>>()
-            .join("\n");
+        let session = get(&args.session_id)?;
+        let other = match (&args.other_session_id, &session.forked_from) {
+            (Some(id), _) => get(id)?,
+            (None, Some(fork)) => get(&fork.session_id)?,
+            (None, None) => {
+                return Err(Error::InvalidParams(format!(
+                    "session {} is not a branch; name the session to compare it with",
+                    session.id
+                )))
+            }
+        };
 
         Ok(ToolCallResult {
-            content: vec![ContentItem::text(format!(
-                "# Available AI Providers\n\n{}",
-                text
-            ))],
+            content: vec![ContentItem::text(other.diff(&session))],
             is_error: false,
         })
     }
@@ -590,6 +4187,129 @@ impl Tool for ListProvidersTool {
 // Helper Functions
 // =============================================================================
 
+/// Directory code is extracted from: the working directory the server was
+/// started in, or a subdirectory of it.
+fn workspace_root(path: Option<&str>) -> Result<PathBuf> {
+    match path {
+        Some(path) => workspace_path(path),
+        None => Ok(std::env::current_dir()?),
+    }
+}
+
+/// Run a code extraction off the async runtime; it reads the whole tree.
+async fn extract_code(extractor: CodeExtractor, symbol: String) -> Result<Extraction> {
+    tokio::task::spawn_blocking(move || extractor.extract(&symbol))
+        .await
+        .map_err(|e| Error::Internal(format!("code extraction failed: {}", e)))?
+}
+
+/// Join names as prose: "A", "A and B", or "A, B, and C".
+fn join_names(names: impl IntoIterator<Item = String>) -> String {
+    let mut names: Vec<String> = names.into_iter().collect();
+    match names.len() {
+        0 | 1 => names.pop().unwrap_or_default(),
+        2 => names.join(" and "),
+        _ => {
+            let last = names.pop().unwrap_or_default();
+            format!("{}, and {}", names.join(", "), last)
+        }
+    }
+}
+
+/// Describe a saved prompt's variables, if it has any.
+fn variables_note(variables: &[String]) -> String {
+    if variables.is_empty() {
+        String::new()
+    } else {
+        format!(" (variables: {})", variables.join(", "))
+    }
+}
+
+/// Serde default for `min_success`: at least one provider must respond.
+fn default_min_success() -> usize {
+    1
+}
+
+/// Serde default for flags that are on unless disabled.
+fn default_true() -> bool {
+    true
+}
+
+/// Render a notice about sanitized content, if any was removed.
+fn sanitization_notice(response: &PromptResponse) -> String {
+    response
+        .metadata
+        .get(SANITIZATION_SUMMARY_KEY)
+        .map(|summary| format!("\n\n> ⚠️ Response sanitized: {}", summary))
+        .unwrap_or_default()
+}
+
+/// Render a consolidated bibliography of the cited sources, if any.
+fn sources_section(sources: &[Source]) -> String {
+    if sources.is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{}", bibliography(sources))
+    }
+}
+
+/// Render the points of disagreement among consensus responses, if any.
+fn disagreement_section(disagreements: &Disagreements) -> String {
+    if disagreements.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from("\n\n## Points of Disagreement");
+    if !disagreements.contradictions.is_empty() {
+        section.push_str("\n\n### Contradictions\n");
+        for c in &disagreements.contradictions {
+            section.push_str(&format!(
+                "\n- **{}**: {}\n  **{}**: {}",
+                c.first.provider, c.first.text, c.second.provider, c.second.text
+            ));
+        }
+    }
+    if !disagreements.partial_claims.is_empty() {
+        section.push_str("\n\n### Claims Not Shared by All Providers\n");
+        for claim in &disagreements.partial_claims {
+            section.push_str(&format!(
+                "\n- {} (held by {}; not by {})",
+                claim.text,
+                claim.held_by.join(", "),
+                claim.missing_from.join(", ")
+            ));
+        }
+    }
+    section
+}
+
+/// Render round-trip divergences from a translation check.
+fn divergence_section(divergences: &Disagreements) -> String {
+    let mut section = String::new();
+    if !divergences.contradictions.is_empty() {
+        section.push_str("\n\n### Changed Meaning\n");
+        for c in &divergences.contradictions {
+            section.push_str(&format!(
+                "\n- Source: {}\n  Back-translation: {}",
+                c.first.text, c.second.text
+            ));
+        }
+    }
+    let (lost, added): (Vec<_>, Vec<_>) = divergences
+        .partial_claims
+        .iter()
+        .partition(|claim| claim.held_by.iter().any(|h| h == SOURCE_LABEL));
+    for (title, claims) in [("Lost in Translation", lost), ("Added in Translation", added)] {
+        if !claims.is_empty() {
+            section.push_str(&format!("\n\n### {}\n", title));
+            for claim in claims {
+                section.push_str(&format!("\n- {}", claim.text));
+            }
+        }
+    }
+    section
+}
+
 /// Parse provider string to Provider enum.
 fn parse_provider(s: &str) -> Result<Provider> {
     match s.to_lowercase().as_str() {
@@ -602,3 +4322,244 @@ fn parse_provider(s: &str) -> Result<Provider> {
         _ => Err(Error::InvalidParams(format!("unknown provider: {}", s))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SlowTool;
+
+    #[async_trait::async_trait]
+    impl Tool for SlowTool {
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "slow".into(),
+                description: "Never finishes in time.".into(),
+                input_schema: json!({ "type": "object" }),
+            }
+        }
+
+        async fn execute(
+            &self,
+            _arguments: serde_json::Value,
+            _context: &ToolContext,
+        ) -> Result<ToolCallResult> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Err(Error::Internal("unreachable".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_enforces_tool_timeout() {
+        let mut registry = ToolRegistry::new(AgentOrchestrator::new());
+        registry.register(Arc::new(SlowTool));
+        registry.set_timeouts(ToolTimeouts::default().with_tool("slow", 0));
+
+        match registry.execute("slow", json!({})).await {
+            Err(Error::ToolTimeout { tool, .. }) => assert_eq!(tool, "slow"),
+            other => panic!("expected tool timeout, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(
+            registry.tools.read().timeouts.for_tool("agent_status"),
+            Duration::from_secs(30)
+        );
+
+        // A file overriding one tool keeps the built-in limits for the rest
+        let timeouts: ToolTimeouts =
+            serde_json::from_value(json!({ "per_tool": { "agent_consensus": 1200 } })).unwrap();
+        assert_eq!(
+            timeouts.for_tool("agent_consensus"),
+            Duration::from_secs(1200)
+        );
+        assert_eq!(timeouts.for_tool("agent_status"), Duration::from_secs(30));
+        assert_eq!(timeouts.for_tool("agent_prompt"), Duration::from_secs(600));
+        for tool in ["agent_workflow_run", "agent_auto", "agent_research"] {
+            assert_eq!(timeouts.for_tool(tool), Duration::from_secs(1200));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_config_tool_persists_preferences() {
+        use crate::orchestrator::OrchestratorConfig;
+        use crate::storage::{MemoryStorage, Storage};
+
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let orchestrator = || {
+            AgentOrchestrator::with_config(OrchestratorConfig {
+                storage: Some(storage.clone()),
+                ..Default::default()
+            })
+        };
+        let registry = ToolRegistry::new(orchestrator());
+        registry
+            .execute(
+                "agent_config",
+                json!({
+                    "disable": ["grok"],
+                    "priorities": { "gemini": 120 },
+                    "settings": { "claude": { "model": "opus" } }
+                }),
+            )
+            .await
+            .unwrap();
+
+        let restarted = orchestrator().provider_preferences().await;
+        assert!(restarted.is_disabled(Provider::Grok));
+        assert_eq!(restarted.priority(Provider::Gemini), 120);
+        assert_eq!(
+            restarted.setting(Provider::Claude),
+            Some(&json!({ "model": "opus" }))
+        );
+
+        let tenant = ToolRegistry::new(orchestrator().for_tenant("ci"));
+        let denied = tenant
+            .execute("agent_config", json!({ "enable": ["grok"] }))
+            .await;
+        assert!(matches!(denied, Err(Error::PermissionDenied(_))));
+        assert!(registry
+            .execute("agent_config", json!({ "disable": ["nobody"] }))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_workflow_review_resolves_paused_step() {
+        use crate::workflow::{Workflow, WorkflowStep, REVIEW_KEY};
+
+        let orchestrator = AgentOrchestrator::new();
+        let registry = ToolRegistry::new(orchestrator.clone());
+        let start = || async {
+            let mut workflow = Workflow::new("release");
+            workflow.add_step(WorkflowStep::review("sign-off", "Ship it?"));
+            let id = orchestrator.start_workflow(workflow).await.unwrap();
+            assert!(orchestrator.execute_workflow_step(&id).await.is_err());
+            id
+        };
+
+        let approved = start().await;
+        registry
+            .execute(
+                "agent_workflow_review",
+                json!({
+                    "workflow_id": approved,
+                    "decision": "approve",
+                    "reviewer": "sam",
+                    "comments": "looks good"
+                }),
+            )
+            .await
+            .unwrap();
+        let workflow = orchestrator.get_workflow(&approved).await.unwrap();
+        assert_eq!(workflow.state, WorkflowState::Completed);
+        let result = workflow.steps[0].result.clone().unwrap();
+        assert!(result.output.ends_with("Comments: looks good"));
+        assert_eq!(result.metadata[REVIEW_KEY]["reviewer"], "sam");
+
+        let rejected = start().await;
+        registry
+            .execute(
+                "agent_workflow_review",
+                json!({ "workflow_id": rejected, "decision": "reject", "comments": "too risky" }),
+            )
+            .await
+            .unwrap();
+        let workflow = orchestrator.get_workflow(&rejected).await.unwrap();
+        assert!(matches!(workflow.state, WorkflowState::Failed(_)));
+        let result = workflow.steps[0].result.clone().unwrap();
+        assert_eq!(result.output, "Rejected: too risky");
+        assert_eq!(result.metadata[REVIEW_KEY]["approved"], false);
+
+        // Only paused steps can be reviewed
+        assert!(registry
+            .execute(
+                "agent_workflow_review",
+                json!({ "workflow_id": approved, "decision": "approve" }),
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_workflow_list_and_status() {
+        use crate::workflow::{Workflow, WorkflowStep};
+
+        let orchestrator = AgentOrchestrator::new();
+        let registry = ToolRegistry::new(orchestrator.clone());
+        let text = |result: ToolCallResult| match &result.content[0] {
+            ContentItem::Text { text } => text.clone(),
+            other => panic!("expected text, got {:?}", other),
+        };
+
+        let mut pending = Workflow::new("draft");
+        pending.add_step(WorkflowStep::prompt("outline", "Outline it"));
+        let pending = orchestrator.start_workflow(pending).await.unwrap();
+        let mut rejected = Workflow::new("release");
+        rejected.add_step(WorkflowStep::review("sign-off", "Ship it?"));
+        let rejected = orchestrator.start_workflow(rejected).await.unwrap();
+        assert!(orchestrator.execute_workflow_step(&rejected).await.is_err());
+        orchestrator
+            .review_step(&rejected, StepReview::new(false, None, Some("too risky".into())))
+            .await
+            .unwrap();
+
+        let list = text(registry.execute("agent_workflow_list", json!({})).await.unwrap());
+        let lines: Vec<&str> = list.lines().filter(|l| l.starts_with("- ")).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(&rejected) && lines[0].contains("**failed**"));
+        assert!(lines[1].contains(&pending) && lines[1].contains("0/1 steps done"));
+        let filtered = text(
+            registry
+                .execute("agent_workflow_list", json!({ "state": "pending" }))
+                .await
+                .unwrap(),
+        );
+        assert!(filtered.contains(&pending) && !filtered.contains(&rejected));
+
+        let status = text(
+            registry
+                .execute("agent_workflow_status", json!({ "workflow_id": rejected }))
+                .await
+                .unwrap(),
+        );
+        assert!(status.contains("## 1. sign-off"));
+        assert!(status.contains("**Review:** Rejected: too risky"));
+        assert!(status.contains("### Metadata"));
+        assert!(registry
+            .execute("agent_workflow_status", json!({ "workflow_id": "missing" }))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tool_step_calls_registered_tool() {
+        use crate::orchestrator::TOOL_KEY;
+        use crate::workflow::{Workflow, WorkflowStep};
+
+        let orchestrator = AgentOrchestrator::new();
+        let _registry = ToolRegistry::new(orchestrator.clone());
+        let mut draft = Workflow::new("draft");
+        draft.add_step(WorkflowStep::prompt("outline", "Outline it"));
+        let draft = orchestrator.start_workflow(draft).await.unwrap();
+
+        // The tool reads the workflows while the step runs
+        let mut report = Workflow::new("report");
+        report.set_context("target", json!(draft));
+        report.add_step(WorkflowStep::tool(
+            "inspect",
+            "agent_workflow_status",
+            json!({ "workflow_id": "{{target}}" }),
+        ));
+        let report = orchestrator.start_workflow(report).await.unwrap();
+        let result = orchestrator.execute_workflow_step(&report).await.unwrap();
+        assert!(result.output.contains("## 1. outline"));
+        assert_eq!(result.metadata[TOOL_KEY], json!("agent_workflow_status"));
+        let workflow = orchestrator.get_workflow(&report).await.unwrap();
+        assert_eq!(workflow.state, WorkflowState::Completed);
+
+        for tool in ["agent_unknown", "agent_workflow_step", "agent_workflow_run"] {
+            let mut workflow = Workflow::new("bad");
+            workflow.add_step(WorkflowStep::tool("call", tool, json!({})));
+            assert!(orchestrator.start_workflow(workflow).await.is_err(), "{}", tool);
+        }
+    }
+}
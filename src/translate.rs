@@ -0,0 +1,114 @@
+//! Translation with back-translation verification.
+//!
+//! One provider translates the text; a different provider translates the
+//! result back into the source language without seeing the original. The
+//! back-translation is compared with the source, and meaning lost, added, or
+//! inverted on the round trip is flagged.
+
+use embeddenator_webpuppet::Provider;
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::{find_disagreements, similarity, Disagreements};
+
+/// Label for the source text in divergence reports.
+pub const SOURCE_LABEL: &str = "source";
+/// Label for the back-translation in divergence reports.
+pub const BACK_TRANSLATION_LABEL: &str = "back-translation";
+
+/// Word overlap between source and back-translation below which the
+/// translation is flagged even without specific divergences.
+const FIDELITY_THRESHOLD: f64 = 0.4;
+
+/// Source language and providers for a translation.
+#[derive(Debug, Clone, Default)]
+pub struct TranslationOptions {
+    /// Language of the source text (defaults to English).
+    pub source_language: Option<String>,
+    /// Provider that translates (defaults to the best available).
+    pub translator: Option<Provider>,
+    /// Provider that back-translates (defaults to the best other provider).
+    pub verifier: Option<Provider>,
+}
+
+impl TranslationOptions {
+    /// Source language, defaulting to English.
+    pub fn source_language(&self) -> &str {
+        self.source_language.as_deref().unwrap_or("English")
+    }
+}
+
+/// A verified translation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationReport {
+    /// The translated text.
+    pub translation: String,
+    /// The translation rendered back into the source language.
+    pub back_translation: String,
+    /// Language translated from.
+    pub source_language: String,
+    /// Language translated into.
+    pub target_language: String,
+    /// Provider that translated.
+    pub translator: String,
+    /// Provider that back-translated.
+    pub verifier: String,
+    /// Word overlap between the source and the back-translation (0.0-1.0).
+    pub fidelity: f64,
+    /// Sentences lost, added, or contradicted on the round trip.
+    pub divergences: Disagreements,
+}
+
+impl TranslationReport {
+    /// Check if the translation needs a human look.
+    pub fn is_flagged(&self) -> bool {
+        self.fidelity < FIDELITY_THRESHOLD || !self.divergences.is_empty()
+    }
+}
+
+/// Build the prompt asking for a translation.
+pub fn translation_prompt(text: &str, source_language: &str, target_language: &str) -> String {
+    format!(
+        "Translate the following text from {} into {}. Preserve meaning, numbers, \
+         and formatting. Respond with only the translation.\n\n{}",
+        source_language, target_language, text
+    )
+}
+
+/// Build the prompt asking for a back-translation.
+pub fn back_translation_prompt(translation: &str, source_language: &str) -> String {
+    format!(
+        "Translate the following text into {} as literally as possible. Respond \
+         with only the translation.\n\n{}",
+        source_language, translation
+    )
+}
+
+/// Compare a source text with its back-translation.
+pub fn compare(source: &str, back_translation: &str) -> (f64, Disagreements) {
+    let divergences = find_disagreements(&[
+        (SOURCE_LABEL, source),
+        (BACK_TRANSLATION_LABEL, back_translation),
+    ]);
+    (similarity(source, back_translation), divergences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_flags_round_trip_changes() {
+        let source = "The meeting starts at 9 tomorrow. Bring the signed contract.";
+
+        let (fidelity, divergences) = compare(source, source);
+        assert_eq!(fidelity, 1.0);
+        assert!(divergences.is_empty());
+
+        let (_, divergences) = compare(
+            source,
+            "The meeting starts at 10 tomorrow. Bring the signed contract.",
+        );
+        assert_eq!(divergences.contradictions.len(), 1);
+        assert_eq!(divergences.contradictions[0].first.provider, SOURCE_LABEL);
+    }
+}
@@ -0,0 +1,274 @@
+//! Callbacks delivering background job results over HTTP.
+//!
+//! A caller that cannot hold an MCP connection open (a CI pipeline, say)
+//! queues a background run with a callback URL; when the job finishes, its
+//! final result is POSTed there as JSON. If a webhook secret is configured,
+//! each delivery carries an HMAC-SHA256 signature of the body so receivers
+//! can check it came from this server.
+//!
+//! Callbacks are off until the operator lists the hosts they may reach, so a
+//! caller cannot make the server POST results to internal or metadata
+//! addresses. Redirects are not followed, and results of runs classified
+//! above the policy's limit (Public by default) are not delivered.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::audit::sign;
+use crate::error::{Error, Result};
+use crate::jobs::{Job, JobKind, JobState};
+use crate::security::DataClassification;
+
+/// Environment variable holding the webhook signing secret.
+pub const SECRET_ENV_VAR: &str = "AGENT_MCP_WEBHOOK_SECRET";
+/// Header carrying the hex HMAC-SHA256 signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-Agent-Signature";
+/// Delivery attempts before giving up.
+const MAX_DELIVERY_ATTEMPTS: u32 = 3;
+/// Deadline for a single delivery attempt.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body POSTed to a callback URL when a job finishes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// ID of the finished job.
+    pub job_id: String,
+    /// Work the job performed.
+    pub kind: JobKind,
+    /// Final state: completed or failed.
+    pub state: JobState,
+    /// Result of the job (for auto runs, the full report).
+    pub result: Option<serde_json::Value>,
+    /// Error from the last attempt, if the job failed.
+    pub error: Option<String>,
+    /// Attempts the job took.
+    pub attempts: u32,
+    /// When the job finished.
+    pub finished_at: DateTime<Utc>,
+}
+
+impl WebhookPayload {
+    /// Payload for a finished job.
+    pub fn new(job: &Job, result: Option<serde_json::Value>) -> Self {
+        Self {
+            job_id: job.id.clone(),
+            kind: job.kind.clone(),
+            state: job.state,
+            result,
+            error: job.last_error.clone(),
+            attempts: job.attempts,
+            finished_at: job.updated_at,
+        }
+    }
+}
+
+/// Where callbacks may be delivered, and which runs may use them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookPolicy {
+    /// Hosts callback URLs may point at; callbacks are disabled when empty.
+    pub allowed_hosts: Vec<String>,
+    /// Highest data classification of a run whose result may be delivered.
+    pub max_classification: DataClassification,
+}
+
+impl WebhookPolicy {
+    /// Check that a run of `classification` may deliver its result to `url`.
+    pub fn check(&self, url: &str, classification: DataClassification) -> Result<()> {
+        let parsed = validate_url(url)?;
+        if self.allowed_hosts.is_empty() {
+            return Err(Error::PermissionDenied(
+                "webhook callbacks are disabled; the operator must allow callback hosts".into(),
+            ));
+        }
+        let host = parsed.host_str().unwrap_or_default();
+        if !self
+            .allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        {
+            return Err(Error::PermissionDenied(format!(
+                "callback host {} is not allowed",
+                host
+            )));
+        }
+        if classification > self.max_classification {
+            return Err(Error::PermissionDenied(format!(
+                "results of {} runs may not be sent to callbacks",
+                classification
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Check that a callback URL is an absolute http(s) URL.
+pub fn validate_url(url: &str) -> Result<reqwest::Url> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| Error::InvalidParams(format!("invalid callback URL {}: {}", url, e)))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed),
+        scheme => Err(Error::InvalidParams(format!(
+            "callback URL must use http or https, not {}",
+            scheme
+        ))),
+    }
+}
+
+/// Sends job results to callback URLs.
+#[derive(Clone, Default)]
+pub struct WebhookSender {
+    client: reqwest::Client,
+    secret: Option<String>,
+    policy: WebhookPolicy,
+}
+
+impl WebhookSender {
+    /// Create a sender delivering under `policy`, signing deliveries with
+    /// `secret` if given.
+    pub fn new(secret: Option<String>, policy: WebhookPolicy) -> Self {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            secret,
+            policy,
+        }
+    }
+
+    /// Get the callback policy.
+    pub fn policy(&self) -> &WebhookPolicy {
+        &self.policy
+    }
+
+    /// Signature of a body, if a secret is configured.
+    pub fn signature(&self, body: &str) -> Option<String> {
+        self.secret
+            .as_deref()
+            .map(|secret| format!("sha256={}", sign(secret.as_bytes(), body)))
+    }
+
+    /// POST the result of a run of `classification` to `url`, retrying with
+    /// backoff on failure.
+    pub async fn deliver(
+        &self,
+        url: &str,
+        classification: DataClassification,
+        payload: &WebhookPayload,
+    ) -> Result<()> {
+        self.policy.check(url, classification)?;
+        let body = serde_json::to_string(payload)?;
+        let mut last_error = String::new();
+
+        for attempt in 1..=MAX_DELIVERY_ATTEMPTS {
+            let mut request = self
+                .client
+                .post(url)
+                .timeout(DELIVERY_TIMEOUT)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            if let Some(signature) = self.signature(&body) {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    info!("Delivered job {} result to {}", payload.job_id, url);
+                    return Ok(());
+                }
+                Ok(response) => last_error = format!("HTTP {}", response.status()),
+                Err(e) => last_error = e.to_string(),
+            }
+            warn!(
+                "Webhook delivery of job {} failed (attempt {}/{}): {}",
+                payload.job_id, attempt, MAX_DELIVERY_ATTEMPTS, last_error
+            );
+            if attempt < MAX_DELIVERY_ATTEMPTS {
+                tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            }
+        }
+
+        Err(Error::Internal(format!(
+            "webhook delivery to {} failed: {}",
+            url, last_error
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_delivers_signed_payload() {
+        assert!(validate_url("ftp://ci.example/hook").is_err());
+        assert!(validate_url("https://ci.example/hook").is_ok());
+
+        // Only allowed hosts receive callbacks, and only for public runs
+        let policy = WebhookPolicy {
+            allowed_hosts: vec!["127.0.0.1".into()],
+            ..Default::default()
+        };
+        let public = DataClassification::Public;
+        assert!(WebhookPolicy::default()
+            .check("https://ci.example/hook", public)
+            .is_err());
+        assert!(policy
+            .check("http://169.254.169.254/latest", public)
+            .is_err());
+        assert!(policy
+            .check("http://127.0.0.1/hook", DataClassification::Confidential)
+            .is_err());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read until the whole JSON body has arrived.
+            while !request.ends_with(b"}") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let payload = WebhookPayload {
+            job_id: "job-1".into(),
+            kind: JobKind::AutoRun {
+                workflow_id: "wf-1".into(),
+                max_cost_usd: None,
+                synthesizer: None,
+                classification: None,
+                visible: false,
+                tenant: None,
+            },
+            state: JobState::Completed,
+            result: Some(serde_json::json!({"synthesis": "Done."})),
+            error: None,
+            attempts: 1,
+            finished_at: Utc::now(),
+        };
+        let sender = WebhookSender::new(Some("secret".into()), policy);
+        sender.deliver(&url, public, &payload).await.unwrap();
+
+        let request = server.await.unwrap();
+        let body = request.split("\r\n\r\n").nth(1).unwrap();
+        let signature = sender.signature(body).unwrap();
+        assert!(request
+            .to_lowercase()
+            .contains(&format!("x-agent-signature: {}", signature)));
+        assert!(body.contains("\"synthesis\":\"Done.\""));
+    }
+}
@@ -1,12 +1,21 @@
 //! Workflow management for multi-step agent tasks.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::confidence::ConfidenceCheck;
+use crate::consensus::ConsensusStrategy;
 use crate::error::{Error, Result};
+use crate::library::PromptRef;
+use crate::map::MapItem;
+use crate::postprocess::PostProcessor;
+use crate::records::RecordField;
+use crate::retry::RetryPolicy;
+use crate::security::DataClassification;
+use crate::tenant::default_tenant;
 
 /// A workflow represents a multi-step agent task.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +38,9 @@ pub struct Workflow {
     pub updated_at: DateTime<Utc>,
     /// Workflow metadata.
     pub metadata: HashMap<String, String>,
+    /// Tenant that started the workflow.
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
 }
 
 impl Workflow {
@@ -45,6 +57,7 @@ impl Workflow {
             created_at: now,
             updated_at: now,
             metadata: HashMap::new(),
+            tenant: default_tenant(),
         }
     }
 
@@ -78,6 +91,171 @@ impl Workflow {
         Ok(())
     }
 
+    /// Continue at the later step `index`, skipping the steps in between.
+    pub fn jump_to(&mut self, index: usize) -> Result<()> {
+        if index <= self.current_step || index >= self.steps.len() {
+            return Err(Error::InvalidState(format!("cannot jump to step {}", index)));
+        }
+        for step in &mut self.steps[self.current_step + 1..index] {
+            step.state = StepState::Skipped;
+        }
+        self.current_step = index;
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+
+    /// Index of the step after `from` a jump to `target`, found by ID or
+    /// name, lands on.
+    pub fn jump_target(&self, from: usize, target: &str) -> Result<usize> {
+        self.steps
+            .iter()
+            .enumerate()
+            .skip(from + 1)
+            .find(|(_, s)| s.id == target || s.name == target)
+            .map(|(i, _)| i)
+            .ok_or_else(|| Error::Workflow(format!("no later step '{}' to jump to", target)))
+    }
+
+    /// Check that every conditional step jumps to a later step.
+    pub fn validate_jumps(&self) -> Result<()> {
+        for (i, step) in self.steps.iter().enumerate() {
+            if let StepConfig::Conditional {
+                then_step,
+                else_step,
+                ..
+            } = &step.config
+            {
+                for target in std::iter::once(then_step).chain(else_step) {
+                    self.jump_target(i, target).map_err(|_| {
+                        Error::Workflow(format!(
+                            "step '{}' jumps to '{}', which is not a later step",
+                            step.name, target
+                        ))
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether steps run once their dependencies complete rather than in
+    /// order: any step lists `depends_on`.
+    pub fn is_graph(&self) -> bool {
+        self.steps.iter().any(|s| !s.depends_on.is_empty())
+    }
+
+    /// Indexes of the steps the step at `index` depends on.
+    fn dependencies(&self, index: usize) -> Vec<usize> {
+        let earlier = &self.steps[..index];
+        self.steps[index]
+            .depends_on
+            .iter()
+            .filter_map(|d| earlier.iter().position(|s| &s.id == d || &s.name == d))
+            .collect()
+    }
+
+    /// Check that every step depends only on earlier steps, so dependencies
+    /// cannot form a cycle, and that a workflow with dependencies has no
+    /// conditional steps to jump in it.
+    pub fn validate_dependencies(&self) -> Result<()> {
+        for (i, step) in self.steps.iter().enumerate() {
+            let earlier = &self.steps[..i];
+            if let Some(missing) = step
+                .depends_on
+                .iter()
+                .find(|d| !earlier.iter().any(|s| &s.id == *d || &s.name == *d))
+            {
+                return Err(Error::Workflow(format!(
+                    "step '{}' depends on '{}', which is not an earlier step",
+                    step.name, missing
+                )));
+            }
+        }
+        if self.is_graph() {
+            if let Some(step) = self.steps.iter().find(|s| s.step_type == StepType::Conditional) {
+                return Err(Error::Workflow(format!(
+                    "conditional step '{}' cannot jump in a workflow with step dependencies",
+                    step.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes of the steps of a graph workflow ready to run: pending, with
+    /// every step they depend on completed.
+    pub fn ready_steps(&self) -> Vec<usize> {
+        (0..self.steps.len())
+            .filter(|&i| {
+                self.steps[i].state == StepState::Pending
+                    && self
+                        .dependencies(i)
+                        .iter()
+                        .all(|&d| self.steps[d].state == StepState::Completed)
+            })
+            .collect()
+    }
+
+    /// Indexes of the steps no other step depends on.
+    pub fn terminal_steps(&self) -> Vec<usize> {
+        let depended: HashSet<usize> = (0..self.steps.len())
+            .flat_map(|i| self.dependencies(i))
+            .collect();
+        (0..self.steps.len())
+            .filter(|i| !depended.contains(i))
+            .collect()
+    }
+
+    /// Point a graph workflow's current step at the step awaiting review,
+    /// else the first step ready to run, else the first unfinished step,
+    /// and complete the workflow once every terminal step has.
+    pub fn settle(&mut self) {
+        let waiting = self
+            .steps
+            .iter()
+            .position(|s| s.state == StepState::WaitingForHuman);
+        self.current_step = waiting
+            .or_else(|| self.ready_steps().first().copied())
+            .or_else(|| {
+                self.steps
+                    .iter()
+                    .position(|s| s.state != StepState::Completed)
+            })
+            .unwrap_or(self.steps.len());
+        let done = self
+            .terminal_steps()
+            .iter()
+            .all(|&i| self.steps[i].state == StepState::Completed);
+        if done && !self.is_complete() {
+            self.current_step = self.steps.len();
+            self.state = WorkflowState::Completed;
+        }
+        self.updated_at = Utc::now();
+    }
+
+    /// Steps the current step may read: every earlier step, or in a graph
+    /// workflow, the steps it depends on, directly or through others. Every
+    /// step once the workflow has no current step.
+    pub fn earlier_steps(&self) -> Vec<&WorkflowStep> {
+        let end = self.current_step.min(self.steps.len());
+        if !self.is_graph() || end == self.steps.len() {
+            return self.steps[..end].iter().collect();
+        }
+        let mut ancestors = vec![false; end];
+        let mut pending = self.dependencies(end);
+        while let Some(i) = pending.pop() {
+            if !ancestors[i] {
+                ancestors[i] = true;
+                pending.extend(self.dependencies(i));
+            }
+        }
+        self.steps[..end]
+            .iter()
+            .zip(ancestors)
+            .filter_map(|(step, ancestor)| ancestor.then_some(step))
+            .collect()
+    }
+
     /// Set workflow to failed state.
     pub fn fail(&mut self, reason: impl Into<String>) {
         self.state = WorkflowState::Failed(reason.into());
@@ -89,6 +267,14 @@ impl Workflow {
         matches!(self.state, WorkflowState::Completed | WorkflowState::Failed(_))
     }
 
+    /// Number of steps that completed.
+    pub fn completed_steps(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|s| s.state == StepState::Completed)
+            .count()
+    }
+
     /// Set context value.
     pub fn set_context(&mut self, key: impl Into<String>, value: serde_json::Value) {
         self.context.insert(key.into(), value);
@@ -99,6 +285,39 @@ impl Workflow {
     pub fn get_context(&self, key: &str) -> Option<&serde_json::Value> {
         self.context.get(key)
     }
+
+    /// Output of an earlier step, found by ID or name, or of the last
+    /// completed step if `step` is `None`.
+    pub fn step_output(&self, step: Option<&str>) -> Option<&str> {
+        let earlier = self.earlier_steps();
+        let mut done = earlier.into_iter().rev().filter_map(|s| Some((s, s.result.as_ref()?)));
+        let (_, result) = match step {
+            Some(id) => done.find(|(s, _)| s.id == id || s.name == id)?,
+            None => done.next()?,
+        };
+        Some(&result.output)
+    }
+
+    /// Earlier steps found by ID or name, in the order given, or every
+    /// earlier step except reviews and conditionals if `ids` is empty.
+    pub fn source_steps(&self, ids: &[String]) -> Result<Vec<&WorkflowStep>> {
+        let earlier = self.earlier_steps();
+        if ids.is_empty() {
+            return Ok(earlier
+                .into_iter()
+                .filter(|s| !matches!(s.step_type, StepType::HumanReview | StepType::Conditional))
+                .collect());
+        }
+        ids.iter()
+            .map(|id| {
+                earlier
+                    .iter()
+                    .find(|s| &s.id == id || &s.name == id)
+                    .copied()
+                    .ok_or_else(|| Error::Workflow(format!("no earlier step '{}'", id)))
+            })
+            .collect()
+    }
 }
 
 /// State of a workflow.
@@ -122,6 +341,19 @@ pub enum WorkflowState {
     Failed(String),
 }
 
+impl WorkflowState {
+    /// Name of the state, without any failure reason.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Completed => "completed",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
 /// A single step in a workflow.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkflowStep {
@@ -137,6 +369,35 @@ pub struct WorkflowStep {
     pub config: StepConfig,
     /// Result of the step (if completed).
     pub result: Option<StepResult>,
+    /// Data classification of the step's prompt.
+    #[serde(default)]
+    pub classification: Option<DataClassification>,
+    /// Whether a human approved the step to run.
+    #[serde(default)]
+    pub approved: bool,
+    /// The reviewer's decision on the step, once it was reviewed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<StepReview>,
+    /// Post-processors applied to the step's output before it is recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_processors: Vec<PostProcessor>,
+    /// Saved prompt version the step's message was rendered from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_prompt: Option<PromptRef>,
+    /// File, relative to the workspace root, the step's output is written
+    /// to; tools then show a summary in place of the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_to: Option<String>,
+    /// Verification pass rating the confidence of the step's answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<ConfidenceCheck>,
+    /// How the step is retried after failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Earlier steps, by ID or name, that must complete before this one
+    /// runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
 }
 
 impl WorkflowStep {
@@ -153,6 +414,15 @@ impl WorkflowStep {
                 context: None,
             },
             result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
         }
     }
 
@@ -168,6 +438,15 @@ impl WorkflowStep {
                 providers,
             },
             result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
         }
     }
 
@@ -181,8 +460,311 @@ impl WorkflowStep {
             config: StepConfig::Consensus {
                 message: message.into(),
                 min_providers: 2,
+                strategy: ConsensusStrategy::default(),
+                judge_provider: None,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a research sprint step.
+    pub fn research(name: impl Into<String>, question: impl Into<String>, providers: Vec<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::Research,
+            state: StepState::Pending,
+            config: StepConfig::Research {
+                question: question.into(),
+                providers,
+                max_rounds: None,
+                time_budget_secs: None,
+                max_cost_usd: None,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a fact-check step checking the output of `source_step` (or,
+    /// if `None`, the last completed step).
+    pub fn fact_check(name: impl Into<String>, source_step: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::FactCheck,
+            state: StepState::Pending,
+            config: StepConfig::FactCheck {
+                source_step,
+                focus: None,
+                provider: None,
+                max_claims: None,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a step validating the diff in the output of `source_step` (or,
+    /// if `None`, the last completed step).
+    pub fn validate_patch(name: impl Into<String>, source_step: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::ValidatePatch,
+            state: StepState::Pending,
+            config: StepConfig::ValidatePatch {
+                source_step,
+                check: true,
+                provider: None,
+                max_refinements: None,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a step sending `message` once per item, with `{{name}}` and
+    /// `{{item}}` filled in from the item.
+    pub fn map(name: impl Into<String>, message: impl Into<String>, items: Vec<MapItem>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::Map,
+            state: StepState::Pending,
+            config: StepConfig::Map {
+                message: message.into(),
+                items,
+                provider: None,
+                json: false,
+                checker: None,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a step joining the outputs of `source_steps` (or, if empty, of
+    /// every earlier step) into one document.
+    pub fn assemble(
+        name: impl Into<String>,
+        title: impl Into<String>,
+        source_steps: Vec<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::Assemble,
+            state: StepState::Pending,
+            config: StepConfig::Assemble {
+                title: title.into(),
+                source_steps,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a step consolidating the findings of `source_steps` (or, if
+    /// empty, of every earlier step) into one answer.
+    pub fn aggregate(
+        name: impl Into<String>,
+        message: impl Into<String>,
+        source_steps: Vec<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::Aggregate,
+            state: StepState::Pending,
+            config: StepConfig::Aggregate {
+                message: message.into(),
+                source_steps,
+                provider: None,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a step extracting records with `fields` from the outputs of
+    /// `source_steps` (or, if empty, of every earlier step), keeping those
+    /// a majority of `providers` agree on.
+    pub fn extract(
+        name: impl Into<String>,
+        message: impl Into<String>,
+        fields: Vec<RecordField>,
+        source_steps: Vec<String>,
+        providers: Vec<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::Extract,
+            state: StepState::Pending,
+            config: StepConfig::Extract {
+                message: message.into(),
+                fields,
+                source_steps,
+                providers,
+                min_agreement: None,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a step posting the output of `source_step` (or, if `None`, the
+    /// last completed step) as a comment on a GitHub pull request or issue.
+    pub fn github_comment(
+        name: impl Into<String>,
+        pull_request: impl Into<String>,
+        source_step: Option<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::GitHubComment,
+            state: StepState::Pending,
+            config: StepConfig::GitHubComment {
+                pull_request: pull_request.into(),
+                source_step,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a conditional step, continuing at `then_step` if `condition`
+    /// holds and otherwise at `else_step`, or the next step if there is none.
+    pub fn conditional(
+        name: impl Into<String>,
+        condition: impl Into<String>,
+        then_step: impl Into<String>,
+        else_step: Option<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::Conditional,
+            state: StepState::Pending,
+            config: StepConfig::Conditional {
+                condition: condition.into(),
+                then_step: then_step.into(),
+                else_step,
+            },
+            result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Create a step calling a registered tool with `arguments`, whose
+    /// `{{key}}` placeholders are filled from the workflow context.
+    pub fn tool(
+        name: impl Into<String>,
+        tool_name: impl Into<String>,
+        arguments: serde_json::Value,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.into(),
+            step_type: StepType::Tool,
+            state: StepState::Pending,
+            config: StepConfig::Tool {
+                tool_name: tool_name.into(),
+                arguments,
             },
             result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
         }
     }
 
@@ -197,7 +779,66 @@ impl WorkflowStep {
                 prompt: prompt.into(),
             },
             result: None,
+            classification: None,
+            approved: false,
+            review: None,
+            post_processors: Vec::new(),
+            saved_prompt: None,
+            write_to: None,
+            confidence: None,
+            retry: None,
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Set the provider for a prompt, fact-check, patch validation, map, or
+    /// aggregate step.
+    pub fn with_provider(mut self, name: impl Into<String>) -> Self {
+        if let StepConfig::Prompt { provider, .. }
+        | StepConfig::FactCheck { provider, .. }
+        | StepConfig::ValidatePatch { provider, .. }
+        | StepConfig::Map { provider, .. }
+        | StepConfig::Aggregate { provider, .. } = &mut self.config
+        {
+            *provider = Some(name.into());
         }
+        self
+    }
+
+    /// Set how a consensus step chooses its answer.
+    pub fn with_consensus_strategy(
+        mut self,
+        strategy: ConsensusStrategy,
+        judge_provider: Option<String>,
+    ) -> Self {
+        if let StepConfig::Consensus {
+            strategy: s,
+            judge_provider: j,
+            ..
+        } = &mut self.config
+        {
+            *s = strategy;
+            *j = judge_provider;
+        }
+        self
+    }
+
+    /// Post-process the step's output with these processors, in order.
+    pub fn with_post_processors(mut self, processors: Vec<PostProcessor>) -> Self {
+        self.post_processors = processors;
+        self
+    }
+
+    /// Run the step once these earlier steps, by ID or name, complete.
+    pub fn with_dependencies(mut self, steps: Vec<String>) -> Self {
+        self.depends_on = steps;
+        self
+    }
+
+    /// Tag the step with a data classification.
+    pub fn with_classification(mut self, classification: DataClassification) -> Self {
+        self.classification = Some(classification);
+        self
     }
 
     /// Mark step as running.
@@ -228,6 +869,22 @@ pub enum StepType {
     Consensus,
     /// Require human review/approval.
     HumanReview,
+    /// Time-boxed research sprint with cited synthesis.
+    Research,
+    /// Verify an earlier step's claims against web sources.
+    FactCheck,
+    /// Check that an earlier step's diff applies and passes a check command.
+    ValidatePatch,
+    /// Send the same prompt once per item.
+    Map,
+    /// Consolidate the deduplicated findings of earlier steps.
+    Aggregate,
+    /// Join the outputs of earlier steps into one document.
+    Assemble,
+    /// Extract records from earlier steps, validated by several providers.
+    Extract,
+    /// Post an earlier step's output as a GitHub comment.
+    GitHubComment,
     /// Conditional branching.
     Conditional,
     /// Custom tool invocation.
@@ -247,6 +904,26 @@ pub enum StepState {
     Completed,
     /// Step failed.
     Failed(String),
+    /// Step was jumped over by a conditional step.
+    Skipped,
+}
+
+impl StepState {
+    /// Name of the state, without any failure reason.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::WaitingForHuman => "waiting_for_human",
+            Self::Completed => "completed",
+            Self::Failed(_) => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
 }
 
 /// Configuration for a workflow step.
@@ -271,6 +948,124 @@ pub enum StepConfig {
     Consensus {
         message: String,
         min_providers: usize,
+        #[serde(default)]
+        strategy: ConsensusStrategy,
+        #[serde(default)]
+        judge_provider: Option<String>,
+    },
+    /// Research sprint configuration.
+    #[serde(rename = "research")]
+    Research {
+        question: String,
+        #[serde(default)]
+        providers: Vec<String>,
+        #[serde(default)]
+        max_rounds: Option<usize>,
+        #[serde(default)]
+        time_budget_secs: Option<u64>,
+        #[serde(default)]
+        max_cost_usd: Option<f64>,
+    },
+    /// Fact-check configuration.
+    #[serde(rename = "fact_check")]
+    FactCheck {
+        /// Step (ID or name) whose output is checked; defaults to the last
+        /// completed step.
+        #[serde(default)]
+        source_step: Option<String>,
+        #[serde(default)]
+        focus: Option<String>,
+        #[serde(default)]
+        provider: Option<String>,
+        #[serde(default)]
+        max_claims: Option<usize>,
+    },
+    /// Patch validation configuration.
+    #[serde(rename = "validate_patch")]
+    ValidatePatch {
+        /// Step (ID or name) whose output holds the diff; defaults to the
+        /// last completed step.
+        #[serde(default)]
+        source_step: Option<String>,
+        /// Run the configured check command after applying the diff.
+        #[serde(default = "default_true")]
+        check: bool,
+        /// Provider asked to correct a failing diff.
+        #[serde(default)]
+        provider: Option<String>,
+        /// Corrections requested before the step fails.
+        #[serde(default)]
+        max_refinements: Option<u32>,
+    },
+    /// Map configuration.
+    #[serde(rename = "map")]
+    Map {
+        /// Prompt with `{{name}}` and `{{item}}` placeholders.
+        message: String,
+        /// Items the prompt is sent for, in order.
+        items: Vec<MapItem>,
+        /// Provider answering every item.
+        #[serde(default)]
+        provider: Option<String>,
+        /// Read each answer as a JSON object.
+        #[serde(default)]
+        json: bool,
+        /// Provider that cross-checks and corrects each answer.
+        #[serde(default)]
+        checker: Option<String>,
+    },
+    /// Assembly configuration.
+    #[serde(rename = "assemble")]
+    Assemble {
+        /// Title of the document.
+        title: String,
+        /// Steps (IDs or names) whose outputs are joined; empty means every
+        /// earlier step except reviews and conditionals.
+        #[serde(default)]
+        source_steps: Vec<String>,
+    },
+    /// Aggregation configuration.
+    #[serde(rename = "aggregate")]
+    Aggregate {
+        /// Instructions for the consolidated answer.
+        message: String,
+        /// Steps (IDs or names) whose findings are aggregated; empty means
+        /// every earlier step except reviews and conditionals.
+        #[serde(default)]
+        source_steps: Vec<String>,
+        /// Provider consolidating the findings.
+        #[serde(default)]
+        provider: Option<String>,
+    },
+    /// Extraction configuration.
+    #[serde(rename = "extract")]
+    Extract {
+        /// What to extract.
+        message: String,
+        /// Fields of each record.
+        fields: Vec<RecordField>,
+        /// Steps (IDs or names) records are extracted from; empty means
+        /// every earlier step except reviews and conditionals.
+        #[serde(default)]
+        source_steps: Vec<String>,
+        /// Providers extracting the records; empty means a single provider
+        /// chosen by the router.
+        #[serde(default)]
+        providers: Vec<String>,
+        /// Providers that must extract a record for it to be kept; defaults
+        /// to a majority of those that answered.
+        #[serde(default)]
+        min_agreement: Option<usize>,
+    },
+    /// GitHub comment configuration.
+    #[serde(rename = "github_comment")]
+    GitHubComment {
+        /// Pull request or issue, as `owner/repo#number` or a URL.
+        pull_request: String,
+        /// Step (ID or name) whose output is posted; defaults to the last
+        /// completed step.
+        #[serde(default)]
+        source_step: Option<String>,
     },
     /// Human review configuration.
     #[serde(rename = "human_review")]
@@ -292,6 +1087,43 @@ pub enum StepConfig {
     },
 }
 
+impl StepConfig {
+    /// The question the step answers, for steps whose answer can be rated.
+    pub fn question(&self) -> Option<&str> {
+        match self {
+            Self::Prompt { message, .. }
+            | Self::ParallelPrompt { message, .. }
+            | Self::Consensus { message, .. }
+            | Self::Aggregate { message, .. } => Some(message),
+            Self::Research { question, .. } => Some(question),
+            _ => None,
+        }
+    }
+
+    /// The texts the step sends or shows, which may hold placeholders for
+    /// earlier outputs and the context.
+    pub fn texts_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Self::Prompt {
+                message, context, ..
+            } => std::iter::once(message).chain(context).collect(),
+            Self::ParallelPrompt { message, .. }
+            | Self::Consensus { message, .. }
+            | Self::Map { message, .. }
+            | Self::Aggregate { message, .. }
+            | Self::Extract { message, .. } => vec![message],
+            Self::Research { question, .. } => vec![question],
+            Self::FactCheck { focus, .. } => focus.iter_mut().collect(),
+            Self::Assemble { title, .. } => vec![title],
+            Self::GitHubComment { pull_request, .. } => vec![pull_request],
+            Self::HumanReview { prompt } => vec![prompt],
+            Self::ValidatePatch { .. } | Self::Conditional { .. } | Self::Tool { .. } => {
+                Vec::new()
+            }
+        }
+    }
+}
+
 /// Result of a workflow step.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepResult {
@@ -307,6 +1139,48 @@ pub struct StepResult {
     pub metadata: HashMap<String, serde_json::Value>,
 }
 
+/// Step result metadata key holding the reviewer's decision on the step.
+pub const REVIEW_KEY: &str = "review";
+
+/// A reviewer's decision on a step paused for human review or approval.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepReview {
+    /// Whether the reviewer approved the step.
+    pub approved: bool,
+    /// Who reviewed the step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewer: Option<String>,
+    /// The reviewer's comments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<String>,
+    /// When the step was reviewed.
+    pub at: DateTime<Utc>,
+}
+
+impl StepReview {
+    /// A decision made now.
+    pub fn new(approved: bool, reviewer: Option<String>, comments: Option<String>) -> Self {
+        Self {
+            approved,
+            reviewer,
+            comments,
+            at: Utc::now(),
+        }
+    }
+
+    /// The decision, who made it, and the comments, in one line.
+    pub fn describe(&self) -> String {
+        let mut text = if self.approved { "Approved" } else { "Rejected" }.to_string();
+        if let Some(reviewer) = &self.reviewer {
+            text.push_str(&format!(" by {}", reviewer));
+        }
+        if let Some(comments) = &self.comments {
+            text.push_str(&format!(": {}", comments));
+        }
+        text
+    }
+}
+
 /// Response from a single provider.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderResponse {
@@ -318,6 +1192,9 @@ pub struct ProviderResponse {
     pub selected: bool,
     /// Confidence/agreement score (0.0-1.0).
     pub confidence: Option<f64>,
+    /// Model, token estimates, timings, and cache status.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 #[cfg(test)]
@@ -346,4 +1223,71 @@ mod tests {
         workflow.advance().unwrap();
         assert!(workflow.is_complete());
     }
+
+    #[tokio::test]
+    async fn test_independent_branches_run_at_once() {
+        use crate::orchestrator::{AgentOrchestrator, OrchestratorConfig};
+        use crate::run::{RunOutcome, DEFAULT_MAX_RUN_STEPS};
+        use crate::testkit::MockProviders;
+        use embeddenator_webpuppet::Provider;
+        use std::time::{Duration, Instant};
+
+        let mocks = MockProviders::new()
+            .with_reply(Provider::Claude, "Claude notes")
+            .with_reply(Provider::Gemini, "Gemini notes")
+            .with_latency(Duration::from_millis(200));
+        let orchestrator = AgentOrchestrator::with_config(OrchestratorConfig {
+            mock_providers: Some(mocks.clone()),
+            ..Default::default()
+        });
+        let review = || {
+            let mut workflow = Workflow::new("review");
+            workflow.add_step(WorkflowStep::prompt("security", "Security").with_provider("claude"));
+            workflow.add_step(WorkflowStep::prompt("speed", "Speed").with_provider("gemini"));
+            workflow.add_step(
+                WorkflowStep::prompt("summary", "Sum up {{steps.speed.output}}")
+                    .with_provider("claude")
+                    .with_dependencies(vec!["security".into(), "speed".into()]),
+            );
+            workflow
+        };
+
+        let id = orchestrator.start_workflow(review()).await.unwrap();
+        let started = Instant::now();
+        let report = orchestrator
+            .run_workflow(&id, false, DEFAULT_MAX_RUN_STEPS)
+            .await
+            .unwrap();
+        assert_eq!(report.outcome, RunOutcome::Completed);
+        assert_eq!(report.steps.len(), 3);
+        assert_eq!(report.steps[2].name, "summary");
+        // Both reviews ran at once; one after the other takes 600ms
+        assert!(started.elapsed() < Duration::from_millis(550));
+        assert!(mocks
+            .calls()
+            .iter()
+            .any(|c| c.message == "Sum up Gemini notes"));
+
+        // Stepping runs one ready step at a time, and the workflow completes
+        // with its terminal step
+        let id = orchestrator.start_workflow(review()).await.unwrap();
+        orchestrator.execute_workflow_step(&id).await.unwrap();
+        let workflow = orchestrator.get_workflow(&id).await.unwrap();
+        assert_eq!(workflow.current().unwrap().name, "speed");
+        assert_eq!(workflow.ready_steps(), vec![1]);
+        assert_eq!(workflow.terminal_steps(), vec![2]);
+        orchestrator.execute_workflow_step(&id).await.unwrap();
+        orchestrator.execute_workflow_step(&id).await.unwrap();
+        let workflow = orchestrator.get_workflow(&id).await.unwrap();
+        assert_eq!(workflow.state, WorkflowState::Completed);
+
+        // Steps depend only on earlier steps, and graphs have no jumps
+        let mut cyclic = Workflow::new("cyclic");
+        cyclic.add_step(WorkflowStep::prompt("a", "A").with_dependencies(vec!["b".into()]));
+        cyclic.add_step(WorkflowStep::prompt("b", "B").with_dependencies(vec!["a".into()]));
+        assert!(orchestrator.start_workflow(cyclic).await.is_err());
+        let mut jumping = review();
+        jumping.add_step(WorkflowStep::conditional("check", "context.ok", "security", None));
+        assert!(jumping.validate_dependencies().is_err());
+    }
 }
@@ -0,0 +1,159 @@
+//! Workspace-scoped state directories.
+//!
+//! Two projects that launch the same server binary must not see each other's
+//! workflows and histories, so persisted state lives under a directory keyed
+//! by workspace: an explicit `--workspace-id`, or else the working directory
+//! the editor started the server in.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::storage::FileStorage;
+#[cfg(feature = "sqlite")]
+use crate::storage::{SqliteStorage, WORKFLOWS};
+
+/// Environment variable overriding the base state directory.
+pub const STATE_DIR_ENV_VAR: &str = "AGENT_MCP_STATE_DIR";
+
+/// A workspace and the directory holding its state.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// Workspace ID, safe to use as a directory name.
+    pub id: String,
+    /// Directory holding this workspace's state.
+    pub state_dir: PathBuf,
+}
+
+impl Workspace {
+    /// Resolve the workspace from an explicit ID, or else the current
+    /// directory, under `state_dir`, or else the default base state
+    /// directory.
+    pub fn resolve(workspace_id: Option<&str>, state_dir: Option<&Path>) -> Result<Self> {
+        let id = match workspace_id {
+            Some(id) => sanitize_id(id),
+            None => id_for_path(&std::env::current_dir().map_err(Error::Io)?),
+        };
+        let base = state_dir.map_or_else(default_base_dir, Path::to_path_buf);
+        Ok(Self::under(&base, id))
+    }
+
+    /// A workspace with the given ID under `base`.
+    pub fn under(base: &Path, id: impl Into<String>) -> Self {
+        let id = id.into();
+        Self {
+            state_dir: base.join("workspaces").join(&id),
+            id,
+        }
+    }
+
+    /// File storage rooted at this workspace's state directory.
+    pub fn storage(&self) -> FileStorage {
+        FileStorage::new(&self.state_dir)
+    }
+
+    /// Whether file storage has written state here and SQLite storage has
+    /// not, as in state directories from before SQLite became the default.
+    #[cfg(feature = "sqlite")]
+    pub fn has_file_state(&self) -> bool {
+        !self.state_dir.join(SqliteStorage::FILE_NAME).exists()
+            && self.state_dir.join(WORKFLOWS).is_dir()
+    }
+
+    /// SQLite storage in this workspace's state directory.
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_storage(&self) -> Result<SqliteStorage> {
+        SqliteStorage::open(&self.state_dir.join(SqliteStorage::FILE_NAME))
+    }
+}
+
+/// Base state directory: `AGENT_MCP_STATE_DIR`, else the XDG state directory.
+pub fn default_base_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(STATE_DIR_ENV_VAR) {
+        return PathBuf::from(dir);
+    }
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir);
+    state_home.join("agent-mcp")
+}
+
+/// Workspace ID for a directory: its name plus a short hash of the full path,
+/// so same-named projects in different places stay apart.
+pub fn id_for_path(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "root".into());
+    let hash = format!("{:x}", Sha256::digest(path.to_string_lossy().as_bytes()));
+    format!("{}-{}", sanitize_id(&name), &hash[..12])
+}
+
+/// Replace characters that are unsafe in a directory name.
+fn sanitize_id(id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match id.trim_matches('.') {
+        "" => "_".into(),
+        trimmed => trimmed.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::storage::{Records, WORKFLOWS};
+    use crate::workflow::Workflow;
+
+    #[test]
+    fn test_workspaces_are_isolated() {
+        let base = std::env::temp_dir().join(format!("agent-mcp-ws-{}", uuid::Uuid::new_v4()));
+        let first = Workspace::under(&base, id_for_path(Path::new("/src/app")));
+        let second = Workspace::under(&base, id_for_path(Path::new("/other/app")));
+        assert_ne!(first.state_dir, second.state_dir);
+        assert!(first.id.starts_with("app-"));
+        assert_eq!(sanitize_id("../team x"), "_team_x");
+
+        let records = |ws: &Workspace| Records::new(Arc::new(ws.storage()), WORKFLOWS, None);
+        let workflow = Workflow::new("Research");
+        records(&first).save(&workflow.id, &workflow).unwrap();
+
+        let loaded: Vec<Workflow> = records(&first).load_all().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id, workflow.id);
+        assert!(records(&second).load_all::<Workflow>().unwrap().is_empty());
+
+        // Workflows survive a restart pointed at the same state directory
+        let restarted = Workspace::resolve(Some(&first.id), Some(&base)).unwrap();
+        assert_eq!(restarted.state_dir, first.state_dir);
+        assert_eq!(records(&restarted).load_all::<Workflow>().unwrap().len(), 1);
+
+        // Likewise with SQLite storage
+        #[cfg(feature = "sqlite")]
+        {
+            assert!(first.has_file_state());
+            let sqlite = |ws: &Workspace| {
+                Records::new(Arc::new(ws.sqlite_storage().unwrap()), WORKFLOWS, None)
+            };
+            sqlite(&second).save(&workflow.id, &workflow).unwrap();
+            let restarted = Workspace::resolve(Some(&second.id), Some(&base)).unwrap();
+            let loaded: Vec<Workflow> = sqlite(&restarted).load_all().unwrap();
+            assert_eq!(loaded[0].id, workflow.id);
+            assert!(sqlite(&first).load_all::<Workflow>().unwrap().is_empty());
+        }
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+}